@@ -1,6 +1,8 @@
 use crate::error::{ParseError, ParseErrorKind::*};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
 use std::iter;
+use std::ops::Range;
 use std::str::{self, FromStr};
 
 /// A cfg expression.
@@ -48,10 +50,11 @@ impl FromStr for Cfg {
         let mut p = Parser::new(s);
         let e = p.cfg()?;
         if let Some(rest) = p.rest() {
-            return Err(ParseError::new(
-                p.t.orig,
-                UnterminatedExpression(rest.to_string()),
-            ));
+            let start = p.t.orig.len() - rest.len();
+            return Err(
+                ParseError::new(p.t.orig, UnterminatedExpression(rest.to_string()))
+                    .with_span(start..p.t.orig.len()),
+            );
         }
         Ok(e)
     }
@@ -89,8 +92,108 @@ impl CfgExpr {
             CfgExpr::Value(ref e) => cfg.contains(e),
         }
     }
+
+    /// Returns whether this expression is true for some target, by trying
+    /// all the values of the cfg options it uses.
+    ///
+    /// Options like `target_os` have exactly one value on any target, and
+    /// `unix` and `windows` are never both set. All other options are treated
+    /// as independent of each other. If there are too many combinations to
+    /// try, the expression is assumed to be satisfiable.
+    pub fn can_match(&self) -> bool {
+        const MAX_COMBINATIONS: usize = 4096;
+
+        let mut atoms = BTreeSet::new();
+        self.collect_cfgs(&mut atoms);
+        // The values of each single valued key, where `None` stands for any
+        // other value.
+        let mut single: BTreeMap<&str, Vec<Option<&str>>> = BTreeMap::new();
+        let mut free = Vec::new();
+        for &atom in &atoms {
+            match atom {
+                Cfg::KeyPair(key, value) if SINGLE_VALUED_KEYS.contains(&key.as_str()) => {
+                    single
+                        .entry(key.as_str())
+                        .or_insert_with(|| vec![None])
+                        .push(Some(value.as_str()));
+                }
+                _ => free.push(atom),
+            }
+        }
+
+        let mut combinations = 1usize;
+        for values in single.values() {
+            combinations = combinations.saturating_mul(values.len());
+        }
+        if free.len() >= 12 || combinations.saturating_mul(1 << free.len()) > MAX_COMBINATIONS {
+            return true;
+        }
+
+        let unix = Cfg::Name("unix".to_string());
+        let windows = Cfg::Name("windows".to_string());
+        let keys = single.keys().copied().collect::<Vec<_>>();
+        for i in 0..combinations {
+            let mut cfgs = Vec::new();
+            let mut rest = i;
+            for key in &keys {
+                let values = &single[key];
+                if let Some(value) = values[rest % values.len()] {
+                    cfgs.push(Cfg::KeyPair(key.to_string(), value.to_string()));
+                }
+                rest /= values.len();
+            }
+            for set in 0..(1usize << free.len()) {
+                let mut cfgs = cfgs.clone();
+                cfgs.extend(
+                    free.iter()
+                        .enumerate()
+                        .filter(|(bit, _)| set & (1 << bit) != 0)
+                        .map(|(_, cfg)| (*cfg).clone()),
+                );
+                // `unix` and `windows` are also the target families.
+                for (name, family) in &[(&unix, "unix"), (&windows, "windows")] {
+                    let family = Cfg::KeyPair("target_family".to_string(), family.to_string());
+                    if cfgs.contains(name) != cfgs.contains(&family) {
+                        if cfgs.contains(name) {
+                            cfgs.push(family);
+                        } else {
+                            cfgs.push((*name).clone());
+                        }
+                    }
+                }
+                if cfgs.contains(&unix) && cfgs.contains(&windows) {
+                    continue;
+                }
+                if self.matches(&cfgs) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn collect_cfgs<'a>(&'a self, cfgs: &mut BTreeSet<&'a Cfg>) {
+        match self {
+            CfgExpr::Not(e) => e.collect_cfgs(cfgs),
+            CfgExpr::All(e) | CfgExpr::Any(e) => e.iter().for_each(|e| e.collect_cfgs(cfgs)),
+            CfgExpr::Value(cfg) => {
+                cfgs.insert(cfg);
+            }
+        }
+    }
 }
 
+/// The cfg keys which have exactly one value on any target.
+const SINGLE_VALUED_KEYS: &[&str] = &[
+    "target_abi",
+    "target_arch",
+    "target_endian",
+    "target_env",
+    "target_os",
+    "target_pointer_width",
+    "target_vendor",
+];
+
 impl FromStr for CfgExpr {
     type Err = ParseError;
 
@@ -98,10 +201,11 @@ impl FromStr for CfgExpr {
         let mut p = Parser::new(s);
         let e = p.expr()?;
         if let Some(rest) = p.rest() {
-            return Err(ParseError::new(
-                p.t.orig,
-                UnterminatedExpression(rest.to_string()),
-            ));
+            let start = p.t.orig.len() - rest.len();
+            return Err(
+                ParseError::new(p.t.orig, UnterminatedExpression(rest.to_string()))
+                    .with_span(start..p.t.orig.len()),
+            );
         }
         Ok(e)
     }
@@ -146,15 +250,7 @@ impl<'a> Parser<'a> {
         match self.peek() {
             Some(Ok(Token::Ident(op @ "all"))) | Some(Ok(Token::Ident(op @ "any"))) => {
                 self.t.next();
-                let mut e = Vec::new();
-                self.eat(&Token::LeftParen)?;
-                while !self.r#try(&Token::RightParen) {
-                    e.push(self.expr()?);
-                    if !self.r#try(&Token::Comma) {
-                        self.eat(&Token::RightParen)?;
-                        break;
-                    }
-                }
+                let e = self.args()?;
                 if op == "all" {
                     Ok(CfgExpr::All(e))
                 } else {
@@ -162,25 +258,64 @@ impl<'a> Parser<'a> {
                 }
             }
             Some(Ok(Token::Ident("not"))) => {
+                let start = self.next_span().start;
                 self.t.next();
-                self.eat(&Token::LeftParen)?;
-                let e = self.expr()?;
-                self.eat(&Token::RightParen)?;
-                Ok(CfgExpr::Not(Box::new(e)))
+                let mut e = self.args()?;
+                if e.len() != 1 {
+                    let end = self
+                        .t
+                        .s
+                        .clone()
+                        .peek()
+                        .map_or(self.t.orig.len(), |&(i, _)| i);
+                    return Err(
+                        ParseError::new(self.t.orig, NotArgCount(e.len())).with_span(start..end)
+                    );
+                }
+                Ok(CfgExpr::Not(Box::new(e.remove(0))))
             }
+            Some(Ok(Token::Ident(op))) if self.is_call() => Err(ParseError::new(
+                self.t.orig,
+                UnknownOperator(op.to_string()),
+            )
+            .with_span(self.next_span())),
             Some(Ok(..)) => self.cfg().map(CfgExpr::Value),
             Some(Err(..)) => Err(self.t.next().unwrap().err().unwrap()),
-            None => Err(ParseError::new(
-                self.t.orig,
-                IncompleteExpr("start of a cfg expression"),
-            )),
+            None => Err(
+                ParseError::new(self.t.orig, IncompleteExpr("start of a cfg expression"))
+                    .with_span(self.pos()..self.pos()),
+            ),
+        }
+    }
+
+    /// Parses the parenthesized, comma separated arguments of `all`, `any`
+    /// or `not`.
+    fn args(&mut self) -> Result<Vec<CfgExpr>, ParseError> {
+        let mut e = Vec::new();
+        self.eat(&Token::LeftParen)?;
+        while !self.r#try(&Token::RightParen) {
+            e.push(self.expr()?);
+            if !self.r#try(&Token::Comma) {
+                self.eat(&Token::RightParen)?;
+                break;
+            }
         }
+        Ok(e)
+    }
+
+    /// Returns whether the next token is an identifier followed by `(`.
+    fn is_call(&self) -> bool {
+        let mut t = self.t.clone();
+        t.next();
+        matches!(t.next(), Some(Ok(Token::LeftParen)))
     }
 
     fn cfg(&mut self) -> Result<Cfg, ParseError> {
+        let span = self.next_span();
         match self.t.next() {
             Some(Ok(Token::Ident(name))) => {
                 let e = if self.r#try(&Token::Equals) {
+                    let span = self.next_span();
                     let val = match self.t.next() {
                         Some(Ok(Token::String(s))) => s,
                         Some(Ok(t)) => {
@@ -190,11 +325,13 @@ impl<'a> Parser<'a> {
                                     expected: "a string",
                                     found: t.classify(),
                                 },
-                            ))
+                            )
+                            .with_span(span))
                         }
                         Some(Err(e)) => return Err(e),
                         None => {
-                            return Err(ParseError::new(self.t.orig, IncompleteExpr("a string")))
+                            return Err(ParseError::new(self.t.orig, IncompleteExpr("a string"))
+                                .with_span(span))
                         }
                     };
                     Cfg::KeyPair(name.to_string(), val.to_string())
@@ -209,9 +346,10 @@ impl<'a> Parser<'a> {
                     expected: "identifier",
                     found: t.classify(),
                 },
-            )),
+            )
+            .with_span(span)),
             Some(Err(e)) => Err(e),
-            None => Err(ParseError::new(self.t.orig, IncompleteExpr("identifier"))),
+            None => Err(ParseError::new(self.t.orig, IncompleteExpr("identifier")).with_span(span)),
         }
     }
 
@@ -229,6 +367,7 @@ impl<'a> Parser<'a> {
     }
 
     fn eat(&mut self, token: &Token<'a>) -> Result<(), ParseError> {
+        let span = self.next_span();
         match self.t.next() {
             Some(Ok(ref t)) if token == t => Ok(()),
             Some(Ok(t)) => Err(ParseError::new(
@@ -237,15 +376,33 @@ impl<'a> Parser<'a> {
                     expected: token.classify(),
                     found: t.classify(),
                 },
-            )),
+            )
+            .with_span(span)),
             Some(Err(e)) => Err(e),
-            None => Err(ParseError::new(
-                self.t.orig,
-                IncompleteExpr(token.classify()),
-            )),
+            None => {
+                Err(ParseError::new(self.t.orig, IncompleteExpr(token.classify())).with_span(span))
+            }
         }
     }
 
+    /// Returns the byte offset of the next token, or the end of the input.
+    fn pos(&self) -> usize {
+        self.t
+            .s
+            .clone()
+            .find(|&(_, ch)| ch != ' ')
+            .map_or(self.t.orig.len(), |(i, _)| i)
+    }
+
+    /// Returns the byte range of the next token.
+    fn next_span(&self) -> Range<usize> {
+        let mut t = self.t.clone();
+        let start = self.pos();
+        t.next();
+        let end = t.s.peek().map_or(self.t.orig.len(), |&(i, _)| i);
+        start..end.max(start)
+    }
+
     /// Returns the rest of the input from the current location.
     fn rest(&self) -> Option<&str> {
         let mut s = self.t.s.clone();
@@ -276,7 +433,8 @@ impl<'a> Iterator for Tokenizer<'a> {
                             return Some(Ok(Token::String(&self.orig[start + 1..end])));
                         }
                     }
-                    return Some(Err(ParseError::new(self.orig, UnterminatedString)));
+                    return Some(Err(ParseError::new(self.orig, UnterminatedString)
+                        .with_span(start..self.orig.len())));
                 }
                 Some((start, ch)) if is_ident_start(ch) => {
                     while let Some(&(end, ch)) = self.s.peek() {
@@ -288,8 +446,9 @@ impl<'a> Iterator for Tokenizer<'a> {
                     }
                     return Some(Ok(Token::Ident(&self.orig[start..])));
                 }
-                Some((_, ch)) => {
-                    return Some(Err(ParseError::new(self.orig, UnexpectedChar(ch))));
+                Some((start, ch)) => {
+                    return Some(Err(ParseError::new(self.orig, UnexpectedChar(ch))
+                        .with_span(start..start + ch.len_utf8())));
                 }
                 None => return None,
             }
@@ -1,9 +1,11 @@
 use std::fmt;
+use std::ops::Range;
 
 #[derive(Debug)]
 pub struct ParseError {
     kind: ParseErrorKind,
     orig: String,
+    span: Option<Range<usize>>,
 }
 
 #[non_exhaustive]
@@ -18,6 +20,8 @@ pub enum ParseErrorKind {
     IncompleteExpr(&'static str),
     UnterminatedExpression(String),
     InvalidTarget(String),
+    UnknownOperator(String),
+    NotArgCount(usize),
 }
 
 impl fmt::Display for ParseError {
@@ -26,7 +30,20 @@ impl fmt::Display for ParseError {
             f,
             "failed to parse `{}` as a cfg expression: {}",
             self.orig, self.kind
-        )
+        )?;
+        if let Some(span) = &self.span {
+            // Point at the offending part of the expression.
+            let before = self.orig[..span.start].chars().count();
+            let width = self.orig[span.clone()].chars().count().max(1);
+            write!(
+                f,
+                "\n  {}\n  {}{}",
+                self.orig,
+                " ".repeat(before),
+                "^".repeat(width)
+            )?;
+        }
+        Ok(())
     }
 }
 
@@ -51,6 +68,16 @@ impl fmt::Display for ParseErrorKind {
                 write!(f, "unexpected content `{}` found after cfg expression", s)
             }
             InvalidTarget(s) => write!(f, "invalid target specifier: {}", s),
+            UnknownOperator(s) => write!(
+                f,
+                "unknown operator `{}`, expected `all`, `any` or `not`",
+                s
+            ),
+            NotArgCount(n) => write!(
+                f,
+                "`not` takes exactly one cfg expression, but {} were given",
+                n
+            ),
         }
     }
 }
@@ -62,6 +89,19 @@ impl ParseError {
         ParseError {
             kind,
             orig: orig.to_string(),
+            span: None,
         }
     }
+
+    /// Sets the byte range of `orig` which caused the error.
+    pub(crate) fn with_span(mut self, span: Range<usize>) -> ParseError {
+        self.span = Some(span);
+        self
+    }
+
+    /// Returns the byte range of the cfg expression which caused the error,
+    /// if known.
+    pub fn span(&self) -> Option<Range<usize>> {
+        self.span.clone()
+    }
 }
@@ -84,14 +84,26 @@ impl Platform {
                             )),
                         _ => (),
                     },
-                    Cfg::KeyPair(name, _) => if name.as_str() == "feature" {
-                        warnings.push(String::from(
+                    Cfg::KeyPair(name, value) => match name.as_str() {
+                        "feature" => warnings.push(String::from(
                             "Found `feature = ...` in `target.'cfg(...)'.dependencies`. \
                              This key is not supported for selecting dependencies \
                              and will not work as expected. \
                              Use the [features] section instead: \
                              https://doc.rust-lang.org/cargo/reference/features.html"
-                        ))
+                        )),
+                        "target_feature" => {
+                            if let Some(name) = value.strip_prefix(&['+', '-'][..]) {
+                                warnings.push(format!(
+                                    "Found `{}` in `target.'cfg(...)'.dependencies`. \
+                                     Target features are matched by name only, \
+                                     so this will never match. \
+                                     Use `target_feature = \"{}\"` instead.",
+                                    e, name
+                                ))
+                            }
+                        }
+                        _ => (),
                     },
                 }
             }
@@ -99,6 +111,14 @@ impl Platform {
 
         if let Platform::Cfg(cfg) = self {
             check_cfg_expr(cfg, warnings);
+            if !cfg.can_match() {
+                warnings.push(format!(
+                    "Found `{}` in `target.'cfg(...)'.dependencies`. \
+                     This expression is false for every target, \
+                     so these dependencies will never be used.",
+                    self
+                ));
+            }
         }
     }
 }
@@ -107,13 +107,48 @@ fn cfg_expr_bad() {
     bad::<CfgExpr>("not", "expected `(`");
     bad::<CfgExpr>("not(a", "expected `)`");
     bad::<CfgExpr>("a = ", "expected a string");
-    bad::<CfgExpr>("all(not())", "expected identifier");
+    bad::<CfgExpr>(
+        "all(not())",
+        "`not` takes exactly one cfg expression, but 0 were given",
+    );
+    bad::<CfgExpr>(
+        "not(a, b)",
+        "`not` takes exactly one cfg expression, but 2 were given",
+    );
     bad::<CfgExpr>(
         "foo(a)",
-        "unexpected content `(a)` found after cfg expression",
+        "unknown operator `foo`, expected `all`, `any` or `not`",
+    );
+    bad::<CfgExpr>(
+        "any(a, and(b, c))",
+        "unknown operator `and`, expected `all`, `any` or `not`",
     );
 }
 
+#[test]
+fn cfg_expr_bad_span() {
+    fn span(s: &str, expected: &str) {
+        let e = CfgExpr::from_str(s).unwrap_err().to_string();
+        let expected = format!("\n  {}\n  {}", s, expected);
+        assert!(
+            e.ends_with(&expected),
+            "when parsing `{}`, expected span:{}\ngot: {}",
+            s,
+            expected,
+            e
+        );
+    }
+
+    span("all(a b)", "      ^");
+    span("all(a, not(b, c))", "       ^^^^^^^^^");
+    span("any(a, and(b))", "       ^^^");
+    span("all(a", "     ^");
+    span("a = \"b", "    ^^");
+    span("a = b", "    ^");
+    span("all(a) b", "       ^");
+    span("all(a, 7)", "       ^");
+}
+
 #[test]
 fn cfg_matches() {
     assert!(e!(foo).matches(&[c!(bar), c!(foo), c!(baz)]));
@@ -216,6 +251,7 @@ fn check_cfg_attributes() {
     ok("any(not(unix), windows)");
     ok("foo");
 
+    ok("target_abi = \"abc\"");
     ok("target_arch = \"abc\"");
     ok("target_feature = \"abc\"");
     ok("target_os = \"abc\"");
@@ -248,4 +284,45 @@ fn check_cfg_attributes() {
         "all(not(debug_assertions), any(windows, proc_macro))",
         &["debug_assertions", "proc_macro"],
     );
+    warn(
+        "target_feature = \"+avx\"",
+        &["Use `target_feature = \"avx\"` instead"],
+    );
+    warn("all(unix, windows)", &["false for every target"]);
+    warn(
+        "all(target_os = \"linux\", target_os = \"macos\")",
+        &["false for every target"],
+    );
+}
+
+#[test]
+fn cfg_can_match() {
+    fn can_match(s: &str) -> bool {
+        CfgExpr::from_str(s).unwrap().can_match()
+    }
+
+    assert!(can_match("unix"));
+    assert!(can_match("all(unix, not(windows))"));
+    assert!(can_match("all(unix, target_family = \"wasm\")"));
+    assert!(can_match(
+        "all(target_feature = \"sse2\", target_feature = \"avx\")"
+    ));
+    assert!(can_match(
+        "all(target_os = \"linux\", target_abi = \"eabihf\")"
+    ));
+    assert!(can_match("not(all(unix, windows))"));
+    assert!(can_match(
+        "any(target_os = \"linux\", target_os = \"macos\")"
+    ));
+
+    assert!(!can_match("any()"));
+    assert!(!can_match("all(unix, windows)"));
+    assert!(!can_match("all(foo, not(foo))"));
+    assert!(!can_match("all(windows, target_family = \"unix\")"));
+    assert!(!can_match(
+        "all(target_os = \"linux\", target_os = \"macos\")"
+    ));
+    assert!(!can_match(
+        "all(target_abi = \"eabi\", target_abi = \"eabihf\")"
+    ));
 }
@@ -1525,6 +1525,8 @@ fn substitute_macros(input: &str) -> String {
         ("[DOCUMENTING]", " Documenting"),
         ("[FRESH]", "       Fresh"),
         ("[UPDATING]", "    Updating"),
+        ("[FETCHING]", "    Fetching"),
+        ("[INDEXING]", "    Indexing"),
         ("[ADDING]", "      Adding"),
         ("[REMOVING]", "    Removing"),
         ("[DOCTEST]", "   Doc-tests"),
@@ -1532,6 +1534,8 @@ fn substitute_macros(input: &str) -> String {
         ("[DOWNLOADING]", " Downloading"),
         ("[DOWNLOADED]", "  Downloaded"),
         ("[UPLOADING]", "   Uploading"),
+        ("[WAITING]", "     Waiting"),
+        ("[PUBLISHED]", "   Published"),
         ("[VERIFYING]", "   Verifying"),
         ("[ARCHIVING]", "   Archiving"),
         ("[INSTALLING]", "  Installing"),
@@ -1548,6 +1552,7 @@ fn substitute_macros(input: &str) -> String {
         ("[LOGOUT]", "      Logout"),
         ("[YANK]", "        Yank"),
         ("[OWNER]", "       Owner"),
+        ("[FEATURES]", "    Features"),
     ];
     let mut result = input.to_owned();
     for &(pat, subst) in &macros {
@@ -106,18 +106,25 @@ fn _validate_upload(
 ///   to validate the contents of the given file. Only the listed files will
 ///   be checked (others will be ignored).
 pub fn validate_crate_contents(
-    reader: impl Read,
+    mut reader: impl Read,
     expected_crate_name: &str,
     expected_files: &[&str],
     expected_contents: &[(&str, &str)],
 ) {
-    let mut rdr = GzDecoder::new(reader);
-    assert_eq!(
-        rdr.header().unwrap().filename().unwrap(),
-        expected_crate_name.as_bytes()
-    );
+    let mut krate = Vec::new();
+    reader.read_to_end(&mut krate).unwrap();
     let mut contents = Vec::new();
-    rdr.read_to_end(&mut contents).unwrap();
+    if krate.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        // zstd doesn't record the name of the file.
+        contents = zstd::decode_all(&krate[..]).unwrap();
+    } else {
+        let mut rdr = GzDecoder::new(&krate[..]);
+        assert_eq!(
+            rdr.header().unwrap().filename().unwrap(),
+            expected_crate_name.as_bytes()
+        );
+        rdr.read_to_end(&mut contents).unwrap();
+    }
     let mut ar = Archive::new(&contents[..]);
     let files: HashMap<PathBuf, String> = ar
         .entries()
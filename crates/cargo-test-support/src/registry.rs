@@ -1,6 +1,8 @@
 use crate::git::repo;
 use crate::paths;
 use cargo::sources::CRATES_IO_INDEX;
+use cargo::util::crate_signature;
+use cargo::util::paseto::SecretKey;
 use cargo::util::Sha256;
 use flate2::write::GzEncoder;
 use flate2::Compression;
@@ -37,6 +39,14 @@ pub fn dl_path() -> PathBuf {
 pub fn dl_url() -> Url {
     generate_url("dl")
 }
+/// Gets the path where prebuilt binaries are downloaded from, organized as
+/// `{name}/{version}/{target}.tar.gz`. See `Package::prebuilt`.
+pub fn prebuilt_dl_path() -> PathBuf {
+    generate_path("prebuilt")
+}
+pub fn prebuilt_dl_url() -> Url {
+    generate_url("prebuilt")
+}
 /// Gets the alternative-registry version of `registry_path`.
 pub fn alt_registry_path() -> PathBuf {
     generate_path("alternative-registry")
@@ -146,6 +156,10 @@ pub struct Package {
     invalid_json: bool,
     proc_macro: bool,
     links: Option<String>,
+    v: Option<u32>,
+    zstd: bool,
+    signing_key: Option<String>,
+    prebuilt: Vec<(String, Vec<(String, String)>)>,
 }
 
 #[derive(Clone)]
@@ -158,6 +172,8 @@ pub struct Dependency {
     registry: Option<String>,
     package: Option<String>,
     optional: bool,
+    public: bool,
+    default_features: bool,
 }
 
 pub fn init() {
@@ -247,6 +263,10 @@ impl Package {
             invalid_json: false,
             proc_macro: false,
             links: None,
+            v: None,
+            zstd: false,
+            signing_key: None,
+            prebuilt: Vec::new(),
         }
     }
 
@@ -272,6 +292,20 @@ impl Package {
         self
     }
 
+    /// Call with `true` to compress the `.crate` file with zstd instead of
+    /// gzip.
+    pub fn zstd(&mut self, zstd: bool) -> &mut Package {
+        self.zstd = zstd;
+        self
+    }
+
+    /// Signs the `.crate` file with the secret key `paserk`, and lists the
+    /// signature in the index, like registries do with `-Z crate-signing`.
+    pub fn signed(&mut self, paserk: &str) -> &mut Package {
+        self.signing_key = Some(paserk.to_string());
+        self
+    }
+
     /// Adds a file to the package.
     pub fn file(&mut self, name: &str, contents: &str) -> &mut Package {
         self.files.push((name.to_string(), contents.to_string()));
@@ -370,11 +404,33 @@ impl Package {
         self
     }
 
+    /// Adds prebuilt binaries for `target`, with the given file names and
+    /// contents, listed in the `prebuilt` field of the index entry.
+    ///
+    /// They are downloaded from `prebuilt_dl_path`, which is only used by
+    /// Cargo if the `prebuilt-dl` field of `config.json` is set.
+    pub fn prebuilt(&mut self, target: &str, bins: &[(&str, &str)]) -> &mut Package {
+        let bins = bins
+            .iter()
+            .map(|(name, contents)| (name.to_string(), contents.to_string()))
+            .collect();
+        self.prebuilt.push((target.to_string(), bins));
+        self
+    }
+
     pub fn links(&mut self, links: &str) -> &mut Package {
         self.links = Some(links.to_string());
         self
     }
 
+    /// Sets the index schema version for this package.
+    ///
+    /// See `cargo::sources::registry::RegistryPackage` for more information.
+    pub fn schema_version(&mut self, version: u32) -> &mut Package {
+        self.v = Some(version);
+        self
+    }
+
     /// Creates the package and place it in the registry.
     ///
     /// This does not actually use Cargo's publishing system, but instead
@@ -402,12 +458,13 @@ impl Package {
                     "name": dep.name,
                     "req": dep.vers,
                     "features": dep.features,
-                    "default_features": true,
+                    "default_features": dep.default_features,
                     "target": dep.target,
                     "optional": dep.optional,
                     "kind": dep.kind,
                     "registry": registry_url,
                     "package": dep.package,
+                    "public": dep.public,
                 })
             })
             .collect::<Vec<_>>();
@@ -420,16 +477,51 @@ impl Package {
         } else {
             serde_json::json!(self.name)
         };
-        let line = serde_json::json!({
+        // This emulates what crates.io does to retain backwards compatibility:
+        // features using the newer syntax go in a separate `features2` map.
+        let (features, features2): (HashMap<_, _>, HashMap<_, _>) =
+            self.features.iter().partition(|(_name, values)| {
+                !values
+                    .iter()
+                    .any(|value| value.starts_with("dep:") || value.contains("?/"))
+            });
+        let mut json = serde_json::json!({
             "name": name,
             "vers": self.vers,
             "deps": deps,
             "cksum": cksum,
-            "features": self.features,
+            "features": features,
             "yanked": self.yanked,
             "links": self.links,
-        })
-        .to_string();
+        });
+        if !features2.is_empty() {
+            json["features2"] = serde_json::json!(features2);
+            json["v"] = serde_json::json!(2);
+        }
+        if let Some(v) = self.v {
+            json["v"] = serde_json::json!(v);
+        }
+        if let Some(paserk) = &self.signing_key {
+            let key = SecretKey::from_paserk(paserk).unwrap();
+            json["sig"] =
+                serde_json::json!(crate_signature::sign(&key, &self.name, &self.vers, &cksum));
+        }
+        if !self.prebuilt.is_empty() {
+            let prebuilt: Vec<_> = self
+                .prebuilt
+                .iter()
+                .map(|(target, bins)| {
+                    let cksum = self.make_prebuilt_archive(target, bins);
+                    let sig = self.signing_key.as_ref().map(|paserk| {
+                        let key = SecretKey::from_paserk(paserk).unwrap();
+                        crate_signature::sign_prebuilt(&key, &self.name, &self.vers, target, &cksum)
+                    });
+                    serde_json::json!({ "target": target, "cksum": cksum, "sig": sig })
+                })
+                .collect();
+            json["prebuilt"] = serde_json::json!(prebuilt);
+        }
+        let line = json.to_string();
 
         let file = match self.name.len() {
             1 => format!("1/{}", self.name),
@@ -484,23 +576,52 @@ impl Package {
         let dst = self.archive_dst();
         t!(fs::create_dir_all(dst.parent().unwrap()));
         let f = t!(File::create(&dst));
-        let mut a = Builder::new(GzEncoder::new(f, Compression::default()));
+        if self.zstd {
+            let encoder = t!(zstd::Encoder::new(f, 0)).auto_finish();
+            self.fill_archive(&mut Builder::new(encoder));
+        } else {
+            self.fill_archive(&mut Builder::new(GzEncoder::new(f, Compression::default())));
+        }
+    }
 
+    fn fill_archive<W: Write>(&self, a: &mut Builder<W>) {
         if !self.files.iter().any(|(name, _)| name == "Cargo.toml") {
-            self.append_manifest(&mut a);
+            self.append_manifest(a);
         }
         if self.files.is_empty() {
-            self.append(&mut a, "src/lib.rs", "");
+            self.append(a, "src/lib.rs", "");
         } else {
             for &(ref name, ref contents) in self.files.iter() {
-                self.append(&mut a, name, contents);
+                self.append(a, name, contents);
             }
         }
         for &(ref name, ref contents) in self.extra_files.iter() {
-            self.append_extra(&mut a, name, contents);
+            self.append_extra(a, name, contents);
         }
     }
 
+    /// Writes the `.tar.gz` archive of prebuilt binaries for `target`, and
+    /// returns its checksum.
+    fn make_prebuilt_archive(&self, target: &str, bins: &[(String, String)]) -> String {
+        let mut ar = Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for (name, contents) in bins {
+            let mut header = Header::new_ustar();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o755);
+            t!(header.set_path(name));
+            header.set_cksum();
+            t!(ar.append(&header, contents.as_bytes()));
+        }
+        let data = t!(t!(ar.into_inner()).finish());
+        let dst = prebuilt_dl_path()
+            .join(&self.name)
+            .join(&self.vers)
+            .join(format!("{}.tar.gz", target));
+        t!(fs::create_dir_all(dst.parent().unwrap()));
+        t!(fs::write(&dst, &data));
+        cksum(&data)
+    }
+
     fn append_manifest<W: Write>(&self, ar: &mut Builder<W>) {
         let mut manifest = format!(
             r#"
@@ -585,6 +706,8 @@ impl Dependency {
             features: Vec::new(),
             package: None,
             optional: false,
+            public: false,
+            default_features: true,
             registry: None,
         }
     }
@@ -630,4 +753,16 @@ impl Dependency {
         self.optional = optional;
         self
     }
+
+    /// Changes this to a public dependency.
+    pub fn public(&mut self, public: bool) -> &mut Self {
+        self.public = public;
+        self
+    }
+
+    /// Adds `default-features = false` if the argument is `false`.
+    pub fn default_features(&mut self, default_features: bool) -> &mut Self {
+        self.default_features = default_features;
+        self
+    }
 }
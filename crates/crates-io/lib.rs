@@ -2,10 +2,11 @@
 #![allow(clippy::identity_op)] // used for vertical alignment
 
 use std::collections::BTreeMap;
+use std::fmt;
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::{Cursor, SeekFrom};
-use std::time::Instant;
+use std::time::{Duration, Instant, SystemTime};
 
 use anyhow::{bail, Context, Result};
 use curl::easy::{Easy, List};
@@ -55,6 +56,16 @@ pub struct NewCrate {
     pub repository: Option<String>,
     pub badges: BTreeMap<String, BTreeMap<String, String>>,
     pub links: Option<String>,
+    /// The signature of the `.crate` file by the publisher.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// Extra fields for the registry, from the
+    /// `package.metadata.registry.<name>` table of the manifest.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Map<String, serde_json::Value>>,
+    /// The SLSA provenance of the `.crate` file, as an in-toto statement.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<serde_json::Value>,
 }
 
 #[derive(Serialize)]
@@ -70,6 +81,8 @@ pub struct NewCrateDependency {
     pub registry: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub explicit_name_in_toml: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public: Option<bool>,
 }
 
 #[derive(Deserialize)]
@@ -81,6 +94,28 @@ pub struct User {
     pub name: Option<String>,
 }
 
+/// Error returned when the registry rejected a request with a
+/// `429 Too Many Requests` response because of its rate limits.
+#[derive(Debug)]
+pub struct RateLimited {
+    /// How long to wait before trying again, from the `Retry-After` header.
+    pub retry_after: Option<Duration>,
+    /// The error message of the registry.
+    pub message: String,
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the registry is rate limiting requests (status 429 Too Many Requests): {}",
+            self.message
+        )
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
 pub struct Warnings {
     pub invalid_categories: Vec<String>,
     pub invalid_badges: Vec<String>,
@@ -134,6 +169,10 @@ impl Registry {
         }
     }
 
+    pub fn set_token(&mut self, token: Option<String>) {
+        self.token = token;
+    }
+
     pub fn host(&self) -> &str {
         &self.host
     }
@@ -333,6 +372,16 @@ impl Registry {
 
         match (self.handle.response_code()?, errors) {
             (0, None) | (200, None) => {}
+            (429, errors) => {
+                return Err(RateLimited {
+                    retry_after: retry_after(&headers),
+                    message: match errors {
+                        Some(errors) => errors.join(", "),
+                        None => body.trim().to_string(),
+                    },
+                }
+                .into())
+            }
             (503, None) if started.elapsed().as_secs() >= 29 && self.host_is_crates_io() => bail!(
                 "Request timed out after 30 seconds. If you're trying to \
                  upload a crate it may be too large. If the crate is under \
@@ -363,6 +412,27 @@ impl Registry {
     }
 }
 
+/// Parses the `Retry-After` header among `headers`, which is either a number
+/// of seconds or a date.
+fn retry_after(headers: &[String]) -> Option<Duration> {
+    let value = headers.iter().find_map(|header| {
+        let (name, value) = header.split_at(header.find(':')?);
+        if name.trim().eq_ignore_ascii_case("retry-after") {
+            Some(value[1..].trim())
+        } else {
+            None
+        }
+    })?;
+    if let Ok(secs) = value.parse() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::from_secs(0)),
+    )
+}
+
 fn reason(code: u32) -> &'static str {
     // Taken from https://developer.mozilla.org/en-US/docs/Web/HTTP/Status
     match code {
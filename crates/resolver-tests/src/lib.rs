@@ -14,7 +14,7 @@ use cargo::core::dependency::DepKind;
 use cargo::core::resolver::{self, ResolveOpts};
 use cargo::core::source::{GitReference, SourceId};
 use cargo::core::Resolve;
-use cargo::core::{Dependency, PackageId, Registry, Summary};
+use cargo::core::{enable_nightly_features, Dependency, PackageId, Registry, Summary};
 use cargo::util::{CargoResult, Config, Graph, IntoUrl};
 
 use proptest::collection::{btree_map, vec};
@@ -115,6 +115,30 @@ pub fn resolve_with_config(
     Ok(resolve.sort())
 }
 
+/// Resolves with the PubGrub based resolver (`-Z pubgrub`), so its results
+/// can be compared with the regular resolver.
+pub fn resolve_with_pubgrub(
+    deps: Vec<Dependency>,
+    registry: &[Summary],
+) -> CargoResult<Vec<PackageId>> {
+    enable_nightly_features();
+    let mut config = Config::default().unwrap();
+    config
+        .configure(
+            1,
+            false,
+            None,
+            false,
+            false,
+            false,
+            &None,
+            &["pubgrub".to_string()],
+            &[],
+        )
+        .unwrap();
+    resolve_with_config(deps, registry, &config)
+}
+
 pub fn resolve_with_config_raw(
     deps: Vec<Dependency>,
     registry: &[Summary],
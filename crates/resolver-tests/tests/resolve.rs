@@ -5,7 +5,7 @@ use cargo::util::{is_ci, Config};
 use resolver_tests::{
     assert_contains, assert_same, dep, dep_kind, dep_loc, dep_req, dep_req_kind, loc_names, names,
     pkg, pkg_id, pkg_loc, registry, registry_strategy, remove_dep, resolve, resolve_and_validated,
-    resolve_with_config, PrettyPrintRegistry, SatResolve, ToDep, ToPkgId,
+    resolve_with_config, resolve_with_pubgrub, PrettyPrintRegistry, SatResolve, ToDep, ToPkgId,
 };
 
 use proptest::prelude::*;
@@ -100,6 +100,47 @@ proptest! {
         }
     }
 
+    /// NOTE: if you think this test has failed spuriously see the note at the top of this macro.
+    #[test]
+    fn prop_pubgrub_agrees_with_resolver(
+            PrettyPrintRegistry(input) in registry_strategy(50, 20, 60)
+    ) {
+        let reg = registry(input.clone());
+        let sat_resolve = SatResolve::new(&reg);
+        // there is only a small chance that any one
+        // crate will be interesting.
+        // So we try some of the most complicated.
+        for this in input.iter().rev().take(10) {
+            let res = resolve(
+                vec![dep_req(&this.name(), &format!("={}", this.version()))],
+                &reg,
+            );
+            let pres = resolve_with_pubgrub(
+                vec![dep_req(&this.name(), &format!("={}", this.version()))],
+                &reg,
+            );
+
+            prop_assert_eq!(
+                res.is_ok(),
+                pres.is_ok(),
+                "pubgrub and regular resolver disagree about weather `{} = \"={}\"` can resolve",
+                this.name(),
+                this.version()
+            );
+            // The two may pick different versions when backtracking, but
+            // whatever pubgrub picks has to be a valid solution.
+            if let Ok(pids) = pres {
+                prop_assert!(
+                    sat_resolve.sat_is_valid_solution(&pids),
+                    "pubgrub picked an invalid solution for `{} = \"={}\"`: {:?}",
+                    this.name(),
+                    this.version(),
+                    pids
+                );
+            }
+        }
+    }
+
     /// NOTE: if you think this test has failed spuriously see the note at the top of this macro.
     #[test]
     fn prop_removing_a_dep_cant_break(
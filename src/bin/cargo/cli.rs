@@ -15,7 +15,7 @@ pub fn main(config: &mut Config) -> CliResult {
         return Ok(());
     }
 
-    let args = match cli().get_matches_safe() {
+    let args = match cli(config).get_matches_safe() {
         Ok(args) => args,
         Err(e) => {
             if e.kind == clap::ErrorKind::UnrecognizedSubcommand {
@@ -42,8 +42,19 @@ Available unstable (nightly-only) flags:
     -Z timings             -- Display concurrency information
     -Z doctest-xcompile    -- Compile and run doctests for non-host target using runner config
     -Z terminal-width      -- Provide a terminal width to rustc for error truncation
-    -Z namespaced-features -- Allow features with `dep:` prefix
     -Z weak-dep-features   -- Allow `dep_name?/feature` feature syntax
+    -Z registry-overlay    -- Write new downloads to an overlay if the registry is read-only
+    -Z test-cache          -- Skip running test binaries which passed with the same inputs
+    -Z parallel-doctests   -- Run the doc tests during the build, along with the other units
+    -Z parallel-install    -- Install the crates given to `cargo install` at the same time
+    -Z install-locked      -- Use the workspace's Cargo.lock for `cargo install --path`
+    -Z prebuilt-install    -- Install the prebuilt binaries of crates advertised by registries
+    -Z local-install-root  -- Use the `install.root` of the project's config in `cargo install`
+    -Z watchman            -- Ask watchman which files changed instead of checking all their mtimes
+    -Z subcommand-context  -- Pass the resolved workspace and configuration to external subcommands
+    -Z advisories          -- Check Cargo.lock against a RustSec advisory database
+    -Z network-sandbox     -- Deny network access to build scripts and procedural macros
+    -Z build-script-sandbox -- Only allow build scripts to write to their package, OUT_DIR and configured paths
 
 Run with 'cargo -Z [FLAG] [SUBCOMMAND]'"
         );
@@ -107,7 +118,7 @@ Run with 'cargo -Z [FLAG] [SUBCOMMAND]'"
         (cmd, Some(args)) => (cmd, args),
         _ => {
             // No subcommand provided.
-            cli().print_help()?;
+            cli(config).print_help()?;
             return Ok(());
         }
     };
@@ -142,7 +153,7 @@ fn expand_aliases(
 ) -> Result<(ArgMatches<'static>, GlobalArgs), CliError> {
     if let (cmd, Some(args)) = args.subcommand() {
         match (
-            commands::builtin_exec(cmd),
+            commands::builtin_exec(config, cmd),
             super::aliased_command(config, cmd)?,
         ) {
             (Some(_), Some(_)) => {
@@ -163,7 +174,7 @@ fn expand_aliases(
                 // Note that an alias to an external command will not receive
                 // these arguments. That may be confusing, but such is life.
                 let global_args = GlobalArgs::new(args);
-                let new_args = cli()
+                let new_args = cli(config)
                     .setting(AppSettings::NoBinaryName)
                     .get_matches_from_safe(alias)?;
                 let (expanded_args, _) = expand_aliases(config, new_args)?;
@@ -220,7 +231,7 @@ fn execute_subcommand(
     cmd: &str,
     subcommand_args: &ArgMatches<'_>,
 ) -> CliResult {
-    if let Some(exec) = commands::builtin_exec(cmd) {
+    if let Some(exec) = commands::builtin_exec(config, cmd) {
         return exec(config, subcommand_args);
     }
 
@@ -262,7 +273,7 @@ impl GlobalArgs {
     }
 }
 
-fn cli() -> App {
+fn cli(config: &Config) -> App {
     let is_rustup = std::env::var_os("RUSTUP_HOME").is_some();
     let usage = if is_rustup {
         "cargo [+toolchain] [OPTIONS] [SUBCOMMAND]"
@@ -340,5 +351,5 @@ See 'cargo help <command>' for more information on a specific command.\n",
                 .number_of_values(1)
                 .global(true),
         )
-        .subcommands(commands::builtin())
+        .subcommands(commands::builtin(config))
 }
@@ -1,5 +1,5 @@
 use crate::command_prelude::*;
-use cargo::ops::{self, TestOptions};
+use cargo::ops::{self, TestFormat, TestOptions};
 
 pub fn cli() -> App {
     subcommand("bench")
@@ -29,6 +29,12 @@ pub fn cli() -> App {
             "Benchmark all targets",
         )
         .arg(opt("no-run", "Compile, but don't run benchmarks"))
+        .arg(
+            opt("format", "Format of the benchmark results: human, json")
+                .value_name("FMT")
+                .possible_values(&["human", "json"])
+                .default_value("human"),
+        )
         .arg_package_spec(
             "Package to run benchmarks for",
             "Benchmark all packages in the workspace",
@@ -60,10 +66,24 @@ pub fn exec(config: &mut Config, args: &ArgMatches<'_>) -> CliResult {
     compile_opts.build_config.requested_profile =
         args.get_profile_name(config, "bench", ProfileChecking::Checked)?;
 
+    let format = match args.value_of("format").unwrap() {
+        "json" => TestFormat::Json,
+        _ => TestFormat::Human,
+    };
+    if format != TestFormat::Human {
+        config.cli_unstable().fail_if_stable_opt("--format", None)?;
+    }
+
     let ops = TestOptions {
         no_run: args.is_present("no-run"),
         no_fail_fast: args.is_present("no-fail-fast"),
         compile_opts,
+        format,
+        timeout: None,
+        partition: None,
+        retries: 0,
+        no_cache: false,
+        coverage: false,
     };
 
     let bench_args = args.value_of("BENCHNAME").into_iter();
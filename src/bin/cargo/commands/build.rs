@@ -38,6 +38,14 @@ pub fn cli() -> App {
             )
             .value_name("PATH"),
         )
+        .arg(opt(
+            "sbom",
+            "Write a CycloneDX SBOM next to each final artifact (unstable)",
+        ))
+        .arg(opt(
+            "rustc-invocations",
+            "Write the rustc command lines of the build to rustc-invocations.json (unstable)",
+        ))
         .arg_manifest_path()
         .arg_message_format()
         .arg_build_plan()
@@ -63,7 +71,17 @@ pub fn exec(config: &mut Config, args: &ArgMatches<'_>) -> CliResult {
     if compile_opts.build_config.export_dir.is_some() {
         config
             .cli_unstable()
-            .fail_if_stable_opt("--out-dir", 6790)?;
+            .fail_if_stable_opt("--out-dir", Some(6790))?;
+    }
+    if args.is_present("sbom") {
+        config.cli_unstable().fail_if_stable_opt("--sbom", None)?;
+        compile_opts.build_config.sbom = true;
+    }
+    if args.is_present("rustc-invocations") {
+        config
+            .cli_unstable()
+            .fail_if_stable_opt("--rustc-invocations", None)?;
+        compile_opts.build_config.rustc_invocations = true;
     }
     ops::compile(&ws, &compile_opts)?;
     Ok(())
@@ -32,6 +32,10 @@ pub fn cli() -> App {
         .arg_target_triple("Check for the target triple")
         .arg_target_dir()
         .arg_manifest_path()
+        .arg(opt(
+            "rustc-invocations",
+            "Write the rustc command lines of the check to rustc-invocations.json (unstable)",
+        ))
         .arg_message_format()
         .arg_unit_graph()
         .after_help("Run `cargo help check` for more detailed information.\n")
@@ -52,7 +56,14 @@ pub fn exec(config: &mut Config, args: &ArgMatches<'_>) -> CliResult {
         }
     };
     let mode = CompileMode::Check { test };
-    let compile_opts = args.compile_options(config, mode, Some(&ws), ProfileChecking::Unchecked)?;
+    let mut compile_opts =
+        args.compile_options(config, mode, Some(&ws), ProfileChecking::Unchecked)?;
+    if args.is_present("rustc-invocations") {
+        config
+            .cli_unstable()
+            .fail_if_stable_opt("--rustc-invocations", None)?;
+        compile_opts.build_config.rustc_invocations = true;
+    }
 
     ops::compile(&ws, &compile_opts)?;
     Ok(())
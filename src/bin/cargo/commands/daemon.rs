@@ -0,0 +1,30 @@
+use crate::command_prelude::*;
+
+use cargo::ops::{self, DaemonOptions};
+
+pub fn cli() -> App {
+    subcommand("daemon")
+        .about("Answer JSON-RPC requests about a workspace over TCP (unstable)")
+        .arg(opt("quiet", "No output printed to stdout").short("q"))
+        .arg(
+            opt("listen", "The address to listen on")
+                .value_name("ADDR")
+                .default_value("127.0.0.1:0"),
+        )
+        .arg_manifest_path()
+        .after_help("Run `cargo help daemon` for more detailed information.\n")
+}
+
+pub fn exec(config: &mut Config, args: &ArgMatches<'_>) -> CliResult {
+    if !config.cli_unstable().unstable_options {
+        return Err(CliError::new(
+            anyhow::format_err!("`cargo daemon` requires `-Z unstable-options`"),
+            101,
+        ));
+    }
+    let opts = DaemonOptions {
+        listen: args.value_of("listen").unwrap().to_string(),
+    };
+    ops::daemon(config, &args.root_manifest(config)?, &opts)?;
+    Ok(())
+}
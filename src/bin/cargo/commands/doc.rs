@@ -17,6 +17,10 @@ pub fn cli() -> App {
         )
         .arg(opt("no-deps", "Don't build documentation for dependencies"))
         .arg(opt("document-private-items", "Document private items"))
+        .arg(opt(
+            "features-matrix",
+            "Check that the docs build with each feature combination of the `[doc]` table",
+        ))
         .arg_jobs()
         .arg_targets_lib_bin(
             "Document only this package's library",
@@ -43,6 +47,13 @@ pub fn exec(config: &mut Config, args: &ArgMatches<'_>) -> CliResult {
         args.compile_options(config, mode, Some(&ws), ProfileChecking::Checked)?;
     compile_opts.rustdoc_document_private_items = args.is_present("document-private-items");
 
+    if args.is_present("features-matrix") {
+        config
+            .cli_unstable()
+            .fail_if_stable_opt("--features-matrix", None)?;
+        ops::doc_features_matrix(&ws, &mut compile_opts)?;
+    }
+
     let doc_opts = DocOptions {
         open_result: args.is_present("open"),
         compile_opts,
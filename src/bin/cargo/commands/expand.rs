@@ -0,0 +1,68 @@
+use crate::command_prelude::*;
+
+use cargo::ops::{self, ExpandOptions};
+
+pub fn cli() -> App {
+    subcommand("expand")
+        .about("Print the code of a target with its macros expanded (unstable)")
+        .arg(opt("quiet", "No output printed to stdout").short("q"))
+        .arg(Arg::with_name("ITEM").help("Path of the item to print, like `module::Type`"))
+        .arg_package("Package to expand")
+        .arg_jobs()
+        .arg_targets_all(
+            "Expand only this package's library",
+            "Expand only the specified binary",
+            "Expand all binaries",
+            "Expand only the specified example",
+            "Expand all examples",
+            "Expand only the specified test target",
+            "Expand all tests",
+            "Expand only the specified bench target",
+            "Expand all benches",
+            "Expand all targets",
+        )
+        .arg_release("Expand artifacts in release mode, with optimizations")
+        .arg_profile("Expand artifacts with the specified profile")
+        .arg_features()
+        .arg_target_triple("Expand for the target triple")
+        .arg_target_dir()
+        .arg_manifest_path()
+        .arg(opt("ugly", "Do not format the expanded code with rustfmt"))
+        .after_help("Run `cargo help expand` for more detailed information.\n")
+}
+
+pub fn exec(config: &mut Config, args: &ArgMatches<'_>) -> CliResult {
+    if !config.cli_unstable().unstable_options {
+        return Err(CliError::new(
+            anyhow::format_err!("`cargo expand` requires `-Z unstable-options`"),
+            101,
+        ));
+    }
+    let ws = args.workspace(config)?;
+    let test = match args.value_of("profile") {
+        Some("test") => true,
+        None => false,
+        Some(profile) => {
+            let err = anyhow::format_err!(
+                "unknown profile: `{}`, only `test` is \
+                 currently supported",
+                profile
+            );
+            return Err(CliError::new(err, 101));
+        }
+    };
+    let mode = CompileMode::Check { test };
+    let compile_opts = args.compile_options_for_single_package(
+        config,
+        mode,
+        Some(&ws),
+        ProfileChecking::Unchecked,
+    )?;
+    let mut opts = ExpandOptions {
+        compile_opts,
+        item: args.value_of("ITEM").map(|s| s.to_string()),
+        ugly: args.is_present("ugly"),
+    };
+    ops::expand(&ws, &mut opts)?;
+    Ok(())
+}
@@ -1,5 +1,6 @@
 use crate::command_prelude::*;
 
+use cargo::drop_print;
 use cargo::ops;
 use cargo::ops::FetchOptions;
 
@@ -9,15 +10,30 @@ pub fn cli() -> App {
         .arg(opt("quiet", "No output printed to stdout").short("q"))
         .arg_manifest_path()
         .arg_target_triple("Fetch dependencies for the target triple")
+        .arg(
+            opt(
+                "offline-report",
+                "Report which dependencies are not available offline, without fetching them",
+            )
+            .conflicts_with("target"),
+        )
         .after_help("Run `cargo help fetch` for more detailed information.\n")
 }
 
 pub fn exec(config: &mut Config, args: &ArgMatches<'_>) -> CliResult {
+    if args.is_present("offline-report") {
+        config.set_offline(true);
+        let ws = args.workspace(config)?;
+        let report = ops::offline_report(&ws)?;
+        drop_print!(config, "{}", report);
+        return Ok(());
+    }
+
     let ws = args.workspace(config)?;
 
     let opts = FetchOptions {
         config,
-        targets: args.targets(),
+        targets: args.targets(Some(&ws)),
     };
     let _ = ops::fetch(&ws, &opts)?;
     Ok(())
@@ -77,13 +77,13 @@ fn try_help(config: &Config) -> CargoResult<bool> {
 ///
 /// Returns None if it is not a built-in command.
 fn check_alias(config: &Config, subcommand: &str) -> Option<String> {
-    if super::builtin_exec(subcommand).is_some() {
+    if super::builtin_exec(config, subcommand).is_some() {
         return Some(subcommand.to_string());
     }
     match aliased_command(config, subcommand) {
         Ok(Some(alias)) => {
             let alias = alias.into_iter().next()?;
-            if super::builtin_exec(&alias).is_some() {
+            if super::builtin_exec(config, &alias).is_some() {
                 Some(alias)
             } else {
                 None
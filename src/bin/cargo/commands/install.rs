@@ -3,6 +3,7 @@ use crate::command_prelude::*;
 use cargo::core::{GitReference, SourceId};
 use cargo::ops;
 use cargo::util::IntoUrl;
+use std::path::Path;
 
 pub fn cli() -> App {
     subcommand("install")
@@ -35,6 +36,14 @@ pub fn cli() -> App {
                 .value_name("SHA")
                 .requires("git"),
         )
+        .arg(
+            opt(
+                "path-in-repo",
+                "Only check out this directory of the git repository",
+            )
+            .value_name("PATH")
+            .requires("git"),
+        )
         .arg(
             opt("path", "Filesystem path to local crate to install")
                 .value_name("PATH")
@@ -44,9 +53,25 @@ pub fn cli() -> App {
             "list",
             "list all installed packages and their versions",
         ))
+        .arg(
+            opt(
+                "format",
+                "Format of the list of installed packages: human, json",
+            )
+            .value_name("FMT")
+            .possible_values(&["human", "json"])
+            .requires("list"),
+        )
         .arg_jobs()
         .arg(opt("force", "Force overwriting existing crates or binaries").short("f"))
         .arg(opt("no-track", "Do not save tracking information"))
+        .arg(
+            opt(
+                "dry-run",
+                "Print what would be built and installed, without building anything",
+            )
+            .conflicts_with("list"),
+        )
         .arg_features()
         .arg_profile("Install artifacts with the specified profile")
         .arg(opt("debug", "Build in debug mode instead of release mode"))
@@ -75,6 +100,14 @@ pub fn cli() -> App {
 }
 
 pub fn exec(config: &mut Config, args: &ArgMatches<'_>) -> CliResult {
+    // The configuration of the current directory is replaced below, so its
+    // `install.root` is looked up first.
+    let local_root = if config.cli_unstable().local_install_root && !args.is_present("root") {
+        ops::project_install_root(config)?
+    } else {
+        None
+    };
+
     if let Some(path) = args.value_of_path("path", config) {
         config.reload_rooted_at(path)?;
     } else {
@@ -114,7 +147,10 @@ pub fn exec(config: &mut Config, args: &ArgMatches<'_>) -> CliResult {
     };
 
     let version = args.value_of("version");
-    let root = args.value_of("root");
+    let root = args
+        .value_of_os("root")
+        .map(Path::new)
+        .or(local_root.as_deref());
 
     // We only provide worksapce information for local crate installation from
     // one of the following sources:
@@ -136,19 +172,37 @@ pub fn exec(config: &mut Config, args: &ArgMatches<'_>) -> CliResult {
     compile_opts.build_config.requested_profile =
         args.get_profile_name(config, "release", ProfileChecking::Checked)?;
 
+    if args.is_present("dry-run") {
+        config
+            .cli_unstable()
+            .fail_if_stable_opt("--dry-run", None)?;
+    }
+
+    if args.is_present("path-in-repo") {
+        config
+            .cli_unstable()
+            .fail_if_stable_opt("--path-in-repo", None)?;
+    }
+
     if args.is_present("list") {
-        ops::install_list(root, config)?;
+        let json = args.value_of("format") == Some("json");
+        if json {
+            config.cli_unstable().fail_if_stable_opt("--format", None)?;
+        }
+        ops::install_list(root, json, config)?;
     } else {
         ops::install(
             config,
             root,
             krates,
             source,
+            args.value_of_os("path-in-repo").map(Path::new),
             from_cwd,
             version,
             &compile_opts,
             args.is_present("force"),
             args.is_present("no-track"),
+            args.is_present("dry-run"),
         )?;
     }
     Ok(())
@@ -0,0 +1,55 @@
+use crate::command_prelude::*;
+
+use cargo::ops::tree::Target;
+use cargo::ops::{self, LicensesFormat, LicensesOptions};
+
+pub fn cli() -> App {
+    subcommand("licenses")
+        .about("Report the licenses of the dependencies of a workspace (unstable)")
+        .arg(opt("quiet", "No output printed to stdout").short("q"))
+        .arg_manifest_path()
+        .arg_package_spec_no_all(
+            "Package to report the dependencies of",
+            "Report the dependencies of all packages in the workspace",
+            "Exclude specific workspace members",
+        )
+        .arg_features()
+        .arg_target_triple(
+            "Only report dependencies used on the given target-triple (default host platform). \
+             Pass `all` to include all targets.",
+        )
+        .arg(opt("dev", "Also report dev-dependencies"))
+        .arg(
+            opt("format", "Output format: human, json, csv, html")
+                .value_name("FMT")
+                .possible_values(&["human", "json", "csv", "html"])
+                .default_value("human"),
+        )
+        .arg(opt(
+            "check",
+            "Fail if a dependency violates the license policy of the `[licenses]` configuration",
+        ))
+        .after_help("Run `cargo help licenses` for more detailed information.\n")
+}
+
+pub fn exec(config: &mut Config, args: &ArgMatches<'_>) -> CliResult {
+    if !config.cli_unstable().unstable_options {
+        return Err(CliError::new(
+            anyhow::format_err!("`cargo licenses` requires `-Z unstable-options`"),
+            101,
+        ));
+    }
+    let ws = args.workspace(config)?;
+    let opts = LicensesOptions {
+        features: values(args, "features"),
+        all_features: args.is_present("all-features"),
+        no_default_features: args.is_present("no-default-features"),
+        packages: args.packages_from_flags()?,
+        target: Target::from_cli(values(args, "target")),
+        dev: args.is_present("dev"),
+        format: LicensesFormat::parse(args.value_of("format").unwrap())?,
+        check: args.is_present("check"),
+    };
+    ops::licenses(&ws, &opts)?;
+    Ok(())
+}
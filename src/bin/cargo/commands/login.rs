@@ -1,5 +1,6 @@
 use crate::command_prelude::*;
 
+use anyhow::format_err;
 use cargo::ops;
 
 pub fn cli() -> App {
@@ -9,21 +10,38 @@ pub fn cli() -> App {
              If token is not specified, it will be read from stdin.",
         )
         .arg(opt("quiet", "No output printed to stdout").short("q"))
-        .arg(Arg::with_name("token"))
+        .arg(Arg::with_name("token").conflicts_with("generate-keypair"))
         .arg(
             opt("host", "Host to set the token for")
                 .value_name("HOST")
                 .hidden(true),
         )
         .arg(opt("registry", "Registry to use").value_name("REGISTRY"))
+        .arg(
+            opt(
+                "generate-keypair",
+                "Generate a secret key to sign asymmetric tokens (unstable)",
+            )
+            .hidden(true),
+        )
         .after_help("Run `cargo help login` for more detailed information.\n")
 }
 
 pub fn exec(config: &mut Config, args: &ArgMatches<'_>) -> CliResult {
+    let generate_keypair = args.is_present("generate-keypair");
+    if generate_keypair && !config.cli_unstable().asymmetric_token {
+        return Err(format_err!(
+            "the `--generate-keypair` flag is unstable, pass `-Z asymmetric-token` to enable it\n\
+             See https://github.com/rust-lang/cargo/issues/10519 for more information \
+             about asymmetric tokens."
+        )
+        .into());
+    }
     ops::registry_login(
         config,
         args.value_of("token").map(String::from),
         args.value_of("registry").map(String::from),
+        generate_keypair,
     )?;
     Ok(())
 }
@@ -13,7 +13,7 @@ pub fn cli() -> App {
 
 pub fn exec(config: &mut Config, args: &ArgMatches<'_>) -> CliResult {
     let unstable = config.cli_unstable();
-    if !(unstable.credential_process || unstable.unstable_options) {
+    if !(unstable.credential_process || unstable.asymmetric_token || unstable.unstable_options) {
         const SEE: &str = "See https://github.com/rust-lang/cargo/issues/8933 for more \
         information about the `cargo logout` command.";
         if features::nightly_features_allowed() {
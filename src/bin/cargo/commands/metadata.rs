@@ -20,7 +20,23 @@ pub fn cli() -> App {
             "Output information only about the workspace members \
              and don't fetch dependencies",
         ))
+        .arg(
+            opt(
+                "checksums",
+                "Also list where the sources of the packages of the lock file \
+                 are downloaded from, with their checksums (unstable)",
+            )
+            .conflicts_with("no-deps"),
+        )
         .arg_manifest_path()
+        .arg(
+            opt(
+                "format",
+                "Output a single JSON document, or one JSON object per line (unstable)",
+            )
+            .value_name("FMT")
+            .possible_values(&["json", "ndjson"]),
+        )
         .arg(
             opt("format-version", "Format version")
                 .value_name("VERSION")
@@ -32,6 +48,18 @@ pub fn cli() -> App {
 pub fn exec(config: &mut Config, args: &ArgMatches<'_>) -> CliResult {
     let ws = args.workspace(config)?;
 
+    let checksums = args.is_present("checksums");
+    if checksums {
+        config
+            .cli_unstable()
+            .fail_if_stable_opt("--checksums", None)?;
+    }
+
+    let ndjson = args.value_of("format") == Some("ndjson");
+    if args.is_present("format") {
+        config.cli_unstable().fail_if_stable_opt("--format", None)?;
+    }
+
     let version = match args.value_of("format-version") {
         None => {
             config.shell().warn(
@@ -50,9 +78,14 @@ pub fn exec(config: &mut Config, args: &ArgMatches<'_>) -> CliResult {
         no_deps: args.is_present("no-deps"),
         filter_platforms: args._values_of("filter-platform"),
         version,
+        checksums,
     };
 
-    let result = ops::output_metadata(&ws, &options)?;
-    config.shell().print_json(&result);
+    if ndjson {
+        ops::output_metadata_ndjson(&ws, &options)?;
+    } else {
+        let result = ops::output_metadata(&ws, &options)?;
+        config.shell().print_json(&result);
+    }
     Ok(())
 }
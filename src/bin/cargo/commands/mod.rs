@@ -1,18 +1,21 @@
 use crate::command_prelude::*;
 
-pub fn builtin() -> Vec<App> {
-    vec![
+pub fn builtin(config: &Config) -> Vec<App> {
+    let builtin = vec![
         bench::cli(),
         build::cli(),
         check::cli(),
         clean::cli(),
+        daemon::cli(),
         doc::cli(),
+        expand::cli(),
         fetch::cli(),
         fix::cli(),
         generate_lockfile::cli(),
         git_checkout::cli(),
         init::cli(),
         install::cli(),
+        licenses::cli(),
         locate_project::cli(),
         login::cli(),
         logout::cli(),
@@ -20,6 +23,7 @@ pub fn builtin() -> Vec<App> {
         new::cli(),
         owner::cli(),
         package::cli(),
+        packaging_info::cli(),
         pkgid::cli(),
         publish::cli(),
         read_manifest::cli(),
@@ -32,25 +36,39 @@ pub fn builtin() -> Vec<App> {
         uninstall::cli(),
         update::cli(),
         vendor::cli(),
+        verify_manifest::cli(),
         verify_project::cli(),
         version::cli(),
         yank::cli(),
-    ]
+    ];
+    builtin
+        .into_iter()
+        .filter(|cmd| !is_shadowed(config, cmd.get_name()))
+        .collect()
 }
 
-pub fn builtin_exec(cmd: &str) -> Option<fn(&mut Config, &ArgMatches<'_>) -> CliResult> {
+pub fn builtin_exec(
+    config: &Config,
+    cmd: &str,
+) -> Option<fn(&mut Config, &ArgMatches<'_>) -> CliResult> {
+    if is_shadowed(config, cmd) {
+        return None;
+    }
     let f = match cmd {
         "bench" => bench::exec,
         "build" => build::exec,
         "check" => check::exec,
         "clean" => clean::exec,
+        "daemon" => daemon::exec,
         "doc" => doc::exec,
+        "expand" => expand::exec,
         "fetch" => fetch::exec,
         "fix" => fix::exec,
         "generate-lockfile" => generate_lockfile::exec,
         "git-checkout" => git_checkout::exec,
         "init" => init::exec,
         "install" => install::exec,
+        "licenses" => licenses::exec,
         "locate-project" => locate_project::exec,
         "login" => login::exec,
         "logout" => logout::exec,
@@ -58,6 +76,7 @@ pub fn builtin_exec(cmd: &str) -> Option<fn(&mut Config, &ArgMatches<'_>) -> Cli
         "new" => new::exec,
         "owner" => owner::exec,
         "package" => package::exec,
+        "packaging-info" => packaging_info::exec,
         "pkgid" => pkgid::exec,
         "publish" => publish::exec,
         "read-manifest" => read_manifest::exec,
@@ -70,6 +89,7 @@ pub fn builtin_exec(cmd: &str) -> Option<fn(&mut Config, &ArgMatches<'_>) -> Cli
         "uninstall" => uninstall::exec,
         "update" => update::exec,
         "vendor" => vendor::exec,
+        "verify-manifest" => verify_manifest::exec,
         "verify-project" => verify_project::exec,
         "version" => version::exec,
         "yank" => yank::exec,
@@ -78,11 +98,24 @@ pub fn builtin_exec(cmd: &str) -> Option<fn(&mut Config, &ArgMatches<'_>) -> Cli
     Some(f)
 }
 
+/// Whether the built-in command `cmd` gives way to an external subcommand of
+/// the same name.
+///
+/// `cargo expand` and `cargo licenses` were provided by the `cargo-expand` and
+/// `cargo-licenses` crates before they were built in, so when those are
+/// installed, cargo doesn't claim the name, and their arguments are passed to
+/// them untouched, like to any external subcommand.
+fn is_shadowed(config: &Config, cmd: &str) -> bool {
+    matches!(cmd, "expand" | "licenses") && crate::find_external_subcommand(config, cmd).is_some()
+}
+
 pub mod bench;
 pub mod build;
 pub mod check;
 pub mod clean;
+pub mod daemon;
 pub mod doc;
+pub mod expand;
 pub mod fetch;
 pub mod fix;
 pub mod generate_lockfile;
@@ -90,6 +123,7 @@ pub mod git_checkout;
 pub mod help;
 pub mod init;
 pub mod install;
+pub mod licenses;
 pub mod locate_project;
 pub mod login;
 pub mod logout;
@@ -97,6 +131,7 @@ pub mod metadata;
 pub mod new;
 pub mod owner;
 pub mod package;
+pub mod packaging_info;
 pub mod pkgid;
 pub mod publish;
 pub mod read_manifest;
@@ -109,6 +144,7 @@ pub mod tree;
 pub mod uninstall;
 pub mod update;
 pub mod vendor;
+pub mod verify_manifest;
 pub mod verify_project;
 pub mod version;
 pub mod yank;
@@ -1,6 +1,7 @@
 use crate::command_prelude::*;
 
-use cargo::ops::{self, PackageOpts};
+use cargo::ops::{self, ListFormat, PackageOpts};
+use cargo::util::compression::CrateCompression;
 
 pub fn cli() -> App {
     subcommand("package")
@@ -13,6 +14,15 @@ pub fn cli() -> App {
             )
             .short("l"),
         )
+        .arg(
+            opt(
+                "message-format",
+                "The format of the list of files [possible values: plain, json]",
+            )
+            .value_name("FMT")
+            .possible_values(&["plain", "json"])
+            .requires("list"),
+        )
         .arg(opt(
             "no-verify",
             "Don't verify the contents by building them",
@@ -29,25 +39,34 @@ pub fn cli() -> App {
         .arg_target_dir()
         .arg_features()
         .arg_manifest_path()
+        .arg_path_dep_version()
         .arg_jobs()
         .after_help("Run `cargo help package` for more detailed information.\n")
 }
 
 pub fn exec(config: &mut Config, args: &ArgMatches<'_>) -> CliResult {
     let ws = args.workspace(config)?;
+    let list = if !args.is_present("list") {
+        None
+    } else if args.value_of("message-format") == Some("json") {
+        Some(ListFormat::Json)
+    } else {
+        Some(ListFormat::Plain)
+    };
     ops::package(
         &ws,
         &PackageOpts {
             config,
             verify: !args.is_present("no-verify"),
-            list: args.is_present("list"),
+            list,
             check_metadata: !args.is_present("no-metadata"),
             allow_dirty: args.is_present("allow-dirty"),
-            targets: args.targets(),
+            targets: args.targets(Some(&ws)),
             jobs: args.jobs()?,
             features: args._values_of("features"),
             all_features: args.is_present("all-features"),
             no_default_features: args.is_present("no-default-features"),
+            compression: CrateCompression::Gzip,
         },
     )?;
     Ok(())
@@ -0,0 +1,43 @@
+use crate::command_prelude::*;
+
+use cargo::ops::{self, PackagingInfoOptions};
+
+pub fn cli() -> App {
+    subcommand("packaging-info")
+        .about("Output the information needed to package a workspace for a distribution (unstable)")
+        .arg(opt("quiet", "No output printed to stdout").short("q"))
+        .arg_manifest_path()
+        .arg_package_spec_no_all(
+            "Package to report",
+            "Report all packages in the workspace",
+            "Exclude specific workspace members",
+        )
+        .arg_features()
+        .arg(
+            opt(
+                "target",
+                "Report the packages as built for the given target-triple (default host platform)",
+            )
+            .value_name("TRIPLE"),
+        )
+        .after_help("Run `cargo help packaging-info` for more detailed information.\n")
+}
+
+pub fn exec(config: &mut Config, args: &ArgMatches<'_>) -> CliResult {
+    if !config.cli_unstable().unstable_options {
+        return Err(CliError::new(
+            anyhow::format_err!("`cargo packaging-info` requires `-Z unstable-options`"),
+            101,
+        ));
+    }
+    let ws = args.workspace(config)?;
+    let opts = PackagingInfoOptions {
+        features: values(args, "features"),
+        all_features: args.is_present("all-features"),
+        no_default_features: args.is_present("no-default-features"),
+        packages: args.packages_from_flags()?,
+        target: args.value_of("target").map(|t| t.to_string()),
+    };
+    ops::packaging_info(&ws, &opts)?;
+    Ok(())
+}
@@ -19,9 +19,22 @@ pub fn cli() -> App {
         .arg_target_triple("Build for the target triple")
         .arg_target_dir()
         .arg_manifest_path()
+        .arg_path_dep_version()
         .arg_features()
         .arg_jobs()
         .arg_dry_run("Perform all checks without uploading")
+        .arg(opt(
+            "wait",
+            "Wait for the published version to be available in the index",
+        ))
+        .arg(opt(
+            "workspace",
+            "Publish all packages in the workspace, in dependency order",
+        ))
+        .arg(opt(
+            "provenance",
+            "Generate an SLSA provenance document for the package (unstable)",
+        ))
         .arg(opt("registry", "Registry to publish to").value_name("REGISTRY"))
         .after_help("Run `cargo help publish` for more detailed information.\n")
 }
@@ -29,9 +42,18 @@ pub fn cli() -> App {
 pub fn exec(config: &mut Config, args: &ArgMatches<'_>) -> CliResult {
     config.load_credentials()?;
 
-    let registry = args.registry(config)?;
+    let registry = if args.is_present("registry") {
+        args.registry(config)?
+    } else {
+        ops::publish_default_registry(config)?
+    };
     let ws = args.workspace(config)?;
     let index = args.index(config)?;
+    if args.is_present("provenance") {
+        config
+            .cli_unstable()
+            .fail_if_stable_opt("--provenance", None)?;
+    }
 
     ops::publish(
         &ws,
@@ -41,13 +63,16 @@ pub fn exec(config: &mut Config, args: &ArgMatches<'_>) -> CliResult {
             index,
             verify: !args.is_present("no-verify"),
             allow_dirty: args.is_present("allow-dirty"),
-            targets: args.targets(),
+            targets: args.targets(Some(&ws)),
             jobs: args.jobs()?,
             dry_run: args.is_present("dry-run"),
             registry,
             features: args._values_of("features"),
             all_features: args.is_present("all-features"),
             no_default_features: args.is_present("no-default-features"),
+            wait: args.is_present("wait"),
+            workspace: args.is_present("workspace"),
+            provenance: args.is_present("provenance"),
         },
     )?;
     Ok(())
@@ -1,6 +1,8 @@
 use crate::command_prelude::*;
 use anyhow::Error;
-use cargo::ops::{self, CompileFilter, FilterRule, LibRule};
+use cargo::ops::{
+    self, CompileFilter, FilterRule, LibRule, PartitionStrategy, TestFormat, TestPartition,
+};
 use cargo::util::errors;
 
 pub fn cli() -> App {
@@ -41,6 +43,45 @@ pub fn cli() -> App {
         .arg(opt("doc", "Test only this library's documentation"))
         .arg(opt("no-run", "Compile, but don't run tests"))
         .arg(opt("no-fail-fast", "Run all tests regardless of failure"))
+        .arg(
+            opt(
+                "list",
+                "List the tests of all the test targets, without running them",
+            )
+            .conflicts_with_all(&["no-run", "coverage"]),
+        )
+        .arg(
+            opt("format", "Format of the test results: human, json, junit")
+                .value_name("FMT")
+                .possible_values(&["human", "json", "junit"])
+                .default_value("human"),
+        )
+        .arg(
+            opt(
+                "timeout",
+                "Kill a test binary when one of its tests runs longer than this",
+            )
+            .value_name("DURATION"),
+        )
+        .arg(opt("partition", "Only run the tests of shard M out of N").value_name("M/N"))
+        .arg(
+            opt(
+                "partition-strategy",
+                "How to split the tests into shards: hash, time",
+            )
+            .value_name("STRATEGY")
+            .possible_values(&["hash", "time"])
+            .default_value("hash"),
+        )
+        .arg(opt("retries", "Run the failed tests again, up to N times").value_name("N"))
+        .arg(opt(
+            "no-cache",
+            "Run the test binaries which passed with the same inputs anyway",
+        ))
+        .arg(opt(
+            "coverage",
+            "Measure the code coverage of the tests, and write lcov and HTML reports",
+        ))
         .arg_package_spec(
             "Package to run tests for",
             "Test all packages in the workspace",
@@ -113,12 +154,91 @@ pub fn exec(config: &mut Config, args: &ArgMatches<'_>) -> CliResult {
         }
     }
 
-    let ops = ops::TestOptions {
+    let format = match args.value_of("format").unwrap() {
+        "json" => TestFormat::Json,
+        "junit" => TestFormat::Junit,
+        _ => TestFormat::Human,
+    };
+    if format != TestFormat::Human {
+        config.cli_unstable().fail_if_stable_opt("--format", None)?;
+    }
+
+    let timeout = args.value_of("timeout");
+    if timeout.is_some() {
+        config
+            .cli_unstable()
+            .fail_if_stable_opt("--timeout", None)?;
+    }
+    let timeout = ops::test_timeout(config, timeout)?;
+
+    let partition = args.value_of("partition");
+    if partition.is_some() || args.occurrences_of("partition-strategy") > 0 {
+        config
+            .cli_unstable()
+            .fail_if_stable_opt("--partition", None)?;
+    }
+    let strategy = match args.value_of("partition-strategy").unwrap() {
+        "time" => PartitionStrategy::Time,
+        _ => PartitionStrategy::Hash,
+    };
+    let partition = match partition {
+        Some(partition) => Some(TestPartition::parse(partition, strategy)?),
+        None => None,
+    };
+
+    if args.is_present("retries") {
+        config
+            .cli_unstable()
+            .fail_if_stable_opt("--retries", None)?;
+    }
+    let retries = args.value_of_u32("retries")?.unwrap_or(0);
+
+    let no_cache = args.is_present("no-cache");
+    if no_cache && !config.cli_unstable().test_cache {
+        return Err(CliError::new(
+            anyhow::format_err!("Usage of `--no-cache` requires `-Z test-cache`"),
+            101,
+        ));
+    }
+
+    let coverage = args.is_present("coverage");
+    if coverage {
+        config
+            .cli_unstable()
+            .fail_if_stable_opt("--coverage", None)?;
+    }
+    compile_opts.build_config.instrument_coverage = coverage;
+
+    let list = args.is_present("list");
+    if list {
+        config.cli_unstable().fail_if_stable_opt("--list", None)?;
+    }
+    if list && format == TestFormat::Junit {
+        return Err(CliError::new(
+            anyhow::format_err!("`--list` can't be used with `--format junit`"),
+            101,
+        ));
+    }
+
+    let mut ops = ops::TestOptions {
         no_run,
         no_fail_fast: args.is_present("no-fail-fast"),
         compile_opts,
+        format,
+        timeout,
+        partition,
+        retries,
+        no_cache,
+        coverage,
     };
 
+    if list {
+        ops::list_tests(&ws, &ops, &test_args)?;
+        return Ok(());
+    }
+
+    ops.compile_opts.build_config.doctest_harness_args =
+        ops::parallel_doctest_args(config, &ops, &test_args);
     let err = ops::run_tests(&ws, &ops, &test_args)?;
     match err {
         None => Ok(()),
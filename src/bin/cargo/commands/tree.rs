@@ -52,6 +52,19 @@ pub fn cli() -> App {
             )
             .short("i"),
         )
+        .arg(multi_opt(
+            "why-feature",
+            "SPEC/FEATURE",
+            "Show what enables the given feature of a package (implies -e features)",
+        ))
+        .arg(opt(
+            "feature-unification",
+            "Show packages built with features some of their dependents didn't request",
+        ))
+        .arg(opt(
+            "rename-aware",
+            "Show the name each dependency is used under in the code of its dependent",
+        ))
         .arg(Arg::with_name("no-indent").long("no-indent").hidden(true))
         .arg(
             Arg::with_name("prefix-depth")
@@ -142,7 +155,12 @@ pub fn exec(config: &mut Config, args: &ArgMatches<'_>) -> CliResult {
     };
     let target = tree::Target::from_cli(targets);
 
-    let edge_kinds = parse_edge_kinds(config, args)?;
+    let why_feature = values(args, "why-feature");
+    let mut edge_kinds = parse_edge_kinds(config, args)?;
+    let feature_unification = args.is_present("feature-unification");
+    if !why_feature.is_empty() || feature_unification {
+        edge_kinds.insert(EdgeKind::Feature);
+    }
     let graph_features = edge_kinds.contains(&EdgeKind::Feature);
 
     let packages = args.packages_from_flags()?;
@@ -192,6 +210,9 @@ subtree of the package given to -p.\n\
         target,
         edge_kinds,
         invert,
+        why_feature,
+        feature_unification,
+        rename_aware: args.is_present("rename-aware"),
         prefix,
         no_dedupe,
         duplicates: args.is_present("duplicates"),
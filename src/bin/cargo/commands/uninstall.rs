@@ -1,6 +1,8 @@
 use crate::command_prelude::*;
 
 use cargo::ops;
+use cargo::util::restricted_names::is_glob_pattern;
+use std::path::Path;
 
 pub fn cli() -> App {
     subcommand("uninstall")
@@ -10,11 +12,28 @@ pub fn cli() -> App {
         .arg_package_spec_simple("Package to uninstall")
         .arg(multi_opt("bin", "NAME", "Only uninstall the binary NAME"))
         .arg(opt("root", "Directory to uninstall packages from").value_name("DIR"))
+        .arg(
+            opt("all", "Uninstall all installed packages (unstable)")
+                .conflicts_with_all(&["spec", "package", "bin"]),
+        )
+        .arg(
+            opt("yes", "Do not ask for confirmation with --all (unstable)")
+                .short("y")
+                .requires("all"),
+        )
         .after_help("Run `cargo help uninstall` for more detailed information.\n")
 }
 
 pub fn exec(config: &mut Config, args: &ArgMatches<'_>) -> CliResult {
-    let root = args.value_of("root");
+    let local_root = if config.cli_unstable().local_install_root && !args.is_present("root") {
+        ops::project_install_root(config)?
+    } else {
+        None
+    };
+    let root = args
+        .value_of_os("root")
+        .map(Path::new)
+        .or(local_root.as_deref());
 
     if args.is_present_with_zero_values("package") {
         return Err(anyhow::anyhow!(
@@ -24,10 +43,24 @@ pub fn exec(config: &mut Config, args: &ArgMatches<'_>) -> CliResult {
         .into());
     }
 
-    let specs = args
+    let specs: Vec<&str> = args
         .values_of("spec")
         .unwrap_or_else(|| args.values_of("package").unwrap_or_default())
         .collect();
-    ops::uninstall(root, specs, &values(args, "bin"), config)?;
+    if args.is_present("all") {
+        config.cli_unstable().fail_if_stable_opt("--all", None)?;
+    }
+    if specs.iter().any(is_glob_pattern) && !config.cli_unstable().unstable_options {
+        return Err(anyhow::format_err!(
+            "glob patterns in `cargo uninstall` are unstable, \
+             pass `-Z unstable-options` to use them"
+        )
+        .into());
+    }
+    let opts = ops::UninstallOptions {
+        all: args.is_present("all"),
+        yes: args.is_present("yes"),
+    };
+    ops::uninstall(root, specs, &values(args, "bin"), &opts, config)?;
     Ok(())
 }
@@ -14,7 +14,15 @@ pub fn cli() -> App {
             "Force updating all dependencies of <name> as well",
         ))
         .arg_dry_run("Don't actually write the lockfile")
-        .arg(opt("precise", "Update a single dependency to exactly PRECISE").value_name("PRECISE"))
+        .arg(multi_opt(
+            "precise",
+            "PRECISE",
+            "Update a single dependency to exactly PRECISE",
+        ))
+        .arg(opt(
+            "allow-yanked",
+            "Allow the version given with --precise to be yanked (unstable)",
+        ))
         .arg_manifest_path()
         .after_help("Run `cargo help update` for more detailed information.\n")
 }
@@ -26,9 +34,16 @@ pub fn exec(config: &mut Config, args: &ArgMatches<'_>) -> CliResult {
         print_available_packages(&ws)?;
     }
 
+    if args.is_present("allow-yanked") {
+        config
+            .cli_unstable()
+            .fail_if_stable_opt("--allow-yanked", Some(4225))?;
+    }
+
     let update_opts = UpdateOptions {
         aggressive: args.is_present("aggressive"),
-        precise: args.value_of("precise"),
+        precise: values(args, "precise"),
+        allow_yanked: args.is_present("allow-yanked"),
         to_update: values(args, "package"),
         dry_run: args.is_present("dry-run"),
         workspace: args.is_present("workspace"),
@@ -32,6 +32,14 @@ pub fn cli() -> App {
                 .long("versioned-dirs")
                 .help("Always include version in subdir name"),
         )
+        .arg(
+            Arg::with_name("platform")
+                .long("platform")
+                .help("Only vendor the dependencies of the given target triple")
+                .value_name("TRIPLE")
+                .multiple(true)
+                .number_of_values(1),
+        )
         .arg(
             Arg::with_name("no-merge-sources")
                 .long("no-merge-sources")
@@ -108,6 +116,7 @@ https://github.com/rust-lang/cargo/issues/new
                 .unwrap_or_default()
                 .map(|s| PathBuf::from(s.to_os_string()))
                 .collect(),
+            platforms: values(args, "platform"),
         },
     )?;
     Ok(())
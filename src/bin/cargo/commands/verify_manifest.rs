@@ -0,0 +1,34 @@
+use crate::command_prelude::*;
+
+use cargo::ops::{self, VerifyManifestOptions};
+use std::process;
+
+pub fn cli() -> App {
+    subcommand("verify-manifest")
+        .about("Check the manifests of a workspace for mistakes (unstable)")
+        .arg(opt("quiet", "No output printed to stdout").short("q"))
+        .arg(opt(
+            "strict",
+            "Also report unused, deprecated and ignored keys, and targets which never match",
+        ))
+        .arg_manifest_path()
+        .after_help("Run `cargo help verify-manifest` for more detailed information.\n")
+}
+
+pub fn exec(config: &mut Config, args: &ArgMatches<'_>) -> CliResult {
+    if !config.cli_unstable().unstable_options {
+        return Err(CliError::new(
+            anyhow::format_err!("`cargo verify-manifest` requires `-Z unstable-options`"),
+            101,
+        ));
+    }
+    let opts = VerifyManifestOptions {
+        strict: args.is_present("strict"),
+    };
+    let report = ops::verify_manifest(&args.root_manifest(config)?, config, &opts)?;
+    config.shell().print_json(&report);
+    if !report.success {
+        process::exit(1)
+    }
+    Ok(())
+}
@@ -112,7 +112,7 @@ fn list_commands(config: &Config) -> BTreeSet<CommandInfo> {
         }
     }
 
-    for cmd in commands::builtin() {
+    for cmd in commands::builtin(config) {
         commands.insert(CommandInfo::BuiltIn {
             name: cmd.get_name().to_string(),
             about: cmd.p.meta.about.map(|s| s.to_string()),
@@ -139,12 +139,17 @@ fn list_aliases(config: &Config) -> Vec<String> {
     }
 }
 
-fn execute_external_subcommand(config: &Config, cmd: &str, args: &[&str]) -> CliResult {
+/// Returns the path of the external subcommand `cmd`, if there is one.
+fn find_external_subcommand(config: &Config, cmd: &str) -> Option<PathBuf> {
     let command_exe = format!("cargo-{}{}", cmd, env::consts::EXE_SUFFIX);
-    let path = search_directories(config)
+    search_directories(config)
         .iter()
         .map(|dir| dir.join(&command_exe))
-        .find(|file| is_executable(file));
+        .find(|file| is_executable(file))
+}
+
+fn execute_external_subcommand(config: &Config, cmd: &str, args: &[&str]) -> CliResult {
+    let path = find_external_subcommand(config, cmd);
     let command = match path {
         Some(command) => command,
         None => {
@@ -161,11 +166,17 @@ fn execute_external_subcommand(config: &Config, cmd: &str, args: &[&str]) -> Cli
     };
 
     let cargo_exe = config.cargo_exe()?;
-    let err = match util::process(&command)
-        .env(cargo::CARGO_ENV, cargo_exe)
-        .args(args)
-        .exec_replace()
-    {
+    let mut process = util::process(&command);
+    process.env(cargo::CARGO_ENV, cargo_exe).args(args);
+    // Kept until the subcommand exits.
+    let _context = if config.cli_unstable().subcommand_context {
+        let context = cargo::ops::SubcommandContext::new(config, args)?;
+        process.env(cargo::ops::SUBCOMMAND_CONTEXT_ENV, context.path());
+        Some(context)
+    } else {
+        None
+    };
+    let err = match process.exec_replace() {
         Ok(()) => return Ok(()),
         Err(e) => e,
     };
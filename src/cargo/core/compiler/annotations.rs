@@ -0,0 +1,222 @@
+//! Rendering of rustc diagnostics as the annotations of CI services, for
+//! `--message-format github`, `teamcity` and `template=...`.
+//!
+//! The diagnostics are still rendered for humans on stderr, and each one with
+//! a primary span is also printed to stdout as a line the CI service picks up
+//! to annotate the code, or as the user-provided template. Diagnostics without
+//! a location, like the "aborting due to previous error" summaries, aren't
+//! annotated.
+
+use serde::Deserialize;
+
+use crate::core::PackageId;
+use crate::util::errors::CargoResult;
+use crate::util::interning::InternedString;
+
+/// The placeholders which can be used in templates.
+const PLACEHOLDERS: &[&str] = &[
+    "level",
+    "file",
+    "line",
+    "column",
+    "end_line",
+    "end_column",
+    "message",
+    "code",
+    "package",
+    "rendered",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnnotationFormat {
+    /// GitHub Actions workflow commands, like `::error file=...::message`.
+    GitHub,
+    /// TeamCity service messages, like `##teamcity[message ...]`.
+    TeamCity,
+    /// A template with placeholders like `{file}` and `{line}`.
+    Template(InternedString),
+}
+
+impl AnnotationFormat {
+    /// Parses the `template=...` message format, checking that only known
+    /// placeholders are used.
+    pub fn template(template: &str) -> CargoResult<AnnotationFormat> {
+        expand(template, |name| {
+            if PLACEHOLDERS.contains(&name) {
+                Some(String::new())
+            } else {
+                None
+            }
+        })?;
+        Ok(AnnotationFormat::Template(InternedString::new(template)))
+    }
+}
+
+#[derive(Deserialize)]
+struct Diagnostic {
+    message: String,
+    code: Option<DiagnosticCode>,
+    level: String,
+    spans: Vec<DiagnosticSpan>,
+    rendered: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DiagnosticCode {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct DiagnosticSpan {
+    file_name: String,
+    line_start: usize,
+    line_end: usize,
+    column_start: usize,
+    column_end: usize,
+    is_primary: bool,
+}
+
+/// Renders the JSON `diagnostic` of rustc as an annotation, or returns `None`
+/// if it isn't a diagnostic with a location.
+pub fn render(format: AnnotationFormat, diagnostic: &str, package_id: PackageId) -> Option<String> {
+    let diagnostic: Diagnostic = serde_json::from_str(diagnostic).ok()?;
+    let span = diagnostic.spans.iter().find(|span| span.is_primary)?;
+    let rendered = diagnostic
+        .rendered
+        .as_ref()
+        .and_then(|rendered| strip_ansi_escapes::strip(rendered).ok())
+        .map(|rendered| String::from_utf8_lossy(&rendered).trim_end().to_string())
+        .unwrap_or_else(|| diagnostic.message.clone());
+    let annotation = match format {
+        AnnotationFormat::GitHub => {
+            let level = match diagnostic.level.as_str() {
+                "error" | "error: internal compiler error" => "error",
+                "warning" => "warning",
+                _ => "notice",
+            };
+            format!(
+                "::{} file={},line={},endLine={},col={},endColumn={},title={}::{}",
+                level,
+                github_property(&span.file_name),
+                span.line_start,
+                span.line_end,
+                span.column_start,
+                span.column_end,
+                github_property(&diagnostic.message),
+                github_data(&rendered),
+            )
+        }
+        AnnotationFormat::TeamCity => {
+            let status = match diagnostic.level.as_str() {
+                "error" | "error: internal compiler error" => "ERROR",
+                "warning" => "WARNING",
+                _ => "NORMAL",
+            };
+            format!(
+                "##teamcity[message text='{}' errorDetails='{}' status='{}']",
+                teamcity_value(&format!(
+                    "{}:{}:{}: {}",
+                    span.file_name, span.line_start, span.column_start, diagnostic.message
+                )),
+                teamcity_value(&rendered),
+                status,
+            )
+        }
+        AnnotationFormat::Template(template) => expand(&template, |name| {
+            let value = match name {
+                "level" => diagnostic.level.clone(),
+                "file" => span.file_name.clone(),
+                "line" => span.line_start.to_string(),
+                "column" => span.column_start.to_string(),
+                "end_line" => span.line_end.to_string(),
+                "end_column" => span.column_end.to_string(),
+                "message" => diagnostic.message.clone(),
+                "code" => diagnostic
+                    .code
+                    .as_ref()
+                    .map(|code| code.code.clone())
+                    .unwrap_or_default(),
+                "package" => package_id.name().to_string(),
+                "rendered" => rendered.clone(),
+                _ => return None,
+            };
+            Some(value)
+        })
+        .ok()?,
+    };
+    Some(annotation)
+}
+
+/// Replaces the `{name}` placeholders of `template` with `value(name)`, with
+/// `{{` and `}}` standing for literal braces.
+fn expand(template: &str, value: impl Fn(&str) -> Option<String>) -> CargoResult<String> {
+    let mut expanded = String::new();
+    let mut rest = template;
+    while let Some(i) = rest.find(&['{', '}'][..]) {
+        expanded.push_str(&rest[..i]);
+        let tail = &rest[i..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            expanded.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+        let end = match (tail.starts_with('{'), tail.find('}')) {
+            (true, Some(end)) => end,
+            _ => anyhow::bail!(
+                "unmatched `{}` in the message format template `{}`, \
+                 use `{}{}` for a literal brace",
+                &tail[..1],
+                template,
+                &tail[..1],
+                &tail[..1]
+            ),
+        };
+        let name = &tail[1..end];
+        match value(name) {
+            Some(value) => expanded.push_str(&value),
+            None => anyhow::bail!(
+                "unknown placeholder `{{{}}}` in the message format template `{}`, \
+                 the supported placeholders are {}",
+                name,
+                template,
+                PLACEHOLDERS
+                    .iter()
+                    .map(|p| format!("`{{{}}}`", p))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+        rest = &tail[end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Escapes the message of a GitHub workflow command.
+fn github_data(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escapes a property of a GitHub workflow command.
+fn github_property(s: &str) -> String {
+    github_data(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// Escapes a value of a TeamCity service message.
+fn teamcity_value(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '|' => escaped.push_str("||"),
+            '\'' => escaped.push_str("|'"),
+            '[' => escaped.push_str("|["),
+            ']' => escaped.push_str("|]"),
+            '\n' => escaped.push_str("|n"),
+            '\r' => escaped.push_str("|r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
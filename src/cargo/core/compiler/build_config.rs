@@ -1,10 +1,11 @@
-use crate::core::compiler::CompileKind;
+use crate::core::compiler::{AnnotationFormat, CompileKind};
 use crate::util::interning::InternedString;
 use crate::util::ProcessBuilder;
 use crate::util::{CargoResult, Config, RustfixDiagnosticServer};
 use anyhow::bail;
 use serde::ser;
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 /// Configuration information for a rustc build.
@@ -12,6 +13,10 @@ use std::path::PathBuf;
 pub struct BuildConfig {
     /// The requested kind of compilation for this session
     pub requested_kinds: Vec<CompileKind>,
+    /// The kinds of compilation of the packages set by
+    /// `build.package-targets`, used instead of `requested_kinds` for these
+    /// packages. This is empty if `--target` is passed.
+    pub package_kinds: HashMap<InternedString, CompileKind>,
     /// Number of rustc jobs to run in parallel.
     pub jobs: u32,
     /// Build profile
@@ -26,6 +31,19 @@ pub struct BuildConfig {
     pub build_plan: bool,
     /// Output the unit graph to stdout instead of actually compiling.
     pub unit_graph: bool,
+    /// Write a CycloneDX SBOM next to each final artifact, for
+    /// `cargo build --sbom`.
+    pub sbom: bool,
+    /// Write the database of the rustc invocations of the build, for
+    /// `cargo build --rustc-invocations`.
+    pub rustc_invocations: bool,
+    /// The arguments for the test harness of the doc tests, which are run as
+    /// part of the build with `-Z parallel-doctests`, or `None` if they are
+    /// run after it.
+    pub doctest_harness_args: Option<Vec<String>>,
+    /// Instrument the crates of the workspace members for code coverage,
+    /// with `-C instrument-coverage`.
+    pub instrument_coverage: bool,
     /// An optional override of the rustc process for primary units
     pub primary_unit_rustc: Option<ProcessBuilder>,
     /// A thread used by `cargo fix` to receive messages on a socket regarding
@@ -45,6 +63,7 @@ impl BuildConfig {
     ///
     /// * `build.jobs`
     /// * `build.target`
+    /// * `build.package-targets`
     /// * `target.$target.ar`
     /// * `target.$target.linker`
     /// * `target.$target.libfoo.metadata`
@@ -56,6 +75,11 @@ impl BuildConfig {
     ) -> CargoResult<BuildConfig> {
         let cfg = config.build_config()?;
         let requested_kinds = CompileKind::from_requested_targets(config, requested_targets)?;
+        let package_kinds = if requested_targets.is_empty() {
+            CompileKind::package_targets(config)?
+        } else {
+            HashMap::new()
+        };
         if jobs == Some(0) {
             anyhow::bail!("jobs must be at least 1")
         }
@@ -70,6 +94,7 @@ impl BuildConfig {
 
         Ok(BuildConfig {
             requested_kinds,
+            package_kinds,
             jobs,
             requested_profile: InternedString::new("dev"),
             mode,
@@ -77,6 +102,10 @@ impl BuildConfig {
             force_rebuild: false,
             build_plan: false,
             unit_graph: false,
+            sbom: false,
+            rustc_invocations: false,
+            doctest_harness_args: None,
+            instrument_coverage: false,
             primary_unit_rustc: None,
             rustfix_diagnostic_server: RefCell::new(None),
             export_dir: None,
@@ -99,6 +128,20 @@ impl BuildConfig {
             _ => bail!("only one `--target` argument is supported"),
         }
     }
+
+    /// All kinds of compilation in this session, which are the requested
+    /// kinds followed by the kinds set by `build.package-targets`.
+    pub fn all_kinds(&self) -> Vec<CompileKind> {
+        let mut kinds = self.requested_kinds.clone();
+        let mut package_kinds: Vec<_> = self.package_kinds.values().copied().collect();
+        package_kinds.sort();
+        for kind in package_kinds {
+            if !kinds.contains(&kind) {
+                kinds.push(kind);
+            }
+        }
+        kinds
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -116,6 +159,9 @@ pub enum MessageFormat {
         ansi: bool,
     },
     Short,
+    /// Human-readable messages on stderr, and the diagnostics of rustc as
+    /// annotations for a CI service on stdout.
+    Annotations(AnnotationFormat),
 }
 
 /// The general "mode" for what to do.
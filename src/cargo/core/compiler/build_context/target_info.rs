@@ -1,7 +1,7 @@
 use crate::core::compiler::{BuildOutput, CompileKind, CompileMode, CompileTarget, CrateType};
 use crate::core::{Dependency, Target, TargetKind, Workspace};
 use crate::util::config::{Config, StringList, TargetConfig};
-use crate::util::{CargoResult, CargoResultExt, ProcessBuilder, Rustc};
+use crate::util::{self, paths, CargoResult, CargoResultExt, ProcessBuilder, Rustc};
 use cargo_platform::{Cfg, CfgExpr};
 use std::cell::RefCell;
 use std::collections::hash_map::{Entry, HashMap};
@@ -659,6 +659,9 @@ pub struct RustcTargetData {
     /// empty if the `--target` flag is not passed.
     target_config: HashMap<CompileTarget, TargetConfig>,
     target_info: HashMap<CompileTarget, TargetInfo>,
+    /// Hashes of the contents of the JSON target specifications of custom
+    /// targets, so that editing a specification rebuilds everything for it.
+    target_spec_hash: HashMap<CompileTarget, u64>,
 }
 
 impl RustcTargetData {
@@ -672,8 +675,15 @@ impl RustcTargetData {
         let host_info = TargetInfo::new(config, requested_kinds, &rustc, CompileKind::Host)?;
         let mut target_config = HashMap::new();
         let mut target_info = HashMap::new();
+        let mut target_spec_hash = HashMap::new();
         for kind in requested_kinds {
             if let CompileKind::Target(target) = *kind {
+                if let Some(path) = target.spec_path() {
+                    let contents = paths::read_bytes(path).chain_err(|| {
+                        format!("failed to read target specification `{}`", path.display())
+                    })?;
+                    target_spec_hash.insert(target, util::hash_u64(&contents));
+                }
                 let tcfg = config.target_cfg_triple(target.short_name())?;
                 target_config.insert(target, tcfg);
                 target_info.insert(
@@ -698,11 +708,21 @@ impl RustcTargetData {
             rustc,
             target_config,
             target_info,
+            target_spec_hash,
             host_config,
             host_info,
         })
     }
 
+    /// Returns the hash of the contents of the JSON target specification for
+    /// the given kind, if it is a custom target.
+    pub fn target_spec_hash(&self, kind: CompileKind) -> Option<u64> {
+        match kind {
+            CompileKind::Host => None,
+            CompileKind::Target(s) => self.target_spec_hash.get(&s).copied(),
+        }
+    }
+
     /// Returns a "short" name for the given kind, suitable for keying off
     /// configuration in Cargo or presenting to users.
     pub fn short_name<'a>(&'a self, kind: &'a CompileKind) -> &'a str {
@@ -5,27 +5,46 @@
 //! shared with an external build system. Each Invocation in the BuildPlan comprises a single
 //! subprocess and defines the build environment, the outputs produced by the subprocess, and the
 //! dependencies on other Invocations.
-
-use std::collections::BTreeMap;
+//!
+//! The serialized plan is versioned with `PLAN_VERSION`, which has to be
+//! bumped whenever a field is removed or changes meaning, so that the tools
+//! replaying the plan can tell whether they understand it. Adding fields
+//! doesn't require a new version. The format is documented in the "Build-plan"
+//! section of `unstable.md`.
+
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::path::PathBuf;
 
 use serde::Serialize;
 
 use super::context::OutputFile;
-use super::{CompileKind, CompileMode, Context, Unit};
-use crate::core::TargetKind;
+use super::{fingerprint, CompileKind, CompileMode, Context, Unit};
+use crate::core::{PackageId, TargetKind};
+use crate::sources::PathSource;
 use crate::util::{internal, CargoResult, Config, ProcessBuilder};
 
+/// The version of the serialized build plan.
+const PLAN_VERSION: u32 = 1;
+
 #[derive(Debug, Serialize)]
 struct Invocation {
     package_name: String,
     package_version: semver::Version,
+    package_id: PackageId,
+    target_name: String,
     target_kind: TargetKind,
     kind: CompileKind,
     compile_mode: CompileMode,
     deps: Vec<usize>,
+    /// The files the invocation reads, from the dep-info of a previous build
+    /// if there is one, or else all the files of the package.
+    inputs: Vec<PathBuf>,
     outputs: Vec<PathBuf>,
     links: BTreeMap<PathBuf, PathBuf>,
+    /// Where rustc writes the dep-info of the invocation, which lists the
+    /// files it actually read.
+    dep_info: Option<PathBuf>,
     program: String,
     args: Vec<String>,
     env: BTreeMap<String, String>,
@@ -35,27 +54,39 @@ struct Invocation {
 #[derive(Debug)]
 pub struct BuildPlan {
     invocation_map: BTreeMap<String, usize>,
+    /// The files of the packages of the invocations, listed once per package.
+    package_files: HashMap<PackageId, Vec<PathBuf>>,
     plan: SerializedBuildPlan,
 }
 
 #[derive(Debug, Serialize)]
 struct SerializedBuildPlan {
+    version: u32,
     invocations: Vec<Invocation>,
     inputs: Vec<PathBuf>,
 }
 
 impl Invocation {
-    pub fn new(unit: &Unit, deps: Vec<usize>) -> Invocation {
+    pub fn new(
+        unit: &Unit,
+        deps: Vec<usize>,
+        inputs: Vec<PathBuf>,
+        dep_info: Option<PathBuf>,
+    ) -> Invocation {
         let id = unit.pkg.package_id();
         Invocation {
             package_name: id.name().to_string(),
             package_version: id.version().clone(),
+            package_id: id,
+            target_name: unit.target.name().to_string(),
             kind: unit.kind,
             target_kind: unit.target.kind().clone(),
             compile_mode: unit.mode,
             deps,
+            inputs,
             outputs: Vec::new(),
             links: BTreeMap::new(),
+            dep_info,
             program: String::new(),
             args: Vec::new(),
             env: BTreeMap::new(),
@@ -105,6 +136,7 @@ impl BuildPlan {
     pub fn new() -> BuildPlan {
         BuildPlan {
             invocation_map: BTreeMap::new(),
+            package_files: HashMap::new(),
             plan: SerializedBuildPlan::new(),
         }
     }
@@ -117,11 +149,45 @@ impl BuildPlan {
             .iter()
             .map(|dep| self.invocation_map[&dep.unit.buildkey()])
             .collect();
-        let invocation = Invocation::new(unit, deps);
+        let inputs = self.inputs(cx, unit)?;
+        // Build scripts and rustdoc don't emit dep-info.
+        let dep_info =
+            if unit.mode.is_run_custom_build() || unit.mode.is_doc() || unit.mode.is_doc_test() {
+                None
+            } else {
+                Some(super::rustc_dep_info_loc(cx, unit))
+            };
+        let invocation = Invocation::new(unit, deps, inputs, dep_info);
         self.plan.invocations.push(invocation);
         Ok(())
     }
 
+    /// Returns the files `unit` reads. They're only known precisely once it
+    /// has been compiled, so without the dep-info of a previous build all the
+    /// files of its package are returned.
+    fn inputs(&mut self, cx: &Context<'_, '_>, unit: &Unit) -> CargoResult<Vec<PathBuf>> {
+        if !unit.mode.is_run_custom_build() {
+            let dep_info_loc = fingerprint::dep_info_loc(cx, unit);
+            if let Some(dep_info) =
+                fingerprint::parse_dep_info(unit.pkg.root(), cx.files().host_root(), &dep_info_loc)?
+            {
+                let inputs: BTreeSet<_> = dep_info.files.into_iter().collect();
+                return Ok(inputs.into_iter().collect());
+            }
+        }
+        let id = unit.pkg.package_id();
+        let files = match self.package_files.entry(id) {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => {
+                let source = PathSource::new(unit.pkg.root(), id.source_id(), cx.bcx.config);
+                let mut files = source.list_files(&unit.pkg)?;
+                files.sort();
+                e.insert(files)
+            }
+        };
+        Ok(files.clone())
+    }
+
     pub fn update(
         &mut self,
         invocation_name: &str,
@@ -155,6 +221,7 @@ impl BuildPlan {
 impl SerializedBuildPlan {
     pub fn new() -> SerializedBuildPlan {
         SerializedBuildPlan {
+            version: PLAN_VERSION,
             invocations: Vec::new(),
             inputs: Vec::new(),
         }
@@ -10,6 +10,8 @@ use super::BuildContext;
 use crate::core::compiler::CompileKind;
 use crate::core::compiler::Unit;
 use crate::core::{Edition, Package, PackageId};
+use crate::ops::VcsInfo;
+use crate::util::errors::ProcessError;
 use crate::util::{self, config, join_paths, process, CargoResult, Config, ProcessBuilder};
 
 /// Structure with enough information to run `rustdoc --test`.
@@ -24,6 +26,16 @@ pub struct Doctest {
     pub linker: Option<PathBuf>,
 }
 
+/// The output of the doc tests of a unit which ran during the build, with
+/// `-Z parallel-doctests`.
+pub struct DoctestOutput {
+    /// The lines printed by rustdoc in order, and whether each one is from
+    /// stdout.
+    pub lines: Vec<(bool, String)>,
+    /// Why the doc tests failed, if they did.
+    pub error: Option<ProcessError>,
+}
+
 /// A structure returning the result of a compilation.
 pub struct Compilation<'cfg> {
     /// An array of all tests created during this compilation.
@@ -52,6 +64,9 @@ pub struct Compilation<'cfg> {
     /// May be for the host or for a specific target.
     pub deps_output: HashMap<CompileKind, PathBuf>,
 
+    /// The path to the sysroot of the compiler used.
+    pub sysroot: PathBuf,
+
     /// The path to the host libdir for the compiler used
     sysroot_host_libdir: PathBuf,
 
@@ -65,6 +80,10 @@ pub struct Compilation<'cfg> {
     /// Libraries to test with rustdoc.
     pub to_doc_test: Vec<Doctest>,
 
+    /// The output of the doc tests which ran during the build, with
+    /// `-Z parallel-doctests`, by package and kind.
+    pub doctest_outputs: HashMap<(PackageId, CompileKind), DoctestOutput>,
+
     /// Features per package enabled during this compilation.
     pub cfgs: HashMap<PackageId, HashSet<String>>,
 
@@ -107,6 +126,7 @@ impl<'cfg> Compilation<'cfg> {
             native_dirs: BTreeSet::new(),
             root_output: HashMap::new(),
             deps_output: HashMap::new(),
+            sysroot: bcx.target_data.info(CompileKind::Host).sysroot.clone(),
             sysroot_host_libdir: bcx
                 .target_data
                 .info(CompileKind::Host)
@@ -114,7 +134,7 @@ impl<'cfg> Compilation<'cfg> {
                 .clone(),
             sysroot_target_libdir: bcx
                 .build_config
-                .requested_kinds
+                .all_kinds()
                 .iter()
                 .chain(Some(&CompileKind::Host))
                 .map(|kind| {
@@ -129,6 +149,7 @@ impl<'cfg> Compilation<'cfg> {
             cdylibs: Vec::new(),
             extra_env: HashMap::new(),
             to_doc_test: Vec::new(),
+            doctest_outputs: HashMap::new(),
             cfgs: HashMap::new(),
             rustdocflags: HashMap::new(),
             config: bcx.config,
@@ -138,7 +159,7 @@ impl<'cfg> Compilation<'cfg> {
             primary_rustc_process,
             target_runners: bcx
                 .build_config
-                .requested_kinds
+                .all_kinds()
                 .iter()
                 .chain(Some(&CompileKind::Host))
                 .map(|kind| Ok((*kind, target_runner(bcx, *kind)?)))
@@ -307,6 +328,14 @@ impl<'cfg> Compilation<'cfg> {
             )
             .env("CARGO_PKG_AUTHORS", &pkg.authors().join(":"))
             .cwd(pkg.root());
+        // Packages extracted from a `.crate` file know which commit they were
+        // created from.
+        if let Some(VcsInfo { git }) = VcsInfo::load(pkg.root()) {
+            cmd.env("CARGO_VCS_SHA1", &git.sha1)
+                .env("CARGO_VCS_TAG", git.tag.as_deref().unwrap_or_default())
+                .env("CARGO_VCS_URL", git.url.as_deref().unwrap_or_default())
+                .env("CARGO_VCS_DIRTY", git.dirty.to_string());
+        }
         Ok(cmd)
     }
 }
@@ -1,10 +1,11 @@
 use crate::core::Target;
+use crate::util::config::ConfigRelativePath;
 use crate::util::errors::{CargoResult, CargoResultExt};
 use crate::util::interning::InternedString;
 use crate::util::Config;
 use anyhow::bail;
 use serde::Serialize;
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
 use std::path::Path;
 
 /// Indicator for how a unit is being compiled.
@@ -65,19 +66,38 @@ impl CompileKind {
                 .collect());
         }
         let kind = match &config.build_config()?.target {
-            Some(val) => {
-                let value = if val.raw_value().ends_with(".json") {
-                    let path = val.clone().resolve_path(config);
-                    path.to_str().expect("must be utf-8 in toml").to_string()
-                } else {
-                    val.raw_value().to_string()
-                };
-                CompileKind::Target(CompileTarget::new(&value)?)
-            }
+            Some(val) => CompileKind::from_config_value(config, val)?,
             None => CompileKind::Host,
         };
         Ok(vec![kind])
     }
+
+    /// Returns the targets of packages set by `build.package-targets`, by
+    /// package name.
+    pub fn package_targets(config: &Config) -> CargoResult<HashMap<InternedString, CompileKind>> {
+        let mut kinds = HashMap::new();
+        if let Some(targets) = &config.build_config()?.package_targets {
+            for (name, val) in targets {
+                kinds.insert(
+                    InternedString::new(name),
+                    CompileKind::from_config_value(config, val)?,
+                );
+            }
+        }
+        Ok(kinds)
+    }
+
+    /// Creates a `CompileKind` from a target in configuration, where paths to
+    /// JSON target specifications are relative to the configuration.
+    fn from_config_value(config: &Config, val: &ConfigRelativePath) -> CargoResult<CompileKind> {
+        let value = if val.raw_value().ends_with(".json") {
+            let path = val.clone().resolve_path(config);
+            path.to_str().expect("must be utf-8 in toml").to_string()
+        } else {
+            val.raw_value().to_string()
+        };
+        Ok(CompileKind::Target(CompileTarget::new(&value)?))
+    }
 }
 
 impl serde::ser::Serialize for CompileKind {
@@ -148,6 +168,16 @@ impl CompileTarget {
         &self.name
     }
 
+    /// Returns the path of the JSON target specification, if this is a custom
+    /// target.
+    pub fn spec_path(&self) -> Option<&Path> {
+        if self.name.ends_with(".json") {
+            Some(Path::new(self.name.as_str()))
+        } else {
+            None
+        }
+    }
+
     /// Returns a "short" version of the target name suitable for usage within
     /// Cargo for configuration and such.
     ///
@@ -1,4 +1,5 @@
 use std::collections::{BTreeSet, HashMap, HashSet};
+use std::mem;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
@@ -13,11 +14,15 @@ use crate::util::profile;
 use super::build_plan::BuildPlan;
 use super::custom_build::{self, BuildDeps, BuildScriptOutputs, BuildScripts};
 use super::fingerprint::Fingerprint;
+use super::fs_watcher::FsWatcher;
 use super::job_queue::JobQueue;
 use super::layout::Layout;
 use super::lto::Lto;
+use super::sandbox::{BuildScriptSandbox, NetworkSandbox};
 use super::unit_graph::UnitDep;
-use super::{BuildContext, Compilation, CompileKind, CompileMode, Executor, FileFlavor};
+use super::{
+    BuildContext, Compilation, CompileKind, CompileMode, DoctestOutput, Executor, FileFlavor,
+};
 
 mod compilation_files;
 use self::compilation_files::CompilationFiles;
@@ -31,6 +36,9 @@ pub struct Context<'a, 'cfg> {
     pub compilation: Compilation<'cfg>,
     /// Output from build scripts, updated after each build script runs.
     pub build_script_outputs: Arc<Mutex<BuildScriptOutputs>>,
+    /// Output from the doc tests which run during the build, with
+    /// `-Z parallel-doctests`.
+    pub doctest_outputs: Arc<Mutex<HashMap<(PackageId, CompileKind), DoctestOutput>>>,
     /// Dependencies (like rerun-if-changed) declared by a build script.
     /// This is *only* populated from the output from previous runs.
     /// If the build script hasn't ever been run, then it must be run.
@@ -39,6 +47,12 @@ pub struct Context<'a, 'cfg> {
     pub fingerprints: HashMap<Unit, Arc<Fingerprint>>,
     /// Cache of file mtimes to reduce filesystem hits.
     pub mtime_cache: HashMap<PathBuf, FileTime>,
+    /// The watcher telling which files changed, with `-Z watchman`.
+    pub fs_watcher: Option<FsWatcher>,
+    /// The network sandbox of `-Z network-sandbox`, if enabled.
+    pub network_sandbox: Option<NetworkSandbox>,
+    /// The write restrictions of `-Z build-script-sandbox`, if enabled.
+    pub build_script_sandbox: Option<BuildScriptSandbox>,
     /// A set used to track which units have been compiled.
     /// A unit may appear in the job graph multiple times as a dependency of
     /// multiple packages, but it only needs to run once.
@@ -92,6 +106,15 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
         let jobserver = match bcx.config.jobserver_from_env() {
             Some(c) => c.clone(),
             None => {
+                if let Some(err) = bcx.config.jobserver_error() {
+                    bcx.config.shell().warn(format!(
+                        "failed to connect to the jobserver of the environment, \
+                         using {} jobs instead: {}\n\
+                         If Cargo is run by `make`, the line of the Makefile \
+                         running it may need a `+` prefix.",
+                        bcx.build_config.jobs, err
+                    ))?;
+                }
                 let client = Client::new(bcx.build_config.jobs as usize)
                     .chain_err(|| "failed to create jobserver")?;
                 client.acquire_raw()?;
@@ -100,13 +123,22 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
         };
 
         let pipelining = bcx.config.build_config()?.pipelining.unwrap_or(true);
+        // The clock has to be taken before any file is checked, so that the
+        // changes made during the build are seen by the next one.
+        let fs_watcher = FsWatcher::start(bcx)?;
+        let network_sandbox = NetworkSandbox::new(bcx)?;
+        let build_script_sandbox = BuildScriptSandbox::new(bcx)?;
 
         Ok(Self {
             bcx,
             compilation: Compilation::new(bcx)?,
             build_script_outputs: Arc::new(Mutex::new(BuildScriptOutputs::default())),
+            doctest_outputs: Arc::new(Mutex::new(HashMap::new())),
             fingerprints: HashMap::new(),
             mtime_cache: HashMap::new(),
+            fs_watcher,
+            network_sandbox,
+            build_script_sandbox,
             compiled: HashSet::new(),
             build_scripts: HashMap::new(),
             build_explicit_deps: HashMap::new(),
@@ -244,6 +276,13 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
             }
 
             super::output_depinfo(&mut self, unit)?;
+            if self.bcx.build_config.sbom {
+                super::output_sbom(&mut self, unit)?;
+            }
+        }
+
+        if self.bcx.build_config.rustc_invocations && !build_plan {
+            super::output_rustc_invocations(&self)?;
         }
 
         for (pkg_id, output) in self.build_script_outputs.lock().unwrap().iter() {
@@ -263,6 +302,7 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
                 self.compilation.native_dirs.insert(dir.clone());
             }
         }
+        self.compilation.doctest_outputs = mem::take(&mut *self.doctest_outputs.lock().unwrap());
         Ok(self.compilation)
     }
 
@@ -287,8 +327,8 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
         let dest = self.bcx.profiles.get_dir_name();
         let host_layout = Layout::new(self.bcx.ws, None, &dest)?;
         let mut targets = HashMap::new();
-        for kind in self.bcx.build_config.requested_kinds.iter() {
-            if let CompileKind::Target(target) = *kind {
+        for kind in self.bcx.build_config.all_kinds() {
+            if let CompileKind::Target(target) = kind {
                 let layout = Layout::new(self.bcx.ws, Some(target), &dest)?;
                 targets.insert(target, layout);
             }
@@ -319,12 +359,12 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
         }
 
         let files = self.files.as_ref().unwrap();
-        for &kind in self
+        for kind in self
             .bcx
             .build_config
-            .requested_kinds
-            .iter()
-            .chain(Some(&CompileKind::Host))
+            .all_kinds()
+            .into_iter()
+            .chain(Some(CompileKind::Host))
         {
             let layout = files.layout(kind);
             self.compilation
@@ -2,9 +2,11 @@ use super::job::{Freshness, Job, Work};
 use super::{fingerprint, Context, LinkType, Unit};
 use crate::core::compiler::context::Metadata;
 use crate::core::compiler::job_queue::JobState;
+use crate::core::compiler::CompileKind;
 use crate::core::{profiles::ProfileRoot, PackageId};
 use crate::util::errors::{CargoResult, CargoResultExt};
 use crate::util::machine_message::{self, Message};
+use crate::util::restricted_names::is_glob_pattern;
 use crate::util::{self, internal, paths, profile};
 use cargo_platform::Cfg;
 use std::collections::hash_map::{Entry, HashMap};
@@ -195,8 +197,22 @@ fn build_work(cx: &mut Context<'_, '_>, unit: &Unit) -> CargoResult<Job> {
         )
         .env("HOST", &bcx.host_triple())
         .env("RUSTC", &bcx.rustc().path)
-        .env("RUSTDOC", &*bcx.config.rustdoc()?)
-        .inherit_jobserver(&cx.jobserver);
+        .env("RUSTDOC", bcx.config.rustdoc()?);
+
+    // Build scripts often run `make`, which only looks at `MAKEFLAGS`. If it
+    // describes the jobserver Cargo got from its own `make`, the script keeps
+    // it along with the other flags of `make`, otherwise the jobserver of
+    // Cargo is appended to the flags already in it.
+    match bcx.config.jobserver_env_var() {
+        Some("MAKEFLAGS") | Some("MFLAGS") => cmd.inherit_jobserver(&cx.jobserver),
+        _ => cmd.inherit_jobserver_for_make(&cx.jobserver),
+    };
+    if let Some(sandbox) = &cx.network_sandbox {
+        sandbox.apply_to_build_script(unit, &mut cmd);
+    }
+    if let Some(sandbox) = &cx.build_script_sandbox {
+        sandbox.apply(unit, &script_out_dir, &mut cmd);
+    }
 
     if let Some(linker) = &bcx.target_data.target_config(unit.kind).linker {
         cmd.env(
@@ -205,6 +221,12 @@ fn build_work(cx: &mut Context<'_, '_>, unit: &Unit) -> CargoResult<Job> {
         );
     }
 
+    if let CompileKind::Target(target) = unit.kind {
+        if let Some(spec) = target.spec_path() {
+            cmd.env("TARGET_SPEC_PATH", spec);
+        }
+    }
+
     if let Some(links) = unit.pkg.manifest().links() {
         cmd.env("CARGO_MANIFEST_LINKS", links);
     }
@@ -286,6 +308,7 @@ fn build_work(cx: &mut Context<'_, '_>, unit: &Unit) -> CargoResult<Job> {
     let extra_verbose = bcx.config.extra_verbose();
     let (prev_output, prev_script_out_dir) = prev_build_output(cx, unit);
     let metadata_hash = cx.get_run_build_script_metadata(unit);
+    let denied_writes = cmd.get_denied_writes().cloned();
 
     paths::create_dir_all(&script_dir)?;
     paths::create_dir_all(&script_out_dir)?;
@@ -368,7 +391,26 @@ fn build_work(cx: &mut Context<'_, '_>, unit: &Unit) -> CargoResult<Job> {
                 },
                 true,
             )
-            .chain_err(|| format!("failed to run custom build command for `{}`", pkg_name));
+            .chain_err(|| {
+                let denied = denied_writes
+                    .as_ref()
+                    .map(|d| d.search())
+                    .unwrap_or_default();
+                let mut msg = format!("failed to run custom build command for `{}`", pkg_name);
+                if !denied.is_empty() {
+                    let denied: Vec<_> = denied
+                        .iter()
+                        .map(|path| format!("`{}`", path.display()))
+                        .collect();
+                    msg.push_str(&format!(
+                        "\nThe sandbox denied writing to {}: build scripts may only \
+                         write to their `OUT_DIR`, their package and the paths of \
+                         `sandbox.writable-paths`.",
+                        denied.join(", ")
+                    ));
+                }
+                msg
+            });
 
         if let Err(error) = output {
             insert_warnings_in_build_outputs(
@@ -395,13 +437,23 @@ fn build_work(cx: &mut Context<'_, '_>, unit: &Unit) -> CargoResult<Job> {
         paths::set_file_time_no_err(output_file, timestamp);
         paths::write(&err_file, &output.stderr)?;
         paths::write(&root_output_file, util::path2bytes(&script_out_dir)?)?;
-        let parsed_output = BuildOutput::parse(
+        let mut parsed_output = BuildOutput::parse(
             &output.stdout,
             &pkg_name,
             &script_out_dir,
             &script_out_dir,
             extra_link_arg,
         )?;
+        // The build script may have handled the denied writes, but they're
+        // likely a bug.
+        if let Some(denied_writes) = &denied_writes {
+            for path in denied_writes.recorded() {
+                parsed_output.warnings.push(format!(
+                    "the sandbox denied writing to `{}`",
+                    path.display()
+                ));
+            }
+        }
 
         if json_messages {
             emit_build_output(state, &parsed_output, script_out_dir.as_path(), id)?;
@@ -567,7 +619,19 @@ impl BuildOutput {
                 "rustc-cfg" => cfgs.push(value.to_string()),
                 "rustc-env" => env.push(BuildOutput::parse_rustc_env(&value, &whence)?),
                 "warning" => warnings.push(value.to_string()),
-                "rerun-if-changed" => rerun_if_changed.push(PathBuf::from(value)),
+                "rerun-if-changed" => {
+                    if is_glob_pattern(&value) {
+                        glob::Pattern::new(&value).map_err(|e| {
+                            anyhow::format_err!(
+                                "invalid glob pattern `{}` for `rerun-if-changed` in {}: {}",
+                                value,
+                                whence,
+                                e
+                            )
+                        })?;
+                    }
+                    rerun_if_changed.push(PathBuf::from(value))
+                }
                 "rerun-if-env-changed" => rerun_if_env_changed.push(value.to_string()),
                 _ => metadata.push((key.to_string(), value.to_string())),
             }
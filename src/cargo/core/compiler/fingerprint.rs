@@ -69,7 +69,7 @@
 //! authors, description, homepage, repo       | ✓           |
 //! Target src path relative to ws             | ✓           |
 //! Target flags (test/bench/for_host/edition) | ✓           |
-//! -C incremental=… flag                      | ✓           |
+//! -C incremental=… flag                      | ✓[^6]       |
 //! mtime of sources                           | ✓[^3]       |
 //! RUSTFLAGS/RUSTDOCFLAGS                     | ✓           |
 //! LTO flags                                  | ✓           | ✓
@@ -86,6 +86,10 @@
 //! [^5]: Config settings that are not otherwise captured anywhere else.
 //!       Currently, this is only `doc.extern-map`.
 //!
+//! [^6]: Only whether incremental compilation is enabled is tracked, not the
+//!       directory itself. Moving the caches with `build.incremental-dir`
+//!       does not cause a rebuild, it only starts a fresh incremental session.
+//!
 //! When deciding what should go in the Metadata vs the Fingerprint, consider
 //! that some files (like dylibs) do not have a hash in their filename. Thus,
 //! if a value changes, only the fingerprint will detect the change (consider,
@@ -113,6 +117,10 @@
 //!   the Unit is built. This is used for capturing the time when the build
 //!   starts, to detect if files are changed in the middle of the build. See
 //!   below for more details.
+//! - With `-Z watchman`, a `watchman-clock` file with the clock of watchman
+//!   when the Unit was last known to be fresh. The files which watchman
+//!   reports as unchanged since then are not checked for their mtime. See
+//!   `fs_watcher` for more details.
 //!
 //! Note that some units are a little different. A Unit for *running* a build
 //! script or for `rustdoc` does not have a dep-info file (it's not
@@ -228,7 +236,9 @@
 //! In the new-style, each `rerun-if` directive is translated to the
 //! corresponding `LocalFingerprint` variant. The `RerunIfChanged` variant
 //! compares the mtime of the given filenames against the mtime of the
-//! "output" file.
+//! "output" file. Glob patterns in `rerun-if-changed` are expanded when the
+//! fingerprint is calculated, and the matched paths are hashed, so that adding
+//! or removing a matching file also re-runs the build script.
 //!
 //! Similar to normal units, the build script "output" file mtime is rewound
 //! to the time just before the build script is executed to handle mid-build
@@ -330,13 +340,16 @@ use serde::{Deserialize, Serialize};
 
 use crate::core::compiler::unit_graph::UnitDep;
 use crate::core::Package;
+use crate::sources::PackageFilter;
 use crate::util;
 use crate::util::errors::{CargoResult, CargoResultExt};
 use crate::util::interning::InternedString;
 use crate::util::paths;
+use crate::util::restricted_names::is_glob_pattern;
 use crate::util::{internal, path_args, profile, ProcessBuilder};
 
 use super::custom_build::BuildDeps;
+use super::fs_watcher::{self, Changes};
 use super::job::{Job, Work};
 use super::{BuildContext, Context, FileFlavor, Unit};
 
@@ -370,6 +383,8 @@ pub fn prepare_target(cx: &mut Context<'_, '_>, unit: &Unit, force: bool) -> Car
     // information about failed comparisons to aid in debugging.
     let fingerprint = calculate(cx, unit)?;
     let mtime_on_use = cx.bcx.config.cli_unstable().mtime_on_use;
+    let clock_loc = fs_watcher::clock_loc(cx, unit);
+    let clock = cx.fs_watcher.as_ref().map(|w| w.clock().to_string());
     let compare = compare_old_fingerprint(&loc, &*fingerprint, mtime_on_use);
     log_compare(unit, &compare);
 
@@ -393,6 +408,9 @@ pub fn prepare_target(cx: &mut Context<'_, '_>, unit: &Unit, force: bool) -> Car
     }
 
     if compare.is_ok() && !force {
+        if let Some(clock) = &clock {
+            paths::write(&clock_loc, clock)?;
+        }
         return Ok(Job::new_fresh());
     }
 
@@ -460,10 +478,14 @@ pub fn prepare_target(cx: &mut Context<'_, '_>, unit: &Unit, force: bool) -> Car
                 *fingerprint.local.lock().unwrap() = new_local;
             }
 
-            write_fingerprint(&loc, &fingerprint)
+            write_fingerprint(&loc, &fingerprint)?;
+            write_clock(&clock_loc, clock.as_deref())
         })
     } else {
-        Work::new(move |_| write_fingerprint(&loc, &fingerprint))
+        Work::new(move |_| {
+            write_fingerprint(&loc, &fingerprint)?;
+            write_clock(&clock_loc, clock.as_deref())
+        })
     };
 
     Ok(Job::new_dirty(write_fingerprint))
@@ -661,8 +683,8 @@ enum LocalFingerprint {
     /// out by a build script. The `output` file is a relative file anchored at
     /// `target_root(...)` which is the actual output of the build script. That
     /// output has already been parsed and the paths printed out via
-    /// `rerun-if-changed` are listed in `paths`. The `paths` field is relative
-    /// to `pkg.root()`
+    /// `rerun-if-changed` are listed in `paths`, with glob patterns replaced by
+    /// the paths they match. The `paths` field is relative to `pkg.root()`
     ///
     /// This is considered up-to-date if all of the `paths` are older than
     /// `output`, otherwise we need to recompile.
@@ -708,6 +730,7 @@ impl LocalFingerprint {
     fn find_stale_item(
         &self,
         mtime_cache: &mut HashMap<PathBuf, FileTime>,
+        changes: Option<&Changes>,
         pkg_root: &Path,
         target_root: &Path,
     ) -> CargoResult<Option<StaleItem>> {
@@ -735,13 +758,19 @@ impl LocalFingerprint {
                         current,
                     }));
                 }
-                Ok(find_stale_file(mtime_cache, &dep_info, info.files.iter()))
+                Ok(find_stale_file(
+                    mtime_cache,
+                    changes,
+                    &dep_info,
+                    info.files.iter(),
+                ))
             }
 
             // We need to verify that no paths listed in `paths` are newer than
             // the `output` path itself, or the last time the build script ran.
             LocalFingerprint::RerunIfChanged { output, paths } => Ok(find_stale_file(
                 mtime_cache,
+                changes,
                 &target_root.join(output),
                 paths.iter().map(|p| pkg_root.join(p)),
             )),
@@ -960,6 +989,7 @@ impl Fingerprint {
     fn check_filesystem(
         &mut self,
         mtime_cache: &mut HashMap<PathBuf, FileTime>,
+        changes: Option<&Changes>,
         pkg_root: &Path,
         target_root: &Path,
     ) -> CargoResult<()> {
@@ -1053,7 +1083,9 @@ impl Fingerprint {
         // files for this package itself. If we do find something log a helpful
         // message and bail out so we stay stale.
         for local in self.local.get_mut().unwrap().iter() {
-            if let Some(item) = local.find_stale_item(mtime_cache, pkg_root, target_root)? {
+            if let Some(item) =
+                local.find_stale_item(mtime_cache, changes, pkg_root, target_root)?
+            {
                 item.log();
                 return Ok(());
             }
@@ -1228,7 +1260,13 @@ fn calculate(cx: &mut Context<'_, '_>, unit: &Unit) -> CargoResult<Arc<Fingerpri
     // After we built the initial `Fingerprint` be sure to update the
     // `fs_status` field of it.
     let target_root = target_root(cx);
-    fingerprint.check_filesystem(&mut cx.mtime_cache, unit.pkg.root(), &target_root)?;
+    let clock_loc = fs_watcher::clock_loc(cx, unit);
+    let config = cx.bcx.config;
+    let changes = match &mut cx.fs_watcher {
+        Some(watcher) => watcher.changes_since(config, &clock_loc),
+        None => None,
+    };
+    fingerprint.check_filesystem(&mut cx.mtime_cache, changes, unit.pkg.root(), &target_root)?;
 
     let fingerprint = Arc::new(fingerprint);
     cx.fingerprints
@@ -1307,9 +1345,14 @@ fn calculate_normal(cx: &mut Context<'_, '_>, unit: &Unit) -> CargoResult<Finger
     } else {
         0
     };
+    // Custom targets are rebuilt when their JSON specification changes.
+    let target = match cx.bcx.target_data.target_spec_hash(unit.kind) {
+        Some(spec) => util::hash_u64((&unit.target, spec)),
+        None => util::hash_u64(&unit.target),
+    };
     Ok(Fingerprint {
         rustc: util::hash_u64(&cx.bcx.rustc().verbose_version),
-        target: util::hash_u64(&unit.target),
+        target,
         profile: profile_hash,
         // Note that .0 is hashed here, not .1 which is the cwd. That doesn't
         // actually affect the output artifact so there's no need to hash it.
@@ -1372,6 +1415,9 @@ fn calculate_run_custom_build(cx: &mut Context<'_, '_>, unit: &Unit) -> CargoRes
         rustc: util::hash_u64(&cx.bcx.rustc().verbose_version),
         deps,
         outputs: if overridden { Vec::new() } else { vec![output] },
+        // Build scripts may inspect the JSON specification of a custom
+        // target through `TARGET_SPEC_PATH`.
+        target: cx.bcx.target_data.target_spec_hash(unit.kind).unwrap_or(0),
 
         // Most of the other info is blank here as we don't really include it
         // in the execution of the build script, but... this may be a latent
@@ -1450,6 +1496,8 @@ fn build_script_local_fingerprints(
     // longstanding bug, in Cargo. Recent refactorings just made it painfully
     // obvious.
     let pkg_root = unit.pkg.root().to_path_buf();
+    let include = unit.pkg.manifest().include().to_vec();
+    let exclude = unit.pkg.manifest().exclude().to_vec();
     let target_dir = target_root(cx);
     let calculate =
         move |deps: &BuildDeps, pkg_fingerprint: Option<&dyn Fn() -> CargoResult<String>>| {
@@ -1480,7 +1528,13 @@ fn build_script_local_fingerprints(
             // Ok so now we're in "new mode" where we can have files listed as
             // dependencies as well as env vars listed as dependencies. Process
             // them all here.
-            Ok(Some(local_fingerprints_deps(deps, &target_dir, &pkg_root)))
+            let filter = PackageFilter::new(&pkg_root, &include, &exclude)?;
+            Ok(Some(local_fingerprints_deps(
+                deps,
+                &target_dir,
+                &pkg_root,
+                &filter,
+            )))
         };
 
     // Note that `false` == "not overridden"
@@ -1513,6 +1567,7 @@ fn local_fingerprints_deps(
     deps: &BuildDeps,
     target_root: &Path,
     pkg_root: &Path,
+    filter: &PackageFilter,
 ) -> Vec<LocalFingerprint> {
     debug!("new local fingerprints deps {:?}", pkg_root);
     let mut local = Vec::new();
@@ -1526,10 +1581,9 @@ fn local_fingerprints_deps(
             .strip_prefix(target_root)
             .unwrap()
             .to_path_buf();
-        let paths = deps
-            .rerun_if_changed
-            .iter()
-            .map(|p| p.strip_prefix(pkg_root).unwrap_or(p).to_path_buf())
+        let paths = expand_rerun_if_changed(pkg_root, &deps.rerun_if_changed, filter)
+            .into_iter()
+            .map(|p| p.strip_prefix(pkg_root).unwrap_or(&p).to_path_buf())
             .collect();
         local.push(LocalFingerprint::RerunIfChanged { output, paths });
     }
@@ -1563,6 +1617,15 @@ fn write_fingerprint(loc: &Path, fingerprint: &Fingerprint) -> CargoResult<()> {
     Ok(())
 }
 
+/// Records the clock of watchman at which a unit was built, if `-Z watchman`
+/// is used.
+fn write_clock(loc: &Path, clock: Option<&str>) -> CargoResult<()> {
+    match clock {
+        Some(clock) => paths::write(loc, clock),
+        None => Ok(()),
+    }
+}
+
 /// Prepare for work when a package starts to build
 pub fn prepare_init(cx: &mut Context<'_, '_>, unit: &Unit) -> CargoResult<()> {
     let new1 = cx.files().fingerprint_dir(unit);
@@ -1577,7 +1640,7 @@ pub fn prepare_init(cx: &mut Context<'_, '_>, unit: &Unit) -> CargoResult<()> {
 
 /// Returns the location that the dep-info file will show up at for the `unit`
 /// specified.
-pub fn dep_info_loc(cx: &mut Context<'_, '_>, unit: &Unit) -> PathBuf {
+pub fn dep_info_loc(cx: &Context<'_, '_>, unit: &Unit) -> PathBuf {
     cx.files().fingerprint_file_path(unit, "dep-")
 }
 
@@ -1681,8 +1744,78 @@ fn pkg_fingerprint(bcx: &BuildContext<'_, '_>, pkg: &Package) -> CargoResult<Str
     source.fingerprint(pkg)
 }
 
+/// Expands the glob patterns in the `rerun-if-changed` paths of a build
+/// script into the paths they currently match, in a stable order. Relative
+/// patterns are relative to `pkg_root`, and other paths are returned as is.
+///
+/// Files in the package which are excluded from it by `filter` are left out
+/// of the matches, and directories containing such files are expanded into
+/// the files of the package in them.
+pub fn expand_rerun_if_changed(
+    pkg_root: &Path,
+    paths: &[PathBuf],
+    filter: &PackageFilter,
+) -> Vec<PathBuf> {
+    let mut expanded = Vec::new();
+    for path in paths {
+        let pattern = match path.to_str() {
+            Some(pattern) if is_glob_pattern(pattern) => pattern,
+            _ => {
+                expanded.extend(expand_package_dir(pkg_root, path, filter));
+                continue;
+            }
+        };
+        let pattern = if path.is_absolute() {
+            pattern.to_string()
+        } else {
+            let root = glob::Pattern::escape(&pkg_root.to_string_lossy());
+            format!("{}/{}", root, pattern)
+        };
+        // Invalid patterns are rejected when parsing the output of the build
+        // script, and unreadable paths can't be read by it either.
+        let mut matches = match glob::glob(&pattern) {
+            Ok(matches) => matches.filter_map(Result::ok).collect::<Vec<_>>(),
+            Err(e) => {
+                debug!("invalid rerun-if-changed pattern {:?}: {}", pattern, e);
+                Vec::new()
+            }
+        };
+        matches.retain(|m| match m.strip_prefix(pkg_root) {
+            Ok(relative) => filter.should_package(relative, m.is_dir()),
+            Err(_) => true,
+        });
+        matches.sort();
+        expanded.extend(matches);
+    }
+    expanded
+}
+
+/// Lists the files of the package in `path`, if it's a directory in the
+/// package and the package has `include` or `exclude` rules. Otherwise,
+/// `path` is returned as is, and its whole contents are checked for changes.
+fn expand_package_dir(pkg_root: &Path, path: &Path, filter: &PackageFilter) -> Vec<PathBuf> {
+    let full_path = pkg_root.join(path);
+    if filter.is_empty() || !full_path.starts_with(pkg_root) || !full_path.is_dir() {
+        return vec![path.to_path_buf()];
+    }
+    walkdir::WalkDir::new(&full_path)
+        .follow_links(true)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+        .into_iter()
+        .filter_entry(|e| {
+            let relative = e.path().strip_prefix(pkg_root).unwrap();
+            e.depth() == 0 || filter.should_package(relative, e.file_type().is_dir())
+        })
+        // Unreadable paths can't be read by the build script either.
+        .filter_map(Result::ok)
+        .filter(|e| !e.file_type().is_dir())
+        .map(|e| e.into_path())
+        .collect()
+}
+
 fn find_stale_file<I>(
     mtime_cache: &mut HashMap<PathBuf, FileTime>,
+    changes: Option<&Changes>,
     reference: &Path,
     paths: I,
 ) -> Option<StaleItem>
@@ -1697,6 +1830,13 @@ where
 
     for path in paths {
         let path = path.as_ref();
+        // A file which didn't change since the unit was last fresh is still
+        // older than the reference.
+        if let Some(changes) = changes {
+            if changes.is_unchanged(path) {
+                continue;
+            }
+        }
         let path_mtime = match mtime_cache.entry(path.to_path_buf()) {
             Entry::Occupied(o) => *o.get(),
             Entry::Vacant(v) => {
@@ -0,0 +1,203 @@
+//! Asking [watchman] which files changed, with `-Z watchman`.
+//!
+//! To know whether a unit is fresh, Cargo compares the mtime of every file
+//! listed in its dep-info with the mtime of the dep-info file itself, which
+//! is a lot of `stat` calls in large workspaces, even when nothing changed.
+//!
+//! With `-Z watchman`, Cargo asks watchman for its clock when a build starts,
+//! and records that clock next to the fingerprint of each unit which is fresh
+//! or built successfully. The next time, Cargo asks watchman which files
+//! changed since the clock of a unit, and only looks at the mtimes of those:
+//! a file which didn't change since then is still older than the dep-info
+//! file. Files outside of the watched root, or in the target directory, are
+//! always checked, as are all the files when watchman can't tell what
+//! changed, like after it restarted.
+//!
+//! [watchman]: https://facebook.github.io/watchman/
+
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use log::debug;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::core::compiler::{BuildContext, Context, Unit};
+use crate::util::errors::{CargoResult, CargoResultExt};
+use crate::util::{paths, Config};
+
+pub struct FsWatcher {
+    /// The directory watchman watches, which contains the workspace.
+    root: PathBuf,
+    /// The target directory, whose files are never skipped.
+    target_root: PathBuf,
+    /// The clock of watchman when this build started.
+    clock: String,
+    /// The files which changed since a clock, by clock, or `None` if
+    /// watchman can't tell.
+    changes: HashMap<String, Option<Changes>>,
+}
+
+/// The files which changed since a clock.
+pub struct Changes {
+    root: PathBuf,
+    target_root: PathBuf,
+    /// The changed files and their parent directories.
+    changed: HashSet<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct WatchProjectResponse {
+    watch: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct ClockResponse {
+    clock: String,
+}
+
+#[derive(Deserialize)]
+struct QueryResponse {
+    #[serde(default)]
+    is_fresh_instance: bool,
+    files: Vec<PathBuf>,
+}
+
+impl FsWatcher {
+    /// Starts watching the workspace of `bcx` if `-Z watchman` is used. If
+    /// watchman isn't available, a warning is printed and all the files are
+    /// checked as usual.
+    pub fn start(bcx: &BuildContext<'_, '_>) -> CargoResult<Option<FsWatcher>> {
+        let config = bcx.config;
+        if !config.cli_unstable().watchman {
+            return Ok(None);
+        }
+        let ws_root = bcx.ws.root();
+        let started =
+            watchman::<WatchProjectResponse>(json!(["watch-project", ws_root])).and_then(|watch| {
+                let clock = watchman::<ClockResponse>(json!(["clock", watch.watch]))?;
+                Ok((watch.watch, clock.clock))
+            });
+        match started {
+            Ok((root, clock)) => {
+                debug!("watchman watches {:?}, clock {}", root, clock);
+                Ok(Some(FsWatcher {
+                    root,
+                    target_root: bcx.ws.target_dir().into_path_unlocked(),
+                    clock,
+                    changes: HashMap::new(),
+                }))
+            }
+            Err(e) => {
+                warn(config, &e)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Returns the files which changed since the clock recorded for a unit
+    /// in `clock_loc`, or `None` if all of its files have to be checked.
+    pub fn changes_since(&mut self, config: &Config, clock_loc: &Path) -> Option<&Changes> {
+        let since = paths::read(clock_loc).ok()?;
+        if !self.changes.contains_key(&since) {
+            let query = json!([
+                "query",
+                self.root,
+                {
+                    "since": since,
+                    "fields": ["name"],
+                    "empty_on_fresh_instance": true,
+                }
+            ]);
+            let changes = match watchman::<QueryResponse>(query) {
+                Ok(response) if response.is_fresh_instance => {
+                    debug!("watchman can't tell what changed since {}", since);
+                    None
+                }
+                Ok(response) => {
+                    let mut changed = HashSet::new();
+                    for file in response.files {
+                        let path = self.root.join(file);
+                        for ancestor in path.ancestors() {
+                            if !changed.insert(ancestor.to_path_buf()) {
+                                break;
+                            }
+                        }
+                    }
+                    Some(Changes {
+                        root: self.root.clone(),
+                        target_root: self.target_root.clone(),
+                        changed,
+                    })
+                }
+                Err(e) => {
+                    drop(warn(config, &e));
+                    None
+                }
+            };
+            self.changes.insert(since.clone(), changes);
+        }
+        self.changes[&since].as_ref()
+    }
+
+    /// Returns the clock of watchman when this build started.
+    pub fn clock(&self) -> &str {
+        &self.clock
+    }
+}
+
+impl Changes {
+    /// Returns whether `path` is known not to have changed since the clock.
+    pub fn is_unchanged(&self, path: &Path) -> bool {
+        path.starts_with(&self.root)
+            && !path.starts_with(&self.target_root)
+            && !self.changed.contains(path)
+    }
+}
+
+/// Returns the location of the file with the clock at which `unit` was last
+/// known to be fresh.
+pub fn clock_loc(cx: &Context<'_, '_>, unit: &Unit) -> PathBuf {
+    cx.files().fingerprint_file_path(unit, "watchman-clock-")
+}
+
+/// Runs a watchman command and parses its response.
+fn watchman<T: DeserializeOwned>(command: Value) -> CargoResult<T> {
+    let mut child = Command::new("watchman")
+        .arg("--no-pretty")
+        .arg("-j")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .chain_err(|| "failed to run `watchman`")?;
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(command.to_string().as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "`watchman` failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let response: Value = serde_json::from_slice(&output.stdout)
+        .chain_err(|| "failed to parse the response of `watchman`")?;
+    if let Some(error) = response.get("error").and_then(|e| e.as_str()) {
+        anyhow::bail!("`watchman` failed: {}", error);
+    }
+    serde_json::from_value(response).chain_err(|| "failed to parse the response of `watchman`")
+}
+
+fn warn(config: &Config, err: &anyhow::Error) -> CargoResult<()> {
+    config.shell().warn(format!(
+        "checking the mtimes of all files, watchman is unavailable: {:#}",
+        err
+    ))
+}
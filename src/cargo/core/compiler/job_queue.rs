@@ -823,6 +823,7 @@ impl<'cfg> DrainState<'cfg> {
         match unit.mode {
             CompileMode::Doc { .. } => format!("{}(doc)", pkg_name),
             CompileMode::RunCustomBuild => format!("{}(build)", pkg_name),
+            CompileMode::Doctest => format!("{}(doctest)", pkg_name),
             _ => {
                 let annotation = match unit.target.kind() {
                     TargetKind::Lib(_) => return pkg_name.to_string(),
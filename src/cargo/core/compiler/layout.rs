@@ -101,7 +101,7 @@
 use crate::core::compiler::CompileTarget;
 use crate::core::Workspace;
 use crate::util::paths;
-use crate::util::{CargoResult, FileLock};
+use crate::util::{short_hash, CargoResult, FileLock};
 use std::path::{Path, PathBuf};
 
 /// Contains the paths of all target output locations.
@@ -117,7 +117,9 @@ pub struct Layout {
     deps: PathBuf,
     /// The directory for build scripts: `$dest/build`
     build: PathBuf,
-    /// The directory for incremental files: `$dest/incremental`
+    /// The directory for incremental files: `$dest/incremental`.
+    /// If `build.incremental-dir` is set:
+    /// `$incremental_dir/$WORKSPACE-$HASH[/$TRIPLE]/debug`.
     incremental: PathBuf,
     /// The directory for fingerprints: `$dest/.fingerprint`
     fingerprint: PathBuf,
@@ -146,7 +148,8 @@ impl Layout {
         if let Some(target) = target {
             root.push(target.short_name());
         }
-        let dest = root.join(dest);
+        let dest_name = dest;
+        let dest = root.join(dest_name);
         // If the root directory doesn't already exist go ahead and create it
         // here. Use this opportunity to exclude it from backups as well if the
         // system supports it since this is a freshly created folder.
@@ -163,10 +166,22 @@ impl Layout {
         let root = root.into_path_unlocked();
         let dest = dest.into_path_unlocked();
 
+        // Incremental caches may be split off from the rest of the target
+        // directory, mirroring its `$TRIPLE/$PROFILE` structure.
+        let incremental = match Layout::incremental_root(ws)? {
+            Some(mut dir) => {
+                if let Some(target) = target {
+                    dir.push(target.short_name());
+                }
+                dir.join(dest_name)
+            }
+            None => dest.join("incremental"),
+        };
+
         Ok(Layout {
             deps: dest.join("deps"),
             build: dest.join("build"),
-            incremental: dest.join("incremental"),
+            incremental,
             fingerprint: dest.join(".fingerprint"),
             examples: dest.join("examples"),
             doc: root.join("doc"),
@@ -176,6 +191,29 @@ impl Layout {
         })
     }
 
+    /// Returns the directory holding the incremental caches of the target
+    /// directory of `ws` if `build.incremental-dir` is set. The directory may
+    /// be shared by many workspaces, so each target directory gets its own
+    /// subdirectory, named after the workspace and a hash of the target
+    /// directory.
+    pub fn incremental_root(ws: &Workspace<'_>) -> CargoResult<Option<PathBuf>> {
+        let dir = match ws.config().incremental_dir()? {
+            Some(dir) => dir,
+            None => return Ok(None),
+        };
+        let target_dir = ws.target_dir().into_path_unlocked();
+        let name = ws
+            .root()
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("workspace");
+        Ok(Some(dir.into_path_unlocked().join(format!(
+            "{}-{}",
+            name,
+            short_hash(&target_dir)
+        ))))
+    }
+
     /// Makes sure all directories stored in the Layout exist on the filesystem.
     pub fn prepare(&mut self) -> CargoResult<()> {
         paths::create_dir_all(&self.deps)?;
@@ -1,3 +1,4 @@
+mod annotations;
 mod build_config;
 mod build_context;
 mod build_plan;
@@ -7,19 +8,24 @@ mod context;
 mod crate_type;
 mod custom_build;
 mod fingerprint;
+mod fs_watcher;
 mod job;
 mod job_queue;
 mod layout;
 mod links;
 mod lto;
+mod sandbox;
 mod output_depinfo;
+mod rustc_invocations;
 pub mod rustdoc;
+mod sbom;
 pub mod standard_lib;
 mod timings;
 mod unit;
 pub mod unit_dependencies;
 pub mod unit_graph;
 
+use std::cell::RefCell;
 use std::env;
 use std::ffi::{OsStr, OsString};
 use std::fs::{self, File};
@@ -31,10 +37,11 @@ use anyhow::Error;
 use lazycell::LazyCell;
 use log::debug;
 
+pub use self::annotations::AnnotationFormat;
 pub use self::build_config::{BuildConfig, CompileMode, MessageFormat};
 pub use self::build_context::{BuildContext, FileFlavor, FileType, RustcTargetData, TargetInfo};
 use self::build_plan::BuildPlan;
-pub use self::compilation::{Compilation, Doctest};
+pub use self::compilation::{Compilation, Doctest, DoctestOutput};
 pub use self::compile_kind::{CompileKind, CompileTarget};
 pub use self::context::{Context, Metadata};
 pub use self::crate_type::CrateType;
@@ -45,12 +52,14 @@ use self::job_queue::{JobQueue, JobState};
 pub(crate) use self::layout::Layout;
 pub use self::lto::Lto;
 use self::output_depinfo::output_depinfo;
+use self::rustc_invocations::output_rustc_invocations;
+use self::sbom::output_sbom;
 use self::unit_graph::UnitDep;
 pub use crate::core::compiler::unit::{Unit, UnitInterner};
 use crate::core::features::nightly_features_allowed;
 use crate::core::manifest::TargetSourcePath;
 use crate::core::profiles::{PanicStrategy, Profile, Strip};
-use crate::core::{Edition, Feature, PackageId, Target};
+use crate::core::{Edition, Feature, PackageId, Target, Workspace};
 use crate::util::errors::{self, CargoResult, CargoResultExt, ProcessError, VerboseError};
 use crate::util::interning::InternedString;
 use crate::util::machine_message::Message;
@@ -156,12 +165,33 @@ fn compile<'cfg>(
     let job = if unit.mode.is_run_custom_build() {
         custom_build::prepare(cx, unit)?
     } else if unit.mode.is_doc_test() {
-        // We run these targets later, so this is just a no-op for now.
-        Job::new_fresh()
+        // The doc tests for other targets are left to `cargo test`, which
+        // decides whether they can run through the runner of their target.
+        let is_cross = match unit.kind {
+            CompileKind::Host => false,
+            CompileKind::Target(target) => bcx.host_triple() != target.short_name(),
+        };
+        match &bcx.build_config.doctest_harness_args {
+            // With `-Z parallel-doctests`, the doc tests run along with the
+            // rest of the build.
+            Some(harness_args)
+                if !build_plan && (!is_cross || bcx.config.cli_unstable().doctest_xcompile) =>
+            {
+                Job::new_dirty(doctest(cx, unit, harness_args)?)
+            }
+            // We run these targets later, so this is just a no-op for now.
+            _ => Job::new_fresh(),
+        }
     } else if build_plan {
         Job::new_dirty(rustc(cx, unit, &exec.clone())?)
     } else {
         let force = exec.force_rebuild(unit) || force_rebuild;
+        // Units compiled without recording their invocation are rebuilt, so
+        // that the database lists all the units.
+        let force = force
+            || (bcx.build_config.rustc_invocations
+                && rustc_invocations::has_invocation(unit)
+                && !rustc_invocations::invocation_loc(cx, unit).exists());
         let mut job = fingerprint::prepare_target(cx, unit, force)?;
         job.before(if job.freshness() == Freshness::Dirty {
             let work = if unit.mode.is_doc() {
@@ -203,8 +233,20 @@ fn compile<'cfg>(
     Ok(())
 }
 
+/// Returns the path of the dep-info file rustc writes when compiling `unit`.
+fn rustc_dep_info_loc(cx: &Context<'_, '_>, unit: &Unit) -> PathBuf {
+    let dep_info_name = match cx.files().metadata(unit) {
+        Some(metadata) => format!("{}-{}.d", unit.target.crate_name(), metadata),
+        None => format!("{}.d", unit.target.crate_name()),
+    };
+    cx.files().out_dir(unit).join(dep_info_name)
+}
+
 fn rustc(cx: &mut Context<'_, '_>, unit: &Unit, exec: &Arc<dyn Executor>) -> CargoResult<Work> {
     let mut rustc = prepare_rustc(cx, &unit.target.rustc_crate_types(), unit)?;
+    if let Some(sandbox) = &cx.network_sandbox {
+        sandbox.apply_to_rustc(cx, unit, &mut rustc);
+    }
     let build_plan = cx.bcx.build_config.build_plan;
 
     let name = unit.pkg.name().to_string();
@@ -225,11 +267,7 @@ fn rustc(cx: &mut Context<'_, '_>, unit: &Unit, exec: &Arc<dyn Executor>) -> Car
     let pass_l_flag = unit.target.is_lib() || !unit.pkg.targets().iter().any(|t| t.is_lib());
     let link_type = (&unit.target).into();
 
-    let dep_info_name = match cx.files().metadata(unit) {
-        Some(metadata) => format!("{}-{}.d", unit.target.crate_name(), metadata),
-        None => format!("{}.d", unit.target.crate_name()),
-    };
-    let rustc_dep_info_loc = root.join(dep_info_name);
+    let rustc_dep_info_loc = rustc_dep_info_loc(cx, unit);
     let dep_info_loc = fingerprint::dep_info_loc(cx, unit);
 
     rustc.args(cx.bcx.rustflags_args(unit));
@@ -254,6 +292,11 @@ fn rustc(cx: &mut Context<'_, '_>, unit: &Unit, exec: &Arc<dyn Executor>) -> Car
     let fingerprint_dir = cx.files().fingerprint_dir(unit);
     let script_metadata = cx.find_build_script_metadata(unit.clone());
     let is_local = unit.is_local();
+    let invocation_loc = if cx.bcx.build_config.rustc_invocations {
+        Some(rustc_invocations::invocation_loc(cx, unit))
+    } else {
+        None
+    };
 
     return Ok(Work::new(move |state| {
         // Only at runtime have we discovered what the extra -L and -l
@@ -319,6 +362,9 @@ fn rustc(cx: &mut Context<'_, '_>, unit: &Unit, exec: &Arc<dyn Executor>) -> Car
             )
             .map_err(verbose_if_simple_exit_code)
             .chain_err(|| format!("could not compile `{}`", name))?;
+            if let Some(invocation_loc) = &invocation_loc {
+                rustc_invocations::write_invocation(invocation_loc, &rustc)?;
+            }
         }
 
         if rustc_dep_info_loc.exists() {
@@ -582,6 +628,9 @@ fn rustdoc(cx: &mut Context<'_, '_>, unit: &Unit) -> CargoResult<Work> {
     let bcx = cx.bcx;
     let mut rustdoc = cx.compilation.rustdoc_process(unit)?;
     rustdoc.inherit_jobserver(&cx.jobserver);
+    if let Some(sandbox) = &cx.network_sandbox {
+        sandbox.apply_to_rustc(cx, unit, &mut rustdoc);
+    }
     rustdoc.arg("--crate-name").arg(&unit.target.crate_name());
     add_path_args(bcx.ws, unit, &mut rustdoc);
     add_cap_lints(bcx, unit, &mut rustdoc);
@@ -603,6 +652,15 @@ fn rustdoc(cx: &mut Context<'_, '_>, unit: &Unit) -> CargoResult<Work> {
         rustdoc.arg("--cfg").arg(&format!("feature=\"{}\"", feat));
     }
 
+    if let Some(doc) = unit.pkg.manifest().doc() {
+        for cfg in doc.cfg.iter().flatten() {
+            rustdoc.arg("--cfg").arg(cfg);
+        }
+        if let Some(args) = &doc.rustdoc_args {
+            rustdoc.args(args);
+        }
+    }
+
     add_error_format_and_color(cx, &mut rustdoc, false)?;
 
     if let Some(args) = cx.bcx.extra_args_for(unit) {
@@ -654,6 +712,161 @@ fn rustdoc(cx: &mut Context<'_, '_>, unit: &Unit) -> CargoResult<Work> {
     }))
 }
 
+/// Returns the rustdoc process which runs the doc tests of `doctest`, without
+/// the `--cfg` flags of its package, its `RUSTDOCFLAGS`, nor the arguments
+/// for the test harness.
+pub fn doctest_process(
+    ws: &Workspace<'_>,
+    compilation: &Compilation<'_>,
+    doctest: &Doctest,
+) -> CargoResult<ProcessBuilder> {
+    let Doctest {
+        args,
+        unstable_opts,
+        unit,
+        linker,
+    } = doctest;
+    let is_cross = match unit.kind {
+        CompileKind::Host => false,
+        CompileKind::Target(target) => target.short_name() != compilation.host,
+    };
+
+    let mut p = compilation.rustdoc_process(unit)?;
+    p.arg("--crate-name").arg(unit.target.crate_name());
+    p.arg("--test");
+    add_path_args(ws, unit, &mut p);
+
+    if ws.config().cli_unstable().doctest_xcompile || is_cross {
+        if let CompileKind::Target(target) = unit.kind {
+            // use `rustc_target()` to properly handle JSON target paths
+            p.arg("--target").arg(target.rustc_target());
+        }
+        p.arg("-Zunstable-options");
+        p.arg("--enable-per-target-ignores");
+        if let Some((runtool, runtool_args)) = compilation.target_runner(unit.kind) {
+            p.arg("--runtool").arg(runtool);
+            for arg in runtool_args {
+                p.arg("--runtool-arg").arg(arg);
+            }
+        }
+        if let Some(linker) = linker {
+            let mut joined = OsString::from("linker=");
+            joined.push(linker);
+            p.arg("-C").arg(joined);
+        }
+    }
+
+    for &rust_dep in &[
+        &compilation.deps_output[&unit.kind],
+        &compilation.deps_output[&CompileKind::Host],
+    ] {
+        let mut arg = OsString::from("dependency=");
+        arg.push(rust_dep);
+        p.arg("-L").arg(arg);
+    }
+
+    for native_dep in compilation.native_dirs.iter() {
+        p.arg("-L").arg(native_dep);
+    }
+
+    for arg in args {
+        p.arg(arg);
+    }
+
+    if *unstable_opts {
+        p.arg("-Zunstable-options");
+    }
+    Ok(p)
+}
+
+/// Runs the doc tests of `unit` as part of the build, with
+/// `-Z parallel-doctests`.
+///
+/// Failing doc tests don't fail the build: their output is kept in
+/// `Context::doctest_outputs`, for `cargo test` to report along with the
+/// other tests.
+fn doctest(cx: &mut Context<'_, '_>, unit: &Unit, harness_args: &[String]) -> CargoResult<Work> {
+    let mut unstable_opts = false;
+    let mut args = extern_args(cx, unit, &mut unstable_opts)?;
+    args.extend(lto_args(cx, unit));
+    let doctest = Doctest {
+        unit: unit.clone(),
+        args,
+        unstable_opts,
+        linker: cx.bcx.linker(unit.kind),
+    };
+    let mut p = doctest_process(cx.bcx.ws, &cx.compilation, &doctest)?;
+    p.inherit_jobserver(&cx.jobserver);
+    for feat in &unit.features {
+        p.arg("--cfg").arg(format!("feature=\"{}\"", feat));
+    }
+    p.args(cx.bcx.rustdocflags_args(unit));
+    for arg in harness_args {
+        p.arg("--test-args").arg(arg);
+    }
+    for dep in cx.unit_deps(unit) {
+        if dep.unit.mode.is_run_custom_build() {
+            p.env("OUT_DIR", cx.files().build_script_out_dir(&dep.unit));
+        }
+    }
+
+    let build_script_outputs = Arc::clone(&cx.build_script_outputs);
+    let doctest_outputs = Arc::clone(&cx.doctest_outputs);
+    let pkg_id = unit.pkg.package_id();
+    let kind = unit.kind;
+    let script_metadata = cx.find_build_script_metadata(unit.clone());
+
+    Ok(Work::new(move |state| {
+        {
+            let build_script_outputs = build_script_outputs.lock().unwrap();
+            if let Some(script_metadata) = script_metadata {
+                if let Some(output) = build_script_outputs.get(pkg_id, script_metadata) {
+                    for cfg in output.cfgs.iter() {
+                        p.arg("--cfg").arg(cfg);
+                    }
+                    for (name, value) in output.env.iter() {
+                        p.env(name, value);
+                    }
+                }
+            }
+            // The doc tests link with the library and its dependencies,
+            // which may need the native libraries of any build script.
+            for (_, output) in build_script_outputs.iter() {
+                for dir in output.library_paths.iter() {
+                    p.arg("-L").arg(dir);
+                }
+            }
+        }
+        state.running(&p);
+
+        let lines = RefCell::new(Vec::new());
+        let result = p.exec_with_streaming(
+            &mut |line| {
+                lines.borrow_mut().push((true, line.to_string()));
+                Ok(())
+            },
+            &mut |line| {
+                lines.borrow_mut().push((false, line.to_string()));
+                Ok(())
+            },
+            false,
+        );
+        let error = match result {
+            Ok(_) => None,
+            Err(e) => Some(e.downcast::<ProcessError>()?),
+        };
+        let output = DoctestOutput {
+            lines: lines.into_inner(),
+            error,
+        };
+        doctest_outputs
+            .lock()
+            .unwrap()
+            .insert((pkg_id, kind), output);
+        Ok(())
+    }))
+}
+
 // The --crate-version flag could have already been passed in RUSTDOCFLAGS
 // or as an extra compiler argument for rustdoc
 fn crate_version_flag_already_present(rustdoc: &ProcessBuilder) -> bool {
@@ -1192,12 +1405,13 @@ fn on_stderr_line_inner(
     // Depending on what we're emitting from Cargo itself, we figure out what to
     // do with this JSON message.
     match options.format {
-        // In the "human" output formats (human/short) or if diagnostic messages
-        // from rustc aren't being included in the output of Cargo's JSON
-        // messages then we extract the diagnostic (if present) here and handle
-        // it ourselves.
+        // In the "human" output formats (human/short/annotations) or if
+        // diagnostic messages from rustc aren't being included in the output
+        // of Cargo's JSON messages then we extract the diagnostic (if present)
+        // here and handle it ourselves.
         MessageFormat::Human
         | MessageFormat::Short
+        | MessageFormat::Annotations(_)
         | MessageFormat::Json {
             render_diagnostics: true,
             ..
@@ -1221,6 +1435,13 @@ fn on_stderr_line_inner(
                         .expect("strip should never fail")
                 };
                 state.stderr(rendered)?;
+                if let MessageFormat::Annotations(format) = options.format {
+                    if let Some(annotation) =
+                        annotations::render(format, compiler_message.get(), package_id)
+                    {
+                        state.stdout(annotation)?;
+                    }
+                }
                 return Ok(true);
             }
         }
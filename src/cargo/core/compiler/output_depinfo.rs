@@ -29,6 +29,7 @@ use std::path::{Path, PathBuf};
 use log::debug;
 
 use super::{fingerprint, Context, FileFlavor, Unit};
+use crate::sources::PackageFilter;
 use crate::util::paths;
 use crate::util::{internal, CargoResult};
 
@@ -86,8 +87,16 @@ fn add_deps_for_unit(
             .unwrap()
             .get(unit.pkg.package_id(), metadata)
         {
-            for path in &output.rerun_if_changed {
-                deps.insert(path.into());
+            let manifest = unit.pkg.manifest();
+            let filter =
+                PackageFilter::new(unit.pkg.root(), manifest.include(), manifest.exclude())?;
+            let paths = fingerprint::expand_rerun_if_changed(
+                unit.pkg.root(),
+                &output.rerun_if_changed,
+                &filter,
+            );
+            for path in paths {
+                deps.insert(path);
             }
         }
     }
@@ -0,0 +1,133 @@
+//! Module for writing the database of rustc invocations of
+//! `cargo build --rustc-invocations`.
+//!
+//! When a unit is compiled, the exact command line, environment and working
+//! directory of its rustc process is recorded next to its fingerprint, since
+//! some of it (like the flags emitted by build scripts) is only known while
+//! the build runs. After a successful build, the records of all the units
+//! compiled by rustc are gathered into `rustc-invocations.json`, in the
+//! directory of the final artifacts of their compile kind. Units which are
+//! fresh but don't have a record yet are rebuilt, so the database is always
+//! complete.
+//!
+//! This is an analog of the `compile_commands.json` of C and C++ build
+//! systems, for static analysis tools and IDE backends which need to re-run
+//! rustc exactly like Cargo did.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use super::{CompileKind, CompileMode, Context, Unit};
+use crate::core::{PackageId, TargetKind};
+use crate::util::errors::{CargoResult, CargoResultExt};
+use crate::util::{paths, ProcessBuilder};
+
+/// The version of the serialized database, which has to be bumped whenever
+/// a field is removed or changes meaning.
+const VERSION: u32 = 1;
+
+/// The name of the database, in the directory of the final artifacts.
+const FILE_NAME: &str = "rustc-invocations.json";
+
+#[derive(Serialize)]
+struct SerializedInvocations<'a> {
+    version: u32,
+    invocations: Vec<Invocation<'a>>,
+}
+
+#[derive(Serialize)]
+struct Invocation<'a> {
+    package_id: PackageId,
+    target_name: &'a str,
+    target_kind: &'a TargetKind,
+    crate_name: String,
+    kind: CompileKind,
+    compile_mode: CompileMode,
+    #[serde(flatten)]
+    command: Command,
+}
+
+/// The rustc process of a unit, as recorded when it was compiled.
+#[derive(Serialize, Deserialize)]
+struct Command {
+    program: String,
+    args: Vec<String>,
+    env: BTreeMap<String, String>,
+    cwd: Option<PathBuf>,
+}
+
+/// Returns whether `unit` is compiled by rustc, and so has an invocation.
+pub fn has_invocation(unit: &Unit) -> bool {
+    !(unit.mode.is_run_custom_build() || unit.mode.is_doc() || unit.mode.is_doc_test())
+}
+
+/// Returns the location of the record of the rustc process of `unit`.
+pub fn invocation_loc(cx: &Context<'_, '_>, unit: &Unit) -> PathBuf {
+    cx.files().fingerprint_file_path(unit, "rustc-invocation-")
+}
+
+/// Records `rustc`, the process which compiled a unit, to `path`.
+pub fn write_invocation(path: &PathBuf, rustc: &ProcessBuilder) -> CargoResult<()> {
+    let to_string = |s: &std::ffi::OsStr, what: &str| {
+        s.to_str()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow::format_err!("unicode {} required, got {:?}", what, s))
+    };
+    let mut env = BTreeMap::new();
+    for (var, value) in rustc.get_envs() {
+        if let Some(value) = value {
+            env.insert(var.clone(), to_string(value, "environment value")?);
+        }
+    }
+    let command = Command {
+        program: to_string(rustc.get_program(), "program string")?,
+        args: rustc
+            .get_args()
+            .iter()
+            .map(|arg| to_string(arg, "argument string"))
+            .collect::<CargoResult<_>>()?,
+        env,
+        cwd: rustc.get_cwd().map(|cwd| cwd.to_path_buf()),
+    };
+    paths::write(path, serde_json::to_string(&command)?)
+}
+
+/// Writes the database of the rustc invocations of all the units of the
+/// build, one for each compile kind.
+pub fn output_rustc_invocations(cx: &Context<'_, '_>) -> CargoResult<()> {
+    let mut units: Vec<&Unit> = cx
+        .bcx
+        .unit_graph
+        .keys()
+        .filter(|unit| has_invocation(unit))
+        .collect();
+    units.sort();
+
+    let mut by_kind: BTreeMap<CompileKind, Vec<Invocation<'_>>> = BTreeMap::new();
+    for unit in units {
+        let loc = invocation_loc(cx, unit);
+        let command: Command = serde_json::from_str(&paths::read(&loc)?)
+            .chain_err(|| format!("failed to parse the rustc invocation at {}", loc.display()))?;
+        by_kind.entry(unit.kind).or_default().push(Invocation {
+            package_id: unit.pkg.package_id(),
+            target_name: unit.target.name(),
+            target_kind: unit.target.kind(),
+            crate_name: unit.target.crate_name(),
+            kind: unit.kind,
+            compile_mode: unit.mode,
+            command,
+        });
+    }
+
+    for (kind, invocations) in by_kind {
+        let path = cx.files().layout(kind).dest().join(FILE_NAME);
+        let serialized = SerializedInvocations {
+            version: VERSION,
+            invocations,
+        };
+        paths::write(&path, serde_json::to_string_pretty(&serialized)?)?;
+    }
+    Ok(())
+}
@@ -3,10 +3,11 @@
 use crate::core::compiler::context::Context;
 use crate::core::compiler::unit::Unit;
 use crate::core::compiler::CompileKind;
+use crate::core::PackageId;
 use crate::sources::CRATES_IO_REGISTRY;
 use crate::util::errors::{internal, CargoResult};
 use crate::util::ProcessBuilder;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::hash;
 use url::Url;
@@ -120,8 +121,20 @@ pub fn add_root_urls(
             }
         })
         .collect();
+    // The dependencies which are documented along with this unit, when
+    // `--no-deps` isn't used. Their docs are in the same directory, so they
+    // are linked to locally instead of to the registry's documentation.
+    let documented: HashSet<PackageId> = cx
+        .unit_deps(unit)
+        .iter()
+        .filter(|dep| dep.unit.mode.is_doc())
+        .map(|dep| dep.unit.pkg.package_id())
+        .collect();
     for dep in cx.unit_deps(unit) {
-        if dep.unit.target.is_linkable() && !dep.unit.mode.is_doc() {
+        if dep.unit.target.is_linkable()
+            && !dep.unit.mode.is_doc()
+            && !documented.contains(&dep.unit.pkg.package_id())
+        {
             for (registry, location) in &map.registries {
                 let sid = dep.unit.pkg.package_id().source_id();
                 let matches_registry = || -> bool {
@@ -0,0 +1,161 @@
+//! The policies of `-Z network-sandbox` and `-Z build-script-sandbox`, which
+//! restrict what the code of packages running during the build can do.
+//!
+//! `-Z network-sandbox` denies network access to build scripts, and to
+//! procedural macros, which run in the rustc processes compiling the crates
+//! using them. Packages listed in the `sandbox.allow-network` configuration
+//! keep network access. A rustc process is only sandboxed if one of the
+//! procedural macros it may load isn't allowed network access.
+//!
+//! `-Z build-script-sandbox` only allows build scripts to write to their
+//! `OUT_DIR`, their package, the temporary directory, `/dev`, and the paths of
+//! the `sandbox.writable-paths` configuration. Packages listed in the
+//! `sandbox.allow-filesystem` configuration can write anywhere.
+//!
+//! See `util::sandbox` for how the processes are sandboxed.
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::core::compiler::{BuildContext, Unit};
+use crate::core::{PackageId, PackageIdSpec};
+use crate::util::errors::{CargoResult, CargoResultExt};
+use crate::util::sandbox::{check_filesystem_sandbox_supported, check_network_sandbox_supported};
+use crate::util::{Config, ProcessBuilder};
+
+use super::context::Context;
+
+/// The `[sandbox]` configuration table.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+struct SandboxConfig {
+    /// Specs of the packages which keep network access.
+    #[serde(default)]
+    allow_network: Vec<String>,
+    /// Specs of the packages whose build scripts can write anywhere.
+    #[serde(default)]
+    allow_filesystem: Vec<String>,
+}
+
+impl SandboxConfig {
+    fn load(config: &Config) -> CargoResult<SandboxConfig> {
+        Ok(config
+            .get::<Option<SandboxConfig>>("sandbox")?
+            .unwrap_or_default())
+    }
+}
+
+/// Parses the package ID specs of the `sandbox.<key>` configuration.
+fn parse_specs(specs: &[String], key: &str) -> CargoResult<Vec<PackageIdSpec>> {
+    specs
+        .iter()
+        .map(|spec| {
+            PackageIdSpec::parse(spec).chain_err(|| {
+                format!(
+                    "invalid package ID specification `{}` in `sandbox.{}`",
+                    spec, key
+                )
+            })
+        })
+        .collect()
+}
+
+pub struct NetworkSandbox {
+    allow: Vec<PackageIdSpec>,
+}
+
+impl NetworkSandbox {
+    /// Returns the sandbox of the build, or `None` if `-Z network-sandbox`
+    /// isn't used.
+    pub fn new(bcx: &BuildContext<'_, '_>) -> CargoResult<Option<NetworkSandbox>> {
+        let config = bcx.config;
+        if !config.cli_unstable().network_sandbox {
+            return Ok(None);
+        }
+        check_network_sandbox_supported()?;
+        let sandbox_config = SandboxConfig::load(config)?;
+        let allow = parse_specs(&sandbox_config.allow_network, "allow-network")?;
+        Ok(Some(NetworkSandbox { allow }))
+    }
+
+    fn allows(&self, pkg_id: PackageId) -> bool {
+        self.allow.iter().any(|spec| spec.matches(pkg_id))
+    }
+
+    /// Denies network access to the process running the build script of
+    /// `unit`, unless its package is allowed network access.
+    pub fn apply_to_build_script(&self, unit: &Unit, cmd: &mut ProcessBuilder) {
+        if !self.allows(unit.pkg.package_id()) {
+            cmd.deny_network();
+        }
+    }
+
+    /// Denies network access to the rustc process compiling `unit`, if it
+    /// depends on procedural macros which aren't allowed network access.
+    pub fn apply_to_rustc(&self, cx: &Context<'_, '_>, unit: &Unit, cmd: &mut ProcessBuilder) {
+        let denied_proc_macro = cx
+            .unit_deps(unit)
+            .iter()
+            .any(|dep| dep.unit.target.proc_macro() && !self.allows(dep.unit.pkg.package_id()));
+        if denied_proc_macro {
+            cmd.deny_network();
+        }
+    }
+}
+
+pub struct BuildScriptSandbox {
+    allow: Vec<PackageIdSpec>,
+    /// The paths of `sandbox.writable-paths`, then the temporary directory
+    /// and `/dev`.
+    writable_paths: Vec<PathBuf>,
+}
+
+impl BuildScriptSandbox {
+    /// Returns the sandbox of the build scripts, or `None` if
+    /// `-Z build-script-sandbox` isn't used.
+    pub fn new(bcx: &BuildContext<'_, '_>) -> CargoResult<Option<BuildScriptSandbox>> {
+        let config = bcx.config;
+        if !config.cli_unstable().build_script_sandbox {
+            return Ok(None);
+        }
+        check_filesystem_sandbox_supported()?;
+        let sandbox_config = SandboxConfig::load(config)?;
+        let allow = parse_specs(&sandbox_config.allow_filesystem, "allow-filesystem")?;
+        // Relative paths are relative to the directory containing the
+        // `.cargo` directory of the config file listing them.
+        let mut writable_paths: Vec<PathBuf> = match config.get_list("sandbox.writable-paths")? {
+            Some(list) => list
+                .val
+                .iter()
+                .map(|(path, def)| def.root(config).join(path))
+                .collect(),
+            None => Vec::new(),
+        };
+        writable_paths.push(env::temp_dir());
+        if cfg!(unix) {
+            writable_paths.push(PathBuf::from("/dev"));
+        }
+        Ok(Some(BuildScriptSandbox {
+            allow,
+            writable_paths,
+        }))
+    }
+
+    /// Restricts the writes of the process running the build script of
+    /// `unit`, whose `OUT_DIR` is `out_dir`, unless its package is allowed
+    /// to write anywhere.
+    pub fn apply(&self, unit: &Unit, out_dir: &Path, cmd: &mut ProcessBuilder) {
+        if self
+            .allow
+            .iter()
+            .any(|spec| spec.matches(unit.pkg.package_id()))
+        {
+            return;
+        }
+        let mut paths = vec![out_dir.to_path_buf(), unit.pkg.root().to_path_buf()];
+        paths.extend(self.writable_paths.iter().cloned());
+        cmd.restrict_writes(paths);
+    }
+}
@@ -0,0 +1,290 @@
+//! Module for generating the SBOMs of `cargo build --sbom`.
+//!
+//! A software bill of materials is written next to every "uplifted" artifact
+//! of the root units, with the name of the artifact followed by `.cdx.json`.
+//! It's a [CycloneDX] JSON document listing the packages which were compiled
+//! for the artifact, as they were compiled for this build: the features are
+//! the ones enabled for the units of the package, not the ones the resolver
+//! could enable, and packages which are only built for the host (the
+//! dependencies of build scripts and procedural macros) are listed with the
+//! `excluded` scope, since they aren't part of the artifact.
+//!
+//! [CycloneDX]: https://cyclonedx.org/docs/1.4/json/
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use serde::Serialize;
+
+use super::{Context, FileFlavor, Unit};
+use crate::core::{Package, PackageId};
+use crate::util::interning::InternedString;
+use crate::util::paths;
+use crate::util::CargoResult;
+
+const SPEC_VERSION: &str = "1.4";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Bom<'a> {
+    bom_format: &'static str,
+    spec_version: &'static str,
+    version: u32,
+    metadata: Metadata<'a>,
+    components: Vec<Component<'a>>,
+    dependencies: Vec<Dependency>,
+}
+
+#[derive(Serialize)]
+struct Metadata<'a> {
+    tools: Vec<Tool>,
+    component: Component<'a>,
+}
+
+#[derive(Serialize)]
+struct Tool {
+    name: &'static str,
+    version: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Component<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(rename = "bom-ref")]
+    bom_ref: String,
+    name: InternedString,
+    version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    hashes: Vec<Hash<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    licenses: Vec<License<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    purl: Option<String>,
+    properties: Vec<Property>,
+}
+
+#[derive(Serialize)]
+struct Hash<'a> {
+    alg: &'static str,
+    content: &'a str,
+}
+
+#[derive(Serialize)]
+struct License<'a> {
+    expression: &'a str,
+}
+
+#[derive(Serialize)]
+struct Property {
+    name: &'static str,
+    value: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Dependency {
+    #[serde(rename = "ref")]
+    bom_ref: String,
+    depends_on: Vec<String>,
+}
+
+/// What was compiled of a package for an artifact.
+#[derive(Default)]
+struct CompiledPackage {
+    /// The package, once one of its units is visited.
+    pkg: Option<Package>,
+    /// The features enabled for any of the units of the package.
+    features: BTreeSet<InternedString>,
+    /// Whether the package is part of the artifact, rather than only used to
+    /// build it.
+    runtime: bool,
+    /// The packages the units of the package depend on.
+    deps: BTreeSet<PackageId>,
+}
+
+/// Writes the SBOMs of the outputs of the root unit `unit`.
+pub fn output_sbom(cx: &mut Context<'_, '_>, unit: &Unit) -> CargoResult<()> {
+    let mut packages = BTreeMap::new();
+    let mut visited = HashSet::new();
+    collect_packages(cx, unit, true, &mut packages, &mut visited);
+
+    let root_id = unit.pkg.package_id();
+    let root = packages.remove(&root_id).unwrap_or_default();
+    let mut dependencies = vec![Dependency {
+        bom_ref: bom_ref(root_id),
+        depends_on: root.deps.iter().map(|&id| bom_ref(id)).collect(),
+    }];
+    let mut components = Vec::new();
+    for (&id, compiled) in &packages {
+        let pkg = compiled.pkg.as_ref().unwrap();
+        components.push(component(pkg, compiled, "library"));
+        dependencies.push(Dependency {
+            bom_ref: bom_ref(id),
+            depends_on: compiled.deps.iter().map(|&id| bom_ref(id)).collect(),
+        });
+    }
+    let kind = if unit.target.is_executable() {
+        "application"
+    } else {
+        "library"
+    };
+    let mut root_component = component(&unit.pkg, &root, kind);
+    root_component.scope = None;
+    let bom = Bom {
+        bom_format: "CycloneDX",
+        spec_version: SPEC_VERSION,
+        version: 1,
+        metadata: Metadata {
+            tools: vec![Tool {
+                name: "cargo",
+                version: crate::version().to_string(),
+            }],
+            component: root_component,
+        },
+        components,
+        dependencies,
+    };
+    let mut contents = serde_json::to_string_pretty(&bom)?;
+    contents.push('\n');
+
+    for output in cx
+        .outputs(unit)?
+        .iter()
+        .filter(|o| !matches!(o.flavor, FileFlavor::DebugInfo | FileFlavor::Auxiliary))
+    {
+        if let Some(ref link_dst) = output.hardlink {
+            let mut file_name = link_dst.file_name().unwrap().to_os_string();
+            file_name.push(".cdx.json");
+            let sbom_path = link_dst.with_file_name(file_name);
+            // If nothing changed don't recreate the file which could alter
+            // its mtime.
+            if paths::read(&sbom_path).ok().as_ref() == Some(&contents) {
+                continue;
+            }
+            paths::write(&sbom_path, &contents)?;
+        }
+    }
+    Ok(())
+}
+
+/// Collects what was compiled of the packages of `unit` and of its
+/// dependencies into `packages`. `runtime` is whether `unit` is part of the
+/// artifact.
+fn collect_packages(
+    cx: &Context<'_, '_>,
+    unit: &Unit,
+    runtime: bool,
+    packages: &mut BTreeMap<PackageId, CompiledPackage>,
+    visited: &mut HashSet<(Unit, bool)>,
+) {
+    if !visited.insert((unit.clone(), runtime)) {
+        return;
+    }
+    let id = unit.pkg.package_id();
+    let compiled = packages.entry(id).or_default();
+    compiled.pkg = Some(unit.pkg.clone());
+    compiled.runtime |= runtime;
+    compiled.features.extend(unit.features.iter().copied());
+    for dep in cx.unit_deps(unit) {
+        let dep_id = dep.unit.pkg.package_id();
+        if dep_id != id {
+            packages.get_mut(&id).unwrap().deps.insert(dep_id);
+        }
+        // Build scripts and procedural macros only run on the host while
+        // building, so neither they nor their dependencies are part of the
+        // artifact.
+        let dep_runtime = runtime
+            && !unit.target.is_custom_build()
+            && !dep.unit.target.is_custom_build()
+            && !dep.unit.target.proc_macro();
+        collect_packages(cx, &dep.unit, dep_runtime, packages, visited);
+    }
+}
+
+fn component<'a>(
+    pkg: &'a Package,
+    compiled: &CompiledPackage,
+    kind: &'static str,
+) -> Component<'a> {
+    let id = pkg.package_id();
+    let metadata = pkg.manifest().metadata();
+    let properties = vec![
+        Property {
+            name: "cargo:source",
+            value: id.source_id().as_url().to_string(),
+        },
+        Property {
+            name: "cargo:features",
+            value: compiled
+                .features
+                .iter()
+                .map(|f| f.as_str())
+                .collect::<Vec<_>>()
+                .join(","),
+        },
+    ];
+    Component {
+        kind,
+        bom_ref: bom_ref(id),
+        name: id.name(),
+        version: id.version().to_string(),
+        scope: Some(if compiled.runtime {
+            "required"
+        } else {
+            "excluded"
+        }),
+        description: metadata.description.as_deref(),
+        hashes: pkg
+            .summary()
+            .checksum()
+            .map(|content| Hash {
+                alg: "SHA-256",
+                content,
+            })
+            .into_iter()
+            .collect(),
+        licenses: metadata
+            .license
+            .as_deref()
+            .map(|expression| License { expression })
+            .into_iter()
+            .collect(),
+        purl: purl(id),
+        properties,
+    }
+}
+
+fn bom_ref(id: PackageId) -> String {
+    id.to_string()
+}
+
+/// Returns the [package URL](https://github.com/package-url/purl-spec) of
+/// `id`, which path packages don't have.
+fn purl(id: PackageId) -> Option<String> {
+    let source_id = id.source_id();
+    let mut purl = format!("pkg:cargo/{}@{}", id.name(), id.version());
+    let qualifier =
+        |url: &str| url::form_urlencoded::byte_serialize(url.as_bytes()).collect::<String>();
+    if source_id.is_git() {
+        let mut vcs_url = format!("git+{}", source_id.url());
+        if let Some(rev) = source_id.precise() {
+            vcs_url.push('@');
+            vcs_url.push_str(rev);
+        }
+        purl.push_str("?vcs_url=");
+        purl.push_str(&qualifier(&vcs_url));
+    } else if source_id.is_registry() {
+        if !source_id.is_default_registry() {
+            purl.push_str("?repository_url=");
+            purl.push_str(&qualifier(source_id.url().as_str()));
+        }
+    } else {
+        return None;
+    }
+    Some(purl)
+}
@@ -251,6 +251,10 @@ fn compute_deps(
                     return false;
                 }
 
+                if !is_linked_into_target(unit, dep) {
+                    return false;
+                }
+
                 // If we've gotten past all that, then this dependency is
                 // actually used!
                 true
@@ -398,11 +402,35 @@ fn compute_deps_custom_build(
 }
 
 /// Returns the dependencies necessary to document a package.
+/// Returns whether `dep` is used by the target of `unit`. The optional
+/// dependencies listed in the `dependencies` field of binaries and examples
+/// are only linked into the targets listing them.
+fn is_linked_into_target(unit: &Unit, dep: &Dependency) -> bool {
+    if dep.kind() != DepKind::Normal {
+        return true;
+    }
+    let name = dep.name_in_toml();
+    let is_target_dep = unit
+        .pkg
+        .targets()
+        .iter()
+        .any(|t| t.dependencies().iter().any(|d| d.as_str() == name.as_str()));
+    !is_target_dep
+        || unit
+            .target
+            .dependencies()
+            .iter()
+            .any(|d| d.as_str() == name.as_str())
+}
+
 fn compute_deps_doc(unit: &Unit, state: &mut State<'_, '_>) -> CargoResult<Vec<UnitDep>> {
     let deps = state
         .deps(unit, UnitFor::new_normal())
         .into_iter()
-        .filter(|&(_id, deps)| deps.iter().any(|dep| dep.kind() == DepKind::Normal));
+        .filter(|&(_id, deps)| {
+            deps.iter()
+                .any(|dep| dep.kind() == DepKind::Normal && is_linked_into_target(unit, dep))
+        });
 
     // To document a library, we depend on dependencies actually being
     // built. If we're documenting *all* libraries, then we also depend on
@@ -3,9 +3,8 @@ use crate::core::compiler::{CompileKind, CompileMode};
 use crate::core::profiles::{Profile, UnitFor};
 use crate::core::{nightly_features_allowed, PackageId, Target};
 use crate::util::interning::InternedString;
-use crate::util::CargoResult;
+use crate::util::{CargoResult, Config};
 use std::collections::HashMap;
-use std::io::Write;
 
 /// The dependency graph of Units.
 pub type UnitGraph = HashMap<Unit, Vec<UnitDep>>;
@@ -62,7 +61,11 @@ struct SerializedUnitDep {
     // internal detail that is mostly used for building the graph.
 }
 
-pub fn emit_serialized_unit_graph(root_units: &[Unit], unit_graph: &UnitGraph) -> CargoResult<()> {
+pub fn emit_serialized_unit_graph(
+    root_units: &[Unit],
+    unit_graph: &UnitGraph,
+    config: &Config,
+) -> CargoResult<()> {
     let is_nightly = nightly_features_allowed();
     let mut units: Vec<(&Unit, &Vec<UnitDep>)> = unit_graph.iter().collect();
     units.sort_unstable();
@@ -111,9 +114,9 @@ pub fn emit_serialized_unit_graph(root_units: &[Unit], unit_graph: &UnitGraph) -
         roots,
     };
 
-    let stdout = std::io::stdout();
-    let mut lock = stdout.lock();
-    serde_json::to_writer(&mut lock, &s)?;
-    drop(writeln!(lock));
+    let mut shell = config.shell();
+    let out = shell.out();
+    serde_json::to_writer(&mut *out, &s)?;
+    drop(writeln!(out));
     Ok(())
 }
@@ -1,7 +1,7 @@
 use cargo_platform::Platform;
 use log::trace;
 use semver::ReqParseError;
-use semver::VersionReq;
+use semver::{Version, VersionReq};
 use serde::ser;
 use serde::Serialize;
 use std::rc::Rc;
@@ -38,6 +38,9 @@ struct Inner {
 
     optional: bool,
     public: bool,
+    /// Whether the version requirement also matches pre-releases, see
+    /// `Dependency::matches_version`.
+    allow_prerelease: bool,
     default_features: bool,
     features: Vec<InternedString>,
 
@@ -92,6 +95,18 @@ pub enum DepKind {
     Build,
 }
 
+impl DepKind {
+    /// Returns the name of the manifest table listing dependencies of this
+    /// kind, like `build-dependencies`.
+    pub fn kind_table(&self) -> &'static str {
+        match self {
+            DepKind::Normal => "dependencies",
+            DepKind::Development => "dev-dependencies",
+            DepKind::Build => "build-dependencies",
+        }
+    }
+}
+
 fn parse_req_with_deprecated(
     name: InternedString,
     req: &str,
@@ -212,6 +227,7 @@ impl Dependency {
                 only_match_name: true,
                 optional: false,
                 public: false,
+                allow_prerelease: false,
                 features: Vec::new(),
                 default_features: true,
                 specified_req: false,
@@ -292,6 +308,16 @@ impl Dependency {
         self.inner.public
     }
 
+    pub fn allows_prerelease(&self) -> bool {
+        self.inner.allow_prerelease
+    }
+
+    /// Sets whether the version requirement also matches pre-releases.
+    pub fn set_allow_prerelease(&mut self, allow_prerelease: bool) -> &mut Dependency {
+        Rc::make_mut(&mut self.inner).allow_prerelease = allow_prerelease;
+        self
+    }
+
     /// Sets whether the dependency is public.
     pub fn set_public(&mut self, public: bool) -> &mut Dependency {
         if public {
@@ -378,7 +404,7 @@ impl Dependency {
     /// Locks this dependency to depending on the specified package ID.
     pub fn lock_to(&mut self, id: PackageId) -> &mut Dependency {
         assert_eq!(self.inner.source_id, id.source_id());
-        assert!(self.inner.req.matches(id.version()));
+        assert!(self.matches_version(id.version()));
         trace!(
             "locking dep from `{}` with `{}` at {} to {}",
             self.package_name(),
@@ -429,6 +455,35 @@ impl Dependency {
         &self.inner.features
     }
 
+    /// Returns `true` if `version` satisfies the version requirement.
+    ///
+    /// Pre-releases normally only match requirements that mention a
+    /// pre-release of the same version. With `allow-prerelease`, they also
+    /// match if the requirement accepts the release they lead up to, or if
+    /// that release is the next incompatible one. For example `^1.0` then
+    /// matches both `1.1.0-rc.1` and `2.0.0-rc.1`.
+    pub fn matches_version(&self, version: &Version) -> bool {
+        let req = &self.inner.req;
+        if req.matches(version) {
+            return true;
+        }
+        if !self.inner.allow_prerelease || !version.is_prerelease() {
+            return false;
+        }
+        let release = Version::new(version.major, version.minor, version.patch);
+        if req.matches(&release) {
+            return true;
+        }
+        let previous = match (release.major, release.minor, release.patch) {
+            (0, 0, 0) => return false,
+            (0, 0, patch) => Version::new(0, 0, patch - 1),
+            (0, minor, 0) => Version::new(0, minor - 1, u64::MAX),
+            (major, 0, 0) => Version::new(major - 1, u64::MAX, u64::MAX),
+            _ => return false,
+        };
+        req.matches(&previous)
+    }
+
     /// Returns `true` if the package (`sum`) can fulfill this dependency request.
     pub fn matches(&self, sum: &Summary) -> bool {
         self.matches_id(sum.package_id())
@@ -436,14 +491,14 @@ impl Dependency {
 
     /// Returns `true` if the package (`id`) can fulfill this dependency request.
     pub fn matches_ignoring_source(&self, id: PackageId) -> bool {
-        self.package_name() == id.name() && self.version_req().matches(id.version())
+        self.package_name() == id.name() && self.matches_version(id.version())
     }
 
     /// Returns `true` if the package (`id`) can fulfill this dependency request.
     pub fn matches_id(&self, id: PackageId) -> bool {
         self.inner.name == id.name()
             && (self.inner.only_match_name
-                || (self.inner.req.matches(id.version()) && self.inner.source_id == id.source_id()))
+                || (self.matches_version(id.version()) && self.inner.source_id == id.source_id()))
     }
 
     pub fn map_source(mut self, to_replace: SourceId, replace_with: SourceId) -> Dependency {
@@ -210,10 +210,37 @@ features! {
         [unstable] named_profiles: bool,
 
         // Opt-in new-resolver behavior.
-        [unstable] resolver: bool,
+        [stable] resolver: bool,
 
         // Allow to specify whether binaries should be stripped.
         [unstable] strip: bool,
+
+        // Allow version requirements of dependencies to match pre-releases.
+        [unstable] allow_prerelease: bool,
+
+        // Allow git dependencies to choose which submodules are checked out.
+        [unstable] git_submodules: bool,
+
+        // Allow members to inherit dependencies from `[workspace.dependencies]`.
+        [unstable] workspace_inheritance: bool,
+
+        // Allow to specify the minimum version of rustc supported by a package.
+        [unstable] rust_version: bool,
+
+        // Allow binaries and examples to declare their own optional dependencies.
+        [unstable] target_dependencies: bool,
+
+        // Allow dev-dependencies to be optional, enabled by features.
+        [unstable] optional_dev_dependencies: bool,
+
+        // Allow to change the directories in which targets are discovered.
+        [unstable] autodiscover_dirs: bool,
+
+        // Allow publishing a package under a different name.
+        [unstable] publish_name: bool,
+
+        // Allow to specify how a package is documented in a `[doc]` table.
+        [unstable] doc_table: bool,
     }
 }
 
@@ -357,10 +384,30 @@ pub struct CliUnstable {
     pub multitarget: bool,
     pub rustdoc_map: bool,
     pub terminal_width: Option<Option<usize>>,
-    pub namespaced_features: bool,
     pub weak_dep_features: bool,
     pub extra_link_arg: bool,
     pub credential_process: bool,
+    pub incremental_dir: bool,
+    pub lockfile_version: bool,
+    pub pubgrub: bool,
+    pub resolve_cache: bool,
+    pub gitoxide: bool,
+    pub git_shallow: bool,
+    pub asymmetric_token: bool,
+    pub index_store: bool,
+    pub registry_overlay: bool,
+    pub crate_signing: bool,
+    pub test_cache: bool,
+    pub parallel_doctests: bool,
+    pub parallel_install: bool,
+    pub install_locked: bool,
+    pub prebuilt_install: bool,
+    pub local_install_root: bool,
+    pub watchman: bool,
+    pub subcommand_context: bool,
+    pub advisories: bool,
+    pub network_sandbox: bool,
+    pub build_script_sandbox: bool,
 }
 
 fn deserialize_build_std<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
@@ -466,10 +513,30 @@ impl CliUnstable {
             "multitarget" => self.multitarget = parse_empty(k, v)?,
             "rustdoc-map" => self.rustdoc_map = parse_empty(k, v)?,
             "terminal-width" => self.terminal_width = Some(parse_usize_opt(v)?),
-            "namespaced-features" => self.namespaced_features = parse_empty(k, v)?,
             "weak-dep-features" => self.weak_dep_features = parse_empty(k, v)?,
             "extra-link-arg" => self.extra_link_arg = parse_empty(k, v)?,
             "credential-process" => self.credential_process = parse_empty(k, v)?,
+            "incremental-dir" => self.incremental_dir = parse_empty(k, v)?,
+            "lockfile-version" => self.lockfile_version = parse_empty(k, v)?,
+            "pubgrub" => self.pubgrub = parse_empty(k, v)?,
+            "resolve-cache" => self.resolve_cache = parse_empty(k, v)?,
+            "gitoxide" => self.gitoxide = parse_empty(k, v)?,
+            "git-shallow" => self.git_shallow = parse_empty(k, v)?,
+            "asymmetric-token" => self.asymmetric_token = parse_empty(k, v)?,
+            "index-store" => self.index_store = parse_empty(k, v)?,
+            "registry-overlay" => self.registry_overlay = parse_empty(k, v)?,
+            "crate-signing" => self.crate_signing = parse_empty(k, v)?,
+            "test-cache" => self.test_cache = parse_empty(k, v)?,
+            "parallel-doctests" => self.parallel_doctests = parse_empty(k, v)?,
+            "parallel-install" => self.parallel_install = parse_empty(k, v)?,
+            "install-locked" => self.install_locked = parse_empty(k, v)?,
+            "prebuilt-install" => self.prebuilt_install = parse_empty(k, v)?,
+            "local-install-root" => self.local_install_root = parse_empty(k, v)?,
+            "watchman" => self.watchman = parse_empty(k, v)?,
+            "subcommand-context" => self.subcommand_context = parse_empty(k, v)?,
+            "advisories" => self.advisories = parse_empty(k, v)?,
+            "network-sandbox" => self.network_sandbox = parse_empty(k, v)?,
+            "build-script-sandbox" => self.build_script_sandbox = parse_empty(k, v)?,
             _ => bail!("unknown `-Z` flag specified: {}", k),
         }
 
@@ -478,14 +545,23 @@ impl CliUnstable {
 
     /// Generates an error if `-Z unstable-options` was not used.
     /// Intended to be used when a user passes a command-line flag that
-    /// requires `-Z unstable-options`.
-    pub fn fail_if_stable_opt(&self, flag: &str, issue: u32) -> CargoResult<()> {
+    /// requires `-Z unstable-options`. The error points to the tracking
+    /// `issue` of the flag, or to the unstable chapter of the documentation
+    /// for flags which don't have one yet.
+    pub fn fail_if_stable_opt(&self, flag: &str, issue: Option<u32>) -> CargoResult<()> {
         if !self.unstable_options {
-            let see = format!(
-                "See https://github.com/rust-lang/cargo/issues/{} for more \
-                 information about the `{}` flag.",
-                issue, flag
-            );
+            let see = match issue {
+                Some(issue) => format!(
+                    "See https://github.com/rust-lang/cargo/issues/{} for more \
+                     information about the `{}` flag.",
+                    issue, flag
+                ),
+                None => format!(
+                    "See https://doc.rust-lang.org/nightly/cargo/reference/unstable.html \
+                     for more information about the `{}` flag.",
+                    flag
+                ),
+            };
             if nightly_features_allowed() {
                 bail!(
                     "the `{}` flag is unstable, pass `-Z unstable-options` to enable it\n\
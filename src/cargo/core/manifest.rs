@@ -16,7 +16,7 @@ use crate::core::{Dependency, PackageId, PackageIdSpec, SourceId, Summary};
 use crate::core::{Edition, Feature, Features, WorkspaceConfig};
 use crate::util::errors::*;
 use crate::util::interning::InternedString;
-use crate::util::toml::{TomlManifest, TomlProfiles};
+use crate::util::toml::{TomlDoc, TomlManifest, TomlProfiles};
 use crate::util::{short_hash, Config, Filesystem};
 
 pub enum EitherManifest {
@@ -39,6 +39,7 @@ pub struct Manifest {
     custom_metadata: Option<toml::Value>,
     profiles: Option<TomlProfiles>,
     publish: Option<Vec<String>>,
+    publish_name: Option<InternedString>,
     publish_lockfile: bool,
     replace: Vec<(PackageIdSpec, Dependency)>,
     patch: HashMap<Url, Vec<Dependency>>,
@@ -46,10 +47,12 @@ pub struct Manifest {
     original: Rc<TomlManifest>,
     unstable_features: Features,
     edition: Edition,
+    rust_version: Option<String>,
     im_a_teapot: Option<bool>,
     default_run: Option<String>,
     metabuild: Option<Vec<String>>,
     resolve_behavior: Option<ResolveBehavior>,
+    doc: Option<TomlDoc>,
 }
 
 /// When parsing `Cargo.toml`, some warnings should silenced
@@ -197,6 +200,9 @@ struct TargetInner {
     // compiler is handled elsewhere.
     src_path: TargetSourcePath,
     required_features: Option<Vec<String>>,
+    /// The optional dependencies declared by the `dependencies` field of a
+    /// binary or example, which are only linked into this target.
+    dependencies: Vec<String>,
     tested: bool,
     benched: bool,
     doc: bool,
@@ -349,6 +355,7 @@ compact_debug! {
                 name
                 src_path
                 required_features
+                dependencies
                 tested
                 benched
                 doc
@@ -373,17 +380,20 @@ impl Manifest {
         custom_metadata: Option<toml::Value>,
         profiles: Option<TomlProfiles>,
         publish: Option<Vec<String>>,
+        publish_name: Option<InternedString>,
         publish_lockfile: bool,
         replace: Vec<(PackageIdSpec, Dependency)>,
         patch: HashMap<Url, Vec<Dependency>>,
         workspace: WorkspaceConfig,
         unstable_features: Features,
         edition: Edition,
+        rust_version: Option<String>,
         im_a_teapot: Option<bool>,
         default_run: Option<String>,
         original: Rc<TomlManifest>,
         metabuild: Option<Vec<String>>,
         resolve_behavior: Option<ResolveBehavior>,
+        doc: Option<TomlDoc>,
     ) -> Manifest {
         Manifest {
             summary,
@@ -396,17 +406,20 @@ impl Manifest {
             custom_metadata,
             profiles,
             publish,
+            publish_name,
             replace,
             patch,
             workspace,
             unstable_features,
             edition,
+            rust_version,
             original,
             im_a_teapot,
             default_run,
             publish_lockfile,
             metabuild,
             resolve_behavior,
+            doc,
         }
     }
 
@@ -456,6 +469,11 @@ impl Manifest {
     pub fn publish(&self) -> &Option<Vec<String>> {
         &self.publish
     }
+    /// The name the package is published under, from the `publish-name`
+    /// field, if it differs from its name.
+    pub fn publish_name(&self) -> Option<InternedString> {
+        self.publish_name
+    }
     pub fn replace(&self) -> &[(PackageIdSpec, Dependency)] {
         &self.replace
     }
@@ -520,6 +538,17 @@ impl Manifest {
         self.edition
     }
 
+    /// The minimum version of rustc supported by the package, from the
+    /// `rust-version` field.
+    pub fn rust_version(&self) -> Option<&str> {
+        self.rust_version.as_deref()
+    }
+
+    /// The `[doc]` table, which sets how the package is documented.
+    pub fn doc(&self) -> Option<&TomlDoc> {
+        self.doc.as_ref()
+    }
+
     pub fn custom_metadata(&self) -> Option<&toml::Value> {
         self.custom_metadata.as_ref()
     }
@@ -605,6 +634,7 @@ impl Target {
                 name: String::new(),
                 src_path,
                 required_features: None,
+                dependencies: Vec::new(),
                 doc: false,
                 doctest: false,
                 harness: true,
@@ -742,6 +772,9 @@ impl Target {
     pub fn required_features(&self) -> Option<&Vec<String>> {
         self.inner.required_features.as_ref()
     }
+    pub fn dependencies(&self) -> &[String] {
+        &self.inner.dependencies
+    }
     pub fn kind(&self) -> &TargetKind {
         &self.inner.kind
     }
@@ -891,6 +924,10 @@ impl Target {
         Arc::make_mut(&mut self.inner).required_features = required_features;
         self
     }
+    pub fn set_dependencies(&mut self, dependencies: Vec<String>) -> &mut Target {
+        Arc::make_mut(&mut self.inner).dependencies = dependencies;
+        self
+    }
 
     pub fn description_named(&self) -> String {
         match self.kind() {
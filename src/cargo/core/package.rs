@@ -2,7 +2,9 @@ use std::cell::{Cell, Ref, RefCell, RefMut};
 use std::cmp::Ordering;
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt;
+use std::fs::{File, OpenOptions};
 use std::hash;
+use std::io::{Read, Write};
 use std::mem;
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
@@ -10,7 +12,7 @@ use std::time::{Duration, Instant};
 
 use anyhow::Context;
 use bytesize::ByteSize;
-use curl::easy::{Easy, HttpVersion};
+use curl::easy::{Easy, HttpVersion, List};
 use curl::multi::{EasyHandle, Multi};
 use lazycell::LazyCell;
 use log::{debug, warn};
@@ -29,7 +31,7 @@ use crate::util::config::PackageCacheLock;
 use crate::util::errors::{CargoResult, CargoResultExt, HttpNot200};
 use crate::util::interning::InternedString;
 use crate::util::network::Retry;
-use crate::util::{self, internal, Config, Progress, ProgressStyle};
+use crate::util::{self, internal, paths, Config, Progress, ProgressStyle};
 
 pub const MANIFEST_PREAMBLE: &str = "\
 # THIS FILE IS AUTOMATICALLY GENERATED BY CARGO
@@ -166,6 +168,11 @@ impl Package {
     pub fn publish(&self) -> &Option<Vec<String>> {
         self.manifest().publish()
     }
+    /// Gets the name the package is published under, which is its name
+    /// unless `publish-name` is set.
+    pub fn published_name(&self) -> InternedString {
+        self.manifest().publish_name().unwrap_or_else(|| self.name())
+    }
     /// Returns `true` if this package is a proc-macro.
     pub fn proc_macro(&self) -> bool {
         self.targets().iter().any(|target| target.proc_macro())
@@ -199,7 +206,7 @@ impl Package {
         self.targets().iter().any(|t| t.is_example() || t.is_bin())
     }
 
-    pub fn serialized(&self, config: &Config) -> SerializedPackage {
+    pub fn serialized(&self) -> SerializedPackage {
         let summary = self.manifest().summary();
         let package_id = summary.package_id();
         let manmeta = self.manifest().metadata();
@@ -213,27 +220,19 @@ impl Package {
             .filter(|t| t.src_path().is_path())
             .cloned()
             .collect();
-        let features = if config.cli_unstable().namespaced_features {
-            // Convert Vec<FeatureValue> to Vec<InternedString>
-            summary
-                .features()
-                .iter()
-                .map(|(k, v)| {
-                    (
-                        *k,
-                        v.iter()
-                            .map(|fv| InternedString::new(&fv.to_string()))
-                            .collect(),
-                    )
-                })
-                .collect()
-        } else {
-            self.manifest()
-                .original()
-                .features()
-                .cloned()
-                .unwrap_or_default()
-        };
+        // Convert Vec<FeatureValue> to Vec<InternedString>
+        let features = summary
+            .features()
+            .iter()
+            .map(|(k, v)| {
+                (
+                    *k,
+                    v.iter()
+                        .map(|fv| InternedString::new(&fv.to_string()))
+                        .collect(),
+                )
+            })
+            .collect();
 
         SerializedPackage {
             name: package_id.name(),
@@ -374,6 +373,18 @@ struct Download<'cfg> {
     /// Actual downloaded data, updated throughout the lifetime of this download.
     data: RefCell<Vec<u8>>,
 
+    /// The file the downloaded data is also written to, so that the download
+    /// can be resumed if Cargo is interrupted, along with its path.
+    partial: RefCell<Option<File>>,
+    partial_path: Option<PathBuf>,
+
+    /// The number of bytes which were already downloaded when the current
+    /// transfer started, and which the server is asked to skip.
+    resumed_from: Cell<u64>,
+
+    /// The status code of the latest response, read from its headers.
+    status: Cell<u32>,
+
     /// The URL that we're downloading from, cached here for error messages and
     /// reenqueuing.
     url: String,
@@ -630,13 +641,18 @@ impl<'a, 'cfg> Downloads<'a, 'cfg> {
         let pkg = source
             .download(id)
             .chain_err(|| anyhow::format_err!("unable to get packages from source"))?;
-        let (url, descriptor) = match pkg {
+        let (url, descriptor, partial_path, authorization) = match pkg {
             MaybePackage::Ready(pkg) => {
                 debug!("{} doesn't need a download", id);
                 assert!(slot.fill(pkg).is_ok());
                 return Ok(Some(slot.borrow().unwrap()));
             }
-            MaybePackage::Download { url, descriptor } => (url, descriptor),
+            MaybePackage::Download {
+                url,
+                descriptor,
+                partial,
+                authorization,
+            } => (url, descriptor, partial, authorization),
         };
 
         // Ok we're going to download this crate, so let's set up all our
@@ -653,6 +669,14 @@ impl<'a, 'cfg> Downloads<'a, 'cfg> {
         handle.url(&url)?;
         handle.follow_location(true)?; // follow redirects
 
+        // libcurl only sends the token to the host of `url`, and not to the
+        // ones it redirects to.
+        if let Some(authorization) = &authorization {
+            let mut headers = List::new();
+            headers.append(&format!("Authorization: {}", authorization))?;
+            handle.http_headers(headers)?;
+        }
+
         // Enable HTTP/2 to be used as it'll allow true multiplexing which makes
         // downloads much faster.
         //
@@ -680,15 +704,22 @@ impl<'a, 'cfg> Downloads<'a, 'cfg> {
         // reduces the number of connections done to a more manageable state.
         try_old_curl!(handle.pipewait(true), "pipewait");
 
+        handle.header_function(move |header| {
+            if let Some(status) = parse_status_line(header) {
+                tls::with(|downloads| {
+                    if let Some(downloads) = downloads {
+                        downloads.pending[&token].0.status.set(status);
+                    }
+                });
+            }
+            true
+        })?;
+
         handle.write_function(move |buf| {
             debug!("{} - {} bytes of data", token, buf.len());
             tls::with(|downloads| {
                 if let Some(downloads) = downloads {
-                    downloads.pending[&token]
-                        .0
-                        .data
-                        .borrow_mut()
-                        .extend_from_slice(buf);
+                    downloads.pending[&token].0.write(buf);
                 }
             });
             Ok(buf.len())
@@ -715,9 +746,36 @@ impl<'a, 'cfg> Downloads<'a, 'cfg> {
                 .status("Downloading", "crates ...")?;
         }
 
+        // Pick up where a previous, interrupted, download left off.
+        let mut data = Vec::new();
+        let mut partial = None;
+        if let Some(path) = &partial_path {
+            if let Ok(mut file) = File::open(path) {
+                if file.read_to_end(&mut data).is_err() {
+                    data.clear();
+                }
+            }
+            partial = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| debug!("failed to open {}: {}", path.display(), e))
+                .ok();
+            if partial.is_none() {
+                data.clear();
+            }
+        }
+        if !data.is_empty() {
+            debug!("resuming download of {} from byte {}", id, data.len());
+        }
+
         let dl = Download {
             token,
-            data: RefCell::new(Vec::new()),
+            data: RefCell::new(data),
+            partial: RefCell::new(partial),
+            partial_path,
+            resumed_from: Cell::new(0),
+            status: Cell::new(0),
             id,
             url,
             descriptor,
@@ -756,10 +814,31 @@ impl<'a, 'cfg> Downloads<'a, 'cfg> {
                 .pending
                 .remove(&token)
                 .expect("got a token for a non-in-progress transfer");
-            let data = mem::take(&mut *dl.data.borrow_mut());
-            let mut handle = self.set.multi.remove(handle)?;
+            let handle = self.set.multi.remove(handle)?;
             self.pending_ids.remove(&dl.id);
 
+            // A server which doesn't support ranges answers a resumed download
+            // with the whole file, which curl rejects, or with 416 if the data
+            // kept doesn't fit the file. The data kept can't be trusted after
+            // these, nor after an error page, so it's thrown away, and a
+            // resumed download starts over from the beginning.
+            let (range_failed, http_error) = match &result {
+                Err(e) => (e.is_range_error(), false),
+                Ok(()) => {
+                    let code = handle.response_code()?;
+                    (code == 416, code != 0 && !(200..300).contains(&code))
+                }
+            };
+            if range_failed || http_error {
+                dl.discard_partial();
+            }
+            if range_failed && dl.resumed_from.get() > 0 {
+                debug!("server rejected range request for {}, starting over", dl.id);
+                self.pending_ids.insert(dl.id);
+                self.enqueue(dl, handle)?;
+                continue;
+            }
+
             // Check if this was a spurious error. If it was a spurious error
             // then we want to re-enqueue our request for another attempt and
             // then we wait for another request to finish.
@@ -793,7 +872,7 @@ impl<'a, 'cfg> Downloads<'a, 'cfg> {
                         }
 
                         let code = handle.response_code()?;
-                        if code != 200 && code != 0 {
+                        if code != 200 && code != 206 && code != 0 {
                             let url = handle.effective_url()?.unwrap_or(url);
                             return Err(HttpNot200 {
                                 code,
@@ -803,10 +882,18 @@ impl<'a, 'cfg> Downloads<'a, 'cfg> {
                         }
                         Ok(())
                     })
-                    .chain_err(|| format!("failed to download from `{}`", dl.url))?
+                    .chain_err(|| format!("failed to download from `{}`", dl.url))
+            };
+            let ret = match ret {
+                Ok(ret) => ret,
+                Err(e) => return self.fall_back(dl.id, e),
             };
             match ret {
-                Some(()) => break (dl, data),
+                Some(()) => {
+                    let data = mem::take(&mut *dl.data.borrow_mut());
+                    break (dl, data);
+                }
+                // Retries continue from the data received so far.
                 None => {
                     self.pending_ids.insert(dl.id);
                     self.enqueue(dl, handle)?
@@ -814,6 +901,10 @@ impl<'a, 'cfg> Downloads<'a, 'cfg> {
             }
         };
 
+        // The data is handed over to the source below, which verifies it, so
+        // the download won't be resumed from it even if it's bad.
+        dl.discard_partial();
+
         // If the progress bar isn't enabled then we still want to provide some
         // semblance of progress of how we're downloading crates, and if the
         // progress bar is enabled this provides a good log of what's happening.
@@ -847,7 +938,13 @@ impl<'a, 'cfg> Downloads<'a, 'cfg> {
             .get_mut(dl.id.source_id())
             .ok_or_else(|| internal(format!("couldn't find source for `{}`", dl.id)))?;
         let start = Instant::now();
-        let pkg = source.finish_download(dl.id, data)?;
+        let pkg = match source.finish_download(dl.id, data) {
+            Ok(pkg) => pkg,
+            Err(e) => {
+                drop(sources);
+                return self.fall_back(dl.id, e);
+            }
+        };
 
         // Assume that no time has passed while we were calling
         // `finish_download`, update all speed checks and timeout limits of all
@@ -863,7 +960,29 @@ impl<'a, 'cfg> Downloads<'a, 'cfg> {
         Ok(slot.borrow().unwrap())
     }
 
-    fn enqueue(&mut self, dl: Download<'cfg>, handle: Easy) -> CargoResult<()> {
+    /// Handles a failed download of `id` by downloading it again from
+    /// another location, if its source has one, such as the next one of a
+    /// list of mirrors. Otherwise `err` is returned.
+    fn fall_back(&mut self, id: PackageId, err: anyhow::Error) -> CargoResult<&'a Package> {
+        let retry = {
+            let mut sources = self.set.sources.borrow_mut();
+            let source = sources
+                .get_mut(id.source_id())
+                .ok_or_else(|| internal(format!("couldn't find source for `{}`", id)))?;
+            source.fall_back(id, &err)?
+        };
+        if !retry {
+            return Err(err);
+        }
+        match self.start(id)? {
+            Some(pkg) => Ok(pkg),
+            None => self.wait(),
+        }
+    }
+
+    fn enqueue(&mut self, dl: Download<'cfg>, mut handle: Easy) -> CargoResult<()> {
+        let resumed_from = dl.data.borrow().len() as u64;
+        handle.resume_from(resumed_from)?;
         let mut handle = self.set.multi.add(handle)?;
         let now = Instant::now();
         handle.set_token(dl.token)?;
@@ -872,7 +991,9 @@ impl<'a, 'cfg> Downloads<'a, 'cfg> {
         self.next_speed_check_bytes_threshold
             .set(u64::from(self.timeout.low_speed_limit));
         dl.timed_out.set(None);
-        dl.current.set(0);
+        dl.resumed_from.set(resumed_from);
+        dl.status.set(0);
+        dl.current.set(resumed_from);
         dl.total.set(0);
         self.pending.insert(dl.token, (dl, handle));
         Ok(())
@@ -934,6 +1055,12 @@ impl<'a, 'cfg> Downloads<'a, 'cfg> {
 
     fn progress(&self, token: usize, total: u64, cur: u64) -> bool {
         let dl = &self.pending[&token].0;
+        // Sizes reported by curl only cover the current transfer.
+        let (total, cur) = if total > 0 {
+            (dl.resumed_from.get() + total, dl.resumed_from.get() + cur)
+        } else {
+            (0, dl.resumed_from.get() + cur)
+        };
         dl.total.set(total);
         let now = Instant::now();
         if cur != dl.current.get() {
@@ -1021,6 +1148,22 @@ impl<'a, 'cfg> Downloads<'a, 'cfg> {
                 }
                 if remaining > 0 && dur > Duration::from_millis(500) {
                     msg.push_str(&format!(", remaining bytes: {}", ByteSize(remaining)));
+                    // Also show how far along the biggest download is, as it
+                    // is usually the one everything ends up waiting for.
+                    let largest = self
+                        .pending
+                        .values()
+                        .map(|(dl, _)| dl)
+                        .filter(|dl| dl.total.get() > 0)
+                        .max_by_key(|dl| dl.total.get());
+                    if let Some(dl) = largest {
+                        msg.push_str(&format!(
+                            ", {} {}/{}",
+                            dl.id.name(),
+                            ByteSize(dl.current.get()),
+                            ByteSize(dl.total.get())
+                        ));
+                    }
                 }
             }
         }
@@ -1028,6 +1171,44 @@ impl<'a, 'cfg> Downloads<'a, 'cfg> {
     }
 }
 
+impl<'cfg> Download<'cfg> {
+    /// Adds data received from the server.
+    fn write(&self, buf: &[u8]) {
+        // Error pages aren't part of the `.crate` file.
+        let status = self.status.get();
+        if status != 0 && status != 200 && status != 206 {
+            return;
+        }
+        self.data.borrow_mut().extend_from_slice(buf);
+        let mut partial = self.partial.borrow_mut();
+        if let Some(file) = &mut *partial {
+            if let Err(e) = file.write_all(buf) {
+                debug!("failed to save partial download of {}: {}", self.id, e);
+                *partial = None;
+            }
+        }
+    }
+
+    /// Throws away the data received so far, along with the file keeping it.
+    fn discard_partial(&self) {
+        self.data.borrow_mut().clear();
+        drop(self.partial.replace(None));
+        if let Some(path) = &self.partial_path {
+            let _ = paths::remove_file(path);
+        }
+    }
+}
+
+/// Returns the status code of a `HTTP/1.1 200 OK` status line, or `None` for
+/// any other header.
+fn parse_status_line(header: &[u8]) -> Option<u32> {
+    let header = std::str::from_utf8(header).ok()?;
+    if !header.starts_with("HTTP/") {
+        return None;
+    }
+    header.split_whitespace().nth(1)?.parse().ok()
+}
+
 #[derive(Copy, Clone)]
 enum WhyTick<'a> {
     DownloadStarted,
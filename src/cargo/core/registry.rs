@@ -11,6 +11,10 @@ use log::{debug, trace};
 use semver::VersionReq;
 use url::Url;
 
+pub use self::query_cache::QueryCache;
+
+mod query_cache;
+
 /// Source of information about a group of packages.
 ///
 /// See also `core::Source`.
@@ -78,6 +82,9 @@ pub struct PackageRegistry<'cfg> {
     patches: HashMap<CanonicalUrl, Vec<Summary>>,
     patches_locked: bool,
     patches_available: HashMap<CanonicalUrl, Vec<PackageId>>,
+
+    // Cache of registry queries for `-Z resolve-cache`.
+    query_cache: Option<QueryCache>,
 }
 
 /// A map of all "locked packages" which is filled in when parsing a lock file
@@ -121,6 +128,7 @@ impl<'cfg> PackageRegistry<'cfg> {
             patches: HashMap::new(),
             patches_locked: false,
             patches_available: HashMap::new(),
+            query_cache: None,
         })
     }
 
@@ -172,6 +180,23 @@ impl<'cfg> PackageRegistry<'cfg> {
         Ok(())
     }
 
+    /// Answers queries of registries from `cache` where possible.
+    pub fn set_query_cache(&mut self, cache: QueryCache) {
+        self.query_cache = Some(cache);
+    }
+
+    /// Saves the queries answered so far to the cache set with
+    /// `set_query_cache`, if any, and the caches of the sources.
+    pub fn save_caches(&mut self) -> CargoResult<()> {
+        if let Some(cache) = &mut self.query_cache {
+            cache.save(self.config);
+        }
+        for (_, source) in self.sources.sources_mut() {
+            source.save_caches()?;
+        }
+        Ok(())
+    }
+
     pub fn add_preloaded(&mut self, source: Box<dyn Source + 'cfg>) {
         self.add_source(source, Kind::Locked);
     }
@@ -578,6 +603,8 @@ impl<'cfg> Registry for PackageRegistry<'cfg> {
                         };
                         return if fuzzy {
                             source.fuzzy_query(dep, callback)
+                        } else if let Some(cache) = &mut self.query_cache {
+                            cache.query(dep, source, self.config, callback)
                         } else {
                             source.query(dep, callback)
                         };
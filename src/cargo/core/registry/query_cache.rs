@@ -0,0 +1,386 @@
+//! A cache of registry query results kept in the target directory.
+//!
+//! With `-Z resolve-cache`, the summaries which registries return for the
+//! locked dependencies of a workspace are saved to
+//! `target/.resolve-cache.json`. On the next run they are read back from that
+//! single file instead of being looked up and parsed from the index entries of
+//! each package one by one, which adds up for workspaces with thousands of
+//! packages in their lock file.
+//!
+//! The cache is only kept for the same version of Cargo and the same
+//! `Cargo.lock`, which is the set of packages the workspace depends on. On
+//! top of that the entries of a registry are only used while its index is in
+//! the state it was when they were saved, see `Source::index_version`.
+//! Registries without a known index state, such as local registries, aren't
+//! cached at all.
+//!
+//! The cache is saved once the workspace is resolved, under a lock so that
+//! Cargos running at the same time take turns, and by renaming a temporary
+//! file over the old one so that the cache is never read half written.
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use log::{debug, info};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use crate::core::dependency::DepKind;
+use crate::core::{Dependency, PackageId, Source, SourceId, Summary, Workspace};
+use crate::util::errors::CargoResult;
+use crate::util::interning::InternedString;
+use crate::util::{paths, Config, Filesystem, StableHasher};
+
+pub struct QueryCache {
+    target_dir: Filesystem,
+    cache_location: PathBuf,
+    lock_location: PathBuf,
+    weak_dep_features: bool,
+    dirty: bool,
+    data: CacheData,
+    /// Summaries which were already decoded during this run.
+    decoded: HashMap<(SourceId, String), Vec<Summary>>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct CacheData {
+    /// Hash of the Cargo version and the contents of `Cargo.lock`.
+    fingerprint: u64,
+    /// Cached queries of each registry, keyed by the URL of the registry.
+    registries: BTreeMap<String, CachedRegistry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedRegistry {
+    /// The state of the index the queries were answered with.
+    index_version: InternedString,
+    /// Summaries returned for each query, keyed by the name and version
+    /// requirement of the dependency.
+    queries: BTreeMap<String, Vec<CachedSummary>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedSummary {
+    name: InternedString,
+    version: Version,
+    deps: Vec<CachedDependency>,
+    features: BTreeMap<InternedString, Vec<InternedString>>,
+    checksum: Option<String>,
+    links: Option<InternedString>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedDependency {
+    name: InternedString,
+    rename: Option<InternedString>,
+    /// The version requirement, if one was specified.
+    req: Option<String>,
+    source: CachedSourceId,
+    registry: Option<CachedSourceId>,
+    kind: Option<String>,
+    optional: bool,
+    public: bool,
+    allow_prerelease: bool,
+    default_features: bool,
+    features: Vec<InternedString>,
+    target: Option<String>,
+}
+
+/// A `SourceId`, which is stored along with its `precise` field since that
+/// isn't part of its URL for all kinds of sources.
+#[derive(Serialize, Deserialize)]
+struct CachedSourceId {
+    url: String,
+    precise: Option<String>,
+}
+
+impl QueryCache {
+    /// Loads the cache of the given workspace, or starts an empty one if it
+    /// doesn't exist or is out of date.
+    pub fn load(ws: &Workspace<'_>) -> QueryCache {
+        let target_dir = ws.target_dir();
+        let cache_location = target_dir.join(".resolve-cache.json").into_path_unlocked();
+        let lock_location = ws.root().join("Cargo.lock");
+        let weak_dep_features = ws.config().cli_unstable().weak_dep_features;
+        let fingerprint = fingerprint(&lock_location, weak_dep_features);
+        let data = match read(&cache_location) {
+            Ok(data) if data.fingerprint == fingerprint => {
+                debug!("reusing existing resolve cache");
+                data
+            }
+            Ok(_) => {
+                debug!("different lock file, creating new resolve cache");
+                CacheData::default()
+            }
+            Err(e) => {
+                debug!("failed to read resolve cache: {}", e);
+                CacheData::default()
+            }
+        };
+        return QueryCache {
+            target_dir,
+            cache_location,
+            lock_location,
+            weak_dep_features,
+            dirty: false,
+            data,
+            decoded: HashMap::new(),
+        };
+
+        fn read(path: &Path) -> CargoResult<CacheData> {
+            let json = paths::read(path)?;
+            Ok(serde_json::from_str(&json)?)
+        }
+    }
+
+    /// Queries `source` for `dep`, answering from the cache if possible.
+    pub fn query(
+        &mut self,
+        dep: &Dependency,
+        source: &mut dyn Source,
+        config: &Config,
+        f: &mut dyn FnMut(Summary),
+    ) -> CargoResult<()> {
+        // Only exact requirements are cached, which is what all the
+        // dependencies of a workspace with an up to date lock file become.
+        // Pre-releases allowed with `allow-prerelease` are left to
+        // `Dependency::matches`, so the registry returns all versions then.
+        if !dep.is_locked() || dep.allows_prerelease() || !dep.source_id().is_registry() {
+            return source.query(dep, f);
+        }
+        let index_version = match source.index_version() {
+            Some(version) => version,
+            None => return source.query(dep, f),
+        };
+        let source_id = source.source_id();
+        let url = source_id.as_url().to_string();
+        let key = format!("{} {}", dep.package_name(), dep.version_req());
+
+        if let Some(summaries) = self.get(source_id, &url, index_version, &key, config) {
+            debug!("resolve cache hit for `{}` from {}", key, url);
+            for summary in summaries {
+                f(summary);
+            }
+            return Ok(());
+        }
+
+        let mut summaries = Vec::new();
+        source.query(dep, &mut |summary| {
+            summaries.push(summary.clone());
+            f(summary)
+        })?;
+        let registry = self
+            .data
+            .registries
+            .entry(url)
+            .or_insert_with(|| CachedRegistry {
+                index_version,
+                queries: BTreeMap::new(),
+            });
+        if registry.index_version != index_version {
+            registry.index_version = index_version;
+            registry.queries.clear();
+        }
+        let cached = summaries.iter().map(CachedSummary::new).collect();
+        registry.queries.insert(key.clone(), cached);
+        self.decoded.insert((source_id, key), summaries);
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Saves the cache if it changed, which is once the workspace is
+    /// resolved.
+    pub fn save(&mut self, config: &Config) {
+        // The lock file may have been updated in the meantime, the cache
+        // belongs to the new one since that's what the next run will use.
+        let fingerprint = fingerprint(&self.lock_location, self.weak_dep_features);
+        if !self.dirty && fingerprint == self.data.fingerprint {
+            return;
+        }
+        self.data.fingerprint = fingerprint;
+        // The cache only saves time, so the command goes on without it if it
+        // can't be written, for example because the target directory is
+        // read-only.
+        match self.write(config) {
+            Ok(()) => {
+                self.dirty = false;
+                info!("updated resolve cache");
+            }
+            Err(e) => crate::display_warning_with_error(
+                &format!(
+                    "failed to write the resolve cache `{}`",
+                    self.cache_location.display()
+                ),
+                &e,
+                &mut config.shell(),
+            ),
+        }
+    }
+
+    fn write(&self, config: &Config) -> CargoResult<()> {
+        let json = serde_json::to_string(&self.data)?;
+        let _lock = self
+            .target_dir
+            .open_rw(".resolve-cache.lock", config, "resolve cache")?;
+        let mut file = tempfile::Builder::new()
+            .prefix(".resolve-cache")
+            .tempfile_in(self.target_dir.as_path_unlocked())?;
+        file.write_all(json.as_bytes())?;
+        file.persist(&self.cache_location).map_err(|e| e.error)?;
+        Ok(())
+    }
+
+    fn get(
+        &mut self,
+        source_id: SourceId,
+        url: &str,
+        index_version: InternedString,
+        key: &str,
+        config: &Config,
+    ) -> Option<Vec<Summary>> {
+        let registry = self.data.registries.get(url)?;
+        if registry.index_version != index_version {
+            return None;
+        }
+        let key = (source_id, key.to_string());
+        if let Some(summaries) = self.decoded.get(&key) {
+            return Some(summaries.clone());
+        }
+        let summaries = registry
+            .queries
+            .get(&key.1)?
+            .iter()
+            .map(|summary| summary.decode(source_id, config))
+            .collect::<CargoResult<Vec<_>>>();
+        match summaries {
+            Ok(summaries) => {
+                self.decoded.insert(key, summaries.clone());
+                Some(summaries)
+            }
+            Err(e) => {
+                info!("failed to decode cached `{}` from {}: {}", key.1, url, e);
+                None
+            }
+        }
+    }
+}
+
+fn fingerprint(lock_location: &Path, weak_dep_features: bool) -> u64 {
+    let mut hasher = StableHasher::new();
+    crate::version().to_string().hash(&mut hasher);
+    // Registries leave out entries using weak dependency features without it.
+    weak_dep_features.hash(&mut hasher);
+    paths::read(lock_location).ok().hash(&mut hasher);
+    hasher.finish()
+}
+
+impl CachedSummary {
+    fn new(summary: &Summary) -> CachedSummary {
+        CachedSummary {
+            name: summary.name(),
+            version: summary.version().clone(),
+            deps: summary
+                .dependencies()
+                .iter()
+                .map(CachedDependency::new)
+                .collect(),
+            features: summary
+                .features()
+                .iter()
+                .map(|(feature, values)| {
+                    let values = values.iter().map(|fv| fv.to_string().into()).collect();
+                    (*feature, values)
+                })
+                .collect(),
+            checksum: summary.checksum().map(|s| s.to_string()),
+            links: summary.links(),
+        }
+    }
+
+    fn decode(&self, source_id: SourceId, config: &Config) -> CargoResult<Summary> {
+        let pkg_id = PackageId::new(self.name, &self.version, source_id)?;
+        let deps = self
+            .deps
+            .iter()
+            .map(CachedDependency::decode)
+            .collect::<CargoResult<Vec<_>>>()?;
+        // The features include those implied by optional dependencies, which
+        // are accepted as if they were listed explicitly.
+        let mut summary = Summary::new(config, pkg_id, deps, &self.features, self.links)?;
+        if let Some(checksum) = &self.checksum {
+            summary.set_checksum(checksum.clone());
+        }
+        Ok(summary)
+    }
+}
+
+impl CachedDependency {
+    fn new(dep: &Dependency) -> CachedDependency {
+        CachedDependency {
+            name: dep.package_name(),
+            rename: dep.explicit_name_in_toml(),
+            req: if dep.specified_req() {
+                Some(dep.version_req().to_string())
+            } else {
+                None
+            },
+            source: CachedSourceId::new(dep.source_id()),
+            registry: dep.registry_id().map(CachedSourceId::new),
+            kind: match dep.kind() {
+                DepKind::Normal => None,
+                DepKind::Development => Some("dev".to_string()),
+                DepKind::Build => Some("build".to_string()),
+            },
+            optional: dep.is_optional(),
+            public: dep.is_public(),
+            allow_prerelease: dep.allows_prerelease(),
+            default_features: dep.uses_default_features(),
+            features: dep.features().to_vec(),
+            target: dep.platform().map(|p| p.to_string()),
+        }
+    }
+
+    fn decode(&self) -> CargoResult<Dependency> {
+        let source_id = self.source.decode()?;
+        let mut dep = Dependency::parse_no_deprecated(self.name, self.req.as_deref(), source_id)?;
+        if let Some(rename) = self.rename {
+            dep.set_explicit_name_in_toml(rename);
+        }
+        if let Some(registry) = &self.registry {
+            dep.set_registry_id(registry.decode()?);
+        }
+        let kind = match self.kind.as_deref() {
+            None => DepKind::Normal,
+            Some("dev") => DepKind::Development,
+            Some("build") => DepKind::Build,
+            Some(kind) => anyhow::bail!("unknown dependency kind `{}`", kind),
+        };
+        let platform = match &self.target {
+            Some(target) => Some(target.parse()?),
+            None => None,
+        };
+        dep.set_kind(kind)
+            .set_public(self.public)
+            .set_optional(self.optional)
+            .set_allow_prerelease(self.allow_prerelease)
+            .set_default_features(self.default_features)
+            .set_features(self.features.iter().cloned())
+            .set_platform(platform);
+        Ok(dep)
+    }
+}
+
+impl CachedSourceId {
+    fn new(id: SourceId) -> CachedSourceId {
+        CachedSourceId {
+            url: id.as_url().to_string(),
+            precise: id.precise().map(|s| s.to_string()),
+        }
+    }
+
+    fn decode(&self) -> CargoResult<SourceId> {
+        Ok(SourceId::from_url(&self.url)?.with_precise(self.precise.clone()))
+    }
+}
@@ -10,7 +10,7 @@
 //! This module impl that cache in all the gory details
 
 use crate::core::resolver::context::Context;
-use crate::core::resolver::errors::describe_path;
+use crate::core::resolver::errors::describe_path_in_context;
 use crate::core::resolver::types::{ConflictReason, DepInfo, FeaturesSet};
 use crate::core::resolver::{ActivateError, ActivateResult, ResolveOpts};
 use crate::core::{Dependency, FeatureValue, PackageId, PackageIdSpec, Registry, Summary};
@@ -272,7 +272,7 @@ impl<'a> RegistryQueryer<'a> {
                     anyhow::format_err!(
                         "failed to get `{}` as a dependency of {}",
                         dep.package_name(),
-                        describe_path(&cx.parents.path_to_bottom(&candidate.package_id())),
+                        describe_path_in_context(cx, &candidate.package_id()),
                     )
                 })?;
                 Ok((dep, candidates, features))
@@ -341,6 +341,17 @@ pub fn resolve_features<'b>(
         ret.push((dep.clone(), Rc::new(base)));
     }
 
+    // Optional dev-dependencies enabled by a feature are only used when
+    // dev-dependencies are.
+    if !opts.dev_deps {
+        valid_dep_names.extend(
+            s.dependencies()
+                .iter()
+                .filter(|d| !d.is_transitive())
+                .map(|d| d.name_in_toml()),
+        );
+    }
+
     // This is a special case for command-line `--features
     // dep_name/feat_name` where `dep_name` does not exist. All other
     // validation is done either in `build_requirements` or
@@ -57,6 +57,12 @@
 //! Listed from most recent to oldest, these are some of the changes we've made
 //! to `Cargo.lock`'s serialization format:
 //!
+//! * Each package is written as an inline table on a single line of a
+//!   top-level `package` array, so that changes to one package never touch
+//!   the lines of its neighbors. The crates.io source is written as
+//!   `crates-io` instead of its full index URL, and the `[metadata]` table is
+//!   no longer written since nothing stores data in it anymore.
+//!
 //! * A `version` marker is now at the top of the lock file which is a way for
 //!   super-old Cargos (at least since this was implemented) to give a formal
 //!   error if they see a lock file from a super-future Cargo. Additionally as
@@ -115,14 +121,16 @@ use super::{Resolve, ResolveVersion};
 use crate::core::{Dependency, GitReference, Package, PackageId, SourceId, Workspace};
 use crate::util::errors::{CargoResult, CargoResultExt};
 use crate::util::interning::InternedString;
-use crate::util::{internal, Graph};
+use crate::util::{internal, Graph, IntoUrl};
 use anyhow::bail;
 use log::debug;
 use serde::de;
 use serde::ser;
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::str::FromStr;
 
 /// The `Cargo.lock` structure.
@@ -159,6 +167,7 @@ impl EncodableResolve {
 
         let mut version = match self.version {
             Some(3) => ResolveVersion::V3,
+            Some(4) => ResolveVersion::V4,
             Some(n) => bail!(
                 "lock file version `{}` was found, but this version of Cargo \
                  does not understand this lock file, perhaps Cargo needs \
@@ -194,14 +203,15 @@ impl EncodableResolve {
                 if !all_pkgs.insert(enc_id.clone()) {
                     anyhow::bail!("package `{}` is specified twice in the lockfile", pkg.name);
                 }
-                let id = match pkg.source.as_ref().or_else(|| path_deps.get(&pkg.name)) {
+                let source = pkg.source.map(|s| s.inner);
+                let id = match source.or_else(|| path_source(&path_deps, &pkg.name, &pkg.version)) {
                     // We failed to find a local package in the workspace.
                     // It must have been removed and should be ignored.
                     None => {
                         debug!("path dependency now missing {} v{}", pkg.name, pkg.version);
                         continue;
                     }
-                    Some(&source) => PackageId::new(&pkg.name, &pkg.version, source)?,
+                    Some(source) => PackageId::new(&pkg.name, &pkg.version, source)?,
                 };
 
                 // If a package has a checksum listed directly on it then record
@@ -259,7 +269,7 @@ impl EncodableResolve {
             // format. That means we have to handle the `None` case a bit more
             // carefully.
             match &enc_id.source {
-                Some(source) => by_source.get(source).cloned(),
+                Some(source) => by_source.get(&source.inner).cloned(),
                 None => {
                     // Look through all possible packages ids for this
                     // name/version. If there's only one `path` dependency then
@@ -290,9 +300,13 @@ impl EncodableResolve {
         };
 
         let mut g = Graph::new();
+        let mut yanked = HashSet::new();
 
-        for &(ref id, _) in live_pkgs.values() {
+        for &(ref id, pkg) in live_pkgs.values() {
             g.add(*id);
+            if pkg.yanked == Some(true) {
+                yanked.insert(*id);
+            }
         }
 
         for &(ref id, pkg) in live_pkgs.values() {
@@ -358,8 +372,9 @@ impl EncodableResolve {
 
         let mut unused_patches = Vec::new();
         for pkg in self.patch.unused {
-            let id = match pkg.source.as_ref().or_else(|| path_deps.get(&pkg.name)) {
-                Some(&src) => PackageId::new(&pkg.name, &pkg.version, src)?,
+            let source = pkg.source.map(|s| s.inner);
+            let id = match source.or_else(|| path_source(&path_deps, &pkg.name, &pkg.version)) {
+                Some(src) => PackageId::new(&pkg.name, &pkg.version, src)?,
                 None => continue,
             };
             unused_patches.push(id);
@@ -389,7 +404,7 @@ impl EncodableResolve {
             version = ResolveVersion::V2;
         }
 
-        Ok(Resolve::new(
+        let mut resolve = Resolve::new(
             g,
             replacements,
             HashMap::new(),
@@ -398,11 +413,33 @@ impl EncodableResolve {
             unused_patches,
             version,
             HashMap::new(),
-        ))
+        );
+        for id in yanked {
+            resolve.allow_yanked(id);
+        }
+        Ok(resolve)
     }
 }
 
-fn build_path_deps(ws: &Workspace<'_>) -> HashMap<String, SourceId> {
+/// Path sources of the workspace, keyed by package name. A name can be listed
+/// with more than one version, for example when several major versions of a
+/// crate are patched in from local paths.
+type PathDeps = HashMap<String, Vec<(String, SourceId)>>;
+
+/// Finds the path source of a package listed without a `source` in the lock
+/// file. If the version no longer matches, for example after a version bump,
+/// the only path source with that name is used.
+fn path_source(path_deps: &PathDeps, name: &str, version: &str) -> Option<SourceId> {
+    let candidates = path_deps.get(name)?;
+    let found = candidates.iter().find(|(v, _)| v == version);
+    match found {
+        Some((_, source)) => Some(*source),
+        None if candidates.len() == 1 => Some(candidates[0].1),
+        None => None,
+    }
+}
+
+fn build_path_deps(ws: &Workspace<'_>) -> PathDeps {
     // If a crate is **not** a path source, then we're probably in a situation
     // such as `cargo install` with a lock file from a remote dependency. In
     // that case we don't need to fixup any path dependencies (as they're not
@@ -412,13 +449,10 @@ fn build_path_deps(ws: &Workspace<'_>) -> HashMap<String, SourceId> {
         .filter(|p| p.package_id().source_id().is_path())
         .collect::<Vec<_>>();
 
-    let mut ret = HashMap::new();
+    let mut ret = PathDeps::new();
     let mut visited = HashSet::new();
     for member in members.iter() {
-        ret.insert(
-            member.package_id().name().to_string(),
-            member.package_id().source_id(),
-        );
+        insert(&mut ret, member);
         visited.insert(member.package_id().source_id());
     }
     for member in members.iter() {
@@ -438,7 +472,7 @@ fn build_path_deps(ws: &Workspace<'_>) -> HashMap<String, SourceId> {
     fn build_pkg(
         pkg: &Package,
         ws: &Workspace<'_>,
-        ret: &mut HashMap<String, SourceId>,
+        ret: &mut PathDeps,
         visited: &mut HashSet<SourceId>,
     ) {
         for dep in pkg.dependencies() {
@@ -449,7 +483,7 @@ fn build_path_deps(ws: &Workspace<'_>) -> HashMap<String, SourceId> {
     fn build_dep(
         dep: &Dependency,
         ws: &Workspace<'_>,
-        ret: &mut HashMap<String, SourceId>,
+        ret: &mut PathDeps,
         visited: &mut HashSet<SourceId>,
     ) {
         let id = dep.source_id();
@@ -464,10 +498,17 @@ fn build_path_deps(ws: &Workspace<'_>) -> HashMap<String, SourceId> {
             Ok(p) => p,
             Err(_) => return,
         };
-        ret.insert(pkg.name().to_string(), pkg.package_id().source_id());
+        insert(ret, &pkg);
         visited.insert(pkg.package_id().source_id());
         build_pkg(&pkg, ws, ret, visited);
     }
+
+    fn insert(ret: &mut PathDeps, pkg: &Package) {
+        let id = pkg.package_id();
+        let versions = ret.entry(id.name().to_string()).or_default();
+        versions.retain(|(version, _)| *version != id.version().to_string());
+        versions.push((id.version().to_string(), id.source_id()));
+    }
 }
 
 impl Patch {
@@ -480,8 +521,11 @@ impl Patch {
 pub struct EncodableDependency {
     name: String,
     version: String,
-    source: Option<SourceId>,
+    source: Option<EncodableSourceId>,
     checksum: Option<String>,
+    /// Set for yanked packages whose use was explicitly allowed with
+    /// `cargo update --allow-yanked`.
+    yanked: Option<bool>,
     dependencies: Option<Vec<EncodablePackageId>>,
     replace: Option<EncodablePackageId>,
 }
@@ -490,7 +534,114 @@ pub struct EncodableDependency {
 pub struct EncodablePackageId {
     name: String,
     version: Option<String>,
-    source: Option<SourceId>,
+    source: Option<EncodableSourceId>,
+}
+
+/// The name crates.io is written as in `ResolveVersion::V4` lock files.
+const CRATES_IO_ALIAS: &str = "crates-io";
+
+/// A `SourceId` as it is written in `Cargo.lock`.
+///
+/// Starting with `ResolveVersion::V4` the crates.io source is written with a
+/// short alias rather than the URL of its index. Comparisons only consider the
+/// underlying `SourceId`, regardless of how it is written.
+#[derive(Debug, Clone, Copy)]
+pub struct EncodableSourceId {
+    inner: SourceId,
+    use_alias: bool,
+}
+
+impl EncodableSourceId {
+    fn new(inner: SourceId, resolve_version: ResolveVersion) -> EncodableSourceId {
+        EncodableSourceId {
+            inner,
+            use_alias: resolve_version >= ResolveVersion::V4,
+        }
+    }
+
+    fn with_precise(self, v: Option<String>) -> EncodableSourceId {
+        EncodableSourceId {
+            inner: self.inner.with_precise(v),
+            use_alias: self.use_alias,
+        }
+    }
+}
+
+impl fmt::Display for EncodableSourceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.use_alias && self.inner.is_default_registry() {
+            f.write_str(CRATES_IO_ALIAS)
+        } else {
+            write!(f, "{}", self.inner.as_url())
+        }
+    }
+}
+
+impl FromStr for EncodableSourceId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> CargoResult<EncodableSourceId> {
+        if s == CRATES_IO_ALIAS {
+            let url = crate::sources::CRATES_IO_INDEX.into_url()?;
+            Ok(EncodableSourceId {
+                inner: SourceId::for_registry(&url)?,
+                use_alias: true,
+            })
+        } else {
+            Ok(EncodableSourceId {
+                inner: SourceId::from_url(s)?,
+                use_alias: false,
+            })
+        }
+    }
+}
+
+impl PartialEq for EncodableSourceId {
+    fn eq(&self, other: &EncodableSourceId) -> bool {
+        self.inner == other.inner
+    }
+}
+
+impl Eq for EncodableSourceId {}
+
+impl Hash for EncodableSourceId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.inner.hash(state)
+    }
+}
+
+impl PartialOrd for EncodableSourceId {
+    fn partial_cmp(&self, other: &EncodableSourceId) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for EncodableSourceId {
+    fn cmp(&self, other: &EncodableSourceId) -> Ordering {
+        self.inner.cmp(&other.inner)
+    }
+}
+
+impl ser::Serialize for EncodableSourceId {
+    fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        s.collect_str(self)
+    }
+}
+
+impl<'de> de::Deserialize<'de> for EncodableSourceId {
+    fn deserialize<D>(d: D) -> Result<EncodableSourceId, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        String::deserialize(d).and_then(|string| {
+            string
+                .parse::<EncodableSourceId>()
+                .map_err(de::Error::custom)
+        })
+    }
 }
 
 impl fmt::Display for EncodablePackageId {
@@ -500,7 +651,7 @@ impl fmt::Display for EncodablePackageId {
             write!(f, " {}", s)?;
         }
         if let Some(s) = &self.source {
-            write!(f, " ({})", s.as_url())?;
+            write!(f, " ({})", s)?;
         }
         Ok(())
     }
@@ -516,7 +667,7 @@ impl FromStr for EncodablePackageId {
         let source_id = match s.next() {
             Some(s) => {
                 if s.starts_with('(') && s.ends_with(')') {
-                    Some(SourceId::from_url(&s[1..s.len() - 1])?)
+                    Some(s[1..s.len() - 1].parse()?)
                 } else {
                     anyhow::bail!("invalid serialized PackageId")
                 }
@@ -582,7 +733,7 @@ impl<'a> ser::Serialize for Resolve {
             }
         }
 
-        let metadata = if metadata.is_empty() {
+        let metadata = if metadata.is_empty() || self.version() >= ResolveVersion::V4 {
             None
         } else {
             Some(metadata)
@@ -595,9 +746,10 @@ impl<'a> ser::Serialize for Resolve {
                 .map(|id| EncodableDependency {
                     name: id.name().to_string(),
                     version: id.version().to_string(),
-                    source: encode_source(id.source_id()),
+                    source: encode_source(id.source_id(), self.version()),
                     dependencies: None,
                     replace: None,
+                    yanked: None,
                     checksum: if self.version() >= ResolveVersion::V2 {
                         self.checksums().get(id).and_then(|x| x.clone())
                     } else {
@@ -612,6 +764,7 @@ impl<'a> ser::Serialize for Resolve {
             metadata,
             patch,
             version: match self.version() {
+                ResolveVersion::V4 => Some(4),
                 ResolveVersion::V3 => Some(3),
                 ResolveVersion::V2 | ResolveVersion::V1 => None,
             },
@@ -667,7 +820,7 @@ fn encodable_resolve_node(
     EncodableDependency {
         name: id.name().to_string(),
         version: id.version().to_string(),
-        source: encode_source(id.source_id()),
+        source: encode_source(id.source_id(), resolve.version()),
         dependencies: deps,
         replace,
         checksum: if resolve.version() >= ResolveVersion::V2 {
@@ -675,6 +828,11 @@ fn encodable_resolve_node(
         } else {
             None
         },
+        yanked: if resolve.yanked().contains(&id) {
+            Some(true)
+        } else {
+            None
+        },
     }
 }
 
@@ -693,7 +851,7 @@ pub fn encodable_package_id(
             }
         }
     }
-    let mut source = encode_source(id_to_encode).map(|s| s.with_precise(None));
+    let mut source = encode_source(id_to_encode, resolve_version).map(|s| s.with_precise(None));
     if let Some(counts) = &state.counts {
         let version_counts = &counts[&id.name()];
         if version_counts[&id.version()] == 1 {
@@ -710,10 +868,10 @@ pub fn encodable_package_id(
     }
 }
 
-fn encode_source(id: SourceId) -> Option<SourceId> {
+fn encode_source(id: SourceId, resolve_version: ResolveVersion) -> Option<EncodableSourceId> {
     if id.is_path() {
         None
     } else {
-        Some(id)
+        Some(EncodableSourceId::new(id, resolve_version))
     }
 }
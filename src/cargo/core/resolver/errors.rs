@@ -1,6 +1,6 @@
 use std::fmt;
 
-use crate::core::{Dependency, PackageId, Registry, Summary};
+use crate::core::{Dependency, PackageId, Registry, Summary, Verbosity};
 use crate::util::lev_distance::lev_distance;
 use crate::util::Config;
 use anyhow::Error;
@@ -82,6 +82,7 @@ pub(super) fn activation_error(
             cx.parents
                 .path_to_bottom(&parent.package_id())
                 .into_iter()
+                .map(|(node, _)| node)
                 .cloned()
                 .collect(),
         )
@@ -90,9 +91,7 @@ pub(super) fn activation_error(
     if !candidates.is_empty() {
         let mut msg = format!("failed to select a version for `{}`.", dep.package_name());
         msg.push_str("\n    ... required by ");
-        msg.push_str(&describe_path(
-            &cx.parents.path_to_bottom(&parent.package_id()),
-        ));
+        msg.push_str(&describe_path_in_context(cx, &parent.package_id()));
 
         msg.push_str("\nversions that meet the requirements `");
         msg.push_str(&dep.version_req().to_string());
@@ -114,6 +113,18 @@ pub(super) fn activation_error(
         conflicting_activations.reverse();
         // Flag used for grouping all semver errors together.
         let mut has_semver = false;
+        let verbose = match config {
+            Some(config) => config.shell().verbosity() == Verbosity::Verbose,
+            None => false,
+        };
+        // Describes a package activated before `dep`, with the full
+        // derivation of why it was selected when verbose.
+        let describe_activated = |msg: &mut String, id: &PackageId| {
+            msg.push_str(&describe_path_in_context(cx, id));
+            if verbose {
+                describe_requirements(msg, cx, id);
+            }
+        };
 
         for (p, r) in &conflicting_activations {
             match r {
@@ -128,7 +139,7 @@ pub(super) fn activation_error(
                     msg.push_str("`, but it conflicts with a previous package which links to `");
                     msg.push_str(link);
                     msg.push_str("` as well:\n");
-                    msg.push_str(&describe_path(&cx.parents.path_to_bottom(p)));
+                    describe_activated(&mut msg, p);
                 }
                 ConflictReason::MissingFeatures(features) => {
                     msg.push_str("\n\nthe package `");
@@ -193,7 +204,7 @@ pub(super) fn activation_error(
             for (p, r) in &conflicting_activations {
                 if let ConflictReason::Semver = r {
                     msg.push_str("\n\n  previously selected ");
-                    msg.push_str(&describe_path(&cx.parents.path_to_bottom(p)));
+                    describe_activated(&mut msg, p);
                 }
             }
         }
@@ -246,9 +257,7 @@ pub(super) fn activation_error(
                 registry.describe_source(dep.source_id()),
             );
             msg.push_str("required by ");
-            msg.push_str(&describe_path(
-                &cx.parents.path_to_bottom(&parent.package_id()),
-            ));
+            msg.push_str(&describe_path_in_context(cx, &parent.package_id()));
 
             // If we have a path dependency with a locked version, then this may
             // indicate that we updated a sub-package and forgot to run `cargo
@@ -323,9 +332,7 @@ pub(super) fn activation_error(
                 msg.push('\n');
             }
             msg.push_str("required by ");
-            msg.push_str(&describe_path(
-                &cx.parents.path_to_bottom(&parent.package_id()),
-            ));
+            msg.push_str(&describe_path_in_context(cx, &parent.package_id()));
 
             msg
         };
@@ -344,12 +351,92 @@ pub(super) fn activation_error(
     to_resolve_err(anyhow::format_err!("{}", msg))
 }
 
+/// Returns String representation of dependency chain for a particular `pkgid`
+/// within the given context.
+pub(super) fn describe_path_in_context(cx: &Context, id: &PackageId) -> String {
+    let iter = cx
+        .parents
+        .path_to_bottom(id)
+        .into_iter()
+        .map(|(p, d)| (p, d.and_then(|d| pick_dependency(d))));
+    describe_path(iter)
+}
+
 /// Returns String representation of dependency chain for a particular `pkgid`.
-pub(super) fn describe_path(path: &[&PackageId]) -> String {
+///
+/// Each element after the first is a package along with the dependency
+/// through which it requires the previous package. If the dependency is not
+/// known, only the package is described.
+pub(super) fn describe_path<'a>(
+    mut path: impl Iterator<Item = (&'a PackageId, Option<&'a Dependency>)>,
+) -> String {
     use std::fmt::Write;
-    let mut dep_path_desc = format!("package `{}`", path[0]);
-    for dep in path[1..].iter() {
-        write!(dep_path_desc, "\n    ... which is depended on by `{}`", dep).unwrap();
+
+    let mut dep_path_desc = match path.next() {
+        Some((pkg, _)) => format!("package `{}`", pkg),
+        None => return String::new(),
+    };
+    for (pkg, dep) in path {
+        match dep {
+            Some(dep) => write!(
+                dep_path_desc,
+                "\n    ... which satisfies {} of package `{}`",
+                describe_requirement(dep),
+                pkg
+            )
+            .unwrap(),
+            None => write!(dep_path_desc, "\n    ... which is depended on by `{}`", pkg).unwrap(),
+        }
     }
     dep_path_desc
 }
+
+/// Appends every requirement that was placed on the already activated `id`,
+/// which explains why it could not be swapped for a different version.
+fn describe_requirements(msg: &mut String, cx: &Context, id: &PackageId) {
+    use std::fmt::Write;
+
+    let mut requirements: Vec<_> = cx
+        .parents
+        .edges(id)
+        .flat_map(|(parent, deps)| deps.iter().map(move |dep| (parent, dep)))
+        .map(|(parent, dep)| (*parent, describe_requirement(dep)))
+        .collect();
+    if requirements.is_empty() {
+        return;
+    }
+    requirements.sort();
+    requirements.dedup();
+    write!(msg, "\n\n  all requirements on `{}`:", id).unwrap();
+    for (parent, requirement) in requirements {
+        write!(msg, "\n    {} of package `{}`", requirement, parent).unwrap();
+    }
+}
+
+/// Describes a dependency as it would appear in the manifest of its parent.
+fn describe_requirement(dep: &Dependency) -> String {
+    let source_kind = if dep.source_id().is_path() {
+        "path "
+    } else if dep.source_id().is_git() {
+        "git "
+    } else {
+        ""
+    };
+    if source_kind.is_empty() {
+        format!(
+            "dependency `{} = \"{}\"`",
+            dep.name_in_toml(),
+            dep.version_req()
+        )
+    } else {
+        format!("{}dependency `{}`", source_kind, dep.name_in_toml())
+    }
+}
+
+/// Picks the dependency to show for an edge of the parents graph. A package
+/// may depend on another in several ways (for example as both a normal and a
+/// build dependency), so choose one deterministically.
+fn pick_dependency(deps: &im_rc::HashSet<Dependency>) -> Option<&Dependency> {
+    deps.iter()
+        .min_by_key(|d| (d.kind(), d.version_req().to_string(), d.name_in_toml()))
+}
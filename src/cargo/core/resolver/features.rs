@@ -128,6 +128,7 @@ impl FeaturesFor {
 impl FeatureOpts {
     fn new(
         ws: &Workspace<'_>,
+        resolve_behavior: ResolveBehavior,
         has_dev_units: HasDevUnits,
         force_all_targets: ForceAllTargets,
     ) -> CargoResult<FeatureOpts> {
@@ -155,7 +156,7 @@ impl FeatureOpts {
         if let Some(feat_opts) = unstable_flags.features.as_ref() {
             enable(feat_opts)?;
         }
-        match ws.resolve_behavior() {
+        match resolve_behavior {
             ResolveBehavior::V1 => {}
             ResolveBehavior::V2 => {
                 enable(&vec!["all".to_string()]).unwrap();
@@ -339,11 +340,39 @@ impl<'a, 'cfg> FeatureResolver<'a, 'cfg> {
         requested_targets: &[CompileKind],
         has_dev_units: HasDevUnits,
         force_all_targets: ForceAllTargets,
+    ) -> CargoResult<ResolvedFeatures> {
+        FeatureResolver::resolve_as(
+            ws,
+            target_data,
+            resolve,
+            package_set,
+            requested_features,
+            specs,
+            requested_targets,
+            has_dev_units,
+            force_all_targets,
+            ws.resolve_behavior(),
+        )
+    }
+
+    /// Like `resolve`, but resolves features the way the given version of
+    /// the resolver would, instead of the one selected by the workspace.
+    pub fn resolve_as(
+        ws: &Workspace<'cfg>,
+        target_data: &RustcTargetData,
+        resolve: &Resolve,
+        package_set: &'a PackageSet<'cfg>,
+        requested_features: &RequestedFeatures,
+        specs: &[PackageIdSpec],
+        requested_targets: &[CompileKind],
+        has_dev_units: HasDevUnits,
+        force_all_targets: ForceAllTargets,
+        resolve_behavior: ResolveBehavior,
     ) -> CargoResult<ResolvedFeatures> {
         use crate::util::profile;
         let _p = profile::start("resolve features");
 
-        let opts = FeatureOpts::new(ws, has_dev_units, force_all_targets)?;
+        let opts = FeatureOpts::new(ws, resolve_behavior, has_dev_units, force_all_targets)?;
         if !opts.new_resolver {
             // Legacy mode.
             return Ok(ResolvedFeatures {
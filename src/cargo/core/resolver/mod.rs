@@ -79,6 +79,7 @@ mod dep_cache;
 mod encode;
 mod errors;
 pub mod features;
+mod pubgrub;
 mod resolve;
 mod types;
 
@@ -127,6 +128,16 @@ pub fn resolve(
     config: Option<&Config>,
     check_public_visible_dependencies: bool,
 ) -> CargoResult<Resolve> {
+    if config.map(|c| c.cli_unstable().pubgrub).unwrap_or(false) {
+        return pubgrub::resolve(
+            summaries,
+            replacements,
+            registry,
+            try_to_use,
+            config,
+            check_public_visible_dependencies,
+        );
+    }
     let cx = Context::new(check_public_visible_dependencies);
     let _p = profile::start("resolving");
     let minimal_versions = match config {
@@ -1027,7 +1038,7 @@ fn check_cycles(resolve: &Resolve) -> CargoResult<()> {
             anyhow::bail!(
                 "cyclic package dependency: package `{}` depends on itself. Cycle:\n{}",
                 id,
-                errors::describe_path(&path.iter().rev().collect::<Vec<_>>()),
+                errors::describe_path(path.iter().rev().map(|p| (p, None))),
             );
         }
 
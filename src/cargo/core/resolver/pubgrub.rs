@@ -0,0 +1,1450 @@
+//! An alternative implementation of the resolver, based on the PubGrub
+//! algorithm, which is enabled with `-Z pubgrub`.
+//!
+//! PubGrub is described in detail in
+//! <https://github.com/dart-lang/pub/blob/master/doc/solver.md>. Instead of
+//! plain backtracking, every conflict is analyzed to derive a new
+//! *incompatibility* (a set of terms which can't all be true at once) that
+//! captures the root cause of the conflict. The search then jumps back to the
+//! decision which made that incompatibility relevant, and never runs into the
+//! same conflict again. As a bonus, the chain of derived incompatibilities
+//! that leads to a failure is a readable explanation of why resolution is
+//! impossible.
+//!
+//! PubGrub only knows about packages which have exactly one version selected,
+//! so Cargo's rules are mapped onto it as follows:
+//!
+//! * Cargo allows one version from each set of semver compatible versions of
+//!   a package from one source. Each such set is a separate PubGrub package,
+//!   called a "slot" here.
+//! * Every feature of a slot is a separate PubGrub package, with the same
+//!   versions as the slot. A version of the feature depends on the exact same
+//!   version of the slot, and on whatever the feature enables in that version.
+//! * A dependency with candidates in several slots (like `>=1.0, <3.0`)
+//!   depends on a "choice" package, whose versions stand for the slots to
+//!   pick from.
+//! * Packages which link to the same native library are made incompatible
+//!   with each other when one of them is picked.
+//!
+//! Public dependencies are not supported yet.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::rc::Rc;
+
+use log::{debug, trace};
+use semver::Version;
+
+use crate::core::resolver::context::SemverCompatibility;
+use crate::core::resolver::dep_cache::{resolve_features, RegistryQueryer};
+use crate::core::resolver::features::RequestedFeatures;
+use crate::core::resolver::{check_cycles, check_duplicate_pkgs_in_lockfile};
+use crate::core::resolver::{ActivateError, Resolve, ResolveOpts, ResolveVersion};
+use crate::core::{Dependency, FeatureValue, PackageId, PackageIdSpec, Registry};
+use crate::core::{SourceId, Summary};
+use crate::util::config::Config;
+use crate::util::errors::{CargoResult, CargoResultExt};
+use crate::util::interning::InternedString;
+use crate::util::{profile, Graph};
+
+/// Builds the list of all packages required to build the first argument,
+/// see `resolver::resolve` for the meaning of the arguments.
+pub(super) fn resolve(
+    summaries: &[(Summary, ResolveOpts)],
+    replacements: &[(PackageIdSpec, Dependency)],
+    registry: &mut dyn Registry,
+    try_to_use: &HashSet<PackageId>,
+    config: Option<&Config>,
+    check_public_visible_dependencies: bool,
+) -> CargoResult<Resolve> {
+    let _p = profile::start("resolving with pubgrub");
+    let minimal_versions = match config {
+        Some(config) => config.cli_unstable().minimal_versions,
+        None => false,
+    };
+    let mut registry =
+        RegistryQueryer::new(registry, replacements, try_to_use, minimal_versions, config);
+    let mut solver = Solver {
+        registry: &mut registry,
+        roots: summaries,
+        try_to_use,
+        minimal_versions,
+        check_public_visible_dependencies,
+        candidates: HashMap::new(),
+        choices: Vec::new(),
+        choice_ids: HashMap::new(),
+        incompats: Vec::new(),
+        incompats_by_node: HashMap::new(),
+        expanded: HashMap::new(),
+        assignments: Vec::new(),
+        decision_level: 0,
+        decisions: HashMap::new(),
+        accumulated: HashMap::new(),
+    };
+    solver.solve()?;
+    let resolve = solver.into_resolve()?;
+
+    check_cycles(&resolve)?;
+    check_duplicate_pkgs_in_lockfile(&resolve)?;
+    trace!("resolved: {:?}", resolve);
+
+    Ok(resolve)
+}
+
+/// A set of semver compatible versions of a package from a single source, of
+/// which Cargo allows at most one to be selected.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+struct Slot {
+    name: InternedString,
+    source_id: SourceId,
+    compat: SemverCompatibility,
+}
+
+impl Slot {
+    fn of(id: PackageId) -> Slot {
+        Slot {
+            name: id.name(),
+            source_id: id.source_id(),
+            compat: id.version().into(),
+        }
+    }
+}
+
+/// What PubGrub calls a package: something that gets exactly one version
+/// selected, or none at all.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+enum Node {
+    /// The virtual package which depends on the packages being resolved. Its
+    /// only version is `0.0.0`.
+    Root,
+    /// The versions of a package in a slot.
+    Package(Slot),
+    /// A feature of a slot, which has the same versions as the slot.
+    Feature(Slot, InternedString),
+    /// A dependency with candidates in several slots. Version `i.0.0` stands
+    /// for the `i`th slot in the `Choice` it refers to.
+    Choice(usize),
+}
+
+/// A set of versions, either finite or the complement of a finite set.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct VersionSet {
+    complement: bool,
+    versions: BTreeSet<Version>,
+}
+
+impl VersionSet {
+    fn empty() -> VersionSet {
+        VersionSet {
+            complement: false,
+            versions: BTreeSet::new(),
+        }
+    }
+
+    fn singleton(version: Version) -> VersionSet {
+        let mut set = VersionSet::empty();
+        set.versions.insert(version);
+        set
+    }
+
+    fn contains(&self, version: &Version) -> bool {
+        self.versions.contains(version) != self.complement
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.complement && self.versions.is_empty()
+    }
+
+    fn complement(&self) -> VersionSet {
+        VersionSet {
+            complement: !self.complement,
+            versions: self.versions.clone(),
+        }
+    }
+
+    fn intersection(&self, other: &VersionSet) -> VersionSet {
+        let (complement, versions) = match (self.complement, other.complement) {
+            (false, false) => (false, &self.versions & &other.versions),
+            (false, true) => (false, &self.versions - &other.versions),
+            (true, false) => (false, &other.versions - &self.versions),
+            (true, true) => (true, &self.versions | &other.versions),
+        };
+        VersionSet {
+            complement,
+            versions,
+        }
+    }
+
+    fn union(&self, other: &VersionSet) -> VersionSet {
+        self.complement()
+            .intersection(&other.complement())
+            .complement()
+    }
+}
+
+/// A statement about the version selected for a node.
+///
+/// A positive term requires a version in the set to be selected. A negative
+/// term forbids the versions in the set, which includes the case where no
+/// version is selected at all.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Term {
+    positive: bool,
+    set: VersionSet,
+}
+
+/// How a term relates to what is known about the node in the partial
+/// solution.
+#[derive(PartialEq, Eq)]
+enum Relation {
+    Satisfied,
+    Contradicted,
+    Inconclusive,
+}
+
+impl Term {
+    /// The term which is true for anything, including no selection.
+    fn any() -> Term {
+        Term::negative(VersionSet::empty())
+    }
+
+    fn positive(set: VersionSet) -> Term {
+        Term {
+            positive: true,
+            set,
+        }
+    }
+
+    fn negative(set: VersionSet) -> Term {
+        Term {
+            positive: false,
+            set,
+        }
+    }
+
+    fn negate(&self) -> Term {
+        Term {
+            positive: !self.positive,
+            set: self.set.clone(),
+        }
+    }
+
+    fn intersection(&self, other: &Term) -> Term {
+        match (self.positive, other.positive) {
+            (true, true) => Term::positive(self.set.intersection(&other.set)),
+            (true, false) => Term::positive(self.set.intersection(&other.set.complement())),
+            (false, true) => Term::positive(self.set.complement().intersection(&other.set)),
+            (false, false) => Term::negative(self.set.union(&other.set)),
+        }
+    }
+
+    fn is_any(&self) -> bool {
+        !self.positive && self.set.is_empty()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.positive && self.set.is_empty()
+    }
+
+    /// Whether everything allowed by `assigned` is allowed by this term.
+    fn is_satisfied_by(&self, assigned: &Term) -> bool {
+        assigned.intersection(self) == *assigned
+    }
+
+    fn relation(&self, assigned: &Term) -> Relation {
+        if self.is_satisfied_by(assigned) {
+            Relation::Satisfied
+        } else if assigned.intersection(self).is_empty() {
+            Relation::Contradicted
+        } else {
+            Relation::Inconclusive
+        }
+    }
+}
+
+/// Why an incompatibility holds.
+#[derive(Clone, Debug)]
+enum Cause {
+    /// The root must be selected.
+    Root,
+    /// The positive term depends on the negated negative term.
+    Dependency,
+    /// The only term depends on a dependency which matches nothing at all.
+    NoCandidates(Dependency),
+    /// There is no version which satisfies the only term.
+    NoVersions,
+    /// Both terms link to the same native library.
+    Links(InternedString),
+    /// Derived from two other incompatibilities during conflict resolution.
+    Derived(usize, usize),
+}
+
+/// A set of terms which can't all be true at the same time.
+#[derive(Debug)]
+struct Incompat {
+    terms: BTreeMap<Node, Term>,
+    cause: Cause,
+}
+
+impl Incompat {
+    fn new(terms: impl IntoIterator<Item = (Node, Term)>, cause: Cause) -> Incompat {
+        let mut incompat = Incompat {
+            terms: BTreeMap::new(),
+            cause,
+        };
+        for (node, term) in terms {
+            incompat.add_term(node, term);
+        }
+        incompat
+    }
+
+    /// Adds a term, merging it with an existing term for the same node.
+    fn add_term(&mut self, node: Node, term: Term) {
+        let term = match self.terms.remove(&node) {
+            Some(prev) => prev.intersection(&term),
+            None => term,
+        };
+        // A term which is always true doesn't affect the incompatibility.
+        if !term.is_any() {
+            self.terms.insert(node, term);
+        }
+    }
+
+    /// Whether this incompatibility can never be satisfied, in which case it
+    /// carries no information.
+    fn is_vacuous(&self) -> bool {
+        self.terms.values().any(|term| term.is_empty())
+    }
+
+    fn is_terminal(&self) -> bool {
+        match self.terms.len() {
+            0 => true,
+            1 => self
+                .terms
+                .iter()
+                .all(|(node, term)| *node == Node::Root && term.positive),
+            _ => false,
+        }
+    }
+}
+
+/// How an incompatibility relates to the partial solution.
+enum IncompatRelation {
+    Satisfied,
+    Contradicted,
+    /// All terms but the one for the given node are satisfied.
+    AlmostSatisfied(Node),
+    Inconclusive,
+}
+
+/// A term in the partial solution, which is either a decision to select a
+/// version, or derived from an incompatibility.
+struct Assignment {
+    node: Node,
+    term: Term,
+    level: usize,
+    cause: Option<usize>,
+}
+
+/// A dependency with candidates in several slots.
+struct Choice {
+    dep: Dependency,
+    features: BTreeSet<InternedString>,
+    /// The slots to choose from, in order of preference, along with the
+    /// versions of each slot which match the dependency.
+    slots: Vec<(Slot, VersionSet)>,
+}
+
+struct Solver<'a, 'r> {
+    registry: &'a mut RegistryQueryer<'r>,
+    roots: &'a [(Summary, ResolveOpts)],
+    try_to_use: &'a HashSet<PackageId>,
+    minimal_versions: bool,
+    check_public_visible_dependencies: bool,
+    /// All versions of each slot seen so far.
+    candidates: HashMap<Slot, BTreeMap<Version, Summary>>,
+    choices: Vec<Choice>,
+    choice_ids: HashMap<(Dependency, BTreeSet<InternedString>), usize>,
+    incompats: Vec<Incompat>,
+    incompats_by_node: HashMap<Node, Vec<usize>>,
+    /// The dependency incompatibilities added for each version considered.
+    expanded: HashMap<(Node, Version), Vec<usize>>,
+    /// The partial solution.
+    assignments: Vec<Assignment>,
+    decision_level: usize,
+    decisions: HashMap<Node, Version>,
+    /// The intersection of all assignments for each node.
+    accumulated: HashMap<Node, Term>,
+}
+
+fn root_version() -> Version {
+    Version::new(0, 0, 0)
+}
+
+fn choice_version(index: usize) -> Version {
+    Version::new(index as u64, 0, 0)
+}
+
+fn activate_error(e: ActivateError) -> anyhow::Error {
+    match e {
+        ActivateError::Fatal(e) => e,
+        ActivateError::Conflict(id, reason) => {
+            anyhow::format_err!("failed to activate `{}`: {:?}", id, reason)
+        }
+    }
+}
+
+impl<'a, 'r> Solver<'a, 'r> {
+    fn solve(&mut self) -> CargoResult<()> {
+        let root = VersionSet::singleton(root_version());
+        self.add_incompat(Incompat::new(
+            vec![(Node::Root, Term::negative(root))],
+            Cause::Root,
+        ));
+        let mut next = Node::Root;
+        loop {
+            self.propagate(next)?;
+            match self.decide()? {
+                Some(node) => next = node,
+                None => return Ok(()),
+            }
+        }
+    }
+
+    fn add_incompat(&mut self, incompat: Incompat) -> usize {
+        let id = self.incompats.len();
+        for node in incompat.terms.keys() {
+            self.incompats_by_node
+                .entry(node.clone())
+                .or_default()
+                .push(id);
+        }
+        self.incompats.push(incompat);
+        id
+    }
+
+    fn assigned(&self, node: &Node) -> Term {
+        self.accumulated
+            .get(node)
+            .cloned()
+            .unwrap_or_else(Term::any)
+    }
+
+    fn assign(&mut self, assignment: Assignment) {
+        let acc = self
+            .accumulated
+            .entry(assignment.node.clone())
+            .or_insert_with(Term::any);
+        *acc = acc.intersection(&assignment.term);
+        if assignment.cause.is_none() {
+            let version = assignment.term.set.versions.iter().next().unwrap();
+            self.decisions
+                .insert(assignment.node.clone(), version.clone());
+        }
+        self.assignments.push(assignment);
+    }
+
+    fn relation(&self, id: usize) -> IncompatRelation {
+        let mut unsatisfied = None;
+        for (node, term) in &self.incompats[id].terms {
+            match term.relation(&self.assigned(node)) {
+                Relation::Satisfied => {}
+                Relation::Contradicted => return IncompatRelation::Contradicted,
+                Relation::Inconclusive => {
+                    if unsatisfied.is_some() {
+                        return IncompatRelation::Inconclusive;
+                    }
+                    unsatisfied = Some(node);
+                }
+            }
+        }
+        match unsatisfied {
+            Some(node) => IncompatRelation::AlmostSatisfied(node.clone()),
+            None => IncompatRelation::Satisfied,
+        }
+    }
+
+    /// Adds the negation of the term for `node` in incompatibility `id` to
+    /// the partial solution.
+    fn derive(&mut self, node: Node, id: usize) {
+        let term = self.incompats[id].terms[&node].negate();
+        trace!("derived {:?} {:?}", node, term);
+        self.assign(Assignment {
+            node,
+            term,
+            level: self.decision_level,
+            cause: Some(id),
+        });
+    }
+
+    /// Unit propagation: derives everything that follows from the
+    /// incompatibilities about `node`, and resolves any conflicts found.
+    fn propagate(&mut self, node: Node) -> CargoResult<()> {
+        let mut changed = vec![node];
+        while let Some(node) = changed.pop() {
+            let ids = match self.incompats_by_node.get(&node) {
+                Some(ids) => ids.clone(),
+                None => continue,
+            };
+            for id in ids.into_iter().rev() {
+                match self.relation(id) {
+                    IncompatRelation::Satisfied => {
+                        let root_cause = self.resolve_conflict(id)?;
+                        let node = match self.relation(root_cause) {
+                            IncompatRelation::AlmostSatisfied(node) => node,
+                            _ => anyhow::bail!(
+                                "internal error: conflict resolution didn't backtrack far \
+                                 enough\n\
+                                 conflict: {}\n\
+                                 root cause: {}",
+                                self.describe_incompat(id),
+                                self.describe_incompat(root_cause)
+                            ),
+                        };
+                        self.derive(node.clone(), root_cause);
+                        changed.clear();
+                        changed.push(node);
+                        break;
+                    }
+                    IncompatRelation::AlmostSatisfied(node) => {
+                        self.derive(node.clone(), id);
+                        if !changed.contains(&node) {
+                            changed.push(node);
+                        }
+                    }
+                    IncompatRelation::Contradicted | IncompatRelation::Inconclusive => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the first assignment after which the terms are satisfied, also
+    /// taking `extra` into account, and only looking at the first `limit`
+    /// assignments.
+    fn find_satisfier(
+        &self,
+        terms: &BTreeMap<Node, Term>,
+        extra: Option<(&Node, &Term)>,
+        limit: usize,
+    ) -> Option<usize> {
+        let mut acc: HashMap<&Node, Term> = terms.keys().map(|node| (node, Term::any())).collect();
+        if let Some((node, term)) = extra {
+            acc.insert(node, term.clone());
+        }
+        let satisfied = |acc: &HashMap<&Node, Term>| {
+            terms
+                .iter()
+                .all(|(node, term)| term.is_satisfied_by(&acc[node]))
+        };
+        if extra.is_some() && satisfied(&acc) {
+            return None;
+        }
+        for (i, assignment) in self.assignments[..limit].iter().enumerate() {
+            if let Some(term) = acc.get_mut(&assignment.node) {
+                *term = term.intersection(&assignment.term);
+                if satisfied(&acc) {
+                    return Some(i);
+                }
+            }
+        }
+        None
+    }
+
+    /// Derives the root cause of the satisfied incompatibility `id`, and
+    /// backtracks to the point where that root cause allows to derive
+    /// something new.
+    fn resolve_conflict(&mut self, mut id: usize) -> CargoResult<usize> {
+        debug!("conflict: {:?}", self.incompats[id]);
+        let mut is_new = false;
+        loop {
+            if self.incompats[id].is_terminal() {
+                return Err(self.report(id));
+            }
+            let terms = &self.incompats[id].terms;
+            let satisfier = match self.find_satisfier(terms, None, self.assignments.len()) {
+                Some(satisfier) => satisfier,
+                None => anyhow::bail!(
+                    "internal error: the conflict `{}` isn't satisfied",
+                    self.describe_incompat(id)
+                ),
+            };
+            let sat = &self.assignments[satisfier];
+            let previous_level = self
+                .find_satisfier(terms, Some((&sat.node, &sat.term)), satisfier)
+                .map(|i| self.assignments[i].level)
+                .unwrap_or(1)
+                .max(1);
+
+            let cause = match sat.cause {
+                Some(cause) if previous_level == sat.level => cause,
+                _ => {
+                    if is_new {
+                        let incompat = self.incompats.pop().unwrap();
+                        id = self.add_incompat(incompat);
+                    }
+                    self.backtrack(previous_level);
+                    return Ok(id);
+                }
+            };
+
+            let node = sat.node.clone();
+            let term = &terms[&node];
+            let mut prior = Incompat::new(Vec::new(), Cause::Derived(id, cause));
+            for (n, t) in terms.iter().chain(&self.incompats[cause].terms) {
+                if *n != node {
+                    prior.add_term(n.clone(), t.clone());
+                }
+            }
+            if !term.is_satisfied_by(&sat.term) {
+                let difference = sat.term.intersection(&term.negate());
+                prior.add_term(node, difference.negate());
+            }
+            debug!("derived: {:?}", prior);
+            self.incompats.push(prior);
+            id = self.incompats.len() - 1;
+            is_new = true;
+        }
+    }
+
+    fn backtrack(&mut self, level: usize) {
+        self.assignments.retain(|a| a.level <= level);
+        self.decision_level = level;
+        self.accumulated.clear();
+        self.decisions.clear();
+        let assignments = std::mem::take(&mut self.assignments);
+        for assignment in assignments {
+            self.assign(assignment);
+        }
+    }
+
+    /// Picks the next version to try, returning the node it was picked for,
+    /// or `None` once every required node has a version.
+    fn decide(&mut self) -> CargoResult<Option<Node>> {
+        let mut pending = self
+            .accumulated
+            .iter()
+            .filter(|(node, term)| term.positive && !self.decisions.contains_key(node))
+            .map(|(node, term)| (node.clone(), term.set.clone()))
+            .collect::<Vec<_>>();
+        pending.sort_by(|a, b| a.0.cmp(&b.0));
+        // Deciding the node with the fewest options first finds conflicts
+        // as early as possible.
+        let (node, set, versions) = match pending
+            .into_iter()
+            .map(|(node, set)| {
+                let versions = self.versions(&node, &set);
+                (node, set, versions)
+            })
+            .min_by_key(|(_, _, versions)| versions.len())
+        {
+            Some(next) => next,
+            None => return Ok(None),
+        };
+
+        let version = match versions.into_iter().next() {
+            Some(version) => version,
+            None => {
+                self.add_incompat(Incompat::new(
+                    vec![(node.clone(), Term::positive(set))],
+                    Cause::NoVersions,
+                ));
+                return Ok(Some(node));
+            }
+        };
+
+        // Don't decide on a version if its dependencies already conflict
+        // with the partial solution; propagation will rule it out instead.
+        let ids = self.expand(&node, &version)?;
+        let mut conflict = ids.iter().any(|&id| {
+            self.incompats[id]
+                .terms
+                .iter()
+                .all(|(n, term)| *n == node || term.is_satisfied_by(&self.assigned(n)))
+        });
+        if let Node::Package(slot) = node {
+            conflict |= self.add_links_conflicts(slot, &version);
+        }
+        if !conflict {
+            trace!("decided {:?} {}", node, version);
+            self.decision_level += 1;
+            self.assign(Assignment {
+                node: node.clone(),
+                term: Term::positive(VersionSet::singleton(version)),
+                level: self.decision_level,
+                cause: None,
+            });
+        }
+        Ok(Some(node))
+    }
+
+    /// The versions of `node` in `set`, in order of preference.
+    fn versions(&self, node: &Node, set: &VersionSet) -> Vec<Version> {
+        let mut versions = match node {
+            Node::Root => vec![root_version()],
+            Node::Package(slot) => self.slot_versions(slot, |_| true),
+            Node::Feature(slot, feature) => {
+                let mut versions = self.slot_versions(slot, |s| {
+                    *feature == "default" || s.features().contains_key(feature)
+                });
+                // Prefer the version already picked for the package.
+                if let Some(picked) = self.decisions.get(&Node::Package(*slot)) {
+                    if let Some(i) = versions.iter().position(|v| v == picked) {
+                        let picked = versions.remove(i);
+                        versions.insert(0, picked);
+                    }
+                }
+                versions
+            }
+            Node::Choice(i) => (0..self.choices[*i].slots.len())
+                .map(choice_version)
+                .collect(),
+        };
+        versions.retain(|v| set.contains(v));
+        versions
+    }
+
+    fn slot_versions(&self, slot: &Slot, filter: impl Fn(&Summary) -> bool) -> Vec<Version> {
+        let candidates = match self.candidates.get(slot) {
+            Some(candidates) => candidates,
+            None => return Vec::new(),
+        };
+        let mut summaries = candidates
+            .values()
+            .filter(|s| filter(&self.effective_summary(s)))
+            .collect::<Vec<_>>();
+        // The same order as `RegistryQueryer::query` uses: prefer previously
+        // locked versions, then the highest (or lowest) version.
+        summaries.sort_by(|a, b| {
+            let a_in_previous = self.try_to_use.contains(&a.package_id());
+            let b_in_previous = self.try_to_use.contains(&b.package_id());
+            let cmp = a.version().cmp(b.version());
+            let cmp = if self.minimal_versions {
+                cmp
+            } else {
+                cmp.reverse()
+            };
+            a_in_previous.cmp(&b_in_previous).reverse().then(cmp)
+        });
+        summaries.iter().map(|s| s.version().clone()).collect()
+    }
+
+    /// Whether any of the versions of `slot` in `set` has default features.
+    fn has_default_features(&self, slot: Slot, set: &VersionSet) -> bool {
+        self.candidates[&slot]
+            .values()
+            .filter(|s| set.contains(s.version()))
+            .any(|s| self.effective_summary(s).features().contains_key("default"))
+    }
+
+    fn summary(&self, slot: &Slot, version: &Version) -> &Summary {
+        &self.candidates[slot][version]
+    }
+
+    /// The summary whose dependencies and features are used for `summary`,
+    /// which differs if it is overridden with `[replace]`.
+    fn effective_summary(&self, summary: &Summary) -> Summary {
+        self.registry
+            .replacement_summary(summary.package_id())
+            .unwrap_or(summary)
+            .clone()
+    }
+
+    /// Adds incompatibilities between a version of `slot` and the other
+    /// picked packages which link to the same native library. Returns
+    /// whether there were any.
+    fn add_links_conflicts(&mut self, slot: Slot, version: &Version) -> bool {
+        let links = match self.summary(&slot, version).links() {
+            Some(links) => links,
+            None => return false,
+        };
+        let mut others = self
+            .decisions
+            .iter()
+            .filter_map(|(node, v)| match node {
+                Node::Package(other) if *other != slot => Some((*other, v.clone())),
+                _ => None,
+            })
+            .filter(|(other, v)| self.summary(other, v).links() == Some(links))
+            .collect::<Vec<_>>();
+        others.sort();
+        for (other, v) in &others {
+            self.add_incompat(Incompat::new(
+                vec![
+                    (
+                        Node::Package(slot),
+                        Term::positive(VersionSet::singleton(version.clone())),
+                    ),
+                    (
+                        Node::Package(*other),
+                        Term::positive(VersionSet::singleton(v.clone())),
+                    ),
+                ],
+                Cause::Links(links),
+            ));
+        }
+        !others.is_empty()
+    }
+
+    /// Adds the incompatibilities describing the dependencies of `version`
+    /// of `node`, the first time it is considered.
+    fn expand(&mut self, node: &Node, version: &Version) -> CargoResult<Vec<usize>> {
+        let key = (node.clone(), version.clone());
+        if let Some(ids) = self.expanded.get(&key) {
+            return Ok(ids.clone());
+        }
+        let parent = key.clone();
+        let exact = VersionSet::singleton(version.clone());
+        let mut new = Vec::new();
+        match *node {
+            Node::Root => {
+                for (summary, opts) in self.roots {
+                    let (features, deps) =
+                        resolve_features(None, summary, opts).map_err(activate_error)?;
+                    let slot = Slot::of(summary.package_id());
+                    let set = VersionSet::singleton(summary.version().clone());
+                    self.candidates
+                        .entry(slot)
+                        .or_default()
+                        .insert(summary.version().clone(), summary.clone());
+                    new.push(depends_on(&parent, Node::Package(slot), set.clone()));
+                    let mut features = features.into_iter().collect::<Vec<_>>();
+                    features.sort();
+                    for feature in features {
+                        new.push(depends_on(
+                            &parent,
+                            Node::Feature(slot, feature),
+                            set.clone(),
+                        ));
+                    }
+                    for (dep, features) in deps {
+                        self.add_dependency(&parent, &dep, features.iter().cloned(), &mut new)?;
+                    }
+                }
+            }
+            Node::Package(slot) => {
+                let summary = self.effective_summary(self.summary(&slot, version));
+                for dep in summary.dependencies() {
+                    if dep.is_transitive() && !dep.is_optional() {
+                        self.add_dependency(
+                            &parent,
+                            dep,
+                            dep.features().iter().cloned(),
+                            &mut new,
+                        )?;
+                    }
+                }
+            }
+            Node::Feature(slot, feature) => {
+                new.push(depends_on(&parent, Node::Package(slot), exact.clone()));
+                let summary = self.effective_summary(self.summary(&slot, version));
+                // `default` is the only feature which may be requested even
+                // if the package doesn't have it.
+                let fvs = summary
+                    .features()
+                    .get(&feature)
+                    .cloned()
+                    .unwrap_or_default();
+                for fv in fvs {
+                    let named = |name: InternedString| {
+                        summary
+                            .dependencies()
+                            .iter()
+                            .filter(move |dep| dep.name_in_toml() == name && dep.is_transitive())
+                    };
+                    match fv {
+                        FeatureValue::Feature(f) => {
+                            new.push(depends_on(&parent, Node::Feature(slot, f), exact.clone()));
+                        }
+                        FeatureValue::Dep { dep_name } => {
+                            for dep in named(dep_name) {
+                                let features = dep.features().iter().cloned();
+                                self.add_dependency(&parent, dep, features, &mut new)?;
+                            }
+                        }
+                        FeatureValue::DepFeature {
+                            dep_name,
+                            dep_feature,
+                            dep_prefix,
+                            // Weak features are always activated in the
+                            // dependency resolver, like in the regular one.
+                            weak: _,
+                        } => {
+                            if !dep_prefix && named(dep_name).any(|dep| dep.is_optional()) {
+                                let f = Node::Feature(slot, dep_name);
+                                new.push(depends_on(&parent, f, exact.clone()));
+                            }
+                            for dep in named(dep_name) {
+                                let features = dep.features().iter().cloned();
+                                let features = features.chain(Some(dep_feature));
+                                self.add_dependency(&parent, dep, features, &mut new)?;
+                            }
+                        }
+                    }
+                }
+            }
+            Node::Choice(i) => {
+                let choice = &self.choices[i];
+                let (slot, set) = choice.slots[version.major as usize].clone();
+                new.push(depends_on(&parent, Node::Package(slot), set.clone()));
+                for &feature in &choice.features {
+                    new.push(depends_on(
+                        &parent,
+                        Node::Feature(slot, feature),
+                        set.clone(),
+                    ));
+                }
+            }
+        }
+
+        let ids = new
+            .into_iter()
+            .filter(|incompat| !incompat.is_vacuous())
+            .map(|incompat| self.add_incompat(incompat))
+            .collect::<Vec<_>>();
+        self.expanded.insert(key, ids.clone());
+        Ok(ids)
+    }
+
+    /// Adds the incompatibilities for `parent` depending on `dep` with the
+    /// given features.
+    fn add_dependency(
+        &mut self,
+        parent: &(Node, Version),
+        dep: &Dependency,
+        features: impl Iterator<Item = InternedString>,
+        new: &mut Vec<Incompat>,
+    ) -> CargoResult<()> {
+        let mut features = features.collect::<BTreeSet<_>>();
+        if dep.uses_default_features() {
+            features.insert(InternedString::new("default"));
+        }
+        for feature in &features {
+            if feature.contains('/') {
+                anyhow::bail!("feature names may not contain slashes: `{}`", feature);
+            }
+        }
+        if self.check_public_visible_dependencies && dep.is_public() {
+            anyhow::bail!(
+                "public dependency `{}` of {} is not supported by `-Z pubgrub` yet",
+                dep.package_name(),
+                self.describe_parent(parent)
+            );
+        }
+
+        let candidates = self.registry.query(dep).chain_err(|| {
+            anyhow::format_err!(
+                "failed to get `{}` as a dependency of {}",
+                dep.package_name(),
+                self.describe_parent(parent),
+            )
+        })?;
+        let mut slots: BTreeMap<Slot, VersionSet> = BTreeMap::new();
+        for summary in candidates.iter() {
+            let slot = Slot::of(summary.package_id());
+            let version = summary.version().clone();
+            self.candidates
+                .entry(slot)
+                .or_default()
+                .entry(version.clone())
+                .or_insert_with(|| summary.clone());
+            slots
+                .entry(slot)
+                .or_insert_with(VersionSet::empty)
+                .versions
+                .insert(version);
+        }
+
+        if slots.is_empty() {
+            let term = Term::positive(VersionSet::singleton(parent.1.clone()));
+            new.push(Incompat::new(
+                vec![(parent.0.clone(), term)],
+                Cause::NoCandidates(dep.clone()),
+            ));
+        } else if slots.len() == 1 {
+            let (slot, set) = slots.into_iter().next().unwrap();
+            new.push(depends_on(parent, Node::Package(slot), set.clone()));
+            for feature in features {
+                // Most packages don't have default features, leave those out
+                // to keep explanations short.
+                if feature == "default" && !self.has_default_features(slot, &set) {
+                    continue;
+                }
+                new.push(depends_on(
+                    parent,
+                    Node::Feature(slot, feature),
+                    set.clone(),
+                ));
+            }
+        } else {
+            let key = (dep.clone(), features);
+            let id = match self.choice_ids.get(&key) {
+                Some(&id) => id,
+                None => {
+                    let mut slots = slots.into_iter().collect::<Vec<_>>();
+                    slots.sort_by_key(|(_, set)| set.versions.iter().next_back().cloned());
+                    if !self.minimal_versions {
+                        slots.reverse();
+                    }
+                    let id = self.choices.len();
+                    self.choices.push(Choice {
+                        dep: key.0.clone(),
+                        features: key.1.clone(),
+                        slots,
+                    });
+                    self.choice_ids.insert(key, id);
+                    id
+                }
+            };
+            let options = (0..self.choices[id].slots.len()).map(choice_version);
+            let set = VersionSet {
+                complement: false,
+                versions: options.collect(),
+            };
+            new.push(depends_on(parent, Node::Choice(id), set));
+        }
+        Ok(())
+    }
+
+    /// Turns the decisions into a `Resolve`.
+    fn into_resolve(self) -> CargoResult<Resolve> {
+        let mut selected = self
+            .decisions
+            .iter()
+            .filter_map(|(node, version)| match node {
+                Node::Package(slot) => Some(self.summary(slot, version).clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        selected.sort_by_key(|s| s.package_id());
+        let ids = selected
+            .iter()
+            .map(|s| s.package_id())
+            .collect::<HashSet<_>>();
+
+        let mut features: HashMap<PackageId, Vec<InternedString>> = HashMap::new();
+        for (node, version) in &self.decisions {
+            if let Node::Feature(slot, feature) = node {
+                let summary = self.effective_summary(self.summary(slot, version));
+                if summary.features().contains_key(feature) {
+                    features
+                        .entry(summary.package_id())
+                        .or_default()
+                        .push(*feature);
+                }
+            }
+        }
+        for features in features.values_mut() {
+            features.sort();
+        }
+
+        let mut graph: Graph<PackageId, HashSet<Dependency>> = Graph::new();
+        let mut replacements = HashMap::new();
+        let mut cksums = HashMap::new();
+        let mut summaries = HashMap::new();
+        for summary in &selected {
+            let effective = self.effective_summary(summary);
+            for s in &[summary.clone(), effective.clone()] {
+                graph.add(s.package_id());
+                cksums.insert(s.package_id(), s.checksum().map(|s| s.to_string()));
+                summaries.insert(s.package_id(), s.clone());
+            }
+            if let Some((id, replacement)) =
+                self.registry.used_replacement_for(summary.package_id())
+            {
+                replacements.insert(id, replacement);
+            }
+
+            let dev_deps = self
+                .roots
+                .iter()
+                .any(|(root, opts)| root.package_id() == summary.package_id() && opts.dev_deps);
+            let enabled = features
+                .get(&effective.package_id())
+                .map(|features| features.iter().cloned().collect())
+                .unwrap_or_default();
+            let opts = ResolveOpts {
+                dev_deps,
+                features: RequestedFeatures {
+                    features: Rc::new(enabled),
+                    all_features: false,
+                    uses_default_features: false,
+                },
+            };
+            let (_, deps) = resolve_features(None, &effective, &opts).map_err(activate_error)?;
+            for (dep, _) in deps {
+                let candidates = self.registry.query(&dep)?;
+                let mut matching = candidates.iter().filter(|s| ids.contains(&s.package_id()));
+                let target = match matching.next() {
+                    Some(target) => target.package_id(),
+                    None => anyhow::bail!(
+                        "internal error: no package selected for dependency `{}` of `{}`",
+                        dep.package_name(),
+                        effective.package_id()
+                    ),
+                };
+                graph
+                    .link(effective.package_id(), target)
+                    .insert(dep.clone());
+            }
+        }
+
+        Ok(Resolve::new(
+            graph,
+            replacements,
+            features,
+            cksums,
+            BTreeMap::new(),
+            Vec::new(),
+            ResolveVersion::default(),
+            summaries,
+        ))
+    }
+
+    fn describe_parent(&self, parent: &(Node, Version)) -> String {
+        match &parent.0 {
+            Node::Package(slot) | Node::Feature(slot, _) => {
+                format!("package `{}`", self.summary(slot, &parent.1).package_id())
+            }
+            _ => self.describe_root(),
+        }
+    }
+
+    fn describe_root(&self) -> String {
+        match self.roots {
+            [(summary, _)] => format!("{} {}", summary.name(), summary.version()),
+            _ => "the workspace".to_string(),
+        }
+    }
+
+    /// Describes the versions of `node` which `term` is about. This is used
+    /// for negative terms too, which read better as requirements in
+    /// sentences like "a depends on b".
+    fn describe(&self, node: &Node, term: &Term) -> String {
+        let set = &term.set;
+        match node {
+            Node::Root => self.describe_root(),
+            Node::Package(slot) => self.describe_slot(slot, set),
+            Node::Feature(slot, feature) if *feature == "default" => {
+                format!("{} with default features", self.describe_slot(slot, set))
+            }
+            Node::Feature(slot, feature) => {
+                format!(
+                    "{} with feature `{}`",
+                    self.describe_slot(slot, set),
+                    feature
+                )
+            }
+            Node::Choice(i) => {
+                let choice = &self.choices[*i];
+                let options = choice
+                    .slots
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| set.contains(&choice_version(*i)))
+                    .collect::<Vec<_>>();
+                if options.len() == choice.slots.len() {
+                    format!("{} {}", choice.dep.package_name(), choice.dep.version_req())
+                } else {
+                    options
+                        .iter()
+                        .map(|(_, (slot, set))| self.describe_slot(slot, set))
+                        .collect::<Vec<_>>()
+                        .join(" or ")
+                }
+            }
+        }
+    }
+
+    /// Describes a set of versions of a slot as ranges of the known versions.
+    fn describe_slot(&self, slot: &Slot, set: &VersionSet) -> String {
+        let known = self
+            .candidates
+            .get(slot)
+            .map(|c| c.keys().cloned().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let mut runs: Vec<(&Version, &Version)> = Vec::new();
+        let mut in_run = false;
+        for version in &known {
+            if !set.contains(version) {
+                in_run = false;
+            } else if in_run {
+                runs.last_mut().unwrap().1 = version;
+            } else {
+                runs.push((version, version));
+                in_run = true;
+            }
+        }
+        let versions = match runs.as_slice() {
+            [] => "(no versions)".to_string(),
+            [(first, last)]
+                if known.len() > 1 && *first == &known[0] && *last == known.last().unwrap() =>
+            {
+                match slot.compat {
+                    SemverCompatibility::Major(major) => format!("{}.*", major),
+                    SemverCompatibility::Minor(minor) => format!("0.{}.*", minor),
+                    SemverCompatibility::Patch(_) => format!(">={}, <={}", first, last),
+                }
+            }
+            runs => runs
+                .iter()
+                .map(|(first, last)| {
+                    if first == last {
+                        first.to_string()
+                    } else {
+                        format!(">={}, <={}", first, last)
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" or "),
+        };
+        format!("{} {}", slot.name, versions)
+    }
+
+    /// Describes what an incompatibility means.
+    fn describe_incompat(&self, id: usize) -> String {
+        let incompat = &self.incompats[id];
+        if incompat.is_terminal() {
+            return "version solving failed".to_string();
+        }
+        let describe = |(node, term): &(&Node, &Term)| self.describe(node, term);
+        let (positive, negative): (Vec<_>, Vec<_>) =
+            incompat.terms.iter().partition(|(_, term)| term.positive);
+        let positive = positive.iter().map(describe).collect::<Vec<_>>();
+        let negative = negative.iter().map(describe).collect::<Vec<_>>();
+        match (positive.as_slice(), negative.as_slice()) {
+            ([p], []) => format!("{} is forbidden", p),
+            ([], [n]) => format!("{} is required", n),
+            ([p], [n]) => format!("{} depends on {}", p, n),
+            ([p1, p2], []) => format!("{} is incompatible with {}", p1, p2),
+            (ps, []) => format!("{} are incompatible", ps.join(", ")),
+            ([], ns) => format!("one of {} is required", ns.join(" or ")),
+            (ps, ns) => format!(
+                "if {} are selected, then {} is required",
+                ps.join(" and "),
+                ns.join(" or ")
+            ),
+        }
+    }
+
+    /// Describes why an incompatibility which wasn't derived holds.
+    fn describe_external(&self, id: usize) -> String {
+        let incompat = &self.incompats[id];
+        let terms = incompat.terms.iter().collect::<Vec<_>>();
+        match &incompat.cause {
+            Cause::NoCandidates(dep) => format!(
+                "{} depends on {} {}, but no versions of it were found in {}",
+                self.describe(terms[0].0, terms[0].1),
+                dep.package_name(),
+                dep.version_req(),
+                dep.source_id()
+            ),
+            Cause::NoVersions => match terms[0] {
+                (Node::Feature(slot, feature), term) => format!(
+                    "{} has no feature `{}`",
+                    self.describe(&Node::Package(*slot), term),
+                    feature
+                ),
+                (node, term) => format!("no version of {} is available", self.describe(node, term)),
+            },
+            Cause::Links(links) => format!(
+                "{} and {} both link to the native library `{}`",
+                self.describe(terms[0].0, terms[0].1),
+                self.describe(terms[1].0, terms[1].1),
+                links
+            ),
+            Cause::Root | Cause::Dependency | Cause::Derived(..) => self.describe_incompat(id),
+        }
+    }
+
+    /// Builds the error for the terminal incompatibility `id`, explaining
+    /// how it was derived.
+    fn report(&self, id: usize) -> anyhow::Error {
+        // Incompatibilities used to derive more than one other get a line
+        // number, so they can be referred to instead of explained again.
+        let mut uses: HashMap<usize, usize> = HashMap::new();
+        let mut stack = vec![id];
+        let mut seen = HashSet::new();
+        while let Some(id) = stack.pop() {
+            if let Cause::Derived(a, b) = self.incompats[id].cause {
+                for cause in [a, b].iter() {
+                    *uses.entry(*cause).or_default() += 1;
+                    if seen.insert(*cause) {
+                        stack.push(*cause);
+                    }
+                }
+            }
+        }
+        let mut reporter = Reporter {
+            solver: self,
+            shared: uses
+                .into_iter()
+                .filter(|&(_, count)| count > 1)
+                .map(|(id, _)| id)
+                .collect(),
+            refs: HashMap::new(),
+            lines: Vec::new(),
+        };
+        match self.incompats[id].cause {
+            Cause::Derived(..) => reporter.build(id),
+            _ => reporter.lines.push(format!(
+                "Because {}, version solving failed.",
+                self.describe_external(id)
+            )),
+        }
+        anyhow::format_err!(
+            "failed to select a version for every dependency:\n\n{}",
+            reporter.lines.join("\n")
+        )
+    }
+}
+
+/// The incompatibility for `parent` depending on `set` of `node`.
+fn depends_on(parent: &(Node, Version), node: Node, set: VersionSet) -> Incompat {
+    let parent_term = Term::positive(VersionSet::singleton(parent.1.clone()));
+    Incompat::new(
+        vec![(parent.0.clone(), parent_term), (node, Term::negative(set))],
+        Cause::Dependency,
+    )
+}
+
+/// Writes out the derivation of an incompatibility as a series of sentences,
+/// in the style of PubGrub's error reporting.
+struct Reporter<'s, 'a, 'r> {
+    solver: &'s Solver<'a, 'r>,
+    /// Incompatibilities which are used more than once.
+    shared: HashSet<usize>,
+    /// The line numbers of incompatibilities which have already been
+    /// explained.
+    refs: HashMap<usize, usize>,
+    lines: Vec<String>,
+}
+
+impl Reporter<'_, '_, '_> {
+    fn causes(&self, id: usize) -> (usize, usize) {
+        match self.solver.incompats[id].cause {
+            Cause::Derived(a, b) => (a, b),
+            _ => unreachable!(),
+        }
+    }
+
+    fn is_derived(&self, id: usize) -> bool {
+        matches!(self.solver.incompats[id].cause, Cause::Derived(..))
+    }
+
+    fn describe(&self, id: usize) -> String {
+        let text = if self.is_derived(id) {
+            self.solver.describe_incompat(id)
+        } else {
+            self.solver.describe_external(id)
+        };
+        match self.refs.get(&id) {
+            Some(line) => format!("{} ({})", text, line),
+            None => text,
+        }
+    }
+
+    fn add_ref(&mut self, id: usize) {
+        let line = self.refs.len() + 1;
+        self.refs.insert(id, line);
+        if let Some(last) = self.lines.last_mut() {
+            last.push_str(&format!(" ({})", line));
+        }
+    }
+
+    fn build(&mut self, id: usize) {
+        self.build_lines(id);
+        if self.shared.contains(&id) && !self.refs.contains_key(&id) {
+            self.add_ref(id);
+        }
+    }
+
+    fn build_lines(&mut self, id: usize) {
+        let (c1, c2) = self.causes(id);
+        let conclusion = self.solver.describe_incompat(id);
+        match (self.is_derived(c1), self.is_derived(c2)) {
+            (false, false) => {
+                let line = format!(
+                    "Because {} and {}, {}.",
+                    self.describe(c1),
+                    self.describe(c2),
+                    conclusion
+                );
+                self.lines.push(line);
+            }
+            (true, false) => self.build_one_each(c1, c2, &conclusion),
+            (false, true) => self.build_one_each(c2, c1, &conclusion),
+            (true, true) => match (self.refs.contains_key(&c1), self.refs.contains_key(&c2)) {
+                (true, true) => {
+                    let line = format!(
+                        "Because {} and {}, {}.",
+                        self.describe(c1),
+                        self.describe(c2),
+                        conclusion
+                    );
+                    self.lines.push(line);
+                }
+                (true, false) => {
+                    self.build(c2);
+                    let line = format!("And because {}, {}.", self.describe(c1), conclusion);
+                    self.lines.push(line);
+                }
+                (false, true) => {
+                    self.build(c1);
+                    let line = format!("And because {}, {}.", self.describe(c2), conclusion);
+                    self.lines.push(line);
+                }
+                (false, false) => {
+                    self.build(c1);
+                    if !self.refs.contains_key(&c1) {
+                        self.add_ref(c1);
+                    }
+                    self.lines.push(String::new());
+                    self.build(c2);
+                    let line = format!("And because {}, {}.", self.describe(c1), conclusion);
+                    self.lines.push(line);
+                }
+            },
+        }
+    }
+
+    /// Explains `id`, which follows from the derived `derived` and the
+    /// external `external`.
+    fn build_one_each(&mut self, derived: usize, external: usize, conclusion: &str) {
+        if self.refs.contains_key(&derived) {
+            let line = format!(
+                "Because {} and {}, {}.",
+                self.describe(derived),
+                self.describe(external),
+                conclusion
+            );
+            self.lines.push(line);
+            return;
+        }
+        // If `derived` itself follows from an external incompatibility, both
+        // external ones can be explained in one sentence, unless `derived`
+        // needs a line number to be referred to later.
+        let (p1, p2) = self.causes(derived);
+        let prior = match (self.is_derived(p1), self.is_derived(p2)) {
+            (true, false) if !self.shared.contains(&derived) => Some((p1, p2)),
+            (false, true) if !self.shared.contains(&derived) => Some((p2, p1)),
+            _ => None,
+        };
+        match prior {
+            Some((prior, prior_external)) => {
+                self.build(prior);
+                let line = format!(
+                    "And because {} and {}, {}.",
+                    self.describe(prior_external),
+                    self.describe(external),
+                    conclusion
+                );
+                self.lines.push(line);
+            }
+            _ => {
+                self.build(derived);
+                let line = format!("And because {}, {}.", self.describe(external), conclusion);
+                self.lines.push(line);
+            }
+        }
+    }
+}
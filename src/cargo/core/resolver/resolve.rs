@@ -5,7 +5,7 @@ use crate::util::errors::CargoResult;
 use crate::util::interning::InternedString;
 use crate::util::Graph;
 use std::borrow::Borrow;
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt;
 
 /// Represents a fully-resolved package dependency graph. Each node in the graph
@@ -42,6 +42,10 @@ pub struct Resolve {
     /// registry every time it runs, and keeps the resolve in a locked state
     /// so it doesn't re-resolve the unused entries.
     unused_patches: Vec<PackageId>,
+    /// Packages that are yanked from their registry, but whose use was
+    /// explicitly allowed with `cargo update --allow-yanked`. These are
+    /// annotated with `yanked = true` in `Cargo.lock`.
+    yanked: HashSet<PackageId>,
     /// A map from packages to a set of their public dependencies
     public_dependencies: HashMap<PackageId, HashSet<PackageId>>,
     /// Version of the `Cargo.lock` format, see
@@ -70,6 +74,11 @@ pub enum ResolveVersion {
     /// `branch = "master"` are no longer encoded the same way as those without
     /// branch specifiers.
     V3,
+    /// A leaner format where each package is listed on a single line, the
+    /// crates.io source is abbreviated, and no `[metadata]` table is written.
+    /// Not used by default, but can be selected with the unstable
+    /// `lockfile.version` config.
+    V4,
 }
 
 impl Resolve {
@@ -107,6 +116,7 @@ impl Resolve {
             checksums,
             metadata,
             unused_patches,
+            yanked: HashSet::new(),
             empty_features: Vec::new(),
             reverse_replacements,
             public_dependencies,
@@ -218,6 +228,11 @@ unable to verify that `{0}` is the same as when the lockfile was generated
         // Be sure to just copy over any unknown metadata.
         self.metadata = previous.metadata.clone();
 
+        // Keep the allowance for yanked packages which are still in use.
+        let graph = &self.graph;
+        let still_used = previous.yanked.iter().filter(|id| graph.contains(*id));
+        self.yanked.extend(still_used);
+
         // Preserve the lockfile encoding where possible to avoid lockfile churn
         self.version = previous.version;
 
@@ -277,6 +292,71 @@ unable to verify that `{0}` is the same as when the lockfile was generated
             .unwrap_or_else(|| panic!("Unknown dependency {:?} for package {:?}", dep, pkg))
     }
 
+    /// Returns the dependency cycles which `pkg` is part of through one of
+    /// its dev-dependencies.
+    ///
+    /// The resolver only rejects cycles of normal and build dependencies, so
+    /// a dev-dependency is allowed to depend on the package which uses it.
+    /// Each returned path starts at `pkg`, continues with the dev-dependency
+    /// and ends with `pkg` again. Paths are sorted so the output is stable.
+    pub fn dev_dependency_cycles(&self, pkg: PackageId) -> Vec<Vec<PackageId>> {
+        let mut dev_deps = self
+            .deps(pkg)
+            .filter(|(_, deps)| deps.iter().all(|d| !d.is_transitive()))
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        dev_deps.sort();
+
+        let mut cycles = Vec::new();
+        for dev_dep in dev_deps {
+            // Breadth-first search along the transitive edges, remembering
+            // where each package was reached from to rebuild the path.
+            let mut parents = HashMap::new();
+            let mut queue = VecDeque::new();
+            queue.push_back(dev_dep);
+            'search: while let Some(id) = queue.pop_front() {
+                let mut deps = self
+                    .deps(id)
+                    .filter(|(_, deps)| deps.iter().any(|d| d.is_transitive()))
+                    .map(|(dep_id, _)| dep_id)
+                    .collect::<Vec<_>>();
+                deps.sort();
+                for dep_id in deps {
+                    if dep_id == dev_dep || parents.contains_key(&dep_id) {
+                        continue;
+                    }
+                    parents.insert(dep_id, id);
+                    if dep_id == pkg {
+                        break 'search;
+                    }
+                    queue.push_back(dep_id);
+                }
+            }
+            if !parents.contains_key(&pkg) {
+                continue;
+            }
+            let mut path = vec![pkg];
+            let mut cur = pkg;
+            while let Some(&parent) = parents.get(&cur) {
+                path.push(parent);
+                cur = parent;
+            }
+            path.push(pkg);
+            path.reverse();
+            cycles.push(path);
+        }
+        cycles
+    }
+
+    /// Displays a path of the dependency graph, like the cycles of
+    /// `dev_dependency_cycles`, as `a v1.0.0 -> b v2.0.0`.
+    pub fn display_path(path: &[PackageId]) -> String {
+        path.iter()
+            .map(|id| format!("{} v{}", id.name(), id.version()))
+            .collect::<Vec<_>>()
+            .join(" -> ")
+    }
+
     pub fn query(&self, spec: &str) -> CargoResult<PackageId> {
         PackageIdSpec::query_str(spec, self.iter())
     }
@@ -297,35 +377,77 @@ unable to verify that `{0}` is the same as when the lockfile was generated
         &self.metadata
     }
 
+    pub fn yanked(&self) -> &HashSet<PackageId> {
+        &self.yanked
+    }
+
+    pub fn allow_yanked(&mut self, pkg: PackageId) {
+        self.yanked.insert(pkg);
+    }
+
     pub fn extern_crate_name(
         &self,
         from: PackageId,
         to: PackageId,
         to_target: &Target,
     ) -> CargoResult<String> {
-        let empty_set: HashSet<Dependency> = HashSet::new();
-        let deps = if from == to {
-            &empty_set
-        } else {
-            self.dependencies_listed(from, to)
-        };
-
         let crate_name = to_target.crate_name();
-        let mut names = deps.iter().map(|d| {
-            d.explicit_name_in_toml()
-                .map(|s| s.as_str().replace("-", "_"))
-                .unwrap_or_else(|| crate_name.clone())
-        });
-        let name = names.next().unwrap_or_else(|| crate_name.clone());
-        for n in names {
-            anyhow::ensure!(
-                n == name,
-                "the crate `{}` depends on crate `{}` multiple times with different names",
+        let mut names = self.extern_crate_names(from, to, to_target);
+        if names.len() > 1 {
+            let names = names
+                .iter()
+                .map(|(name, deps)| {
+                    let renamed = if *name == crate_name {
+                        String::new()
+                    } else {
+                        format!(" (renamed from `{}`)", to.name())
+                    };
+                    let mut tables: Vec<_> = deps.iter().map(|d| d.kind().kind_table()).collect();
+                    tables.sort_unstable();
+                    tables.dedup();
+                    format!("`{}`{} in `{}`", name, renamed, tables.join("`, `"))
+                })
+                .collect::<Vec<_>>();
+            anyhow::bail!(
+                "the crate `{}` depends on crate `{}` multiple times with different names: {}",
                 from,
                 to,
+                names.join(", "),
             );
         }
-        Ok(name)
+        Ok(names.pop().map(|(name, _)| name).unwrap_or(crate_name))
+    }
+
+    /// Returns the names `from` uses for its dependency `to` in its code,
+    /// sorted by name, along with the dependency declarations using each of
+    /// them.
+    ///
+    /// There is more than one name if the same package is listed several
+    /// times in the manifest of `from` with different `package` renames,
+    /// which is an error when compiling.
+    pub fn extern_crate_names(
+        &self,
+        from: PackageId,
+        to: PackageId,
+        to_target: &Target,
+    ) -> Vec<(String, Vec<&Dependency>)> {
+        if from == to {
+            return Vec::new();
+        }
+        let crate_name = to_target.crate_name();
+        let mut names: Vec<(String, Vec<&Dependency>)> = Vec::new();
+        for dep in self.dependencies_listed(from, to) {
+            let name = dep
+                .explicit_name_in_toml()
+                .map(|s| s.as_str().replace("-", "_"))
+                .unwrap_or_else(|| crate_name.clone());
+            match names.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, deps)) => deps.push(dep),
+                None => names.push((name, vec![dep])),
+            }
+        }
+        names.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        names
     }
 
     fn dependencies_listed(&self, from: PackageId, to: PackageId) -> &HashSet<Dependency> {
@@ -375,7 +497,7 @@ impl PartialEq for Resolve {
         compare! {
             // fields to compare
             graph replacements reverse_replacements empty_features features
-            checksums metadata unused_patches public_dependencies summaries
+            checksums metadata unused_patches yanked public_dependencies summaries
             |
             // fields to ignore
             version
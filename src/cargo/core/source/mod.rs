@@ -1,13 +1,15 @@
 use std::collections::hash_map::HashMap;
 use std::fmt;
+use std::path::PathBuf;
 
 use crate::core::package::PackageSet;
 use crate::core::{Dependency, Package, PackageId, Summary};
+use crate::util::interning::InternedString;
 use crate::util::{CargoResult, Config};
 
 mod source_id;
 
-pub use self::source_id::{GitReference, SourceId};
+pub use self::source_id::{GitReference, GitSubmodules, SourceId};
 
 /// Something that finds and downloads remote packages based on names and versions.
 pub trait Source {
@@ -62,6 +64,14 @@ pub trait Source {
 
     fn finish_download(&mut self, package: PackageId, contents: Vec<u8>) -> CargoResult<Package>;
 
+    /// Called after downloading `package` failed with `err`. Sources which
+    /// can get the package from another location, such as the next one of a
+    /// list of mirrors, switch the following `download` of it to that
+    /// location and return `true`.
+    fn fall_back(&mut self, _package: PackageId, _err: &anyhow::Error) -> CargoResult<bool> {
+        Ok(false)
+    }
+
     /// Generates a unique string which represents the fingerprint of the
     /// current state of the source.
     ///
@@ -101,11 +111,61 @@ pub trait Source {
     /// Query if a package is yanked. Only registry sources can mark packages
     /// as yanked. This ignores the yanked whitelist.
     fn is_yanked(&mut self, _pkg: PackageId) -> CargoResult<bool>;
+
+    /// Returns a string describing the current state of the index of this
+    /// source, which changes whenever the index is updated. This is `None`
+    /// for sources without an index, or whose state can't be tracked.
+    fn index_version(&self) -> Option<InternedString> {
+        None
+    }
+
+    /// Returns the prebuilt binaries of `pkg` for the `target` triple, if
+    /// this source has any. Only registry sources can provide them.
+    fn prebuilt_binary(
+        &mut self,
+        _pkg: PackageId,
+        _target: &str,
+    ) -> CargoResult<Option<PrebuiltBinary>> {
+        Ok(None)
+    }
+
+    /// Returns the URL the `.crate` file of `pkg` is downloaded from, if this
+    /// source downloads packages over the network.
+    fn download_url(&mut self, _pkg: PackageId) -> CargoResult<Option<String>> {
+        Ok(None)
+    }
+
+    /// Saves the caches which this source filled while being queried, such
+    /// as the index store of registries with `-Z index-store`.
+    fn save_caches(&mut self) -> CargoResult<()> {
+        Ok(())
+    }
+}
+
+/// A prebuilt `.tar.gz` archive of the binaries of a package, used by `cargo
+/// install -Z prebuilt-install` instead of building them.
+pub struct PrebuiltBinary {
+    /// Where to download the archive from.
+    pub url: String,
+    /// The SHA-256 checksum of the archive.
+    pub cksum: String,
+    /// The signature of the archive by the publisher of the package. See
+    /// `util::crate_signature`.
+    pub signature: Option<String>,
 }
 
 pub enum MaybePackage {
     Ready(Package),
-    Download { url: String, descriptor: String },
+    Download {
+        url: String,
+        descriptor: String,
+        /// Where the data downloaded so far is kept, so that an interrupted
+        /// download can be resumed.
+        partial: Option<PathBuf>,
+        /// The value of the `Authorization` header sent with the request, for
+        /// registries which require authentication for downloads.
+        authorization: Option<String>,
+    },
 }
 
 impl<'a, T: Source + ?Sized + 'a> Source for Box<T> {
@@ -153,6 +213,11 @@ impl<'a, T: Source + ?Sized + 'a> Source for Box<T> {
         (**self).finish_download(id, data)
     }
 
+    /// Forwards to `Source::fall_back`.
+    fn fall_back(&mut self, id: PackageId, err: &anyhow::Error) -> CargoResult<bool> {
+        (**self).fall_back(id, err)
+    }
+
     /// Forwards to `Source::fingerprint`.
     fn fingerprint(&self, pkg: &Package) -> CargoResult<String> {
         (**self).fingerprint(pkg)
@@ -178,6 +243,26 @@ impl<'a, T: Source + ?Sized + 'a> Source for Box<T> {
     fn is_yanked(&mut self, pkg: PackageId) -> CargoResult<bool> {
         (**self).is_yanked(pkg)
     }
+
+    fn index_version(&self) -> Option<InternedString> {
+        (**self).index_version()
+    }
+
+    fn prebuilt_binary(
+        &mut self,
+        pkg: PackageId,
+        target: &str,
+    ) -> CargoResult<Option<PrebuiltBinary>> {
+        (**self).prebuilt_binary(pkg, target)
+    }
+
+    fn download_url(&mut self, pkg: PackageId) -> CargoResult<Option<String>> {
+        (**self).download_url(pkg)
+    }
+
+    fn save_caches(&mut self) -> CargoResult<()> {
+        (**self).save_caches()
+    }
 }
 
 impl<'a, T: Source + ?Sized + 'a> Source for &'a mut T {
@@ -217,6 +302,10 @@ impl<'a, T: Source + ?Sized + 'a> Source for &'a mut T {
         (**self).finish_download(id, data)
     }
 
+    fn fall_back(&mut self, id: PackageId, err: &anyhow::Error) -> CargoResult<bool> {
+        (**self).fall_back(id, err)
+    }
+
     fn fingerprint(&self, pkg: &Package) -> CargoResult<String> {
         (**self).fingerprint(pkg)
     }
@@ -240,6 +329,26 @@ impl<'a, T: Source + ?Sized + 'a> Source for &'a mut T {
     fn is_yanked(&mut self, pkg: PackageId) -> CargoResult<bool> {
         (**self).is_yanked(pkg)
     }
+
+    fn index_version(&self) -> Option<InternedString> {
+        (**self).index_version()
+    }
+
+    fn prebuilt_binary(
+        &mut self,
+        pkg: PackageId,
+        target: &str,
+    ) -> CargoResult<Option<PrebuiltBinary>> {
+        (**self).prebuilt_binary(pkg, target)
+    }
+
+    fn download_url(&mut self, pkg: PackageId) -> CargoResult<Option<String>> {
+        (**self).download_url(pkg)
+    }
+
+    fn save_caches(&mut self) -> CargoResult<()> {
+        (**self).save_caches()
+    }
 }
 
 /// A `HashMap` of `SourceId` -> `Box<Source>`.
@@ -38,6 +38,8 @@ struct SourceIdInner {
     /// WARNING: this is not always set for alt-registries when the name is
     /// not known.
     name: Option<String>,
+    /// For git sources, the submodules which are checked out.
+    submodules: GitSubmodules,
 }
 
 /// The possible kinds of code source. Along with `SourceIdInner`, this fully defines the
@@ -69,6 +71,18 @@ pub enum GitReference {
     DefaultBranch,
 }
 
+/// The submodules which are checked out along with a Git repository.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GitSubmodules {
+    /// All of them, recursively.
+    All,
+    /// None of them.
+    Skip,
+    /// Only the ones at these paths, relative to the root of the repository,
+    /// with all of their own submodules.
+    Only(Vec<String>),
+}
+
 impl SourceId {
     /// Creates a `SourceId` object from the kind and URL.
     ///
@@ -80,6 +94,7 @@ impl SourceId {
             url,
             precise: None,
             name: None,
+            submodules: GitSubmodules::All,
         });
         Ok(source_id)
     }
@@ -115,6 +130,7 @@ impl SourceId {
             "git" => {
                 let mut url = url.into_url()?;
                 let mut reference = GitReference::DefaultBranch;
+                let mut submodules = GitSubmodules::All;
                 for (k, v) in url.query_pairs() {
                     match &k[..] {
                         // Map older 'ref' to branch.
@@ -122,13 +138,16 @@ impl SourceId {
 
                         "rev" => reference = GitReference::Rev(v.into_owned()),
                         "tag" => reference = GitReference::Tag(v.into_owned()),
+                        "submodules" => submodules = GitSubmodules::from_query(&v),
                         _ => {}
                     }
                 }
                 let precise = url.fragment().map(|s| s.to_owned());
                 url.set_fragment(None);
                 url.set_query(None);
-                Ok(SourceId::for_git(&url, reference)?.with_precise(precise))
+                Ok(SourceId::for_git(&url, reference)?
+                    .with_git_submodules(submodules)
+                    .with_precise(precise))
             }
             "registry" => {
                 let url = url.into_url()?;
@@ -200,6 +219,7 @@ impl SourceId {
             url,
             precise: None,
             name: Some(key.to_string()),
+            submodules: GitSubmodules::All,
         }))
     }
 
@@ -314,6 +334,25 @@ impl SourceId {
         }
     }
 
+    /// Gets the submodules which are checked out if this is a git source,
+    /// otherwise `None`.
+    pub fn git_submodules(self) -> Option<&'static GitSubmodules> {
+        match self.inner.kind {
+            SourceKind::Git(_) => Some(&self.inner.submodules),
+            _ => None,
+        }
+    }
+
+    /// Creates a new `SourceId` from this git source which only checks out
+    /// the given submodules.
+    pub fn with_git_submodules(self, submodules: GitSubmodules) -> SourceId {
+        assert!(self.is_git(), "id is not git, id={}", self);
+        SourceId::wrap(SourceIdInner {
+            submodules,
+            ..(*self.inner).clone()
+        })
+    }
+
     /// Creates a new `SourceId` from this source with the given `precise`.
     pub fn with_precise(self, v: Option<String>) -> SourceId {
         SourceId::wrap(SourceIdInner {
@@ -423,6 +462,10 @@ impl Ord for SourceId {
                 if ord != Ordering::Equal {
                     return ord;
                 }
+                let ord = self.inner.submodules.cmp(&other.inner.submodules);
+                if ord != Ordering::Equal {
+                    return ord;
+                }
             }
         }
 
@@ -479,9 +522,7 @@ impl fmt::Display for SourceId {
                 // Don't replace the URL display for git references,
                 // because those are kind of expected to be URLs.
                 write!(f, "{}", self.inner.url)?;
-                if let Some(pretty) = reference.pretty_ref() {
-                    write!(f, "?{}", pretty)?;
-                }
+                write_git_query(f, reference, &self.inner.submodules)?;
 
                 if let Some(ref s) = self.inner.precise {
                     let len = cmp::min(s.len(), 8);
@@ -538,7 +579,14 @@ impl Hash for SourceId {
             SourceKind::Directory => 4usize.hash(into),
         }
         match self.inner.kind {
-            SourceKind::Git(_) => self.inner.canonical_url.hash(into),
+            SourceKind::Git(_) => {
+                self.inner.canonical_url.hash(into);
+                // Only hashed when set, so that the hash of sources checking
+                // out all submodules is the same as in previous versions.
+                if self.inner.submodules != GitSubmodules::All {
+                    self.inner.submodules.hash(into);
+                }
+            }
             _ => self.inner.url.as_str().hash(into),
         }
     }
@@ -561,12 +609,11 @@ impl<'a> fmt::Display for SourceIdAsUrl<'a> {
                 kind: SourceKind::Git(ref reference),
                 ref url,
                 ref precise,
+                ref submodules,
                 ..
             } => {
                 write!(f, "git+{}", url)?;
-                if let Some(pretty) = reference.pretty_ref() {
-                    write!(f, "?{}", pretty)?;
-                }
+                write_git_query(f, reference, submodules)?;
                 if let Some(precise) = precise.as_ref() {
                     write!(f, "#{}", precise)?;
                 }
@@ -591,6 +638,66 @@ impl<'a> fmt::Display for SourceIdAsUrl<'a> {
     }
 }
 
+/// Writes the query of the URL of a git source, with its reference and
+/// submodules.
+fn write_git_query(
+    f: &mut Formatter<'_>,
+    reference: &GitReference,
+    submodules: &GitSubmodules,
+) -> fmt::Result {
+    let mut sep = '?';
+    if let Some(pretty) = reference.pretty_ref() {
+        write!(f, "{}{}", sep, pretty)?;
+        sep = '&';
+    }
+    match submodules {
+        GitSubmodules::All => Ok(()),
+        GitSubmodules::Skip => write!(f, "{}submodules=false", sep),
+        GitSubmodules::Only(paths) => write!(f, "{}submodules={}", sep, paths.join(",")),
+    }
+}
+
+impl GitSubmodules {
+    /// Returns the setting checking out only the submodules at `paths`.
+    pub fn from_paths(paths: &[String]) -> CargoResult<GitSubmodules> {
+        if let Some(path) = paths.iter().find(|p| p.contains(',')) {
+            anyhow::bail!(
+                "submodule path `{}` contains a comma, which isn't supported",
+                path
+            );
+        }
+        let mut paths: Vec<_> = paths
+            .iter()
+            .map(|p| p.trim_matches('/').to_string())
+            .collect();
+        paths.sort();
+        paths.dedup();
+        if paths.is_empty() {
+            Ok(GitSubmodules::Skip)
+        } else {
+            Ok(GitSubmodules::Only(paths))
+        }
+    }
+
+    /// Parses the `submodules` query parameter of the URL of a git source.
+    fn from_query(v: &str) -> GitSubmodules {
+        match v {
+            "false" | "" => GitSubmodules::Skip,
+            _ => GitSubmodules::Only(v.split(',').map(|s| s.to_string()).collect()),
+        }
+    }
+
+    /// Returns whether the submodule at `path`, relative to the root of the
+    /// repository, is checked out.
+    pub fn includes(&self, path: &str) -> bool {
+        match self {
+            GitSubmodules::All => true,
+            GitSubmodules::Skip => false,
+            GitSubmodules::Only(paths) => paths.iter().any(|p| p == path),
+        }
+    }
+}
+
 impl GitReference {
     /// Returns a `Display`able view of this git reference, or None if using
     /// the head of the default branch
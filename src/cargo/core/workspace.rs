@@ -5,7 +5,7 @@ use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::slice;
 
-use glob::glob;
+use glob::{glob, Pattern};
 use log::debug;
 use url::Url;
 
@@ -69,6 +69,10 @@ pub struct Workspace<'cfg> {
     // or only the root package for non-virtual workspaces.
     default_members: Vec<PathBuf>,
 
+    // The `default_members` of the workspace as seen from its root, which are
+    // selected by `--default-members` regardless of the current directory.
+    root_default_members: Vec<PathBuf>,
+
     // `true` if this is a temporary workspace created for the purposes of the
     // `cargo install` or `cargo package` commands.
     is_ephemeral: bool,
@@ -92,6 +96,20 @@ pub struct Workspace<'cfg> {
 
     /// Workspace-level custom metadata
     custom_metadata: Option<toml::Value>,
+
+    /// The kind of version requirement given to the path dependencies on
+    /// other members which don't specify one, when packaging.
+    path_dep_version: PathDepVersion,
+}
+
+/// The version requirement written in the packaged manifest for a path
+/// dependency on another member of the workspace without a `version`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathDepVersion {
+    /// A caret requirement, such as `1.2.3`, accepting compatible versions.
+    Caret,
+    /// An exact requirement, such as `=1.2.3`.
+    Exact,
 }
 
 // Separate structure for tracking loaded packages (to avoid loading anything
@@ -185,12 +203,14 @@ impl<'cfg> Workspace<'cfg> {
             members: Vec::new(),
             member_ids: HashSet::new(),
             default_members: Vec::new(),
+            root_default_members: Vec::new(),
             is_ephemeral: false,
             require_optional_deps: true,
             loaded_packages: RefCell::new(HashMap::new()),
             ignore_lock: false,
             resolve_behavior: None,
             custom_metadata: None,
+            path_dep_version: PathDepVersion::Caret,
         }
     }
 
@@ -244,6 +264,7 @@ impl<'cfg> Workspace<'cfg> {
         ws.members.push(ws.current_manifest.clone());
         ws.member_ids.insert(id);
         ws.default_members.push(ws.current_manifest.clone());
+        ws.root_default_members.push(ws.current_manifest.clone());
         Ok(ws)
     }
 
@@ -373,6 +394,15 @@ impl<'cfg> Workspace<'cfg> {
         }
     }
 
+    /// Returns an iterator over the default packages of this workspace as
+    /// seen from its root, even if the current package is another member.
+    pub fn root_default_members<'a>(&'a self) -> Members<'a, 'cfg> {
+        Members {
+            ws: self,
+            iter: self.root_default_members.iter(),
+        }
+    }
+
     /// Returns true if the package is a member of the workspace.
     pub fn is_member(&self, pkg: &Package) -> bool {
         self.member_ids.contains(&pkg.package_id())
@@ -403,6 +433,44 @@ impl<'cfg> Workspace<'cfg> {
         self
     }
 
+    pub fn path_dep_version(&self) -> PathDepVersion {
+        self.path_dep_version
+    }
+
+    pub fn set_path_dep_version(
+        &mut self,
+        path_dep_version: PathDepVersion,
+    ) -> &mut Workspace<'cfg> {
+        self.path_dep_version = path_dep_version;
+        self
+    }
+
+    /// Returns the version requirement to package a path dependency on the
+    /// package at `path` with, if it doesn't specify one.
+    ///
+    /// This is only `Some` if that package is a member of the workspace
+    /// which can be published, so that the requirement can be fulfilled
+    /// once it is.
+    pub fn path_dep_version_req(&self, path: &Path) -> Option<String> {
+        let path = paths::normalize_path(path);
+        let member = self.members().find(|pkg| pkg.root() == path)?;
+        if matches!(member.publish(), Some(regs) if regs.is_empty()) {
+            return None;
+        }
+        Some(match self.path_dep_version {
+            PathDepVersion::Caret => member.version().to_string(),
+            PathDepVersion::Exact => format!("={}", member.version()),
+        })
+    }
+
+    /// Returns the name the package at `path` is published under, if it is a
+    /// member of the workspace with `publish-name` set.
+    pub fn path_dep_publish_name(&self, path: &Path) -> Option<InternedString> {
+        let path = paths::normalize_path(path);
+        let member = self.members().find(|pkg| pkg.root() == path)?;
+        member.manifest().publish_name()
+    }
+
     pub fn custom_metadata(&self) -> Option<&toml::Value> {
         self.custom_metadata.as_ref()
     }
@@ -514,6 +582,8 @@ impl<'cfg> Workspace<'cfg> {
                 debug!("find_members - only me as a member");
                 self.members.push(self.current_manifest.clone());
                 self.default_members.push(self.current_manifest.clone());
+                self.root_default_members
+                    .push(self.current_manifest.clone());
                 if let Ok(pkg) = self.current() {
                     let id = pkg.package_id();
                     self.member_ids.insert(id);
@@ -527,18 +597,28 @@ impl<'cfg> Workspace<'cfg> {
 
         let members_paths =
             workspace_config.members_paths(workspace_config.members.as_ref().unwrap_or(&vec![]))?;
-        let default_members_paths = if root_manifest_path == self.current_manifest {
-            if let Some(ref default) = workspace_config.default_members {
-                Some(workspace_config.members_paths(default)?)
-            } else {
-                None
-            }
-        } else {
-            None
+        let default_members_paths = match workspace_config.default_members {
+            Some(ref default) => Some(workspace_config.members_paths(default)?),
+            None => None,
         };
 
+        // Packages matched by a glob which belong to another workspace, like
+        // the root and members of a workspace nested in this one, aren't
+        // members. Explicitly listed ones still are, and are reported as
+        // errors by `validate`.
+        let explicit_members = workspace_config.explicit_members_paths();
+        let mut other_workspaces = HashSet::new();
         for path in &members_paths {
-            self.find_path_deps(&path.join("Cargo.toml"), &root_manifest_path, false)?;
+            let manifest_path = path.join("Cargo.toml");
+            if !explicit_members.contains(&paths::normalize_path(path))
+                && manifest_path.exists()
+                && self.belongs_to_other_workspace(&manifest_path)?
+            {
+                debug!("find_members - other workspace {}", path.display());
+                other_workspaces.insert(paths::normalize_path(path));
+                continue;
+            }
+            self.find_path_deps(&manifest_path, &root_manifest_path, false)?;
         }
 
         if let Some(default) = default_members_paths {
@@ -553,7 +633,8 @@ impl<'cfg> Workspace<'cfg> {
                     // include `/Cargo.toml`, and because excluded paths may not
                     // be crates.
                     let exclude = members_paths.contains(&normalized_path)
-                        && workspace_config.is_excluded(&normalized_path);
+                        && (workspace_config.is_excluded(&normalized_path)
+                            || other_workspaces.contains(&normalized_path));
                     if exclude {
                         continue;
                     }
@@ -563,10 +644,16 @@ impl<'cfg> Workspace<'cfg> {
                         path.display()
                     )
                 }
-                self.default_members.push(manifest_path)
+                self.root_default_members.push(manifest_path)
             }
-        } else if self.is_virtual() {
-            self.default_members = self.members.clone()
+        } else if let Some(MaybePackage::Virtual(_)) = self.packages.maybe_get(&root_manifest_path)
+        {
+            self.root_default_members = self.members.clone()
+        } else {
+            self.root_default_members.push(root_manifest_path.clone())
+        }
+        if root_manifest_path == self.current_manifest {
+            self.default_members = self.root_default_members.clone();
         } else {
             self.default_members.push(self.current_manifest.clone())
         }
@@ -574,6 +661,15 @@ impl<'cfg> Workspace<'cfg> {
         self.find_path_deps(&root_manifest_path, &root_manifest_path, false)
     }
 
+    /// Returns whether the package at `manifest_path` is the root or a
+    /// member of another workspace than this one.
+    fn belongs_to_other_workspace(&mut self, manifest_path: &Path) -> CargoResult<bool> {
+        Ok(match self.find_root(manifest_path)? {
+            Some(root) => Some(root) != self.root_manifest,
+            None => false,
+        })
+    }
+
     fn find_path_deps(
         &mut self,
         manifest_path: &Path,
@@ -584,12 +680,11 @@ impl<'cfg> Workspace<'cfg> {
         if self.members.contains(&manifest_path) {
             return Ok(());
         }
-        if is_path_dep
-            && !manifest_path.parent().unwrap().starts_with(self.root())
-            && self.find_root(&manifest_path)? != self.root_manifest
-        {
-            // If `manifest_path` is a path dependency outside of the workspace,
-            // don't add it, or any of its dependencies, as a members.
+        if is_path_dep && self.find_root(&manifest_path)? != self.root_manifest {
+            // If `manifest_path` is a path dependency belonging to another
+            // workspace, or to none, don't add it, or any of its
+            // dependencies, as a members. This includes the packages of a
+            // workspace nested in this one.
             return Ok(());
         }
 
@@ -1059,9 +1154,20 @@ impl<'cfg> Workspace<'cfg> {
         specs: &[PackageIdSpec],
         requested_features: &RequestedFeatures,
     ) -> CargoResult<Vec<(&Package, RequestedFeatures)>> {
+        // In the root of a virtual workspace there is no "current" package,
+        // so the features apply to the single member selected with `-p`.
+        let current = self.current_opt().or_else(|| {
+            let mut selected = self
+                .members()
+                .filter(|m| specs.iter().any(|spec| spec.matches(m.package_id())));
+            match (selected.next(), selected.next()) {
+                (Some(member), None) => Some(member),
+                _ => None,
+            }
+        });
         let ms = self.members().filter_map(|member| {
             let member_id = member.package_id();
-            match self.current_opt() {
+            match current {
                 // The features passed on the command-line only apply to
                 // the "current" package (determined by the cwd).
                 Some(current) if member_id == current.package_id() => {
@@ -1190,10 +1296,27 @@ impl WorkspaceRootConfig {
         !explicit_member && excluded
     }
 
+    /// Returns the normalized paths of the `members` entries which aren't
+    /// globs.
+    fn explicit_members_paths(&self) -> HashSet<PathBuf> {
+        self.members
+            .iter()
+            .flatten()
+            .filter(|member| Pattern::escape(member) == **member)
+            .map(|member| paths::normalize_path(&self.root_dir.join(member)))
+            .collect()
+    }
+
     fn has_members_list(&self) -> bool {
         self.members.is_some()
     }
 
+    /// Returns the directories the `members` entries currently expand to,
+    /// which change when a member matching a glob is added or removed.
+    pub fn expand_members(&self) -> CargoResult<Vec<PathBuf>> {
+        self.members_paths(self.members.as_ref().unwrap_or(&vec![]))
+    }
+
     fn members_paths(&self, globs: &[String]) -> CargoResult<Vec<PathBuf>> {
         let mut expanded_list = Vec::new();
 
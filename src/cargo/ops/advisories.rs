@@ -0,0 +1,373 @@
+//! Checking the packages of `Cargo.lock` against an advisory database in the
+//! [RustSec] format, with `-Z advisories`.
+//!
+//! The database is a git repository, fetched into `$CARGO_HOME/advisory-db`
+//! like the registry indices: only the git objects are stored, and the
+//! advisories are read from the tree of the fetched branch. `cargo update`
+//! fetches the latest advisories, while the other commands only fetch the
+//! database if it wasn't fetched before, and use the cached one in the
+//! offline mode.
+//!
+//! The advisories of a package are the `crates/<name>/RUSTSEC-*.md` files of
+//! the database, whose metadata is the TOML code block they start with.
+//! A version of the package is vulnerable if it matches neither the
+//! `patched` nor the `unaffected` requirements of an advisory. Informational
+//! advisories, like unmaintained crates, and withdrawn ones are ignored.
+//!
+//! [RustSec]: https://github.com/rustsec/advisory-db
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::bail;
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+use url::Url;
+
+use crate::core::resolver::Resolve;
+use crate::core::{GitReference, PackageId, Workspace};
+use crate::sources::git::{self, FetchDepth};
+use crate::util::errors::{CargoResult, CargoResultExt};
+use crate::util::{short_hash, Config, IntoUrl};
+
+/// The database used if `advisories.db` isn't set.
+const DEFAULT_DB: &str = "https://github.com/rustsec/advisory-db";
+
+/// The branch fetched if `advisories.branch` isn't set.
+const DEFAULT_BRANCH: &str = "main";
+
+/// The `[advisories]` configuration table.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+struct AdvisoriesConfig {
+    /// The git URL of the database.
+    db: Option<String>,
+    /// The branch of the database to fetch.
+    branch: Option<String>,
+    /// `warn` or `deny`.
+    policy: Option<String>,
+    /// IDs of advisories which aren't reported.
+    #[serde(default)]
+    ignore: Vec<String>,
+}
+
+/// The metadata of an advisory file.
+#[derive(Deserialize)]
+struct Advisory {
+    advisory: AdvisoryInfo,
+    #[serde(default)]
+    versions: AdvisoryVersions,
+}
+
+#[derive(Deserialize)]
+struct AdvisoryInfo {
+    id: String,
+    package: String,
+    /// Only set in the TOML advisories, the Markdown ones have it as their
+    /// first heading.
+    title: Option<String>,
+    url: Option<String>,
+    /// The kind of informational advisory, like `unmaintained`.
+    informational: Option<String>,
+    /// The date the advisory was withdrawn.
+    withdrawn: Option<toml::Value>,
+}
+
+#[derive(Deserialize, Default)]
+struct AdvisoryVersions {
+    #[serde(default)]
+    patched: Vec<String>,
+    #[serde(default)]
+    unaffected: Vec<String>,
+}
+
+/// Checks the registry packages of `resolve` against the advisory database,
+/// warning about the vulnerable ones, or failing with the `deny` policy.
+/// The database is fetched first if `update` is true, or if it wasn't
+/// fetched before.
+pub fn check_advisories(ws: &Workspace<'_>, resolve: &Resolve, update: bool) -> CargoResult<()> {
+    let config = ws.config();
+    if !config.cli_unstable().advisories {
+        return Ok(());
+    }
+    let adv_config = config
+        .get::<Option<AdvisoriesConfig>>("advisories")?
+        .unwrap_or_default();
+    let deny = match adv_config.policy.as_deref() {
+        None | Some("warn") => false,
+        Some("deny") => true,
+        Some(policy) => bail!(
+            "invalid `advisories.policy` value `{}`, expected `warn` or `deny`",
+            policy
+        ),
+    };
+    let url = adv_config
+        .db
+        .as_deref()
+        .unwrap_or(DEFAULT_DB)
+        .into_url()
+        .chain_err(|| "invalid `advisories.db` URL")?;
+    let branch = adv_config.branch.as_deref().unwrap_or(DEFAULT_BRANCH);
+
+    let _lock = config.acquire_package_cache_lock()?;
+    let repo = match open_db(config, &url, branch, update)? {
+        Some(repo) => repo,
+        None => return Ok(()),
+    };
+    let head = repo
+        .refname_to_id(&format!("refs/remotes/origin/{}", branch))
+        .and_then(|oid| repo.find_commit(oid))
+        .and_then(|commit| commit.tree())
+        .chain_err(|| format!("failed to read the advisory database `{}`", url))?;
+
+    let mut pkg_ids: Vec<PackageId> = resolve
+        .iter()
+        .filter(|pkg_id| pkg_id.source_id().is_default_registry())
+        .collect();
+    pkg_ids.sort();
+    let mut versions_of_name = HashMap::new();
+    for pkg_id in &pkg_ids {
+        *versions_of_name.entry(pkg_id.name()).or_insert(0) += 1;
+    }
+
+    let mut advisories_of_name = HashMap::new();
+    let mut vulnerable = 0;
+    for pkg_id in pkg_ids {
+        let advisories = match advisories_of_name.get(&pkg_id.name()) {
+            Some(advisories) => advisories,
+            None => {
+                let advisories = read_advisories(config, &repo, &head, pkg_id.name().as_str())?;
+                advisories_of_name
+                    .entry(pkg_id.name())
+                    .or_insert(advisories)
+            }
+        };
+        for (advisory, title) in advisories {
+            let info = &advisory.advisory;
+            if adv_config.ignore.contains(&info.id) || !affects(advisory, pkg_id.version()) {
+                continue;
+            }
+            vulnerable += 1;
+            let spec = if versions_of_name[&pkg_id.name()] > 1 {
+                format!("{}:{}", pkg_id.name(), pkg_id.version())
+            } else {
+                pkg_id.name().to_string()
+            };
+            let mut msg = format!(
+                "package `{}` in Cargo.lock is affected by {}: {}\n\
+                 advisory: {}\n",
+                pkg_id,
+                info.id,
+                title,
+                info.url
+                    .clone()
+                    .unwrap_or_else(|| format!("https://rustsec.org/advisories/{}", info.id))
+            );
+            let patched = &advisory.versions.patched;
+            if patched.is_empty() {
+                msg.push_str("no patched version is available");
+            } else {
+                msg.push_str(&format!("patched versions: {}\n", patched.join(", ")));
+                match compatible_patch(advisory, pkg_id.version()) {
+                    Some(version) => msg.push_str(&format!(
+                        "to update to a compatible patched version, run \
+                         `cargo update -p {} --precise {}`",
+                        spec, version
+                    )),
+                    None => msg.push_str(&format!(
+                        "no patched version is compatible with {}, \
+                         the requirements on `{}` need to be updated",
+                        pkg_id.version(),
+                        pkg_id.name()
+                    )),
+                }
+            }
+            if deny {
+                config.shell().error(msg)?;
+            } else {
+                config.shell().warn(msg)?;
+            }
+        }
+    }
+    if deny && vulnerable > 0 {
+        bail!(
+            "Cargo.lock contains {} known {}, which `advisories.policy` denies\n\
+             Advisories can be ignored by adding their ID to `advisories.ignore`.",
+            vulnerable,
+            if vulnerable == 1 {
+                "vulnerability"
+            } else {
+                "vulnerabilities"
+            }
+        );
+    }
+    Ok(())
+}
+
+/// Opens the cached advisory database fetched from `url`, fetching `branch`
+/// first if `update` is true or if it wasn't fetched before. Returns `None`
+/// if the database isn't available offline.
+fn open_db(
+    config: &Config,
+    url: &Url,
+    branch: &str,
+    update: bool,
+) -> CargoResult<Option<git2::Repository>> {
+    let ident = format!(
+        "{}-{}",
+        url.host_str().unwrap_or("local"),
+        short_hash(&url.as_str())
+    );
+    let dir = config.home().join("advisory-db").join(ident);
+    let path = config.assert_package_cache_locked(&dir);
+    let reference = GitReference::Branch(branch.to_string());
+    let refname = format!("refs/remotes/origin/{}", branch);
+
+    let mut repo = match git2::Repository::open(path) {
+        Ok(repo) => repo,
+        Err(_) => {
+            crate::util::paths::create_dir_all(path)?;
+            let mut opts = git2::RepositoryInitOptions::new();
+            opts.external_template(false).bare(true);
+            git2::Repository::init_opts(path, &opts)
+                .chain_err(|| "failed to initialize the advisory database")?
+        }
+    };
+    let fetched = repo.refname_to_id(&refname).is_ok();
+    if fetched && !update {
+        return Ok(Some(repo));
+    }
+    if !config.network_allowed() {
+        if !fetched {
+            config.shell().warn(format!(
+                "the advisory database `{}` was never fetched and can't be in \
+                 the offline mode, packages aren't checked for vulnerabilities",
+                url
+            ))?;
+            return Ok(None);
+        }
+        return Ok(Some(repo));
+    }
+    config
+        .shell()
+        .status("Updating", format!("advisory database `{}`", url))?;
+    git::fetch(
+        &mut repo,
+        url.as_str(),
+        &reference,
+        FetchDepth::for_config(config),
+        config,
+    )
+    .chain_err(|| format!("failed to fetch the advisory database `{}`", url))?;
+    Ok(Some(repo))
+}
+
+/// Reads the advisories of the package `name` in the database `tree`, with
+/// their titles.
+fn read_advisories(
+    config: &Config,
+    repo: &git2::Repository,
+    tree: &git2::Tree<'_>,
+    name: &str,
+) -> CargoResult<Vec<(Advisory, String)>> {
+    let dir = match tree.get_path(&Path::new("crates").join(name)) {
+        Ok(entry) => entry.to_object(repo)?.peel_to_tree()?,
+        Err(_) => return Ok(Vec::new()),
+    };
+    let mut advisories = Vec::new();
+    for entry in dir.iter() {
+        let file_name = entry.name().unwrap_or_default();
+        if !file_name.ends_with(".md") && !file_name.ends_with(".toml") {
+            continue;
+        }
+        let blob = entry.to_object(repo)?.peel_to_blob()?;
+        let contents = String::from_utf8_lossy(blob.content());
+        match parse_advisory(file_name, &contents) {
+            Ok((advisory, _))
+                if advisory.advisory.informational.is_some()
+                    || advisory.advisory.withdrawn.is_some()
+                    || advisory.advisory.package != name => {}
+            Ok(advisory) => advisories.push(advisory),
+            Err(e) => config.shell().warn(format!(
+                "failed to parse the advisory `crates/{}/{}`: {:#}",
+                name, file_name, e
+            ))?,
+        }
+    }
+    Ok(advisories)
+}
+
+/// Parses the advisory file `file_name`. Markdown advisories start with a
+/// TOML code block, followed by a heading with their title.
+fn parse_advisory(file_name: &str, contents: &str) -> CargoResult<(Advisory, String)> {
+    if file_name.ends_with(".toml") {
+        let advisory: Advisory = toml::from_str(contents)?;
+        let title = advisory.advisory.title.clone().unwrap_or_default();
+        return Ok((advisory, title));
+    }
+    let mut lines = contents.lines();
+    if lines.next().map(str::trim_end) != Some("```toml") {
+        bail!("the advisory doesn't start with a TOML code block");
+    }
+    let mut metadata = String::new();
+    for line in &mut lines {
+        if line.trim_end() == "```" {
+            break;
+        }
+        metadata.push_str(line);
+        metadata.push('\n');
+    }
+    let advisory: Advisory = toml::from_str(&metadata)?;
+    let title = lines
+        .find_map(|line| line.strip_prefix("# "))
+        .map(|title| title.trim().to_string())
+        .or_else(|| advisory.advisory.title.clone())
+        .unwrap_or_default();
+    Ok((advisory, title))
+}
+
+/// Parses the version requirements of an advisory, ignoring invalid ones.
+fn requirements(reqs: &[String]) -> Vec<VersionReq> {
+    reqs.iter()
+        .filter_map(|req| VersionReq::parse(req).ok())
+        .collect()
+}
+
+/// Whether `version` is affected by `advisory`.
+fn affects(advisory: &Advisory, version: &Version) -> bool {
+    let versions = &advisory.versions;
+    !requirements(&versions.patched)
+        .iter()
+        .chain(&requirements(&versions.unaffected))
+        .any(|req| req.matches(version))
+}
+
+/// Returns the lowest patched version of `advisory` which is semver
+/// compatible with `version`, taken from the lower bounds of the `patched`
+/// requirements.
+fn compatible_patch(advisory: &Advisory, version: &Version) -> Option<Version> {
+    let compatible = VersionReq::parse(&format!("^{}", version)).ok()?;
+    let patched = requirements(&advisory.versions.patched);
+    advisory
+        .versions
+        .patched
+        .iter()
+        .flat_map(|req| req.split(','))
+        .filter_map(|bound| {
+            let bound = bound.trim();
+            let bound = [">=", "^", "=", "~"]
+                .iter()
+                .find_map(|op| bound.strip_prefix(op))
+                .unwrap_or(bound)
+                .trim();
+            // `1.2` stands for `1.2.0` in a lower bound.
+            let padding = 2usize.saturating_sub(bound.matches('.').count());
+            Version::parse(&format!("{}{}", bound, ".0".repeat(padding))).ok()
+        })
+        .filter(|patch| {
+            patch > version
+                && compatible.matches(patch)
+                && patched.iter().any(|req| req.matches(patch))
+        })
+        .min()
+}
@@ -26,6 +26,7 @@ pub struct CleanOptions<'a> {
 /// Cleans the package's build artifacts.
 pub fn clean(ws: &Workspace<'_>, opts: &CleanOptions<'_>) -> CargoResult<()> {
     let mut target_dir = ws.target_dir();
+    let mut incremental_dir = Layout::incremental_root(ws)?;
     let config = ws.config();
 
     // If the doc option is set, we just want to delete the doc directory.
@@ -47,6 +48,7 @@ pub fn clean(ws: &Workspace<'_>, opts: &CleanOptions<'_>) -> CargoResult<()> {
         // that profile.
         let dir_name = profiles.get_dir_name();
         target_dir = target_dir.join(dir_name);
+        incremental_dir = incremental_dir.map(|dir| dir.join(dir_name));
     }
 
     // If we have a spec, then we need to delete some packages, otherwise, just
@@ -55,6 +57,9 @@ pub fn clean(ws: &Workspace<'_>, opts: &CleanOptions<'_>) -> CargoResult<()> {
     // Note that we don't bother grabbing a lock here as we're just going to
     // blow it all away anyway.
     if opts.spec.is_empty() {
+        if let Some(incremental_dir) = incremental_dir {
+            rm_rf(&incremental_dir, config)?;
+        }
         return rm_rf(&target_dir.into_path_unlocked(), config);
     }
 
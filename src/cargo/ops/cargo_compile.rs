@@ -22,7 +22,7 @@
 //!       executed, and then removed from the graph when finished. This
 //!       repeats until the queue is empty.
 
-use std::collections::{BTreeSet, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
@@ -40,8 +40,9 @@ use crate::core::{PackageId, PackageIdSpec, TargetKind, Workspace};
 use crate::ops;
 use crate::ops::resolve::WorkspaceResolve;
 use crate::util::config::Config;
+use crate::util::interning::InternedString;
 use crate::util::restricted_names::is_glob_pattern;
-use crate::util::{closest_msg, profile, CargoResult, StableHasher};
+use crate::util::{closest_msg, profile, CargoResult, Rustc, StableHasher};
 
 use anyhow::Context as _;
 
@@ -105,14 +106,36 @@ pub enum Packages {
     All,
     OptOut(Vec<String>),
     Packages(Vec<String>),
+    /// The workspace's default members, plus the packages in `opt_in` and
+    /// minus those in `opt_out`.
+    DefaultMembers {
+        opt_in: Vec<String>,
+        opt_out: Vec<String>,
+    },
 }
 
 impl Packages {
-    pub fn from_flags(all: bool, exclude: Vec<String>, package: Vec<String>) -> CargoResult<Self> {
+    pub fn from_flags(
+        all: bool,
+        default_members: bool,
+        exclude: Vec<String>,
+        package: Vec<String>,
+    ) -> CargoResult<Self> {
+        if default_members {
+            if all {
+                anyhow::bail!("--default-members cannot be used together with --workspace");
+            }
+            return Ok(Packages::DefaultMembers {
+                opt_in: package,
+                opt_out: exclude,
+            });
+        }
         Ok(match (all, exclude.len(), package.len()) {
             (false, 0, 0) => Packages::Default,
             (false, 0, _) => Packages::Packages(package),
-            (false, _, _) => anyhow::bail!("--exclude can only be used together with --workspace"),
+            (false, _, _) => anyhow::bail!(
+                "--exclude can only be used together with --workspace or --default-members"
+            ),
             (true, 0, _) => Packages::All,
             (true, _, _) => Packages::OptOut(exclude),
         })
@@ -166,6 +189,30 @@ impl Packages {
                 .map(Package::package_id)
                 .map(PackageIdSpec::from_package_id)
                 .collect(),
+            Packages::DefaultMembers { opt_in, opt_out } => {
+                let mut specs = if opt_in.is_empty() {
+                    Vec::new()
+                } else {
+                    Packages::Packages(opt_in.clone()).to_package_id_specs(ws)?
+                };
+                let (mut patterns, mut names) = opt_patterns_and_names(opt_out)?;
+                let defaults: Vec<_> = ws.root_default_members().map(Package::package_id).collect();
+                for pkg in ws.members() {
+                    let excluded =
+                        names.remove(pkg.name().as_str()) || match_patterns(pkg, &mut patterns);
+                    let id = pkg.package_id();
+                    if !excluded
+                        && defaults.contains(&id)
+                        && !specs.iter().any(|spec| spec.matches(id))
+                    {
+                        specs.push(PackageIdSpec::from_package_id(id));
+                    }
+                }
+                let warn = |e| ws.config().shell().warn(e);
+                emit_package_not_found(ws, names, true).or_else(warn)?;
+                emit_pattern_not_found(ws, patterns, true).or_else(warn)?;
+                specs
+            }
         };
         if specs.is_empty() {
             if ws.is_virtual() {
@@ -209,6 +256,26 @@ impl Packages {
                 emit_pattern_not_found(ws, patterns, false)?;
                 packages
             }
+            Packages::DefaultMembers { opt_in, opt_out } => {
+                let (mut in_patterns, mut in_names) = opt_patterns_and_names(opt_in)?;
+                let (mut out_patterns, mut out_names) = opt_patterns_and_names(opt_out)?;
+                let defaults: Vec<_> = ws.root_default_members().map(Package::package_id).collect();
+                let packages = ws
+                    .members()
+                    .filter(|pkg| {
+                        let opted_in = in_names.remove(pkg.name().as_str())
+                            | match_patterns(pkg, &mut in_patterns);
+                        let opted_out = out_names.remove(pkg.name().as_str())
+                            | match_patterns(pkg, &mut out_patterns);
+                        opted_in || (!opted_out && defaults.contains(&pkg.package_id()))
+                    })
+                    .collect();
+                emit_package_not_found(ws, in_names, false)?;
+                emit_pattern_not_found(ws, in_patterns, false)?;
+                emit_package_not_found(ws, out_names, true)?;
+                emit_pattern_not_found(ws, out_patterns, true)?;
+                packages
+            }
         };
         Ok(packages)
     }
@@ -221,11 +288,12 @@ impl Packages {
             Packages::All => ws.members().count() > 1,
             Packages::Packages(_) => true,
             Packages::OptOut(_) => true,
+            Packages::DefaultMembers { .. } => true,
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum LibRule {
     /// Include the library, fail if not present
     True,
@@ -235,13 +303,13 @@ pub enum LibRule {
     False,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum FilterRule {
     All,
     Just(Vec<String>),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum CompileFilter {
     Default {
         /// Flag whether targets can be safely skipped when required-features are not satisfied.
@@ -279,9 +347,9 @@ pub fn compile_ws<'a>(
     exec: &Arc<dyn Executor>,
 ) -> CargoResult<Compilation<'a>> {
     let interner = UnitInterner::new();
-    let bcx = create_bcx(ws, options, &interner)?;
+    let bcx = create_bcx(ws, options, &interner).map_err(|e| ops::explain_offline_error(ws, e))?;
     if options.build_config.unit_graph {
-        unit_graph::emit_serialized_unit_graph(&bcx.roots, &bcx.unit_graph)?;
+        unit_graph::emit_serialized_unit_graph(&bcx.roots, &bcx.unit_graph, ws.config())?;
         return Ok(Compilation::new(&bcx)?);
     }
 
@@ -332,7 +400,8 @@ pub fn create_bcx<'a, 'cfg>(
     }
     config.validate_term_config()?;
 
-    let target_data = RustcTargetData::new(ws, &build_config.requested_kinds)?;
+    let target_kinds = build_config.all_kinds();
+    let target_data = RustcTargetData::new(ws, &target_kinds)?;
 
     let specs = spec.to_package_id_specs(ws)?;
     let dev_deps = ws.require_optional_deps() || filter.need_dev_deps(build_config.mode);
@@ -345,7 +414,7 @@ pub fn create_bcx<'a, 'cfg>(
     let resolve = ops::resolve_ws_with_opts(
         ws,
         &target_data,
-        &build_config.requested_kinds,
+        &target_kinds,
         &opts,
         &specs,
         has_dev_units,
@@ -450,6 +519,7 @@ pub fn create_bcx<'a, 'cfg>(
         &to_builds,
         filter,
         &explicit_host_kinds,
+        &build_config.package_kinds,
         build_config.mode,
         &resolve,
         &workspace_resolve,
@@ -514,6 +584,8 @@ pub fn create_bcx<'a, 'cfg>(
         unit_graph = new_graph.1;
     }
 
+    check_rust_version(config, &target_data.rustc, &unit_graph)?;
+
     let mut extra_compiler_args = HashMap::new();
     if let Some(args) = extra_args {
         if units.len() != 1 {
@@ -547,6 +619,25 @@ pub fn create_bcx<'a, 'cfg>(
             }
         }
     }
+    if build_config.instrument_coverage {
+        // Only the code of the workspace members is measured, including when
+        // it's built as a dependency of another target, like the library of
+        // an integration test.
+        for unit in unit_graph.keys() {
+            if ws.is_member(&unit.pkg)
+                && !unit.mode.is_doc()
+                && !unit.mode.is_doc_test()
+                && !unit.mode.is_run_custom_build()
+                && !unit.target.is_custom_build()
+                && !unit.target.proc_macro()
+            {
+                extra_compiler_args
+                    .entry(unit.clone())
+                    .or_default()
+                    .extend(vec!["-C".to_string(), "instrument-coverage".to_string()]);
+            }
+        }
+    }
 
     let bcx = BuildContext::new(
         ws,
@@ -562,6 +653,67 @@ pub fn create_bcx<'a, 'cfg>(
     Ok(bcx)
 }
 
+/// Checks that the active rustc is at least the `rust-version` of every
+/// package to build, so that an incompatible package is reported up-front,
+/// rather than with obscure compilation errors.
+///
+/// This is an error, unless `build.incompatible-rust-version` is set to
+/// `warn`.
+fn check_rust_version(config: &Config, rustc: &Rustc, unit_graph: &UnitGraph) -> CargoResult<()> {
+    let deny = match config.build_config()?.incompatible_rust_version.as_deref() {
+        None | Some("deny") => true,
+        Some("warn") => false,
+        Some(other) => anyhow::bail!(
+            "`build.incompatible-rust-version` must be `deny` or `warn`, found `{}`",
+            other
+        ),
+    };
+    // Pre-releases like nightlies are considered to be the release they lead to.
+    let mut rustc_version = rustc.version.clone();
+    rustc_version.pre = Vec::new();
+
+    let pkgs = unit_graph
+        .keys()
+        .map(|unit| (unit.pkg.package_id(), &unit.pkg))
+        .collect::<BTreeMap<_, _>>();
+    let mut incompatible = Vec::new();
+    for (pkg_id, pkg) in pkgs {
+        let rust_version = match pkg.manifest().rust_version() {
+            Some(rust_version) => rust_version,
+            None => continue,
+        };
+        let mut parts = rust_version.split('.').map(|part| part.parse().unwrap());
+        let required = semver::Version::new(
+            parts.next().unwrap(),
+            parts.next().unwrap(),
+            parts.next().unwrap_or(0),
+        );
+        if rustc_version < required {
+            incompatible.push(format!(
+                "package `{}` cannot be built because it requires rustc {} or newer, \
+                 while the currently active rustc version is {}",
+                pkg_id, rust_version, rustc.version
+            ));
+        }
+    }
+
+    if incompatible.is_empty() {
+        return Ok(());
+    }
+    if deny {
+        anyhow::bail!(
+            "{}\n\
+             Update the toolchain, or set `build.incompatible-rust-version = \"warn\"` \
+             to try building anyway.",
+            incompatible.join("\n")
+        );
+    }
+    for message in incompatible {
+        config.shell().warn(message)?;
+    }
+    Ok(())
+}
+
 impl FilterRule {
     pub fn new(targets: Vec<String>, all: bool) -> FilterRule {
         if all {
@@ -774,6 +926,7 @@ fn generate_targets(
     packages: &[&Package],
     filter: &CompileFilter,
     requested_kinds: &[CompileKind],
+    package_kinds: &HashMap<InternedString, CompileKind>,
     mode: CompileMode,
     resolve: &Resolve,
     workspace_resolve: &Option<Resolve>,
@@ -854,7 +1007,13 @@ fn generate_targets(
             let features_for = FeaturesFor::from_for_host(target.proc_macro());
             let features = resolved_features.activated_features(pkg.package_id(), features_for);
 
-            for kind in requested_kinds {
+            // Packages with a target set in `build.package-targets` are only
+            // built for that target.
+            let kinds = match package_kinds.get(&pkg.name()) {
+                Some(kind) => std::slice::from_ref(kind),
+                None => requested_kinds,
+            };
+            for kind in kinds {
                 let unit = interner.intern(
                     pkg,
                     target,
@@ -0,0 +1,555 @@
+//! Implementation of `cargo daemon`.
+//!
+//! The daemon answers [JSON-RPC 2.0](https://www.jsonrpc.org/specification)
+//! requests sent over TCP, one JSON object per line, so that tools like IDEs
+//! can check a workspace or query it without starting a new Cargo each time.
+//! The `Config` of the daemon lives as long as it does, so the index is only
+//! updated once, and the `Workspace` is kept between requests until one of
+//! its manifests changes, or its `members` globs match other directories.
+//!
+//! Each connection is read by its own thread, which passes its requests to the
+//! main thread, so an idle client doesn't block the others. The main thread
+//! handles the requests one at a time, in the order they arrive. While a
+//! request is handled, the shell is replaced with one writing to a buffer,
+//! which is parsed into the result.
+//!
+//! Anyone who can connect to the daemon could run build scripts, so the first
+//! request of a connection must be `authenticate`, with a random token the
+//! daemon writes to a file only its user can read. The daemon also only
+//! listens on loopback addresses.
+
+use std::cell::RefCell;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::Duration;
+
+use filetime::FileTime;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::core::compiler::{BuildConfig, CompileMode, MessageFormat};
+use crate::core::{Shell, Workspace};
+use crate::ops::{self, CompileFilter, CompileOptions, OutputMetadataOptions, Packages};
+use crate::util::errors::CargoResultExt;
+use crate::util::{paths, CargoResult, Config};
+
+/// The error codes of JSON-RPC.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// The code of the errors of Cargo itself, like an invalid manifest.
+const CARGO_ERROR: i64 = -32000;
+/// The code of the error closing a connection which didn't authenticate.
+const UNAUTHENTICATED: i64 = -32001;
+
+/// How long the daemon waits for a client to read a response.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct DaemonOptions {
+    /// The address to listen on, like `127.0.0.1:0`. It must be a loopback
+    /// address.
+    pub listen: String,
+}
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ResponseError>,
+}
+
+#[derive(Serialize)]
+struct ResponseError {
+    code: i64,
+    message: String,
+}
+
+/// The parameters of the `authenticate` method.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AuthenticateParams {
+    token: String,
+}
+
+/// The parameters of the `check` and `unit-graph` methods.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct CompileParams {
+    packages: Vec<String>,
+    workspace: bool,
+    exclude: Vec<String>,
+    all_targets: bool,
+    features: Vec<String>,
+    all_features: bool,
+    no_default_features: bool,
+    target: Vec<String>,
+}
+
+/// The parameters of the `metadata` method.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct MetadataParams {
+    no_deps: bool,
+    features: Vec<String>,
+    all_features: bool,
+    no_default_features: bool,
+    filter_platform: Vec<String>,
+}
+
+/// The result of the `check` method.
+#[derive(Serialize)]
+struct CheckResult {
+    success: bool,
+    /// The JSON messages of `--message-format json`.
+    messages: Vec<Value>,
+    /// Everything else Cargo printed, like the error of a failed build.
+    output: String,
+}
+
+/// Why a request failed.
+enum RequestError {
+    /// The parameters don't match the method.
+    InvalidParams(String),
+    /// Cargo failed to handle the request.
+    Cargo(anyhow::Error),
+}
+
+impl From<anyhow::Error> for RequestError {
+    fn from(err: anyhow::Error) -> RequestError {
+        RequestError::Cargo(err)
+    }
+}
+
+/// A workspace kept between requests, with the modification times of its
+/// manifests and the expansion of its `members` when it was loaded.
+struct CachedWorkspace<'cfg> {
+    ws: Workspace<'cfg>,
+    manifests: Vec<(PathBuf, Option<FileTime>)>,
+    members: Vec<PathBuf>,
+}
+
+struct Daemon<'cfg> {
+    config: &'cfg Config,
+    manifest_path: PathBuf,
+    ws: Option<CachedWorkspace<'cfg>>,
+    shutdown: bool,
+}
+
+/// A request read by the thread of a connection, with the stream to write its
+/// response to.
+struct Incoming {
+    line: String,
+    writer: TcpStream,
+}
+
+/// A writer appending to a buffer shared with the daemon.
+struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Runs the daemon for the workspace of `manifest_path` until it receives a
+/// `shutdown` request.
+pub fn daemon(config: &Config, manifest_path: &Path, opts: &DaemonOptions) -> CargoResult<()> {
+    let listener = TcpListener::bind(&opts.listen)
+        .chain_err(|| format!("failed to listen on `{}`", opts.listen))?;
+    let addr = listener.local_addr()?;
+    // Even with the token, other hosts mustn't be able to connect, since
+    // nothing encrypts it.
+    if !addr.ip().is_loopback() {
+        anyhow::bail!(
+            "cannot listen on `{}`: `cargo daemon` only listens on loopback \
+             addresses, like `127.0.0.1`, since its connections aren't \
+             encrypted and can run build scripts",
+            opts.listen
+        );
+    }
+    let token = generate_token()?;
+    // The file is only readable by the user running the daemon, and removed
+    // when it stops.
+    let mut token_file = tempfile::Builder::new()
+        .prefix("cargo-daemon-token-")
+        .tempfile()
+        .chain_err(|| "failed to create the token file")?;
+    token_file.write_all(token.as_bytes())?;
+    token_file.flush()?;
+    // Clients started the daemon with port 0 read the port from here.
+    crate::drop_println!(
+        config,
+        "{}",
+        serde_json::json!({ "listening": addr, "token_file": token_file.path() })
+    );
+    config.shell().out().flush()?;
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || accept(listener, token, tx));
+
+    let mut daemon = Daemon {
+        config,
+        manifest_path: manifest_path.to_path_buf(),
+        ws: None,
+        shutdown: false,
+    };
+    for mut incoming in rx {
+        let response = daemon.handle(&incoming.line);
+        if let Err(e) = write_response(&mut incoming.writer, &response) {
+            // A client going away doesn't stop the daemon.
+            log::debug!("failed to send a response: {:?}", e);
+        }
+        if daemon.shutdown {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Accepts the connections, and reads each one on its own thread.
+fn accept(listener: TcpListener, token: String, tx: Sender<Incoming>) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                // Like a failed connection, this doesn't stop the daemon.
+                log::warn!("failed to accept a connection: {}", e);
+                continue;
+            }
+        };
+        let token = token.clone();
+        let tx = tx.clone();
+        thread::spawn(move || {
+            if let Err(e) = read_requests(stream, &token, &tx) {
+                log::debug!("connection failed: {:?}", e);
+            }
+        });
+    }
+}
+
+/// Passes the requests of a connection to the daemon until it is closed, once
+/// the client authenticated.
+fn read_requests(stream: TcpStream, token: &str, tx: &Sender<Incoming>) -> CargoResult<()> {
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+    let mut authenticated = false;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if !authenticated {
+            let response = authenticate(&line, token);
+            write_response(&mut writer, &response)?;
+            if response.error.is_some() {
+                break;
+            }
+            authenticated = true;
+            continue;
+        }
+        let incoming = Incoming {
+            line,
+            writer: writer.try_clone()?,
+        };
+        if tx.send(incoming).is_err() {
+            // The daemon stopped.
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Answers the first request of a connection, which must be `authenticate`
+/// with the token of the daemon.
+fn authenticate(line: &str, token: &str) -> Response {
+    let request = match parse_request(line) {
+        Ok(request) => request,
+        Err(response) => return response,
+    };
+    if request.method != "authenticate" {
+        return error_response(
+            request.id,
+            UNAUTHENTICATED,
+            "the first request must be `authenticate`".to_string(),
+        );
+    }
+    let params: AuthenticateParams = match serde_json::from_value(request.params) {
+        Ok(params) => params,
+        Err(e) => return error_response(request.id, INVALID_PARAMS, e.to_string()),
+    };
+    // Compares every byte, so the time taken doesn't tell how much matched.
+    let matches = params.token.len() == token.len()
+        && params
+            .token
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0;
+    if !matches {
+        return error_response(request.id, UNAUTHENTICATED, "invalid token".to_string());
+    }
+    Response {
+        jsonrpc: "2.0",
+        id: request.id,
+        result: Some(Value::Null),
+        error: None,
+    }
+}
+
+/// Returns a random token for the clients to authenticate with.
+fn generate_token() -> CargoResult<String> {
+    let mut bytes = [0; 32];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|e| anyhow::format_err!("failed to generate a token: {}", e))?;
+    Ok(hex::encode(bytes))
+}
+
+fn write_response(writer: &mut TcpStream, response: &Response) -> CargoResult<()> {
+    let mut encoded = serde_json::to_string(response)?;
+    encoded.push('\n');
+    writer.write_all(encoded.as_bytes())?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Parses a request, or returns the error response if it isn't valid.
+fn parse_request(line: &str) -> Result<Request, Response> {
+    let request: Request = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => {
+            let code = if serde_json::from_str::<Value>(line).is_ok() {
+                INVALID_REQUEST
+            } else {
+                PARSE_ERROR
+            };
+            return Err(error_response(Value::Null, code, e.to_string()));
+        }
+    };
+    if request.jsonrpc != "2.0" {
+        return Err(error_response(
+            request.id,
+            INVALID_REQUEST,
+            format!("unsupported JSON-RPC version `{}`", request.jsonrpc),
+        ));
+    }
+    Ok(request)
+}
+
+impl<'cfg> Daemon<'cfg> {
+    fn handle(&mut self, line: &str) -> Response {
+        let request = match parse_request(line) {
+            Ok(request) => request,
+            Err(response) => return response,
+        };
+        let result = match request.method.as_str() {
+            "check" => params(request.params).and_then(|p| self.check(p)),
+            "metadata" => params(request.params).and_then(|p| self.metadata(p)),
+            "unit-graph" => params(request.params).and_then(|p| self.unit_graph(p)),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            method => {
+                return error_response(
+                    request.id,
+                    METHOD_NOT_FOUND,
+                    format!("unknown method `{}`", method),
+                )
+            }
+        };
+        match result {
+            Ok(result) => Response {
+                jsonrpc: "2.0",
+                id: request.id,
+                result: Some(result),
+                error: None,
+            },
+            Err(RequestError::InvalidParams(msg)) => {
+                error_response(request.id, INVALID_PARAMS, msg)
+            }
+            Err(RequestError::Cargo(e)) => {
+                error_response(request.id, CARGO_ERROR, format!("{:#}", e))
+            }
+        }
+    }
+
+    /// Returns the workspace, loading it again if one of its manifests
+    /// changed since the last request, or a member was added or removed in
+    /// a directory matched by a `workspace.members` glob.
+    fn workspace(&mut self) -> CargoResult<&Workspace<'cfg>> {
+        let stale = match &mut self.ws {
+            Some(cached) => {
+                cached
+                    .manifests
+                    .iter()
+                    .any(|(path, mtime)| paths::mtime(path).ok() != *mtime)
+                    || member_dirs(&mut cached.ws)? != cached.members
+            }
+            None => true,
+        };
+        if stale {
+            let mut ws = Workspace::new(&self.manifest_path, self.config)?;
+            let members = member_dirs(&mut ws)?;
+            let mut manifests: Vec<PathBuf> = ws
+                .members()
+                .map(|pkg| pkg.manifest_path().to_path_buf())
+                .collect();
+            manifests.push(ws.root().join("Cargo.toml"));
+            manifests.push(self.manifest_path.clone());
+            let manifests = manifests
+                .into_iter()
+                .map(|path| {
+                    let mtime = paths::mtime(&path).ok();
+                    (path, mtime)
+                })
+                .collect();
+            self.ws = Some(CachedWorkspace {
+                ws,
+                manifests,
+                members,
+            });
+        }
+        Ok(&self.ws.as_ref().unwrap().ws)
+    }
+
+    /// Runs `f` with the output of Cargo captured, and returns its result
+    /// along with the output.
+    fn capture<T>(
+        &mut self,
+        f: impl FnOnce(&Workspace<'cfg>) -> CargoResult<T>,
+    ) -> (CargoResult<T>, String) {
+        let config = self.config;
+        let buf = Rc::new(RefCell::new(Vec::new()));
+        let mut shell = Shell::from_write(Box::new(Capture(Rc::clone(&buf))));
+        shell.set_verbosity(config.shell().verbosity());
+        let prev = std::mem::replace(&mut *config.shell(), shell);
+        let result = self.workspace().and_then(f);
+        *config.shell() = prev;
+        let output = String::from_utf8_lossy(&buf.borrow()).into_owned();
+        (result, output)
+    }
+
+    fn compile_options(
+        &self,
+        params: CompileParams,
+        mode: CompileMode,
+    ) -> CargoResult<CompileOptions> {
+        let mut build_config = BuildConfig::new(self.config, None, &params.target, mode)?;
+        build_config.message_format = MessageFormat::Json {
+            render_diagnostics: false,
+            short: false,
+            ansi: false,
+        };
+        let mut opts = CompileOptions::new(self.config, mode)?;
+        opts.build_config = build_config;
+        opts.features = params.features;
+        opts.all_features = params.all_features;
+        opts.no_default_features = params.no_default_features;
+        opts.spec = Packages::from_flags(params.workspace, false, params.exclude, params.packages)?;
+        if params.all_targets {
+            opts.filter = CompileFilter::new_all_targets();
+        }
+        Ok(opts)
+    }
+
+    fn check(&mut self, params: CompileParams) -> Result<Value, RequestError> {
+        let opts = self.compile_options(params, CompileMode::Check { test: false })?;
+        let (result, output) = self.capture(|ws| {
+            let result = ops::compile(ws, &opts).map(drop);
+            if let Err(e) = &result {
+                crate::display_error(e, &mut ws.config().shell());
+            }
+            result
+        });
+        let mut messages = Vec::new();
+        let mut other = String::new();
+        for line in output.lines() {
+            match serde_json::from_str::<Value>(line) {
+                Ok(message @ Value::Object(_)) => messages.push(message),
+                _ => {
+                    other.push_str(line);
+                    other.push('\n');
+                }
+            }
+        }
+        Ok(serde_json::to_value(CheckResult {
+            success: result.is_ok(),
+            messages,
+            output: other,
+        })
+        .map_err(anyhow::Error::from)?)
+    }
+
+    fn metadata(&mut self, params: MetadataParams) -> Result<Value, RequestError> {
+        let opts = OutputMetadataOptions {
+            features: params.features,
+            no_default_features: params.no_default_features,
+            all_features: params.all_features,
+            no_deps: params.no_deps,
+            version: 1,
+            filter_platforms: params.filter_platform,
+            checksums: false,
+        };
+        let (result, _output) = self.capture(|ws| ops::output_metadata(ws, &opts));
+        Ok(serde_json::to_value(result?).map_err(anyhow::Error::from)?)
+    }
+
+    fn unit_graph(&mut self, params: CompileParams) -> Result<Value, RequestError> {
+        let mut opts = self.compile_options(params, CompileMode::Build)?;
+        opts.build_config.unit_graph = true;
+        let (result, output) = self.capture(|ws| ops::compile(ws, &opts).map(drop));
+        result?;
+        let graph = output
+            .lines()
+            .find(|line| line.starts_with('{'))
+            .ok_or_else(|| anyhow::format_err!("no unit graph was printed"))?;
+        Ok(serde_json::from_str(graph).map_err(anyhow::Error::from)?)
+    }
+}
+
+/// Returns the directories the `workspace.members` of the root manifest of
+/// `ws` currently expand to.
+fn member_dirs(ws: &mut Workspace<'_>) -> CargoResult<Vec<PathBuf>> {
+    match ws.load_workspace_config()? {
+        Some(root_config) => root_config.expand_members(),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn params<T: Default + serde::de::DeserializeOwned>(params: Value) -> Result<T, RequestError> {
+    if params.is_null() {
+        return Ok(T::default());
+    }
+    serde_json::from_value(params).map_err(|e| RequestError::InvalidParams(e.to_string()))
+}
+
+fn error_response(id: Value, code: i64, message: String) -> Response {
+    Response {
+        jsonrpc: "2.0",
+        id,
+        result: None,
+        error: Some(ResponseError { code, message }),
+    }
+}
@@ -1,9 +1,10 @@
 use crate::core::compiler::RustcTargetData;
 use crate::core::resolver::{HasDevUnits, ResolveOpts};
-use crate::core::{Shell, Workspace};
+use crate::core::{Package, Shell, Workspace};
 use crate::ops;
 use crate::util::CargoResult;
 use std::collections::HashMap;
+use std::mem;
 use std::path::Path;
 use std::process::Command;
 
@@ -25,11 +26,12 @@ pub fn doc(ws: &Workspace<'_>, options: &DocOptions) -> CargoResult<()> {
         options.compile_opts.all_features,
         !options.compile_opts.no_default_features,
     );
-    let target_data = RustcTargetData::new(ws, &options.compile_opts.build_config.requested_kinds)?;
+    let target_kinds = options.compile_opts.build_config.all_kinds();
+    let target_data = RustcTargetData::new(ws, &target_kinds)?;
     let ws_resolve = ops::resolve_ws_with_opts(
         ws,
         &target_data,
-        &options.compile_opts.build_config.requested_kinds,
+        &target_kinds,
         &opts,
         &specs,
         HasDevUnits::No,
@@ -96,6 +98,66 @@ pub fn doc(ws: &Workspace<'_>, options: &DocOptions) -> CargoResult<()> {
     Ok(())
 }
 
+/// Documents the packages selected by `options` with each combination of
+/// features in the `features-matrix` of their `[doc]` table, to check that
+/// their docs build with all of them, for `cargo doc --features-matrix`.
+///
+/// The packages and features of `options` are restored afterwards, so that
+/// `doc` then builds the docs which are kept with the features given on the
+/// command line.
+pub fn doc_features_matrix(
+    ws: &Workspace<'_>,
+    options: &mut ops::CompileOptions,
+) -> CargoResult<()> {
+    let specs = options.spec.to_package_id_specs(ws)?;
+    let matrices: Vec<(&Package, &Vec<Vec<String>>)> = ws
+        .members()
+        .filter(|pkg| specs.iter().any(|spec| spec.matches(pkg.package_id())))
+        .filter_map(|pkg| {
+            let matrix = pkg.manifest().doc()?.features_matrix.as_ref()?;
+            Some((pkg, matrix))
+        })
+        .collect();
+    if matrices.is_empty() {
+        ws.config()
+            .shell()
+            .warn("none of the selected packages has a `features-matrix` in its `[doc]` table")?;
+        return Ok(());
+    }
+
+    let spec = mem::replace(&mut options.spec, ops::Packages::Default);
+    let features = mem::take(&mut options.features);
+    let all_features = options.all_features;
+    let no_default_features = options.no_default_features;
+    let mut result = Ok(());
+    'matrix: for (pkg, matrix) in matrices {
+        for combination in matrix {
+            let features = combination.join(", ");
+            ws.config()
+                .shell()
+                .status("Features", format!("{} [{}]", pkg.name(), features))?;
+            options.spec =
+                ops::Packages::Packages(vec![format!("{}:{}", pkg.name(), pkg.version())]);
+            options.features = combination.clone();
+            options.all_features = false;
+            options.no_default_features = true;
+            if let Err(e) = ops::compile(ws, options) {
+                result = Err(e.context(format!(
+                    "failed to document `{}` with the features [{}]",
+                    pkg.name(),
+                    features
+                )));
+                break 'matrix;
+            }
+        }
+    }
+    options.spec = spec;
+    options.features = features;
+    options.all_features = all_features;
+    options.no_default_features = no_default_features;
+    result
+}
+
 fn open_docs(path: &Path, shell: &mut Shell) -> CargoResult<()> {
     match std::env::var_os("BROWSER") {
         Some(browser) => {
@@ -0,0 +1,297 @@
+//! Implementation of `cargo expand`.
+//!
+//! The selected target is checked like with `cargo check`, except that rustc
+//! is asked to print its code once the macros are expanded, with
+//! `-Z unpretty=expanded`, instead of emitting metadata. The dependencies are
+//! checked as usual, so rustc gets the same features, cfgs and edition as in
+//! a normal build. Since `-Z` flags are unstable, this requires a nightly
+//! rustc, unless `RUSTC_BOOTSTRAP` is set in the environment.
+//!
+//! The expanded code is printed by rustc's pretty printer, which indents the
+//! items of a module by four spaces, and closes them with a `}` at the
+//! indentation of the item. This is what is used to find the item to print,
+//! if one was given, before the code is formatted with rustfmt.
+
+use std::env;
+use std::ffi::OsString;
+use std::io::Write;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+
+use crate::core::compiler::{CompileMode, DefaultExecutor, Executor, Unit};
+use crate::core::{Edition, PackageId, Target, Workspace};
+use crate::ops::{self, CompileFilter, CompileOptions, FilterRule, LibRule};
+use crate::util::errors::{CargoResult, CargoResultExt};
+use crate::util::{process, ProcessBuilder};
+
+/// The rustc flag printing the expanded code.
+const UNPRETTY_EXPANDED: &str = "-Zunpretty=expanded";
+
+pub struct ExpandOptions {
+    pub compile_opts: CompileOptions,
+    /// The path of the item to print, like `foo::Bar`, relative to the root
+    /// of the crate, or `None` to print the whole crate.
+    pub item: Option<String>,
+    /// Don't format the expanded code with rustfmt.
+    pub ugly: bool,
+}
+
+/// Captures the output of the rustc process printing the expanded code.
+struct ExpandExecutor {
+    /// The package and the mode of the expanded target, and the filter
+    /// selecting it.
+    package: PackageId,
+    mode: CompileMode,
+    filter: CompileFilter,
+    expanded: Mutex<Option<(String, Edition)>>,
+}
+
+impl Executor for ExpandExecutor {
+    fn exec(
+        &self,
+        cmd: &ProcessBuilder,
+        id: PackageId,
+        target: &Target,
+        mode: CompileMode,
+        on_stdout_line: &mut dyn FnMut(&str) -> CargoResult<()>,
+        on_stderr_line: &mut dyn FnMut(&str) -> CargoResult<()>,
+    ) -> CargoResult<()> {
+        if !cmd.get_args().iter().any(|arg| arg == UNPRETTY_EXPANDED) {
+            return DefaultExecutor.exec(cmd, id, target, mode, on_stdout_line, on_stderr_line);
+        }
+        let mut expanded = String::new();
+        cmd.exec_with_streaming(
+            &mut |line| {
+                expanded.push_str(line);
+                expanded.push('\n');
+                Ok(())
+            },
+            on_stderr_line,
+            false,
+        )?;
+        *self.expanded.lock().unwrap() = Some((expanded, target.edition()));
+        Ok(())
+    }
+
+    /// The code is only printed when the expanded target is compiled, so it's
+    /// always rebuilt, unlike its dependencies.
+    fn force_rebuild(&self, unit: &Unit) -> bool {
+        unit.pkg.package_id() == self.package
+            && unit.mode == self.mode
+            && self.filter.target_run(&unit.target)
+    }
+}
+
+pub fn expand(ws: &Workspace<'_>, opts: &mut ExpandOptions) -> CargoResult<()> {
+    let config = ws.config();
+    let rustc = config.load_global_rustc(Some(ws))?;
+    if !rustc.verbose_version.contains("-nightly")
+        && !rustc.verbose_version.contains("-dev")
+        && env::var_os("RUSTC_BOOTSTRAP").is_none()
+    {
+        anyhow::bail!(
+            "`cargo expand` requires a nightly rustc, since it uses the unstable \
+             `-Z unpretty=expanded` flag, but `{}` is rustc {}",
+            rustc.path.display(),
+            rustc.version
+        );
+    }
+
+    let compile_opts = &mut opts.compile_opts;
+    let mut packages = compile_opts.spec.get_packages(ws)?;
+    if packages.is_empty() {
+        packages.push(ws.current()?);
+    }
+    // Expand the library of the package by default, if it has one.
+    if let CompileFilter::Default { .. } = compile_opts.filter {
+        if packages
+            .iter()
+            .all(|pkg| pkg.targets().iter().any(|t| t.is_lib()))
+        {
+            compile_opts.filter = CompileFilter::new(
+                LibRule::True,
+                FilterRule::none(),
+                FilterRule::none(),
+                FilterRule::none(),
+                FilterRule::none(),
+            );
+        }
+    }
+    compile_opts.target_rustc_args = Some(vec![UNPRETTY_EXPANDED.to_string()]);
+
+    let executor = Arc::new(ExpandExecutor {
+        package: packages[0].package_id(),
+        mode: compile_opts.build_config.mode,
+        filter: compile_opts.filter.clone(),
+        expanded: Mutex::new(None),
+    });
+    let exec: Arc<dyn Executor> = executor.clone();
+    ops::compile_with_exec(ws, compile_opts, &exec)?;
+
+    let (expanded, edition) = match executor.expanded.lock().unwrap().take() {
+        Some(expanded) => expanded,
+        None => anyhow::bail!("rustc didn't print the expanded code"),
+    };
+    let expanded = match &opts.item {
+        Some(item) => match select_item(&expanded, item) {
+            Some(code) => code,
+            None => anyhow::bail!("no item `{}` in the expanded code", item),
+        },
+        None => expanded,
+    };
+    let expanded = if opts.ugly {
+        expanded
+    } else {
+        match rustfmt(&expanded, edition) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                config.shell().warn(format!(
+                    "failed to format the expanded code, printing it as is: {:#}",
+                    e
+                ))?;
+                expanded
+            }
+        }
+    };
+    config.shell().out().write_all(expanded.as_bytes())?;
+    Ok(())
+}
+
+/// Formats `code` with rustfmt, or the `RUSTFMT` environment variable.
+fn rustfmt(code: &str, edition: Edition) -> CargoResult<String> {
+    let rustfmt = env::var_os("RUSTFMT").unwrap_or_else(|| OsString::from("rustfmt"));
+    let mut cmd = process(&rustfmt);
+    cmd.arg("--edition").arg(edition.to_string());
+    let mut child = cmd
+        .build_command()
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .chain_err(|| format!("failed to run {}", cmd))?;
+    child.stdin.take().unwrap().write_all(code.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} failed ({}): {}",
+            cmd,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    String::from_utf8(output.stdout).chain_err(|| format!("{} printed invalid UTF-8", cmd))
+}
+
+/// Returns the code of the item at `path` in the pretty-printed `code`, with
+/// its attributes.
+fn select_item(code: &str, path: &str) -> Option<String> {
+    let mut lines: Vec<&str> = code.lines().collect();
+    let mut indent = 0;
+    let segments: Vec<&str> = path.split("::").collect();
+    for (i, name) in segments.iter().enumerate() {
+        let last = i + 1 == segments.len();
+        let (start, decl, end) = find_item(&lines, indent, name, !last)?;
+        if last {
+            let mut item = String::new();
+            for line in &lines[start..=end] {
+                item.push_str(line.get(indent..).unwrap_or_else(|| line.trim_start()));
+                item.push('\n');
+            }
+            return Some(item);
+        }
+        // The body of the module, without its braces.
+        lines = lines[decl + 1..end].to_vec();
+        indent += 4;
+    }
+    None
+}
+
+/// Finds the item `name` declared at `indent` in `lines`, and returns the
+/// indices of its first attribute, of its declaration, and of its last line.
+/// If `module` is true, only modules are looked for.
+fn find_item(
+    lines: &[&str],
+    indent: usize,
+    name: &str,
+    module: bool,
+) -> Option<(usize, usize, usize)> {
+    let at_indent = |line: &str| {
+        line.len() > indent
+            && line.as_bytes()[..indent].iter().all(|b| *b == b' ')
+            && !line[indent..].starts_with(' ')
+    };
+    let mut attrs_start = None;
+    for (i, line) in lines.iter().enumerate() {
+        if !at_indent(line) {
+            continue;
+        }
+        let decl = &line[indent..];
+        if decl.starts_with("#[") {
+            attrs_start = attrs_start.or(Some(i));
+            continue;
+        }
+        let start = attrs_start.take().unwrap_or(i);
+        match declared_item(decl) {
+            Some((kind, item)) if item == name && (!module || kind == "mod") => {}
+            _ => continue,
+        }
+        let decl_end = decl.trim_end();
+        if decl_end.ends_with(';') || decl_end.ends_with('}') {
+            return Some((start, i, i));
+        }
+        // The item ends with a `}` at its indentation, or before the next
+        // item if its declaration spans several lines and ends with a `;`.
+        for (j, line) in lines.iter().enumerate().skip(i + 1) {
+            if at_indent(line) {
+                let end = if line[indent..].starts_with('}') {
+                    j
+                } else {
+                    j - 1
+                };
+                return Some((start, i, end));
+            }
+        }
+        return Some((start, i, lines.len() - 1));
+    }
+    None
+}
+
+/// Returns the kind and the name of the item declared by `decl`, like
+/// `("fn", "main")` for `pub(crate) async fn main() {`.
+fn declared_item(decl: &str) -> Option<(&str, &str)> {
+    let mut words = decl.split_whitespace().peekable();
+    while let Some(word) = words.next() {
+        match word {
+            "pub" | "unsafe" | "async" | "default" | "extern" => {}
+            // `const fn`, but not `const NAME`.
+            "const" if is_fn_qualifier(words.peek()) => {}
+            _ if word.starts_with("pub(") || word.starts_with('"') => {
+                // Skip the rest of `pub(in path)`.
+                if word.starts_with("pub(in") && !word.ends_with(')') {
+                    words.next();
+                }
+            }
+            "mod" | "fn" | "struct" | "enum" | "union" | "trait" | "type" | "const" | "static"
+            | "macro_rules!" => {
+                let mut name = words.next()?;
+                if word == "static" && name == "mut" {
+                    name = words.next()?;
+                }
+                let len = name
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or(name.len());
+                return Some((word, &name[..len]));
+            }
+            _ => return None,
+        }
+    }
+    None
+}
+
+/// Whether `word` can follow `const` in the declaration of a function.
+fn is_fn_qualifier(word: Option<&&str>) -> bool {
+    matches!(
+        word,
+        Some(&"fn") | Some(&"unsafe") | Some(&"async") | Some(&"extern")
+    )
+}
@@ -1,9 +1,13 @@
 use crate::core::compiler::{BuildConfig, CompileMode, RustcTargetData};
-use crate::core::{PackageSet, Resolve, Workspace};
+use crate::core::resolver::features::{ForceAllTargets, HasDevUnits};
+use crate::core::source::MaybePackage;
+use crate::core::{PackageId, PackageSet, Resolve, SourceId, Workspace};
 use crate::ops;
+use crate::sources::SourceConfigMap;
 use crate::util::CargoResult;
 use crate::util::Config;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
 
 pub struct FetchOptions<'a> {
     pub config: &'a Config,
@@ -17,47 +21,153 @@ pub fn fetch<'a>(
     options: &FetchOptions<'a>,
 ) -> CargoResult<(Resolve, PackageSet<'a>)> {
     ws.emit_warnings()?;
+    fetch_inner(ws, options).map_err(|e| explain_offline_error(ws, e))
+}
+
+fn fetch_inner<'a>(
+    ws: &Workspace<'a>,
+    options: &FetchOptions<'a>,
+) -> CargoResult<(Resolve, PackageSet<'a>)> {
     let (packages, resolve) = ops::resolve_ws(ws)?;
 
     let jobs = Some(1);
     let config = ws.config();
     let build_config = BuildConfig::new(config, jobs, &options.targets, CompileMode::Build)?;
-    let data = RustcTargetData::new(ws, &build_config.requested_kinds)?;
-    let mut fetched_packages = HashSet::new();
-    let mut deps_to_fetch = ws.members().map(|p| p.package_id()).collect::<Vec<_>>();
-    let mut to_download = Vec::new();
+    let target_kinds = build_config.all_kinds();
+    let data = RustcTargetData::new(ws, &target_kinds)?;
+    // Download everything a build for the requested targets would, so that
+    // an `--offline` cross build doesn't discover anything missing on the
+    // host side, such as the dependencies of build scripts and proc-macros.
+    // Development dependencies are fetched too, since the build may include
+    // tests and examples.
+    let member_ids = ws.members().map(|p| p.package_id()).collect::<Vec<_>>();
+    let force_all_targets = if options.targets.is_empty() {
+        ForceAllTargets::Yes
+    } else {
+        ForceAllTargets::No
+    };
+    packages.download_accessible(
+        &resolve,
+        &member_ids,
+        HasDevUnits::Yes,
+        &target_kinds,
+        &data,
+        force_all_targets,
+    )?;
 
-    while let Some(id) = deps_to_fetch.pop() {
-        if !fetched_packages.insert(id) {
-            continue;
+    Ok((resolve, packages))
+}
+
+/// Everything in `Cargo.lock` which isn't available locally, and would have
+/// to be fetched before building offline.
+pub struct OfflineReport {
+    resolve: Resolve,
+    missing: Vec<(PackageId, Missing)>,
+}
+
+/// Why a package isn't available offline.
+enum Missing {
+    /// The `.crate` file of a registry package isn't downloaded.
+    Crate,
+    /// The package isn't listed in the local copy of the registry index.
+    IndexEntry,
+    /// The locked revision of the git repository isn't fetched.
+    GitRevision,
+}
+
+impl OfflineReport {
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+impl fmt::Display for OfflineReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.missing.is_empty() {
+            return writeln!(f, "all packages in Cargo.lock are available offline");
         }
+        writeln!(
+            f,
+            "the following packages in Cargo.lock are not available offline:"
+        )?;
+        for (id, missing) in &self.missing {
+            let reason = match missing {
+                Missing::Crate => "the `.crate` file is not downloaded".to_string(),
+                Missing::IndexEntry => "it is not listed in the local registry index".to_string(),
+                Missing::GitRevision => format!(
+                    "git revision `{}` is not fetched",
+                    id.source_id().precise().unwrap_or("")
+                ),
+            };
+            writeln!(f, "  {}: {}", id, reason)?;
+            let path = self
+                .resolve
+                .path_to_top(id)
+                .into_iter()
+                .rev()
+                .map(|p| format!("{} v{}", p.name(), p.version()))
+                .collect::<Vec<_>>();
+            writeln!(f, "    needed by: {}", path.join(" -> "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks which packages of `Cargo.lock` would have to be fetched in order
+/// to build offline, without accessing the network.
+pub fn offline_report(ws: &Workspace<'_>) -> CargoResult<OfflineReport> {
+    let config = ws.config();
+    let resolve = match ops::load_pkg_lockfile(ws)? {
+        Some(resolve) => resolve,
+        None => anyhow::bail!(
+            "a Cargo.lock file is required to check which packages are available offline, \
+             run `cargo generate-lockfile` to create one"
+        ),
+    };
+    assert!(config.offline(), "must not fetch anything while checking");
+    let _lock = config.acquire_package_cache_lock()?;
+    let source_config = SourceConfigMap::new(config)?;
 
-        to_download.push(id);
-        let deps = resolve
-            .deps(id)
-            .filter(|&(_id, deps)| {
-                deps.iter().any(|d| {
-                    // If no target was specified then all dependencies are
-                    // fetched.
-                    if options.targets.is_empty() {
-                        return true;
-                    }
-
-                    // Otherwise we only download this dependency if any of the
-                    // requested platforms would match this dependency. Note
-                    // that this is a bit lossy because not all dependencies are
-                    // always compiled for all platforms, but it should be
-                    // "close enough" for now.
-                    build_config
-                        .requested_kinds
-                        .iter()
-                        .any(|kind| data.dep_platform_activated(d, *kind))
-                })
-            })
-            .map(|(id, _deps)| id);
-        deps_to_fetch.extend(deps);
+    let mut by_source = BTreeMap::<SourceId, Vec<PackageId>>::new();
+    for id in resolve.iter() {
+        if !id.source_id().is_path() {
+            by_source.entry(id.source_id()).or_default().push(id);
+        }
     }
-    packages.get_many(to_download)?;
 
-    Ok((resolve, packages))
+    let mut missing = Vec::new();
+    for (source_id, mut ids) in by_source {
+        ids.sort();
+        let mut source = source_config.load(source_id, &HashSet::new())?;
+        // Offline, updating only fails if the locked revision of a git
+        // repository isn't in the local database.
+        if let Err(e) = source.update() {
+            if !source_id.is_git() {
+                return Err(e);
+            }
+            missing.extend(ids.into_iter().map(|id| (id, Missing::GitRevision)));
+            continue;
+        }
+        for id in ids {
+            match source.download(id) {
+                Ok(MaybePackage::Ready(_)) => {}
+                Ok(MaybePackage::Download { .. }) => missing.push((id, Missing::Crate)),
+                Err(_) => missing.push((id, Missing::IndexEntry)),
+            }
+        }
+    }
+    missing.sort_by_key(|(id, _)| *id);
+    Ok(OfflineReport { resolve, missing })
+}
+
+/// Adds the report of what isn't available offline to an error of a command
+/// run with `--offline`, as the error was most likely caused by it.
+pub fn explain_offline_error(ws: &Workspace<'_>, err: anyhow::Error) -> anyhow::Error {
+    if !ws.config().offline() {
+        return err;
+    }
+    match offline_report(ws) {
+        Ok(report) if !report.is_empty() => err.context(report.to_string().trim_end().to_string()),
+        _ => err,
+    }
 }
@@ -14,7 +14,13 @@ use crate::util::CargoResult;
 pub struct UpdateOptions<'a> {
     pub config: &'a Config,
     pub to_update: Vec<String>,
-    pub precise: Option<&'a str>,
+    /// Versions (or git revisions) to update to. If a single value is given,
+    /// it applies to every package in `to_update`, otherwise there must be
+    /// one value per package, in the same order.
+    pub precise: Vec<String>,
+    /// Allows the versions given with `precise` to be yanked. Such packages
+    /// are annotated in `Cargo.lock` so that they keep being used.
+    pub allow_yanked: bool,
     pub aggressive: bool,
     pub dry_run: bool,
     pub workspace: bool,
@@ -31,15 +37,30 @@ pub fn generate_lockfile(ws: &Workspace<'_>) -> CargoResult<()> {
         &[],
         true,
     )?;
+    registry.save_caches()?;
     ops::write_pkg_lockfile(ws, &mut resolve)?;
+    ops::check_advisories(ws, &resolve, true)?;
     Ok(())
 }
 
 pub fn update_lockfile(ws: &Workspace<'_>, opts: &UpdateOptions<'_>) -> CargoResult<()> {
-    if opts.aggressive && opts.precise.is_some() {
+    if opts.aggressive && !opts.precise.is_empty() {
         anyhow::bail!("cannot specify both aggressive and precise simultaneously")
     }
 
+    if opts.allow_yanked && opts.precise.is_empty() {
+        anyhow::bail!("`--allow-yanked` can only be used together with `--precise`")
+    }
+
+    if opts.precise.len() > 1 && opts.precise.len() != opts.to_update.len() {
+        anyhow::bail!(
+            "`--precise` was specified {} times, but {} packages were specified with `-p`\n\
+             When `--precise` is given more than once, it must be given once for each `-p`.",
+            opts.precise.len(),
+            opts.to_update.len()
+        )
+    }
+
     if ws.members().count() == 0 {
         anyhow::bail!("you can't generate a lockfile for an empty workspace.")
     }
@@ -55,28 +76,27 @@ pub fn update_lockfile(ws: &Workspace<'_>, opts: &UpdateOptions<'_>) -> CargoRes
     let previous_resolve = match ops::load_pkg_lockfile(ws)? {
         Some(resolve) => resolve,
         None => {
-            match opts.precise {
-                None => return generate_lockfile(ws),
-
-                // Precise option specified, so calculate a previous_resolve required
-                // by precise package update later.
-                Some(_) => {
-                    let mut registry = PackageRegistry::new(opts.config)?;
-                    ops::resolve_with_previous(
-                        &mut registry,
-                        ws,
-                        &ResolveOpts::everything(),
-                        None,
-                        None,
-                        &[],
-                        true,
-                    )?
-                }
+            if opts.precise.is_empty() {
+                return generate_lockfile(ws);
             }
+
+            // Precise option specified, so calculate a previous_resolve required
+            // by precise package update later.
+            let mut registry = PackageRegistry::new(opts.config)?;
+            ops::resolve_with_previous(
+                &mut registry,
+                ws,
+                &ResolveOpts::everything(),
+                None,
+                None,
+                &[],
+                true,
+            )?
         }
     };
     let mut registry = PackageRegistry::new(opts.config)?;
     let mut to_avoid = HashSet::new();
+    let mut allowed_yanked = Vec::new();
 
     if opts.to_update.is_empty() {
         if !opts.workspace {
@@ -85,26 +105,44 @@ pub fn update_lockfile(ws: &Workspace<'_>, opts: &UpdateOptions<'_>) -> CargoRes
         }
     } else {
         let mut sources = Vec::new();
-        for name in opts.to_update.iter() {
+        // Precise requests grouped by source, as a single source may be asked
+        // to pin several of its packages at once.
+        let mut precise_sources: BTreeMap<SourceId, Vec<String>> = BTreeMap::new();
+        for (i, name) in opts.to_update.iter().enumerate() {
             let dep = previous_resolve.query(name)?;
             if opts.aggressive {
                 fill_with_deps(&previous_resolve, dep, &mut to_avoid, &mut HashSet::new());
             } else {
                 to_avoid.insert(dep);
-                sources.push(match opts.precise {
+                let precise = match opts.precise.len() {
+                    0 => None,
+                    1 => Some(&opts.precise[0]),
+                    _ => Some(&opts.precise[i]),
+                };
+                match precise {
                     Some(precise) => {
                         // TODO: see comment in `resolve.rs` as well, but this
                         //       seems like a pretty hokey reason to single out
                         //       the registry as well.
                         let precise = if dep.source_id().is_registry() {
+                            if opts.allow_yanked {
+                                allowed_yanked.push(PackageId::new(
+                                    dep.name(),
+                                    precise.as_str(),
+                                    dep.source_id(),
+                                )?);
+                            }
                             format!("{}={}->{}", dep.name(), dep.version(), precise)
                         } else {
                             precise.to_string()
                         };
-                        dep.source_id().with_precise(Some(precise))
+                        let precise_list = precise_sources.entry(dep.source_id()).or_default();
+                        if !precise_list.contains(&precise) {
+                            precise_list.push(precise);
+                        }
                     }
-                    None => dep.source_id().with_precise(None),
-                });
+                    None => sources.push(dep.source_id().with_precise(None)),
+                }
             }
             if let Ok(unused_id) =
                 PackageIdSpec::query_str(name, previous_resolve.unused_patches().iter().cloned())
@@ -113,7 +151,21 @@ pub fn update_lockfile(ws: &Workspace<'_>, opts: &UpdateOptions<'_>) -> CargoRes
             }
         }
 
+        for (source_id, precise) in precise_sources {
+            if !source_id.is_registry() && precise.len() > 1 {
+                anyhow::bail!(
+                    "cannot update packages from `{}` to different revisions: {}",
+                    source_id,
+                    precise.join(", ")
+                )
+            }
+            // Registries list each requested package separated by a comma,
+            // see `RegistryIndex::query_inner`.
+            sources.push(source_id.with_precise(Some(precise.join(","))));
+        }
+
         registry.add_sources(sources)?;
+        registry.add_to_yanked_whitelist(allowed_yanked.iter().copied());
     }
 
     let mut resolve = ops::resolve_with_previous(
@@ -125,12 +177,66 @@ pub fn update_lockfile(ws: &Workspace<'_>, opts: &UpdateOptions<'_>) -> CargoRes
         &[],
         true,
     )?;
+    registry.save_caches()?;
+
+    // Record which of the explicitly allowed packages are actually yanked, so
+    // the lock file keeps them from being dropped by later resolutions.
+    if !allowed_yanked.is_empty() {
+        let pkg_set = registry.get(&allowed_yanked)?;
+        let mut sources = pkg_set.sources_mut();
+        for pkg_id in allowed_yanked {
+            if !resolve.contains(&pkg_id) {
+                continue;
+            }
+            if let Some(source) = sources.get_mut(pkg_id.source_id()) {
+                if source.is_yanked(pkg_id)? {
+                    resolve.allow_yanked(pkg_id);
+                }
+            }
+        }
+    }
+
+    let changes = compare_dependency_graphs(&previous_resolve, &resolve);
+
+    // Only the workspace members are meant to be updated with `--workspace`,
+    // so don't let the versions other packages are locked to drift.
+    if opts.workspace && opts.to_update.is_empty() {
+        let members: HashSet<_> = ws
+            .members()
+            .map(|member| (member.name(), member.package_id().source_id()))
+            .collect();
+        let drifted: Vec<_> = changes
+            .iter()
+            .filter(|(removed, added)| !removed.is_empty() && !added.is_empty())
+            .filter(|(removed, _)| !members.contains(&(removed[0].name(), removed[0].source_id())))
+            .map(|(removed, added)| {
+                let removed: Vec<_> = removed.iter().map(|id| id.to_string()).collect();
+                let added: Vec<_> = added
+                    .iter()
+                    .map(|id| match id.source_id().precise() {
+                        Some(rev) if id.source_id().is_git() => format!("#{}", &rev[..8]),
+                        _ => format!("v{}", id.version()),
+                    })
+                    .collect();
+                format!("  {} -> {}", removed.join(", "), added.join(", "))
+            })
+            .collect();
+        if !drifted.is_empty() {
+            anyhow::bail!(
+                "updating the workspace members would change the locked versions \
+                 of other packages:\n{}\n\
+                 The dependency requirements of the workspace no longer match the \
+                 locked versions, update them with `cargo update -p <SPEC>`.",
+                drifted.join("\n")
+            );
+        }
+    }
 
     // Summarize what is changing for the user.
     let print_change = |status: &str, msg: String, color: Color| {
         opts.config.shell().status_with_color(status, msg, color)
     };
-    for (removed, added) in compare_dependency_graphs(&previous_resolve, &resolve) {
+    for (removed, added) in changes {
         if removed.len() == 1 && added.len() == 1 {
             let msg = if removed[0].source_id().is_git() {
                 format!(
@@ -158,6 +264,7 @@ pub fn update_lockfile(ws: &Workspace<'_>, opts: &UpdateOptions<'_>) -> CargoRes
     } else {
         ops::write_pkg_lockfile(ws, &mut resolve)?;
     }
+    ops::check_advisories(ws, &resolve, true)?;
     return Ok(());
 
     fn fill_with_deps<'a>(
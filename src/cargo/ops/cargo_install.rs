@@ -1,20 +1,33 @@
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
-use std::path::{Path, PathBuf};
+use std::ffi::OsString;
+use std::path::{Component, Path, PathBuf};
+use std::process::Stdio;
+use std::sync::mpsc::{self, RecvTimeoutError};
 use std::sync::Arc;
-use std::{env, fs};
+use std::time::Duration;
+use std::{env, fs, thread};
 
 use anyhow::{bail, format_err};
+use flate2::read::GzDecoder;
 use semver::VersionReq;
-use tempfile::Builder as TempFileBuilder;
+use tar::Archive;
+use tempfile::{Builder as TempFileBuilder, TempDir};
 
 use crate::core::compiler::Freshness;
-use crate::core::compiler::{CompileKind, DefaultExecutor, Executor};
+use crate::core::compiler::{CompileKind, DefaultExecutor, Executor, UnitInterner};
 use crate::core::{Dependency, Edition, Package, PackageId, Source, SourceId, Workspace};
 use crate::ops::common_for_install_and_uninstall::*;
 use crate::sources::{GitSource, PathSource, SourceConfigMap};
+use crate::util::crate_signature;
 use crate::util::errors::{CargoResult, CargoResultExt};
-use crate::util::{paths, Config, Filesystem, Rustc, ToSemver};
-use crate::{drop_println, ops};
+use crate::util::{
+    paths, process, Config, Filesystem, Progress, ProgressStyle, Rustc, Sha256, ToSemver,
+};
+use crate::{drop_eprint, drop_print, drop_println, ops};
+
+/// The index of the crate to install, in the processes which install a single
+/// crate for `-Z parallel-install`.
+const INSTALL_ONLY_ENV: &str = "__CARGO_INSTALL_ONLY";
 
 struct Transaction {
     bins: Vec<PathBuf>,
@@ -36,18 +49,30 @@ impl Drop for Transaction {
 
 pub fn install(
     config: &Config,
-    root: Option<&str>,
+    root: Option<&Path>,
     krates: Vec<&str>,
     source_id: SourceId,
+    path_in_repo: Option<&Path>,
     from_cwd: bool,
     vers: Option<&str>,
     opts: &ops::CompileOptions,
     force: bool,
     no_track: bool,
+    dry_run: bool,
 ) -> CargoResult<()> {
     let root = resolve_root(root, config)?;
     let map = SourceConfigMap::new(config)?;
 
+    // Set in the processes which install a single crate for
+    // `-Z parallel-install`, see `install_parallel`.
+    let only = env::var(INSTALL_ONLY_ENV)
+        .ok()
+        .and_then(|i| i.parse::<usize>().ok());
+    let krates = match only {
+        Some(i) => krates.into_iter().skip(i).take(1).collect(),
+        None => krates,
+    };
+
     let (installed_anything, scheduled_error) = if krates.len() <= 1 {
         install_one(
             config,
@@ -55,56 +80,67 @@ pub fn install(
             &map,
             krates.into_iter().next(),
             source_id,
+            path_in_repo,
             from_cwd,
             vers,
             opts,
             force,
             no_track,
+            dry_run,
             true,
         )?;
         (true, false)
     } else {
-        let mut succeeded = vec![];
-        let mut failed = vec![];
-        // "Tracks whether or not the source (such as a registry or git repo) has been updated.
-        // This is used to avoid updating it multiple times when installing multiple crates.
-        let mut did_update = false;
-        for krate in krates {
-            let root = root.clone();
-            let map = map.clone();
-            match install_one(
-                config,
-                &root,
-                &map,
-                Some(krate),
-                source_id,
-                from_cwd,
-                vers,
-                opts,
-                force,
-                no_track,
-                !did_update,
-            ) {
-                Ok(still_needs_update) => {
-                    succeeded.push(krate);
-                    did_update |= !still_needs_update;
-                }
-                Err(e) => {
-                    crate::display_error(&e, &mut config.shell());
-                    failed.push(krate);
-                    // We assume an update was performed if we got an error.
-                    did_update = true;
+        let (succeeded, failed) = if config.cli_unstable().parallel_install {
+            install_parallel(config, &krates, opts.build_config.jobs)?
+        } else {
+            let mut succeeded = vec![];
+            let mut failed = vec![];
+            // "Tracks whether or not the source (such as a registry or git repo) has been updated.
+            // This is used to avoid updating it multiple times when installing multiple crates.
+            let mut did_update = false;
+            for krate in krates {
+                let root = root.clone();
+                let map = map.clone();
+                match install_one(
+                    config,
+                    &root,
+                    &map,
+                    Some(krate),
+                    source_id,
+                    path_in_repo,
+                    from_cwd,
+                    vers,
+                    opts,
+                    force,
+                    no_track,
+                    dry_run,
+                    !did_update,
+                ) {
+                    Ok(still_needs_update) => {
+                        succeeded.push(krate);
+                        did_update |= !still_needs_update;
+                    }
+                    Err(e) => {
+                        crate::display_error(&e, &mut config.shell());
+                        failed.push(krate);
+                        // We assume an update was performed if we got an error.
+                        did_update = true;
+                    }
                 }
             }
-        }
+            (succeeded, failed)
+        };
 
+        let verb = if dry_run { "check" } else { "install" };
         let mut summary = vec![];
         if !succeeded.is_empty() {
-            summary.push(format!("Successfully installed {}!", succeeded.join(", ")));
+            summary.push(format!("Successfully {}ed {}!", verb, succeeded.join(", ")));
         }
         if !failed.is_empty() {
             summary.push(format!(
-                "Failed to install {} (see error(s) above).",
+                "Failed to {} {} (see error(s) above).",
+                verb,
                 failed.join(", ")
             ));
         }
@@ -115,22 +151,20 @@ pub fn install(
         (!succeeded.is_empty(), !failed.is_empty())
     };
 
-    if installed_anything {
+    // The process which started the one installing a single crate warns
+    // about the PATH itself.
+    if installed_anything && only.is_none() && !dry_run {
         // Print a warning that if this directory isn't in PATH that they won't be
         // able to run these commands.
         let dst = root.join("bin").into_path_unlocked();
         let path = env::var_os("PATH").unwrap_or_default();
-        for path in env::split_paths(&path) {
-            if path == dst {
-                return Ok(());
-            }
+        if !env::split_paths(&path).any(|path| same_dir(&path, &dst)) {
+            config.shell().warn(format!(
+                "be sure to add `{}` to your PATH to be \
+                 able to run the installed binaries",
+                dst.display()
+            ))?;
         }
-
-        config.shell().warn(&format!(
-            "be sure to add `{}` to your PATH to be \
-             able to run the installed binaries",
-            dst.display()
-        ))?;
     }
 
     if scheduled_error {
@@ -140,6 +174,88 @@ pub fn install(
     Ok(())
 }
 
+/// Installs each of `krates` with its own `cargo install` process for
+/// `-Z parallel-install`, so that they are built at the same time, and
+/// returns the crates which were installed and those which failed.
+///
+/// The processes run with the same arguments as this one, and
+/// `__CARGO_INSTALL_ONLY` tells each of them which of the crates to install.
+/// They share a jobserver, so that they run no more than `-j` jobs between
+/// them. The output of each process is shown once it finishes, so that the
+/// output of the crates isn't interleaved, with a progress bar in the
+/// meantime.
+fn install_parallel<'a>(
+    config: &Config,
+    krates: &[&'a str],
+    jobs: u32,
+) -> CargoResult<(Vec<&'a str>, Vec<&'a str>)> {
+    let jobserver =
+        jobserver::Client::new(jobs as usize).chain_err(|| "failed to create jobserver")?;
+    let args: Vec<OsString> = env::args_os().skip(1).collect();
+    let (tx, rx) = mpsc::channel();
+    for i in 0..krates.len() {
+        let mut cmd = process(config.cargo_exe()?);
+        cmd.args(&args)
+            .env(INSTALL_ONLY_ENV, i.to_string())
+            .inherit_jobserver(&jobserver);
+        if config.shell().err_supports_color() {
+            cmd.env("CARGO_TERM_COLOR", "always");
+        }
+        let mut command = cmd.build_command();
+        let tx = tx.clone();
+        thread::spawn(move || {
+            let output = command.stdin(Stdio::null()).output();
+            let _ = tx.send((i, output));
+        });
+    }
+    drop(tx);
+
+    let mut installed = vec![None; krates.len()];
+    let mut progress = Progress::with_style("Installing", ProgressStyle::Ratio, config);
+    loop {
+        let running: Vec<&str> = krates
+            .iter()
+            .zip(&installed)
+            .filter(|(_, installed)| installed.is_none())
+            .map(|(krate, _)| *krate)
+            .collect();
+        if running.is_empty() {
+            break;
+        }
+        let done = krates.len() - running.len();
+        progress.tick_now(done, krates.len(), &format!(": {}", running.join(", ")))?;
+        let (i, output) = match rx.recv_timeout(Duration::from_millis(500)) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        progress.clear();
+        installed[i] = Some(match output {
+            Ok(output) => {
+                drop_print!(config, "{}", String::from_utf8_lossy(&output.stdout));
+                drop_eprint!(config, "{}", String::from_utf8_lossy(&output.stderr));
+                output.status.success()
+            }
+            Err(e) => {
+                let e = anyhow::Error::from(e)
+                    .context(format!("failed to run `cargo install` for `{}`", krates[i]));
+                crate::display_error(&e, &mut config.shell());
+                false
+            }
+        });
+    }
+    progress.clear();
+
+    let (succeeded, failed): (Vec<_>, Vec<_>) = krates
+        .iter()
+        .zip(installed)
+        .partition(|(_, installed)| *installed == Some(true));
+    Ok((
+        succeeded.into_iter().map(|(krate, _)| *krate).collect(),
+        failed.into_iter().map(|(krate, _)| *krate).collect(),
+    ))
+}
+
 // Returns whether a subsequent call should attempt to update again.
 // The `needs_update_if_source_is_index` parameter indicates whether or not the source index should
 // be updated. This is used ensure it is only updated once when installing multiple crates.
@@ -151,11 +267,13 @@ fn install_one(
     map: &SourceConfigMap<'_>,
     krate: Option<&str>,
     source_id: SourceId,
+    path_in_repo: Option<&Path>,
     from_cwd: bool,
     vers: Option<&str>,
     opts: &ops::CompileOptions,
     force: bool,
     no_track: bool,
+    dry_run: bool,
     needs_update_if_source_is_index: bool,
 ) -> CargoResult<bool> {
     if let Some(name) = krate {
@@ -170,6 +288,8 @@ fn install_one(
 
     let dst = root.join("bin").into_path_unlocked();
 
+    // The registry the package comes from, to look for prebuilt binaries.
+    let mut registry_source = None;
     let pkg = {
         let dep = {
             if let Some(krate) = krate {
@@ -196,6 +316,9 @@ fn install_one(
 
         if source_id.is_git() {
             let mut source = GitSource::new(source_id, config)?;
+            if let Some(path) = path_in_repo {
+                source.set_sparse_path(path_in_repo_dir(path)?);
+            }
             select_pkg(
                 &mut source,
                 dep,
@@ -246,13 +369,16 @@ fn install_one(
                     force,
                 ) {
                     let msg = format!(
-                        "package `{}` is already installed, use --force to override",
+                        "package `{}` is already installed and up to date, use --force to reinstall",
                         pkg
                     );
                     config.shell().status("Ignored", &msg)?;
                     return Ok(true);
                 }
-                select_dep_pkg(&mut source, dep, config, needs_update_if_source_is_index)?
+                let pkg =
+                    select_dep_pkg(&mut source, dep, config, needs_update_if_source_is_index)?;
+                registry_source = Some(source);
+                pkg
             } else {
                 bail!(
                     "must specify a crate to install from \
@@ -331,15 +457,33 @@ fn install_one(
         Ok(duplicates)
     };
 
+    // Path sources have no version to compare against, so their files are
+    // hashed to tell whether they changed since they were last installed.
+    let source_hash = if source_id.is_path() && !no_track {
+        Some(path_source_hash(&ws)?)
+    } else {
+        None
+    };
+
     // WARNING: no_track does not perform locking, so there is no protection
     // of concurrent installs.
     if no_track {
         // Check for conflicts.
         no_track_duplicates()?;
     } else {
-        if is_installed(&pkg, config, opts, &rustc, &target, root, &dst, force)? {
+        if is_installed(
+            &pkg,
+            config,
+            opts,
+            &rustc,
+            &target,
+            root,
+            &dst,
+            force,
+            source_hash.as_deref(),
+        )? {
             let msg = format!(
-                "package `{}` is already installed, use --force to override",
+                "package `{}` is already installed and up to date, use --force to reinstall",
                 pkg
             );
             config.shell().status("Ignored", &msg)?;
@@ -347,31 +491,84 @@ fn install_one(
         }
     }
 
+    if dry_run {
+        let duplicates = if no_track {
+            no_track_duplicates()?
+        } else {
+            let tracker = InstallTracker::load(config, root)?;
+            let (_freshness, duplicates) = tracker.check_upgrade(
+                &dst,
+                &pkg,
+                true,
+                opts,
+                &target,
+                &rustc.verbose_version,
+                None,
+            )?;
+            duplicates
+        };
+        check_yanked_install(&ws)?;
+        print_dry_run(config, &ws, opts, &pkg, &dst, &duplicates)?;
+        return Ok(false);
+    }
+
     config.shell().status("Installing", &pkg)?;
 
     check_yanked_install(&ws)?;
 
-    let exec: Arc<dyn Executor> = Arc::new(DefaultExecutor);
-    let compile = ops::compile_ws(&ws, opts, &exec).chain_err(|| {
-        if let Some(td) = td_opt.take() {
-            // preserve the temporary directory, so the user can inspect it
-            td.into_path();
+    let prebuilt = match &mut registry_source {
+        Some(source)
+            if config.cli_unstable().prebuilt_install && can_use_prebuilt(opts, &target) =>
+        {
+            match prebuilt_binaries(config, source, &pkg, &target) {
+                Ok(prebuilt) => prebuilt,
+                Err(e) => {
+                    crate::display_warning_with_error(
+                        &format!(
+                            "failed to install the prebuilt binaries of `{}`, \
+                             building it from source instead",
+                            pkg
+                        ),
+                        &e,
+                        &mut config.shell(),
+                    );
+                    None
+                }
+            }
         }
+        _ => None,
+    };
 
-        format_err!(
-            "failed to compile `{}`, intermediate artifacts can be \
-             found at `{}`",
-            pkg,
-            ws.target_dir().display()
-        )
-    })?;
-    let mut binaries: Vec<(&str, &Path)> = compile
-        .binaries
-        .iter()
-        .map(|(_, bin)| {
+    let compile;
+    let bin_paths: Vec<&Path> = if let Some((_dir, bins)) = &prebuilt {
+        bins.iter().map(|bin| bin.as_path()).collect()
+    } else {
+        let exec: Arc<dyn Executor> = Arc::new(DefaultExecutor);
+        compile = ops::compile_ws(&ws, opts, &exec).chain_err(|| {
+            if let Some(td) = td_opt.take() {
+                // preserve the temporary directory, so the user can inspect it
+                let _ = td.keep();
+            }
+
+            format_err!(
+                "failed to compile `{}`, intermediate artifacts can be \
+                 found at `{}`",
+                pkg,
+                ws.target_dir().display()
+            )
+        })?;
+        compile
+            .binaries
+            .iter()
+            .map(|(_, bin)| bin.as_path())
+            .collect()
+    };
+    let mut binaries: Vec<(&str, &Path)> = bin_paths
+        .into_iter()
+        .map(|bin| {
             let name = bin.file_name().unwrap();
             if let Some(s) = name.to_str() {
-                Ok((s, bin.as_ref()))
+                Ok((s, bin))
             } else {
                 bail!("Binary `{:?}` name can't be serialized into string", name)
             }
@@ -383,12 +580,25 @@ fn install_one(
     // This is primarily to make testing easier.
     binaries.sort_unstable();
 
+    // The build may have written the lock file, so hash the sources again.
+    let source_hash = match source_hash {
+        Some(_) => Some(path_source_hash(&ws)?),
+        None => None,
+    };
+
     let (tracker, duplicates) = if no_track {
         (None, no_track_duplicates()?)
     } else {
         let tracker = InstallTracker::load(config, root)?;
-        let (_freshness, duplicates) =
-            tracker.check_upgrade(&dst, &pkg, force, opts, &target, &rustc.verbose_version)?;
+        let (_freshness, duplicates) = tracker.check_upgrade(
+            &dst,
+            &pkg,
+            force,
+            opts,
+            &target,
+            &rustc.verbose_version,
+            None,
+        )?;
         (Some(tracker), duplicates)
     };
 
@@ -454,7 +664,8 @@ fn install_one(
             vers.map(|s| s.to_string()),
             opts,
             &target,
-            &rustc.verbose_version,
+            &rustc,
+            source_hash.as_deref(),
         );
 
         if let Err(e) = remove_orphaned_bins(&ws, &mut tracker, &duplicates, &pkg, &dst) {
@@ -499,7 +710,6 @@ fn install_one(
             "Installed",
             format!("package `{}` {}", pkg, executables(successful_bins.iter())),
         )?;
-        Ok(false)
     } else {
         if !to_install.is_empty() {
             config.shell().status(
@@ -524,8 +734,114 @@ fn install_one(
                 ),
             )?;
         }
-        Ok(false)
     }
+    warn_shadowed_bins(config, &dst, &successful_bins)?;
+    Ok(false)
+}
+
+/// Warns about the installed binaries `bins` which won't be run from `PATH`
+/// because a binary of the same name is in a directory which comes before
+/// `dst` in `PATH`, or in any directory of `PATH` if `dst` isn't in it.
+fn warn_shadowed_bins(config: &Config, dst: &Path, bins: &BTreeSet<String>) -> CargoResult<()> {
+    let path = env::var_os("PATH").unwrap_or_default();
+    let dirs: Vec<PathBuf> = env::split_paths(&path).collect();
+    let dst_in_path = dirs.iter().any(|dir| same_dir(dir, dst));
+    for bin in bins {
+        let shadowing = dirs
+            .iter()
+            .take_while(|dir| !same_dir(dir, dst))
+            .map(|dir| dir.join(bin))
+            .find(|candidate| candidate.is_file());
+        if let Some(shadowing) = shadowing {
+            let position = if dst_in_path {
+                format!("comes before `{}` in PATH", dst.display())
+            } else {
+                "is in PATH".to_string()
+            };
+            config.shell().warn(format!(
+                "the installed binary `{}` is shadowed by `{}`, which {}",
+                bin,
+                shadowing.display(),
+                position
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether `a` and `b` are the same directory, even through symlinks.
+fn same_dir(a: &Path, b: &Path) -> bool {
+    if a == b {
+        return true;
+    }
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Prints what `cargo install --dry-run` would build and install, once the
+/// package is resolved, without building anything.
+fn print_dry_run(
+    config: &Config,
+    ws: &Workspace<'_>,
+    opts: &ops::CompileOptions,
+    pkg: &Package,
+    dst: &Path,
+    duplicates: &BTreeMap<String, Option<PackageId>>,
+) -> CargoResult<()> {
+    let interner = UnitInterner::new();
+    let bcx = ops::create_bcx(ws, opts, &interner)?;
+    let features: BTreeSet<&str> = bcx
+        .roots
+        .iter()
+        .flat_map(|unit| unit.features.iter().map(|f| f.as_str()))
+        .collect();
+    let deps: BTreeSet<PackageId> = bcx
+        .unit_graph
+        .keys()
+        .map(|unit| unit.pkg.package_id())
+        .filter(|&id| id != pkg.package_id())
+        .collect();
+    let bins: BTreeSet<String> = bcx
+        .roots
+        .iter()
+        .filter(|unit| unit.target.is_executable())
+        .map(|unit| format!("{}{}", unit.target.name(), env::consts::EXE_SUFFIX))
+        .collect();
+
+    drop_println!(config, "{}", pkg);
+    if features.is_empty() {
+        drop_println!(config, "  features: (none)");
+    } else {
+        let features: Vec<&str> = features.into_iter().collect();
+        drop_println!(config, "  features: {}", features.join(", "));
+    }
+    if deps.is_empty() {
+        drop_println!(config, "  dependencies to build: (none)");
+    } else {
+        drop_println!(config, "  dependencies to build:");
+        for dep in deps {
+            drop_println!(config, "    {}", dep);
+        }
+    }
+    drop_println!(config, "  binaries:");
+    for bin in bins {
+        let path = dst.join(&bin);
+        match duplicates.get(&bin) {
+            Some(Some(pkg_id)) => drop_println!(
+                config,
+                "    {} (replaces the one of `{}`)",
+                path.display(),
+                pkg_id
+            ),
+            Some(None) => {
+                drop_println!(config, "    {} (replaces an existing file)", path.display())
+            }
+            None => drop_println!(config, "    {}", path.display()),
+        }
+    }
+    Ok(())
 }
 
 fn is_installed(
@@ -537,10 +853,18 @@ fn is_installed(
     root: &Filesystem,
     dst: &Path,
     force: bool,
+    source_hash: Option<&str>,
 ) -> CargoResult<bool> {
     let tracker = InstallTracker::load(config, root)?;
-    let (freshness, _duplicates) =
-        tracker.check_upgrade(dst, pkg, force, opts, target, &rustc.verbose_version)?;
+    let (freshness, _duplicates) = tracker.check_upgrade(
+        dst,
+        pkg,
+        force,
+        opts,
+        target,
+        &rustc.verbose_version,
+        source_hash,
+    )?;
     Ok(freshness == Freshness::Fresh)
 }
 
@@ -571,7 +895,8 @@ where
     if let Ok(pkg) = select_dep_pkg(source, dep, config, false) {
         let (_ws, rustc, target) =
             make_ws_rustc_target(config, opts, &source.source_id(), pkg.clone())?;
-        if let Ok(true) = is_installed(&pkg, config, opts, &rustc, &target, root, dst, force) {
+        if let Ok(true) = is_installed(&pkg, config, opts, &rustc, &target, root, dst, force, None)
+        {
             return Ok(Some(pkg));
         }
     }
@@ -591,6 +916,14 @@ fn make_ws_rustc_target<'cfg>(
     };
     ws.set_ignore_lock(config.lock_update_allowed());
     ws.set_require_optional_deps(false);
+    if source_id.is_path()
+        && config.cli_unstable().install_locked
+        && ws.root().join("Cargo.lock").exists()
+    {
+        // Build with the lock file of the workspace of the package, like
+        // `--locked` does.
+        ws.set_ignore_lock(false);
+    }
 
     let rustc = config.load_global_rustc(Some(&ws))?;
     let target = match &opts.build_config.single_requested_kind()? {
@@ -601,8 +934,137 @@ fn make_ws_rustc_target<'cfg>(
     Ok((ws, rustc, target))
 }
 
+/// Returns whether prebuilt binaries can be installed instead of building
+/// the package with `opts`, which is only the case when it would be built
+/// the default way.
+fn can_use_prebuilt(opts: &ops::CompileOptions, target: &str) -> bool {
+    opts.features.is_empty()
+        && !opts.all_features
+        && !opts.no_default_features
+        && !opts.filter.is_specific()
+        && opts.build_config.requested_profile == "release"
+        && !target.ends_with(".json")
+}
+
+/// Downloads and unpacks the prebuilt binaries of `pkg` for `target`, if its
+/// registry has any.
+///
+/// The archive is checked against the checksum listed in the index, and its
+/// signature against the `trusted-keys` of the registry, if it has any. The
+/// binaries are unpacked to a temporary directory, which is returned with
+/// their paths.
+fn prebuilt_binaries(
+    config: &Config,
+    source: &mut dyn Source,
+    pkg: &Package,
+    target: &str,
+) -> CargoResult<Option<(TempDir, Vec<PathBuf>)>> {
+    let prebuilt = {
+        let _lock = config.acquire_package_cache_lock()?;
+        match source.prebuilt_binary(pkg.package_id(), target)? {
+            Some(prebuilt) => prebuilt,
+            None => return Ok(None),
+        }
+    };
+    config.shell().status(
+        "Downloading",
+        format!("prebuilt binaries of `{}` for `{}`", pkg, target),
+    )?;
+
+    let mut data = Vec::new();
+    let mut handle = ops::http_handle(config)?;
+    handle.get(true)?;
+    handle.url(&prebuilt.url)?;
+    handle.follow_location(true)?;
+    {
+        let mut handle = handle.transfer();
+        handle.write_function(|buf| {
+            data.extend_from_slice(buf);
+            Ok(buf.len())
+        })?;
+        handle
+            .perform()
+            .chain_err(|| format!("failed to download `{}`", prebuilt.url))?;
+    }
+    let code = handle.response_code()?;
+    // File URLs have no response code.
+    if code != 200 && code != 0 {
+        bail!("failed to download `{}`, got {}", prebuilt.url, code);
+    }
+
+    let cksum = Sha256::new().update(&data).finish_hex();
+    if cksum != prebuilt.cksum {
+        bail!(
+            "the checksum of the archive is {}, not {} as listed in the index",
+            cksum,
+            prebuilt.cksum
+        );
+    }
+    if let Some(trusted_keys) = ops::registry_trusted_keys(config, source.source_id())? {
+        crate_signature::verify_prebuilt(
+            prebuilt.signature.as_deref(),
+            &trusted_keys,
+            pkg.package_id(),
+            target,
+            &cksum,
+        )
+        .chain_err(|| "failed to verify the signature of the archive")?;
+    }
+
+    // Only the binaries of the package are unpacked, directly in the
+    // temporary directory.
+    let names: BTreeSet<String> = pkg
+        .targets()
+        .iter()
+        .filter(|t| t.is_bin())
+        .map(|t| format!("{}{}", t.name(), env::consts::EXE_SUFFIX))
+        .collect();
+    let dir = TempFileBuilder::new().prefix("cargo-install").tempdir()?;
+    let mut bins = Vec::new();
+    let mut archive = Archive::new(GzDecoder::new(&data[..]));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let name = path.to_str().unwrap_or_default();
+        if !entry.header().entry_type().is_file() || !names.contains(name) {
+            bail!(
+                "the archive contains `{}`, which is not a binary of the package",
+                path.display()
+            );
+        }
+        let dst = dir.path().join(name);
+        entry.unpack(&dst)?;
+        bins.push(dst);
+    }
+    if bins.is_empty() {
+        bail!("the archive contains no binaries");
+    }
+    Ok(Some((dir, bins)))
+}
+
 /// Parses x.y.z as if it were =x.y.z, and gives CLI-specific error messages in the case of invalid
 /// values.
+/// Validates the `--path-in-repo` directory, which must be relative to the
+/// root of the repository and may not leave it.
+fn path_in_repo_dir(path: &Path) -> CargoResult<PathBuf> {
+    let mut dir = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(c) => dir.push(c),
+            Component::CurDir => {}
+            _ => bail!(
+                "`--path-in-repo {}` must be a path relative to the root of the repository, \
+                 without `..`",
+                path.display()
+            ),
+        }
+    }
+    if dir.as_os_str().is_empty() {
+        bail!("`--path-in-repo` must not be the root of the repository");
+    }
+    Ok(dir)
+}
+
 fn parse_semver_flag(v: &str) -> CargoResult<VersionReq> {
     // If the version begins with character <, >, =, ^, ~ parse it as a
     // version range, otherwise parse it as a specific version
@@ -680,9 +1142,15 @@ fn check_yanked_install(ws: &Workspace<'_>) -> CargoResult<()> {
 }
 
 /// Display a list of installed binaries.
-pub fn install_list(dst: Option<&str>, config: &Config) -> CargoResult<()> {
+pub fn install_list(dst: Option<&Path>, json: bool, config: &Config) -> CargoResult<()> {
     let root = resolve_root(dst, config)?;
     let tracker = InstallTracker::load(config, &root)?;
+    if json {
+        let installs = tracker.installed_packages();
+        let listing = serde_json::json!({ "installs": installs });
+        drop_println!(config, "{}", listing);
+        return Ok(());
+    }
     for (k, v) in tracker.all_installed_bins() {
         drop_println!(config, "{}:", k);
         for bin in v {
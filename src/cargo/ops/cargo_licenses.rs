@@ -0,0 +1,611 @@
+//! Implementation of `cargo licenses`.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+
+use crate::core::compiler::{CompileKind, RustcTargetData};
+use crate::core::resolver::{ForceAllTargets, HasDevUnits, ResolveOpts};
+use crate::core::{PackageId, Workspace};
+use crate::drop_print;
+use crate::ops::tree::Target;
+use crate::ops::{self, Packages};
+use crate::util::{CargoResult, Config};
+
+pub struct LicensesOptions {
+    pub features: Vec<String>,
+    pub all_features: bool,
+    pub no_default_features: bool,
+    /// The packages whose dependencies are reported.
+    pub packages: Packages,
+    /// The platform to filter dependencies for.
+    pub target: Target,
+    /// Whether to include the dev-dependencies of the packages.
+    pub dev: bool,
+    pub format: LicensesFormat,
+    /// Whether to fail if a dependency violates the license policy of the
+    /// `[licenses]` configuration.
+    pub check: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LicensesFormat {
+    Human,
+    Json,
+    Csv,
+    Html,
+}
+
+impl LicensesFormat {
+    pub fn parse(s: &str) -> CargoResult<LicensesFormat> {
+        match s {
+            "human" => Ok(LicensesFormat::Human),
+            "json" => Ok(LicensesFormat::Json),
+            "csv" => Ok(LicensesFormat::Csv),
+            "html" => Ok(LicensesFormat::Html),
+            _ => bail!(
+                "invalid license report format `{}`, expected `human`, `json`, `csv` or `html`",
+                s
+            ),
+        }
+    }
+}
+
+/// The license policy of the `[licenses]` configuration table.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+struct LicensePolicy {
+    /// If not empty, the only licenses dependencies may use.
+    #[serde(default)]
+    allow: Vec<String>,
+    /// Licenses dependencies may not use.
+    #[serde(default)]
+    deny: Vec<String>,
+    /// Names of packages which aren't checked.
+    #[serde(default)]
+    exceptions: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct LicenseReport {
+    packages: Vec<PackageLicense>,
+    violations: Vec<Violation>,
+}
+
+#[derive(Serialize)]
+struct PackageLicense {
+    name: String,
+    version: String,
+    id: PackageId,
+    /// The `license` field of the manifest.
+    license: Option<String>,
+    /// The normalized SPDX expression of `license`, if it's valid.
+    normalized_license: Option<String>,
+    /// The `license-file` field of the manifest.
+    license_file: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Violation {
+    id: PackageId,
+    reason: String,
+}
+
+pub fn licenses(ws: &Workspace<'_>, opts: &LicensesOptions) -> CargoResult<()> {
+    let config = ws.config();
+    let policy = config
+        .get::<Option<LicensePolicy>>("licenses")?
+        .unwrap_or_default();
+    let allow = normalize_policy(&policy.allow, "allow")?;
+    let deny = normalize_policy(&policy.deny, "deny")?;
+
+    let requested_targets = match &opts.target {
+        Target::All | Target::Host => Vec::new(),
+        Target::Specific(t) => t.clone(),
+    };
+    let requested_kinds = CompileKind::from_requested_targets(config, &requested_targets)?;
+    let target_data = RustcTargetData::new(ws, &requested_kinds)?;
+    let specs = opts.packages.to_package_id_specs(ws)?;
+    let resolve_opts = ResolveOpts::new(
+        /*dev_deps*/ true,
+        &opts.features,
+        opts.all_features,
+        !opts.no_default_features,
+    );
+    let has_dev = if opts.dev {
+        HasDevUnits::Yes
+    } else {
+        HasDevUnits::No
+    };
+    let force_all = if opts.target == Target::All {
+        ForceAllTargets::Yes
+    } else {
+        ForceAllTargets::No
+    };
+    let ws_resolve = ops::resolve_ws_with_opts(
+        ws,
+        &target_data,
+        &requested_kinds,
+        &resolve_opts,
+        &specs,
+        has_dev,
+        force_all,
+    )?;
+
+    let member_ids: Vec<PackageId> = ws
+        .members_with_features(&specs, &resolve_opts.features)?
+        .into_iter()
+        .map(|(p, _fts)| p.package_id())
+        .collect();
+    let kinds = match opts.target {
+        Target::All => None,
+        _ => Some(&requested_kinds[..]),
+    };
+    let mut used = BTreeSet::new();
+    for &id in &member_ids {
+        ops::collect_used_deps(
+            &ws_resolve.targeted_resolve,
+            id,
+            opts.dev,
+            kinds,
+            &target_data,
+            &mut used,
+        );
+    }
+    let members: HashSet<PackageId> = ws.members().map(|p| p.package_id()).collect();
+    let dep_ids: Vec<PackageId> = used
+        .into_iter()
+        .filter(|id| !members.contains(id))
+        .collect();
+
+    let mut report = LicenseReport {
+        packages: Vec::new(),
+        violations: Vec::new(),
+    };
+    for pkg in ws_resolve.pkg_set.get_many(dep_ids)? {
+        let metadata = pkg.manifest().metadata();
+        let expr = metadata.license.as_deref().map(LicenseExpr::parse);
+        if opts.check && !policy.exceptions.iter().any(|e| *e == *pkg.name()) {
+            let reason = match &expr {
+                Some(Ok(expr)) if expr.satisfies(&allow, &deny) => None,
+                Some(Ok(expr)) => Some(format!("`{}` is not allowed by the license policy", expr)),
+                Some(Err(e)) => Some(e.to_string()),
+                // A license file can't be checked.
+                None if !allow.is_empty() => Some(match &metadata.license_file {
+                    Some(file) => format!(
+                        "no SPDX license expression, only the license file `{}`",
+                        file
+                    ),
+                    None => "no license".to_string(),
+                }),
+                None => None,
+            };
+            if let Some(reason) = reason {
+                report.violations.push(Violation {
+                    id: pkg.package_id(),
+                    reason,
+                });
+            }
+        }
+        report.packages.push(PackageLicense {
+            name: pkg.name().to_string(),
+            version: pkg.version().to_string(),
+            id: pkg.package_id(),
+            license: metadata.license.clone(),
+            normalized_license: expr.and_then(|e| e.ok()).map(|e| e.to_string()),
+            license_file: metadata.license_file.clone(),
+        });
+    }
+
+    match opts.format {
+        LicensesFormat::Human => print_human(config, &report),
+        LicensesFormat::Json => config.shell().print_json(&report),
+        LicensesFormat::Csv => print_csv(config, &report),
+        LicensesFormat::Html => print_html(config, &report),
+    }
+
+    if !report.violations.is_empty() {
+        let mut msg = format!(
+            "{} {} the license policy:",
+            report.violations.len(),
+            if report.violations.len() == 1 {
+                "dependency violates"
+            } else {
+                "dependencies violate"
+            }
+        );
+        for violation in &report.violations {
+            msg.push_str(&format!("\n  {}: {}", violation.id, violation.reason));
+        }
+        bail!(msg);
+    }
+    Ok(())
+}
+
+/// Parses and normalizes the licenses of the `allow` or `deny` list of the
+/// license policy.
+fn normalize_policy(licenses: &[String], key: &str) -> CargoResult<BTreeSet<String>> {
+    licenses
+        .iter()
+        .map(|l| match LicenseExpr::parse(l)? {
+            LicenseExpr::License(l) => Ok(l),
+            expr @ LicenseExpr::With(..) => Ok(expr.to_string()),
+            _ => bail!(
+                "`licenses.{}` must only contain licenses, not expressions like `{}`",
+                key,
+                l
+            ),
+        })
+        .collect()
+}
+
+fn print_human(config: &Config, report: &LicenseReport) {
+    let mut by_license: BTreeMap<String, Vec<&PackageLicense>> = BTreeMap::new();
+    for pkg in &report.packages {
+        let license = match (&pkg.normalized_license, &pkg.license, &pkg.license_file) {
+            (Some(l), _, _) => l.clone(),
+            (None, Some(l), _) => format!("{} (invalid)", l),
+            (None, None, Some(f)) => format!("license file `{}`", f),
+            (None, None, None) => "no license".to_string(),
+        };
+        by_license.entry(license).or_default().push(pkg);
+    }
+    for (license, pkgs) in by_license {
+        let pkgs: Vec<String> = pkgs
+            .iter()
+            .map(|p| format!("{} v{}", p.name, p.version))
+            .collect();
+        drop_print!(
+            config,
+            "{} ({}): {}\n",
+            license,
+            pkgs.len(),
+            pkgs.join(", ")
+        );
+    }
+}
+
+fn print_csv(config: &Config, report: &LicenseReport) {
+    fn field(s: &str) -> String {
+        if s.contains(&[',', '"', '\n', '\r'][..]) {
+            format!("\"{}\"", s.replace('"', "\"\""))
+        } else {
+            s.to_string()
+        }
+    }
+    drop_print!(config, "name,version,source,license,license_file\n");
+    for pkg in &report.packages {
+        let license = pkg.normalized_license.as_ref().or(pkg.license.as_ref());
+        drop_print!(
+            config,
+            "{},{},{},{},{}\n",
+            field(&pkg.name),
+            field(&pkg.version),
+            field(&pkg.id.source_id().as_url().to_string()),
+            field(license.map_or("", |l| l.as_str())),
+            field(pkg.license_file.as_deref().unwrap_or("")),
+        );
+    }
+}
+
+fn print_html(config: &Config, report: &LicenseReport) {
+    fn escape(s: &str) -> String {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+    let mut out = String::from(
+        "<!DOCTYPE html>\n\
+         <html>\n\
+         <head>\n\
+         <meta charset=\"utf-8\">\n\
+         <title>Licenses of dependencies</title>\n\
+         </head>\n\
+         <body>\n\
+         <table>\n\
+         <tr><th>Package</th><th>Version</th><th>Source</th><th>License</th><th>License file</th></tr>\n",
+    );
+    for pkg in &report.packages {
+        let license = pkg.normalized_license.as_ref().or(pkg.license.as_ref());
+        out.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            escape(&pkg.name),
+            escape(&pkg.version),
+            escape(&pkg.id.source_id().as_url().to_string()),
+            escape(license.map_or("", |l| l.as_str())),
+            escape(pkg.license_file.as_deref().unwrap_or("")),
+        ));
+    }
+    out.push_str("</table>\n");
+    if !report.violations.is_empty() {
+        out.push_str("<h2>License policy violations</h2>\n<ul>\n");
+        for violation in &report.violations {
+            out.push_str(&format!(
+                "<li>{}: {}</li>\n",
+                escape(&violation.id.to_string()),
+                escape(&violation.reason)
+            ));
+        }
+        out.push_str("</ul>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    drop_print!(config, "{}", out);
+}
+
+/// A parsed SPDX license expression.
+#[derive(Debug, PartialEq)]
+enum LicenseExpr {
+    License(String),
+    /// A license with an exception.
+    With(String, String),
+    And(Vec<LicenseExpr>),
+    Or(Vec<LicenseExpr>),
+}
+
+impl LicenseExpr {
+    /// Parses the SPDX expression `s`, normalizing the license identifiers
+    /// to their canonical case and non-deprecated form, and accepting the
+    /// legacy `/` separator for `OR` and lowercase operators.
+    fn parse(s: &str) -> CargoResult<LicenseExpr> {
+        let spaced = s
+            .replace('(', " ( ")
+            .replace(')', " ) ")
+            .replace('/', " / ");
+        let mut tokens = Vec::new();
+        for word in spaced.split_whitespace() {
+            tokens.push(match word.to_ascii_uppercase().as_str() {
+                "OR" | "/" => Token::Or,
+                "AND" => Token::And,
+                "WITH" => Token::With,
+                "(" => Token::Open,
+                ")" => Token::Close,
+                _ => Token::Id(word),
+            });
+        }
+        let mut parser = Parser {
+            tokens: &tokens,
+            pos: 0,
+        };
+        let expr = parser
+            .or()
+            .and_then(|expr| match parser.tokens.get(parser.pos) {
+                None => Some(expr),
+                Some(_) => None,
+            });
+        match expr {
+            Some(expr) => Ok(expr),
+            None => bail!("invalid SPDX license expression `{}`", s),
+        }
+    }
+
+    /// Whether the licenses can be chosen among `allow`, if it's not empty,
+    /// without using any of `deny`.
+    fn satisfies(&self, allow: &BTreeSet<String>, deny: &BTreeSet<String>) -> bool {
+        match self {
+            LicenseExpr::License(l) => (allow.is_empty() || allow.contains(l)) && !deny.contains(l),
+            LicenseExpr::With(l, _) => {
+                let full = self.to_string();
+                (allow.is_empty() || allow.contains(l) || allow.contains(&full))
+                    && !deny.contains(l)
+                    && !deny.contains(&full)
+            }
+            LicenseExpr::And(exprs) => exprs.iter().all(|e| e.satisfies(allow, deny)),
+            LicenseExpr::Or(exprs) => exprs.iter().any(|e| e.satisfies(allow, deny)),
+        }
+    }
+}
+
+impl fmt::Display for LicenseExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn join(
+            f: &mut fmt::Formatter<'_>,
+            exprs: &[LicenseExpr],
+            op: &str,
+            parens: impl Fn(&LicenseExpr) -> bool,
+        ) -> fmt::Result {
+            for (i, expr) in exprs.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " {} ", op)?;
+                }
+                if parens(expr) {
+                    write!(f, "({})", expr)?;
+                } else {
+                    write!(f, "{}", expr)?;
+                }
+            }
+            Ok(())
+        }
+        match self {
+            LicenseExpr::License(l) => write!(f, "{}", l),
+            LicenseExpr::With(l, e) => write!(f, "{} WITH {}", l, e),
+            LicenseExpr::And(exprs) => join(f, exprs, "AND", |e| {
+                matches!(e, LicenseExpr::Or(_) | LicenseExpr::And(_))
+            }),
+            LicenseExpr::Or(exprs) => join(f, exprs, "OR", |e| matches!(e, LicenseExpr::Or(_))),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    Id(&'a str),
+    And,
+    Or,
+    With,
+    Open,
+    Close,
+}
+
+/// A recursive descent parser of SPDX expressions, where `AND` binds
+/// tighter than `OR`.
+struct Parser<'a> {
+    tokens: &'a [Token<'a>],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Option<&'a Token<'a>> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        token
+    }
+
+    fn peek_is(&self, token: &Token<'_>) -> bool {
+        self.tokens.get(self.pos) == Some(token)
+    }
+
+    fn or(&mut self) -> Option<LicenseExpr> {
+        let mut exprs = vec![self.and()?];
+        while self.peek_is(&Token::Or) {
+            self.pos += 1;
+            exprs.push(self.and()?);
+        }
+        Some(flatten(exprs, true))
+    }
+
+    fn and(&mut self) -> Option<LicenseExpr> {
+        let mut exprs = vec![self.with()?];
+        while self.peek_is(&Token::And) {
+            self.pos += 1;
+            exprs.push(self.with()?);
+        }
+        Some(flatten(exprs, false))
+    }
+
+    fn with(&mut self) -> Option<LicenseExpr> {
+        match self.next()? {
+            Token::Open => {
+                let expr = self.or()?;
+                match self.next()? {
+                    Token::Close => Some(expr),
+                    _ => None,
+                }
+            }
+            Token::Id(id) => {
+                let license = normalize_license_id(id);
+                if self.peek_is(&Token::With) {
+                    self.pos += 1;
+                    match self.next()? {
+                        Token::Id(exception) => Some(LicenseExpr::With(
+                            license,
+                            normalize_exception_id(exception),
+                        )),
+                        _ => None,
+                    }
+                } else {
+                    Some(LicenseExpr::License(license))
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Combines `exprs` with `OR` if `or` is `true`, or `AND` otherwise, merging
+/// nested expressions of the same operator, like `(A OR B) OR C`.
+fn flatten(exprs: Vec<LicenseExpr>, or: bool) -> LicenseExpr {
+    if exprs.len() == 1 {
+        return exprs.into_iter().next().unwrap();
+    }
+    let mut flat = Vec::new();
+    for expr in exprs {
+        match expr {
+            LicenseExpr::Or(inner) if or => flat.extend(inner),
+            LicenseExpr::And(inner) if !or => flat.extend(inner),
+            expr => flat.push(expr),
+        }
+    }
+    if or {
+        LicenseExpr::Or(flat)
+    } else {
+        LicenseExpr::And(flat)
+    }
+}
+
+/// Commonly used SPDX license identifiers, in their canonical case.
+const LICENSE_IDS: &[&str] = &[
+    "0BSD",
+    "AGPL-3.0-only",
+    "AGPL-3.0-or-later",
+    "Apache-1.1",
+    "Apache-2.0",
+    "Artistic-2.0",
+    "BSD-1-Clause",
+    "BSD-2-Clause",
+    "BSD-3-Clause",
+    "BSL-1.0",
+    "CC-BY-4.0",
+    "CC0-1.0",
+    "CDDL-1.0",
+    "EPL-1.0",
+    "EPL-2.0",
+    "EUPL-1.2",
+    "GPL-2.0-only",
+    "GPL-2.0-or-later",
+    "GPL-3.0-only",
+    "GPL-3.0-or-later",
+    "ISC",
+    "LGPL-2.0-only",
+    "LGPL-2.0-or-later",
+    "LGPL-2.1-only",
+    "LGPL-2.1-or-later",
+    "LGPL-3.0-only",
+    "LGPL-3.0-or-later",
+    "MIT",
+    "MIT-0",
+    "MPL-1.1",
+    "MPL-2.0",
+    "NCSA",
+    "OpenSSL",
+    "Unicode-DFS-2016",
+    "Unlicense",
+    "WTFPL",
+    "Zlib",
+];
+
+/// The deprecated identifiers of the GNU licenses, which don't say whether
+/// later versions are allowed.
+const DEPRECATED_GNU_IDS: &[&str] = &[
+    "AGPL-3.0", "GPL-2.0", "GPL-3.0", "LGPL-2.0", "LGPL-2.1", "LGPL-3.0",
+];
+
+/// Commonly used SPDX exception identifiers, in their canonical case.
+const EXCEPTION_IDS: &[&str] = &[
+    "Classpath-exception-2.0",
+    "GCC-exception-3.1",
+    "LLVM-exception",
+];
+
+fn normalize_license_id(id: &str) -> String {
+    let (base, or_later) = match id.strip_suffix('+') {
+        Some(base) => (base, true),
+        None => (id, false),
+    };
+    if let Some(gnu) = DEPRECATED_GNU_IDS
+        .iter()
+        .find(|gnu| gnu.eq_ignore_ascii_case(base))
+    {
+        let suffix = if or_later { "or-later" } else { "only" };
+        return format!("{}-{}", gnu, suffix);
+    }
+    let base = LICENSE_IDS
+        .iter()
+        .find(|known| known.eq_ignore_ascii_case(base))
+        .map_or(base, |known| *known);
+    if or_later {
+        format!("{}+", base)
+    } else {
+        base.to_string()
+    }
+}
+
+fn normalize_exception_id(id: &str) -> String {
+    EXCEPTION_IDS
+        .iter()
+        .find(|known| known.eq_ignore_ascii_case(id))
+        .map_or(id, |known| *known)
+        .to_string()
+}
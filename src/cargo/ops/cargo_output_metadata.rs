@@ -8,7 +8,7 @@ use crate::util::interning::InternedString;
 use crate::util::CargoResult;
 use cargo_platform::Platform;
 use serde::Serialize;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
 use std::path::PathBuf;
 
 const VERSION: u32 = 1;
@@ -20,27 +20,36 @@ pub struct OutputMetadataOptions {
     pub no_deps: bool,
     pub version: u32,
     pub filter_platforms: Vec<String>,
+    pub checksums: bool,
 }
 
 /// Loads the manifest, resolves the dependencies of the package to the concrete
 /// used versions - considering overrides - and writes all dependencies in a JSON
 /// format to stdout.
 pub fn output_metadata(ws: &Workspace<'_>, opt: &OutputMetadataOptions) -> CargoResult<ExportInfo> {
-    if opt.version != VERSION {
-        anyhow::bail!(
-            "metadata version {} not supported, only {} is currently supported",
-            opt.version,
-            VERSION
-        );
-    }
-    let config = ws.config();
+    check_version(opt)?;
     let (packages, resolve) = if opt.no_deps {
-        let packages = ws.members().map(|pkg| pkg.serialized(config)).collect();
+        let packages = ws.members().map(|pkg| pkg.serialized()).collect();
         (packages, None)
     } else {
-        let (packages, resolve) = build_resolve_graph(ws, opt)?;
+        let mut nodes = Vec::new();
+        build_resolve_graph(ws, opt, &mut |pkg, node| {
+            nodes.push((pkg.package_id(), pkg.serialized(), node));
+            Ok(())
+        })?;
+        nodes.sort_by_key(|(pkg_id, _, _)| *pkg_id);
+        let (packages, nodes) = nodes.into_iter().map(|(_, pkg, node)| (pkg, node)).unzip();
+        let resolve = MetadataResolve {
+            nodes,
+            root: ws.current_opt().map(|pkg| pkg.package_id()),
+        };
         (packages, Some(resolve))
     };
+    let checksums = if opt.checksums {
+        Some(source_checksums(ws)?)
+    } else {
+        None
+    };
 
     Ok(ExportInfo {
         packages,
@@ -50,9 +59,64 @@ pub fn output_metadata(ws: &Workspace<'_>, opt: &OutputMetadataOptions) -> Cargo
         version: VERSION,
         workspace_root: ws.root().to_path_buf(),
         metadata: ws.custom_metadata().cloned(),
+        checksums,
     })
 }
 
+/// Like `output_metadata`, but writes the metadata to stdout as one JSON
+/// object per line, for `--format ndjson`. The workspace is described first,
+/// then each package is written along with its node of the resolve graph as
+/// soon as the graph reaches it, so that nothing is buffered.
+pub fn output_metadata_ndjson(ws: &Workspace<'_>, opt: &OutputMetadataOptions) -> CargoResult<()> {
+    check_version(opt)?;
+    let config = ws.config();
+    config.shell().print_json(&MetadataLine::Workspace {
+        version: VERSION,
+        workspace_members: ws.members().map(|pkg| pkg.package_id()).collect(),
+        workspace_root: ws.root().to_path_buf(),
+        target_directory: ws.target_dir().into_path_unlocked(),
+        metadata: ws.custom_metadata().cloned(),
+        root: if opt.no_deps {
+            None
+        } else {
+            ws.current_opt().map(|pkg| pkg.package_id())
+        },
+    });
+    if opt.no_deps {
+        for pkg in ws.members() {
+            config
+                .shell()
+                .print_json(&MetadataLine::Package(pkg.serialized()));
+        }
+    } else {
+        build_resolve_graph(ws, opt, &mut |pkg, node| {
+            let mut shell = config.shell();
+            shell.print_json(&MetadataLine::Package(pkg.serialized()));
+            shell.print_json(&MetadataLine::ResolveNode(node));
+            Ok(())
+        })?;
+    }
+    if opt.checksums {
+        for checksum in source_checksums(ws)? {
+            config
+                .shell()
+                .print_json(&MetadataLine::SourceChecksum(checksum));
+        }
+    }
+    Ok(())
+}
+
+fn check_version(opt: &OutputMetadataOptions) -> CargoResult<()> {
+    if opt.version != VERSION {
+        anyhow::bail!(
+            "metadata version {} not supported, only {} is currently supported",
+            opt.version,
+            VERSION
+        );
+    }
+    Ok(())
+}
+
 /// This is the structure that is serialized and displayed to the user.
 ///
 /// See cargo-metadata.adoc for detailed documentation of the format.
@@ -65,6 +129,43 @@ pub struct ExportInfo {
     version: u32,
     workspace_root: PathBuf,
     metadata: Option<toml::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    checksums: Option<Vec<SourceChecksum>>,
+}
+
+/// Where the source of a package of the lock file is fetched from, for
+/// `--checksums`.
+#[derive(Serialize)]
+struct SourceChecksum {
+    id: PackageId,
+    /// The URL of the `.crate` file, or of the repository for git packages.
+    url: Option<String>,
+    /// The SHA-256 checksum of the `.crate` file.
+    checksum: Option<String>,
+    /// The revision of the repository for git packages.
+    rev: Option<String>,
+    /// The name of the directory the `.crate` file is unpacked into.
+    directory: Option<String>,
+}
+
+/// A line of `--format ndjson`, whose `reason` field is the kebab-case name of
+/// the variant.
+#[derive(Serialize)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+enum MetadataLine {
+    /// The first line, with the fields of `ExportInfo` which don't need the
+    /// dependencies to be resolved. `root` is the root of the resolve graph.
+    Workspace {
+        version: u32,
+        workspace_members: Vec<PackageId>,
+        workspace_root: PathBuf,
+        target_directory: PathBuf,
+        metadata: Option<toml::Value>,
+        root: Option<PackageId>,
+    },
+    Package(SerializedPackage),
+    ResolveNode(MetadataResolveNode),
+    SourceChecksum(SourceChecksum),
 }
 
 #[derive(Serialize)]
@@ -103,11 +204,13 @@ impl From<&Dependency> for DepKindInfo {
     }
 }
 
-/// Builds the resolve graph as it will be displayed to the user.
+/// Builds the resolve graph as it will be displayed to the user, calling
+/// `on_node` with each package of the graph and its node.
 fn build_resolve_graph(
     ws: &Workspace<'_>,
     metadata_opts: &OutputMetadataOptions,
-) -> CargoResult<(Vec<SerializedPackage>, MetadataResolve)> {
+    on_node: &mut dyn FnMut(&Package, MetadataResolveNode) -> CargoResult<()>,
+) -> CargoResult<()> {
     // TODO: Without --filter-platform, features are being resolved for `host` only.
     // How should this work?
     let requested_kinds =
@@ -141,44 +244,34 @@ fn build_resolve_graph(
         .map(|pkg| (pkg.package_id(), Package::clone(pkg)))
         .collect();
 
-    // Start from the workspace roots, and recurse through filling out the
-    // map, filtering targets as necessary.
-    let mut node_map = BTreeMap::new();
+    // Start from the workspace roots, and recurse through the graph,
+    // filtering targets as necessary.
+    let mut visited = HashSet::new();
     for member_pkg in ws.members() {
         build_resolve_graph_r(
-            &mut node_map,
+            &mut visited,
             member_pkg.package_id(),
             &ws_resolve.targeted_resolve,
             &package_map,
             &target_data,
             &requested_kinds,
-        );
+            on_node,
+        )?;
     }
-    // Get a Vec of Packages.
-    let config = ws.config();
-    let actual_packages = package_map
-        .into_iter()
-        .filter_map(|(pkg_id, pkg)| node_map.get(&pkg_id).map(|_| pkg))
-        .map(|pkg| pkg.serialized(config))
-        .collect();
-
-    let mr = MetadataResolve {
-        nodes: node_map.into_iter().map(|(_pkg_id, node)| node).collect(),
-        root: ws.current_opt().map(|pkg| pkg.package_id()),
-    };
-    Ok((actual_packages, mr))
+    Ok(())
 }
 
 fn build_resolve_graph_r(
-    node_map: &mut BTreeMap<PackageId, MetadataResolveNode>,
+    visited: &mut HashSet<PackageId>,
     pkg_id: PackageId,
     resolve: &Resolve,
     package_map: &BTreeMap<PackageId, Package>,
     target_data: &RustcTargetData,
     requested_kinds: &[CompileKind],
-) {
-    if node_map.contains_key(&pkg_id) {
-        return;
+    on_node: &mut dyn FnMut(&Package, MetadataResolveNode) -> CargoResult<()>,
+) -> CargoResult<()> {
+    if !visited.insert(pkg_id) {
+        return Ok(());
     }
     // This normalizes the IDs so that they are consistent between the
     // `packages` array and the `resolve` map. This is a bit of a hack to
@@ -209,21 +302,29 @@ fn build_resolve_graph_r(
                 })
             }
         })
-        .filter_map(|(dep_id, deps)| {
-            let mut dep_kinds: Vec<_> = deps.iter().map(DepKindInfo::from).collect();
-            dep_kinds.sort();
-            package_map
+        .flat_map(|(dep_id, _deps)| {
+            let lib_target = package_map
                 .get(&dep_id)
-                .and_then(|pkg| pkg.targets().iter().find(|t| t.is_lib()))
-                .and_then(|lib_target| resolve.extern_crate_name(pkg_id, dep_id, lib_target).ok())
-                .map(|name| Dep {
+                .and_then(|pkg| pkg.targets().iter().find(|t| t.is_lib()));
+            // A package listed under several names gets an entry for each of
+            // them, with the dependency kinds using that name.
+            let names = match lib_target {
+                Some(lib_target) => resolve.extern_crate_names(pkg_id, dep_id, lib_target),
+                None => Vec::new(),
+            };
+            names.into_iter().map(move |(name, deps)| {
+                let mut dep_kinds: Vec<_> = deps.into_iter().map(DepKindInfo::from).collect();
+                dep_kinds.sort();
+                Dep {
                     name,
                     pkg: normalize_id(dep_id),
                     dep_kinds,
-                })
+                }
+            })
         })
         .collect();
-    let dumb_deps: Vec<PackageId> = deps.iter().map(|dep| normalize_id(dep.pkg)).collect();
+    let mut dumb_deps: Vec<PackageId> = deps.iter().map(|dep| normalize_id(dep.pkg)).collect();
+    dumb_deps.dedup();
     let to_visit = dumb_deps.clone();
     let node = MetadataResolveNode {
         id: normalize_id(pkg_id),
@@ -231,15 +332,53 @@ fn build_resolve_graph_r(
         deps,
         features,
     };
-    node_map.insert(pkg_id, node);
+    on_node(&package_map[&pkg_id], node)?;
     for dep_id in to_visit {
         build_resolve_graph_r(
-            node_map,
+            visited,
             dep_id,
             resolve,
             package_map,
             target_data,
             requested_kinds,
-        );
+            on_node,
+        )?;
+    }
+    Ok(())
+}
+
+/// Lists where the sources of the packages of the lock file are fetched
+/// from, leaving out the path packages.
+fn source_checksums(ws: &Workspace<'_>) -> CargoResult<Vec<SourceChecksum>> {
+    let (pkg_set, resolve) = ops::resolve_ws(ws)?;
+    let _lock = ws.config().acquire_package_cache_lock()?;
+    let mut sources = pkg_set.sources_mut();
+    let mut ids: Vec<PackageId> = resolve.iter().collect();
+    ids.sort();
+    let mut checksums = Vec::new();
+    for id in ids {
+        let source_id = id.source_id();
+        if source_id.is_git() {
+            checksums.push(SourceChecksum {
+                id,
+                url: Some(source_id.url().to_string()),
+                checksum: None,
+                rev: source_id.precise().map(|rev| rev.to_string()),
+                directory: None,
+            });
+        } else if source_id.is_registry() {
+            let url = match sources.get_mut(source_id) {
+                Some(source) => source.download_url(id)?,
+                None => None,
+            };
+            checksums.push(SourceChecksum {
+                id,
+                url,
+                checksum: resolve.checksums().get(&id).cloned().flatten(),
+                rev: None,
+                directory: Some(format!("{}-{}", id.name(), id.version())),
+            });
+        }
     }
+    Ok(checksums)
 }
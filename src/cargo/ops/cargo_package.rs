@@ -6,24 +6,29 @@ use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::Arc;
 
-use flate2::read::GzDecoder;
-use flate2::{Compression, GzBuilder};
+use ignore::gitignore::GitignoreBuilder;
+use ignore::Match;
 use log::debug;
-use tar::{Archive, Builder, EntryType, Header, HeaderMode};
+use serde::{Deserialize, Serialize};
+use tar::{Archive, Builder, EntryType, Header};
 
 use crate::core::compiler::{BuildConfig, CompileMode, DefaultExecutor, Executor};
+use crate::core::dependency::DepKind;
+use crate::core::{Dependency, Package, PackageId, PackageSet, Resolve, Source, SourceId};
 use crate::core::{Feature, Shell, Verbosity, Workspace};
-use crate::core::{Package, PackageId, PackageSet, Resolve, Source, SourceId};
 use crate::sources::PathSource;
+use crate::util::compression::{self, CrateCompression};
 use crate::util::errors::{CargoResult, CargoResultExt};
+use crate::util::interning::InternedString;
 use crate::util::paths;
 use crate::util::toml::TomlManifest;
-use crate::util::{self, restricted_names, Config, FileLock};
+use crate::util::{self, restricted_names, Config, FileLock, Sha256};
 use crate::{drop_println, ops};
 
 pub struct PackageOpts<'cfg> {
     pub config: &'cfg Config,
-    pub list: bool,
+    /// Print the files of the package in this format instead of packaging it.
+    pub list: Option<ListFormat>,
     pub check_metadata: bool,
     pub allow_dirty: bool,
     pub verify: bool,
@@ -32,10 +37,78 @@ pub struct PackageOpts<'cfg> {
     pub features: Vec<String>,
     pub all_features: bool,
     pub no_default_features: bool,
+    pub compression: CrateCompression,
+}
+
+/// How `cargo package --list` prints the files of the package.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ListFormat {
+    /// The path of each file, one per line.
+    Plain,
+    /// A JSON object with the size, checksum and origin of each file.
+    Json,
 }
 
 const VCS_INFO_FILE: &str = ".cargo_vcs_info.json";
 
+/// The contents of `.cargo_vcs_info.json`.
+#[derive(Serialize, Deserialize)]
+pub struct VcsInfo {
+    pub git: GitVcsInfo,
+}
+
+/// The state of the git checkout a package was created from.
+#[derive(Serialize, Deserialize)]
+pub struct GitVcsInfo {
+    /// The commit which was checked out.
+    pub sha1: String,
+    /// A tag pointing to the commit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// The URL of the `origin` remote, without credentials.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Whether uncommitted changes were packaged, with `--allow-dirty`.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub dirty: bool,
+    /// The packaged files with uncommitted changes, relative to the package.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dirty_files: Vec<String>,
+}
+
+impl VcsInfo {
+    /// Reads the VCS information of a package extracted from a `.crate`
+    /// file, if it was created from a git checkout.
+    pub fn load(pkg_root: &Path) -> Option<VcsInfo> {
+        let contents = fs::read(pkg_root.join(VCS_INFO_FILE)).ok()?;
+        serde_json::from_slice(&contents).ok()
+    }
+}
+
+fn is_false(b: &bool) -> bool {
+    !b
+}
+
+/// The `[package]` table of the configuration.
+#[derive(Default, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct PackageConfig {
+    /// Files larger than this, in bytes, are warned about.
+    large_file_size: Option<u64>,
+    /// Whether the warnings about the files of the package are errors.
+    deny_file_warnings: Option<bool>,
+}
+
+/// The default for `package.large-file-size`.
+const DEFAULT_LARGE_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Names of files which the system or editors leave in the working
+/// directory, besides editor swap and backup files.
+const JUNK_FILES: &[&str] = &[".DS_Store", "Thumbs.db", "desktop.ini"];
+
+/// Directories which usually hold files only needed to test the package.
+const FIXTURE_DIRS: &[&str] = &["tests", "benches", "fixtures", "testdata"];
+
 struct ArchiveFile {
     /// The relative path in the archive (not including the top-level package
     /// name directory).
@@ -63,12 +136,24 @@ enum GeneratedFile {
 }
 
 pub fn package(ws: &Workspace<'_>, opts: &PackageOpts<'_>) -> CargoResult<Option<FileLock>> {
-    if ws.root().join("Cargo.lock").exists() {
+    package_and_check(ws, opts, |_| Ok(()))
+}
+
+/// Like `package`, but calls `check` with the size of the `.crate` file once
+/// it is built, before verifying it, so that it can be rejected early.
+pub fn package_and_check(
+    ws: &Workspace<'_>,
+    opts: &PackageOpts<'_>,
+    check: impl FnOnce(u64) -> CargoResult<()>,
+) -> CargoResult<Option<FileLock>> {
+    let resolve = if ws.root().join("Cargo.lock").exists() {
         // Make sure the Cargo.lock is up-to-date and valid.
-        let _ = ops::resolve_ws(ws)?;
+        Some(ops::resolve_ws(ws)?.1)
         // If Cargo.lock does not exist, it will be generated by `build_lock`
         // below, and will be validated during the verification step.
-    }
+    } else {
+        None
+    };
     let pkg = ws.current()?;
     let config = ws.config();
 
@@ -89,26 +174,33 @@ pub fn package(ws: &Workspace<'_>, opts: &PackageOpts<'_>) -> CargoResult<Option
 
     // Check (git) repository state, getting the current commit hash if not
     // dirty.
-    let vcs_info = if !opts.allow_dirty {
-        // This will error if a dirty repo is found.
-        check_repo_state(pkg, &src_files, config)?
-            .map(|h| format!("{{\n  \"git\": {{\n    \"sha1\": \"{}\"\n  }}\n}}\n", h))
-    } else {
-        None
+    // This will error if a dirty repo is found, unless it's allowed.
+    let vcs_info = match check_repo_state(pkg, &src_files, config, opts.allow_dirty)? {
+        Some(vcs_info) => Some(serde_json::to_string_pretty(&vcs_info)? + "\n"),
+        None => None,
     };
 
     let ar_files = build_ar_list(ws, pkg, src_files, vcs_info)?;
 
-    if opts.list {
-        for ar_file in ar_files {
-            drop_println!(config, "{}", ar_file.rel_str);
+    match opts.list {
+        Some(ListFormat::Plain) => {
+            for ar_file in ar_files {
+                drop_println!(config, "{}", ar_file.rel_str);
+            }
+            return Ok(None);
+        }
+        Some(ListFormat::Json) => {
+            let listing = list_json(ws, pkg, ar_files, opts.compression)?;
+            config.shell().print_json(&listing);
+            return Ok(None);
         }
-        return Ok(None);
+        None => {}
     }
 
-    verify_dependencies(pkg)?;
+    check_files(pkg, &ar_files, config)?;
+    verify_dependencies(ws, pkg)?;
 
-    let filename = format!("{}-{}.crate", pkg.name(), pkg.version());
+    let filename = format!("{}-{}.crate", pkg.published_name(), pkg.version());
     let dir = ws.target_dir().join("package");
     let mut dst = {
         let tmp = format!(".{}", filename);
@@ -123,11 +215,32 @@ pub fn package(ws: &Workspace<'_>, opts: &PackageOpts<'_>) -> CargoResult<Option
         .shell()
         .status("Packaging", pkg.package_id().to_string())?;
     dst.file().set_len(0)?;
-    tar(ws, ar_files, dst.file(), &filename)
+    tar(ws, ar_files, dst.file(), &filename, opts.compression)
         .chain_err(|| anyhow::format_err!("failed to prepare local package for uploading"))?;
+    check(dst.file().metadata()?.len())?;
     if opts.verify {
         dst.seek(SeekFrom::Start(0))?;
-        run_verify(ws, &dst, opts).chain_err(|| "failed to verify package tarball")?
+        let result = run_verify(ws, &dst, opts);
+        if result.is_err() && !kept_dev_dependencies(pkg).is_empty() {
+            // The note is only a hint, so failing to give it mustn't hide the
+            // error of the verification.
+            let note = || -> CargoResult<()> {
+                let resolve = match resolve {
+                    Some(resolve) => resolve,
+                    None => {
+                        // Without a Cargo.lock, the package is resolved on its
+                        // own, in an ephemeral workspace which doesn't write one.
+                        let tmp_ws = Workspace::ephemeral(pkg.clone(), config, None, true)?;
+                        ops::resolve_ws(&tmp_ws)?.1
+                    }
+                };
+                note_dev_dependency_cycles(config, pkg, &resolve)
+            };
+            if let Err(e) = note() {
+                debug!("failed to look for dev-dependency cycles: {:?}", e);
+            }
+        }
+        result.chain_err(|| "failed to verify package tarball")?
     }
     dst.seek(SeekFrom::Start(0))?;
     {
@@ -139,6 +252,29 @@ pub fn package(ws: &Workspace<'_>, opts: &PackageOpts<'_>) -> CargoResult<Option
     Ok(Some(dst))
 }
 
+/// Returns the size of the `.crate` file of the current package compressed
+/// with `compression`, without writing or verifying it. `package_and_check`
+/// gives the size of the file it builds instead.
+pub fn crate_size(ws: &Workspace<'_>, compression: CrateCompression) -> CargoResult<u64> {
+    let pkg = ws.current()?;
+    let config = ws.config();
+    let mut src = PathSource::new(pkg.root(), pkg.package_id().source_id(), config);
+    src.update()?;
+    let src_files = src.list_files(pkg)?;
+
+    // The warnings and statuses are printed again when actually packaging.
+    let verbosity = config.shell().verbosity();
+    config.shell().set_verbosity(Verbosity::Quiet);
+    let result = build_ar_list(ws, pkg, src_files, None).and_then(|ar_files| {
+        let mut size = ByteCount(0);
+        let filename = format!("{}-{}.crate", pkg.published_name(), pkg.version());
+        tar(ws, ar_files, &mut size, &filename, compression)?;
+        Ok(size.0)
+    });
+    config.shell().set_verbosity(verbosity);
+    result
+}
+
 /// Builds list of files to archive.
 fn build_ar_list(
     ws: &Workspace<'_>,
@@ -199,50 +335,65 @@ fn build_ar_list(
             contents: FileContents::Generated(GeneratedFile::VcsInfo(vcs_info)),
         });
     }
-    if let Some(license_file) = &pkg.manifest().metadata().license_file {
-        let license_path = Path::new(license_file);
-        let abs_license_path = paths::normalize_path(&pkg.root().join(license_path));
-        if abs_license_path.exists() {
-            match abs_license_path.strip_prefix(&pkg.root()) {
-                Ok(rel_license_path) => {
-                    if !result.iter().any(|ar| ar.rel_path == rel_license_path) {
+    // The license file, and the readme which may be inherited from the
+    // workspace, can be outside of the package, and are then copied in.
+    let metadata = pkg.manifest().metadata();
+    for &(key, file) in &[
+        ("license-file", &metadata.license_file),
+        ("readme", &metadata.readme),
+    ] {
+        let file = match file {
+            Some(file) => file,
+            None => continue,
+        };
+        let file_path = Path::new(file);
+        let abs_file_path = paths::normalize_path(&pkg.root().join(file_path));
+        if abs_file_path.exists() {
+            match abs_file_path.strip_prefix(pkg.root()) {
+                Ok(rel_file_path) => {
+                    // The readme is packaged like any other file.
+                    if key == "license-file"
+                        && !result.iter().any(|ar| ar.rel_path == rel_file_path)
+                    {
                         result.push(ArchiveFile {
-                            rel_path: rel_license_path.to_path_buf(),
-                            rel_str: rel_license_path
+                            rel_path: rel_file_path.to_path_buf(),
+                            rel_str: rel_file_path
                                 .to_str()
                                 .expect("everything was utf8")
                                 .to_string(),
-                            contents: FileContents::OnDisk(abs_license_path),
+                            contents: FileContents::OnDisk(abs_file_path),
                         });
                     }
                 }
                 Err(_) => {
-                    // The license exists somewhere outside of the package.
-                    let license_name = license_path.file_name().unwrap();
+                    // The file exists somewhere outside of the package.
+                    let file_name = file_path.file_name().unwrap();
                     if result
                         .iter()
-                        .any(|ar| ar.rel_path.file_name().unwrap() == license_name)
+                        .any(|ar| ar.rel_path.file_name().unwrap() == file_name)
                     {
                         ws.config().shell().warn(&format!(
-                            "license-file `{}` appears to be a path outside of the package, \
+                            "{} `{}` appears to be a path outside of the package, \
                             but there is already a file named `{}` in the root of the package. \
                             The archived crate will contain the copy in the root of the package. \
-                            Update the license-file to point to the path relative \
+                            Update the {} to point to the path relative \
                             to the root of the package to remove this warning.",
-                            license_file,
-                            license_name.to_str().unwrap()
+                            key,
+                            file,
+                            file_name.to_str().unwrap(),
+                            key
                         ))?;
                     } else {
                         result.push(ArchiveFile {
-                            rel_path: PathBuf::from(license_name),
-                            rel_str: license_name.to_str().unwrap().to_string(),
-                            contents: FileContents::OnDisk(abs_license_path),
+                            rel_path: PathBuf::from(file_name),
+                            rel_str: file_name.to_str().unwrap().to_string(),
+                            contents: FileContents::OnDisk(abs_file_path),
                         });
                     }
                 }
             }
-        } else {
-            let rel_msg = if license_path.is_absolute() {
+        } else if key == "license-file" {
+            let rel_msg = if file_path.is_absolute() {
                 "".to_string()
             } else {
                 format!(" (relative to `{}`)", pkg.root().display())
@@ -251,7 +402,7 @@ fn build_ar_list(
                 "license-file `{}` does not appear to exist{}.\n\
                 Please update the license-file setting in the manifest at `{}`\n\
                 This may become a hard error in the future.",
-                license_path.display(),
+                file_path.display(),
                 rel_msg,
                 pkg.manifest_path().display()
             ))?;
@@ -334,10 +485,137 @@ fn check_metadata(pkg: &Package, config: &Config) -> CargoResult<()> {
     Ok(())
 }
 
+/// Checks for files which likely aren't meant to be in the package: large
+/// files, junk left by the system or editors, and symlinks pointing outside
+/// of the package. These are warnings, unless `package.deny-file-warnings`
+/// is set.
+fn check_files(pkg: &Package, ar_files: &[ArchiveFile], config: &Config) -> CargoResult<()> {
+    let package_config = config
+        .get::<Option<PackageConfig>>("package")?
+        .unwrap_or_default();
+    let large_file_size = package_config
+        .large_file_size
+        .unwrap_or(DEFAULT_LARGE_FILE_SIZE);
+    let root = pkg.root();
+    let canonical_root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+
+    let mut problems = Vec::new();
+    for ar_file in ar_files {
+        let disk_path = match &ar_file.contents {
+            FileContents::OnDisk(disk_path) => disk_path,
+            FileContents::Generated(_) => continue,
+        };
+        // The manifest is always packaged, and the license file may be
+        // outside of the package on purpose.
+        if ar_file.rel_str == "Cargo.toml.orig" || !disk_path.starts_with(root) {
+            continue;
+        }
+        let name = &ar_file.rel_str;
+
+        let file_name = ar_file
+            .rel_path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .unwrap_or_default();
+        if JUNK_FILES.contains(&file_name)
+            || file_name.ends_with(".swp")
+            || file_name.ends_with('~')
+        {
+            problems.push(format!(
+                "`{}` looks like a file left by the system or an editor",
+                name
+            ));
+        }
+
+        let metadata = fs::symlink_metadata(disk_path)
+            .chain_err(|| format!("failed to read metadata of `{}`", disk_path.display()))?;
+        if metadata.file_type().is_symlink() {
+            if let Ok(target) = fs::canonicalize(disk_path) {
+                if !target.starts_with(&canonical_root) {
+                    problems.push(format!(
+                        "`{}` is a symlink to `{}`, which is outside of the package",
+                        name,
+                        target.display()
+                    ));
+                }
+            }
+        }
+
+        let size = fs::metadata(disk_path).map(|m| m.len()).unwrap_or(0);
+        if size > large_file_size {
+            let is_fixture = ar_file
+                .rel_path
+                .parent()
+                .into_iter()
+                .flat_map(Path::components)
+                .any(|c| FIXTURE_DIRS.iter().any(|fixture| c.as_os_str() == *fixture));
+            if is_fixture {
+                problems.push(format!(
+                    "`{}` is {} bytes, and looks like a test fixture which users \
+                     of the package don't need",
+                    name, size
+                ));
+            } else {
+                problems.push(format!(
+                    "`{}` is {} bytes, more than `package.large-file-size` ({} bytes)",
+                    name, size, large_file_size
+                ));
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        return Ok(());
+    }
+    if package_config.deny_file_warnings == Some(true) {
+        let mut msg = String::from("the package contains unexpected files:");
+        for problem in problems {
+            msg.push_str("\n  - ");
+            msg.push_str(&problem);
+        }
+        msg.push_str(
+            "\nThese files can be left out with `package.exclude` in Cargo.toml, \
+             or allowed by unsetting `package.deny-file-warnings` in the configuration.",
+        );
+        anyhow::bail!(msg)
+    }
+    for problem in problems {
+        config.shell().warn(problem)?;
+    }
+    config
+        .shell()
+        .note("files can be left out of the package with `package.exclude` in Cargo.toml")?;
+    Ok(())
+}
+
+/// Returns the version requirement of `dep` in the packaged manifest, if it is
+/// a path dependency on a member of the workspace which doesn't specify one.
+pub fn member_version_req(ws: &Workspace<'_>, dep: &Dependency) -> Option<String> {
+    if !dep.source_id().is_path() || dep.specified_req() {
+        return None;
+    }
+    let path = dep.source_id().url().to_file_path().ok()?;
+    ws.path_dep_version_req(&path)
+}
+
+/// Returns the name `dep` is published under, if it is a path dependency on a
+/// member of the workspace with `publish-name` set.
+pub fn member_publish_name(ws: &Workspace<'_>, dep: &Dependency) -> Option<InternedString> {
+    if !dep.source_id().is_path() {
+        return None;
+    }
+    let path = dep.source_id().url().to_file_path().ok()?;
+    ws.path_dep_publish_name(&path)
+}
+
 // Checks that the package dependencies are safe to deploy.
-fn verify_dependencies(pkg: &Package) -> CargoResult<()> {
+fn verify_dependencies(ws: &Workspace<'_>, pkg: &Package) -> CargoResult<()> {
     for dep in pkg.dependencies() {
-        if dep.source_id().is_path() && !dep.specified_req() && dep.is_transitive() {
+        if dep.source_id().is_path()
+            && !dep.specified_req()
+            && dep.is_transitive()
+            && member_version_req(ws, dep).is_none()
+        {
             anyhow::bail!(
                 "all path dependencies must have a version specified \
                  when packaging.\ndependency `{}` does not specify \
@@ -357,7 +635,8 @@ fn check_repo_state(
     p: &Package,
     src_files: &[PathBuf],
     config: &Config,
-) -> CargoResult<Option<String>> {
+    allow_dirty: bool,
+) -> CargoResult<Option<VcsInfo>> {
     if let Ok(repo) = git2::Repository::discover(p.root()) {
         if let Some(workdir) = repo.workdir() {
             debug!("found a git repo at {:?}", workdir);
@@ -369,18 +648,20 @@ fn check_repo_state(
                         "found (git) Cargo.toml at {:?} in workdir {:?}",
                         path, workdir
                     );
-                    return git(p, src_files, &repo);
+                    return git(p, src_files, &repo, allow_dirty);
                 }
             }
-            config.shell().verbose(|shell| {
-                shell.warn(format!(
-                    "No (git) Cargo.toml found at `{}` in workdir `{}`",
-                    path.display(),
-                    workdir.display()
-                ))
-            })?;
+            if !allow_dirty {
+                config.shell().verbose(|shell| {
+                    shell.warn(format!(
+                        "No (git) Cargo.toml found at `{}` in workdir `{}`",
+                        path.display(),
+                        workdir.display()
+                    ))
+                })?;
+            }
         }
-    } else {
+    } else if !allow_dirty {
         config.shell().verbose(|shell| {
             shell.warn(format!("No (git) VCS found for `{}`", p.root().display()))
         })?;
@@ -394,7 +675,8 @@ fn check_repo_state(
         p: &Package,
         src_files: &[PathBuf],
         repo: &git2::Repository,
-    ) -> CargoResult<Option<String>> {
+        allow_dirty: bool,
+    ) -> CargoResult<Option<VcsInfo>> {
         let workdir = repo.workdir().unwrap();
 
         let mut sub_repos = Vec::new();
@@ -440,9 +722,22 @@ fn check_repo_state(
                     .to_string()
             })
             .collect::<Vec<_>>();
-        if dirty.is_empty() {
-            let rev_obj = repo.revparse_single("HEAD")?;
-            Ok(Some(rev_obj.id().to_string()))
+        if dirty.is_empty() || allow_dirty {
+            let head = match repo.revparse_single("HEAD") {
+                Ok(head) => head,
+                // Nothing was committed yet.
+                Err(_) if allow_dirty => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+            Ok(Some(VcsInfo {
+                git: GitVcsInfo {
+                    sha1: head.id().to_string(),
+                    tag: head_tag(repo, head.id()),
+                    url: origin_url(repo),
+                    dirty: !dirty.is_empty(),
+                    dirty_files: dirty,
+                },
+            }))
         } else {
             anyhow::bail!(
                 "{} files in the working directory contain changes that were \
@@ -454,6 +749,38 @@ fn check_repo_state(
         }
     }
 
+    /// Returns the first tag, by name, pointing to the commit `head`.
+    fn head_tag(repo: &git2::Repository, head: git2::Oid) -> Option<String> {
+        let names = repo.tag_names(None).ok()?;
+        let mut names = names.iter().flatten().collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+            .into_iter()
+            .find(|name| {
+                let commit = repo
+                    .revparse_single(&format!("refs/tags/{}", name))
+                    .and_then(|obj| obj.peel_to_commit());
+                matches!(commit, Ok(commit) if commit.id() == head)
+            })
+            .map(|name| name.to_string())
+    }
+
+    /// Returns the URL of the `origin` remote, without the user name and
+    /// password it may contain.
+    fn origin_url(repo: &git2::Repository) -> Option<String> {
+        let remote = repo.find_remote("origin").ok()?;
+        let url = remote.url()?;
+        match url::Url::parse(url) {
+            Ok(mut parsed) if parsed.has_host() => {
+                let _ = parsed.set_username("");
+                let _ = parsed.set_password(None);
+                Some(parsed.to_string())
+            }
+            // Like `git@github.com:rust-lang/cargo.git`.
+            _ => Some(url.to_string()),
+        }
+    }
+
     /// Helper to recursively open all submodules.
     fn open_submodules(
         repo: &git2::Repository,
@@ -471,25 +798,144 @@ fn check_repo_state(
     }
 }
 
+/// The listing of `cargo package --list --message-format json`.
+#[derive(Serialize)]
+struct PackageListing {
+    name: InternedString,
+    version: String,
+    files: Vec<ListedFile>,
+    /// The total size of the files.
+    size: u64,
+    /// The size of the `.crate` file, which is exact unless the registry
+    /// accepts another compression when publishing.
+    compressed_size: u64,
+}
+
+#[derive(Serialize)]
+struct ListedFile {
+    path: String,
+    size: u64,
+    sha256: String,
+    /// Whether Cargo generates the file rather than copying it.
+    generated: bool,
+    /// The `include` pattern which selected the file, or the negated
+    /// `exclude` pattern which kept it in the package.
+    rule: Option<String>,
+}
+
+fn list_json(
+    ws: &Workspace<'_>,
+    pkg: &Package,
+    ar_files: Vec<ArchiveFile>,
+    compression: CrateCompression,
+) -> CargoResult<PackageListing> {
+    let root = pkg.root();
+    let include = pkg.manifest().include();
+    let mut builder = GitignoreBuilder::new(root);
+    let patterns = if include.is_empty() {
+        pkg.manifest().exclude()
+    } else {
+        include
+    };
+    for pattern in patterns {
+        builder.add_line(None, pattern)?;
+    }
+    let rules = builder.build()?;
+
+    let mut files = Vec::new();
+    for ar_file in ar_files.iter() {
+        let (contents, rule) = match &ar_file.contents {
+            FileContents::OnDisk(disk_path) => {
+                let contents = paths::read_bytes(disk_path)?;
+                // `Cargo.toml` is always included, and the license file may
+                // be copied from outside of the package.
+                let rule = match disk_path.strip_prefix(root) {
+                    Ok(rel_path) if rel_path != Path::new("Cargo.toml") => {
+                        match rules.matched_path_or_any_parents(rel_path, false) {
+                            Match::Ignore(glob) if !include.is_empty() => {
+                                Some(glob.original().to_string())
+                            }
+                            Match::Whitelist(glob) if include.is_empty() => {
+                                Some(glob.original().to_string())
+                            }
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+                (contents, rule)
+            }
+            FileContents::Generated(generated_kind) => (
+                generated_contents(ws, pkg, generated_kind)?.into_bytes(),
+                None,
+            ),
+        };
+        files.push(ListedFile {
+            path: ar_file.rel_str.clone(),
+            size: contents.len() as u64,
+            sha256: Sha256::new().update(&contents).finish_hex(),
+            generated: matches!(ar_file.contents, FileContents::Generated(_)),
+            rule,
+        });
+    }
+
+    let mut compressed = ByteCount(0);
+    let filename = format!("{}-{}.crate", pkg.published_name(), pkg.version());
+    tar(ws, ar_files, &mut compressed, &filename, compression)?;
+
+    Ok(PackageListing {
+        name: pkg.name(),
+        version: pkg.version().to_string(),
+        size: files.iter().map(|file| file.size).sum(),
+        files,
+        compressed_size: compressed.0,
+    })
+}
+
+/// A writer which only counts the bytes written to it.
+struct ByteCount(u64);
+
+impl Write for ByteCount {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn generated_contents(
+    ws: &Workspace<'_>,
+    pkg: &Package,
+    generated_kind: &GeneratedFile,
+) -> CargoResult<String> {
+    match generated_kind {
+        GeneratedFile::Manifest => pkg.to_registry_toml(ws),
+        GeneratedFile::Lockfile => build_lock(ws),
+        GeneratedFile::VcsInfo(s) => Ok(s.clone()),
+    }
+}
+
 fn tar(
     ws: &Workspace<'_>,
     ar_files: Vec<ArchiveFile>,
-    dst: &File,
+    dst: impl Write,
     filename: &str,
+    compression: CrateCompression,
 ) -> CargoResult<()> {
     // Prepare the encoder and its header.
-    let filename = Path::new(filename);
-    let encoder = GzBuilder::new()
-        .filename(util::path2bytes(filename)?)
-        .write(dst, Compression::best());
+    let encoder = compression.encoder(dst, Path::new(filename))?;
 
     // Put all package files into a compressed archive.
     let mut ar = Builder::new(encoder);
     let pkg = ws.current()?;
     let config = ws.config();
 
-    let base_name = format!("{}-{}", pkg.name(), pkg.version());
+    let base_name = format!("{}-{}", pkg.published_name(), pkg.version());
     let base_path = Path::new(&base_name);
+    let mtime = archive_mtime(config)?;
     for ar_file in ar_files {
         let ArchiveFile {
             rel_path,
@@ -500,7 +946,15 @@ fn tar(
         config
             .shell()
             .verbose(|shell| shell.status("Archiving", &rel_str))?;
+        // Only the contents of the files and whether they are executable are
+        // kept, so the same sources always produce the same archive.
         let mut header = Header::new_gnu();
+        header.set_entry_type(EntryType::file());
+        header.set_mtime(mtime);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_username("")?;
+        header.set_groupname("")?;
         match contents {
             FileContents::OnDisk(disk_path) => {
                 let mut file = File::open(&disk_path).chain_err(|| {
@@ -509,7 +963,12 @@ fn tar(
                 let metadata = file.metadata().chain_err(|| {
                     format!("could not learn metadata for: `{}`", disk_path.display())
                 })?;
-                header.set_metadata_in_mode(&metadata, HeaderMode::Deterministic);
+                header.set_mode(if is_executable(&metadata) {
+                    0o755
+                } else {
+                    0o644
+                });
+                header.set_size(metadata.len());
                 header.set_cksum();
                 ar.append_data(&mut header, &ar_path, &mut file)
                     .chain_err(|| {
@@ -517,12 +976,7 @@ fn tar(
                     })?;
             }
             FileContents::Generated(generated_kind) => {
-                let contents = match generated_kind {
-                    GeneratedFile::Manifest => pkg.to_registry_toml(ws)?,
-                    GeneratedFile::Lockfile => build_lock(ws)?,
-                    GeneratedFile::VcsInfo(s) => s,
-                };
-                header.set_entry_type(EntryType::file());
+                let contents = generated_contents(ws, pkg, &generated_kind)?;
                 header.set_mode(0o644);
                 header.set_size(contents.len() as u64);
                 header.set_cksum();
@@ -537,6 +991,36 @@ fn tar(
     Ok(())
 }
 
+/// The modification time of the files in the archive, which is
+/// `SOURCE_DATE_EPOCH` if it's set.
+///
+/// It isn't 0 by default, since some tools treat such files as invalid.
+fn archive_mtime(config: &Config) -> CargoResult<u64> {
+    const DEFAULT_MTIME: u64 = 1153704088;
+
+    match config.get_env("SOURCE_DATE_EPOCH") {
+        Some(epoch) => epoch.trim().parse().map_err(|_| {
+            anyhow::format_err!(
+                "`SOURCE_DATE_EPOCH` must be a number of seconds since the Unix epoch, \
+                 but it is `{}`",
+                epoch
+            )
+        }),
+        None => Ok(DEFAULT_MTIME),
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::prelude::*;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
 /// Generate warnings when packaging Cargo.lock, and the resolve have changed.
 fn compare_resolve(
     config: &Config,
@@ -645,16 +1129,59 @@ fn check_yanked(config: &Config, pkg_set: &PackageSet<'_>, resolve: &Resolve) ->
     Ok(())
 }
 
+/// Returns the path dev-dependencies of `pkg` with a `version`, which are
+/// kept in the published manifest.
+fn kept_dev_dependencies(pkg: &Package) -> Vec<InternedString> {
+    pkg.dependencies()
+        .iter()
+        .filter(|dep| dep.kind() == DepKind::Development)
+        .filter(|dep| dep.source_id().is_path() && dep.specified_req())
+        .map(|dep| dep.package_name())
+        .collect()
+}
+
+/// Explains dev-dependencies which depend on the package being verified,
+/// with the `resolve` of its workspace.
+///
+/// A path dev-dependency with a `version` is kept in the published manifest,
+/// so the packaged crate looks for it in the registry. If that dev-dependency
+/// depends on the package itself, it can't have been published yet, which
+/// otherwise only shows up as a confusing "no matching package" error.
+fn note_dev_dependency_cycles(
+    config: &Config,
+    pkg: &Package,
+    resolve: &Resolve,
+) -> CargoResult<()> {
+    let kept_dev_deps = kept_dev_dependencies(pkg);
+    for cycle in resolve.dev_dependency_cycles(pkg.package_id()) {
+        let dev_dep = cycle[1].name();
+        if !kept_dev_deps.contains(&dev_dep) {
+            continue;
+        }
+        config.shell().note(format!(
+            "dev-dependency `{dev}` of `{name}` depends on `{name}` itself:\n  {path}\n\
+             `{dev}` can't be published before `{name}`, so it can't be found in \
+             the registry when verifying `{name}`.\n\
+             Remove the `version` of the `{dev}` dev-dependency to leave it out of \
+             the published package.",
+            dev = dev_dep,
+            name = pkg.name(),
+            path = Resolve::display_path(&cycle),
+        ))?;
+    }
+    Ok(())
+}
+
 fn run_verify(ws: &Workspace<'_>, tar: &FileLock, opts: &PackageOpts<'_>) -> CargoResult<()> {
     let config = ws.config();
     let pkg = ws.current()?;
 
     config.shell().status("Verifying", pkg)?;
 
-    let f = GzDecoder::new(tar.file());
+    let f = compression::decoder(tar.file())?;
     let dst = tar
         .parent()
-        .join(&format!("{}-{}", pkg.name(), pkg.version()));
+        .join(format!("{}-{}", pkg.published_name(), pkg.version()));
     if dst.exists() {
         paths::remove_dir_all(&dst)?;
     }
@@ -0,0 +1,251 @@
+//! Implementation of `cargo packaging-info`.
+//!
+//! The output is versioned with `VERSION`, which has to be bumped whenever a
+//! field is removed or changes meaning. The format is documented in
+//! `cargo-packaging-info.md`.
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::core::compiler::{
+    CompileKind, CompileMode, CrateType, FileFlavor, RustcTargetData, TargetInfo,
+};
+use crate::core::resolver::{ForceAllTargets, HasDevUnits, ResolveOpts};
+use crate::core::{Package, PackageId, Target, Workspace};
+use crate::ops::{self, Packages};
+use crate::util::CargoResult;
+
+const VERSION: u32 = 1;
+
+/// The prefixes of the names of the files in the root of a package which
+/// distributions install along with it.
+const LICENSE_FILE_PREFIXES: &[&str] = &["license", "licence", "copying", "notice"];
+
+pub struct PackagingInfoOptions {
+    pub features: Vec<String>,
+    pub all_features: bool,
+    pub no_default_features: bool,
+    /// The packages to report.
+    pub packages: Packages,
+    /// The target triple the packages are built for, the host if `None`.
+    pub target: Option<String>,
+}
+
+#[derive(Serialize)]
+struct PackagingInfo {
+    version: u32,
+    target: String,
+    packages: Vec<PackageInfo>,
+}
+
+#[derive(Serialize)]
+struct PackageInfo {
+    id: PackageId,
+    name: String,
+    version: String,
+    description: Option<String>,
+    homepage: Option<String>,
+    repository: Option<String>,
+    license: Option<String>,
+    license_files: Vec<PathBuf>,
+    binaries: Vec<Binary>,
+    cdylibs: Vec<Cdylib>,
+    dependencies: Vec<DependencyInfo>,
+}
+
+#[derive(Serialize)]
+struct Binary {
+    name: String,
+    install_path: String,
+}
+
+#[derive(Serialize)]
+struct Cdylib {
+    name: String,
+    file_name: String,
+    install_path: String,
+    soname: Option<String>,
+    soversion: String,
+}
+
+#[derive(Serialize)]
+struct DependencyInfo {
+    id: PackageId,
+    name: String,
+    version: String,
+    source: String,
+    license: Option<String>,
+    license_file: Option<String>,
+}
+
+/// Prints the information distributions need to package the selected
+/// packages of the workspace, as JSON.
+pub fn packaging_info(ws: &Workspace<'_>, opts: &PackagingInfoOptions) -> CargoResult<()> {
+    let config = ws.config();
+    let requested_targets: Vec<String> = opts.target.iter().cloned().collect();
+    let requested_kinds = CompileKind::from_requested_targets(config, &requested_targets)?;
+    let kind = requested_kinds[0];
+    let target_data = RustcTargetData::new(ws, &requested_kinds)?;
+    let specs = opts.packages.to_package_id_specs(ws)?;
+    let resolve_opts = ResolveOpts::new(
+        /*dev_deps*/ false,
+        &opts.features,
+        opts.all_features,
+        !opts.no_default_features,
+    );
+    let ws_resolve = ops::resolve_ws_with_opts(
+        ws,
+        &target_data,
+        &requested_kinds,
+        &resolve_opts,
+        &specs,
+        HasDevUnits::No,
+        ForceAllTargets::No,
+    )?;
+    let resolve = &ws_resolve.targeted_resolve;
+    // The features of the packages are only known once all of them are
+    // downloaded.
+    let pkgs = ws_resolve.pkg_set.get_many(resolve.iter())?;
+    let triple = target_data.short_name(&kind).to_string();
+
+    let mut info = PackagingInfo {
+        version: VERSION,
+        target: triple.clone(),
+        packages: Vec::new(),
+    };
+    let members = ws.members_with_features(&specs, &resolve_opts.features)?;
+    for (member, _) in members {
+        let id = member.package_id();
+        let features = ops::resolve_all_features(
+            resolve,
+            &ws_resolve.resolved_features,
+            &ws_resolve.pkg_set,
+            id,
+        );
+        let info_for_kind = target_data.info(kind);
+        let mut binaries = Vec::new();
+        let mut cdylibs = Vec::new();
+        for target in member.targets() {
+            let enabled = match target.required_features() {
+                Some(rf) => rf.iter().all(|f| features.contains(f)),
+                None => true,
+            };
+            if !enabled {
+                continue;
+            }
+            if target.is_bin() {
+                for file_name in file_names(info_for_kind, target, &triple, CrateType::Bin)? {
+                    binaries.push(Binary {
+                        name: target.name().to_string(),
+                        install_path: format!("bin/{}", file_name),
+                    });
+                }
+            } else if target.is_cdylib() {
+                for file_name in file_names(info_for_kind, target, &triple, CrateType::Cdylib)? {
+                    let soversion = soversion(member);
+                    // Only ELF libraries have a soname.
+                    let soname = if file_name.ends_with(".so") {
+                        Some(format!("{}.{}", file_name, soversion))
+                    } else {
+                        None
+                    };
+                    cdylibs.push(Cdylib {
+                        name: target.name().to_string(),
+                        install_path: format!("lib/{}", file_name),
+                        file_name,
+                        soname,
+                        soversion,
+                    });
+                }
+            }
+        }
+
+        let mut deps = BTreeSet::new();
+        // The dependencies of the build scripts and proc-macros are built for
+        // the host, which `collect_used_deps` always includes.
+        ops::collect_used_deps(resolve, id, false, Some(&[kind]), &target_data, &mut deps);
+        deps.remove(&id);
+        let dependencies = pkgs
+            .iter()
+            .filter(|pkg| deps.contains(&pkg.package_id()))
+            .map(|pkg| {
+                let metadata = pkg.manifest().metadata();
+                DependencyInfo {
+                    id: pkg.package_id(),
+                    name: pkg.name().to_string(),
+                    version: pkg.version().to_string(),
+                    source: pkg.package_id().source_id().as_url().to_string(),
+                    license: metadata.license.clone(),
+                    license_file: metadata.license_file.clone(),
+                }
+            })
+            .collect();
+
+        let metadata = member.manifest().metadata();
+        info.packages.push(PackageInfo {
+            id,
+            name: member.name().to_string(),
+            version: member.version().to_string(),
+            description: metadata.description.clone(),
+            homepage: metadata.homepage.clone(),
+            repository: metadata.repository.clone(),
+            license: metadata.license.clone(),
+            license_files: license_files(member)?,
+            binaries,
+            cdylibs,
+            dependencies,
+        });
+    }
+    config.shell().print_json(&info);
+    Ok(())
+}
+
+/// Returns the names of the files of `crate_type` produced for `target`.
+fn file_names(
+    info: &TargetInfo,
+    target: &Target,
+    triple: &str,
+    crate_type: CrateType,
+) -> CargoResult<Vec<String>> {
+    let (file_types, _) = info.rustc_outputs(CompileMode::Build, target.kind(), triple)?;
+    Ok(file_types
+        .iter()
+        .filter(|ft| ft.flavor == FileFlavor::Normal && ft.crate_type.as_ref() == Some(&crate_type))
+        .map(|ft| ft.uplift_filename(target))
+        .collect())
+}
+
+/// Returns the part of the version of `pkg` which only changes with
+/// incompatible releases, by the SemVer rules of Cargo: the major version, or
+/// the minor version prefixed with `0.` before 1.0.0.
+fn soversion(pkg: &Package) -> String {
+    let version = pkg.version();
+    if version.major > 0 {
+        version.major.to_string()
+    } else {
+        format!("0.{}", version.minor)
+    }
+}
+
+/// Returns the license files of `pkg`: its `license-file`, and the files in
+/// its root with names like `LICENSE`, `COPYING` or `NOTICE`.
+fn license_files(pkg: &Package) -> CargoResult<Vec<PathBuf>> {
+    let mut files = BTreeSet::new();
+    if let Some(file) = &pkg.manifest().metadata().license_file {
+        files.insert(pkg.root().join(file));
+    }
+    for entry in std::fs::read_dir(pkg.root())? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_lowercase();
+        if entry.file_type()?.is_file()
+            && LICENSE_FILE_PREFIXES
+                .iter()
+                .any(|prefix| name.starts_with(prefix))
+        {
+            files.insert(entry.path());
+        }
+    }
+    Ok(files.into_iter().collect())
+}
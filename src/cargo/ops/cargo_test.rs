@@ -1,16 +1,154 @@
+use std::collections::{BTreeMap, HashMap};
+use std::env;
 use std::ffi::OsString;
+use std::fmt::Write;
+use std::io::{self, BufRead, BufReader, Read};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::core::compiler::{Compilation, CompileKind, Doctest};
-use crate::core::shell::Verbosity;
-use crate::core::Workspace;
+use anyhow::bail;
+use serde_json::value::RawValue;
+
+use crate::core::compiler::{self, Compilation, CompileKind, Doctest, DoctestOutput};
+use crate::core::nightly_features_allowed;
+use crate::core::shell::{ColorChoice, Verbosity};
+use crate::core::{Package, Target, TargetKind, Workspace};
 use crate::ops;
-use crate::util::errors::CargoResult;
-use crate::util::{add_path_args, CargoTestError, Config, ProcessError, Test};
+use crate::sources::PathSource;
+use crate::util::errors::{process_error, CargoResult, CargoResultExt};
+use crate::util::machine_message::{self, Message};
+use crate::util::{
+    hash_u64, paths, process, to_hex, CargoTestError, Config, ProcessBuilder, ProcessError, Test,
+};
 
 pub struct TestOptions {
     pub compile_opts: ops::CompileOptions,
     pub no_run: bool,
     pub no_fail_fast: bool,
+    /// The format of the results of the tests.
+    pub format: TestFormat,
+    /// The longest a single test may run before its test binary is killed.
+    pub timeout: Option<Duration>,
+    /// The shard of the tests to run, set with `--partition`.
+    pub partition: Option<TestPartition>,
+    /// How many times to run the failed tests of a test harness again, with
+    /// `--retries`.
+    pub retries: u32,
+    /// Whether to run the test binaries which passed with the same inputs
+    /// anyway, with `--no-cache`.
+    pub no_cache: bool,
+    /// Whether to measure the code coverage of the tests, with `--coverage`.
+    pub coverage: bool,
+}
+
+/// A shard of the tests of all the test targets, set with `--partition`.
+#[derive(Clone, Copy, Debug)]
+pub struct TestPartition {
+    /// The shard to run, starting from 1.
+    pub index: usize,
+    /// The number of shards.
+    pub count: usize,
+    pub strategy: PartitionStrategy,
+}
+
+/// How the tests are split into shards, set with `--partition-strategy`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PartitionStrategy {
+    /// By the hash of the name of each test.
+    Hash,
+    /// So that each shard takes about the same time, according to the
+    /// durations of the tests recorded in previous runs.
+    Time,
+}
+
+impl TestPartition {
+    /// Parses the value of `--partition`, like `2/4`.
+    pub fn parse(value: &str, strategy: PartitionStrategy) -> CargoResult<TestPartition> {
+        let mut parts = value.splitn(2, '/');
+        let index = parts.next().and_then(|n| n.parse::<usize>().ok());
+        let count = parts.next().and_then(|n| n.parse::<usize>().ok());
+        match (index, count) {
+            (Some(index), Some(count)) if index >= 1 && index <= count => Ok(TestPartition {
+                index,
+                count,
+                strategy,
+            }),
+            _ => bail!(
+                "invalid partition `{}`, expected a shard and a number of shards \
+                 like `2/4`, with the shard between 1 and the number of shards",
+                value
+            ),
+        }
+    }
+}
+
+impl TestOptions {
+    /// Whether the test harnesses are run with JSON output, which Cargo
+    /// handles itself.
+    fn json_events(&self) -> bool {
+        self.format != TestFormat::Human
+            || self.timeout.is_some()
+            || self.partition.is_some()
+            || self.retries > 0
+    }
+
+    /// Arguments for libtest to print the events Cargo needs.
+    fn harness_args(&self) -> &'static [&'static str] {
+        if self.json_events() {
+            &["-Zunstable-options", "--format=json", "--report-time"]
+        } else {
+            &[]
+        }
+    }
+}
+
+/// The format of the results of the tests, set with `--format`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TestFormat {
+    /// The output of each test harness, as-is.
+    Human,
+    /// The JSON events of all the test harnesses as a single stream on
+    /// stdout, each one with the package and target it comes from.
+    Json,
+    /// A single JUnit XML report for all the test harnesses, written to
+    /// `junit.xml` in the target directory.
+    Junit,
+}
+
+/// Returns the longest a single test may run, from `--timeout` or else the
+/// `test.timeout` config value, which is only used with `-Z unstable-options`.
+pub fn test_timeout(config: &Config, cli_timeout: Option<&str>) -> CargoResult<Option<Duration>> {
+    if let Some(timeout) = cli_timeout {
+        return parse_test_timeout(timeout).map(Some);
+    }
+    if !config.cli_unstable().unstable_options {
+        return Ok(None);
+    }
+    match config.get_string("test.timeout")? {
+        Some(timeout) => parse_test_timeout(&timeout.val)
+            .chain_err(|| {
+                format!(
+                    "invalid configuration for key `test.timeout` in {}",
+                    timeout.definition
+                )
+            })
+            .map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Parses a timeout like `60s`, `5m` or `1h 30m`, with the syntax of
+/// `humantime`.
+fn parse_test_timeout(value: &str) -> CargoResult<Duration> {
+    match humantime::parse_duration(value) {
+        Ok(timeout) if timeout > Duration::from_secs(0) => Ok(timeout),
+        Ok(_) => bail!("invalid test timeout `{}`, it must not be zero", value),
+        Err(e) => bail!("invalid test timeout `{}`: {}", value, e),
+    }
 }
 
 pub fn run_tests(
@@ -23,16 +161,59 @@ pub fn run_tests(
     if options.no_run {
         return Ok(None);
     }
-    let (test, mut errors) = run_unit_tests(ws.config(), options, test_args, &compilation)?;
+    let coverage = Coverage::new(ws, options, &compilation)?;
+    let coverage = coverage.as_ref();
+    let plan = match options.partition {
+        Some(partition) => Some(plan_partition(
+            ws,
+            partition,
+            test_args,
+            &compilation,
+            coverage,
+        )?),
+        None => None,
+    };
+    let plan = plan.as_ref();
+    let mut events = TestEvents::new(ws, options, "test", test_args);
+    let mut cache = TestCache::load(ws, options);
+    let (test, mut errors) = run_unit_tests(
+        ws.config(),
+        options,
+        test_args,
+        &compilation,
+        plan,
+        cache.as_mut(),
+        coverage,
+        &mut events,
+    )?;
+    if let Some(cache) = &cache {
+        cache.save()?;
+    }
 
     // If we have an error and want to fail fast, then return.
     if !errors.is_empty() && !options.no_fail_fast {
+        events.finish(ws, false)?;
+        if let Some(coverage) = coverage {
+            coverage.report(ws.config(), &compilation)?;
+        }
         return Ok(Some(CargoTestError::new(test, errors)));
     }
 
-    let (doctest, docerrors) = run_doc_tests(ws, options, test_args, &compilation)?;
+    let (doctest, docerrors) = run_doc_tests(
+        ws,
+        options,
+        test_args,
+        &compilation,
+        plan,
+        coverage,
+        &mut events,
+    )?;
     let test = if docerrors.is_empty() { test } else { doctest };
     errors.extend(docerrors);
+    events.finish(ws, errors.is_empty())?;
+    if let Some(coverage) = coverage {
+        coverage.report(ws.config(), &compilation)?;
+    }
     if errors.is_empty() {
         Ok(None)
     } else {
@@ -51,10 +232,21 @@ pub fn run_benches(
         return Ok(None);
     }
 
+    let mut events = TestEvents::new(ws, options, "bench", args);
     let mut args = args.to_vec();
     args.push("--bench");
 
-    let (test, errors) = run_unit_tests(ws.config(), options, &args, &compilation)?;
+    let (test, errors) = run_unit_tests(
+        ws.config(),
+        options,
+        &args,
+        &compilation,
+        None,
+        None,
+        None,
+        &mut events,
+    )?;
+    events.finish(ws, errors.is_empty())?;
 
     match errors.len() {
         0 => Ok(None),
@@ -62,6 +254,172 @@ pub fn run_benches(
     }
 }
 
+/// Returns the arguments for the test harness of the doc tests, if they run
+/// during the build with `-Z parallel-doctests`, instead of after the other
+/// tests.
+pub fn parallel_doctest_args(
+    config: &Config,
+    options: &TestOptions,
+    test_args: &[&str],
+) -> Option<Vec<String>> {
+    // The doc tests of a shard are only known once everything is built, and
+    // Cargo needs to watch the harness as it runs for a timeout, and to have
+    // removed the previous coverage data.
+    if !config.cli_unstable().parallel_doctests
+        || options.no_run
+        || options.partition.is_some()
+        || options.timeout.is_some()
+        || options.coverage
+    {
+        return None;
+    }
+    let mut args: Vec<String> = test_args
+        .iter()
+        .chain(options.harness_args())
+        .map(|arg| arg.to_string())
+        .collect();
+    // The output is shown once the build is done, rather than on a terminal.
+    if !options.json_events() {
+        let color = harness_color_args(config, test_args);
+        args.extend(color.iter().map(|arg| arg.to_string()));
+    }
+    Some(args)
+}
+
+/// Lists the tests of all the test targets, with `--list`, as a single JSON
+/// message with `--format json`, or otherwise by target.
+pub fn list_tests(
+    ws: &Workspace<'_>,
+    options: &TestOptions,
+    test_args: &[&str],
+) -> CargoResult<()> {
+    let config = ws.config();
+    let compilation = compile_tests(ws, options)?;
+
+    let mut targets = Vec::new();
+    for (unit, exe) in &compilation.tests {
+        let tests = if unit.target.harness() {
+            let mut cmd = compilation.target_process(exe, unit.kind, &unit.pkg)?;
+            cmd.args(test_args);
+            Some(list_harness_tests(config, &cmd, false)?)
+        } else {
+            None
+        };
+        let target = machine_message::TestListTarget {
+            target: &unit.target,
+            executable: Some(exe),
+            doctest: false,
+            tests,
+        };
+        targets.push((&unit.pkg, target));
+    }
+    for doctest in doc_tests(config, &compilation)? {
+        let mut p = doc_test_process(ws, &compilation, doctest)?;
+        for arg in test_args {
+            p.arg("--test-args").arg(arg);
+        }
+        let target = machine_message::TestListTarget {
+            target: &doctest.unit.target,
+            executable: None,
+            doctest: true,
+            tests: Some(list_harness_tests(config, &p, true)?),
+        };
+        targets.push((&doctest.unit.pkg, target));
+    }
+
+    if options.format == TestFormat::Json {
+        let mut packages: Vec<machine_message::TestListPackage<'_>> = Vec::new();
+        for (pkg, target) in targets {
+            let package_id = pkg.package_id();
+            match packages.iter_mut().find(|p| p.package_id == package_id) {
+                Some(package) => package.targets.push(target),
+                None => packages.push(machine_message::TestListPackage {
+                    package_id,
+                    targets: vec![target],
+                }),
+            }
+        }
+        let msg = machine_message::TestList { packages }.to_json_string();
+        crate::drop_println!(config, "{}", msg);
+        return Ok(());
+    }
+
+    for (pkg, target) in &targets {
+        let kind = if target.doctest {
+            "doctest"
+        } else {
+            target.target.kind().description()
+        };
+        crate::drop_println!(
+            config,
+            "{} {} `{}`:",
+            pkg.name(),
+            kind,
+            target.target.name()
+        );
+        match &target.tests {
+            Some(tests) => {
+                for test in tests {
+                    let ignored = if test.ignored { " (ignored)" } else { "" };
+                    crate::drop_println!(config, "    {}{}", test.name, ignored);
+                }
+            }
+            None => crate::drop_println!(config, "    (no test harness)"),
+        }
+    }
+    Ok(())
+}
+
+/// Lists the tests of a test harness, by running `cmd` with `--list`, once
+/// more for the ignored tests. The options for the harness are passed with
+/// `--test-args` to rustdoc.
+fn list_harness_tests(
+    config: &Config,
+    cmd: &ProcessBuilder,
+    rustdoc: bool,
+) -> CargoResult<Vec<machine_message::ListedTest>> {
+    let list = |ignored: bool| -> CargoResult<Vec<String>> {
+        let mut cmd = cmd.clone();
+        let mut args = vec!["--list", "--format", "terse"];
+        if ignored {
+            args.push("--ignored");
+        }
+        for arg in args {
+            if rustdoc {
+                cmd.arg("--test-args");
+            }
+            cmd.arg(arg);
+        }
+        config
+            .shell()
+            .verbose(|shell| shell.status("Listing", &cmd))?;
+        let output = cmd.exec_with_output()?;
+        Ok(parse_test_list(&output.stdout))
+    };
+    let ignored = list(true)?;
+    let tests = list(false)?
+        .into_iter()
+        .map(|name| machine_message::ListedTest {
+            ignored: ignored.contains(&name),
+            name,
+        })
+        .collect();
+    Ok(tests)
+}
+
+/// Returns the names of the tests and benchmarks in the output of a test
+/// harness run with `--list --format terse`.
+fn parse_test_list(stdout: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(stdout)
+        .lines()
+        .filter_map(|line| {
+            line.strip_suffix(": test")
+                .or_else(|| line.strip_suffix(": bench"))
+        })
+        .map(|name| name.to_string())
+        .collect()
+}
+
 fn compile_tests<'a>(ws: &Workspace<'a>, options: &TestOptions) -> CargoResult<Compilation<'a>> {
     let mut compilation = ops::compile(ws, &options.compile_opts)?;
     compilation.tests.sort();
@@ -74,6 +432,10 @@ fn run_unit_tests(
     options: &TestOptions,
     test_args: &[&str],
     compilation: &Compilation<'_>,
+    plan: Option<&PartitionPlan>,
+    mut cache: Option<&mut TestCache>,
+    coverage: Option<&Coverage>,
+    events: &mut TestEvents,
 ) -> CargoResult<(Test, Vec<ProcessError>)> {
     let cwd = config.cwd();
     let mut errors = Vec::new();
@@ -83,8 +445,45 @@ fn run_unit_tests(
         let exe_display = exe.strip_prefix(cwd).unwrap_or(exe).display();
         let mut cmd = compilation.target_process(exe, unit.kind, &unit.pkg)?;
         cmd.args(test_args);
-        if unit.target.harness() && config.shell().verbosity() == Verbosity::Quiet {
-            cmd.arg("--quiet");
+        if let Some(coverage) = coverage {
+            coverage.profile(&mut cmd, exe);
+        }
+        if let Some(plan) = plan {
+            match plan.suites.get(&suite_name(&unit.pkg, &unit.target, false)) {
+                Some(names) if !names.is_empty() => {
+                    cmd.arg("--exact").args(names);
+                }
+                Some(_) => {}
+                None => continue,
+            }
+        }
+        if events.bench_protocol(&unit.target) {
+            cmd.env("CARGO_BENCH_FORMAT", "json");
+        }
+        let read_output = unit.target.harness() && events.read_output;
+        if unit.target.harness() {
+            if options.json_events() {
+                cmd.args(options.harness_args());
+            } else if config.shell().verbosity() == Verbosity::Quiet {
+                cmd.arg("--quiet");
+            }
+        }
+        if read_output {
+            cmd.args(&harness_color_args(config, test_args));
+        }
+        let cache_key = match &cache {
+            Some(_) => Some(TestCache::key(config, compilation, unit, &cmd, exe)?),
+            None => None,
+        };
+        if let (Some(cache), Some(key)) = (&cache, &cache_key) {
+            if cache.is_fresh(exe, key) {
+                config.shell().status(
+                    "Cached",
+                    format!("{} (passed with the same inputs)", exe_display),
+                )?;
+                events.cached(config, &unit.pkg, &unit.target, exe);
+                continue;
+            }
         }
         config
             .shell()
@@ -93,11 +492,61 @@ fn run_unit_tests(
             .shell()
             .verbose(|shell| shell.status("Running", &cmd))?;
 
-        let result = cmd.exec();
+        let mut result = if options.json_events() {
+            let harness = unit.target.harness();
+            events.run(config, &cmd, &unit.pkg, &unit.target, Some(exe), harness)
+        } else if read_output {
+            events.exec(config, &cmd)
+        } else {
+            cmd.exec()
+        };
+        // Only a test binary whose tests all pass at once is cached, and not
+        // one whose tests only passed when they were run again.
+        if let (Some(cache), Some(key)) = (cache.as_deref_mut(), cache_key) {
+            cache.record(exe, key, result.is_ok());
+        }
+
+        // Run the failed tests again, until they pass or the retries run out.
+        if unit.target.harness() {
+            for attempt in 2..=options.retries + 1 {
+                let failed = match &result {
+                    Err(_) => events.failed_tests(),
+                    Ok(()) => break,
+                };
+                if failed.is_empty() {
+                    break;
+                }
+                let mut cmd = compilation.target_process(exe, unit.kind, &unit.pkg)?;
+                cmd.args(test_args)
+                    .args(options.harness_args())
+                    .arg("--exact")
+                    .args(&failed);
+                if let Some(coverage) = coverage {
+                    coverage.profile(&mut cmd, exe);
+                }
+                let tests = if failed.len() == 1 { "test" } else { "tests" };
+                config.shell().status(
+                    "Retrying",
+                    format!(
+                        "{} failed {} of {} (attempt {}/{})",
+                        failed.len(),
+                        tests,
+                        exe_display,
+                        attempt,
+                        options.retries + 1
+                    ),
+                )?;
+                config
+                    .shell()
+                    .verbose(|shell| shell.status("Running", &cmd))?;
+                result = events.retry(config, &cmd, &unit.pkg, &unit.target, exe, attempt);
+            }
+        }
 
         match result {
             Err(e) => {
                 let e = e.downcast::<ProcessError>()?;
+                events.target_failed(&unit.pkg, &unit.target, Some(exe));
                 errors.push((
                     unit.target.kind().clone(),
                     test.clone(),
@@ -135,101 +584,1489 @@ fn run_doc_tests(
     options: &TestOptions,
     test_args: &[&str],
     compilation: &Compilation<'_>,
+    plan: Option<&PartitionPlan>,
+    coverage: Option<&Coverage>,
+    events: &mut TestEvents,
 ) -> CargoResult<(Test, Vec<ProcessError>)> {
     let config = ws.config();
     let mut errors = Vec::new();
-    let doctest_xcompile = config.cli_unstable().doctest_xcompile;
-
-    for doctest_info in &compilation.to_doc_test {
-        let Doctest {
-            args,
-            unstable_opts,
-            unit,
-            linker,
-        } = doctest_info;
-
-        if !doctest_xcompile {
-            match unit.kind {
-                CompileKind::Host => {}
-                CompileKind::Target(target) => {
-                    if target.short_name() != compilation.host {
-                        // Skip doctests, -Zdoctest-xcompile not enabled.
-                        continue;
+
+    for doctest in doc_tests(config, compilation)? {
+        let unit = &doctest.unit;
+        if let Some(plan) = plan {
+            if !plan
+                .suites
+                .contains_key(&suite_name(&unit.pkg, &unit.target, true))
+            {
+                continue;
+            }
+        }
+
+        config.shell().status("Doc-tests", unit.target.name())?;
+        // With `-Z parallel-doctests`, the doc tests of the host ran during
+        // the build.
+        let ran = compilation
+            .doctest_outputs
+            .get(&(unit.pkg.package_id(), unit.kind));
+        let result = match ran {
+            Some(output) => events.replay(
+                config,
+                output,
+                &unit.pkg,
+                &unit.target,
+                options.json_events(),
+            ),
+            None => {
+                let mut p = doc_test_process(ws, compilation, doctest)?;
+                for arg in test_args.iter().chain(options.harness_args()) {
+                    p.arg("--test-args").arg(arg);
+                }
+                if events.read_output {
+                    for arg in harness_color_args(config, test_args) {
+                        p.arg("--test-args").arg(arg);
                     }
                 }
+                // The doc tests aren't instrumented themselves, but they run the
+                // instrumented code of the library.
+                if let Some(coverage) = coverage {
+                    p.env(
+                        "LLVM_PROFILE_FILE",
+                        coverage.profile_file(&format!("doctest-{}", unit.target.crate_name())),
+                    );
+                }
+                config
+                    .shell()
+                    .verbose(|shell| shell.status("Running", p.to_string()))?;
+                if options.json_events() {
+                    events.run(config, &p, &unit.pkg, &unit.target, None, true)
+                } else if events.read_output {
+                    events.exec(config, &p)
+                } else {
+                    p.exec()
+                }
+            }
+        };
+        if let Err(e) = result {
+            let e = e.downcast::<ProcessError>()?;
+            events.target_failed(&unit.pkg, &unit.target, None);
+            errors.push(e);
+            if !options.no_fail_fast {
+                return Ok((Test::Doc, errors));
             }
         }
+    }
+    Ok((Test::Doc, errors))
+}
 
-        config.shell().status("Doc-tests", unit.target.name())?;
-        let mut p = compilation.rustdoc_process(unit)?;
-        p.arg("--crate-name").arg(&unit.target.crate_name());
-        p.arg("--test");
-        add_path_args(ws, unit, &mut p);
+/// Returns the rustdoc process which runs the doc tests of `doctest`,
+/// without the arguments for the test harness.
+fn doc_test_process(
+    ws: &Workspace<'_>,
+    compilation: &Compilation<'_>,
+    doctest: &Doctest,
+) -> CargoResult<ProcessBuilder> {
+    let unit = &doctest.unit;
+    let mut p = compiler::doctest_process(ws, compilation, doctest)?;
+    if let Some(cfgs) = compilation.cfgs.get(&unit.pkg.package_id()) {
+        for cfg in cfgs.iter() {
+            p.arg("--cfg").arg(cfg);
+        }
+    }
+    if let Some(flags) = compilation.rustdocflags.get(&unit.pkg.package_id()) {
+        p.args(flags);
+    }
+    Ok(p)
+}
 
-        if doctest_xcompile {
-            if let CompileKind::Target(target) = unit.kind {
-                // use `rustc_target()` to properly handle JSON target paths
-                p.arg("--target").arg(target.rustc_target());
+/// Returns the doc tests to run.
+///
+/// The doc tests for other targets than the host are run with
+/// `-Zdoctest-xcompile`, or otherwise through the runner of their target, if
+/// it has one. Since rustdoc needs unstable options to use the runner, this
+/// is only done on the nightly channel, and `target.<triple>.run-doctests`
+/// can turn it off for runners which can't run doc tests.
+fn doc_tests<'a>(
+    config: &Config,
+    compilation: &'a Compilation<'_>,
+) -> CargoResult<Vec<&'a Doctest>> {
+    let mut doctests = Vec::new();
+    for doctest in &compilation.to_doc_test {
+        let kind = doctest.unit.kind;
+        let target = match &doctest.unit.kind {
+            CompileKind::Target(target) if is_cross(compilation, kind) => target.short_name(),
+            _ => {
+                doctests.push(doctest);
+                continue;
             }
-            p.arg("-Zunstable-options");
-            p.arg("--enable-per-target-ignores");
-            if let Some((runtool, runtool_args)) = compilation.target_runner(unit.kind) {
-                p.arg("--runtool").arg(runtool);
-                for arg in runtool_args {
-                    p.arg("--runtool-arg").arg(arg);
+        };
+        if config.cli_unstable().doctest_xcompile {
+            doctests.push(doctest);
+            continue;
+        }
+        let run_doctests = config.target_cfg_triple(target)?.run_doctests;
+        let skipped = if compilation.target_runner(kind).is_none() {
+            "it has no runner".to_string()
+        } else if !nightly_features_allowed() {
+            "running them through its runner requires the nightly channel".to_string()
+        } else if let Some(false) = run_doctests.as_ref().map(|v| v.val) {
+            let definition = &run_doctests.as_ref().unwrap().definition;
+            format!(
+                "`target.{}.run-doctests` is false in {}",
+                target, definition
+            )
+        } else {
+            doctests.push(doctest);
+            continue;
+        };
+        config.shell().verbose(|shell| {
+            shell.note(format!(
+                "skipping the doc tests of `{}` for target `{}`, since {}",
+                doctest.unit.pkg.name(),
+                target,
+                skipped
+            ))
+        })?;
+    }
+    Ok(doctests)
+}
+
+/// Whether `kind` is a target other than the host.
+fn is_cross(compilation: &Compilation<'_>, kind: CompileKind) -> bool {
+    match kind {
+        CompileKind::Host => false,
+        CompileKind::Target(target) => target.short_name() != compilation.host,
+    }
+}
+
+/// The name identifying the tests of a target, in the JUnit report and the
+/// recorded test durations.
+fn suite_name(pkg: &Package, target: &Target, doctest: bool) -> String {
+    let kind = if doctest {
+        "doctest"
+    } else {
+        target.kind().description()
+    };
+    format!("{}::{}::{}", pkg.name(), kind, target.name())
+}
+
+/// The tests of the shard selected with `--partition`.
+struct PartitionPlan {
+    /// The names of the tests to run for each test suite which has some in
+    /// the shard, keyed by the suite name. The doc tests and the targets
+    /// without a test harness are split as a whole, and have no names.
+    suites: HashMap<String, Vec<String>>,
+}
+
+/// Lists the tests of all the test targets and selects the ones of the
+/// shard to run.
+fn plan_partition(
+    ws: &Workspace<'_>,
+    partition: TestPartition,
+    test_args: &[&str],
+    compilation: &Compilation<'_>,
+    coverage: Option<&Coverage>,
+) -> CargoResult<PartitionPlan> {
+    let config = ws.config();
+    // Each item is a test suite name, and the name of a test of the suite if
+    // the suite can be split.
+    let mut items: Vec<(String, Option<String>)> = Vec::new();
+    for (unit, exe) in &compilation.tests {
+        let suite = suite_name(&unit.pkg, &unit.target, false);
+        if !unit.target.harness() {
+            items.push((suite, None));
+            continue;
+        }
+        let mut cmd = compilation.target_process(exe, unit.kind, &unit.pkg)?;
+        cmd.args(test_args).args(&["--list", "--format", "terse"]);
+        if let Some(coverage) = coverage {
+            coverage.profile(&mut cmd, exe);
+        }
+        config
+            .shell()
+            .verbose(|shell| shell.status("Listing", &cmd))?;
+        let output = cmd.exec_with_output()?;
+        for name in parse_test_list(&output.stdout) {
+            items.push((suite.clone(), Some(name)));
+        }
+    }
+    for doctest in doc_tests(config, compilation)? {
+        items.push((
+            suite_name(&doctest.unit.pkg, &doctest.unit.target, true),
+            None,
+        ));
+    }
+    let total = items.len();
+
+    let key = |(suite, name): &(String, Option<String>)| match name {
+        Some(name) => format!("{}::{}", suite, name),
+        None => suite.clone(),
+    };
+    let shard = partition.index - 1;
+    let selected: Vec<(String, Option<String>)> = match partition.strategy {
+        PartitionStrategy::Hash => items
+            .into_iter()
+            .filter(|item| hash_u64(key(item)) % partition.count as u64 == shard as u64)
+            .collect(),
+        PartitionStrategy::Time => {
+            let timings = load_test_timings(ws);
+            // A suite which isn't split takes as long as all of its tests.
+            let duration = |key: &str| -> Option<f64> {
+                timings.get(key).copied().or_else(|| {
+                    let prefix = format!("{}::", key);
+                    let mut matching = timings
+                        .iter()
+                        .filter(|(k, _)| k.starts_with(&prefix))
+                        .map(|(_, time)| *time)
+                        .peekable();
+                    matching.peek()?;
+                    Some(matching.sum())
+                })
+            };
+            let mut items: Vec<_> = items
+                .into_iter()
+                .map(|item| {
+                    let key = key(&item);
+                    (duration(&key), key, item)
+                })
+                .collect();
+            // Tests which never ran are assumed to take the average time.
+            let known: Vec<f64> = items.iter().filter_map(|(time, ..)| *time).collect();
+            let average = if known.is_empty() {
+                1.0
+            } else {
+                known.iter().sum::<f64>() / known.len() as f64
+            };
+            let mut items: Vec<_> = items
+                .drain(..)
+                .map(|(time, key, item)| (time.unwrap_or(average), key, item))
+                .collect();
+            // The longest tests first, each to the shard with the least work.
+            items.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap().then_with(|| a.1.cmp(&b.1)));
+            let mut loads = vec![0.0; partition.count];
+            let mut selected = Vec::new();
+            for (time, _key, item) in items {
+                let mut least = 0;
+                for (i, load) in loads.iter().enumerate() {
+                    if *load < loads[least] {
+                        least = i;
+                    }
+                }
+                loads[least] += time;
+                if least == shard {
+                    selected.push(item);
                 }
             }
-            if let Some(linker) = linker {
-                let mut joined = OsString::from("linker=");
-                joined.push(linker);
-                p.arg("-C").arg(joined);
+            selected
+        }
+    };
+
+    config.shell().status(
+        "Partition",
+        format!(
+            "{}/{}, {} of {} tests",
+            partition.index,
+            partition.count,
+            selected.len(),
+            total
+        ),
+    )?;
+    let mut suites: HashMap<String, Vec<String>> = HashMap::new();
+    for (suite, name) in selected {
+        let names = suites.entry(suite).or_default();
+        names.extend(name);
+    }
+    Ok(PartitionPlan { suites })
+}
+
+/// The test binaries which passed, with `-Z test-cache`, to skip them when
+/// they would run again with the same inputs.
+struct TestCache {
+    path: PathBuf,
+    /// The key of the inputs of each test binary which passed the last time
+    /// it ran, by the path of the binary.
+    passed: BTreeMap<String, String>,
+    /// Whether to run the test binaries which passed anyway, with
+    /// `--no-cache`.
+    rerun: bool,
+}
+
+impl TestCache {
+    /// Loads the test binaries which passed, if `-Z test-cache` is used.
+    fn load(ws: &Workspace<'_>, options: &TestOptions) -> Option<TestCache> {
+        // Skipping a test binary would leave its code out of the coverage.
+        if !ws.config().cli_unstable().test_cache || options.coverage {
+            return None;
+        }
+        let path = ws.target_dir().as_path_unlocked().join("test-cache.json");
+        let passed = paths::read(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        Some(TestCache {
+            path,
+            passed,
+            rerun: options.no_cache,
+        })
+    }
+
+    /// Returns the key of the inputs of running `cmd`: the test binary, as
+    /// of when it was last built, with its arguments and whole environment,
+    /// the files of its package, which the tests may read as fixtures, and
+    /// the dynamic libraries it may load from the target directory.
+    fn key(
+        config: &Config,
+        compilation: &Compilation<'_>,
+        unit: &compiler::Unit,
+        cmd: &ProcessBuilder,
+        exe: &Path,
+    ) -> CargoResult<String> {
+        let mut env = env::vars_os()
+            .map(|(key, value)| (key, Some(value)))
+            .collect::<BTreeMap<_, _>>();
+        for (key, value) in cmd.get_envs() {
+            env.insert(key.into(), value.clone());
+        }
+
+        let src = PathSource::new(unit.pkg.root(), unit.pkg.package_id().source_id(), config);
+        let mut files = src.list_files(&unit.pkg)?;
+        let dylib_dirs = compilation
+            .deps_output
+            .get(&unit.kind)
+            .into_iter()
+            .chain(&compilation.native_dirs);
+        for dir in dylib_dirs {
+            for entry in dir.read_dir().into_iter().flatten().flatten() {
+                let path = entry.path();
+                if path.extension() == Some(env::consts::DLL_EXTENSION.as_ref()) {
+                    files.push(path);
+                }
             }
         }
+        files.push(exe.to_path_buf());
+        let mtimes = files
+            .iter()
+            .map(|file| {
+                let mtime = paths::mtime(file).ok();
+                (file, mtime.map(|m| (m.unix_seconds(), m.nanoseconds())))
+            })
+            .collect::<Vec<_>>();
+
+        let key = (cmd.to_string(), env, cmd.get_cwd(), mtimes);
+        Ok(to_hex(hash_u64(key)))
+    }
 
-        for &rust_dep in &[
-            &compilation.deps_output[&unit.kind],
-            &compilation.deps_output[&CompileKind::Host],
-        ] {
-            let mut arg = OsString::from("dependency=");
-            arg.push(rust_dep);
-            p.arg("-L").arg(arg);
+    /// Whether the test binary `exe` passed the last time it ran, with the
+    /// same inputs.
+    fn is_fresh(&self, exe: &Path, key: &str) -> bool {
+        !self.rerun && self.passed.get(&*exe.to_string_lossy()).map(|k| &**k) == Some(key)
+    }
+
+    fn record(&mut self, exe: &Path, key: String, passed: bool) {
+        let exe = exe.to_string_lossy().into_owned();
+        if passed {
+            self.passed.insert(exe, key);
+        } else {
+            self.passed.remove(&exe);
         }
+    }
+
+    fn save(&self) -> CargoResult<()> {
+        paths::write(&self.path, serde_json::to_string_pretty(&self.passed)?)
+    }
+}
+
+/// The code coverage of the tests, with `--coverage`.
+///
+/// The test binaries write their raw profiles to `target/coverage`, and once
+/// they have run, these are merged with `llvm-profdata`, and reported with
+/// `llvm-cov`, as `target/coverage/lcov.info` and in `target/coverage/html`.
+struct Coverage {
+    dir: PathBuf,
+    llvm_profdata: PathBuf,
+    llvm_cov: PathBuf,
+}
 
-        for native_dep in compilation.native_dirs.iter() {
-            p.arg("-L").arg(native_dep);
+impl Coverage {
+    /// Locates the LLVM tools, and removes the coverage data of the previous
+    /// run, if `--coverage` is used.
+    fn new(
+        ws: &Workspace<'_>,
+        options: &TestOptions,
+        compilation: &Compilation<'_>,
+    ) -> CargoResult<Option<Coverage>> {
+        if !options.coverage {
+            return Ok(None);
         }
+        let llvm_profdata = llvm_tool(compilation, "llvm-profdata")?;
+        let llvm_cov = llvm_tool(compilation, "llvm-cov")?;
+        let dir = ws.target_dir().as_path_unlocked().join("coverage");
+        if dir.exists() {
+            paths::remove_dir_all(&dir)?;
+        }
+        paths::create_dir_all(&dir)?;
+        Ok(Some(Coverage {
+            dir,
+            llvm_profdata,
+            llvm_cov,
+        }))
+    }
 
-        for arg in test_args {
-            p.arg("--test-args").arg(arg);
+    /// Returns the pattern of the raw profiles written by the processes of
+    /// `name`, with their process ID and a signature of the binary.
+    fn profile_file(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}-%p-%m.profraw", name))
+    }
+
+    /// Makes the test binary `exe` write its raw profile with the others.
+    fn profile(&self, cmd: &mut ProcessBuilder, exe: &Path) {
+        let name = exe.file_stem().unwrap_or_default().to_string_lossy();
+        cmd.env("LLVM_PROFILE_FILE", self.profile_file(&name));
+    }
+
+    /// Merges the raw profiles, and writes the reports.
+    fn report(&self, config: &Config, compilation: &Compilation<'_>) -> CargoResult<()> {
+        let mut profiles = Vec::new();
+        for entry in self.dir.read_dir()? {
+            let path = entry?.path();
+            if path.extension() == Some("profraw".as_ref()) {
+                profiles.push(path);
+            }
+        }
+        if profiles.is_empty() {
+            return config
+                .shell()
+                .warn("no coverage data was recorded, since no test binary ran");
         }
+        profiles.sort();
 
-        if let Some(cfgs) = compilation.cfgs.get(&unit.pkg.package_id()) {
-            for cfg in cfgs.iter() {
-                p.arg("--cfg").arg(cfg);
+        let profdata = self.dir.join("coverage.profdata");
+        let mut merge = process(&self.llvm_profdata);
+        merge
+            .arg("merge")
+            .arg("-sparse")
+            .args(&profiles)
+            .arg("-o")
+            .arg(&profdata);
+        config
+            .shell()
+            .verbose(|shell| shell.status("Running", &merge))?;
+        merge
+            .exec_with_output()
+            .chain_err(|| "failed to merge the coverage data")?;
+
+        let mut instr_profile = OsString::from("-instr-profile=");
+        instr_profile.push(&profdata);
+        // `llvm-cov` reads the coverage mappings from the test binaries, the
+        // first one as an argument and the others with `-object`.
+        let mut objects = Vec::new();
+        for (i, (_, exe)) in compilation.tests.iter().enumerate() {
+            if i > 0 {
+                objects.push(OsString::from("-object"));
             }
+            objects.push(exe.clone().into_os_string());
         }
 
-        for arg in args {
-            p.arg(arg);
+        let lcov = self.dir.join("lcov.info");
+        let mut export = process(&self.llvm_cov);
+        export
+            .arg("export")
+            .arg("-format=lcov")
+            .arg(&instr_profile)
+            .args(&objects);
+        config
+            .shell()
+            .verbose(|shell| shell.status("Running", &export))?;
+        let output = export
+            .exec_with_output()
+            .chain_err(|| "failed to export the coverage data")?;
+        paths::write(&lcov, &output.stdout)?;
+
+        let html = self.dir.join("html");
+        let mut output_dir = OsString::from("-output-dir=");
+        output_dir.push(&html);
+        let mut show = process(&self.llvm_cov);
+        show.arg("show")
+            .arg("-format=html")
+            .arg(&instr_profile)
+            .arg(&output_dir)
+            .args(&objects);
+        config
+            .shell()
+            .verbose(|shell| shell.status("Running", &show))?;
+        show.exec_with_output()
+            .chain_err(|| "failed to write the coverage report")?;
+
+        let cwd = config.cwd();
+        let index = html.join("index.html");
+        config.shell().status(
+            "Coverage",
+            format!(
+                "{} and {}",
+                lcov.strip_prefix(cwd).unwrap_or(&lcov).display(),
+                index.strip_prefix(cwd).unwrap_or(&index).display()
+            ),
+        )
+    }
+}
+
+/// Returns the path of an LLVM tool, from the `llvm-tools-preview`
+/// component of the toolchain, or else from `PATH`.
+fn llvm_tool(compilation: &Compilation<'_>, name: &str) -> CargoResult<PathBuf> {
+    let exe = format!("{}{}", name, env::consts::EXE_SUFFIX);
+    let path = compilation
+        .sysroot
+        .join("lib")
+        .join("rustlib")
+        .join(&compilation.host)
+        .join("bin")
+        .join(&exe);
+    if path.exists() {
+        return Ok(path);
+    }
+    paths::resolve_executable(Path::new(&exe)).map_err(|_| {
+        anyhow::format_err!(
+            "could not find `{}`, which is needed for `--coverage`, \
+             in the toolchain or in PATH\n\n\
+             help: install it with `rustup component add llvm-tools-preview`",
+            name
+        )
+    })
+}
+
+/// The file where the durations of the tests are recorded, for
+/// `--partition-strategy time`.
+fn test_timings_path(ws: &Workspace<'_>) -> PathBuf {
+    ws.target_dir().as_path_unlocked().join("test-timings.json")
+}
+
+/// Loads the durations of the tests recorded by previous runs, in seconds,
+/// keyed by the suite name and the test name. Durations which can't be ones,
+/// from a corrupted or hand-edited file, are discarded so that the tests are
+/// assumed to take the average time.
+fn load_test_timings(ws: &Workspace<'_>) -> BTreeMap<String, f64> {
+    let timings: BTreeMap<String, f64> = paths::read(&test_timings_path(ws))
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default();
+    timings
+        .into_iter()
+        .filter(|(_, time)| time.is_finite() && *time >= 0.0)
+        .collect()
+}
+
+/// Handles the events of the test harnesses, when they are run with JSON
+/// output for `--format`, `--timeout` or `--partition`.
+struct TestEvents {
+    format: TestFormat,
+    timeout: Option<Duration>,
+    /// Whether to record the durations of the tests, for
+    /// `--partition-strategy time`.
+    record_timings: bool,
+    /// The tests of the current test harness which are running, with the
+    /// time they started.
+    running: HashMap<String, Instant>,
+    /// The names and output of the tests of the current test harness which
+    /// failed, to display them with `--format human`.
+    failures: Vec<(String, String)>,
+    /// The JUnit test suites, one for each run of a test harness.
+    suites: Vec<JunitSuite>,
+    /// The suite and name of the tests which passed when they were run again
+    /// for `--retries`, with the number of times they ran.
+    flaky: Vec<(String, String, u32)>,
+    /// The test targets which failed, for the summary of `--no-fail-fast`.
+    failed_targets: Vec<FailedTarget>,
+    /// Whether to print the summary of the failed test targets.
+    summary: bool,
+    /// Whether to find out which tests failed from the output of the test
+    /// harnesses, for the summary, when they aren't run with JSON output.
+    read_output: bool,
+    /// The tests which failed in the output of the last test harness.
+    output_failures: Vec<String>,
+    /// The command and arguments to run the failed tests again, without the
+    /// test name filters: `cargo test` or `cargo bench`, whether the
+    /// package must be selected, and the options for the test harness.
+    rerun: (&'static str, bool, Vec<String>),
+    passed: u64,
+    failed: u64,
+    ignored: u64,
+    measured: u64,
+    filtered_out: u64,
+}
+
+/// A test target which failed, for the summary of `--no-fail-fast`.
+struct FailedTarget {
+    pkg: Package,
+    target: Target,
+    /// The test binary, or `None` for doctests.
+    executable: Option<PathBuf>,
+    /// The names of the tests which failed, if the test harness reported
+    /// them.
+    tests: Vec<String>,
+}
+
+struct JunitSuite {
+    name: String,
+    cases: Vec<JunitCase>,
+}
+
+struct JunitCase {
+    name: String,
+    /// The duration of the test in seconds, if libtest reported it.
+    time: Option<f64>,
+    /// `None` if the test passed, otherwise the outcome and the output of the
+    /// test, if any.
+    failure: Option<(&'static str, Option<String>)>,
+    /// The output of the earlier runs of the test which failed, when it was
+    /// run again for `--retries`.
+    retries: Vec<Option<String>>,
+}
+
+impl TestEvents {
+    fn new(
+        ws: &Workspace<'_>,
+        options: &TestOptions,
+        command: &'static str,
+        test_args: &[&str],
+    ) -> TestEvents {
+        // The summary needs the output of the test harnesses, so they only
+        // go through Cargo when it's asked for.
+        let summary = options.no_fail_fast && ws.config().cli_unstable().unstable_options;
+        let read_output = summary && !options.json_events();
+        let needs_spec = options.compile_opts.spec.needs_spec_flag(ws);
+        TestEvents {
+            format: options.format,
+            timeout: options.timeout,
+            record_timings: options.partition.is_some(),
+            running: HashMap::new(),
+            failures: Vec::new(),
+            suites: Vec::new(),
+            flaky: Vec::new(),
+            failed_targets: Vec::new(),
+            summary,
+            read_output,
+            output_failures: Vec::new(),
+            rerun: (command, needs_spec, harness_options(test_args)),
+            passed: 0,
+            failed: 0,
+            ignored: 0,
+            measured: 0,
+            filtered_out: 0,
+        }
+    }
+
+    /// Runs a test harness, handling the JSON events it prints on stdout.
+    ///
+    /// Everything else the process prints, including all the output of
+    /// targets with `harness = false`, is forwarded to stderr so that stdout
+    /// only has JSON with `--format json`.
+    fn run(
+        &mut self,
+        config: &Config,
+        cmd: &ProcessBuilder,
+        pkg: &Package,
+        target: &Target,
+        executable: Option<&Path>,
+        harness: bool,
+    ) -> CargoResult<()> {
+        self.suites.push(JunitSuite {
+            name: suite_name(pkg, target, executable.is_none()),
+            cases: Vec::new(),
+        });
+        self.running.clear();
+        let on_stdout_line = |events: &mut TestEvents, line: &str| -> CargoResult<()> {
+            match serde_json::from_str::<Box<RawValue>>(line) {
+                Ok(event) if harness => events.event(config, pkg, target, executable, event)?,
+                Ok(event) if events.bench_protocol(target) => {
+                    let value: serde_json::Value = serde_json::from_str(event.get())?;
+                    match (value["type"].as_str(), executable) {
+                        (Some("bench"), Some(executable)) => {
+                            events.measured += 1;
+                            events.bench(config, pkg, target, executable, &value);
+                        }
+                        _ => crate::drop_eprintln!(config, "{}", line),
+                    }
+                }
+                _ => crate::drop_eprintln!(config, "{}", line),
+            }
+            Ok(())
+        };
+        let timeout = match self.timeout {
+            Some(timeout) if harness => timeout,
+            _ => {
+                let mut on_stderr_line = |line: &str| -> CargoResult<()> {
+                    crate::drop_eprintln!(config, "{}", line);
+                    Ok(())
+                };
+                cmd.exec_with_streaming(
+                    &mut |line| on_stdout_line(self, line),
+                    &mut on_stderr_line,
+                    false,
+                )?;
+                return Ok(());
+            }
+        };
+
+        // The output is read by separate threads, so that the test binary
+        // can be killed when a test runs too long, even if it prints nothing.
+        let mut child = cmd
+            .build_command()
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .chain_err(|| {
+                process_error(&format!("could not execute process {}", cmd), None, None)
+            })?;
+        let (tx, rx) = mpsc::channel();
+        read_lines(child.stdout.take().unwrap(), true, tx.clone());
+        read_lines(child.stderr.take().unwrap(), false, tx);
+        loop {
+            let deadline = self.running.values().min().map(|start| *start + timeout);
+            let line = match deadline {
+                Some(deadline) => {
+                    rx.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                }
+                None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match line {
+                Ok((true, line)) => on_stdout_line(self, &line)?,
+                Ok((false, line)) => crate::drop_eprintln!(config, "{}", line),
+                Err(RecvTimeoutError::Timeout) => {
+                    let _ = child.kill();
+                    let status = child.wait()?;
+                    let mut hung: Vec<String> = self
+                        .running
+                        .iter()
+                        .filter(|(_, start)| start.elapsed() >= timeout)
+                        .map(|(name, _)| name.clone())
+                        .collect();
+                    hung.sort();
+                    let suite = self.suites.last_mut().unwrap();
+                    for name in &hung {
+                        suite.cases.push(JunitCase {
+                            name: name.clone(),
+                            time: Some(timeout.as_secs_f64()),
+                            failure: Some((
+                                "failure",
+                                Some(format!("timed out after {:?}", timeout)),
+                            )),
+                            retries: Vec::new(),
+                        });
+                    }
+                    self.failed += hung.len() as u64;
+                    let hung = hung
+                        .iter()
+                        .map(|name| format!("`{}`", name))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    let msg = format!(
+                        "test {} did not finish within {:?}, killed process {}",
+                        hung, timeout, cmd
+                    );
+                    return Err(process_error(&msg, Some(status), None).into());
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            let msg = format!("process didn't exit successfully: {}", cmd);
+            return Err(process_error(&msg, Some(status), None).into());
+        }
+        Ok(())
+    }
+
+    /// Runs a test harness with its stdout going through Cargo, to find out
+    /// which tests failed from its output.
+    ///
+    /// The output is forwarded as it comes, even when a line isn't finished,
+    /// so that it looks the same as when the test harness is run directly.
+    fn exec(&mut self, config: &Config, cmd: &ProcessBuilder) -> CargoResult<()> {
+        self.output_failures.clear();
+        let mut child = cmd
+            .build_command()
+            .stdout(Stdio::piped())
+            .spawn()
+            .chain_err(|| {
+                process_error(&format!("could not execute process {}", cmd), None, None)
+            })?;
+        let mut stdout = child.stdout.take().unwrap();
+        let mut buf = [0; 4096];
+        let mut line = Vec::new();
+        loop {
+            let n = match stdout.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            {
+                let mut shell = config.shell();
+                let out = shell.out();
+                let _ = out.write_all(&buf[..n]).and_then(|()| out.flush());
+            }
+            for &b in &buf[..n] {
+                if b != b'\n' {
+                    line.push(b);
+                    continue;
+                }
+                if let Some(name) = failed_test_name(&String::from_utf8_lossy(&line)) {
+                    self.output_failures.push(name);
+                }
+                line.clear();
+            }
         }
+        let status = child.wait()?;
+        if !status.success() {
+            let msg = format!("process didn't exit successfully: {}", cmd);
+            return Err(process_error(&msg, Some(status), None).into());
+        }
+        Ok(())
+    }
 
-        if *unstable_opts {
-            p.arg("-Zunstable-options");
+    /// Handles the output of doc tests which already ran during the build,
+    /// with `-Z parallel-doctests`, as if they were running now.
+    fn replay(
+        &mut self,
+        config: &Config,
+        output: &DoctestOutput,
+        pkg: &Package,
+        target: &Target,
+        json: bool,
+    ) -> CargoResult<()> {
+        if json {
+            self.suites.push(JunitSuite {
+                name: suite_name(pkg, target, true),
+                cases: Vec::new(),
+            });
+            self.running.clear();
+        }
+        self.output_failures.clear();
+        for (is_out, line) in &output.lines {
+            if !is_out {
+                crate::drop_eprintln!(config, "{}", line);
+            } else if json {
+                match serde_json::from_str::<Box<RawValue>>(line) {
+                    Ok(event) => self.event(config, pkg, target, None, event)?,
+                    Err(_) => crate::drop_eprintln!(config, "{}", line),
+                }
+            } else {
+                crate::drop_println!(config, "{}", line);
+                if self.read_output {
+                    if let Some(name) = failed_test_name(line) {
+                        self.output_failures.push(name);
+                    }
+                }
+            }
+        }
+        match &output.error {
+            Some(e) => Err(ProcessError {
+                desc: e.desc.clone(),
+                exit: e.exit,
+                output: None,
+            }
+            .into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Records the last test harness which ran as failed, for the summary of
+    /// `--no-fail-fast`.
+    fn target_failed(&mut self, pkg: &Package, target: &Target, executable: Option<&Path>) {
+        let tests = if self.read_output {
+            mem::take(&mut self.output_failures)
+        } else {
+            self.failed_tests()
+        };
+        self.failed_targets.push(FailedTarget {
+            pkg: pkg.clone(),
+            target: target.clone(),
+            executable: executable.map(|exe| exe.to_path_buf()),
+            tests,
+        });
+    }
+
+    /// Returns the command to run the failed tests of `failed` again.
+    fn rerun_command(&self, failed: &FailedTarget) -> String {
+        let (command, needs_spec, harness_options) = &self.rerun;
+        let mut rerun = format!("cargo {}", command);
+        if *needs_spec {
+            rerun.push_str(&format!(" -p {}", failed.pkg.name()));
+        }
+        let name = failed.target.name();
+        let target = match failed.target.kind() {
+            _ if failed.executable.is_none() => "--doc".to_string(),
+            TargetKind::Lib(_) => "--lib".to_string(),
+            TargetKind::Bin => format!("--bin {}", name),
+            TargetKind::Test => format!("--test {}", name),
+            TargetKind::Bench => format!("--bench {}", name),
+            TargetKind::ExampleLib(_) | TargetKind::ExampleBin => format!("--example {}", name),
+            TargetKind::CustomBuild => unreachable!(),
+        };
+        rerun.push(' ');
+        rerun.push_str(&target);
+        let mut args = harness_options.clone();
+        // Doc tests can't be selected by name, since rustdoc splits the test
+        // arguments on whitespace.
+        if failed.executable.is_some() && !failed.tests.is_empty() {
+            args.push("--exact".to_string());
+            args.extend(failed.tests.iter().cloned());
         }
+        if !args.is_empty() {
+            rerun.push_str(" --");
+            for arg in args {
+                rerun.push(' ');
+                rerun.push_str(&shell_quote(&arg));
+            }
+        }
+        rerun
+    }
 
-        if let Some(flags) = compilation.rustdocflags.get(&unit.pkg.package_id()) {
-            p.args(flags);
+    /// Prints the test targets which failed, with `--no-fail-fast`.
+    fn print_summary(&self, config: &Config) -> CargoResult<()> {
+        if !self.summary || self.failed_targets.is_empty() {
+            return Ok(());
         }
+        let n = self.failed_targets.len();
+        let targets = if n == 1 { "target" } else { "targets" };
         config
             .shell()
-            .verbose(|shell| shell.status("Running", p.to_string()))?;
-        if let Err(e) = p.exec() {
-            let e = e.downcast::<ProcessError>()?;
-            errors.push(e);
-            if !options.no_fail_fast {
-                return Ok((Test::Doc, errors));
+            .status("Summary", format!("{} test {} failed", n, targets))?;
+        for failed in &self.failed_targets {
+            let kind = match failed.executable {
+                Some(_) => failed.target.kind().description(),
+                None => "doctest",
+            };
+            let location = match &failed.executable {
+                Some(exe) => {
+                    let exe = exe.strip_prefix(config.cwd()).unwrap_or(exe);
+                    format!(" ({})", exe.display())
+                }
+                None => String::new(),
+            };
+            crate::drop_eprintln!(
+                config,
+                "    {} {} `{}`{}",
+                failed.pkg.name(),
+                kind,
+                failed.target.name(),
+                location
+            );
+            if !failed.tests.is_empty() {
+                crate::drop_eprintln!(config, "      failed: {}", failed.tests.join(", "));
             }
+            crate::drop_eprintln!(config, "      rerun: {}", self.rerun_command(failed));
         }
+        Ok(())
     }
-    Ok((Test::Doc, errors))
+
+    /// Reports a test binary which wasn't run, since it passed with the same
+    /// inputs before.
+    fn cached(&self, config: &Config, pkg: &Package, target: &Target, exe: &Path) {
+        if self.format == TestFormat::Json {
+            let msg = machine_message::TestCached {
+                package_id: pkg.package_id(),
+                target,
+                executable: exe,
+            }
+            .to_json_string();
+            crate::drop_println!(config, "{}", msg);
+        }
+    }
+
+    /// Returns the names of the tests of the last test harness which failed.
+    fn failed_tests(&self) -> Vec<String> {
+        let cases = self.suites.last().map(|suite| suite.cases.as_slice());
+        cases
+            .unwrap_or_default()
+            .iter()
+            .filter(|case| matches!(case.failure, Some(("failure", _))))
+            .map(|case| case.name.clone())
+            .collect()
+    }
+
+    /// Runs the failed tests of the last test harness again with `cmd`, for
+    /// `--retries`.
+    ///
+    /// The results replace the ones of the earlier run, and the tests which
+    /// pass this time are counted as passed, but flaky.
+    fn retry(
+        &mut self,
+        config: &Config,
+        cmd: &ProcessBuilder,
+        pkg: &Package,
+        target: &Target,
+        exe: &Path,
+        attempt: u32,
+    ) -> CargoResult<()> {
+        let counts = (
+            self.passed,
+            self.failed,
+            self.ignored,
+            self.measured,
+            self.filtered_out,
+        );
+        let result = self.run(config, cmd, pkg, target, Some(exe), true);
+        let retry = self.suites.pop().unwrap();
+        let (passed, failed, ignored, measured, filtered_out) = counts;
+        self.passed = passed;
+        self.failed = failed;
+        self.ignored = ignored;
+        self.measured = measured;
+        self.filtered_out = filtered_out;
+
+        let suite = self.suites.last_mut().unwrap();
+        for case in retry.cases {
+            let earlier = match suite.cases.iter_mut().find(|c| c.name == case.name) {
+                Some(earlier) => earlier,
+                None => continue,
+            };
+            let output = earlier.failure.take().and_then(|(_, output)| output);
+            earlier.retries.push(output);
+            earlier.time = case.time;
+            earlier.failure = case.failure;
+            if earlier.failure.is_none() {
+                self.passed += 1;
+                self.failed -= 1;
+                self.flaky
+                    .push((suite.name.clone(), case.name.clone(), attempt));
+                if self.format == TestFormat::Json {
+                    let msg = machine_message::TestFlaky {
+                        package_id: pkg.package_id(),
+                        target,
+                        name: &case.name,
+                        attempts: attempt,
+                    }
+                    .to_json_string();
+                    crate::drop_println!(config, "{}", msg);
+                }
+            }
+        }
+        result
+    }
+
+    fn event(
+        &mut self,
+        config: &Config,
+        pkg: &Package,
+        target: &Target,
+        executable: Option<&Path>,
+        event: Box<RawValue>,
+    ) -> CargoResult<()> {
+        let value: serde_json::Value = serde_json::from_str(event.get())?;
+        let count = |key: &str| value[key].as_u64().unwrap_or(0);
+        let name = value["name"].as_str().unwrap_or_default();
+        match (value["type"].as_str(), value["event"].as_str()) {
+            (Some("suite"), Some("started")) => {
+                self.failures.clear();
+                if self.format == TestFormat::Human {
+                    let n = count("test_count");
+                    let s = if n == 1 { "" } else { "s" };
+                    crate::drop_println!(config, "\nrunning {} test{}", n, s);
+                }
+            }
+            (Some("suite"), Some(outcome)) => {
+                self.passed += count("passed");
+                self.failed += count("failed");
+                self.ignored += count("ignored");
+                self.measured += count("measured");
+                self.filtered_out += count("filtered_out");
+                if self.format == TestFormat::Human {
+                    self.print_suite_result(config, &value, outcome);
+                }
+            }
+            (Some("test"), Some("started")) | (Some("bench"), Some("started")) => {
+                self.running.insert(name.to_string(), Instant::now());
+            }
+            // libtest reports a benchmark with a `started` event, then this
+            // one with its result.
+            (Some("bench"), None) => {
+                if let Some(executable) = executable {
+                    self.bench(config, pkg, target, executable, &value);
+                }
+                return Ok(());
+            }
+            // A warning that the test is slow, it keeps running.
+            (Some("test"), Some("timeout")) => {}
+            (Some("test"), Some(outcome)) | (Some("bench"), Some(outcome)) => {
+                self.running.remove(name);
+                let stdout = value["stdout"].as_str().map(String::from);
+                let failure = match outcome {
+                    "ok" => None,
+                    "ignored" => Some(("skipped", None)),
+                    _ => Some(("failure", stdout.clone())),
+                };
+                if self.format == TestFormat::Human {
+                    let outcome = match outcome {
+                        "ok" | "ignored" => outcome,
+                        _ => {
+                            self.failures
+                                .push((name.to_string(), stdout.unwrap_or_default()));
+                            "FAILED"
+                        }
+                    };
+                    crate::drop_println!(config, "test {} ... {}", name, outcome);
+                }
+                let suite = self.suites.last_mut().unwrap();
+                suite.cases.push(JunitCase {
+                    name: name.to_string(),
+                    time: value["exec_time"].as_f64(),
+                    failure,
+                    retries: Vec::new(),
+                });
+            }
+            _ => {}
+        }
+        if self.format == TestFormat::Json {
+            let msg = machine_message::TestMessage {
+                package_id: pkg.package_id(),
+                target,
+                executable,
+                doctest: executable.is_none(),
+                event,
+            }
+            .to_json_string();
+            crate::drop_println!(config, "{}", msg);
+        }
+        Ok(())
+    }
+
+    /// Whether the output of a target with `harness = false` is read for the
+    /// results of its benchmarks, which it prints as JSON lines like the
+    /// `bench` events of libtest, when `cargo bench --format json` sets
+    /// `CARGO_BENCH_FORMAT=json`.
+    fn bench_protocol(&self, target: &Target) -> bool {
+        !target.harness() && self.rerun.0 == "bench" && self.format == TestFormat::Json
+    }
+
+    /// Handles the result of a benchmark.
+    fn bench(
+        &mut self,
+        config: &Config,
+        pkg: &Package,
+        target: &Target,
+        executable: &Path,
+        value: &serde_json::Value,
+    ) {
+        let name = value["name"].as_str().unwrap_or_default();
+        self.running.remove(name);
+        let median = value["median"].as_f64().unwrap_or(0.0);
+        let deviation = value["deviation"].as_f64().unwrap_or(0.0);
+        match self.format {
+            TestFormat::Json => {
+                let msg = machine_message::BenchResult {
+                    package_id: pkg.package_id(),
+                    target,
+                    executable,
+                    name,
+                    median,
+                    deviation,
+                    iterations: value["iterations"].as_u64(),
+                    mib_per_second: value["mib_per_second"].as_f64(),
+                }
+                .to_json_string();
+                crate::drop_println!(config, "{}", msg);
+            }
+            TestFormat::Human => crate::drop_println!(
+                config,
+                "test {} ... bench: {:>14.2} ns/iter (+/- {:.2})",
+                name,
+                median,
+                deviation
+            ),
+            TestFormat::Junit => {}
+        }
+    }
+
+    /// Prints the result of a test harness like libtest does, for
+    /// `--format human`.
+    fn print_suite_result(&self, config: &Config, value: &serde_json::Value, outcome: &str) {
+        if !self.failures.is_empty() {
+            crate::drop_println!(config, "\nfailures:\n");
+            for (name, stdout) in &self.failures {
+                crate::drop_println!(config, "---- {} stdout ----\n{}", name, stdout);
+            }
+            crate::drop_println!(config, "\nfailures:");
+            for (name, _) in &self.failures {
+                crate::drop_println!(config, "    {}", name);
+            }
+        }
+        let count = |key: &str| value[key].as_u64().unwrap_or(0);
+        let result = if outcome == "ok" { "ok" } else { "FAILED" };
+        let time = value["exec_time"].as_f64().unwrap_or(0.0);
+        crate::drop_println!(
+            config,
+            "\ntest result: {}. {} passed; {} failed; {} ignored; {} measured; \
+             {} filtered out; finished in {:.2}s\n",
+            result,
+            count("passed"),
+            count("failed"),
+            count("ignored"),
+            count("measured"),
+            count("filtered_out"),
+            time
+        );
+    }
+
+    /// Prints the totals of all the test harnesses with `--format json`, or
+    /// writes the JUnit report with `--format junit`, and records the
+    /// durations of the tests with `--partition`.
+    fn finish(&self, ws: &Workspace<'_>, success: bool) -> CargoResult<()> {
+        let config = ws.config();
+        self.print_summary(config)?;
+        if self.record_timings {
+            let mut timings = load_test_timings(ws);
+            for suite in &self.suites {
+                for case in &suite.cases {
+                    if let Some(time) = case.time {
+                        timings.insert(format!("{}::{}", suite.name, case.name), time);
+                    }
+                }
+            }
+            paths::write(
+                test_timings_path(ws),
+                serde_json::to_string_pretty(&timings)?,
+            )?;
+        }
+        match self.format {
+            TestFormat::Human => {
+                if !self.flaky.is_empty() {
+                    crate::drop_println!(config, "flaky tests, which passed when run again:");
+                    for (suite, name, attempts) in &self.flaky {
+                        crate::drop_println!(
+                            config,
+                            "    {}::{} ... flaky-pass (attempt {})",
+                            suite,
+                            name,
+                            attempts
+                        );
+                    }
+                    crate::drop_println!(config);
+                }
+            }
+            TestFormat::Json => {
+                let msg = machine_message::TestFinished {
+                    success,
+                    passed: self.passed,
+                    failed: self.failed,
+                    ignored: self.ignored,
+                    measured: self.measured,
+                    filtered_out: self.filtered_out,
+                    flaky: self.flaky.len() as u64,
+                    failures: self
+                        .failed_targets
+                        .iter()
+                        .map(|failed| machine_message::TestFailure {
+                            package_id: failed.pkg.package_id(),
+                            target: &failed.target,
+                            executable: failed.executable.as_deref(),
+                            doctest: failed.executable.is_none(),
+                            tests: &failed.tests,
+                            rerun: self.rerun_command(failed),
+                        })
+                        .collect(),
+                }
+                .to_json_string();
+                crate::drop_println!(config, "{}", msg);
+            }
+            TestFormat::Junit => {
+                let path = ws.target_dir().as_path_unlocked().join("junit.xml");
+                paths::write(&path, self.junit_xml())?;
+                config
+                    .shell()
+                    .status("JUnit", format!("report saved to {}", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn junit_xml(&self) -> String {
+        fn count<'a>(cases: impl IntoIterator<Item = &'a JunitCase>, kind: &str) -> usize {
+            cases
+                .into_iter()
+                .filter(|case| matches!(case.failure, Some((k, _)) if k == kind))
+                .count()
+        }
+
+        let all_cases = || self.suites.iter().flat_map(|s| &s.cases);
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            xml,
+            "<testsuites tests=\"{}\" failures=\"{}\" skipped=\"{}\">",
+            all_cases().count(),
+            count(all_cases(), "failure"),
+            count(all_cases(), "skipped")
+        );
+        for suite in &self.suites {
+            let time: f64 = suite.cases.iter().filter_map(|case| case.time).sum();
+            let _ = writeln!(
+                xml,
+                "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" \
+                 time=\"{:.3}\">",
+                escape_xml(&suite.name),
+                suite.cases.len(),
+                count(&suite.cases, "failure"),
+                count(&suite.cases, "skipped"),
+                time
+            );
+            for case in &suite.cases {
+                let _ = write!(
+                    xml,
+                    "    <testcase classname=\"{}\" name=\"{}\"",
+                    escape_xml(&suite.name),
+                    escape_xml(&case.name)
+                );
+                if let Some(time) = case.time {
+                    let _ = write!(xml, " time=\"{:.3}\"", time);
+                }
+                if case.failure.is_none() && case.retries.is_empty() {
+                    xml.push_str("/>\n");
+                    continue;
+                }
+                xml.push_str(">\n");
+                // The earlier failed runs of a test which was run again, like
+                // the Maven Surefire plugin reports them.
+                let retry_kind = match case.failure {
+                    None => "flakyFailure",
+                    Some(_) => "rerunFailure",
+                };
+                let failures = case.retries.iter().map(|output| (retry_kind, output));
+                let failures =
+                    failures.chain(case.failure.as_ref().map(|(kind, output)| (*kind, output)));
+                for (kind, output) in failures {
+                    match output {
+                        None => {
+                            let _ = writeln!(xml, "      <{}/>", kind);
+                        }
+                        Some(output) => {
+                            let _ = writeln!(xml, "      <{0}>{1}</{0}>", kind, escape_xml(output));
+                        }
+                    }
+                }
+                xml.push_str("    </testcase>\n");
+            }
+            xml.push_str("  </testsuite>\n");
+        }
+        xml.push_str("</testsuites>\n");
+        xml
+    }
+}
+
+fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Sends the lines read from `pipe` to `tx`, from a new thread.
+fn read_lines<R: Read + Send + 'static>(pipe: R, is_out: bool, tx: Sender<(bool, String)>) {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(pipe);
+        let mut line = Vec::new();
+        while let Ok(n) = reader.read_until(b'\n', &mut line) {
+            if n == 0 {
+                break;
+            }
+            let text = String::from_utf8_lossy(&line);
+            if tx
+                .send((is_out, text.trim_end_matches(&['\r', '\n'][..]).to_string()))
+                .is_err()
+            {
+                break;
+            }
+            line.clear();
+        }
+    });
+}
+
+/// Returns the options for the test harness in `test_args`, leaving out the
+/// test name filters, to run the failed tests again.
+fn harness_options(test_args: &[&str]) -> Vec<String> {
+    // The options of libtest which take a value as the next argument.
+    const WITH_VALUE: &[&str] = &[
+        "--color",
+        "--format",
+        "--logfile",
+        "--shuffle-seed",
+        "--skip",
+        "--test-threads",
+        "-Z",
+    ];
+    let mut options = Vec::new();
+    let mut args = test_args.iter();
+    while let Some(arg) = args.next() {
+        if arg.starts_with('-') && *arg != "--exact" {
+            options.push(arg.to_string());
+            if WITH_VALUE.contains(arg) {
+                options.extend(args.next().map(|value| value.to_string()));
+            }
+        }
+    }
+    options
+}
+
+/// Quotes `arg` for a shell, if needed.
+fn shell_quote(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "_-:./=,+@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Returns the arguments for the test harness to keep its colors when its
+/// output goes through Cargo.
+fn harness_color_args(config: &Config, test_args: &[&str]) -> Vec<&'static str> {
+    if test_args.iter().any(|arg| arg.starts_with("--color")) {
+        return Vec::new();
+    }
+    let color = match config.shell().color_choice() {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::CargoAuto => atty::is(atty::Stream::Stdout),
+    };
+    if color {
+        vec!["--color", "always"]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Returns the name of the test from a `test NAME ... FAILED` line of the
+/// output of a test harness.
+fn failed_test_name(line: &str) -> Option<String> {
+    strip_ansi(line)
+        .trim_end()
+        .strip_prefix("test ")
+        .and_then(|text| text.strip_suffix(" ... FAILED"))
+        .map(|name| name.to_string())
+}
+
+/// Removes the ANSI escape sequences for colors from `s`.
+fn strip_ansi(s: &str) -> String {
+    let mut stripped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip up to the final letter of the escape sequence.
+            for c in &mut chars {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
 }
@@ -1,29 +1,53 @@
 use anyhow::bail;
 use std::collections::BTreeSet;
 use std::env;
+use std::io;
+use std::path::Path;
 
 use crate::core::PackageId;
 use crate::core::{PackageIdSpec, SourceId};
+use crate::drop_eprint;
 use crate::ops::common_for_install_and_uninstall::*;
 use crate::sources::PathSource;
-use crate::util::errors::CargoResult;
+use crate::util::errors::{CargoResult, CargoResultExt};
 use crate::util::paths;
+use crate::util::restricted_names::is_glob_pattern;
 use crate::util::Config;
 use crate::util::Filesystem;
 
+pub struct UninstallOptions {
+    /// Uninstall all installed packages instead of the given specs.
+    pub all: bool,
+    /// Do not ask for confirmation before uninstalling all packages.
+    pub yes: bool,
+}
+
 pub fn uninstall(
-    root: Option<&str>,
+    root: Option<&Path>,
     specs: Vec<&str>,
     bins: &[String],
+    opts: &UninstallOptions,
     config: &Config,
 ) -> CargoResult<()> {
+    let root = resolve_root(root, config)?;
+    // The specs to uninstall, along with how they are shown in the summary.
+    let expanded;
+    let specs: Vec<(&str, &str)> = if opts.all || specs.iter().any(is_glob_pattern) {
+        expanded = expand_specs(&root, &specs, opts, config)?;
+        expanded
+            .iter()
+            .map(|(label, spec)| (label.as_str(), spec.as_str()))
+            .collect()
+    } else {
+        specs.iter().map(|spec| (*spec, *spec)).collect()
+    };
+
     if specs.len() > 1 && !bins.is_empty() {
         bail!("A binary can only be associated with a single installed package, specifying multiple specs with --bin is redundant.");
     }
 
-    let root = resolve_root(root, config)?;
     let scheduled_error = if specs.len() == 1 {
-        uninstall_one(&root, specs[0], bins, config)?;
+        uninstall_one(&root, specs[0].1, bins, config)?;
         false
     } else if specs.is_empty() {
         uninstall_cwd(&root, bins, config)?;
@@ -31,13 +55,13 @@ pub fn uninstall(
     } else {
         let mut succeeded = vec![];
         let mut failed = vec![];
-        for spec in specs {
+        for (label, spec) in specs {
             let root = root.clone();
             match uninstall_one(&root, spec, bins, config) {
-                Ok(()) => succeeded.push(spec),
+                Ok(()) => succeeded.push(label),
                 Err(e) => {
                     crate::display_error(&e, &mut config.shell());
-                    failed.push(spec)
+                    failed.push(label)
                 }
             }
         }
@@ -70,6 +94,80 @@ pub fn uninstall(
     Ok(())
 }
 
+/// Replaces the glob patterns in `specs` with the installed packages whose
+/// names they match, or returns all installed packages with `--all`, once the
+/// user confirmed.
+///
+/// The packages are returned as a label, like `foo v1.0.0`, and a full
+/// package ID specification, since packages from different sources may share
+/// a name. Specs which aren't patterns are their own label.
+fn expand_specs(
+    root: &Filesystem,
+    specs: &[&str],
+    opts: &UninstallOptions,
+    config: &Config,
+) -> CargoResult<Vec<(String, String)>> {
+    let tracker = InstallTracker::load(config, root)?;
+    let mut installed: Vec<PackageId> = tracker
+        .all_installed_bins()
+        .map(|(pkg_id, _bins)| *pkg_id)
+        .collect();
+    installed.sort();
+    let expand = |pkg_id: &PackageId| {
+        (
+            format!("{} v{}", pkg_id.name(), pkg_id.version()),
+            PackageIdSpec::from_package_id(*pkg_id).to_string(),
+        )
+    };
+
+    if opts.all {
+        if installed.is_empty() {
+            bail!("no packages are installed in `{}`", root.display());
+        }
+        let expanded: Vec<(String, String)> = installed.iter().map(expand).collect();
+        if !opts.yes {
+            let labels: Vec<&str> = expanded.iter().map(|(label, _)| label.as_str()).collect();
+            drop_eprint!(
+                config,
+                "Uninstall {} package{} ({})? [y/N] ",
+                labels.len(),
+                if labels.len() == 1 { "" } else { "s" },
+                labels.join(", ")
+            );
+            let mut answer = String::new();
+            io::stdin().read_line(&mut answer)?;
+            if !matches!(answer.trim(), "y" | "Y" | "yes") {
+                bail!("uninstall cancelled, no packages were uninstalled");
+            }
+        }
+        return Ok(expanded);
+    }
+
+    let mut expanded = Vec::new();
+    for spec in specs {
+        if !is_glob_pattern(spec) {
+            expanded.push((spec.to_string(), spec.to_string()));
+            continue;
+        }
+        let pattern = glob::Pattern::new(spec)
+            .chain_err(|| format!("cannot build glob pattern from `{}`", spec))?;
+        let matches: Vec<(String, String)> = installed
+            .iter()
+            .filter(|pkg_id| pattern.matches(&pkg_id.name()))
+            .map(expand)
+            .collect();
+        if matches.is_empty() {
+            bail!("no installed packages match the pattern `{}`", spec);
+        }
+        for m in matches {
+            if !expanded.contains(&m) {
+                expanded.push(m);
+            }
+        }
+    }
+    Ok(expanded)
+}
+
 pub fn uninstall_one(
     root: &Filesystem,
     spec: &str,
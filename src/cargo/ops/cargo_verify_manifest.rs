@@ -0,0 +1,326 @@
+//! Implementation of `cargo verify-manifest`.
+//!
+//! The strict checks look for mistakes in the manifests of a workspace which
+//! Cargo otherwise accepts, often without a warning, since they don't prevent
+//! the packages from building.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+use cargo_platform::Platform;
+use serde::Serialize;
+
+use crate::core::{FeatureValue, Package, Workspace};
+use crate::util::interning::InternedString;
+use crate::util::{toml as cargo_toml, CargoResult, Config};
+
+pub struct VerifyManifestOptions {
+    /// Also run the strict checks, instead of only checking that the
+    /// manifests can be loaded.
+    pub strict: bool,
+}
+
+/// The result of `cargo verify-manifest`, printed as JSON.
+#[derive(Serialize)]
+pub struct ManifestReport {
+    pub success: bool,
+    pub problems: Vec<ManifestProblem>,
+}
+
+#[derive(Serialize)]
+pub struct ManifestProblem {
+    /// The kind of problem, like `unused-key`.
+    pub kind: &'static str,
+    pub manifest_path: PathBuf,
+    /// The key of the manifest the problem is about, if any.
+    pub key: Option<String>,
+    pub message: String,
+}
+
+impl ManifestReport {
+    fn push(&mut self, kind: &'static str, path: &Path, key: Option<String>, message: String) {
+        self.problems.push(ManifestProblem {
+            kind,
+            manifest_path: path.to_path_buf(),
+            key,
+            message,
+        });
+    }
+}
+
+/// Checks the manifests of the workspace at `manifest_path`.
+///
+/// A workspace which can't be loaded is reported as an `invalid` problem
+/// instead of an error, so that the report is always printed.
+pub fn verify_manifest(
+    manifest_path: &Path,
+    config: &Config,
+    opts: &VerifyManifestOptions,
+) -> CargoResult<ManifestReport> {
+    let mut report = ManifestReport {
+        success: true,
+        problems: Vec::new(),
+    };
+    let ws = match Workspace::new(manifest_path, config) {
+        Ok(ws) => ws,
+        Err(e) => {
+            report.success = false;
+            report.push("invalid", manifest_path, None, format!("{:?}", e));
+            return Ok(report);
+        }
+    };
+    if !opts.strict {
+        return Ok(report);
+    }
+
+    let mut manifests = ws
+        .members()
+        .map(|pkg| pkg.manifest_path().to_path_buf())
+        .collect::<BTreeSet<_>>();
+    manifests.insert(ws.root_manifest().to_path_buf());
+    for path in &manifests {
+        check_keys(path, config, &mut report)?;
+    }
+
+    let target_list = target_list(&ws)?;
+    for pkg in ws.members() {
+        check_targets(pkg, target_list.as_ref(), &mut report);
+        check_ignored(&ws, pkg, &mut report);
+    }
+
+    report.success = report.problems.is_empty();
+    Ok(report)
+}
+
+/// Reports the keys of the manifest which Cargo ignores, and the ones which
+/// are deprecated.
+fn check_keys(path: &Path, config: &Config, report: &mut ManifestReport) -> CargoResult<()> {
+    for key in cargo_toml::unused_manifest_keys(path, config)? {
+        let message = format!("unused manifest key: {}", key);
+        report.push("unused-key", path, Some(key), message);
+    }
+
+    let contents = crate::util::paths::read(path)?;
+    let toml = cargo_toml::parse(&contents, path, config)?;
+    let mut deprecated = Vec::new();
+    deprecated_keys(&toml, &mut deprecated);
+    for (key, message) in deprecated {
+        report.push("deprecated-key", path, Some(key), message);
+    }
+    Ok(())
+}
+
+/// Collects the deprecated keys of the manifest `toml`, with the message to
+/// report for each of them.
+fn deprecated_keys(toml: &toml::Value, deprecated: &mut Vec<(String, String)>) {
+    let table = match toml.as_table() {
+        Some(table) => table,
+        None => return,
+    };
+    if table.contains_key("project") {
+        renamed(deprecated, "project".to_string(), "package");
+    }
+    let package = table.get("package").or_else(|| table.get("project"));
+    if let Some(package) = package.and_then(|p| p.as_table()) {
+        if package.contains_key("publish-lockfile") {
+            let key = "package.publish-lockfile".to_string();
+            let message = format!("`{}` is deprecated and has no effect", key);
+            deprecated.push((key, message));
+        }
+    }
+
+    let mut dep_tables = vec![(String::new(), table)];
+    if let Some(targets) = table.get("target").and_then(|t| t.as_table()) {
+        for (platform, platform_table) in targets {
+            if let Some(platform_table) = platform_table.as_table() {
+                dep_tables.push((format!("target.{}.", platform), platform_table));
+            }
+        }
+    }
+    for (prefix, dep_table) in dep_tables {
+        for (old, new) in &[
+            ("dev_dependencies", "dev-dependencies"),
+            ("build_dependencies", "build-dependencies"),
+        ] {
+            if dep_table.contains_key(*old) {
+                renamed(deprecated, format!("{}{}", prefix, old), new);
+            }
+        }
+        for kind in &[
+            "dependencies",
+            "dev-dependencies",
+            "dev_dependencies",
+            "build-dependencies",
+            "build_dependencies",
+        ] {
+            let deps = match dep_table.get(*kind).and_then(|d| d.as_table()) {
+                Some(deps) => deps,
+                None => continue,
+            };
+            for (name, dep) in deps {
+                if dep.get("default_features").is_some() {
+                    renamed(
+                        deprecated,
+                        format!("{}{}.{}.default_features", prefix, kind, name),
+                        "default-features",
+                    );
+                }
+            }
+        }
+    }
+
+    let mut targets = Vec::new();
+    if let Some(lib) = table.get("lib") {
+        targets.push(("lib".to_string(), lib));
+    }
+    for kind in &["bin", "example", "test", "bench"] {
+        if let Some(array) = table.get(*kind).and_then(|t| t.as_array()) {
+            for (i, target) in array.iter().enumerate() {
+                targets.push((format!("{}.{}", kind, i), target));
+            }
+        }
+    }
+    for (prefix, target) in targets {
+        for (old, new) in &[("crate_type", "crate-type"), ("proc_macro", "proc-macro")] {
+            if target.get(*old).is_some() {
+                renamed(deprecated, format!("{}.{}", prefix, old), new);
+            }
+        }
+    }
+
+    fn renamed(deprecated: &mut Vec<(String, String)>, key: String, new: &str) {
+        let message = format!("`{}` is deprecated in favor of `{}`", key, new);
+        deprecated.push((key, message));
+    }
+}
+
+/// Returns the targets rustc knows about, or `None` if it can't list them.
+fn target_list(ws: &Workspace<'_>) -> CargoResult<Option<HashSet<String>>> {
+    let rustc = ws.config().load_global_rustc(Some(ws))?;
+    let mut process = rustc.process();
+    process.arg("--print").arg("target-list");
+    let output = match rustc.cached_output(&process) {
+        Ok((output, _error)) => output,
+        Err(_) => return Ok(None),
+    };
+    Ok(Some(output.lines().map(|line| line.to_string()).collect()))
+}
+
+/// Reports the `[target]` sections of `pkg` which can never match, and the
+/// features which only enable dependencies declared in them.
+fn check_targets(
+    pkg: &Package,
+    target_list: Option<&HashSet<String>>,
+    report: &mut ManifestReport,
+) {
+    let path = pkg.manifest_path();
+    let mut reported = HashSet::new();
+    // Whether each dependency, by name, can be used on some platform.
+    let mut usable = BTreeMap::new();
+    for dep in pkg.dependencies() {
+        let never = match dep.platform() {
+            Some(platform) => {
+                let message = never_matches(platform, target_list);
+                if let Some(message) = &message {
+                    if reported.insert(platform.clone()) {
+                        let key = format!("target.{}", platform);
+                        let message = format!("`{}` {}", key, message);
+                        report.push("never-matching-target", path, Some(key), message);
+                    }
+                }
+                message.is_some()
+            }
+            None => false,
+        };
+        *usable.entry(dep.name_in_toml()).or_insert(false) |= !never;
+    }
+
+    let features = pkg.summary().features();
+    let unusable = |name: &InternedString| usable.get(name) == Some(&false);
+    // Whether `name` is the implicit feature of an optional dependency.
+    let is_implicit = |name: &InternedString| match features.get(name).map(|v| v.as_slice()) {
+        Some([FeatureValue::Dep { dep_name }]) => dep_name == name,
+        _ => false,
+    };
+    for (feature, values) in features {
+        for value in values {
+            let dep_name = match value {
+                FeatureValue::Feature(name) if is_implicit(name) => name,
+                FeatureValue::Feature(_) => continue,
+                FeatureValue::Dep { dep_name } => dep_name,
+                FeatureValue::DepFeature { dep_name, .. } => dep_name,
+            };
+            // Implicit features of optional dependencies are reported through
+            // their `[target]` section.
+            if dep_name == feature || !unusable(dep_name) {
+                continue;
+            }
+            let key = format!("features.{}", feature);
+            let message = format!(
+                "feature `{}` includes `{}`, but `{}` is only a dependency for \
+                 targets which can never match",
+                feature, value, dep_name
+            );
+            report.push("unused-feature", path, Some(key), message);
+        }
+    }
+}
+
+/// Returns why `platform` can never match, if it can't.
+fn never_matches(platform: &Platform, target_list: Option<&HashSet<String>>) -> Option<String> {
+    match platform {
+        Platform::Name(name) => match target_list {
+            Some(list) if !list.contains(name) => Some(format!(
+                "is not a target known to rustc, and only matches a custom target \
+                 named `{}`",
+                name
+            )),
+            _ => None,
+        },
+        Platform::Cfg(expr) => {
+            if expr.can_match() {
+                None
+            } else {
+                Some("can never match, since its `cfg` expression is always false".to_string())
+            }
+        }
+    }
+}
+
+/// Reports the parts of the manifest of `pkg` which are ignored because it
+/// isn't the root of its workspace.
+fn check_ignored(ws: &Workspace<'_>, pkg: &Package, report: &mut ManifestReport) {
+    if pkg.manifest_path() == ws.root_manifest() {
+        return;
+    }
+    let manifest = pkg.manifest();
+    let mut ignored = Vec::new();
+    if manifest.original().has_profiles() {
+        ignored.push("profile");
+    }
+    if !manifest.replace().is_empty() {
+        ignored.push("replace");
+    }
+    if !manifest.patch().is_empty() {
+        ignored.push("patch");
+    }
+    if manifest.resolve_behavior().is_some()
+        && manifest.resolve_behavior() != Some(ws.resolve_behavior())
+    {
+        ignored.push("package.resolver");
+    }
+    for key in ignored {
+        let message = format!(
+            "`{}` is ignored, since the package is not the root of its workspace; \
+             set it in `{}` instead",
+            key,
+            ws.root_manifest().display()
+        );
+        report.push(
+            "ignored-key",
+            pkg.manifest_path(),
+            Some(key.to_string()),
+            message,
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use std::collections::{btree_map, BTreeMap, BTreeSet};
 use std::env;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
 use std::io::prelude::*;
 use std::io::SeekFrom;
 use std::path::{Path, PathBuf};
@@ -8,11 +10,14 @@ use anyhow::{bail, format_err};
 use serde::{Deserialize, Serialize};
 
 use crate::core::compiler::Freshness;
-use crate::core::{Dependency, Package, PackageId, Source, SourceId};
+use crate::core::dependency::DepKind;
+use crate::core::{Dependency, Package, PackageId, Source, SourceId, Workspace};
 use crate::ops::{self, CompileFilter, CompileOptions};
 use crate::sources::PathSource;
+use crate::util::config::{ConfigRelativePath, Definition, Value};
 use crate::util::errors::{CargoResult, CargoResultExt};
-use crate::util::Config;
+use crate::util::hex::{hash_u64_file, to_hex};
+use crate::util::{Config, Rustc, StableHasher};
 use crate::util::{FileLock, Filesystem};
 
 /// On-disk tracking for which package installed which binary.
@@ -76,11 +81,51 @@ struct InstallInfo {
     /// None if unknown (when loading from v1).
     /// Currently not used, possibly may be used in the future.
     rustc: Option<String>,
+    /// The version of rustc, like `1.47.0`.
+    /// None if unknown (when loading from v1 or an older v2).
+    rustc_version: Option<String>,
+    /// Where the package comes from.
+    /// None if unknown (when loading from v1 or an older v2).
+    source: Option<InstallSource>,
+    /// For packages installed from a path, a hash of their files, to tell
+    /// whether they changed since they were installed.
+    /// None for other sources, or if unknown.
+    source_hash: Option<String>,
     /// Forwards compatibility.
     #[serde(flatten)]
     other: BTreeMap<String, serde_json::Value>,
 }
 
+/// The source an installed package comes from, so that it can be installed
+/// again the same way.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct InstallSource {
+    /// One of `registry`, `local-registry`, `directory`, `git` or `path`.
+    kind: String,
+    url: String,
+    /// The branch, tag or revision which was requested, for git.
+    reference: Option<String>,
+    /// The commit which was installed, for git.
+    rev: Option<String>,
+}
+
+/// An installed package, for `cargo install --list --format json`.
+#[derive(Serialize)]
+pub struct InstalledPackage<'a> {
+    package_id: PackageId,
+    name: &'a str,
+    version: String,
+    source: InstallSource,
+    version_req: Option<&'a str>,
+    bins: &'a BTreeSet<String>,
+    features: &'a BTreeSet<String>,
+    all_features: bool,
+    no_default_features: bool,
+    profile: &'a str,
+    target: Option<&'a str>,
+    rustc_version: Option<&'a str>,
+}
+
 /// Tracking information for the set of installed packages.
 #[derive(Default, Deserialize, Serialize)]
 pub struct CrateListingV1 {
@@ -161,6 +206,7 @@ impl InstallTracker {
         opts: &CompileOptions,
         target: &str,
         _rustc: &str,
+        source_hash: Option<&str>,
     ) -> CargoResult<(Freshness, BTreeMap<String, Option<PackageId>>)> {
         let exes = exe_names(pkg, &opts.filter);
         // Check if any tracked exe's are already installed.
@@ -194,8 +240,9 @@ impl InstallTracker {
         if matching_duplicates.len() == duplicates.len() {
             // Determine if it is dirty or fresh.
             let source_id = pkg.package_id().source_id();
-            if source_id.is_path() {
-                // `cargo install --path ...` is always rebuilt.
+            if source_id.is_path() && source_hash.is_none() {
+                // `cargo install --path ...` is rebuilt unless its files are
+                // known to be unchanged.
                 return Ok((Freshness::Dirty, duplicates));
             }
             let is_up_to_date = |dupe_pkg_id| {
@@ -215,6 +262,7 @@ impl InstallTracker {
                 dupe_pkg_id.version() == pkg.version()
                     && dupe_pkg_id.source_id() == source_id
                     && precise_equal
+                    && info.source_hash.as_deref() == source_hash
                     && info.is_up_to_date(opts, target, &exes)
             };
             if matching_duplicates.iter().all(is_up_to_date) {
@@ -268,10 +316,11 @@ impl InstallTracker {
         version_req: Option<String>,
         opts: &CompileOptions,
         target: &str,
-        rustc: &str,
+        rustc: &Rustc,
+        source_hash: Option<&str>,
     ) {
         self.v2
-            .mark_installed(package, bins, version_req, opts, target, rustc);
+            .mark_installed(package, bins, version_req, opts, target, rustc, source_hash);
         self.v1.mark_installed(package, bins);
     }
 
@@ -293,6 +342,32 @@ impl InstallTracker {
         Ok(())
     }
 
+    /// All the installed packages, with what is known about how they were
+    /// installed.
+    pub fn installed_packages(&self) -> Vec<InstalledPackage<'_>> {
+        self.v2
+            .installs
+            .iter()
+            .map(|(pkg_id, info)| InstalledPackage {
+                package_id: *pkg_id,
+                name: pkg_id.name().as_str(),
+                version: pkg_id.version().to_string(),
+                source: info
+                    .source
+                    .clone()
+                    .unwrap_or_else(|| InstallSource::new(pkg_id.source_id())),
+                version_req: info.version_req.as_deref(),
+                bins: &info.bins,
+                features: &info.features,
+                all_features: info.all_features,
+                no_default_features: info.no_default_features,
+                profile: &info.profile,
+                target: info.target.as_deref(),
+                rustc_version: info.rustc_version.as_deref(),
+            })
+            .collect()
+    }
+
     /// Iterator of all installed binaries.
     /// Items are `(pkg_id, bins)` where `bins` is the set of binaries that
     /// package installed.
@@ -402,7 +477,8 @@ impl CrateListingV2 {
         version_req: Option<String>,
         opts: &CompileOptions,
         target: &str,
-        rustc: &str,
+        rustc: &Rustc,
+        source_hash: Option<&str>,
     ) {
         // Remove bins from any other packages.
         for info in &mut self.installs.values_mut() {
@@ -428,7 +504,10 @@ impl CrateListingV2 {
             info.no_default_features = opts.no_default_features;
             info.profile = opts.build_config.requested_profile.to_string();
             info.target = Some(target.to_string());
-            info.rustc = Some(rustc.to_string());
+            info.rustc = Some(rustc.verbose_version.clone());
+            info.rustc_version = Some(rustc.version.to_string());
+            info.source = Some(InstallSource::new(pkg.package_id().source_id()));
+            info.source_hash = source_hash.map(String::from);
         } else {
             self.installs.insert(
                 pkg.package_id(),
@@ -440,7 +519,10 @@ impl CrateListingV2 {
                     no_default_features: opts.no_default_features,
                     profile: opts.build_config.requested_profile.to_string(),
                     target: Some(target.to_string()),
-                    rustc: Some(rustc.to_string()),
+                    rustc: Some(rustc.verbose_version.clone()),
+                    rustc_version: Some(rustc.version.to_string()),
+                    source: Some(InstallSource::new(pkg.package_id().source_id())),
+                    source_hash: source_hash.map(String::from),
                     other: BTreeMap::new(),
                 },
             );
@@ -482,6 +564,9 @@ impl InstallInfo {
             profile: "release".to_string(),
             target: None,
             rustc: None,
+            rustc_version: None,
+            source: None,
+            source_hash: None,
             other: BTreeMap::new(),
         }
     }
@@ -499,8 +584,76 @@ impl InstallInfo {
     }
 }
 
+impl InstallSource {
+    fn new(source_id: SourceId) -> InstallSource {
+        let kind = if source_id.is_git() {
+            "git"
+        } else if source_id.is_path() {
+            "path"
+        } else if source_id.is_remote_registry() {
+            "registry"
+        } else if source_id.is_registry() {
+            "local-registry"
+        } else {
+            "directory"
+        };
+        let reference = source_id
+            .git_reference()
+            .and_then(|reference| reference.pretty_ref())
+            .map(|reference| reference.to_string());
+        InstallSource {
+            kind: kind.to_string(),
+            url: source_id.url().to_string(),
+            reference,
+            rev: source_id
+                .precise()
+                .filter(|_| source_id.is_git())
+                .map(String::from),
+        }
+    }
+}
+
+/// Hashes the files of the current package of `ws`, of its path dependencies
+/// and of its lock file, to tell whether they changed since the package was
+/// installed from a path.
+pub fn path_source_hash(ws: &Workspace<'_>) -> CargoResult<String> {
+    let mut pkgs = BTreeMap::new();
+    let mut pending = vec![ws.current()?.clone()];
+    while let Some(pkg) = pending.pop() {
+        if pkgs.contains_key(pkg.root()) {
+            continue;
+        }
+        for dep in pkg.dependencies() {
+            if dep.kind() == DepKind::Development || !dep.source_id().is_path() {
+                continue;
+            }
+            if let Ok(path) = dep.source_id().url().to_file_path() {
+                pending.push(ws.load(&path.join("Cargo.toml"))?);
+            }
+        }
+        pkgs.insert(pkg.root().to_path_buf(), pkg);
+    }
+    let mut hasher = StableHasher::new();
+    for pkg in pkgs.values() {
+        let src = PathSource::new(pkg.root(), pkg.package_id().source_id(), ws.config());
+        let mut files = src.list_files(pkg)?;
+        files.sort();
+        for file in files {
+            file.strip_prefix(pkg.root())
+                .unwrap_or(&file)
+                .hash(&mut hasher);
+            hasher.write_u64(hash_u64_file(&File::open(&file)?)?);
+        }
+    }
+    let lock = ws.root().join("Cargo.lock");
+    if lock.exists() {
+        hasher.write_u64(hash_u64_file(&File::open(&lock)?)?);
+    }
+    Ok(to_hex(hasher.finish()))
+}
+
 /// Determines the root directory where installation is done.
-pub fn resolve_root(flag: Option<&str>, config: &Config) -> CargoResult<Filesystem> {
+pub fn resolve_root(flag: Option<&Path>, config: &Config) -> CargoResult<Filesystem> {
     let config_root = config.get_path("install.root")?;
     Ok(flag
         .map(PathBuf::from)
@@ -510,6 +663,23 @@ pub fn resolve_root(flag: Option<&str>, config: &Config) -> CargoResult<Filesyst
         .unwrap_or_else(|| config.home().clone()))
 }
 
+/// Returns the `install.root` set in a configuration file of the current
+/// directory or one of its parents, rather than in Cargo's home, for
+/// `-Z local-install-root`. Unlike the other values of `install.root`, it's
+/// always relative to the directory containing the `.cargo` directory.
+pub fn project_install_root(config: &Config) -> CargoResult<Option<PathBuf>> {
+    let root = match config.get::<Option<Value<ConfigRelativePath>>>("install.root")? {
+        Some(root) => root,
+        None => return Ok(None),
+    };
+    match &root.definition {
+        Definition::Path(file) if !file.starts_with(config.home().as_path_unlocked()) => {
+            Ok(Some(root.val.resolve_path(config)))
+        }
+        _ => Ok(None),
+    }
+}
+
 /// Determines the `PathSource` from a `SourceId`.
 pub fn path_source(source_id: SourceId, config: &Config) -> CargoResult<PathSource<'_>> {
     let path = source_id
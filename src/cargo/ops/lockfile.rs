@@ -33,6 +33,11 @@ pub fn resolve_to_string(ws: &Workspace<'_>, resolve: &mut Resolve) -> CargoResu
 }
 
 pub fn write_pkg_lockfile(ws: &Workspace<'_>, resolve: &mut Resolve) -> CargoResult<()> {
+    let pinned_version = pinned_lockfile_version(ws)?;
+    if let Some(version) = pinned_version {
+        resolve.set_version(version);
+    }
+
     let (orig, mut out, ws_root) = resolve_to_string_orig(ws, resolve)?;
 
     // If the lock file contents haven't changed so don't rewrite it. This is
@@ -66,8 +71,8 @@ pub fn write_pkg_lockfile(ws: &Workspace<'_>, resolve: &mut Resolve) -> CargoRes
     // encoding to whatever the latest default is. That way we can slowly roll
     // out lock file updates as they're otherwise already updated, and changes
     // which don't touch dependencies won't seemingly spuriously update the lock
-    // file.
-    if resolve.version() < ResolveVersion::default() {
+    // file. A version pinned through config is left alone.
+    if pinned_version.is_none() && resolve.version() < ResolveVersion::default() {
         resolve.set_version(ResolveVersion::default());
         out = serialize_resolve(resolve, orig.as_deref());
     }
@@ -84,6 +89,34 @@ pub fn write_pkg_lockfile(ws: &Workspace<'_>, resolve: &mut Resolve) -> CargoRes
     Ok(())
 }
 
+/// Returns the lock file version requested with the `lockfile.version` config
+/// value, if any.
+fn pinned_lockfile_version(ws: &Workspace<'_>) -> CargoResult<Option<ResolveVersion>> {
+    let config = ws.config();
+    let version = match config.get::<Option<u32>>("lockfile.version")? {
+        Some(version) => version,
+        None => return Ok(None),
+    };
+    if !config.cli_unstable().lockfile_version {
+        anyhow::bail!(
+            "the `lockfile.version` config value requires the \
+             `-Z lockfile-version` command-line option"
+        );
+    }
+    let version = match version {
+        1 => ResolveVersion::V1,
+        2 => ResolveVersion::V2,
+        3 => ResolveVersion::V3,
+        4 => ResolveVersion::V4,
+        n => anyhow::bail!(
+            "lock file version `{}` specified in `lockfile.version` is not supported, \
+             expected a version from 1 to 4",
+            n
+        ),
+    };
+    Ok(Some(version))
+}
+
 fn resolve_to_string_orig(
     ws: &Workspace<'_>,
     resolve: &mut Resolve,
@@ -139,19 +172,28 @@ fn serialize_resolve(resolve: &Resolve, orig: Option<&str>) -> String {
     }
 
     let deps = toml["package"].as_array().unwrap();
-    for dep in deps {
-        let dep = dep.as_table().unwrap();
+    if resolve.version() >= ResolveVersion::V4 {
+        emit_inline_packages("package", deps, &mut out);
+    } else {
+        for dep in deps {
+            let dep = dep.as_table().unwrap();
 
-        out.push_str("[[package]]\n");
-        emit_package(dep, &mut out);
+            out.push_str("[[package]]\n");
+            emit_package(dep, &mut out);
+        }
     }
 
     if let Some(patch) = toml.get("patch") {
         let list = patch["unused"].as_array().unwrap();
-        for entry in list {
-            out.push_str("[[patch.unused]]\n");
-            emit_package(entry.as_table().unwrap(), &mut out);
-            out.push('\n');
+        if resolve.version() >= ResolveVersion::V4 {
+            out.push_str("[patch]\n");
+            emit_inline_packages("unused", list, &mut out);
+        } else {
+            for entry in list {
+                out.push_str("[[patch.unused]]\n");
+                emit_package(entry.as_table().unwrap(), &mut out);
+                out.push('\n');
+            }
         }
     }
 
@@ -201,6 +243,9 @@ fn emit_package(dep: &toml::value::Table, out: &mut String) {
     if dep.contains_key("checksum") {
         out.push_str(&format!("checksum = {}\n", &dep["checksum"]));
     }
+    if dep.contains_key("yanked") {
+        out.push_str(&format!("yanked = {}\n", &dep["yanked"]));
+    }
 
     if let Some(s) = dep.get("dependencies") {
         let slice = s.as_array().unwrap();
@@ -219,3 +264,25 @@ fn emit_package(dep: &toml::value::Table, out: &mut String) {
         out.push_str(&format!("replace = {}\n\n", &dep["replace"]));
     }
 }
+
+/// Emits an array of packages with each package as an inline table on its own
+/// line, as used by `ResolveVersion::V4`.
+fn emit_inline_packages(key: &str, packages: &[toml::Value], out: &mut String) {
+    out.push_str(&format!("{} = [\n", key));
+    for package in packages {
+        let package = package.as_table().unwrap();
+        let mut fields = vec![
+            format!("name = {}", &package["name"]),
+            format!("version = {}", &package["version"]),
+        ];
+        for key in &["source", "checksum", "yanked", "dependencies", "replace"] {
+            match package.get(*key) {
+                Some(toml::Value::Array(a)) if a.is_empty() => {}
+                Some(value) => fields.push(format!("{} = {}", key, value)),
+                None => {}
+            }
+        }
+        out.push_str(&format!("    {{ {} }},\n", fields.join(", ")));
+    }
+    out.push_str("]\n\n");
+}
@@ -1,52 +1,82 @@
+pub use self::advisories::check_advisories;
 pub use self::cargo_clean::{clean, CleanOptions};
 pub use self::cargo_compile::{
     compile, compile_with_exec, compile_ws, create_bcx, resolve_all_features, CompileOptions,
 };
 pub use self::cargo_compile::{CompileFilter, FilterRule, LibRule, Packages};
-pub use self::cargo_doc::{doc, DocOptions};
-pub use self::cargo_fetch::{fetch, FetchOptions};
+pub use self::cargo_daemon::{daemon, DaemonOptions};
+pub use self::cargo_doc::{doc, doc_features_matrix, DocOptions};
+pub use self::cargo_expand::{expand, ExpandOptions};
+pub use self::cargo_fetch::{explain_offline_error, fetch, offline_report};
+pub use self::cargo_fetch::{FetchOptions, OfflineReport};
 pub use self::cargo_generate_lockfile::generate_lockfile;
 pub use self::cargo_generate_lockfile::update_lockfile;
 pub use self::cargo_generate_lockfile::UpdateOptions;
 pub use self::cargo_install::{install, install_list};
+pub use self::cargo_licenses::{licenses, LicensesFormat, LicensesOptions};
 pub use self::cargo_new::{init, new, NewOptions, VersionControl};
-pub use self::cargo_output_metadata::{output_metadata, ExportInfo, OutputMetadataOptions};
-pub use self::cargo_package::{package, PackageOpts};
+pub use self::cargo_output_metadata::{
+    output_metadata, output_metadata_ndjson, ExportInfo, OutputMetadataOptions,
+};
+pub use self::cargo_package::{
+    crate_size, member_publish_name, member_version_req, package, package_and_check, GitVcsInfo,
+    ListFormat, PackageOpts, VcsInfo,
+};
+pub use self::cargo_packaging_info::{packaging_info, PackagingInfoOptions};
 pub use self::cargo_pkgid::pkgid;
 pub use self::cargo_read_manifest::{read_package, read_packages};
 pub use self::cargo_run::run;
-pub use self::cargo_test::{run_benches, run_tests, TestOptions};
-pub use self::cargo_uninstall::uninstall;
+pub use self::cargo_test::{
+    list_tests, parallel_doctest_args, run_benches, run_tests, test_timeout, PartitionStrategy,
+    TestFormat, TestOptions, TestPartition,
+};
+pub use self::cargo_uninstall::{uninstall, UninstallOptions};
+pub use self::cargo_verify_manifest::{
+    verify_manifest, ManifestProblem, ManifestReport, VerifyManifestOptions,
+};
+pub use self::common_for_install_and_uninstall::project_install_root;
 pub use self::fix::{fix, fix_maybe_exec_rustc, FixOptions};
 pub use self::lockfile::{load_pkg_lockfile, resolve_to_string, write_pkg_lockfile};
 pub use self::registry::HttpTimeout;
 pub use self::registry::{configure_http_handle, http_handle, http_handle_and_timeout};
 pub use self::registry::{modify_owners, yank, OwnersOptions, PublishOpts};
 pub use self::registry::{needs_custom_http_transport, registry_login, registry_logout, search};
-pub use self::registry::{publish, registry_configuration, RegistryConfig};
+pub use self::registry::{
+    publish, publish_default_registry, registry_configuration, registry_token,
+    registry_trusted_keys, RegistryConfig,
+};
 pub use self::resolve::{
-    add_overrides, get_resolved_packages, resolve_with_previous, resolve_ws, resolve_ws_with_opts,
+    add_overrides, collect_used_deps, get_resolved_packages, resolve_with_previous, resolve_ws,
+    resolve_ws_with_opts,
 };
+pub use self::subcommand_context::{SubcommandContext, SUBCOMMAND_CONTEXT_ENV};
 pub use self::vendor::{vendor, VendorOptions};
 
+mod advisories;
 mod cargo_clean;
 mod cargo_compile;
+mod cargo_daemon;
 mod cargo_doc;
+mod cargo_expand;
 mod cargo_fetch;
 mod cargo_generate_lockfile;
 mod cargo_install;
+mod cargo_licenses;
 mod cargo_new;
 mod cargo_output_metadata;
 mod cargo_package;
+mod cargo_packaging_info;
 mod cargo_pkgid;
 mod cargo_read_manifest;
 mod cargo_run;
 mod cargo_test;
 mod cargo_uninstall;
+mod cargo_verify_manifest;
 mod common_for_install_and_uninstall;
 mod fix;
 mod lockfile;
 mod registry;
 mod resolve;
+mod subcommand_context;
 pub mod tree;
 mod vendor;
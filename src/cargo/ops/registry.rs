@@ -1,32 +1,42 @@
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
-use std::io::{self, BufRead};
+use std::io::{self, BufRead, Seek, SeekFrom};
 use std::iter::repeat;
 use std::path::PathBuf;
 use std::str;
-use std::time::Duration;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 use std::{cmp, env};
 
 use anyhow::{bail, format_err};
-use crates_io::{self, NewCrate, NewCrateDependency, Registry};
+use crates_io::{self, NewCrate, NewCrateDependency, RateLimited, Registry};
 use curl::easy::{Easy, InfoType, SslOpt, SslVersion};
 use log::{log, Level};
 use percent_encoding::{percent_encode, NON_ALPHANUMERIC};
+use semver::VersionReq;
+use serde::de::IgnoredAny;
 
 use crate::core::dependency::DepKind;
 use crate::core::manifest::ManifestMetadata;
+use crate::core::shell::Verbosity;
 use crate::core::source::Source;
-use crate::core::{Package, SourceId, Workspace};
+use crate::core::{Dependency, Feature, Package, SourceId, Workspace};
 use crate::ops;
 use crate::sources::{RegistrySource, SourceConfigMap, CRATES_IO_REGISTRY};
+use crate::util::compression::CrateCompression;
 use crate::util::config::{self, Config, SslVersionConfig, SslVersionConfigRange};
+use crate::util::crate_signature;
 use crate::util::errors::{CargoResult, CargoResultExt};
 use crate::util::important_paths::find_root_manifest_for_wd;
-use crate::util::IntoUrl;
+use crate::util::paseto::SecretKey;
 use crate::util::{paths, validate_package_name};
+use crate::util::{IntoUrl, Sha256};
 use crate::{drop_print, drop_println, version};
 
 mod auth;
+mod provenance;
+
+pub use self::auth::Mutation;
 
 /// Registry settings loaded from config files.
 ///
@@ -39,6 +49,10 @@ pub struct RegistryConfig {
     pub token: Option<String>,
     /// Process used for fetching a token.
     pub credential_process: Option<(PathBuf, Vec<String>)>,
+    /// The secret key signing asymmetric tokens, as a PASERK.
+    pub secret_key: Option<String>,
+    /// The subject of asymmetric tokens, for registries which need one.
+    pub secret_key_subject: Option<String>,
 }
 
 pub struct PublishOpts<'cfg> {
@@ -54,9 +68,190 @@ pub struct PublishOpts<'cfg> {
     pub features: Vec<String>,
     pub all_features: bool,
     pub no_default_features: bool,
+    /// Wait for the published version to be available in the index, even if
+    /// `publish.wait` isn't set.
+    pub wait: bool,
+    /// Publish all members of the workspace, in the order of their
+    /// dependencies on each other.
+    pub workspace: bool,
+    /// Generate the SLSA provenance of the packages, see `provenance.rs`.
+    pub provenance: bool,
+}
+
+/// The `[publish]` table of the configuration.
+#[derive(Default, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct PublishConfig {
+    /// Whether to wait for the published version to be available.
+    wait: Option<bool>,
+    /// How long to wait for it, in seconds.
+    timeout: Option<u64>,
+    // Read by `publish_default_registry` and `check_denied_registries`,
+    // which need to know where they are defined.
+    #[allow(dead_code)]
+    default_registry: Option<IgnoredAny>,
+    #[allow(dead_code)]
+    deny_registries: Option<IgnoredAny>,
 }
 
 pub fn publish(ws: &Workspace<'_>, opts: &PublishOpts<'_>) -> CargoResult<()> {
+    if opts.workspace {
+        return publish_workspace(ws, opts);
+    }
+    publish_package(ws, opts, opts.verify, opts.wait)
+}
+
+/// Publishes the members of the workspace one after the other, each after
+/// the members it depends on.
+///
+/// Path dependencies are replaced with their version requirements when
+/// packaging, or with the version of the member they point to if they don't
+/// have one, so Cargo waits for each member to be available in the index
+/// before packaging the members which depend on it. With `--dry-run`, the
+/// members which depend on other members can't be built from the registry,
+/// and are packaged without verification.
+fn publish_workspace(ws: &Workspace<'_>, opts: &PublishOpts<'_>) -> CargoResult<()> {
+    let order = publish_order(ws)?;
+    let mut earlier = HashSet::new();
+    for (i, pkg) in order.iter().enumerate() {
+        let mut member_ws = Workspace::new(pkg.manifest_path(), opts.config)?;
+        member_ws.set_path_dep_version(ws.path_dep_version());
+        let depends_on_earlier = pkg
+            .dependencies()
+            .iter()
+            .any(|dep| earlier.contains(&dep.source_id()));
+        let verify = opts.verify && !(opts.dry_run && depends_on_earlier);
+        if opts.verify && !verify {
+            opts.config.shell().warn(format!(
+                "`{}` is not verified, since it depends on members of the \
+                 workspace which are not published with `--dry-run`",
+                pkg.name()
+            ))?;
+        }
+        let last = i + 1 == order.len();
+        publish_package(&member_ws, opts, verify, opts.wait || !last)?;
+        earlier.insert(pkg.package_id().source_id());
+    }
+    Ok(())
+}
+
+/// Returns the registry `cargo publish` uses when `--registry` isn't passed:
+/// `publish.default-registry` if it is set, or else `registry.default`.
+pub fn publish_default_registry(config: &Config) -> CargoResult<Option<String>> {
+    match config.get_string("publish.default-registry")? {
+        Some(registry) => {
+            validate_package_name(&registry.val, "registry name", "").chain_err(|| {
+                format!(
+                    "invalid `publish.default-registry` in {}",
+                    registry.definition
+                )
+            })?;
+            if registry.val == CRATES_IO_REGISTRY {
+                Ok(None)
+            } else {
+                Ok(Some(registry.val))
+            }
+        }
+        None => config.default_registry(),
+    }
+}
+
+/// Fails if the registry `reg_id`, named `name` if it was selected by name,
+/// is listed in `publish.deny-registries`. `by_index` is whether it was
+/// selected with `--index` instead.
+fn check_denied_registries(
+    config: &Config,
+    pkg: &Package,
+    name: Option<&str>,
+    by_index: bool,
+    reg_id: SourceId,
+) -> CargoResult<()> {
+    let denied = match config.get_list("publish.deny-registries")? {
+        Some(denied) => denied.val,
+        None => return Ok(()),
+    };
+    let name = match name {
+        Some(name) => name.to_string(),
+        None if !by_index || reg_id.is_default_registry() => CRATES_IO_REGISTRY.to_string(),
+        // Registries given with `--index` can only be denied by name if they
+        // are configured in `registries`.
+        None => match find_registry_name(config, reg_id)? {
+            Some(name) => name,
+            None => return Ok(()),
+        },
+    };
+    if let Some((_, definition)) = denied.iter().find(|(denied, _)| *denied == name) {
+        bail!(
+            "`{}` cannot be published.\n\
+             The registry `{}` is denied by `publish.deny-registries` in {}.",
+            pkg.name(),
+            name,
+            definition
+        );
+    }
+    Ok(())
+}
+
+/// Returns the members of the workspace to publish, each after the members
+/// it depends on. Members with `publish = false` are left out.
+///
+/// Development dependencies are ignored, since they are allowed to depend on
+/// each other in a cycle and aren't needed to build the package.
+fn publish_order<'a>(ws: &'a Workspace<'_>) -> CargoResult<Vec<&'a Package>> {
+    let mut remaining = ws
+        .members()
+        .filter(|pkg| !matches!(pkg.publish(), Some(registries) if registries.is_empty()))
+        .map(|pkg| (pkg.name(), pkg))
+        .collect::<BTreeMap<_, _>>();
+    if remaining.is_empty() {
+        bail!("no members of the workspace can be published");
+    }
+    let member_ids = remaining
+        .values()
+        .map(|pkg| pkg.package_id().source_id())
+        .collect::<HashSet<_>>();
+    let mut published = HashSet::new();
+    let mut order = Vec::new();
+    while !remaining.is_empty() {
+        let ready = remaining
+            .values()
+            .filter(|pkg| {
+                pkg.dependencies().iter().all(|dep| {
+                    dep.kind() == DepKind::Development
+                        || !member_ids.contains(&dep.source_id())
+                        || published.contains(&dep.source_id())
+                })
+            })
+            .map(|pkg| pkg.name())
+            .collect::<Vec<_>>();
+        if ready.is_empty() {
+            let names = remaining
+                .keys()
+                .map(|name| format!("`{}`", name))
+                .collect::<Vec<_>>();
+            bail!(
+                "the workspace members {} depend on each other in a cycle, \
+                 and cannot be published",
+                names.join(", ")
+            );
+        }
+        for name in ready {
+            let pkg = remaining.remove(&name).unwrap();
+            published.insert(pkg.package_id().source_id());
+            order.push(pkg);
+        }
+    }
+    Ok(order)
+}
+
+/// Publishes the current package of `ws`. If `wait` is set, Cargo waits for
+/// it to be available in the index even if `publish.wait` isn't set.
+fn publish_package(
+    ws: &Workspace<'_>,
+    opts: &PublishOpts<'_>,
+    verify: bool,
+    wait: bool,
+) -> CargoResult<()> {
     let pkg = ws.current()?;
     let mut publish_registry = opts.registry.clone();
 
@@ -89,24 +284,36 @@ pub fn publish(ws: &Workspace<'_>, opts: &PublishOpts<'_>) -> CargoResult<()> {
         }
     }
 
-    let (mut registry, _reg_cfg, reg_id) = registry(
+    let (mut registry, reg_cfg, reg_id) = registry(
         opts.config,
         opts.token.clone(),
         opts.index.clone(),
-        publish_registry,
+        publish_registry.clone(),
         true,
         !opts.dry_run,
+        Some(Mutation::PrePublish),
     )?;
-    verify_dependencies(pkg, &registry, reg_id)?;
+    check_denied_registries(
+        opts.config,
+        pkg,
+        publish_registry.as_deref(),
+        opts.index.is_some(),
+        reg_id,
+    )?;
+    let (compression, max_upload_size) = publish_limits(opts.config, reg_id)?;
+    let max_upload_size = preflight(ws, &registry, reg_id, compression, max_upload_size)?;
+    let signing_key = registry_signing_key(opts.config, publish_registry.as_deref())?;
+    let metadata = registry_metadata(pkg, publish_registry.as_deref())?;
+    let started = SystemTime::now();
 
     // Prepare a tarball, with a non-suppressible warning if metadata
     // is missing since this is being put online.
-    let tarball = ops::package(
+    let tarball = ops::package_and_check(
         ws,
         &ops::PackageOpts {
             config: opts.config,
-            verify: opts.verify,
-            list: false,
+            verify,
+            list: None,
             check_metadata: true,
             allow_dirty: opts.allow_dirty,
             targets: opts.targets.clone(),
@@ -114,38 +321,307 @@ pub fn publish(ws: &Workspace<'_>, opts: &PublishOpts<'_>) -> CargoResult<()> {
             features: opts.features.clone(),
             all_features: opts.all_features,
             no_default_features: opts.no_default_features,
+            compression,
+        },
+        |size| {
+            let problem = max_upload_size.and_then(|max| size_problem(size, max, reg_id));
+            report_problems(pkg, reg_id, problem.into_iter().collect())
         },
     )?
     .unwrap();
 
+    let mut file = tarball.file();
+    file.seek(SeekFrom::Start(0))?;
+    let cksum = Sha256::new()
+        .update_file(file)
+        .chain_err(|| "failed to read the packaged crate")?
+        .finish_hex();
+    let vers = pkg.version().to_string();
+    let signature =
+        signing_key.map(|key| crate_signature::sign(&key, &pkg.published_name(), &vers, &cksum));
+    let provenance = if opts.provenance {
+        let provenance =
+            provenance::provenance(ws, pkg, tarball.file(), &cksum, reg_id, opts, started)?;
+        if !opts.dry_run && accepts_provenance(opts.config, reg_id)? {
+            Some(provenance)
+        } else {
+            let path =
+                tarball
+                    .parent()
+                    .join(format!("{}-{}.provenance.json", pkg.published_name(), vers));
+            opts.config
+                .shell()
+                .status("Writing", format!("provenance to {}", path.display()))?;
+            paths::write(&path, serde_json::to_string_pretty(&provenance)? + "\n")?;
+            None
+        }
+    } else {
+        None
+    };
+
+    // Asymmetric tokens are signed for the exact tarball being published, so
+    // the token checked before packaging is replaced.
+    if !opts.dry_run && opts.token.is_none() && reg_cfg.token.is_none() {
+        if reg_cfg.secret_key.is_some() {
+            let token = auth::auth_token(
+                opts.config,
+                None,
+                &reg_cfg,
+                reg_id,
+                publish_registry.as_deref(),
+                registry.host(),
+                Some(Mutation::Publish {
+                    name: &pkg.published_name(),
+                    vers: &vers,
+                    cksum: &cksum,
+                }),
+            )?;
+            registry.set_token(Some(token));
+        }
+    }
+
     // Upload said tarball to the specified destination
     opts.config
         .shell()
         .status("Uploading", pkg.package_id().to_string())?;
     transmit(
-        opts.config,
+        ws,
         pkg,
         tarball.file(),
         &mut registry,
         reg_id,
+        signature,
+        metadata,
+        provenance,
         opts.dry_run,
     )?;
 
+    let publish_config = opts
+        .config
+        .get::<Option<PublishConfig>>("publish")?
+        .unwrap_or_default();
+    if !opts.dry_run && (wait || publish_config.wait == Some(true)) {
+        let timeout = Duration::from_secs(publish_config.timeout.unwrap_or(60));
+        wait_for_publish(opts.config, reg_id, pkg, timeout)?;
+    }
+
     Ok(())
 }
 
-fn verify_dependencies(
+/// Polls the index of the registry `reg_id` until the version of `pkg` which
+/// was just published can be resolved, or `timeout` expires.
+fn wait_for_publish(
+    config: &Config,
+    reg_id: SourceId,
     pkg: &Package,
+    timeout: Duration,
+) -> CargoResult<()> {
+    const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+    let version_req = format!("={}", pkg.version());
+    let name = format!("{} v{}", pkg.published_name(), pkg.version());
+    let query = Dependency::parse_no_deprecated(pkg.published_name(), Some(&version_req), reg_id)?;
+    config.shell().status(
+        "Waiting",
+        format!(
+            "on `{}` to be available at {}",
+            name,
+            reg_id.display_index()
+        ),
+    )?;
+
+    let start = Instant::now();
+    loop {
+        {
+            let _lock = config.acquire_package_cache_lock()?;
+            // The index was already updated in this session, and is fetched
+            // again on each attempt. Its `Updating` status is not repeated.
+            config.updated_sources().remove(&reg_id);
+            let mut src = RegistrySource::remote(reg_id, &HashSet::new(), config);
+            let verbosity = config.shell().verbosity();
+            config.shell().set_verbosity(Verbosity::Quiet);
+            let result = src.update().and_then(|()| src.query_vec(&query));
+            config.shell().set_verbosity(verbosity);
+            if !result?.is_empty() {
+                break;
+            }
+        }
+
+        if start.elapsed() >= timeout {
+            config.shell().warn(format!(
+                "timed out waiting for `{}` to be available at {}",
+                name,
+                reg_id.display_index()
+            ))?;
+            config.shell().note(
+                "The registry may have a backlog that is delaying making the \
+                 crate available. The crate should be available soon.",
+            )?;
+            return Ok(());
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    config.shell().status(
+        "Published",
+        format!("{} at {}", name, reg_id.display_index()),
+    )?;
+    Ok(())
+}
+
+/// The size limit of `.crate` files on crates.io, unless its `config.json`
+/// specifies one.
+const CRATES_IO_MAX_UPLOAD_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Returns the best compression of `.crate` files the registry `reg_id`
+/// accepts, and the size of the largest one it accepts, according to its
+/// `config.json`.
+fn publish_limits(
+    config: &Config,
+    reg_id: SourceId,
+) -> CargoResult<(CrateCompression, Option<u64>)> {
+    let _lock = config.acquire_package_cache_lock()?;
+    let mut src = RegistrySource::remote(reg_id, &HashSet::new(), config);
+    let (accepted, max_upload_size) = match src.config()? {
+        Some(cfg) => (cfg.compression, cfg.max_upload_size),
+        None => (Vec::new(), None),
+    };
+    Ok((CrateCompression::negotiate(&accepted), max_upload_size))
+}
+
+/// Whether the registry `reg_id` accepts the provenance of the crates
+/// published to it, according to its `config.json`.
+fn accepts_provenance(config: &Config, reg_id: SourceId) -> CargoResult<bool> {
+    let _lock = config.acquire_package_cache_lock()?;
+    let mut src = RegistrySource::remote(reg_id, &HashSet::new(), config);
+    Ok(matches!(src.config()?, Some(cfg) if cfg.provenance))
+}
+
+/// Checks the current package against the constraints of the registry
+/// before packaging it, reporting all of the problems at once instead of
+/// having the registry reject the upload one problem at a time.
+///
+/// Returns the size limit of the `.crate` file, which is checked once it is
+/// built for publishing, unless other problems are found here.
+fn preflight(
+    ws: &Workspace<'_>,
     registry: &Registry,
     registry_src: SourceId,
+    compression: CrateCompression,
+    max_upload_size: Option<u64>,
+) -> CargoResult<Option<u64>> {
+    let pkg = ws.current()?;
+    let to_crates_io = registry_src.is_default_registry() || registry.host_is_crates_io();
+    let mut problems = Vec::new();
+
+    // Other registries may accept packages without this metadata, which is
+    // only warned about when packaging.
+    let md = pkg.manifest().metadata();
+    if to_crates_io {
+        if md.description.is_none() {
+            problems.push("the manifest has no `description`".to_string());
+        }
+        if md.license.is_none() && md.license_file.is_none() {
+            problems.push("the manifest has neither `license` nor `license-file`".to_string());
+        }
+    }
+    if let Some(readme) = &md.readme {
+        if !pkg.root().join(readme).is_file() {
+            problems.push(format!("the `readme` file `{}` does not exist", readme));
+        }
+    }
+
+    verify_dependencies(ws, pkg, registry_src, to_crates_io, &mut problems);
+
+    let max_upload_size = match max_upload_size {
+        Some(size) => Some(size),
+        None if to_crates_io => Some(CRATES_IO_MAX_UPLOAD_SIZE),
+        None => None,
+    };
+    // Publishing stops here, so the package is only built to report its
+    // size along with the other problems. Packaging may fail because of
+    // them, like dependencies which can't be resolved, which are reported
+    // instead.
+    match max_upload_size {
+        Some(max_upload_size) if !problems.is_empty() => {
+            if let Ok(size) = ops::crate_size(ws, compression) {
+                problems.extend(size_problem(size, max_upload_size, registry_src));
+            }
+        }
+        _ => {}
+    }
+
+    report_problems(pkg, registry_src, problems)?;
+    Ok(max_upload_size)
+}
+
+/// Returns the problem with a `.crate` file of `size` bytes, if it is larger
+/// than the `max_upload_size` of the registry `registry_src`.
+fn size_problem(size: u64, max_upload_size: u64, registry_src: SourceId) -> Option<String> {
+    if size <= max_upload_size {
+        return None;
+    }
+    Some(format!(
+        "the `.crate` file is {} bytes, more than the {} bytes accepted by {}\n\
+         Use `cargo package --list` to find the files which can be left out \
+         with `package.exclude`.",
+        size,
+        max_upload_size,
+        registry_src.display_registry_name(),
+    ))
+}
+
+/// Fails with the `problems` which prevent `pkg` from being published to
+/// `registry_src`, if there are any.
+fn report_problems(
+    pkg: &Package,
+    registry_src: SourceId,
+    problems: Vec<String>,
 ) -> CargoResult<()> {
+    if problems.is_empty() {
+        return Ok(());
+    }
+    let mut msg = format!(
+        "`{} v{}` cannot be published to {}:",
+        pkg.name(),
+        pkg.version(),
+        registry_src.display_registry_name()
+    );
+    for problem in problems {
+        msg.push_str("\n  - ");
+        msg.push_str(&problem.replace('\n', "\n    "));
+    }
+    bail!(msg)
+}
+
+fn verify_dependencies(
+    ws: &Workspace<'_>,
+    pkg: &Package,
+    registry_src: SourceId,
+    to_crates_io: bool,
+    problems: &mut Vec<String>,
+) {
     for dep in pkg.dependencies().iter() {
+        // crates.io rejects wildcard requirements, which can't be fulfilled
+        // reliably once other versions are published.
+        if to_crates_io && dep.specified_req() && dep.version_req().to_string() == "*" {
+            problems.push(format!(
+                "dependency `{}` has a wildcard (`*`) version requirement, \
+                 which crates.io does not accept",
+                dep.package_name()
+            ));
+        }
         if dep.source_id().is_path() || dep.source_id().is_git() {
             if !dep.specified_req() {
                 if !dep.is_transitive() {
                     // dev-dependencies will be stripped in TomlManifest::prepare_for_publish
                     continue;
                 }
+                if ops::member_version_req(ws, dep).is_some() {
+                    // TomlManifest::prepare_for_publish will use the version
+                    // of the workspace member.
+                    continue;
+                }
                 let which = if dep.source_id().is_path() {
                     "path"
                 } else {
@@ -155,15 +631,14 @@ fn verify_dependencies(
                     || "crates.io".to_string(),
                     |registry_id| registry_id.display_registry_name(),
                 );
-                bail!(
-                    "all dependencies must have a version specified when publishing.\n\
-                     dependency `{}` does not specify a version\n\
+                problems.push(format!(
+                    "dependency `{}` does not specify a version\n\
                      Note: The published dependency will use the version from {},\n\
                      the `{}` specification will be removed from the dependency declaration.",
                     dep.package_name(),
                     dep_version_source,
                     which,
-                )
+                ));
             }
         // TomlManifest::prepare_for_publish will rewrite the dependency
         // to be just the `version` field.
@@ -178,27 +653,99 @@ fn verify_dependencies(
             // This extra hostname check is mostly to assist with testing,
             // but also prevents someone using `--index` to specify
             // something that points to crates.io.
-            if registry_src.is_default_registry() || registry.host_is_crates_io() {
-                bail!("crates cannot be published to crates.io with dependencies sourced from other\n\
-                       registries. `{}` needs to be published to crates.io before publishing this crate.\n\
-                       (crate `{}` is pulled from {})",
-                      dep.package_name(),
-                      dep.package_name(),
-                      dep.source_id());
+            if to_crates_io {
+                problems.push(format!(
+                    "crates cannot be published to crates.io with dependencies sourced from other\n\
+                     registries. `{}` needs to be published to crates.io before publishing this crate.\n\
+                     (crate `{}` is pulled from {})",
+                    dep.package_name(),
+                    dep.package_name(),
+                    dep.source_id()
+                ));
             }
         }
     }
-    Ok(())
+}
+
+/// Returns the `package.metadata.registry.<name>` table of `pkg` as JSON, to
+/// be sent along with the crate when publishing to the alternative registry
+/// `name`.
+fn registry_metadata(
+    pkg: &Package,
+    name: Option<&str>,
+) -> CargoResult<Option<serde_json::Map<String, serde_json::Value>>> {
+    let name = match name {
+        Some(name) if name != CRATES_IO_REGISTRY => name,
+        _ => return Ok(None),
+    };
+    let value = match pkg
+        .manifest()
+        .custom_metadata()
+        .and_then(|metadata| metadata.get("registry"))
+        .and_then(|registries| registries.get(name))
+    {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let path = format!("package.metadata.registry.{}", name);
+    match toml_to_json(value, &path)? {
+        serde_json::Value::Object(map) => Ok(Some(map)),
+        _ => bail!("`{}` must be a table", path),
+    }
+}
+
+/// Converts the TOML `value` found at `path` in the manifest to JSON, failing
+/// for the floats which JSON can't represent (`nan` and `inf`).
+fn toml_to_json(value: &toml::Value, path: &str) -> CargoResult<serde_json::Value> {
+    use serde_json::Value;
+    Ok(match value {
+        toml::Value::String(s) => Value::String(s.clone()),
+        toml::Value::Integer(i) => Value::from(*i),
+        toml::Value::Boolean(b) => Value::Bool(*b),
+        toml::Value::Float(f) => match serde_json::Number::from_f64(*f) {
+            Some(n) => Value::Number(n),
+            None => bail!(
+                "`{}` can't be sent to the registry: `{}` can't be represented in JSON",
+                path,
+                f
+            ),
+        },
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(values) => Value::Array(
+            values
+                .iter()
+                .enumerate()
+                .map(|(i, v)| toml_to_json(v, &format!("{}[{}]", path, i)))
+                .collect::<CargoResult<_>>()?,
+        ),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .iter()
+                .map(|(k, v)| Ok((k.clone(), toml_to_json(v, &format!("{}.{}", path, k))?)))
+                .collect::<CargoResult<_>>()?,
+        ),
+    })
 }
 
 fn transmit(
-    config: &Config,
+    ws: &Workspace<'_>,
     pkg: &Package,
     tarball: &File,
     registry: &mut Registry,
     registry_id: SourceId,
+    signature: Option<String>,
+    metadata: Option<serde_json::Map<String, serde_json::Value>>,
+    provenance: Option<serde_json::Value>,
     dry_run: bool,
 ) -> CargoResult<()> {
+    let config = ws.config();
+    // Only send `public` if the package opted in to the unstable feature, so
+    // that registries keep seeing the same request as before otherwise.
+    let public_dependency = pkg
+        .manifest()
+        .unstable_features()
+        .require(Feature::public_dependency())
+        .is_ok();
     let deps = pkg
         .dependencies()
         .iter()
@@ -221,12 +768,25 @@ fn transmit(
                 None
             };
 
+            // Members of the workspace published under another name are
+            // depended on by that name, renamed to the name used here.
+            let publish_name = ops::member_publish_name(ws, dep);
+            let explicit_name_in_toml = match publish_name {
+                Some(_) => Some(dep.name_in_toml().to_string()),
+                None => dep.explicit_name_in_toml().map(|s| s.to_string()),
+            };
+
             Ok(NewCrateDependency {
                 optional: dep.is_optional(),
                 default_features: dep.uses_default_features(),
-                name: dep.package_name().to_string(),
+                name: publish_name
+                    .unwrap_or_else(|| dep.package_name())
+                    .to_string(),
                 features: dep.features().iter().map(|s| s.to_string()).collect(),
-                version_req: dep.version_req().to_string(),
+                version_req: match ops::member_version_req(ws, dep) {
+                    Some(req) => VersionReq::parse(&req)?.to_string(),
+                    None => dep.version_req().to_string(),
+                },
                 target: dep.platform().map(|s| s.to_string()),
                 kind: match dep.kind() {
                     DepKind::Normal => "normal",
@@ -235,7 +795,12 @@ fn transmit(
                 }
                 .to_string(),
                 registry: dep_registry,
-                explicit_name_in_toml: dep.explicit_name_in_toml().map(|s| s.to_string()),
+                explicit_name_in_toml,
+                public: if public_dependency {
+                    Some(dep.is_public())
+                } else {
+                    None
+                },
             })
         })
         .collect::<CargoResult<Vec<NewCrateDependency>>>()?;
@@ -286,28 +851,29 @@ fn transmit(
         None => BTreeMap::new(),
     };
 
-    let publish = registry.publish(
-        &NewCrate {
-            name: pkg.name().to_string(),
-            vers: pkg.version().to_string(),
-            deps,
-            features: string_features,
-            authors: authors.clone(),
-            description: description.clone(),
-            homepage: homepage.clone(),
-            documentation: documentation.clone(),
-            keywords: keywords.clone(),
-            categories: categories.clone(),
-            readme: readme_content,
-            readme_file: readme.clone(),
-            repository: repository.clone(),
-            license: license.clone(),
-            license_file: license_file.clone(),
-            badges: badges.clone(),
-            links: links.clone(),
-        },
-        tarball,
-    );
+    let new_crate = NewCrate {
+        name: pkg.published_name().to_string(),
+        vers: pkg.version().to_string(),
+        deps,
+        features: string_features,
+        authors: authors.clone(),
+        description: description.clone(),
+        homepage: homepage.clone(),
+        documentation: documentation.clone(),
+        keywords: keywords.clone(),
+        categories: categories.clone(),
+        readme: readme_content,
+        readme_file: readme.clone(),
+        repository: repository.clone(),
+        license: license.clone(),
+        license_file: license_file.clone(),
+        badges: badges.clone(),
+        links: links.clone(),
+        signature,
+        metadata,
+        provenance,
+    };
+    let publish = with_rate_limit(config, || registry.publish(&new_crate, tarball));
 
     match publish {
         Ok(warnings) => {
@@ -346,6 +912,52 @@ fn transmit(
     }
 }
 
+/// Runs the API request `f`, retrying it up to `net.retry` times while the
+/// registry rejects it because of its rate limits. Each retry waits for as
+/// long as the registry asks with `Retry-After`, or for twice as long as the
+/// previous one otherwise, up to a minute. Cargo gives up if the registry
+/// asks to wait for longer than that.
+fn with_rate_limit<T>(config: &Config, mut f: impl FnMut() -> anyhow::Result<T>) -> CargoResult<T> {
+    const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+    let mut remaining = config.net_config()?.retry.unwrap_or(2);
+    let mut backoff = Duration::from_secs(1);
+    loop {
+        let err = match f() {
+            Ok(value) => return Ok(value),
+            Err(e) => e,
+        };
+        let delay = match err.downcast_ref::<RateLimited>() {
+            Some(limited) if remaining > 0 => {
+                let delay = match limited.retry_after {
+                    Some(delay) if delay > MAX_BACKOFF => {
+                        let msg = format!(
+                            "the registry asks to retry in {}, which is longer than \
+                             Cargo waits for ({} at most)",
+                            humantime::format_duration(delay),
+                            humantime::format_duration(MAX_BACKOFF)
+                        );
+                        return Err(err.context(msg).into());
+                    }
+                    Some(delay) => delay,
+                    None => backoff,
+                };
+                config.shell().warn(format!(
+                    "{}\nretrying in {} ({} tries remaining)",
+                    limited,
+                    humantime::format_duration(delay),
+                    remaining
+                ))?;
+                delay
+            }
+            _ => return Err(err),
+        };
+        thread::sleep(delay);
+        backoff = cmp::min(backoff * 2, MAX_BACKOFF);
+        remaining -= 1;
+    }
+}
+
 /// Returns the index and token from the config file for the given registry.
 ///
 /// `registry` is typically the registry specified on the command-line. If
@@ -364,7 +976,7 @@ pub fn registry_configuration(
         ))
     };
     // `registry.default` is handled in command-line parsing.
-    let (index, token, process) = match registry {
+    let (index, token, process, credential) = match registry {
         Some(registry) => {
             validate_package_name(&registry, "registry name", "")?;
             let index = Some(config.get_registry_index(&registry)?.to_string());
@@ -385,7 +997,18 @@ pub fn registry_configuration(
             } else {
                 None
             };
-            (index, token, process)
+            let credential = if config.cli_unstable().asymmetric_token {
+                let key = format!("registries.{}.credential", registry);
+                let credential = config.get::<Option<CredentialConfig>>(&key)?;
+                if credential.is_none() && token.is_none() && process.is_none() {
+                    config.get::<Option<CredentialConfig>>("registry.credential")?
+                } else {
+                    credential
+                }
+            } else {
+                None
+            };
+            (index, token, process, credential)
         }
         None => {
             // Use crates.io default.
@@ -401,10 +1024,24 @@ pub fn registry_configuration(
             } else {
                 None
             };
-            (None, token, process)
+            let credential = if config.cli_unstable().asymmetric_token {
+                config.get::<Option<CredentialConfig>>("registry.credential")?
+            } else {
+                None
+            };
+            (None, token, process, credential)
         }
     };
 
+    let credential = credential.unwrap_or_default();
+    if credential.secret_key.is_some() && (token.is_some() || process.is_some()) {
+        bail!(
+            "a `credential.secret-key` was specified in the config together with a token \
+             or a `credential-process` for the registry `{}`\n\
+             Only one of these values may be set, remove one or the other to proceed.",
+            registry.unwrap_or(CRATES_IO_REGISTRY)
+        );
+    }
     let credential_process =
         process.map(|process| (process.path.resolve_program(config), process.args));
 
@@ -412,9 +1049,20 @@ pub fn registry_configuration(
         index,
         token,
         credential_process,
+        secret_key: credential.secret_key,
+        secret_key_subject: credential.secret_key_subject,
     })
 }
 
+/// The `credential` table of a registry, with the secret key of asymmetric
+/// tokens.
+#[derive(Default, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct CredentialConfig {
+    secret_key: Option<String>,
+    secret_key_subject: Option<String>,
+}
+
 /// Returns the `Registry` and `Source` based on command-line and config settings.
 ///
 /// * `token`: The token from the command-line. If not set, uses the token
@@ -426,6 +1074,7 @@ pub fn registry_configuration(
 ///   `[source]` replacement if defined.
 /// * `force_update`: If `true`, forces the index to be updated.
 /// * `validate_token`: If `true`, the token must be set.
+/// * `mutation`: The operation asymmetric tokens are signed for.
 fn registry(
     config: &Config,
     token: Option<String>,
@@ -433,6 +1082,7 @@ fn registry(
     registry: Option<String>,
     force_update: bool,
     validate_token: bool,
+    mutation: Option<Mutation<'_>>,
 ) -> CargoResult<(Registry, RegistryConfig, SourceId)> {
     if index.is_some() && registry.is_some() {
         // Otherwise we would silently ignore one or the other.
@@ -498,10 +1148,11 @@ fn registry(
                 let token = auth::auth_token(
                     config,
                     token.as_deref(),
-                    reg_cfg.token.as_deref(),
-                    reg_cfg.credential_process.as_ref(),
+                    &reg_cfg,
+                    sid,
                     registry.as_deref(),
                     &api_host,
+                    mutation,
                 )?;
                 log::debug!("found token {:?}", token);
                 Some(token)
@@ -514,6 +1165,143 @@ fn registry(
     Ok((Registry::new_handle(api_host, token, handle), reg_cfg, sid))
 }
 
+/// Finds the name of the registry `sid` in the `registries` table of the
+/// configuration. Packages from a lock file only know the index URL of their
+/// registry, so the registry is looked up by it.
+fn find_registry_name(config: &Config, sid: SourceId) -> CargoResult<Option<String>> {
+    let names: Option<HashMap<String, RegistryIndexConfig>> = config.get("registries")?;
+    let mut names = names
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(name, cfg)| cfg.index.map(|_| name));
+    Ok(names.find(|name| {
+        let id = config
+            .get_registry_index(name)
+            .ok()
+            .and_then(|url| SourceId::for_registry(&url).ok());
+        matches!(id, Some(id) if id.canonical_url() == sid.canonical_url())
+    }))
+}
+
+/// Returns the key signing the `.crate` files published to the registry
+/// `name`, or crates.io, with `-Z crate-signing`.
+fn registry_signing_key(config: &Config, name: Option<&str>) -> CargoResult<Option<SecretKey>> {
+    if !config.cli_unstable().crate_signing {
+        return Ok(None);
+    }
+    let key = match name {
+        Some(name) => format!("registries.{}.signing-key", name),
+        None => "registry.signing-key".to_string(),
+    };
+    match config.get_string(&key)? {
+        Some(paserk) => SecretKey::from_paserk(&paserk.val)
+            .map(Some)
+            .chain_err(|| format!("invalid `{}` in {}", key, paserk.definition)),
+        None => Ok(None),
+    }
+}
+
+/// Returns the keys which the `.crate` files downloaded from the registry
+/// `sid` must be signed with, with `-Z crate-signing`.
+pub fn registry_trusted_keys(config: &Config, sid: SourceId) -> CargoResult<Option<Vec<String>>> {
+    let key = if sid.is_default_registry() {
+        "registry.trusted-keys".to_string()
+    } else {
+        match find_registry_name(config, sid)? {
+            Some(name) => format!("registries.{}.trusted-keys", name),
+            None => return Ok(None),
+        }
+    };
+    Ok(config
+        .get::<Option<config::StringList>>(&key)?
+        .map(|keys| keys.as_slice().to_vec()))
+}
+
+/// Returns the token to download `.crate` files from the registry `sid`, for
+/// registries which require authentication. `api` is the API endpoint of the
+/// registry, passed to credential processes.
+///
+/// A `download-token` configured for the registry is only sent to its download
+/// endpoint, and takes precedence over the token used for the web API.
+pub fn registry_token(config: &Config, sid: SourceId, api: Option<&str>) -> CargoResult<String> {
+    let name = if sid.is_default_registry() {
+        None
+    } else {
+        match find_registry_name(config, sid)? {
+            Some(name) => Some(name),
+            None => bail!(
+                "{} requires authentication, but isn't configured in `registries`, \
+                 so no token can be found for it",
+                sid
+            ),
+        }
+    };
+    let download_token_key = match &name {
+        Some(name) => format!("registries.{}.download-token", name),
+        None => String::from("registry.download-token"),
+    };
+    if let Some(token) = config.get_string(&download_token_key)? {
+        return Ok(token.val);
+    }
+    if let Some(token) = config.credentials_value(name.as_deref(), &["download-token"])? {
+        return Ok(token);
+    }
+    let mut reg_cfg = registry_configuration(config, name.as_deref())?;
+    let logged_in = |reg_cfg: &RegistryConfig| {
+        reg_cfg.token.is_some()
+            || reg_cfg.credential_process.is_some()
+            || reg_cfg.secret_key.is_some()
+    };
+    if !logged_in(&reg_cfg) {
+        reg_cfg.token = config.credentials_value(name.as_deref(), &["token"])?;
+        if reg_cfg.token.is_none() && config.cli_unstable().asymmetric_token {
+            reg_cfg.secret_key =
+                config.credentials_value(name.as_deref(), &["credential", "secret-key"])?;
+            reg_cfg.secret_key_subject =
+                config.credentials_value(name.as_deref(), &["credential", "secret-key-subject"])?;
+        }
+    }
+    if !logged_in(&reg_cfg) {
+        bail!(
+            "no token found for registry `{}`, which requires authentication, \
+             please run `cargo login{}`",
+            name.as_deref().unwrap_or(CRATES_IO_REGISTRY),
+            name.as_ref()
+                .map(|name| format!(" --registry {}", name))
+                .unwrap_or_default()
+        );
+    }
+    auth::auth_token(
+        config,
+        None,
+        &reg_cfg,
+        sid,
+        name.as_deref(),
+        api.unwrap_or_else(|| sid.url().as_str()),
+        None,
+    )
+}
+
+/// A registry of the `registries` table, of which only the `index` is used.
+/// The other keys are listed so that they aren't warned about as unused.
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct RegistryIndexConfig {
+    index: Option<String>,
+    #[allow(dead_code)]
+    token: Option<IgnoredAny>,
+    #[allow(dead_code)]
+    download_token: Option<IgnoredAny>,
+    #[allow(dead_code)]
+    credential_process: Option<IgnoredAny>,
+    #[allow(dead_code)]
+    credential: Option<IgnoredAny>,
+    #[allow(dead_code)]
+    signing_key: Option<IgnoredAny>,
+    #[allow(dead_code)]
+    trusted_keys: Option<IgnoredAny>,
+}
+
 /// Creates a new HTTP handle with appropriate global configuration for cargo.
 pub fn http_handle(config: &Config) -> CargoResult<Easy> {
     let (mut handle, timeout) = http_handle_and_timeout(config)?;
@@ -713,8 +1501,48 @@ pub fn registry_login(
     config: &Config,
     token: Option<String>,
     reg: Option<String>,
+    generate_keypair: bool,
 ) -> CargoResult<()> {
-    let (registry, reg_cfg, _) = registry(config, token.clone(), None, reg.clone(), false, false)?;
+    let (registry, reg_cfg, _) =
+        registry(config, token.clone(), None, reg.clone(), false, false, None)?;
+    let reg_name = reg.as_ref().map_or("crates.io", String::as_str);
+
+    if generate_keypair {
+        if reg_cfg.token.is_some()
+            || reg_cfg.credential_process.is_some()
+            || config
+                .credentials_value(reg.as_deref(), &["token"])?
+                .is_some()
+        {
+            bail!(
+                "a token or a `credential-process` is already configured for `{}`, \
+                 remove it to use an asymmetric token",
+                reg_name
+            );
+        }
+        let old_key = config.credentials_value(reg.as_deref(), &["credential", "secret-key"])?;
+        let secret_key = SecretKey::generate()?;
+        config::save_secret_key(config, Some(secret_key.to_paserk()), reg.as_deref())?;
+        config
+            .shell()
+            .status("Login", format!("secret key for `{}` saved", reg_name))?;
+        if let Some(old_key) = old_key {
+            if let Ok(old_key) = SecretKey::from_paserk(&old_key) {
+                config.shell().note(format!(
+                    "the previous key was replaced, its public key can be removed \
+                     from the registry:\n{}",
+                    old_key.public_paserk()
+                ))?;
+            }
+        }
+        drop_println!(
+            config,
+            "add this public key to your account on {}:\n{}",
+            registry.host(),
+            secret_key.public_paserk()
+        );
+        return Ok(());
+    }
 
     let token = match token {
         Some(token) => token,
@@ -751,32 +1579,35 @@ pub fn registry_login(
         registry.host(),
     )?;
 
-    config.shell().status(
-        "Login",
-        format!(
-            "token for `{}` saved",
-            reg.as_ref().map_or("crates.io", String::as_str)
-        ),
-    )?;
+    config
+        .shell()
+        .status("Login", format!("token for `{}` saved", reg_name))?;
     Ok(())
 }
 
 pub fn registry_logout(config: &Config, reg: Option<String>) -> CargoResult<()> {
-    let (registry, reg_cfg, _) = registry(config, None, None, reg.clone(), false, false)?;
+    let (registry, reg_cfg, _) = registry(config, None, None, reg.clone(), false, false, None)?;
     let reg_name = reg.as_deref().unwrap_or("crates.io");
-    if reg_cfg.credential_process.is_none() && reg_cfg.token.is_none() {
+    if reg_cfg.credential_process.is_none()
+        && reg_cfg.token.is_none()
+        && reg_cfg.secret_key.is_none()
+    {
         config.shell().status(
             "Logout",
             format!("not currently logged in to `{}`", reg_name),
         )?;
         return Ok(());
     }
-    auth::logout(
-        config,
-        reg_cfg.credential_process.as_ref(),
-        reg.as_deref(),
-        registry.host(),
-    )?;
+    if reg_cfg.secret_key.is_some() {
+        config::save_secret_key(config, None, reg.as_deref())?;
+    } else {
+        auth::logout(
+            config,
+            reg_cfg.credential_process.as_ref(),
+            reg.as_deref(),
+            registry.host(),
+        )?;
+    }
     config.shell().status(
         "Logout",
         format!(
@@ -814,12 +1645,12 @@ pub fn modify_owners(config: &Config, opts: &OwnersOptions) -> CargoResult<()> {
         opts.registry.clone(),
         true,
         true,
+        Some(Mutation::Owners { name: &name }),
     )?;
 
     if let Some(ref v) = opts.to_add {
         let v = v.iter().map(|s| &s[..]).collect::<Vec<_>>();
-        let msg = registry
-            .add_owners(&name, &v)
+        let msg = with_rate_limit(config, || registry.add_owners(&name, &v))
             .map_err(|e| format_err!("failed to invite owners to crate {}: {}", name, e))?;
 
         config.shell().status("Owner", msg)?;
@@ -830,8 +1661,7 @@ pub fn modify_owners(config: &Config, opts: &OwnersOptions) -> CargoResult<()> {
         config
             .shell()
             .status("Owner", format!("removing {:?} from crate {}", v, name))?;
-        registry
-            .remove_owners(&name, &v)
+        with_rate_limit(config, || registry.remove_owners(&name, &v))
             .chain_err(|| format!("failed to remove owners from crate {}", name))?;
     }
 
@@ -874,21 +1704,30 @@ pub fn yank(
         None => bail!("a version must be specified to yank"),
     };
 
-    let (mut registry, _, _) = registry(config, token, index, reg, true, true)?;
+    let mutation = if undo {
+        Mutation::Unyank {
+            name: &name,
+            vers: &version,
+        }
+    } else {
+        Mutation::Yank {
+            name: &name,
+            vers: &version,
+        }
+    };
+    let (mut registry, _, _) = registry(config, token, index, reg, true, true, Some(mutation))?;
 
     if undo {
         config
             .shell()
             .status("Unyank", format!("{}:{}", name, version))?;
-        registry
-            .unyank(&name, &version)
+        with_rate_limit(config, || registry.unyank(&name, &version))
             .chain_err(|| "failed to undo a yank")?;
     } else {
         config
             .shell()
             .status("Yank", format!("{}:{}", name, version))?;
-        registry
-            .yank(&name, &version)
+        with_rate_limit(config, || registry.yank(&name, &version))
             .chain_err(|| "failed to yank")?;
     }
 
@@ -934,7 +1773,7 @@ pub fn search(
         prefix
     }
 
-    let (mut registry, _, source_id) = registry(config, None, index, reg, false, false)?;
+    let (mut registry, _, source_id) = registry(config, None, index, reg, false, false, None)?;
     let (crates, total_crates) = registry
         .search(query, limit)
         .chain_err(|| "failed to retrieve search results from the registry")?;
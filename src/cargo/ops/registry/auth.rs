@@ -1,12 +1,16 @@
 //! Registry authentication support.
 
+use super::RegistryConfig;
+use crate::core::SourceId;
 use crate::sources::CRATES_IO_REGISTRY;
+use crate::util::paseto::SecretKey;
 use crate::util::{config, process_error, CargoResult, CargoResultExt, Config};
 use anyhow::bail;
 use anyhow::format_err;
 use std::io::{Read, Write};
 use std::path::PathBuf;
 use std::process::{Command, Stdio};
+use std::time::SystemTime;
 
 enum Action {
     Get,
@@ -14,27 +18,110 @@ enum Action {
     Erase,
 }
 
+/// The operation an asymmetric token is signed for, which the registry can
+/// check to make sure the token isn't used for anything else.
+pub enum Mutation<'a> {
+    /// Checks before publishing, while the package isn't built yet.
+    PrePublish,
+    Publish {
+        name: &'a str,
+        vers: &'a str,
+        cksum: &'a str,
+    },
+    Yank {
+        name: &'a str,
+        vers: &'a str,
+    },
+    Unyank {
+        name: &'a str,
+        vers: &'a str,
+    },
+    Owners {
+        name: &'a str,
+    },
+}
+
 /// Returns the token to use for the given registry.
+///
+/// With a secret key, this is an asymmetric token signed for `mutation`, or
+/// for reading from the registry if `None`.
 pub(super) fn auth_token(
     config: &Config,
     cli_token: Option<&str>,
-    config_token: Option<&str>,
-    credential_process: Option<&(PathBuf, Vec<String>)>,
+    reg_cfg: &RegistryConfig,
+    sid: SourceId,
     registry_name: Option<&str>,
     api_url: &str,
+    mutation: Option<Mutation<'_>>,
 ) -> CargoResult<String> {
-    let token = match (cli_token, config_token, credential_process) {
-        (None, None, None) => {
-            bail!("no upload token found, please run `cargo login` or pass `--token`");
-        }
-        (Some(cli_token), _, _) => cli_token.to_string(),
-        (None, Some(config_token), _) => config_token.to_string(),
-        (None, None, Some(process)) => {
+    if let Some(cli_token) = cli_token {
+        return Ok(cli_token.to_string());
+    }
+    if let Some(token) = &reg_cfg.token {
+        return Ok(token.clone());
+    }
+    if let Some(secret_key) = &reg_cfg.secret_key {
+        return asymmetric_token(
+            secret_key,
+            reg_cfg.secret_key_subject.as_deref(),
+            sid,
+            mutation,
+        );
+    }
+    match &reg_cfg.credential_process {
+        Some(process) => {
             let registry_name = registry_name.unwrap_or(CRATES_IO_REGISTRY);
-            run_command(config, process, registry_name, api_url, Action::Get)?.unwrap()
+            Ok(run_command(config, process, registry_name, api_url, Action::Get)?.unwrap())
+        }
+        None => bail!("no upload token found, please run `cargo login` or pass `--token`"),
+    }
+}
+
+/// Signs a token for `mutation` with `secret_key`, for the registry `sid`.
+fn asymmetric_token(
+    secret_key: &str,
+    subject: Option<&str>,
+    sid: SourceId,
+    mutation: Option<Mutation<'_>>,
+) -> CargoResult<String> {
+    let secret_key = SecretKey::from_paserk(secret_key)
+        .chain_err(|| "failed to load the secret key of the registry")?;
+    let mut message = serde_json::json!({
+        "iat": humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+        "v": 1,
+    });
+    if let Some(subject) = subject {
+        message["sub"] = subject.into();
+    }
+    let (kind, name, vers, cksum) = match mutation {
+        None => (None, None, None, None),
+        Some(Mutation::PrePublish) => (Some("publish"), None, None, None),
+        Some(Mutation::Publish { name, vers, cksum }) => {
+            (Some("publish"), Some(name), Some(vers), Some(cksum))
         }
+        Some(Mutation::Yank { name, vers }) => (Some("yank"), Some(name), Some(vers), None),
+        Some(Mutation::Unyank { name, vers }) => (Some("unyank"), Some(name), Some(vers), None),
+        Some(Mutation::Owners { name }) => (Some("owners"), Some(name), None, None),
     };
-    Ok(token)
+    let fields = [
+        ("mutation", kind),
+        ("name", name),
+        ("vers", vers),
+        ("cksum", cksum),
+    ];
+    for (key, value) in fields.iter() {
+        if let Some(value) = value {
+            message[*key] = (*value).into();
+        }
+    }
+    let footer = serde_json::json!({
+        "url": sid.url().as_str(),
+        "kip": secret_key.public_id(),
+    });
+    Ok(secret_key.sign(
+        message.to_string().as_bytes(),
+        footer.to_string().as_bytes(),
+    ))
 }
 
 /// Saves the given token.
@@ -0,0 +1,182 @@
+//! The SLSA provenance of published crates, generated by
+//! `cargo publish --provenance`.
+//!
+//! The provenance is an [in-toto statement] about the `.crate` file, with a
+//! [SLSA provenance v0.2] predicate recording the git revision the package was
+//! created from, the CI job or machine which packaged it, and the parameters
+//! of `cargo publish`. The revision is read from the `.cargo_vcs_info.json`
+//! file of the packaged crate, so it is the one the registry gets.
+//!
+//! Registries which set `provenance` to `true` in their `config.json` receive
+//! the provenance in the `provenance` field of the publish request. For other
+//! registries, and with `--dry-run`, it is written next to the `.crate` file,
+//! as `<name>-<version>.provenance.json`, to be published some other way.
+//!
+//! [in-toto statement]: https://github.com/in-toto/attestation/blob/main/spec/v0.1.0/statement.md
+//! [SLSA provenance v0.2]: https://slsa.dev/provenance/v0.2
+
+use std::env;
+use std::fs::File;
+use std::io::{Seek, SeekFrom};
+use std::path::Path;
+use std::time::SystemTime;
+
+use serde_json::{json, Value};
+
+use crate::core::{Package, SourceId, Workspace};
+use crate::ops::{PublishOpts, VcsInfo};
+use crate::util::compression;
+use crate::util::errors::{CargoResult, CargoResultExt};
+use crate::version;
+
+/// The `buildType` of the provenance, describing what the parameters are.
+const BUILD_TYPE: &str = "https://doc.rust-lang.org/cargo/reference/unstable.html#provenance";
+
+/// The `builder.id` of the packages not published by a known CI service.
+const LOCAL_BUILDER_ID: &str = "urn:cargo:local";
+
+/// Returns the provenance of `pkg` packaged as `tarball`, whose SHA-256
+/// checksum is `cksum`, to publish to `reg_id` with `opts`. `started` is when
+/// the packaging started.
+pub fn provenance(
+    ws: &Workspace<'_>,
+    pkg: &Package,
+    tarball: &File,
+    cksum: &str,
+    reg_id: SourceId,
+    opts: &PublishOpts<'_>,
+    started: SystemTime,
+) -> CargoResult<Value> {
+    let config = ws.config();
+    let filename = format!("{}-{}.crate", pkg.published_name(), pkg.version());
+    let vcs_info = read_vcs_info(tarball, &filename)
+        .chain_err(|| format!("failed to read the VCS information of `{}`", filename))?;
+    if vcs_info.is_none() {
+        config.shell().warn(format!(
+            "the provenance of `{}` does not record its source revision, \
+             since it was not packaged from a git checkout",
+            pkg.name()
+        ))?;
+    }
+    let rustc = config.load_global_rustc(Some(ws))?;
+    let (builder_id, invocation_id, ci) = builder();
+
+    let mut config_source = json!({});
+    let mut materials = Vec::new();
+    if let Some(VcsInfo { git }) = &vcs_info {
+        config_source["digest"] = json!({ "sha1": git.sha1 });
+        if let Some(url) = &git.url {
+            let uri = format!("git+{}", url);
+            config_source["uri"] = json!(uri);
+            // The revision only holds the whole source of the package if
+            // there were no uncommitted changes.
+            if !git.dirty {
+                materials.push(json!({
+                    "uri": uri,
+                    "digest": { "sha1": git.sha1 },
+                }));
+            }
+        }
+        if let Some(entry_point) = manifest_in_repo(pkg) {
+            config_source["entryPoint"] = json!(entry_point);
+        }
+    }
+
+    Ok(json!({
+        "_type": "https://in-toto.io/Statement/v0.1",
+        "subject": [{
+            "name": filename,
+            "digest": { "sha256": cksum },
+        }],
+        "predicateType": "https://slsa.dev/provenance/v0.2",
+        "predicate": {
+            "builder": { "id": builder_id },
+            "buildType": BUILD_TYPE,
+            "invocation": {
+                "configSource": config_source,
+                "parameters": {
+                    "package": pkg.name(),
+                    "version": pkg.version().to_string(),
+                    "registry": reg_id.url().as_str(),
+                    "features": opts.features,
+                    "all_features": opts.all_features,
+                    "no_default_features": opts.no_default_features,
+                    "target": opts.targets,
+                    "verify": opts.verify,
+                    "allow_dirty": opts.allow_dirty,
+                },
+                "environment": {
+                    "cargo": version().to_string(),
+                    "rustc": rustc.version.to_string(),
+                    "host": rustc.host.as_str(),
+                    "os": env::consts::OS,
+                    "arch": env::consts::ARCH,
+                    "ci": ci,
+                },
+            },
+            "metadata": {
+                "buildInvocationId": invocation_id,
+                "buildStartedOn": humantime::format_rfc3339_seconds(started).to_string(),
+                "buildFinishedOn": humantime::format_rfc3339_seconds(SystemTime::now())
+                    .to_string(),
+                "completeness": {
+                    "parameters": true,
+                    "environment": false,
+                    "materials": !materials.is_empty(),
+                },
+                "reproducible": false,
+            },
+            "materials": materials,
+        },
+    }))
+}
+
+/// Reads the `.cargo_vcs_info.json` file of the `.crate` file `tarball`.
+fn read_vcs_info(tarball: &File, filename: &str) -> CargoResult<Option<VcsInfo>> {
+    let mut tarball = tarball;
+    tarball.seek(SeekFrom::Start(0))?;
+    let vcs_info_path = Path::new(filename.trim_end_matches(".crate")).join(".cargo_vcs_info.json");
+    let mut archive = tar::Archive::new(compression::decoder(tarball)?);
+    for entry in archive.entries()? {
+        let entry = entry?;
+        if entry.path()? == vcs_info_path {
+            return Ok(Some(serde_json::from_reader(entry)?));
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the path of the manifest of `pkg` in its git repository.
+fn manifest_in_repo(pkg: &Package) -> Option<String> {
+    let repo = git2::Repository::discover(pkg.root()).ok()?;
+    let workdir = repo.workdir()?.canonicalize().ok()?;
+    let manifest = pkg.manifest_path().canonicalize().ok()?;
+    let path = manifest.strip_prefix(workdir).ok()?;
+    Some(path.to_str()?.replace('\\', "/"))
+}
+
+/// Returns the ID of the builder, the ID of the build for it, and the name
+/// of the CI service it is part of, if any.
+fn builder() -> (String, Option<String>, Option<&'static str>) {
+    let var = |name: &str| env::var(name).unwrap_or_default();
+    if var("GITHUB_ACTIONS") == "true" {
+        let run = format!(
+            "{}/{}/actions/runs/{}",
+            var("GITHUB_SERVER_URL"),
+            var("GITHUB_REPOSITORY"),
+            var("GITHUB_RUN_ID")
+        );
+        let invocation = format!("{}/attempts/{}", run, var("GITHUB_RUN_ATTEMPT"));
+        (run, Some(invocation), Some("github-actions"))
+    } else if var("GITLAB_CI") == "true" {
+        let runner = format!(
+            "{}/{}/-/runners/{}",
+            var("CI_SERVER_URL"),
+            var("CI_PROJECT_PATH"),
+            var("CI_RUNNER_ID")
+        );
+        (runner, Some(var("CI_JOB_URL")), Some("gitlab-ci"))
+    } else {
+        (LOCAL_BUILDER_ID.to_string(), None, None)
+    }
+}
@@ -11,7 +11,8 @@
 //!   providing the most power and flexibility.
 
 use crate::core::compiler::{CompileKind, RustcTargetData};
-use crate::core::registry::PackageRegistry;
+use crate::core::dependency::DepKind;
+use crate::core::registry::{PackageRegistry, QueryCache};
 use crate::core::resolver::features::{FeatureResolver, ForceAllTargets, ResolvedFeatures};
 use crate::core::resolver::{self, HasDevUnits, Resolve, ResolveOpts};
 use crate::core::summary::Summary;
@@ -22,7 +23,7 @@ use crate::sources::PathSource;
 use crate::util::errors::{CargoResult, CargoResultExt};
 use crate::util::{profile, CanonicalUrl};
 use log::{debug, trace};
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 
 /// Result for `resolve_ws_with_opts`.
 pub struct WorkspaceResolve<'cfg> {
@@ -56,8 +57,9 @@ version. This may also occur with an optional dependency that is not enabled.";
 /// This is a simple interface used by commands like `clean`, `fetch`, and
 /// `package`, which don't specify any options or features.
 pub fn resolve_ws<'a>(ws: &Workspace<'a>) -> CargoResult<(PackageSet<'a>, Resolve)> {
-    let mut registry = PackageRegistry::new(ws.config())?;
+    let mut registry = workspace_registry(ws)?;
     let resolve = resolve_with_registry(ws, &mut registry)?;
+    registry.save_caches()?;
     let packages = get_resolved_packages(&resolve, registry)?;
     Ok((packages, resolve))
 }
@@ -81,7 +83,7 @@ pub fn resolve_ws_with_opts<'cfg>(
     has_dev_units: HasDevUnits,
     force_all_targets: ForceAllTargets,
 ) -> CargoResult<WorkspaceResolve<'cfg>> {
-    let mut registry = PackageRegistry::new(ws.config())?;
+    let mut registry = workspace_registry(ws)?;
     let mut add_patches = true;
     let resolve = if ws.ignore_lock() {
         None
@@ -110,6 +112,9 @@ pub fn resolve_ws_with_opts<'cfg>(
             }
         }
 
+        warn_allowed_yanked(ws, &resolve)?;
+        ops::check_advisories(ws, &resolve, false)?;
+
         Some(resolve)
     } else {
         ops::load_pkg_lockfile(ws)?
@@ -124,6 +129,7 @@ pub fn resolve_ws_with_opts<'cfg>(
         specs,
         add_patches,
     )?;
+    registry.save_caches()?;
 
     let pkg_set = get_resolved_packages(&resolved_with_overrides, registry)?;
 
@@ -161,6 +167,32 @@ pub fn resolve_ws_with_opts<'cfg>(
     })
 }
 
+/// Creates the `PackageRegistry` used to resolve the workspace, which reuses
+/// the registry queries of previous runs with `-Z resolve-cache`.
+fn workspace_registry<'cfg>(ws: &Workspace<'cfg>) -> CargoResult<PackageRegistry<'cfg>> {
+    let mut registry = PackageRegistry::new(ws.config())?;
+    if ws.config().cli_unstable().resolve_cache {
+        registry.set_query_cache(QueryCache::load(ws));
+    }
+    Ok(registry)
+}
+
+/// Warns about yanked packages which are only used because they were allowed
+/// with `cargo update --allow-yanked`.
+fn warn_allowed_yanked(ws: &Workspace<'_>, resolve: &Resolve) -> CargoResult<()> {
+    let mut yanked: Vec<_> = resolve.yanked().iter().collect();
+    yanked.sort();
+    for pkg_id in yanked {
+        ws.config().shell().warn(format!(
+            "package `{}` in Cargo.lock is yanked in registry `{}`, \
+             but was explicitly allowed with `cargo update --allow-yanked`",
+            pkg_id,
+            pkg_id.source_id().display_registry_name()
+        ))?;
+    }
+    Ok(())
+}
+
 fn resolve_with_registry<'cfg>(
     ws: &Workspace<'cfg>,
     registry: &mut PackageRegistry<'cfg>,
@@ -612,3 +644,39 @@ fn register_previous_locks(
         }
     }
 }
+
+/// Collects `id` and the packages it depends on, transitively, into `used`.
+///
+/// Dev-dependencies are only followed if `dev` is set. Dependencies for a
+/// specific platform are only followed if they are activated for one of
+/// `kinds`, or for the host, unless `kinds` is `None`, which means all
+/// platforms.
+pub fn collect_used_deps(
+    resolve: &Resolve,
+    id: PackageId,
+    dev: bool,
+    kinds: Option<&[CompileKind]>,
+    target_data: &RustcTargetData,
+    used: &mut BTreeSet<PackageId>,
+) {
+    if !used.insert(id) {
+        return;
+    }
+    for (dep_id, deps) in resolve.deps(id) {
+        let is_used = deps.iter().any(|dep| {
+            if dep.kind() == DepKind::Development && !dev {
+                return false;
+            }
+            match kinds {
+                Some(kinds) => kinds
+                    .iter()
+                    .chain(Some(&CompileKind::Host))
+                    .any(|kind| target_data.dep_platform_activated(dep, *kind)),
+                None => true,
+            }
+        });
+        if is_used {
+            collect_used_deps(resolve, dep_id, dev, kinds, target_data, used);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! The context Cargo hands to external subcommands with
+//! `-Z subcommand-context`.
+//!
+//! A `cargo-foo` subcommand usually has to find the workspace, the target
+//! directory and the configuration by itself, and often does it slightly
+//! differently than Cargo does. With `-Z subcommand-context`, Cargo writes
+//! what it resolved as a JSON document, and sets `CARGO_CONTEXT_FILE` in the
+//! environment of the subcommand to a path it can read the document from.
+//!
+//! Cargo replaces itself with the subcommand on Unix, so nothing is left to
+//! remove the file afterwards. There the document is written to an unlinked
+//! temporary file whose descriptor is inherited by the subcommand, and the
+//! path is the `/dev/fd` entry of that descriptor. On Windows, Cargo waits
+//! for the subcommand, and removes the temporary file once it exits.
+
+use std::collections::HashMap;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use log::debug;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::core::profiles::Profiles;
+use crate::core::Workspace;
+use crate::util::config::ConfigValue as CV;
+use crate::util::errors::{CargoResult, CargoResultExt};
+use crate::util::important_paths::find_root_manifest_for_wd;
+use crate::util::interning::InternedString;
+use crate::util::Config;
+
+/// The environment variable with the path of the context.
+pub const SUBCOMMAND_CONTEXT_ENV: &str = "CARGO_CONTEXT_FILE";
+
+/// The version of the context, which has to be bumped whenever a field is
+/// removed or changes meaning.
+const VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct SerializedContext {
+    version: u32,
+    cargo: PathBuf,
+    cwd: PathBuf,
+    workspace_root: Option<PathBuf>,
+    manifest_path: Option<PathBuf>,
+    target_dir: Option<PathBuf>,
+    profile: InternedString,
+    profile_dir: Option<PathBuf>,
+    config: Value,
+}
+
+/// The file with the context of an external subcommand, which has to be
+/// kept until the subcommand exits.
+pub struct SubcommandContext {
+    #[cfg(unix)]
+    file: std::fs::File,
+    #[cfg(not(unix))]
+    file: tempfile::NamedTempFile,
+}
+
+impl SubcommandContext {
+    /// Writes the context of an external subcommand run with `args`.
+    ///
+    /// The `--manifest-path`, `--release` and `--profile` arguments of the
+    /// subcommand are taken into account, since most subcommands accept
+    /// them like the built-in ones.
+    pub fn new(config: &Config, args: &[&str]) -> CargoResult<SubcommandContext> {
+        let manifest_path = match arg_value(args, "--manifest-path") {
+            Some(path) => Ok(config.cwd().join(path)),
+            None => find_root_manifest_for_wd(config.cwd()),
+        };
+        let ws = match manifest_path.and_then(|path| Workspace::new(&path, config)) {
+            Ok(ws) => Some(ws),
+            Err(e) => {
+                debug!("no workspace in the subcommand context: {:#}", e);
+                None
+            }
+        };
+        let profile = match arg_value(args, "--profile") {
+            Some(profile) => InternedString::new(profile),
+            None if has_arg(args, "--release") => InternedString::new("release"),
+            None => InternedString::new("dev"),
+        };
+        let target_dir = match &ws {
+            Some(ws) => Some(ws.target_dir()),
+            None => config.target_dir()?,
+        };
+        let target_dir = target_dir.map(|dir| dir.into_path_unlocked());
+        let profile_dir = match (&ws, &target_dir) {
+            (Some(ws), Some(target_dir)) => {
+                Profiles::new(ws.profiles(), config, profile, ws.unstable_features())
+                    .ok()
+                    .map(|profiles| target_dir.join(profiles.get_dir_name()))
+            }
+            _ => None,
+        };
+        let context = SerializedContext {
+            version: VERSION,
+            cargo: config.cargo_exe()?.to_path_buf(),
+            cwd: config.cwd().to_path_buf(),
+            workspace_root: ws.as_ref().map(|ws| ws.root().to_path_buf()),
+            manifest_path: ws.as_ref().map(|ws| ws.root_manifest().to_path_buf()),
+            target_dir,
+            profile,
+            profile_dir,
+            config: table_to_json(config.values()?),
+        };
+        let json = serde_json::to_vec(&context)?;
+        write_context(&json).chain_err(|| "failed to write the context of the subcommand")
+    }
+
+    /// Returns the path the subcommand can read the context from.
+    pub fn path(&self) -> PathBuf {
+        #[cfg(unix)]
+        {
+            use std::os::unix::io::AsRawFd;
+            PathBuf::from("/dev/fd").join(self.file.as_raw_fd().to_string())
+        }
+        #[cfg(not(unix))]
+        {
+            self.file.path().to_path_buf()
+        }
+    }
+}
+
+#[cfg(unix)]
+fn write_context(json: &[u8]) -> CargoResult<SubcommandContext> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut file = tempfile::tempfile()?;
+    file.write_all(json)?;
+    file.seek(SeekFrom::Start(0))?;
+    // Let the subcommand inherit the descriptor.
+    unsafe {
+        let fd = file.as_raw_fd();
+        let flags = libc::fcntl(fd, libc::F_GETFD);
+        if flags == -1 || libc::fcntl(fd, libc::F_SETFD, flags & !libc::FD_CLOEXEC) == -1 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+    Ok(SubcommandContext { file })
+}
+
+#[cfg(not(unix))]
+fn write_context(json: &[u8]) -> CargoResult<SubcommandContext> {
+    let mut file = tempfile::Builder::new()
+        .prefix("cargo-context")
+        .suffix(".json")
+        .tempfile()?;
+    file.write_all(json)?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(SubcommandContext { file })
+}
+
+fn table_to_json(table: &HashMap<String, CV>) -> Value {
+    Value::Object(
+        table
+            .iter()
+            .map(|(key, value)| (key.clone(), to_json(value)))
+            .collect(),
+    )
+}
+
+fn to_json(value: &CV) -> Value {
+    match value {
+        CV::Integer(i, _) => Value::from(*i),
+        CV::String(s, _) => Value::from(s.as_str()),
+        CV::List(list, _) => {
+            Value::Array(list.iter().map(|(s, _)| Value::from(s.as_str())).collect())
+        }
+        CV::Table(table, _) => table_to_json(table),
+        CV::Boolean(b, _) => Value::from(*b),
+    }
+}
+
+/// Returns the value of the `name` option in `args`, given either as
+/// `name VALUE` or `name=VALUE`.
+fn arg_value<'a>(args: &[&'a str], name: &str) -> Option<&'a str> {
+    let mut iter = args.iter().take_while(|arg| **arg != "--");
+    while let Some(arg) = iter.next() {
+        if *arg == name {
+            return iter.next().copied();
+        }
+        if let Some(value) = arg.strip_prefix(name).and_then(|v| v.strip_prefix('=')) {
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn has_arg(args: &[&str], name: &str) -> bool {
+    args.iter()
+        .take_while(|arg| **arg != "--")
+        .any(|arg| *arg == name)
+}
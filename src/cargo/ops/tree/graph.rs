@@ -8,7 +8,7 @@ use crate::core::resolver::Resolve;
 use crate::core::{FeatureMap, FeatureValue, Package, PackageId, PackageIdSpec, Workspace};
 use crate::util::interning::InternedString;
 use crate::util::CargoResult;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub enum Node {
@@ -33,6 +33,17 @@ pub enum EdgeKind {
     Feature,
 }
 
+/// Features of a package requested by one of the packages depending on it.
+#[derive(Debug, Default)]
+pub struct FeatureRequest {
+    /// Features listed in the dependency declarations, including `default`
+    /// unless `default-features = false` is used.
+    pub declared: BTreeSet<InternedString>,
+    /// Features enabled by the features of the dependent, with the
+    /// `dep_name/feat_name` syntax.
+    pub enabled: BTreeSet<InternedString>,
+}
+
 /// Set of outgoing edges for a single node.
 ///
 /// Edges are separated by the edge kind (`DepKind` or `Feature`). This is
@@ -79,6 +90,12 @@ pub struct Graph<'a> {
     /// Key is the index of a package node, value is a map of dep_name to a
     /// set of `(pkg_node_index, is_optional)`.
     dep_name_map: HashMap<usize, HashMap<InternedString, HashSet<(usize, bool)>>>,
+    /// Names used in the code of a package for its dependencies, only
+    /// filled in with `--rename-aware`.
+    ///
+    /// Key is the index of the dependent package node, the edge kind, and the
+    /// index of the dependency package node.
+    extern_names: HashMap<(usize, EdgeKind, usize), InternedString>,
 }
 
 impl<'a> Graph<'a> {
@@ -90,6 +107,7 @@ impl<'a> Graph<'a> {
             package_map,
             cli_features: HashSet::new(),
             dep_name_map: HashMap::new(),
+            extern_names: HashMap::new(),
         }
     }
 
@@ -144,6 +162,91 @@ impl<'a> Graph<'a> {
         result.into_iter().map(|(_node, i)| i).collect()
     }
 
+    /// Returns the index of the node for the feature `name` of the given
+    /// package node, if that feature is enabled.
+    pub fn feature_index(&self, package_index: usize, name: &str) -> Option<usize> {
+        let node = Node::Feature {
+            node_index: package_index,
+            name: InternedString::new(name),
+        };
+        self.index.get(&node).copied()
+    }
+
+    /// Returns the features requested for each package node, keyed by the
+    /// index of the package node and then by the index of the package node
+    /// of each dependent. Features requested on the command-line are listed
+    /// under `None`.
+    ///
+    /// This must be called before the graph is inverted.
+    pub fn feature_requests(&self) -> HashMap<usize, HashMap<Option<usize>, FeatureRequest>> {
+        let mut result: HashMap<usize, HashMap<Option<usize>, FeatureRequest>> = HashMap::new();
+        for (from, node_edges) in self.edges.iter().enumerate() {
+            for (kind, edges) in &node_edges.0 {
+                for to in edges {
+                    match (&self.nodes[from], &self.nodes[*to], kind) {
+                        (Node::Package { .. }, Node::Package { .. }, EdgeKind::Dep(_)) => {
+                            // A dependency without any features.
+                            result
+                                .entry(*to)
+                                .or_default()
+                                .entry(Some(from))
+                                .or_default();
+                        }
+                        (Node::Package { .. }, Node::Feature { node_index, name }, _) => {
+                            result
+                                .entry(*node_index)
+                                .or_default()
+                                .entry(Some(from))
+                                .or_default()
+                                .declared
+                                .insert(*name);
+                        }
+                        (
+                            Node::Feature {
+                                node_index: from_package,
+                                ..
+                            },
+                            Node::Feature { node_index, name },
+                            _,
+                        ) if from_package != node_index => {
+                            result
+                                .entry(*node_index)
+                                .or_default()
+                                .entry(Some(*from_package))
+                                .or_default()
+                                .enabled
+                                .insert(*name);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        for index in &self.cli_features {
+            if let Node::Feature { node_index, name } = &self.nodes[*index] {
+                result
+                    .entry(*node_index)
+                    .or_default()
+                    .entry(None)
+                    .or_default()
+                    .declared
+                    .insert(*name);
+            }
+        }
+        result
+    }
+
+    /// Returns the name the package node `from` uses in its code for the
+    /// dependency `to` of the given kind, if known. `to` may also be a
+    /// feature node of the dependency.
+    pub fn extern_name(&self, from: usize, kind: &EdgeKind, to: usize) -> Option<InternedString> {
+        let to = match self.nodes[to] {
+            Node::Package { .. } => to,
+            Node::Feature { node_index, .. } => node_index,
+        };
+        self.extern_names.get(&(from, *kind, to)).copied()
+    }
+
     pub fn package_for_id(&self, id: PackageId) -> &Package {
         self.package_map[&id]
     }
@@ -370,6 +473,21 @@ fn add_pkg(
                 requested_kind,
                 opts,
             );
+            if opts.rename_aware {
+                let name = match dep.explicit_name_in_toml() {
+                    Some(name) => Some(InternedString::new(&name.replace("-", "_"))),
+                    None => dep_pkg
+                        .targets()
+                        .iter()
+                        .find(|t| t.is_lib())
+                        .map(|t| InternedString::new(&t.crate_name())),
+                };
+                if let Some(name) = name {
+                    graph
+                        .extern_names
+                        .insert((from_index, EdgeKind::Dep(dep.kind()), dep_index), name);
+                }
+            }
             if opts.graph_features {
                 // Add the dependency node with feature nodes in-between.
                 dep_name_map
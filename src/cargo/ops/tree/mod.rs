@@ -3,9 +3,11 @@
 use self::format::Pattern;
 use crate::core::compiler::{CompileKind, RustcTargetData};
 use crate::core::dependency::DepKind;
-use crate::core::resolver::{ForceAllTargets, HasDevUnits, ResolveOpts};
+use crate::core::resolver::features::FeatureResolver;
+use crate::core::resolver::{ForceAllTargets, HasDevUnits, Resolve, ResolveBehavior, ResolveOpts};
 use crate::core::{Package, PackageId, PackageIdSpec, Workspace};
 use crate::ops::{self, Packages};
+use crate::util::interning::InternedString;
 use crate::util::{CargoResult, Config};
 use crate::{drop_print, drop_println};
 use anyhow::{bail, Context};
@@ -15,6 +17,7 @@ use std::str::FromStr;
 
 mod format;
 mod graph;
+mod unification;
 
 pub use {graph::EdgeKind, graph::Node};
 
@@ -29,6 +32,17 @@ pub struct TreeOptions {
     /// The dependency kinds to display.
     pub edge_kinds: HashSet<EdgeKind>,
     pub invert: Vec<String>,
+    /// Features to explain, in the form `<spec>/<feature>`. Shows the inverted
+    /// tree of everything that enables each of them. Implies `graph_features`.
+    pub why_feature: Vec<String>,
+    /// If `true`, instead of a tree, show the packages which are built with
+    /// features that some of their dependents didn't request, and suggest
+    /// how to avoid that. Implies `graph_features`.
+    pub feature_unification: bool,
+    /// If `true`, prefix each dependency with the name its dependent uses
+    /// for it in its code, which is different from the package name if the
+    /// dependency is renamed with `package = "..."`.
+    pub rename_aware: bool,
     /// The style of prefix for each line.
     pub prefix: Prefix,
     /// If `true`, duplicates will be repeated.
@@ -127,6 +141,28 @@ pub fn build_and_print(ws: &Workspace<'_>, opts: &TreeOptions) -> CargoResult<()
     if opts.graph_features && opts.duplicates {
         bail!("the `-e features` flag does not support `--duplicates`");
     }
+    if !opts.why_feature.is_empty() && !opts.invert.is_empty() {
+        bail!("the `--why-feature` flag cannot be used together with `--invert`");
+    }
+    if opts.feature_unification
+        && (!opts.invert.is_empty() || opts.duplicates || !opts.why_feature.is_empty())
+    {
+        bail!(
+            "the `--feature-unification` flag cannot be used together with \
+             `--invert`, `--duplicates` or `--why-feature`"
+        );
+    }
+    if opts.rename_aware
+        && (!opts.invert.is_empty()
+            || opts.duplicates
+            || !opts.why_feature.is_empty()
+            || opts.feature_unification)
+    {
+        bail!(
+            "the `--rename-aware` flag cannot be used together with \
+             `--invert`, `--duplicates`, `--why-feature` or `--feature-unification`"
+        );
+    }
     let requested_targets = match &opts.target {
         Target::All | Target::Host => Vec::new(),
         Target::Specific(t) => t.clone(),
@@ -186,6 +222,34 @@ pub fn build_and_print(ws: &Workspace<'_>, opts: &TreeOptions) -> CargoResult<()
         opts,
     )?;
 
+    if opts.feature_unification {
+        let format = Pattern::new(&opts.format)
+            .with_context(|| format!("tree format `{}` not valid", opts.format))?;
+        // Compare with the features the new feature resolver would enable.
+        let resolver2_features = match ws.resolve_behavior() {
+            ResolveBehavior::V1 => Some(FeatureResolver::resolve_as(
+                ws,
+                &target_data,
+                &ws_resolve.targeted_resolve,
+                &ws_resolve.pkg_set,
+                &resolve_opts.features,
+                &specs,
+                &requested_kinds,
+                has_dev,
+                force_all,
+                ResolveBehavior::V2,
+            )?),
+            ResolveBehavior::V2 => None,
+        };
+        return unification::print(
+            ws,
+            &ws_resolve.targeted_resolve,
+            &graph,
+            &format,
+            resolver2_features.as_ref(),
+        );
+    }
+
     let root_specs = if opts.invert.is_empty() {
         specs
     } else {
@@ -197,7 +261,9 @@ pub fn build_and_print(ws: &Workspace<'_>, opts: &TreeOptions) -> CargoResult<()
     let root_ids = ws_resolve.targeted_resolve.specs_to_ids(&root_specs)?;
     let root_indexes = graph.indexes_from_ids(&root_ids);
 
-    let root_indexes = if opts.duplicates {
+    let root_indexes = if !opts.why_feature.is_empty() {
+        why_feature_indexes(&ws_resolve.targeted_resolve, &graph, &opts.why_feature)?
+    } else if opts.duplicates {
         // `-d -p foo` will only show duplicates within foo's subtree
         graph = graph.from_reachable(root_indexes.as_slice());
         graph.find_duplicates()
@@ -205,14 +271,70 @@ pub fn build_and_print(ws: &Workspace<'_>, opts: &TreeOptions) -> CargoResult<()
         root_indexes
     };
 
-    if !opts.invert.is_empty() || opts.duplicates {
+    if !opts.invert.is_empty() || opts.duplicates || !opts.why_feature.is_empty() {
         graph.invert();
     }
 
     print(ws.config(), opts, root_indexes, &graph)?;
+
+    // The dev-dependency cycles show up as a package depending on itself,
+    // which is explained once the trees are printed.
+    if opts.invert.is_empty() && !opts.duplicates && opts.why_feature.is_empty() {
+        if let HasDevUnits::Yes = has_dev {
+            for id in root_ids {
+                for cycle in ws_resolve.targeted_resolve.dev_dependency_cycles(id) {
+                    ws.config().shell().note(format!(
+                        "dev-dependency `{}` of `{}` depends on `{}` itself: {}",
+                        cycle[1].name(),
+                        id.name(),
+                        id.name(),
+                        Resolve::display_path(&cycle)
+                    ))?;
+                }
+            }
+        }
+    }
     Ok(())
 }
 
+/// Finds the feature nodes for the `<spec>/<feature>` values given to
+/// `--why-feature`.
+fn why_feature_indexes(
+    resolve: &Resolve,
+    graph: &Graph<'_>,
+    why_feature: &[String],
+) -> CargoResult<Vec<usize>> {
+    let mut result = Vec::new();
+    for value in why_feature {
+        let mut parts = value.rsplitn(2, '/');
+        let (feature, spec) = match (parts.next(), parts.next()) {
+            (Some(feature), Some(spec)) if !feature.is_empty() && !spec.is_empty() => {
+                (feature, spec)
+            }
+            _ => bail!(
+                "invalid value `{}` for `--why-feature`, \
+                 expected a package and a feature like `serde/std`",
+                value
+            ),
+        };
+        let ids = resolve.specs_to_ids(&[PackageIdSpec::parse(spec)?])?;
+        let package_indexes = graph.indexes_from_ids(&ids);
+        let feature_indexes: Vec<usize> = package_indexes
+            .iter()
+            .filter_map(|index| graph.feature_index(*index, feature))
+            .collect();
+        if feature_indexes.is_empty() {
+            bail!(
+                "feature `{}` is not enabled for package `{}`",
+                feature,
+                spec
+            );
+        }
+        result.extend(feature_indexes);
+    }
+    Ok(result)
+}
+
 /// Prints a tree for each given root.
 fn print(
     config: &Config,
@@ -248,6 +370,7 @@ fn print(
             config,
             graph,
             root_index,
+            None,
             &format,
             symbols,
             opts.prefix,
@@ -262,10 +385,14 @@ fn print(
 }
 
 /// Prints a package and all of its dependencies.
+///
+/// `extern_name` is the name the dependent of the package uses for it, if
+/// `--rename-aware` is used.
 fn print_node<'a>(
     config: &Config,
     graph: &'a Graph<'_>,
     node_index: usize,
+    extern_name: Option<InternedString>,
     format: &Pattern,
     symbols: &Symbols,
     prefix: Prefix,
@@ -306,6 +433,9 @@ fn print_node<'a>(
     } else {
         " (*)"
     };
+    if let Some(extern_name) = extern_name {
+        drop_print!(config, "{}: ", extern_name);
+    }
     drop_println!(config, "{}{}", format.display(graph, node_index), star);
 
     if !new || in_cycle {
@@ -380,6 +510,7 @@ fn print_dependencies<'a>(
             config,
             graph,
             *dependency,
+            graph.extern_name(node_index, kind, *dependency),
             format,
             symbols,
             prefix,
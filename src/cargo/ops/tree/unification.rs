@@ -0,0 +1,204 @@
+//! Implementation of `cargo tree --feature-unification`.
+//!
+//! Cargo builds a package only once with the union of the features requested
+//! by everything depending on it. This reports the packages where that union
+//! enables features which some of the dependents didn't ask for, and suggests
+//! changes which would leave those features out.
+
+use super::format::Pattern;
+use super::graph::{FeatureRequest, Graph};
+use super::Node;
+use crate::core::resolver::features::{FeaturesFor, ResolvedFeatures};
+use crate::core::resolver::Resolve;
+use crate::core::{FeatureMap, FeatureValue, Workspace};
+use crate::drop_println;
+use crate::util::interning::InternedString;
+use crate::util::CargoResult;
+use std::collections::{BTreeSet, HashMap};
+
+/// Prints every package built with features unified from several dependents.
+///
+/// `resolver2_features` are the features the new feature resolver would
+/// enable, if the workspace doesn't already use it.
+pub fn print(
+    ws: &Workspace<'_>,
+    resolve: &Resolve,
+    graph: &Graph<'_>,
+    format: &Pattern,
+    resolver2_features: Option<&ResolvedFeatures>,
+) -> CargoResult<()> {
+    let config = ws.config();
+    let requests = graph.feature_requests();
+    let mut package_indexes: Vec<usize> = requests
+        .iter()
+        .filter(|(_index, requests)| requests.len() > 1)
+        .map(|(index, _requests)| *index)
+        .collect();
+    package_indexes.sort_unstable_by(|a, b| graph.node(*a).cmp(graph.node(*b)));
+
+    let mut first = true;
+    for package_index in package_indexes {
+        let (package_id, features) = match graph.node(package_index) {
+            Node::Package {
+                package_id,
+                features,
+                ..
+            } => (*package_id, features),
+            Node::Feature { .. } => unreachable!(),
+        };
+        let feature_map = resolve.summary(package_id).features();
+        let requests = &requests[&package_index];
+        let expanded: HashMap<Option<usize>, BTreeSet<InternedString>> = requests
+            .iter()
+            .map(|(from, request)| (*from, expand_request(feature_map, request)))
+            .collect();
+        let unified = union(expanded.values());
+        if expanded.values().all(|features| *features == unified) {
+            continue;
+        }
+
+        if !first {
+            drop_println!(config);
+        }
+        first = false;
+        drop_println!(
+            config,
+            "{} is built with {}",
+            format.display(graph, package_index),
+            list(features.iter())
+        );
+        let mut from_indexes: Vec<Option<usize>> = requests.keys().copied().collect();
+        from_indexes.sort_unstable_by(|a, b| match (a, b) {
+            (Some(a), Some(b)) => graph.node(*a).cmp(graph.node(*b)),
+            _ => a.cmp(b),
+        });
+        for from in &from_indexes {
+            let request = &requests[from];
+            let requested = request.declared.union(&request.enabled);
+            match from {
+                Some(from) => drop_println!(
+                    config,
+                    "    {} requests {}",
+                    format.display(graph, *from),
+                    list(requested)
+                ),
+                None => drop_println!(config, "    the command-line requests {}", list(requested)),
+            }
+        }
+
+        // Suggest disabling the default features of dependencies declared by
+        // workspace members, if nothing else needs them.
+        let default = InternedString::new("default");
+        for from in from_indexes.iter().flatten() {
+            let request = &requests[&Some(*from)];
+            let from_id = match graph.node(*from) {
+                Node::Package { package_id, .. } => *package_id,
+                Node::Feature { .. } => unreachable!(),
+            };
+            if !request.declared.contains(&default) || !ws.is_member(graph.package_for_id(from_id))
+            {
+                continue;
+            }
+            let mut without_default = FeatureRequest {
+                declared: request.declared.clone(),
+                enabled: request.enabled.clone(),
+            };
+            without_default.declared.remove(&default);
+            let without_default = expand_request(feature_map, &without_default);
+            let remaining = union(
+                expanded
+                    .iter()
+                    .filter(|(other, _features)| **other != Some(*from))
+                    .map(|(_other, features)| features)
+                    .chain(Some(&without_default)),
+            );
+            let left_out: Vec<_> = unified.difference(&remaining).collect();
+            if !left_out.is_empty() {
+                drop_println!(
+                    config,
+                    "    help: adding `default-features = false` to the `{}` dependency \
+                     of {} would leave out {}",
+                    package_id.name(),
+                    format.display(graph, *from),
+                    list(left_out.into_iter())
+                );
+            }
+        }
+
+        // Suggest the new feature resolver, if it would change the features.
+        if let Some(resolver2_features) = resolver2_features {
+            let for_target = resolver2_features
+                .activated_features_unverified(package_id, FeaturesFor::NormalOrDev);
+            let for_host =
+                resolver2_features.activated_features_unverified(package_id, FeaturesFor::HostDep);
+            let changed = |f: &Option<Vec<InternedString>>| matches!(f, Some(f) if f != features);
+            if changed(&for_target) || changed(&for_host) {
+                let mut help = String::from("    help: `resolver = \"2\"` would build it with ");
+                match (&for_target, &for_host) {
+                    (Some(for_target), Some(for_host)) if for_target != for_host => {
+                        help.push_str(&list(for_target.iter()));
+                        help.push_str(", and with ");
+                        help.push_str(&list(for_host.iter()));
+                        help.push_str(" for build dependencies and proc-macros");
+                    }
+                    (Some(features), _) | (None, Some(features)) => {
+                        help.push_str(&list(features.iter()))
+                    }
+                    (None, None) => unreachable!(),
+                }
+                drop_println!(config, "{}", help);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns all features of a package enabled by the given request, including
+/// those enabled by other features of the package.
+fn expand_request(feature_map: &FeatureMap, request: &FeatureRequest) -> BTreeSet<InternedString> {
+    let mut result = BTreeSet::new();
+    let mut to_visit: Vec<InternedString> = request
+        .declared
+        .iter()
+        .chain(&request.enabled)
+        .copied()
+        .collect();
+    while let Some(feature) = to_visit.pop() {
+        // `default` is always requested, even if the package doesn't have
+        // such a feature.
+        let fvs = match feature_map.get(&feature) {
+            Some(fvs) => fvs,
+            None => continue,
+        };
+        if !result.insert(feature) {
+            continue;
+        }
+        for fv in fvs {
+            match fv {
+                FeatureValue::Feature(name) => to_visit.push(*name),
+                FeatureValue::DepFeature {
+                    dep_name,
+                    dep_prefix: false,
+                    weak: false,
+                    ..
+                } => to_visit.push(*dep_name),
+                _ => {}
+            }
+        }
+    }
+    result
+}
+
+fn union<'a>(sets: impl Iterator<Item = &'a BTreeSet<InternedString>>) -> BTreeSet<InternedString> {
+    sets.flatten().copied().collect()
+}
+
+/// Formats a list of features for the output.
+fn list<'a>(features: impl Iterator<Item = &'a InternedString>) -> String {
+    let features: Vec<&str> = features.map(|f| f.as_str()).collect();
+    if features.is_empty() {
+        "no features".to_string()
+    } else {
+        format!("features: {}", features.join(", "))
+    }
+}
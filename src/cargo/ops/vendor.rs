@@ -1,7 +1,10 @@
+use crate::core::compiler::{CompileKind, CompileTarget, RustcTargetData};
+use crate::core::resolver::Resolve;
 use crate::core::shell::Verbosity;
-use crate::core::{GitReference, Workspace};
+use crate::core::{GitReference, GitSubmodules, PackageId, Workspace};
 use crate::ops;
 use crate::sources::path::PathSource;
+use crate::util::toml::VecStringOrBool;
 use crate::util::Sha256;
 use crate::util::{paths, CargoResult, CargoResultExt, Config};
 use anyhow::bail;
@@ -18,6 +21,9 @@ pub struct VendorOptions<'a> {
     pub versioned_dirs: bool,
     pub destination: &'a Path,
     pub extra: Vec<PathBuf>,
+    /// Only vendor the dependencies of these target triples, or all of them
+    /// if empty.
+    pub platforms: Vec<String>,
 }
 
 pub fn vendor(ws: &Workspace<'_>, opts: &VendorOptions<'_>) -> CargoResult<()> {
@@ -37,6 +43,13 @@ pub fn vendor(ws: &Workspace<'_>, opts: &VendorOptions<'_>) -> CargoResult<()> {
             config,
             "To use vendored sources, add this to your .cargo/config.toml for this project:\n\n"
         );
+        if !opts.platforms.is_empty() {
+            crate::drop_print!(
+                config,
+                "# Only the dependencies of these platforms are vendored:\n# {}\n\n",
+                opts.platforms.join(", ")
+            );
+        }
         crate::drop_print!(config, "{}", &toml::to_string(&vendor_config).unwrap());
     }
 
@@ -64,6 +77,7 @@ enum VendorSource {
         branch: Option<String>,
         tag: Option<String>,
         rev: Option<String>,
+        submodules: Option<VecStringOrBool>,
         #[serde(rename = "replace-with")]
         replace_with: String,
     },
@@ -109,12 +123,13 @@ fn sync(
     for ws in workspaces {
         let (packages, resolve) =
             ops::resolve_ws(ws).chain_err(|| "failed to load pkg lockfile")?;
+        let pkg_ids = vendored_ids(ws, &resolve, opts)?;
 
         packages
-            .get_many(resolve.iter())
+            .get_many(pkg_ids.iter().cloned())
             .chain_err(|| "failed to download packages")?;
 
-        for pkg in resolve.iter() {
+        for &pkg in pkg_ids.iter() {
             // Don't delete actual source code!
             if pkg.source_id().is_path() {
                 if let Ok(path) = pkg.source_id().url().to_file_path() {
@@ -141,12 +156,13 @@ fn sync(
     for ws in workspaces {
         let (packages, resolve) =
             ops::resolve_ws(ws).chain_err(|| "failed to load pkg lockfile")?;
+        let pkg_ids = vendored_ids(ws, &resolve, opts)?;
 
         packages
-            .get_many(resolve.iter())
+            .get_many(pkg_ids.iter().cloned())
             .chain_err(|| "failed to download packages")?;
 
-        for pkg in resolve.iter() {
+        for &pkg in pkg_ids.iter() {
             // No need to vendor path crates since they're already in the
             // repository
             if pkg.source_id().is_path() {
@@ -281,11 +297,17 @@ fn sync(
                     GitReference::DefaultBranch => {}
                 }
             }
+            let submodules = match source_id.git_submodules() {
+                Some(GitSubmodules::Skip) => Some(VecStringOrBool::Bool(false)),
+                Some(GitSubmodules::Only(paths)) => Some(VecStringOrBool::VecString(paths.clone())),
+                Some(GitSubmodules::All) | None => None,
+            };
             VendorSource::Git {
                 git: source_id.url().to_string(),
                 branch,
                 tag,
                 rev,
+                submodules,
                 replace_with: merged_source_name.to_string(),
             }
         } else {
@@ -297,6 +319,45 @@ fn sync(
     Ok(VendorConfig { source: config })
 }
 
+/// Returns the packages of `resolve` to vendor, which are the ones reachable
+/// from the members of `ws` on the platforms of `opts`.
+fn vendored_ids(
+    ws: &Workspace<'_>,
+    resolve: &Resolve,
+    opts: &VendorOptions<'_>,
+) -> CargoResult<Vec<PackageId>> {
+    if opts.platforms.is_empty() {
+        return Ok(resolve.iter().collect());
+    }
+    let kinds = opts
+        .platforms
+        .iter()
+        .map(|platform| Ok(CompileKind::Target(CompileTarget::new(platform)?)))
+        .collect::<CargoResult<Vec<_>>>()?;
+    let target_data = RustcTargetData::new(ws, &kinds)?;
+
+    // Build dependencies are filtered like the others, by the given
+    // platforms rather than by the host.
+    let mut seen = HashSet::new();
+    let mut queue: Vec<_> = ws.members().map(|pkg| pkg.package_id()).collect();
+    while let Some(id) = queue.pop() {
+        if !seen.insert(id) {
+            continue;
+        }
+        for (dep_id, deps) in resolve.deps(id) {
+            let activated = deps.iter().any(|dep| {
+                kinds
+                    .iter()
+                    .any(|kind| target_data.dep_platform_activated(dep, *kind))
+            });
+            if activated {
+                queue.push(dep_id);
+            }
+        }
+    }
+    Ok(resolve.iter().filter(|id| seen.contains(id)).collect())
+}
+
 fn cp_sources(
     src: &Path,
     paths: &[PathBuf],
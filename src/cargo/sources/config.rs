@@ -4,14 +4,17 @@
 //! structure usable by Cargo itself. Currently this is primarily used to map
 //! sources to one another via the `replace-with` key in `.cargo/config`.
 
-use crate::core::{GitReference, PackageId, Source, SourceId};
-use crate::sources::{ReplacedSource, CRATES_IO_REGISTRY};
+use crate::core::{GitReference, GitSubmodules, PackageId, Source, SourceId};
+use crate::sources::{MirroredSource, ReplacedSource, CRATES_IO_REGISTRY};
 use crate::util::config::{self, ConfigRelativePath, OptValue};
 use crate::util::errors::{CargoResult, CargoResultExt};
+use crate::util::toml::VecStringOrBool;
 use crate::util::{Config, IntoUrl};
 use anyhow::bail;
 use log::debug;
+use serde::de::Unexpected;
 use std::collections::{HashMap, HashSet};
+use std::fmt;
 use url::Url;
 
 #[derive(Clone)]
@@ -27,8 +30,9 @@ pub struct SourceConfigMap<'cfg> {
 #[derive(Debug, serde::Deserialize)]
 #[serde(rename_all = "kebab-case")]
 struct SourceConfigDef {
-    /// Indicates this source should be replaced with another of the given name.
-    replace_with: OptValue<String>,
+    /// Indicates this source should be replaced with another of the given
+    /// name, or with the first one of a list of mirrors which works.
+    replace_with: OptValue<ReplaceWith>,
     /// A directory source.
     directory: Option<ConfigRelativePath>,
     /// A registry source. Value is a URL.
@@ -43,6 +47,83 @@ struct SourceConfigDef {
     tag: OptValue<String>,
     /// The git revision.
     rev: OptValue<String>,
+    /// The git submodules which are checked out.
+    submodules: OptValue<VecStringOrBool>,
+}
+
+/// The value of `replace-with`, either the name of a single source or a list
+/// of mirrors.
+///
+/// Values of other types are kept as `Invalid`, describing what was found,
+/// and reported by `SourceConfigMap`: failing while deserializing would name
+/// the key twice in the error, once for the value and once for `Value<T>`.
+#[derive(Debug)]
+enum ReplaceWith {
+    Source(String),
+    Mirrors(Vec<String>),
+    Invalid(String),
+}
+
+impl<'de> serde::Deserialize<'de> for ReplaceWith {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = ReplaceWith;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("a string or a list of strings")
+            }
+
+            fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(ReplaceWith::Source(s.to_string()))
+            }
+
+            fn visit_seq<V>(self, v: V) -> Result<Self::Value, V::Error>
+            where
+                V: serde::de::SeqAccess<'de>,
+            {
+                let seq = serde::de::value::SeqAccessDeserializer::new(v);
+                serde::Deserialize::deserialize(seq).map(ReplaceWith::Mirrors)
+            }
+
+            fn visit_i64<E>(self, i: i64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(invalid(Unexpected::Signed(i)))
+            }
+
+            fn visit_bool<E>(self, b: bool) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(invalid(Unexpected::Bool(b)))
+            }
+
+            fn visit_map<V>(self, _map: V) -> Result<Self::Value, V::Error>
+            where
+                V: serde::de::MapAccess<'de>,
+            {
+                Ok(invalid(Unexpected::Map))
+            }
+        }
+
+        fn invalid(found: Unexpected<'_>) -> ReplaceWith {
+            ReplaceWith::Invalid(format!(
+                "invalid type: {}, expected a string or a list of strings",
+                found
+            ))
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
 }
 
 /// Configuration for a particular source, found in TOML looking like:
@@ -60,10 +141,11 @@ struct SourceConfig {
 
     /// Whether or not this source is replaced with another.
     ///
-    /// This field is a tuple of `(name, location)` where `location` is where
+    /// This field is a tuple of `(names, location)` where `location` is where
     /// this configuration key was defined (such as the `.cargo/config` path
-    /// or the environment variable name).
-    replace_with: Option<(String, String)>,
+    /// or the environment variable name). There is more than one name when
+    /// the source is replaced with a list of mirrors.
+    replace_with: Option<(Vec<String>, String)>,
 }
 
 impl<'cfg> SourceConfigMap<'cfg> {
@@ -106,12 +188,39 @@ impl<'cfg> SourceConfigMap<'cfg> {
     ) -> CargoResult<Box<dyn Source + 'cfg>> {
         debug!("loading: {}", id);
 
-        let mut name = match self.id2name.get(&id) {
+        let orig_name = match self.id2name.get(&id) {
             Some(name) => name,
-            None => return Ok(id.load(self.config, yanked_whitelist)?),
+            None => return id.load(self.config, yanked_whitelist),
+        };
+        let (names, cfg_loc) = match &self.cfgs[orig_name].replace_with {
+            Some(replace_with) => replace_with,
+            None => return id.load(self.config, yanked_whitelist),
         };
-        let mut cfg_loc = "";
-        let orig_name = name;
+        if let [name] = names.as_slice() {
+            return self.load_replacement(id, orig_name, name, cfg_loc, yanked_whitelist);
+        }
+
+        // Mirrors are tried in order, followed by the replaced source itself.
+        let mut sources = Vec::new();
+        for name in names {
+            let source = self.load_replacement(id, orig_name, name, cfg_loc, yanked_whitelist)?;
+            sources.push((name.clone(), source));
+        }
+        let source = id.load(self.config, yanked_whitelist)?;
+        sources.push((orig_name.clone(), source));
+        Ok(Box::new(MirroredSource::new(id, sources, self.config)))
+    }
+
+    /// Loads the source replacing `id`, as configured by the source `name`
+    /// and the ones it is replaced with in turn.
+    fn load_replacement<'a>(
+        &'a self,
+        id: SourceId,
+        orig_name: &str,
+        mut name: &'a str,
+        mut cfg_loc: &'a str,
+        yanked_whitelist: &HashSet<PackageId>,
+    ) -> CargoResult<Box<dyn Source + 'cfg>> {
         let new_id;
         loop {
             let cfg = match self.cfgs.get(name) {
@@ -126,8 +235,17 @@ impl<'cfg> SourceConfigMap<'cfg> {
                 ),
             };
             match &cfg.replace_with {
-                Some((s, c)) => {
-                    name = s;
+                Some((names, c)) => {
+                    if names.len() != 1 {
+                        bail!(
+                            "the source `{}` is replaced with a list of mirrors, \
+                             which can't be used as a mirror itself \
+                             (configuration in `{}`)",
+                            name,
+                            c
+                        )
+                    }
+                    name = &names[0];
                     cfg_loc = c;
                 }
                 None if id == cfg.id => return Ok(id.load(self.config, yanked_whitelist)?),
@@ -229,7 +347,22 @@ restore the source replacement configuration to continue the build
                     },
                 },
             };
-            srcs.push(SourceId::for_git(&url, reference)?);
+            let mut source_id = SourceId::for_git(&url, reference)?;
+            if let Some(submodules) = def.submodules {
+                let submodules = match &submodules.val {
+                    VecStringOrBool::Bool(true) => GitSubmodules::All,
+                    VecStringOrBool::Bool(false) => GitSubmodules::Skip,
+                    VecStringOrBool::VecString(paths) => GitSubmodules::from_paths(paths)
+                        .chain_err(|| {
+                            format!(
+                                "invalid `source.{}.submodules` (in {})",
+                                name, submodules.definition
+                            )
+                        })?,
+                };
+                source_id = source_id.with_git_submodules(submodules);
+            }
+            srcs.push(source_id);
         } else {
             let check_not_set = |key, v: OptValue<String>| {
                 if let Some(val) = v {
@@ -246,6 +379,14 @@ restore the source replacement configuration to continue the build
             check_not_set("branch", def.branch)?;
             check_not_set("tag", def.tag)?;
             check_not_set("rev", def.rev)?;
+            if let Some(val) = def.submodules {
+                bail!(
+                    "source definition `source.{}` specifies `submodules`, \
+                     but that requires a `git` key to be specified (in {})",
+                    name,
+                    val.definition
+                );
+            }
         }
         if name == "crates-io" && srcs.is_empty() {
             srcs.push(SourceId::crates_io(self.config)?);
@@ -265,9 +406,29 @@ restore the source replacement configuration to continue the build
         }
         let src = srcs[0];
 
-        let replace_with = def
-            .replace_with
-            .map(|val| (val.val, val.definition.to_string()));
+        let replace_with = match def.replace_with {
+            Some(val) => {
+                let names = match val.val {
+                    ReplaceWith::Source(name) => vec![name],
+                    ReplaceWith::Mirrors(names) => names,
+                    ReplaceWith::Invalid(msg) => {
+                        return Err(anyhow::Error::msg(msg).context(format!(
+                            "error in {}: could not load config key `source.{}.replace-with`",
+                            val.definition, name
+                        )));
+                    }
+                };
+                if names.is_empty() {
+                    bail!(
+                        "`source.{}.replace-with` is an empty list of mirrors (in {})",
+                        name,
+                        val.definition
+                    );
+                }
+                Some((names, val.definition.to_string()))
+            }
+            None => None,
+        };
 
         self.add(
             &name,
@@ -1,4 +1,5 @@
 pub use self::source::GitSource;
-pub use self::utils::{fetch, GitCheckout, GitDatabase, GitRemote};
+pub use self::utils::{fetch, FetchDepth, GitCheckout, GitDatabase, GitRemote};
 mod source;
+pub mod ssh;
 mod utils;
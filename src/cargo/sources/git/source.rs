@@ -1,7 +1,7 @@
 use crate::core::source::{MaybePackage, Source, SourceId};
-use crate::core::GitReference;
 use crate::core::{Dependency, Package, PackageId, Summary};
-use crate::sources::git::utils::GitRemote;
+use crate::core::{GitReference, GitSubmodules};
+use crate::sources::git::utils::{FetchDepth, GitRemote};
 use crate::sources::PathSource;
 use crate::util::errors::CargoResult;
 use crate::util::hex::short_hash;
@@ -9,6 +9,7 @@ use crate::util::Config;
 use anyhow::Context;
 use log::trace;
 use std::fmt::{self, Debug, Formatter};
+use std::path::PathBuf;
 use url::Url;
 
 pub struct GitSource<'cfg> {
@@ -18,6 +19,10 @@ pub struct GitSource<'cfg> {
     source_id: SourceId,
     path_source: Option<PathSource<'cfg>>,
     ident: String,
+    /// How much of the history of the repository is fetched.
+    depth: FetchDepth,
+    /// The only directory of the repository which is checked out, if any.
+    sparse_path: Option<PathBuf>,
     config: &'cfg Config,
 }
 
@@ -26,20 +31,31 @@ impl<'cfg> GitSource<'cfg> {
         assert!(source_id.is_git(), "id is not git, id={}", source_id);
 
         let remote = GitRemote::new(source_id.url());
-        let ident = ident(&source_id);
+        let depth = FetchDepth::for_config(config);
+        let ident = database_ident(&source_id, depth);
+
+        let mut manifest_reference = source_id.git_reference().unwrap().clone();
+        let locked_rev = match source_id.precise() {
+            Some(s) if is_full_oid(s) => Some(git2::Oid::from_str(s)?),
+            // Anything else, such as an abbreviated hash or a tag passed to
+            // `cargo update --precise`, is looked up in the repository in
+            // place of the reference from the manifest.
+            Some(s) => {
+                manifest_reference = GitReference::Rev(s.to_string());
+                None
+            }
+            None => None,
+        };
 
         let source = GitSource {
             remote,
-            manifest_reference: source_id.git_reference().unwrap().clone(),
-            locked_rev: match source_id.precise() {
-                Some(s) => Some(git2::Oid::from_str(s).with_context(|| {
-                    format!("precise value for git is not a git revision: {}", s)
-                })?),
-                None => None,
-            },
+            manifest_reference,
+            locked_rev,
             source_id,
             path_source: None,
             ident,
+            depth,
+            sparse_path: None,
             config,
         };
 
@@ -50,6 +66,16 @@ impl<'cfg> GitSource<'cfg> {
         self.remote.url()
     }
 
+    /// Only checks out the directory `path` of the repository, relative to
+    /// its root, and only fetches the commits the references point to, for
+    /// `cargo install --path-in-repo`. The packages of the source are the
+    /// ones in this directory.
+    pub fn set_sparse_path(&mut self, path: PathBuf) {
+        self.depth = FetchDepth::Shallow;
+        self.ident = database_ident(&self.source_id, self.depth);
+        self.sparse_path = Some(path);
+    }
+
     pub fn read_packages(&mut self) -> CargoResult<Vec<Package>> {
         if self.path_source.is_none() {
             self.update()?;
@@ -58,6 +84,24 @@ impl<'cfg> GitSource<'cfg> {
     }
 }
 
+/// Returns whether `s` is a complete hex-encoded commit hash, as opposed to
+/// some other kind of revision.
+fn is_full_oid(s: &str) -> bool {
+    s.len() == 40 && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Returns the name of the directory of the database of the repository
+/// `id`, fetched with `depth`.
+fn database_ident(id: &SourceId, depth: FetchDepth) -> String {
+    let mut ident = ident(id);
+    // Shallow clones are kept apart from full ones, which older versions of
+    // Cargo may try to fetch into with `libgit2`.
+    if depth == FetchDepth::Shallow {
+        ident.push_str("-shallow");
+    }
+    ident
+}
+
 fn ident(id: &SourceId) -> String {
     let ident = id
         .canonical_url()
@@ -158,6 +202,7 @@ impl<'cfg> Source for GitSource<'cfg> {
                     db,
                     &self.manifest_reference,
                     locked_rev,
+                    self.depth,
                     self.config,
                 )?
             }
@@ -171,14 +216,45 @@ impl<'cfg> Source for GitSource<'cfg> {
         // Check out `actual_rev` from the database to a scoped location on the
         // filesystem. This will use hard links and such to ideally make the
         // checkout operation here pretty fast.
+        //
+        // Checkouts skipping some submodules are kept apart from the ones
+        // with all of them.
+        let submodules = self.source_id.git_submodules().unwrap();
+        let mut checkout_name = short_id.as_str().to_string();
+        if *submodules != GitSubmodules::All {
+            checkout_name.push('-');
+            checkout_name.push_str(&short_hash(submodules));
+        }
+        // So are sparse checkouts.
+        if let Some(sparse_path) = &self.sparse_path {
+            checkout_name.push_str("-sparse-");
+            checkout_name.push_str(&short_hash(sparse_path));
+        }
         let checkout_path = git_path
             .join("checkouts")
             .join(&self.ident)
-            .join(short_id.as_str());
-        db.copy_to(actual_rev, &checkout_path, self.config)?;
-
+            .join(checkout_name);
+        db.copy_to(
+            actual_rev,
+            &checkout_path,
+            submodules,
+            self.sparse_path.as_deref(),
+            self.config,
+        )?;
+
+        let mut packages_path = checkout_path;
+        if let Some(sparse_path) = &self.sparse_path {
+            packages_path.push(sparse_path);
+            if !packages_path.is_dir() {
+                anyhow::bail!(
+                    "`{}` is not a directory of the git repository `{}`",
+                    sparse_path.display(),
+                    self.remote.url()
+                );
+            }
+        }
         let source_id = self.source_id.with_precise(Some(actual_rev.to_string()));
-        let path_source = PathSource::new_recursive(&checkout_path, source_id, self.config);
+        let path_source = PathSource::new_recursive(&packages_path, source_id, self.config);
 
         self.path_source = Some(path_source);
         self.locked_rev = Some(actual_rev);
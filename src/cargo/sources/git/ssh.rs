@@ -0,0 +1,529 @@
+//! Support for the OpenSSH configuration in the SSH transport of `libgit2`.
+//!
+//! `libgit2` connects to SSH remotes with `libssh2`, which knows nothing of
+//! `~/.ssh/config` nor of `known_hosts` files. So that `ssh://` URLs behave
+//! the same as with the `ssh` command, Cargo reads the configuration itself:
+//!
+//! * Host aliases are resolved, with their `HostName`, `User`, and `Port`.
+//! * The keys of `IdentityFile` are tried after the ones of the SSH agent.
+//!   `libssh2` only finds the agent through `SSH_AUTH_SOCK`, so hosts with
+//!   another `IdentityAgent` are fetched with the `git` command instead.
+//! * The key of the host is checked against the `known_hosts` files, and the
+//!   entries of the `net.ssh.known-hosts` config value. Unknown hosts are
+//!   rejected unless `StrictHostKeyChecking` accepts them.
+
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::format_err;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use crypto_hash::{Algorithm, Hasher};
+use log::debug;
+use url::Url;
+
+use crate::util::errors::CargoResult;
+use crate::util::{Config, Sha256};
+
+/// The SSH settings of a remote, with the URL to connect to it.
+pub struct SshSettings<'cfg> {
+    /// The URL of the remote, with its host alias resolved.
+    pub url: String,
+    /// The private keys to try after the ones of the SSH agent.
+    pub identity_files: Vec<PathBuf>,
+    /// The `IdentityAgent` of the host, if set.
+    identity_agent: Option<String>,
+    /// The `known_hosts` files the key of the host is looked up in.
+    known_hosts_files: Vec<PathBuf>,
+    /// The `known_hosts` entries of the `net.ssh.known-hosts` config value.
+    config_known_hosts: Vec<String>,
+    /// Whether hosts missing from `known_hosts` are accepted.
+    accept_unknown_hosts: bool,
+    /// Why the key of the host was rejected, if it was.
+    host_key_error: RefCell<Option<String>>,
+    /// Whether a warning about the key of the host was printed, so that it
+    /// isn't repeated for every connection.
+    warned: Cell<bool>,
+    config: &'cfg Config,
+}
+
+impl<'cfg> SshSettings<'cfg> {
+    /// Returns the settings of the remote at `url`, or `None` if it isn't an
+    /// SSH remote.
+    pub fn new(url: &str, config: &'cfg Config) -> CargoResult<Option<SshSettings<'cfg>>> {
+        let original = url;
+        let mut url = match Url::parse(url) {
+            Ok(url) if url.scheme() == "ssh" => url,
+            _ => return Ok(None),
+        };
+        let alias = match url.host_str() {
+            Some(host) => host.to_string(),
+            None => return Ok(None),
+        };
+        let home = home::home_dir().unwrap_or_default();
+        let host = HostConfig::load(&home, &alias);
+
+        // The URL was parsed from one with a host, so these can't fail.
+        if let Some(hostname) = &host.hostname {
+            url.set_host(Some(&hostname.replace("%h", &alias))).unwrap();
+        }
+        if url.username().is_empty() {
+            if let Some(user) = &host.user {
+                url.set_username(user).unwrap();
+            }
+        }
+        if url.port().is_none() {
+            url.set_port(host.port).unwrap();
+        }
+        if url.as_str() != original {
+            debug!("resolved ssh host `{}` of {} to {}", alias, original, url);
+        }
+
+        let known_hosts_files = if host.user_known_hosts_files.is_empty() {
+            vec![
+                home.join(".ssh/known_hosts"),
+                home.join(".ssh/known_hosts2"),
+            ]
+        } else {
+            host.user_known_hosts_files
+        };
+        let known_hosts_files = known_hosts_files
+            .into_iter()
+            .chain(vec![
+                PathBuf::from("/etc/ssh/ssh_known_hosts"),
+                PathBuf::from("/etc/ssh/ssh_known_hosts2"),
+            ])
+            .collect();
+        let config_known_hosts = config
+            .net_config()?
+            .ssh
+            .as_ref()
+            .and_then(|ssh| ssh.known_hosts.clone())
+            .unwrap_or_default();
+        let accept_unknown_hosts = match host.strict_host_key_checking.as_deref() {
+            Some(value) => ["no", "off", "accept-new"].contains(&&*value.to_lowercase()),
+            None => false,
+        };
+
+        Ok(Some(SshSettings {
+            url: url.to_string(),
+            identity_files: host.identity_files,
+            identity_agent: host.identity_agent,
+            known_hosts_files,
+            config_known_hosts,
+            accept_unknown_hosts,
+            host_key_error: RefCell::new(None),
+            warned: Cell::new(false),
+            config,
+        }))
+    }
+
+    /// Checks the key of the host in `cert`, which is a callback of
+    /// `RemoteCallbacks::certificate_check`. The reason a key is rejected is
+    /// kept for `host_key_error`.
+    ///
+    /// `git2` only exposes the hashes of the key, so the keys are compared by
+    /// their SHA-256 fingerprints. A key is only rejected if it is revoked,
+    /// or if the host is known with another key of the same type: a host
+    /// only known with keys of other types may just have negotiated a type
+    /// missing from `known_hosts`, so it is a warning, like an unknown host.
+    pub fn check_host_key(
+        &self,
+        cert: &git2::cert::Cert<'_>,
+        host: &str,
+    ) -> Result<git2::CertificateCheckStatus, git2::Error> {
+        let port = Url::parse(&self.url).ok().and_then(|url| url.port());
+        let name = match port {
+            Some(port) if port != 22 => format!("[{}]:{}", host, port),
+            _ => host.to_string(),
+        };
+        let hostkey = cert.as_hostkey();
+        let offered = match hostkey.and_then(|hostkey| hostkey.hash_sha256()) {
+            Some(hash) => hash,
+            None => {
+                if self.accept_unknown_hosts {
+                    debug!("no SHA-256 fingerprint of the key of `{}`", name);
+                    return Ok(git2::CertificateCheckStatus::CertificateOk);
+                }
+                let error = format!("the key of `{}` has no SHA-256 fingerprint to check", name);
+                let e = git2::Error::from_str(&error);
+                *self.host_key_error.borrow_mut() = Some(error);
+                return Err(e);
+            }
+        };
+        let key_type = match hostkey.and_then(|hostkey| hostkey.hostkey_type()) {
+            Some(git2::cert::SshHostKeyType::Unknown) | None => None,
+            Some(key_type) => Some(key_type.name()),
+        };
+        let fingerprint = format!("SHA256:{}", STANDARD.encode(offered).trim_end_matches('='));
+
+        let mut known_keys = Vec::new();
+        for (i, line) in self.config_known_hosts.iter().enumerate() {
+            let location = format!("the `net.ssh.known-hosts` config value (entry {})", i + 1);
+            known_keys.extend(match_line(line, &name).map(|entry| (entry, location)));
+        }
+        for file in &self.known_hosts_files {
+            let contents = match fs::read_to_string(file) {
+                Ok(contents) => contents,
+                Err(_) => continue,
+            };
+            for (i, line) in contents.lines().enumerate() {
+                let location = format!("{}:{}", file.display(), i + 1);
+                known_keys.extend(match_line(line, &name).map(|entry| (entry, location)));
+            }
+        }
+
+        let error = if let Some((_, location)) = known_keys
+            .iter()
+            .find(|(entry, _)| entry.revoked && entry.fingerprint() == *offered)
+        {
+            format!(
+                "the key of `{}` ({}) is revoked by {}",
+                name, fingerprint, location
+            )
+        } else if known_keys
+            .iter()
+            .any(|(entry, _)| !entry.revoked && entry.fingerprint() == *offered)
+        {
+            return Ok(git2::CertificateCheckStatus::CertificateOk);
+        } else if let Some((entry, location)) = known_keys
+            .iter()
+            .find(|(entry, _)| !entry.revoked && Some(&*entry.key_type) == key_type)
+        {
+            format!(
+                "the {} key of `{}` ({}) doesn't match the one of {}\n\n\
+                 Its key may have been changed, or someone may be impersonating \
+                 it. Update its key once you made sure the new one is correct.",
+                entry.key_type, name, fingerprint, location
+            )
+        } else if let Some((_, location)) = known_keys.iter().find(|(entry, _)| !entry.revoked) {
+            let key_type = key_type.unwrap_or("unknown");
+            self.warn(&format!(
+                "the {} key of `{}` ({}) can't be checked, as {} only has keys \
+                 of other types for it, connecting anyway\n\n\
+                 Add its {} key, for example with `ssh-keyscan`, once you made \
+                 sure it is correct.",
+                key_type, name, fingerprint, location, key_type
+            ));
+            return Ok(git2::CertificateCheckStatus::CertificateOk);
+        } else if self.accept_unknown_hosts {
+            debug!("accepting the key of unknown host `{}`", name);
+            return Ok(git2::CertificateCheckStatus::CertificateOk);
+        } else {
+            format!(
+                "`{}` isn't a known host, its key is {}\n\n\
+                 Add its key to `~/.ssh/known_hosts`, for example by connecting \
+                 to it with `ssh` once, or to the `net.ssh.known-hosts` config \
+                 value, once you made sure the key is correct.",
+                name, fingerprint
+            )
+        };
+        let e = git2::Error::from_str(&error);
+        *self.host_key_error.borrow_mut() = Some(error);
+        Err(e)
+    }
+
+    /// Prints a warning about the key of the host, once per remote.
+    fn warn(&self, msg: &str) {
+        if !self.warned.replace(true) {
+            let _ = self.config.shell().warn(msg);
+        }
+    }
+
+    /// Returns the error for the rejected key of the host, if it was.
+    pub fn host_key_error(&self) -> Option<anyhow::Error> {
+        self.host_key_error
+            .borrow()
+            .as_ref()
+            .map(|error| format_err!("host key verification failed: {}", error))
+    }
+
+    /// Returns whether the host has an `IdentityAgent` other than the one of
+    /// `SSH_AUTH_SOCK`. `libssh2` only looks for the agent there, and the
+    /// variable can't be changed without affecting the other threads, so
+    /// these hosts must be fetched with `ssh`.
+    pub fn has_identity_agent(&self) -> bool {
+        match self.identity_agent.as_deref() {
+            Some(agent) => agent != "SSH_AUTH_SOCK" && agent != "$SSH_AUTH_SOCK",
+            None => false,
+        }
+    }
+}
+
+/// The settings of `~/.ssh/config` for a host.
+#[derive(Debug, Default)]
+pub struct HostConfig {
+    pub hostname: Option<String>,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    pub identity_files: Vec<PathBuf>,
+    pub identity_agent: Option<String>,
+    pub user_known_hosts_files: Vec<PathBuf>,
+    pub strict_host_key_checking: Option<String>,
+}
+
+impl HostConfig {
+    /// Loads the settings of `host` from `.ssh/config` in `home`. Errors
+    /// reading the file are ignored, like `ssh` does for a missing file.
+    pub fn load(home: &Path, host: &str) -> HostConfig {
+        let mut config = HostConfig::default();
+        config.read(home, &home.join(".ssh/config"), host, 0);
+        config
+    }
+
+    fn read(&mut self, home: &Path, path: &Path, host: &str, depth: u32) {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                debug!("failed to read `{}`: {}", path.display(), e);
+                return;
+            }
+        };
+        // Settings before the first `Host` apply to every host.
+        let mut matching = true;
+        for line in contents.lines() {
+            let (keyword, args) = match split_line(line) {
+                Some(line) => line,
+                None => continue,
+            };
+            let first = args.first().map(String::as_str);
+            match &*keyword.to_lowercase() {
+                "host" => matching = host_matches(host, &args),
+                // `Match` conditions aren't supported, so their settings are
+                // ignored.
+                "match" => matching = args.iter().any(|arg| arg.eq_ignore_ascii_case("all")),
+                _ if !matching => {}
+                "include" if depth < 16 => {
+                    for pattern in &args {
+                        for path in include_paths(home, pattern) {
+                            self.read(home, &path, host, depth + 1);
+                        }
+                    }
+                }
+                "hostname" if self.hostname.is_none() => {
+                    self.hostname = first.map(str::to_string);
+                }
+                "user" if self.user.is_none() => self.user = first.map(str::to_string),
+                "port" if self.port.is_none() => self.port = first.and_then(|p| p.parse().ok()),
+                "identityfile" => self
+                    .identity_files
+                    .extend(first.map(|path| expand_path(home, path, host))),
+                "identityagent" if self.identity_agent.is_none() => {
+                    self.identity_agent = first.map(str::to_string);
+                }
+                "userknownhostsfile" if self.user_known_hosts_files.is_empty() => {
+                    self.user_known_hosts_files = args
+                        .iter()
+                        .map(|path| expand_path(home, path, host))
+                        .collect();
+                }
+                "stricthostkeychecking" if self.strict_host_key_checking.is_none() => {
+                    self.strict_host_key_checking = first.map(str::to_string);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Splits a line of `~/.ssh/config` into its keyword and arguments, or
+/// returns `None` for blank lines and comments.
+fn split_line(line: &str) -> Option<(String, Vec<String>)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let end = line
+        .find(|c: char| c.is_whitespace() || c == '=')
+        .unwrap_or(line.len());
+    let (keyword, rest) = line.split_at(end);
+    let rest = rest.trim_start();
+    let rest = rest.strip_prefix('=').unwrap_or(rest);
+
+    let mut args = Vec::new();
+    let mut chars = rest.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let mut arg = String::new();
+        if c == '"' {
+            arg.extend(chars.by_ref().take_while(|&c| c != '"'));
+        } else {
+            arg.push(c);
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                arg.push(c);
+            }
+        }
+        args.push(arg);
+    }
+    Some((keyword.to_string(), args))
+}
+
+/// Returns whether `host` matches the patterns of a `Host` line, which is
+/// when one of them matches and none of the negated ones do.
+fn host_matches(host: &str, patterns: &[String]) -> bool {
+    let mut matches = false;
+    for pattern in patterns {
+        match pattern.strip_prefix('!') {
+            Some(pattern) if wildcard_matches(pattern, host) => return false,
+            Some(_) => {}
+            None => matches |= wildcard_matches(pattern, host),
+        }
+    }
+    matches
+}
+
+/// Matches `text` against `pattern`, where `*` matches any string and `?`
+/// any character.
+fn wildcard_matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_lowercase().chars().collect::<Vec<_>>();
+    let text = text.to_lowercase().chars().collect::<Vec<_>>();
+    fn matches(pattern: &[char], text: &[char]) -> bool {
+        match pattern.split_first() {
+            None => text.is_empty(),
+            Some(('*', rest)) => (0..=text.len()).any(|i| matches(rest, &text[i..])),
+            Some((&p, rest)) => match text.split_first() {
+                Some((&t, text)) => (p == '?' || p == t) && matches(rest, text),
+                None => false,
+            },
+        }
+    }
+    matches(&pattern, &text)
+}
+
+/// Expands `~` and the `%d` and `%h` tokens of a path in `~/.ssh/config`.
+fn expand_path(home: &Path, path: &str, host: &str) -> PathBuf {
+    let home_str = home.to_string_lossy();
+    let path = path.replace("%d", &home_str).replace("%h", host);
+    match path.strip_prefix("~/") {
+        Some(rest) => home.join(rest),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Returns the files included by an `Include` of `pattern`, which may have
+/// wildcards in its file name.
+fn include_paths(home: &Path, pattern: &str) -> Vec<PathBuf> {
+    let path = expand_path(home, pattern, "");
+    let path = if path.is_relative() {
+        home.join(".ssh").join(path)
+    } else {
+        path
+    };
+    let name = match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) if name.contains(&['*', '?'][..]) => name.to_string(),
+        _ => return vec![path],
+    };
+    let dir = path.parent().unwrap();
+    let mut paths = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            matches!(entry.file_name().to_str(), Some(file) if wildcard_matches(&name, file))
+        })
+        .map(|entry| entry.path())
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+}
+
+/// A key of a `known_hosts` entry for the host being checked.
+#[derive(Debug, PartialEq)]
+pub struct KnownKey {
+    /// The type of the key, like `ssh-ed25519`.
+    pub key_type: String,
+    /// The key, in the SSH wire format.
+    pub key: Vec<u8>,
+    /// Whether the entry is `@revoked`.
+    pub revoked: bool,
+}
+
+impl KnownKey {
+    /// Returns the SHA-256 hash of the key, which `git2` gives for the key
+    /// of the host.
+    fn fingerprint(&self) -> [u8; 32] {
+        Sha256::new().update(&self.key).finish()
+    }
+}
+
+/// Returns the key of the `known_hosts` entry `line` if it is one for
+/// `name`, which is the host name with its port if it isn't 22.
+pub fn match_line(line: &str, name: &str) -> Option<KnownKey> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let mut fields = line.split_whitespace();
+    let mut hosts = fields.next()?;
+    let mut revoked = false;
+    if hosts.starts_with('@') {
+        match hosts {
+            "@revoked" => revoked = true,
+            // Certificate authorities aren't supported.
+            _ => return None,
+        }
+        hosts = fields.next()?;
+    }
+    let key_type = fields.next()?.to_string();
+    let key = STANDARD.decode(fields.next()?).ok()?;
+
+    let mut matches = false;
+    for pattern in hosts.split(',') {
+        if pattern.starts_with("|1|") {
+            matches |= hashed_host_matches(pattern, name);
+        } else {
+            match pattern.strip_prefix('!') {
+                Some(pattern) if wildcard_matches(pattern, name) => return None,
+                Some(_) => {}
+                None => matches |= wildcard_matches(pattern, name),
+            }
+        }
+    }
+    if !matches {
+        return None;
+    }
+    Some(KnownKey {
+        key_type,
+        key,
+        revoked,
+    })
+}
+
+/// Returns whether the hashed host `|1|salt|hash` of a `known_hosts` entry is
+/// `name`, where the hash is the HMAC-SHA1 of the name keyed with the salt.
+fn hashed_host_matches(hashed: &str, name: &str) -> bool {
+    let mut parts = hashed["|1|".len()..].split('|');
+    let (salt, hash) = match (parts.next(), parts.next()) {
+        (Some(salt), Some(hash)) => (salt, hash),
+        _ => return false,
+    };
+    let (salt, hash) = match (STANDARD.decode(salt), STANDARD.decode(hash)) {
+        (Ok(salt), Ok(hash)) => (salt, hash),
+        _ => return false,
+    };
+    hmac_sha1(&salt, name.as_bytes()) == hash
+}
+
+fn hmac_sha1(key: &[u8], message: &[u8]) -> Vec<u8> {
+    const BLOCK_SIZE: usize = 64;
+    let mut block = [0; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        let key = crypto_hash::digest(Algorithm::SHA1, key);
+        block[..key.len()].copy_from_slice(&key);
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let pad = |byte: u8| block.iter().map(|b| b ^ byte).collect::<Vec<_>>();
+
+    let mut inner = Hasher::new(Algorithm::SHA1);
+    let _ = inner.write_all(&pad(0x36));
+    let _ = inner.write_all(message);
+    let mut outer = Hasher::new(Algorithm::SHA1);
+    let _ = outer.write_all(&pad(0x5c));
+    let _ = outer.write_all(&inner.finish());
+    outer.finish()
+}
@@ -106,11 +106,14 @@
 //!
 //! That's the dream at least, we'll see how this plays out.
 
-use crate::core::GitReference;
+use super::ssh::SshSettings;
+use crate::core::{GitReference, GitSubmodules};
+use crate::ops::HttpTimeout;
 use crate::util::errors::{CargoResult, CargoResultExt};
 use crate::util::paths;
 use crate::util::process_builder::process;
 use crate::util::{network, Config, IntoUrl, Progress};
+use crate::version;
 use anyhow::{anyhow, Context};
 use curl::easy::List;
 use git2::{self, ErrorClass, ObjectType};
@@ -139,6 +142,29 @@ impl GitShortID {
     }
 }
 
+/// How much of the history of a remote repository is fetched.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FetchDepth {
+    /// All of the history of the fetched references.
+    Full,
+    /// Only the commits the fetched references point to. `libgit2` doesn't
+    /// support shallow repositories, so these are fetched with the `git` CLI
+    /// or gitoxide.
+    Shallow,
+}
+
+impl FetchDepth {
+    /// Returns the depth of fetches into the git databases and registry
+    /// indices, which is shallow with `-Z git-shallow`.
+    pub fn for_config(config: &Config) -> FetchDepth {
+        if config.cli_unstable().git_shallow {
+            FetchDepth::Shallow
+        } else {
+            FetchDepth::Full
+        }
+    }
+}
+
 /// `GitRemote` represents a remote repository. It gets cloned into a local
 /// `GitDatabase`.
 #[derive(PartialEq, Clone, Debug, Serialize)]
@@ -168,6 +194,10 @@ pub struct GitCheckout<'a> {
     revision: git2::Oid,
     #[serde(skip_serializing)]
     repo: git2::Repository,
+    /// The only directory of the repository which is checked out, if the
+    /// checkout is sparse.
+    #[serde(skip_serializing)]
+    sparse_path: Option<PathBuf>,
 }
 
 // Implementations
@@ -191,6 +221,7 @@ impl GitRemote {
         db: Option<GitDatabase>,
         reference: &GitReference,
         locked_rev: Option<git2::Oid>,
+        depth: FetchDepth,
         cargo_config: &Config,
     ) -> CargoResult<(GitDatabase, git2::Oid)> {
         // If we have a previous instance of `GitDatabase` then fetch into that
@@ -198,8 +229,17 @@ impl GitRemote {
         // populated the database with the latest version of `reference`, so
         // return that database and the rev we resolve to.
         if let Some(mut db) = db {
-            fetch(&mut db.repo, self.url.as_str(), reference, cargo_config)
-                .context(format!("failed to fetch into: {}", into.display()))?;
+            fetch(
+                &mut db.repo,
+                self.url.as_str(),
+                reference,
+                depth,
+                cargo_config,
+            )
+            .and_then(|()| {
+                self.deepen_if_missing(&mut db.repo, reference, locked_rev, cargo_config)
+            })
+            .context(format!("failed to fetch into: {}", into.display()))?;
             match locked_rev {
                 Some(rev) => {
                     if db.contains(rev) {
@@ -222,7 +262,8 @@ impl GitRemote {
         }
         paths::create_dir_all(into)?;
         let mut repo = init(into, true)?;
-        fetch(&mut repo, self.url.as_str(), reference, cargo_config)
+        fetch(&mut repo, self.url.as_str(), reference, depth, cargo_config)
+            .and_then(|()| self.deepen_if_missing(&mut repo, reference, locked_rev, cargo_config))
             .context(format!("failed to clone into: {}", into.display()))?;
         let rev = match locked_rev {
             Some(rev) => rev,
@@ -239,6 +280,40 @@ impl GitRemote {
         ))
     }
 
+    /// Fetches the full history of `reference` into a shallow `repo` if the
+    /// revision to check out isn't among the fetched commits. This is the
+    /// case for `rev` pins and locked revisions which are older than the tips
+    /// of the fetched branches.
+    fn deepen_if_missing(
+        &self,
+        repo: &mut git2::Repository,
+        reference: &GitReference,
+        locked_rev: Option<git2::Oid>,
+        cargo_config: &Config,
+    ) -> CargoResult<()> {
+        if !repo.is_shallow() {
+            return Ok(());
+        }
+        let found = match locked_rev {
+            Some(rev) => repo.find_commit(rev).is_ok(),
+            None => reference.resolve(repo, None).is_ok(),
+        };
+        if found {
+            return Ok(());
+        }
+        debug!(
+            "deepening shallow clone of {} to find {:?}",
+            self.url, reference
+        );
+        fetch(
+            repo,
+            self.url.as_str(),
+            reference,
+            FetchDepth::Full,
+            cargo_config,
+        )
+    }
+
     pub fn db_at(&self, db_path: &Path) -> CargoResult<GitDatabase> {
         let repo = git2::Repository::open(db_path)?;
         Ok(GitDatabase {
@@ -250,15 +325,19 @@ impl GitRemote {
 }
 
 impl GitDatabase {
+    /// Checks out `rev` to `dest`. If `sparse_path` is given, only this
+    /// directory of the repository is checked out.
     pub fn copy_to(
         &self,
         rev: git2::Oid,
         dest: &Path,
+        submodules: &GitSubmodules,
+        sparse_path: Option<&Path>,
         cargo_config: &Config,
     ) -> CargoResult<GitCheckout<'_>> {
         let mut checkout = None;
         if let Ok(repo) = git2::Repository::open(dest) {
-            let mut co = GitCheckout::new(dest, self, rev, repo);
+            let mut co = GitCheckout::new(dest, self, rev, repo, sparse_path);
             if !co.is_fresh() {
                 // After a successful fetch operation the subsequent reset can
                 // fail sometimes for corrupt repositories where the fetch
@@ -280,9 +359,9 @@ impl GitDatabase {
         };
         let checkout = match checkout {
             Some(c) => c,
-            None => GitCheckout::clone_into(dest, self, rev, cargo_config)?,
+            None => GitCheckout::clone_into(dest, self, rev, sparse_path, cargo_config)?,
         };
-        checkout.update_submodules(cargo_config)?;
+        checkout.update_submodules(submodules, cargo_config)?;
         Ok(checkout)
     }
 
@@ -386,12 +465,14 @@ impl<'a> GitCheckout<'a> {
         database: &'a GitDatabase,
         revision: git2::Oid,
         repo: git2::Repository,
+        sparse_path: Option<&Path>,
     ) -> GitCheckout<'a> {
         GitCheckout {
             location: path.to_path_buf(),
             database,
             revision,
             repo,
+            sparse_path: sparse_path.map(Path::to_path_buf),
         }
     }
 
@@ -399,6 +480,7 @@ impl<'a> GitCheckout<'a> {
         into: &Path,
         database: &'a GitDatabase,
         revision: git2::Oid,
+        sparse_path: Option<&Path>,
         config: &Config,
     ) -> CargoResult<GitCheckout<'a>> {
         let dirname = into.parent().unwrap();
@@ -420,7 +502,7 @@ impl<'a> GitCheckout<'a> {
         // need authentication information we may want progress bars and such.
         let url = database.path.into_url()?;
         let mut repo = None;
-        with_fetch_options(&git_config, url.as_str(), config, &mut |fopts| {
+        with_fetch_options(&git_config, url.as_str(), None, config, &mut |fopts| {
             let mut checkout = git2::build::CheckoutBuilder::new();
             checkout.dry_run(); // we'll do this below during a `reset`
 
@@ -436,7 +518,7 @@ impl<'a> GitCheckout<'a> {
         })?;
         let repo = repo.unwrap();
 
-        let checkout = GitCheckout::new(into, database, revision, repo);
+        let checkout = GitCheckout::new(into, database, revision, repo, sparse_path);
         checkout.reset(config)?;
         Ok(checkout)
     }
@@ -455,7 +537,13 @@ impl<'a> GitCheckout<'a> {
         info!("fetch {}", self.repo.path().display());
         let url = self.database.path.into_url()?;
         let reference = GitReference::Rev(self.revision.to_string());
-        fetch(&mut self.repo, url.as_str(), &reference, cargo_config)?;
+        fetch(
+            &mut self.repo,
+            url.as_str(),
+            &reference,
+            FetchDepth::Full,
+            cargo_config,
+        )?;
         Ok(())
     }
 
@@ -478,13 +566,38 @@ impl<'a> GitCheckout<'a> {
         }
 
         let object = self.repo.find_object(self.revision, None)?;
-        reset(&self.repo, &object, config)?;
+        reset(&self.repo, &object, self.sparse_path.as_deref(), config)?;
         paths::create(ok_file)?;
         Ok(())
     }
 
-    fn update_submodules(&self, cargo_config: &Config) -> CargoResult<()> {
-        return update_submodules(&self.repo, cargo_config);
+    fn update_submodules(
+        &self,
+        submodules: &GitSubmodules,
+        cargo_config: &Config,
+    ) -> CargoResult<()> {
+        info!("update submodules for: {:?}", self.repo.workdir().unwrap());
+
+        // Only the submodules of the repository itself are filtered, the
+        // ones of the submodules which are checked out are all updated.
+        for mut child in self.repo.submodules()? {
+            let path = child.path().to_string_lossy().into_owned();
+            let sparse_skipped = match &self.sparse_path {
+                Some(sparse_path) => !child.path().starts_with(sparse_path),
+                None => false,
+            };
+            if !submodules.includes(&path) || sparse_skipped {
+                info!("skipping submodule `{}`", path);
+                continue;
+            }
+            update_submodule(&self.repo, &mut child, cargo_config).chain_err(|| {
+                format!(
+                    "failed to update submodule `{}`",
+                    child.name().unwrap_or("")
+                )
+            })?;
+        }
+        return Ok(());
 
         fn update_submodules(repo: &git2::Repository, cargo_config: &Config) -> CargoResult<()> {
             info!("update submodules for: {:?}", repo.workdir().unwrap());
@@ -543,7 +656,7 @@ impl<'a> GitCheckout<'a> {
             cargo_config
                 .shell()
                 .status("Updating", format!("git submodule `{}`", url))?;
-            fetch(&mut repo, url, &reference, cargo_config).chain_err(|| {
+            fetch(&mut repo, url, &reference, FetchDepth::Full, cargo_config).chain_err(|| {
                 format!(
                     "failed to fetch submodule `{}` from {}",
                     child.name().unwrap_or(""),
@@ -552,7 +665,7 @@ impl<'a> GitCheckout<'a> {
             })?;
 
             let obj = repo.find_object(head, None)?;
-            reset(&repo, &obj, cargo_config)?;
+            reset(&repo, &obj, None, cargo_config)?;
             update_submodules(&repo, cargo_config)
         }
     }
@@ -585,18 +698,34 @@ impl<'a> GitCheckout<'a> {
 /// credentials until we give it a reason to not do so. To ensure we don't
 /// just sit here looping forever we keep track of authentications we've
 /// attempted and we don't try the same ones again.
-fn with_authentication<T, F>(url: &str, cfg: &git2::Config, mut f: F) -> CargoResult<T>
+fn with_authentication<T, F>(
+    url: &str,
+    cfg: &git2::Config,
+    ssh: Option<&SshSettings<'_>>,
+    mut f: F,
+) -> CargoResult<T>
 where
     F: FnMut(&mut git2::Credentials<'_>) -> CargoResult<T>,
 {
     let mut cred_helper = git2::CredentialHelper::new(url);
     cred_helper.config(cfg);
 
+    // Private keys which don't exist are skipped, like `ssh` does, as
+    // libgit2 gives up on the first one failing to load.
+    let identity_files = ssh
+        .map(|ssh| ssh.identity_files.clone())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|path| path.exists())
+        .collect::<Vec<_>>();
+
     let mut ssh_username_requested = false;
     let mut cred_helper_bad = None;
     let mut ssh_agent_attempts = Vec::new();
+    let mut ssh_key_attempts = Vec::new();
     let mut any_attempts = false;
     let mut tried_sshkey = false;
+    let mut tried_identity_files = 0;
     let mut url_attempt = None;
 
     let orig_url = url;
@@ -649,6 +778,17 @@ where
             return git2::Cred::ssh_key_from_agent(username);
         }
 
+        // Then try the private keys of `IdentityFile` in `~/.ssh/config`, once
+        // each.
+        if allowed.contains(git2::CredentialType::SSH_KEY) {
+            if let Some(path) = identity_files.get(tried_identity_files) {
+                tried_identity_files += 1;
+                let username = username.unwrap();
+                ssh_key_attempts.push(format!("`{}` as `{}`", path.display(), username));
+                return git2::Cred::ssh_key(username, None, path, None);
+            }
+        }
+
         // Sometimes libgit2 will ask for a username/password in plaintext. This
         // is where Cargo would have an interactive prompt if we supported it,
         // but we currently don't! Right now the only way we support fetching a
@@ -715,23 +855,28 @@ where
                         ssh_agent_attempts.push(s.to_string());
                         return git2::Cred::ssh_key_from_agent(&s);
                     }
+                    if let Some(path) = identity_files.get(attempts - 2) {
+                        ssh_key_attempts.push(format!("`{}` as `{}`", path.display(), s));
+                        return git2::Cred::ssh_key(&s, None, path, None);
+                    }
                 }
                 Err(git2::Error::from_str("no authentication available"))
             });
 
-            // If we made two attempts then that means:
+            // If we made as many attempts as there are keys plus one then
+            // that means:
             //
             // 1. A username was requested, we returned `s`.
             // 2. An ssh key was requested, we returned to look up `s` in the
-            //    ssh agent.
-            // 3. For whatever reason that lookup failed, so we were asked again
-            //    for another mode of authentication.
+            //    ssh agent, and then each of the private keys.
+            // 3. For whatever reason these failed, so we were asked again for
+            //    another mode of authentication.
             //
-            // Essentially, if `attempts == 2` then in theory the only error was
-            // that this username failed to authenticate (e.g., no other network
-            // errors happened). Otherwise something else is funny so we bail
-            // out.
-            if attempts != 2 {
+            // Essentially, if all the keys were tried then in theory the only
+            // error was that this username failed to authenticate (e.g., no
+            // other network errors happened). Otherwise something else is
+            // funny so we bail out.
+            if attempts != identity_files.len() + 2 {
                 break;
             }
         }
@@ -741,6 +886,12 @@ where
         Err(e) => e,
     };
 
+    // A rejected host key fails the connection before any authentication,
+    // and is explained on its own.
+    if let Some(e) = ssh.and_then(|ssh| ssh.host_key_error()) {
+        return Err(e);
+    }
+
     // In the case of an authentication failure (where we tried something) then
     // we try to give a more helpful error message about precisely what we
     // tried.
@@ -768,6 +919,13 @@ where
                 names
             ));
         }
+        if !ssh_key_attempts.is_empty() {
+            msg.push_str(&format!(
+                "\n* attempted ssh authentication with the `IdentityFile` keys \
+                 of `~/.ssh/config`, but none succeeded: {}",
+                ssh_key_attempts.join(", ")
+            ));
+        }
         if let Some(failed_cred_helper) = cred_helper_bad {
             if failed_cred_helper {
                 msg.push_str(
@@ -815,9 +973,18 @@ where
     Err(err)
 }
 
-fn reset(repo: &git2::Repository, obj: &git2::Object<'_>, config: &Config) -> CargoResult<()> {
+/// Resets `repo` to `obj`, checking out only `sparse_path` if it's given.
+fn reset(
+    repo: &git2::Repository,
+    obj: &git2::Object<'_>,
+    sparse_path: Option<&Path>,
+    config: &Config,
+) -> CargoResult<()> {
     let mut pb = Progress::new("Checkout", config);
     let mut opts = git2::build::CheckoutBuilder::new();
+    if let Some(sparse_path) = sparse_path {
+        opts.path(sparse_path);
+    }
     opts.progress(|_, cur, max| {
         drop(pb.tick(cur, max));
     });
@@ -830,14 +997,18 @@ fn reset(repo: &git2::Repository, obj: &git2::Object<'_>, config: &Config) -> Ca
 pub fn with_fetch_options(
     git_config: &git2::Config,
     url: &str,
+    ssh: Option<&SshSettings<'_>>,
     config: &Config,
     cb: &mut dyn FnMut(git2::FetchOptions<'_>) -> CargoResult<()>,
 ) -> CargoResult<()> {
     let mut progress = Progress::new("Fetch", config);
     network::with_retry(config, || {
-        with_authentication(url, git_config, |f| {
+        with_authentication(url, git_config, ssh, |f| {
             let mut rcb = git2::RemoteCallbacks::new();
             rcb.credentials(f);
+            if let Some(ssh) = ssh {
+                rcb.certificate_check(move |cert, host| ssh.check_host_key(cert, host));
+            }
 
             rcb.transfer_progress(|stats| {
                 progress
@@ -859,6 +1030,7 @@ pub fn fetch(
     repo: &mut git2::Repository,
     url: &str,
     reference: &GitReference,
+    depth: FetchDepth,
     config: &Config,
 ) -> CargoResult<()> {
     if config.frozen() {
@@ -871,12 +1043,18 @@ pub fn fetch(
         anyhow::bail!("can't update a git repository in the offline mode")
     }
 
+    // Turning a shallow repository into a full one needs a fetch even if the
+    // references are up-to-date.
+    let unshallow = depth == FetchDepth::Full && repo.is_shallow();
+
     // If we're fetching from GitHub, attempt GitHub's special fast path for
     // testing if we've already got an up-to-date copy of the repository
-    match github_up_to_date(repo, url, reference, config) {
-        Ok(true) => return Ok(()),
-        Ok(false) => {}
-        Err(e) => debug!("failed to check github {:?}", e),
+    if !unshallow {
+        match github_up_to_date(repo, url, reference, config) {
+            Ok(true) => return Ok(()),
+            Ok(false) => {}
+            Err(e) => debug!("failed to check github {:?}", e),
+        }
     }
 
     // We reuse repositories quite a lot, so before we go through and update the
@@ -922,12 +1100,53 @@ pub fn fetch(
     // flavors of authentication possible while also still giving us all the
     // speed and portability of using `libgit2`.
     if let Some(true) = config.net_config()?.git_fetch_with_cli {
-        return fetch_with_cli(repo, url, &refspecs, tags, config);
+        return fetch_with_cli(repo, url, &refspecs, tags, depth, config);
     }
 
-    debug!("doing a fetch for {}", url);
+    // `gitoxide` is a pure Rust implementation of git, which is faster than
+    // `libgit2` for large fetches like the one of the crates.io index. It
+    // also handles shallow repositories, unlike `libgit2`.
+    if config.cli_unstable().gitoxide || depth == FetchDepth::Shallow || unshallow {
+        return fetch_with_gitoxide(repo, url, &refspecs, tags, depth, config);
+    }
+
+    // libgit2 doesn't read the OpenSSH configuration, so host aliases are
+    // resolved here. It can't use another SSH agent than the one of
+    // `SSH_AUTH_SOCK` though, so hosts with an `IdentityAgent` are fetched
+    // with `ssh`, which reads the configuration itself.
+    let ssh = SshSettings::new(url, config)?;
+    if matches!(&ssh, Some(ssh) if ssh.has_identity_agent()) {
+        return fetch_with_cli(repo, url, &refspecs, tags, depth, config);
+    }
+    let url = ssh.as_ref().map_or(url, |ssh| ssh.url.as_str());
+
+    // Listing the references of the remote is a lot cheaper than negotiating
+    // a pack with it, so first narrow the fetch down to the references which
+    // changed since the last one, and skip it entirely if none did. This is
+    // only worth it if something was fetched before.
     let git_config = git2::Config::open_default()?;
-    with_fetch_options(&git_config, url, config, &mut |mut opts| {
+    let fetched_before = refspecs
+        .iter()
+        .any(|refspec| match refspec.split_once(':') {
+            Some((_, dst)) => repo.refname_to_id(dst).is_ok(),
+            None => false,
+        });
+    if fetched_before {
+        match remote_heads(repo, url, ssh.as_ref(), &git_config) {
+            Ok(heads) => {
+                let stale = stale_refspecs(repo, url, reference, &refspecs, &heads)?;
+                if stale.is_empty() {
+                    debug!("remote references of {} are up-to-date", url);
+                    return Ok(());
+                }
+                refspecs = stale;
+            }
+            Err(e) => debug!("failed to list the references of {}: {:?}", url, e),
+        }
+    }
+
+    debug!("doing a fetch for {}", url);
+    with_fetch_options(&git_config, url, ssh.as_ref(), config, &mut |mut opts| {
         if tags {
             opts.download_tags(git2::AutotagOption::All);
         }
@@ -971,11 +1190,79 @@ pub fn fetch(
     })
 }
 
+/// Lists the references advertised by the remote at `url`, with the objects
+/// they point to.
+fn remote_heads(
+    repo: &git2::Repository,
+    url: &str,
+    ssh: Option<&SshSettings<'_>>,
+    git_config: &git2::Config,
+) -> CargoResult<Vec<(String, git2::Oid)>> {
+    // Failures aren't retried here, as the fetch which follows does that.
+    with_authentication(url, git_config, ssh, |f| {
+        let mut rcb = git2::RemoteCallbacks::new();
+        rcb.credentials(f);
+        if let Some(ssh) = ssh {
+            rcb.certificate_check(move |cert, host| ssh.check_host_key(cert, host));
+        }
+        let mut remote = repo.remote_anonymous(url)?;
+        let connection = remote.connect_auth(git2::Direction::Fetch, Some(rcb), None)?;
+        let heads = connection
+            .list()?
+            .iter()
+            .map(|head| (head.name().to_string(), head.oid()))
+            .collect::<Vec<_>>();
+        Ok(heads)
+    })
+}
+
+/// Returns the refspecs of `refspecs` whose remote reference in `heads`
+/// doesn't match the local one it is fetched into.
+///
+/// Refspecs with a glob are always returned, as are those whose local
+/// reference points to an object missing from the repository. Remote
+/// references which don't exist are dropped, as fetching them wouldn't
+/// update anything, unless `reference` is the branch or tag which was
+/// deleted: its local copy would be silently used otherwise.
+fn stale_refspecs(
+    repo: &git2::Repository,
+    url: &str,
+    reference: &GitReference,
+    refspecs: &[String],
+    heads: &[(String, git2::Oid)],
+) -> CargoResult<Vec<String>> {
+    let mut stale = Vec::new();
+    for refspec in refspecs {
+        let (src, dst) = match refspec.split_once(':') {
+            Some((src, dst)) if !src.contains('*') => (src, dst),
+            _ => {
+                stale.push(refspec.clone());
+                continue;
+            }
+        };
+        let local = repo.refname_to_id(dst).ok();
+        let remote = match heads.iter().find(|(name, _)| name == src) {
+            Some((_, oid)) => *oid,
+            None => match reference {
+                GitReference::Branch(_) | GitReference::Tag(_) if local.is_some() => {
+                    anyhow::bail!("the reference `{}` was deleted from `{}`", src, url)
+                }
+                _ => continue,
+            },
+        };
+        if local != Some(remote) || repo.find_object(remote, None).is_err() {
+            stale.push(refspec.clone());
+        }
+    }
+    Ok(stale)
+}
+
 fn fetch_with_cli(
     repo: &mut git2::Repository,
     url: &str,
     refspecs: &[String],
     tags: bool,
+    depth: FetchDepth,
     config: &Config,
 ) -> CargoResult<()> {
     let mut cmd = process("git");
@@ -983,6 +1270,15 @@ fn fetch_with_cli(
     if tags {
         cmd.arg("--tags");
     }
+    match depth {
+        FetchDepth::Shallow => {
+            cmd.arg("--depth=1");
+        }
+        FetchDepth::Full if repo.is_shallow() => {
+            cmd.arg("--unshallow");
+        }
+        FetchDepth::Full => {}
+    }
     cmd.arg("--force") // handle force pushes
         .arg("--update-head-ok") // see discussion in #2078
         .arg(url)
@@ -1006,6 +1302,170 @@ fn fetch_with_cli(
     Ok(())
 }
 
+fn fetch_with_gitoxide(
+    repo: &mut git2::Repository,
+    url: &str,
+    refspecs: &[String],
+    tags: bool,
+    depth: FetchDepth,
+    config: &Config,
+) -> CargoResult<()> {
+    use gix::odb::pack::index::write::ProgressId;
+    use gix::remote::fetch::Shallow;
+    use std::num::NonZeroU32;
+    use std::sync::atomic::Ordering;
+    use std::sync::mpsc::{self, RecvTimeoutError};
+    use std::thread;
+    use std::time::Duration;
+
+    debug!("doing a fetch for {} with gitoxide", url);
+    config
+        .shell()
+        .verbose(|s| s.status("Fetching", format!("{} with gitoxide", url)))?;
+    let shallow = match depth {
+        FetchDepth::Shallow => Shallow::DepthAtRemote(NonZeroU32::new(1).unwrap()),
+        FetchDepth::Full if repo.is_shallow() => Shallow::undo(),
+        FetchDepth::Full => Shallow::NoChange,
+    };
+    let fetch = GitoxideFetch {
+        path: repo.path().to_path_buf(),
+        url: url.to_string(),
+        // Like the `git` CLI, force updates to handle force pushes.
+        refspecs: refspecs.iter().map(|spec| format!("+{}", spec)).collect(),
+        tags,
+        shallow,
+        config_overrides: gitoxide_config_overrides(config)?,
+    };
+
+    let index_objects: gix::progress::Id = ProgressId::IndexObjects.into();
+    let mut progress = Progress::new("Fetch", config);
+    network::with_retry(config, || {
+        // The fetch runs on its own thread, so that the progress gitoxide
+        // tracks can be reported from this one while it goes on.
+        let root = gix::progress::tree::Root::new();
+        let fetch = fetch.clone();
+        let fetch_progress = root.add_child("fetch");
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || drop(tx.send(fetch.run(fetch_progress))));
+        let mut tasks = Vec::new();
+        loop {
+            match rx.recv_timeout(Duration::from_millis(100)) {
+                Ok(result) => return result,
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    anyhow::bail!("the fetch of `{}` with gitoxide panicked", url)
+                }
+            }
+            root.sorted_snapshot(&mut tasks);
+            let indexed = tasks
+                .iter()
+                .filter(|(_, task)| task.id == index_objects)
+                .find_map(|(_, task)| task.progress.as_ref());
+            if let Some(indexed) = indexed {
+                let done_at = indexed.done_at.unwrap_or(0);
+                progress.tick(indexed.step.load(Ordering::Relaxed), done_at)?;
+            }
+        }
+    })
+}
+
+/// A fetch with gitoxide, which owns all it needs to run on another thread.
+#[derive(Clone)]
+struct GitoxideFetch {
+    path: PathBuf,
+    url: String,
+    refspecs: Vec<String>,
+    tags: bool,
+    shallow: gix::remote::fetch::Shallow,
+    config_overrides: Vec<gix::bstr::BString>,
+}
+
+impl GitoxideFetch {
+    fn run(self, mut progress: gix::progress::tree::Item) -> CargoResult<()> {
+        use gix::remote::fetch::Tags;
+        use gix::remote::Direction;
+
+        let GitoxideFetch {
+            path,
+            url,
+            refspecs,
+            tags,
+            shallow,
+            config_overrides,
+        } = self;
+        let options = gix::open::Options::default().config_overrides(config_overrides);
+        let repo = gix::open_opts(&path, options)
+            .chain_err(|| format!("failed to open `{}` with gitoxide", path.display()))?;
+        let remote = repo
+            .remote_at(url.as_str())?
+            .with_refspecs(refspecs.iter().map(|spec| spec.as_str()), Direction::Fetch)?
+            .with_fetch_tags(if tags { Tags::All } else { Tags::None });
+        remote
+            .connect(Direction::Fetch)?
+            .prepare_fetch(&mut progress, Default::default())?
+            .with_shallow(shallow)
+            .receive(&mut progress, &gix::interrupt::IS_INTERRUPTED)
+            .chain_err(|| format!("failed to fetch `{}` with gitoxide", url))?;
+        Ok(())
+    }
+}
+
+/// Translates Cargo's `[http]` configuration to the git configuration
+/// gitoxide reads, so that its fetches go through the same proxy, trust the
+/// same certificates and time out like Cargo's other network requests.
+fn gitoxide_config_overrides(config: &Config) -> CargoResult<Vec<gix::bstr::BString>> {
+    use crate::util::config::SslVersionConfig;
+    use gix::config::tree::{gitoxide, Core, Http, Key};
+
+    let http = config.http_config()?;
+    let timeout = HttpTimeout::new(config)?;
+    let user_agent = match &http.user_agent {
+        Some(user_agent) => user_agent.clone(),
+        None => version().to_string(),
+    };
+    let mut overrides = vec![
+        // Reflogs need a committer, which may not be configured, and are of
+        // no use for the repositories Cargo manages.
+        Core::LOG_ALL_REF_UPDATES.validated_assignment_fmt(&false)?,
+        Http::LOW_SPEED_LIMIT.validated_assignment_fmt(&timeout.low_speed_limit)?,
+        Http::LOW_SPEED_TIME.validated_assignment_fmt(&timeout.dur.as_secs())?,
+        gitoxide::Http::CONNECT_TIMEOUT.validated_assignment_fmt(&timeout.dur.as_millis())?,
+        Http::USER_AGENT.validated_assignment_fmt(&user_agent)?,
+    ];
+    if let Some(proxy) = &http.proxy {
+        overrides.push(Http::PROXY.validated_assignment_fmt(proxy)?);
+    }
+    if let Some(cainfo) = &http.cainfo {
+        let cainfo = cainfo.resolve_path(config);
+        overrides.push(Http::SSL_CA_INFO.validated_assignment_fmt(&cainfo.display())?);
+    }
+    if let Some(check_revoke) = http.check_revoke {
+        overrides.push(Http::SCHANNEL_CHECK_REVOKE.validated_assignment_fmt(&check_revoke)?);
+    }
+    if let Some(debug) = http.debug {
+        overrides.push(gitoxide::Http::VERBOSE.validated_assignment_fmt(&debug)?);
+    }
+    if let Some(multiplexing) = http.multiplexing {
+        let version = if multiplexing { "HTTP/2" } else { "HTTP/1.1" };
+        overrides.push(Http::VERSION.validated_assignment_fmt(&version)?);
+    }
+    match &http.ssl_version {
+        Some(SslVersionConfig::Single(version)) => {
+            overrides.push(Http::SSL_VERSION.validated_assignment_fmt(version)?);
+        }
+        Some(SslVersionConfig::Range(range)) => {
+            if let Some(min) = &range.min {
+                overrides.push(gitoxide::Http::SSL_VERSION_MIN.validated_assignment_fmt(min)?);
+            }
+            if let Some(max) = &range.max {
+                overrides.push(gitoxide::Http::SSL_VERSION_MAX.validated_assignment_fmt(max)?);
+            }
+        }
+        None => {}
+    }
+    Ok(overrides)
+}
+
 /// Cargo has a bunch of long-lived git repositories in its global cache and
 /// some, like the index, are updated very frequently. Right now each update
 /// creates a new "pack file" inside the git database, and over time this can
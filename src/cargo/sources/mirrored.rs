@@ -0,0 +1,215 @@
+//! A source replaced with a list of mirrors.
+//!
+//! With `replace-with = ["mirror-a", "mirror-b"]`, the index is read from the
+//! first of the mirrors which can be reached, and every package is downloaded
+//! from there too. If a mirror can't be reached, or a package downloaded from
+//! it doesn't match its checksum, Cargo moves on to the next mirror, and
+//! ultimately to the replaced source itself.
+
+use std::collections::HashMap;
+
+use crate::core::source::{MaybePackage, PrebuiltBinary};
+use crate::core::{Dependency, Package, PackageId, Source, SourceId, Summary};
+use crate::util::errors::CargoResult;
+use crate::util::interning::InternedString;
+use crate::util::Config;
+
+pub struct MirroredSource<'cfg> {
+    source_id: SourceId,
+    /// The mirrors in the order they are tried, with the name of their
+    /// configuration, followed by the replaced source.
+    sources: Vec<(String, Box<dyn Source + 'cfg>)>,
+    /// The index in `sources` of the source the index is read from.
+    current: usize,
+    /// The index in `sources` of the source each package is downloaded from,
+    /// for the packages which failed to download from `current`.
+    downloads: HashMap<PackageId, usize>,
+    /// Whether `update` was called, in which case sources are also updated
+    /// before being used after the previous one failed.
+    updated: bool,
+    config: &'cfg Config,
+}
+
+impl<'cfg> MirroredSource<'cfg> {
+    pub fn new(
+        source_id: SourceId,
+        sources: Vec<(String, Box<dyn Source + 'cfg>)>,
+        config: &'cfg Config,
+    ) -> MirroredSource<'cfg> {
+        assert!(!sources.is_empty());
+        MirroredSource {
+            source_id,
+            sources,
+            current: 0,
+            downloads: HashMap::new(),
+            updated: false,
+            config,
+        }
+    }
+
+    /// Runs `f` with the source the index is read from, moving on to the next
+    /// source each time it fails.
+    fn with_fallback<T>(
+        &mut self,
+        what: &str,
+        mut f: impl FnMut(&mut (dyn Source + 'cfg)) -> CargoResult<T>,
+    ) -> CargoResult<T> {
+        let first = self.current;
+        loop {
+            let source = &mut *self.sources[self.current].1;
+            let result = if self.updated && self.current != first {
+                source.update().and_then(|()| f(source))
+            } else {
+                f(source)
+            };
+            let err = match result {
+                Ok(value) => return Ok(value),
+                Err(e) if self.current + 1 == self.sources.len() => return Err(e),
+                Err(e) => e,
+            };
+            self.config.shell().warn(format!(
+                "failed to {} from `{}`, trying `{}` instead\n\nCaused by:\n  {:#}",
+                what,
+                self.sources[self.current].0,
+                self.sources[self.current + 1].0,
+                err
+            ))?;
+            self.current += 1;
+        }
+    }
+
+    /// Returns the source `id` is downloaded from.
+    fn download_source(&self, id: PackageId) -> usize {
+        self.downloads.get(&id).copied().unwrap_or(self.current)
+    }
+}
+
+impl<'cfg> Source for MirroredSource<'cfg> {
+    fn source_id(&self) -> SourceId {
+        self.source_id
+    }
+
+    fn replaced_source_id(&self) -> SourceId {
+        self.sources[self.current].1.replaced_source_id()
+    }
+
+    fn supports_checksums(&self) -> bool {
+        self.sources[self.current].1.supports_checksums()
+    }
+
+    fn requires_precise(&self) -> bool {
+        self.sources[self.current].1.requires_precise()
+    }
+
+    fn query(&mut self, dep: &Dependency, f: &mut dyn FnMut(Summary)) -> CargoResult<()> {
+        self.with_fallback("query the index", |source| source.query(dep, &mut *f))
+    }
+
+    fn fuzzy_query(&mut self, dep: &Dependency, f: &mut dyn FnMut(Summary)) -> CargoResult<()> {
+        self.with_fallback("query the index", |source| source.fuzzy_query(dep, &mut *f))
+    }
+
+    fn update(&mut self) -> CargoResult<()> {
+        self.updated = true;
+        self.with_fallback("update the index", |source| source.update())
+    }
+
+    fn download(&mut self, id: PackageId) -> CargoResult<MaybePackage> {
+        let index = self.download_source(id);
+        self.sources[index].1.download(id)
+    }
+
+    fn finish_download(&mut self, id: PackageId, data: Vec<u8>) -> CargoResult<Package> {
+        let index = self.download_source(id);
+        let (name, source) = &mut self.sources[index];
+        let pkg = source.finish_download(id, data)?;
+        self.config
+            .shell()
+            .verbose(|s| s.status("Mirror", format!("`{}` served {}", name, id)))?;
+        Ok(pkg)
+    }
+
+    fn fall_back(&mut self, id: PackageId, err: &anyhow::Error) -> CargoResult<bool> {
+        let index = self.download_source(id);
+        if self.sources[index].1.fall_back(id, err)? {
+            return Ok(true);
+        }
+        if index + 1 == self.sources.len() {
+            return Ok(false);
+        }
+        self.config.shell().warn(format!(
+            "failed to download `{}` from `{}`, trying `{}` instead\n\nCaused by:\n  {:#}",
+            id,
+            self.sources[index].0,
+            self.sources[index + 1].0,
+            err
+        ))?;
+        self.downloads.insert(id, index + 1);
+
+        // Make sure the next source knows about the package, which updates
+        // its index if it wasn't used so far.
+        let source = &mut *self.sources[index + 1].1;
+        if self.updated {
+            source.update()?;
+        }
+        let dep = Dependency::parse_no_deprecated(
+            id.name(),
+            Some(&format!("={}", id.version())),
+            id.source_id(),
+        )?;
+        source.query(&dep, &mut |_| {})?;
+        Ok(true)
+    }
+
+    fn fingerprint(&self, pkg: &Package) -> CargoResult<String> {
+        let index = self.download_source(pkg.package_id());
+        self.sources[index].1.fingerprint(pkg)
+    }
+
+    fn verify(&self, id: PackageId) -> CargoResult<()> {
+        let index = self.download_source(id);
+        self.sources[index].1.verify(id)
+    }
+
+    fn describe(&self) -> String {
+        self.sources[self.current].1.describe()
+    }
+
+    fn is_replaced(&self) -> bool {
+        self.sources[self.current].1.is_replaced()
+    }
+
+    fn add_to_yanked_whitelist(&mut self, pkgs: &[PackageId]) {
+        for (_name, source) in &mut self.sources {
+            source.add_to_yanked_whitelist(pkgs);
+        }
+    }
+
+    fn is_yanked(&mut self, pkg: PackageId) -> CargoResult<bool> {
+        self.sources[self.current].1.is_yanked(pkg)
+    }
+
+    fn index_version(&self) -> Option<InternedString> {
+        self.sources[self.current].1.index_version()
+    }
+
+    fn prebuilt_binary(
+        &mut self,
+        pkg: PackageId,
+        target: &str,
+    ) -> CargoResult<Option<PrebuiltBinary>> {
+        self.sources[self.current].1.prebuilt_binary(pkg, target)
+    }
+
+    fn download_url(&mut self, pkg: PackageId) -> CargoResult<Option<String>> {
+        let index = self.download_source(pkg);
+        self.sources[index].1.download_url(pkg)
+    }
+
+    fn save_caches(&mut self) -> CargoResult<()> {
+        for (_, source) in self.sources.iter_mut() {
+            source.save_caches()?;
+        }
+        Ok(())
+    }
+}
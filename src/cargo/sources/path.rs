@@ -3,7 +3,7 @@ use std::fs;
 use std::path::{Path, PathBuf};
 
 use filetime::FileTime;
-use ignore::gitignore::GitignoreBuilder;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use ignore::Match;
 use log::{trace, warn};
 
@@ -106,45 +106,10 @@ impl<'cfg> PathSource<'cfg> {
 
     fn _list_files(&self, pkg: &Package) -> CargoResult<Vec<PathBuf>> {
         let root = pkg.root();
+        let package_filter =
+            PackageFilter::new(root, pkg.manifest().include(), pkg.manifest().exclude())?;
         let no_include_option = pkg.manifest().include().is_empty();
 
-        let mut exclude_builder = GitignoreBuilder::new(root);
-        for rule in pkg.manifest().exclude() {
-            exclude_builder.add_line(None, rule)?;
-        }
-        let ignore_exclude = exclude_builder.build()?;
-
-        let mut include_builder = GitignoreBuilder::new(root);
-        for rule in pkg.manifest().include() {
-            include_builder.add_line(None, rule)?;
-        }
-        let ignore_include = include_builder.build()?;
-
-        let ignore_should_package = |relative_path: &Path, is_dir: bool| -> CargoResult<bool> {
-            // "Include" and "exclude" options are mutually exclusive.
-            if no_include_option {
-                match ignore_exclude.matched_path_or_any_parents(relative_path, is_dir) {
-                    Match::None => Ok(true),
-                    Match::Ignore(_) => Ok(false),
-                    Match::Whitelist(_) => Ok(true),
-                }
-            } else {
-                if is_dir {
-                    // Generally, include directives don't list every
-                    // directory (nor should they!). Just skip all directory
-                    // checks, and only check files.
-                    return Ok(true);
-                }
-                match ignore_include
-                    .matched_path_or_any_parents(relative_path, /* is_dir */ false)
-                {
-                    Match::None => Ok(false),
-                    Match::Ignore(_) => Ok(true),
-                    Match::Whitelist(_) => Ok(false),
-                }
-            }
-        };
-
         let mut filter = |path: &Path, is_dir: bool| -> CargoResult<bool> {
             let relative_path = path.strip_prefix(root)?;
 
@@ -155,7 +120,7 @@ impl<'cfg> PathSource<'cfg> {
                 return Ok(true);
             }
 
-            ignore_should_package(relative_path, is_dir)
+            Ok(package_filter.should_package(relative_path, is_dir))
         };
 
         // Attempt Git-prepopulate only if no `include` (see rust-lang/cargo#4135).
@@ -462,6 +427,73 @@ impl<'cfg> PathSource<'cfg> {
     }
 }
 
+/// The `package.include` and `package.exclude` rules of a package, which
+/// decide which of the files under its root are part of it.
+pub struct PackageFilter {
+    include: Gitignore,
+    exclude: Gitignore,
+    no_include_option: bool,
+    no_exclude_option: bool,
+}
+
+impl PackageFilter {
+    pub fn new(root: &Path, include: &[String], exclude: &[String]) -> CargoResult<PackageFilter> {
+        let mut exclude_builder = GitignoreBuilder::new(root);
+        for rule in exclude {
+            exclude_builder.add_line(None, rule)?;
+        }
+
+        let mut include_builder = GitignoreBuilder::new(root);
+        for rule in include {
+            include_builder.add_line(None, rule)?;
+        }
+
+        Ok(PackageFilter {
+            include: include_builder.build()?,
+            exclude: exclude_builder.build()?,
+            no_include_option: include.is_empty(),
+            no_exclude_option: exclude.is_empty(),
+        })
+    }
+
+    /// Returns whether there are no `include` or `exclude` rules, so every
+    /// file is part of the package.
+    pub fn is_empty(&self) -> bool {
+        self.no_include_option && self.no_exclude_option
+    }
+
+    /// Returns whether the path, relative to the package root, is part of the
+    /// package.
+    pub fn should_package(&self, relative_path: &Path, is_dir: bool) -> bool {
+        // "Include" and "exclude" options are mutually exclusive.
+        if self.no_include_option {
+            match self
+                .exclude
+                .matched_path_or_any_parents(relative_path, is_dir)
+            {
+                Match::None => true,
+                Match::Ignore(_) => false,
+                Match::Whitelist(_) => true,
+            }
+        } else {
+            if is_dir {
+                // Generally, include directives don't list every
+                // directory (nor should they!). Just skip all directory
+                // checks, and only check files.
+                return true;
+            }
+            match self
+                .include
+                .matched_path_or_any_parents(relative_path, /* is_dir */ false)
+            {
+                Match::None => false,
+                Match::Ignore(_) => true,
+                Match::Whitelist(_) => false,
+            }
+        }
+    }
+}
+
 impl<'cfg> Debug for PathSource<'cfg> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "the paths source")
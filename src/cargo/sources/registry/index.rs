@@ -62,13 +62,20 @@
 //! blobs and loads 0 files git. Removing 200ms or more from Cargo's startup
 //! time is certainly nothing to sneeze at!
 //!
+//! Cargo may also be asked to keep the entries it parses in a memory-mapped
+//! store shared by all invocations, with `-Z index-store`, so that even the
+//! JSON of the versions in use isn't parsed again. See `store.rs` for how it
+//! works.
+//!
 //! Note that this is just a high-level overview, there's of course lots of
 //! details like invalidating caches and whatnot which are handled below, but
 //! hopefully those are more obvious inline in the code itself.
 
 use crate::core::dependency::Dependency;
 use crate::core::{PackageId, SourceId, Summary};
-use crate::sources::registry::{RegistryData, RegistryPackage};
+use crate::sources::registry::store::{self, IndexStore, RawData};
+use crate::sources::registry::{RegistryData, RegistryPackage, RegistryPrebuilt};
+use crate::util::errors::CargoResultExt;
 use crate::util::interning::InternedString;
 use crate::util::paths;
 use crate::util::{internal, CargoResult, Config, Filesystem, ToSemver};
@@ -168,6 +175,9 @@ pub struct RegistryIndex<'cfg> {
     source_id: SourceId,
     path: Filesystem,
     summaries_cache: HashMap<InternedString, Summaries>,
+    /// The store of parsed index entries, with `-Z index-store`. It is
+    /// opened the first time a package is loaded.
+    store: Option<IndexStore>,
     config: &'cfg Config,
 }
 
@@ -191,8 +201,10 @@ pub struct RegistryIndex<'cfg> {
 struct Summaries {
     /// A raw vector of uninterpreted bytes. This is what `Unparsed` start/end
     /// fields are indexes into. If a `Summaries` is loaded from the crates.io
-    /// index then this field will be empty since nothing is `Unparsed`.
-    raw_data: Vec<u8>,
+    /// index then this field will be empty since nothing is `Unparsed`. The
+    /// data of `Encoded` summaries is borrowed from the map of the index
+    /// store.
+    raw_data: RawData,
 
     /// All known versions of a crate, keyed from their `Version` to the
     /// possibly parsed or unparsed version of the full summary.
@@ -205,6 +217,10 @@ enum MaybeIndexSummary {
     /// into `Summaries::raw_data` which this is an entry of.
     Unparsed { start: usize, end: usize },
 
+    /// A summary encoded in the index store, which has not been decoded. The
+    /// `start` and `end` are pointers into `Summaries::raw_data`.
+    Encoded { start: usize, end: usize },
+
     /// An actually parsed summary.
     Parsed(IndexSummary),
 }
@@ -215,6 +231,10 @@ enum MaybeIndexSummary {
 pub struct IndexSummary {
     pub summary: Summary,
     pub yanked: bool,
+    /// The signature of the `.crate` file, see `util::crate_signature`.
+    pub signature: Option<String>,
+    /// The prebuilt binaries of the package.
+    pub prebuilt: Vec<RegistryPrebuilt>,
 }
 
 /// A representation of the cache on disk that Cargo maintains of summaries.
@@ -236,6 +256,7 @@ impl<'cfg> RegistryIndex<'cfg> {
             source_id,
             path: path.clone(),
             summaries_cache: HashMap::new(),
+            store: None,
             config,
         }
     }
@@ -253,6 +274,40 @@ impl<'cfg> RegistryIndex<'cfg> {
             .ok_or_else(|| internal(format!("no hash listed for {}", pkg)))
     }
 
+    /// Returns the signature listed for a specified `PackageId`, if any.
+    pub fn signature(
+        &mut self,
+        pkg: PackageId,
+        load: &mut dyn RegistryData,
+    ) -> CargoResult<Option<String>> {
+        let req = VersionReq::exact(pkg.version());
+        let summary = self
+            .summaries(pkg.name(), &req, load)?
+            .next()
+            .ok_or_else(|| internal(format!("no signature listed for {}", pkg)))?;
+        Ok(summary.signature.clone())
+    }
+
+    /// Returns the prebuilt binaries of `pkg` for `target` listed in the
+    /// index, if any.
+    pub fn prebuilt(
+        &mut self,
+        pkg: PackageId,
+        target: &str,
+        load: &mut dyn RegistryData,
+    ) -> CargoResult<Option<RegistryPrebuilt>> {
+        let req = VersionReq::exact(pkg.version());
+        let summary = self
+            .summaries(pkg.name(), &req, load)?
+            .next()
+            .ok_or_else(|| internal(format!("no entry listed for {}", pkg)))?;
+        Ok(summary
+            .prebuilt
+            .iter()
+            .find(|prebuilt| prebuilt.target == target)
+            .cloned())
+    }
+
     /// Load a list of summaries for `name` package in this registry which
     /// match `req`
     ///
@@ -271,7 +326,6 @@ impl<'cfg> RegistryIndex<'cfg> {
     {
         let source_id = self.source_id;
         let config = self.config;
-        let namespaced_features = self.config.cli_unstable().namespaced_features;
         let weak_dep_features = self.config.cli_unstable().weak_dep_features;
 
         // First up actually parse what summaries we have available. If Cargo
@@ -300,11 +354,7 @@ impl<'cfg> RegistryIndex<'cfg> {
                     }
                 },
             )
-            .filter(move |is| {
-                is.summary
-                    .unstable_gate(namespaced_features, weak_dep_features)
-                    .is_ok()
-            }))
+            .filter(move |is| is.summary.unstable_gate(weak_dep_features).is_ok()))
     }
 
     fn load_summaries(
@@ -327,6 +377,23 @@ impl<'cfg> RegistryIndex<'cfg> {
         let cache_root = root.join(".cache");
         let index_version = load.current_version();
 
+        // The store is only valid for a given state of the index, so it is
+        // started over if the index was updated since it was opened.
+        if let (true, Some(index_version)) =
+            (self.config.cli_unstable().index_store, &index_version)
+        {
+            let stale = match &self.store {
+                Some(store) => store.index_version() != index_version.as_str(),
+                None => true,
+            };
+            if stale {
+                if let Some(mut store) = self.store.take() {
+                    save_store(&mut store, &mut self.summaries_cache, self.source_id)?;
+                }
+                self.store = Some(IndexStore::open(&cache_root.join(".store"), index_version));
+            }
+        }
+
         // See module comment in `registry/mod.rs` for why this is structured
         // the way it is.
         let fs_name = name
@@ -346,6 +413,16 @@ impl<'cfg> RegistryIndex<'cfg> {
         // reject any candidates that have the wrong name, and with this it'll
         // along the way produce helpful "did you mean?" suggestions.
         for path in UncanonicalizedIter::new(&raw_path).take(1024) {
+            if let Some(data) = self.store.as_ref().and_then(|store| store.get(&path)) {
+                match Summaries::parse_store(data) {
+                    Ok(summaries) => {
+                        log::debug!("loaded `{}` from the index store", path);
+                        self.summaries_cache.insert(name, summaries);
+                        return Ok(self.summaries_cache.get_mut(&name).unwrap());
+                    }
+                    Err(e) => log::debug!("failed to parse `{}` in the index store: {}", path, e),
+                }
+            }
             let summaries = Summaries::parse(
                 index_version.as_deref(),
                 root,
@@ -356,6 +433,9 @@ impl<'cfg> RegistryIndex<'cfg> {
                 self.config,
             )?;
             if let Some(summaries) = summaries {
+                if let Some(store) = &mut self.store {
+                    store.add(&path, summaries.json_lines());
+                }
                 self.summaries_cache.insert(name, summaries);
                 return Ok(self.summaries_cache.get_mut(&name).unwrap());
             }
@@ -400,8 +480,17 @@ impl<'cfg> RegistryIndex<'cfg> {
         online: bool,
     ) -> CargoResult<usize> {
         let source_id = self.source_id;
+        // Pre-releases allowed by `allow-prerelease` are not matched by the
+        // requirement itself, so fetch everything and leave the filtering to
+        // `Dependency::matches`.
+        let any = VersionReq::any();
+        let req = if dep.allows_prerelease() {
+            &any
+        } else {
+            dep.version_req()
+        };
         let summaries = self
-            .summaries(dep.package_name(), dep.version_req(), load)?
+            .summaries(dep.package_name(), req, load)?
             // First filter summaries for `--offline`. If we're online then
             // everything is a candidate, otherwise if we're offline we're only
             // going to consider candidates which are actually present on disk.
@@ -423,21 +512,24 @@ impl<'cfg> RegistryIndex<'cfg> {
         // will have a precise version listed of the form
         // `<pkg>=<p_req>o-><f_req>` where `<pkg>` is the name of a crate on
         // this source, `<p_req>` is the version installed and `<f_req> is the
-        // version requested (argument to `--precise`).
+        // version requested (argument to `--precise`). Several of these may
+        // be listed, separated by commas, when multiple packages of this
+        // source are updated at once.
         let name = dep.package_name().as_str();
-        let summaries = summaries.filter(|s| match source_id.precise() {
-            Some(p) if p.starts_with(name) && p[name.len()..].starts_with('=') => {
+        let precise = source_id.precise().and_then(|p| {
+            p.split(',')
+                .find(|p| p.starts_with(name) && p[name.len()..].starts_with('='))
+        });
+        let summaries = summaries.filter(|s| match precise {
+            Some(p) => {
                 let mut vers = p[name.len() + 1..].splitn(2, "->");
-                if dep
-                    .version_req()
-                    .matches(&vers.next().unwrap().to_semver().unwrap())
-                {
+                if dep.matches_version(&vers.next().unwrap().to_semver().unwrap()) {
                     vers.next().unwrap() == s.version().to_string()
                 } else {
                     true
                 }
             }
-            _ => true,
+            None => true,
         });
 
         let mut count = 0;
@@ -455,6 +547,36 @@ impl<'cfg> RegistryIndex<'cfg> {
             .any(|summary| summary.yanked);
         Ok(found)
     }
+
+    /// Saves the packages added to the index store, with `-Z index-store`.
+    pub fn save_store(&mut self) -> CargoResult<()> {
+        match &mut self.store {
+            Some(store) => save_store(store, &mut self.summaries_cache, self.source_id),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Saves the packages added to `store`.
+fn save_store(
+    store: &mut IndexStore,
+    summaries_cache: &mut HashMap<InternedString, Summaries>,
+    source_id: SourceId,
+) -> CargoResult<()> {
+    if !store.has_added() {
+        return Ok(());
+    }
+    // The store is unmapped to be replaced, so the summaries loaded from it
+    // can't borrow from it anymore.
+    for summaries in summaries_cache.values_mut() {
+        summaries.raw_data.detach();
+    }
+    store.save().chain_err(|| {
+        format!(
+            "failed to save the index store of {}",
+            source_id.display_registry_name()
+        )
+    })
 }
 
 impl Summaries {
@@ -499,7 +621,7 @@ impl Summaries {
                     Ok(s) => {
                         log::debug!("fast path for registry cache of {:?}", relative);
                         if cfg!(debug_assertions) {
-                            cache_contents = Some(s.raw_data);
+                            cache_contents = Some(s.raw_data.to_vec());
                         } else {
                             return Ok(Some(s));
                         }
@@ -520,7 +642,7 @@ impl Summaries {
         let mut hit_closure = false;
         let mut cache_bytes = None;
         let err = load.load(root, relative, &mut |contents| {
-            ret.raw_data = contents.to_vec();
+            ret.raw_data = RawData::Owned(contents.to_vec());
             let mut cache = SummariesCache::default();
             hit_closure = true;
             for line in split(contents, b'\n') {
@@ -582,6 +704,43 @@ impl Summaries {
         Ok(Some(ret))
     }
 
+    /// Loads the data of a package in the index store.
+    fn parse_store(data: RawData) -> CargoResult<Summaries> {
+        let mut ret = Summaries::default();
+        for (version, start, len) in store::versions(&data)? {
+            ret.versions.insert(
+                Version::parse(version)?,
+                MaybeIndexSummary::Encoded {
+                    start,
+                    end: start + len,
+                },
+            );
+        }
+        ret.raw_data = data;
+        Ok(ret)
+    }
+
+    /// Returns the JSON lines of the versions, to add them to the index
+    /// store.
+    ///
+    /// These are the lines of the index file if it was just loaded, or the
+    /// `Unparsed` entries if this was loaded from a cache file.
+    fn json_lines(&self) -> Box<dyn Iterator<Item = &[u8]> + '_> {
+        let raw_data = &self.raw_data;
+        if self
+            .versions
+            .values()
+            .all(|v| matches!(v, MaybeIndexSummary::Unparsed { .. }))
+        {
+            Box::new(self.versions.values().map(move |v| match v {
+                MaybeIndexSummary::Unparsed { start, end } => &raw_data[*start..*end],
+                _ => unreachable!(),
+            }))
+        } else {
+            Box::new(split(raw_data, b'\n'))
+        }
+    }
+
     /// Parses an open `File` which represents information previously cached by
     /// Cargo.
     pub fn parse_cache(contents: Vec<u8>, last_index_update: &str) -> CargoResult<Summaries> {
@@ -592,7 +751,7 @@ impl Summaries {
             ret.versions
                 .insert(version, MaybeIndexSummary::Unparsed { start, end });
         }
-        ret.raw_data = contents;
+        ret.raw_data = RawData::Owned(contents);
         return Ok(ret);
 
         // Returns the start/end offsets of `inner` with `outer`. Asserts that
@@ -631,8 +790,16 @@ impl Summaries {
 // versions of Cargo share the same cache they don't get too confused. The git
 // sha lets us know when the file needs to be regenerated (it needs regeneration
 // whenever the index itself updates).
+//
+// Entries with an index schema version newer than `INDEX_V_MAX` are left out
+// of the cache, so the version byte must be bumped along with `INDEX_V_MAX`
+// to make sure a newer Cargo doesn't reuse a cache which is missing entries.
+
+const CURRENT_CACHE_VERSION: u8 = 2;
 
-const CURRENT_CACHE_VERSION: u8 = 1;
+/// The maximum schema version of the `v` field in the index this version of
+/// Cargo understands. Entries with a newer version are skipped.
+const INDEX_V_MAX: u32 = 2;
 
 impl<'a> SummariesCache<'a> {
     fn parse(data: &'a [u8], last_index_update: &str) -> CargoResult<SummariesCache<'a>> {
@@ -698,14 +865,22 @@ impl MaybeIndexSummary {
         raw_data: &[u8],
         source_id: SourceId,
     ) -> CargoResult<&IndexSummary> {
-        let (start, end) = match self {
-            MaybeIndexSummary::Unparsed { start, end } => (*start, *end),
+        let summary = match self {
+            MaybeIndexSummary::Unparsed { start, end } => {
+                IndexSummary::parse(config, &raw_data[*start..*end], source_id)?
+            }
+            MaybeIndexSummary::Encoded { start, end } => IndexSummary::from_package(
+                config,
+                store::decode_package(&raw_data[*start..*end])?,
+                source_id,
+            )?,
             MaybeIndexSummary::Parsed(summary) => return Ok(summary),
         };
-        let summary = IndexSummary::parse(config, &raw_data[start..end], source_id)?;
         *self = MaybeIndexSummary::Parsed(summary);
         match self {
-            MaybeIndexSummary::Unparsed { .. } => unreachable!(),
+            MaybeIndexSummary::Unparsed { .. } | MaybeIndexSummary::Encoded { .. } => {
+                unreachable!()
+            }
             MaybeIndexSummary::Parsed(summary) => Ok(summary),
         }
     }
@@ -723,16 +898,41 @@ impl IndexSummary {
     ///
     /// The `line` provided is expected to be valid JSON.
     fn parse(config: &Config, line: &[u8], source_id: SourceId) -> CargoResult<IndexSummary> {
+        IndexSummary::from_package(config, serde_json::from_slice(line)?, source_id)
+    }
+
+    /// Converts an entry of the index into an `IndexSummary`.
+    fn from_package(
+        config: &Config,
+        package: RegistryPackage<'_>,
+        source_id: SourceId,
+    ) -> CargoResult<IndexSummary> {
         let RegistryPackage {
             name,
             vers,
             cksum,
             deps,
-            features,
+            mut features,
             yanked,
             links,
-        } = serde_json::from_slice(line)?;
-        log::trace!("json parsed registry {}/{}", name, vers);
+            features2,
+            v,
+            sig,
+            prebuilt,
+        } = package;
+        let v = v.unwrap_or(1);
+        log::trace!("json parsed registry {}/{}/v{}", name, vers, v);
+        if v > INDEX_V_MAX {
+            anyhow::bail!(
+                "index entry for {}/{} uses unsupported schema version {}",
+                name,
+                vers,
+                v
+            );
+        }
+        if let Some(features2) = features2 {
+            features.extend(features2);
+        }
         let pkgid = PackageId::new(name, &vers, source_id)?;
         let deps = deps
             .into_iter()
@@ -743,6 +943,8 @@ impl IndexSummary {
         Ok(IndexSummary {
             summary,
             yanked: yanked.unwrap_or(false),
+            signature: sig,
+            prebuilt: prebuilt.unwrap_or_default(),
         })
     }
 }
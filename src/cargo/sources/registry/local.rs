@@ -1,30 +1,209 @@
-use crate::core::PackageId;
+use crate::core::dependency::DepKind;
+use crate::core::{PackageId, SourceId};
+use crate::ops;
 use crate::sources::registry::{MaybeLock, RegistryConfig, RegistryData};
-use crate::util::errors::CargoResult;
+use crate::util::compression;
+use crate::util::errors::{CargoResult, CargoResultExt};
 use crate::util::interning::InternedString;
 use crate::util::paths;
 use crate::util::{Config, Filesystem, Sha256};
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::SeekFrom;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use tar::Archive;
+
+/// The file in a generated index recording which `.crate` files it was
+/// generated from.
+const GENERATED_FROM: &str = ".generated-from";
 
 pub struct LocalRegistry<'cfg> {
     index_path: Filesystem,
     root: Filesystem,
     src_path: Filesystem,
+    /// Whether the directory only contains `.crate` files, and the index is
+    /// generated from them in `index_path`.
+    generated_index: bool,
     config: &'cfg Config,
 }
 
 impl<'cfg> LocalRegistry<'cfg> {
     pub fn new(root: &Path, config: &'cfg Config, name: &str) -> LocalRegistry<'cfg> {
+        let generated_index = root.is_dir() && !root.join("index").exists();
+        let index_path = if generated_index {
+            config.registry_index_path().join(name)
+        } else {
+            Filesystem::new(root.join("index"))
+        };
         LocalRegistry {
             src_path: config.registry_source_path().join(name),
-            index_path: Filesystem::new(root.join("index")),
+            index_path,
             root: Filesystem::new(root.to_path_buf()),
+            generated_index,
             config,
         }
     }
+
+    /// Generates the index from the `.crate` files in the directory, unless
+    /// it was already generated from the same files.
+    fn generate_index(&self) -> CargoResult<()> {
+        let root = self.root.as_path_unlocked();
+        let mut crate_files = Vec::new();
+        for entry in root.read_dir()? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) == Some("crate") && path.is_file() {
+                crate_files.push(path);
+            }
+        }
+        crate_files.sort();
+
+        let mut generated_from = String::new();
+        for path in &crate_files {
+            let meta = path.metadata()?;
+            let mtime = paths::mtime(path)?;
+            generated_from.push_str(&format!(
+                "{} {} {}\n",
+                path.file_name().unwrap().to_string_lossy(),
+                meta.len(),
+                mtime
+            ));
+        }
+        let index_path = self.config.assert_package_cache_locked(&self.index_path);
+        let marker = index_path.join(GENERATED_FROM);
+        if paths::read(&marker).ok().as_deref() == Some(&generated_from) {
+            return Ok(());
+        }
+
+        self.config.shell().status(
+            "Indexing",
+            format!("{} crate files in `{}`", crate_files.len(), root.display()),
+        )?;
+        let mut files = BTreeMap::<String, Vec<String>>::new();
+        for path in &crate_files {
+            let (name, line) = index_line(path, self.config)
+                .chain_err(|| format!("failed to index `{}`", path.display()))?;
+            files.entry(index_file(&name)).or_default().push(line);
+        }
+
+        if index_path.exists() {
+            paths::remove_dir_all(index_path)?;
+        }
+        for (file, lines) in files {
+            let dst = index_path.join(file);
+            paths::create_dir_all(dst.parent().unwrap())?;
+            paths::write(&dst, lines.join("\n") + "\n")?;
+        }
+        paths::create_dir_all(index_path)?;
+        paths::write(&marker, generated_from)
+    }
+}
+
+/// Returns the path of the index file listing the versions of `name`.
+fn index_file(name: &str) -> String {
+    let name = name.to_lowercase();
+    match name.len() {
+        1 => format!("1/{}", name),
+        2 => format!("2/{}", name),
+        3 => format!("3/{}/{}", &name[..1], name),
+        _ => format!("{}/{}/{}", &name[0..2], &name[2..4], name),
+    }
+}
+
+/// Reads the manifest packaged in the `.crate` file at `path`, and returns
+/// the name of the package along with its line in the index.
+fn index_line(path: &Path, config: &Config) -> CargoResult<(String, String)> {
+    let mut crate_file = paths::open(path)?;
+    let cksum = Sha256::new().update_file(&crate_file)?.finish_hex();
+    crate_file.seek(SeekFrom::Start(0))?;
+
+    // The manifest can only be read along with the rest of the package, to
+    // infer its targets.
+    let tmp = tempfile::Builder::new().prefix("cargo-index").tempdir()?;
+    Archive::new(compression::decoder(crate_file)?)
+        .unpack(tmp.path())
+        .chain_err(|| "failed to unpack the crate file")?;
+    let package_dirs = tmp
+        .path()
+        .read_dir()?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<CargoResult<Vec<PathBuf>>>()?;
+    let package_dir = match package_dirs.as_slice() {
+        [dir] if dir.is_dir() => dir,
+        _ => anyhow::bail!("the crate file does not contain a single package directory"),
+    };
+    let manifest_path = package_dir.join("Cargo.toml");
+    let (pkg, _) = ops::read_package(&manifest_path, SourceId::for_path(package_dir)?, config)?;
+
+    let file_name = format!("{}-{}.crate", pkg.name(), pkg.version());
+    if path.file_name().and_then(|f| f.to_str()) != Some(file_name.as_str()) {
+        anyhow::bail!(
+            "the crate file contains `{} v{}`, and must be named `{}`",
+            pkg.name(),
+            pkg.version(),
+            file_name
+        );
+    }
+
+    let crates_io = SourceId::crates_io(config)?;
+    let deps = pkg
+        .dependencies()
+        .iter()
+        // Dev-dependencies without a version are not published.
+        .filter(|dep| dep.is_transitive() || dep.specified_req())
+        .map(|dep| {
+            // In the index, the `registry` is null for the registry the local
+            // registry stands in for, which is crates.io.
+            let registry = match dep.registry_id() {
+                Some(id) if id != crates_io => Some(id.url().to_string()),
+                _ => None,
+            };
+            serde_json::json!({
+                "name": dep.name_in_toml(),
+                "req": dep.version_req().to_string(),
+                "features": dep.features(),
+                "optional": dep.is_optional(),
+                "default_features": dep.uses_default_features(),
+                "target": dep.platform().map(|p| p.to_string()),
+                "kind": match dep.kind() {
+                    DepKind::Normal => "normal",
+                    DepKind::Build => "build",
+                    DepKind::Development => "dev",
+                },
+                "registry": registry,
+                "package": dep.explicit_name_in_toml().map(|_| dep.package_name()),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // Like crates.io, features using the newer syntax go in a separate
+    // `features2` map.
+    let (features, features2): (HashMap<_, _>, HashMap<_, _>) = pkg
+        .manifest()
+        .original()
+        .features()
+        .cloned()
+        .unwrap_or_default()
+        .into_iter()
+        .partition(|(_name, values)| {
+            !values
+                .iter()
+                .any(|value| value.starts_with("dep:") || value.contains("?/"))
+        });
+    let mut json = serde_json::json!({
+        "name": pkg.name(),
+        "vers": pkg.version().to_string(),
+        "deps": deps,
+        "cksum": cksum,
+        "features": features,
+        "yanked": false,
+        "links": pkg.manifest().links(),
+    });
+    if !features2.is_empty() {
+        json["features2"] = serde_json::json!(features2);
+        json["v"] = serde_json::json!(2);
+    }
+    Ok((pkg.name().to_string(), json.to_string()))
 }
 
 impl<'cfg> RegistryData for LocalRegistry<'cfg> {
@@ -37,6 +216,11 @@ impl<'cfg> RegistryData for LocalRegistry<'cfg> {
     }
 
     fn assert_index_locked<'a>(&self, path: &'a Filesystem) -> &'a Path {
+        // A generated index is written by Cargo, and synchronized like the
+        // index of remote registries.
+        if self.generated_index {
+            return self.config.assert_package_cache_locked(path);
+        }
         // Note that the `*_unlocked` variant is used here since we're not
         // modifying the index and it's required to be externally synchronized.
         path.as_path_unlocked()
@@ -69,6 +253,9 @@ impl<'cfg> RegistryData for LocalRegistry<'cfg> {
         if !root.is_dir() {
             anyhow::bail!("local registry path is not a directory: {}", root.display())
         }
+        if self.generated_index {
+            return self.generate_index();
+        }
         let index_path = self.index_path.clone().into_path_unlocked();
         if !index_path.is_dir() {
             anyhow::bail!(
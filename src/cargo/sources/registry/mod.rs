@@ -165,16 +165,18 @@ use std::fs::{File, OpenOptions};
 use std::io::Write;
 use std::path::{Path, PathBuf};
 
-use flate2::read::GzDecoder;
 use log::debug;
 use semver::{Version, VersionReq};
 use serde::Deserialize;
 use tar::Archive;
 
 use crate::core::dependency::{DepKind, Dependency};
-use crate::core::source::MaybePackage;
+use crate::core::source::{MaybePackage, PrebuiltBinary};
 use crate::core::{Package, PackageId, Source, SourceId, Summary};
+use crate::ops;
 use crate::sources::PathSource;
+use crate::util::compression;
+use crate::util::crate_signature;
 use crate::util::errors::CargoResultExt;
 use crate::util::hex;
 use crate::util::interning::InternedString;
@@ -188,10 +190,15 @@ const CRATE_TEMPLATE: &str = "{crate}";
 const VERSION_TEMPLATE: &str = "{version}";
 const PREFIX_TEMPLATE: &str = "{prefix}";
 const LOWER_PREFIX_TEMPLATE: &str = "{lowerprefix}";
+const TARGET_TEMPLATE: &str = "{target}";
 
 pub struct RegistrySource<'cfg> {
     source_id: SourceId,
     src_path: Filesystem,
+    /// Where packages were unpacked in the read-only shared registry, if
+    /// new ones are unpacked to an overlay in `src_path` instead. These are
+    /// trusted as is, unlike its `.crate` files.
+    shared_src_path: Option<Filesystem>,
     config: &'cfg Config,
     updated: bool,
     ops: Box<dyn RegistryData + 'cfg>,
@@ -222,6 +229,49 @@ pub struct RegistryConfig {
     /// operations like yanks, owner modifications, publish new crates, etc.
     /// If this is None, the registry does not support API commands.
     pub api: Option<String>,
+
+    /// Compressions of `.crate` files accepted on publish, besides gzip which
+    /// is always accepted. Currently only `"zstd"` is recognized.
+    #[serde(default)]
+    pub compression: Vec<String>,
+
+    /// Whether the registry requires a token for every request, including
+    /// downloads of `.crate` files, instead of only for API commands.
+    #[serde(rename = "auth-required", default)]
+    pub auth_required: bool,
+
+    /// The largest `.crate` file, in bytes, the registry accepts on publish.
+    #[serde(rename = "max-upload-size", default)]
+    pub max_upload_size: Option<u64>,
+
+    /// Download endpoint for the prebuilt binaries of crates, used by
+    /// `cargo install -Z prebuilt-install`.
+    ///
+    /// Like `dl`, the string is a template in which `{crate}` and `{version}`
+    /// are replaced, as well as `{target}` with the target triple the
+    /// binaries were built for. The registry lists the targets it has
+    /// binaries for in the `prebuilt` field of the index entries.
+    #[serde(rename = "prebuilt-dl", default)]
+    pub prebuilt_dl: Option<String>,
+
+    /// Whether the registry accepts the SLSA provenance of the crates
+    /// published with `cargo publish --provenance`, in the `provenance` field
+    /// of the publish request.
+    #[serde(default)]
+    pub provenance: bool,
+}
+
+/// A prebuilt archive of the binaries of a version of a package, listed in
+/// the `prebuilt` field of its index entry.
+#[derive(Clone, Deserialize)]
+pub struct RegistryPrebuilt {
+    /// The target triple the binaries were built for.
+    pub target: String,
+    /// The SHA-256 checksum of the `.tar.gz` archive.
+    pub cksum: String,
+    /// The signature of the archive by the publisher of the package. See
+    /// `util::crate_signature`.
+    pub sig: Option<String>,
 }
 
 /// A single line in the index representing a single version of a package.
@@ -243,6 +293,25 @@ pub struct RegistryPackage<'a> {
     /// Added early 2018 (see <https://github.com/rust-lang/cargo/pull/4978>),
     /// can be `None` if published before then.
     links: Option<InternedString>,
+    /// Features that use the newer feature syntax (such as `dep?/feat`).
+    ///
+    /// These are kept separate from `features` so that older versions of
+    /// Cargo, which cannot parse them, skip them instead of failing to parse
+    /// the whole entry. They are merged with `features` when loaded.
+    features2: Option<BTreeMap<InternedString, Vec<InternedString>>>,
+    /// The schema version of this entry.
+    ///
+    /// If this is `None`, it defaults to version 1. Entries with unknown
+    /// versions are ignored.
+    ///
+    /// Version 2 indicates the presence of the `features2` field.
+    v: Option<u32>,
+    /// The signature of the `.crate` file by its publisher, checked with
+    /// `-Z crate-signing`. See `util::crate_signature`.
+    sig: Option<String>,
+    /// The prebuilt binaries of this version, for `cargo install -Z
+    /// prebuilt-install`.
+    prebuilt: Option<Vec<RegistryPrebuilt>>,
 }
 
 #[test]
@@ -254,6 +323,9 @@ fn escaped_char_in_json() {
     let _: RegistryPackage<'_> = serde_json::from_str(
         r#"{"name":"a","vers":"0.0.1","deps":[],"cksum":"bae3","features":{"test":["k","q"]},"links":"a-sys"}"#
     ).unwrap();
+    let _: RegistryPackage<'_> = serde_json::from_str(
+        r#"{"name":"a","vers":"0.0.1","deps":[],"cksum":"bae3","features":{},"features2":{"std":["b?/std"]},"v":2}"#
+    ).unwrap();
 
     // Now we add escaped cher all the places they can go
     // these are not valid, but it should error later than json parsing
@@ -387,18 +459,29 @@ pub trait RegistryData {
     fn is_crate_downloaded(&self, _pkg: PackageId) -> bool {
         true
     }
+    /// Called after downloading `pkg` failed with `err`, returns whether the
+    /// download should be started again. See `Source::fall_back`.
+    fn fall_back(&mut self, _pkg: PackageId, _err: &anyhow::Error) -> CargoResult<bool> {
+        Ok(false)
+    }
     fn assert_index_locked<'a>(&self, path: &'a Filesystem) -> &'a Path;
     fn current_version(&self) -> Option<InternedString>;
 }
 
 pub enum MaybeLock {
     Ready(File),
-    Download { url: String, descriptor: String },
+    Download {
+        url: String,
+        descriptor: String,
+        partial: Option<PathBuf>,
+        authorization: Option<String>,
+    },
 }
 
 mod index;
 mod local;
 mod remote;
+mod store;
 
 fn short_name(id: SourceId) -> String {
     let hash = hex::short_hash(&id);
@@ -437,6 +520,9 @@ impl<'cfg> RegistrySource<'cfg> {
     ) -> RegistrySource<'cfg> {
         RegistrySource {
             src_path: config.registry_source_path().join(name),
+            shared_src_path: config
+                .shared_registry_path()
+                .map(|shared| shared.join("src").join(name)),
             config,
             source_id,
             updated: false,
@@ -461,6 +547,20 @@ impl<'cfg> RegistrySource<'cfg> {
         // The `.cargo-ok` file is used to track if the source is already
         // unpacked.
         let package_dir = format!("{}-{}", pkg.name(), pkg.version());
+        // Packages unpacked in the shared registry aren't checked against
+        // `tarball`, which was checked against the index: checking every file
+        // would cost about as much as unpacking it again. The shared registry
+        // is trusted like the rest of the read-only Cargo home, such as its
+        // installed binaries.
+        if let Some(shared_src_path) = &self.shared_src_path {
+            let path = shared_src_path.join(&package_dir).join(PACKAGE_SOURCE_LOCK);
+            let path = self.config.assert_package_cache_locked(&path);
+            if let Ok(meta) = path.metadata() {
+                if meta.len() > 0 {
+                    return Ok(path.parent().unwrap().to_path_buf());
+                }
+            }
+        }
         let dst = self.src_path.join(&package_dir);
         dst.create_dir()?;
         let path = dst.join(PACKAGE_SOURCE_LOCK);
@@ -471,8 +571,7 @@ impl<'cfg> RegistrySource<'cfg> {
                 return Ok(unpack_dir.to_path_buf());
             }
         }
-        let gz = GzDecoder::new(tarball);
-        let mut tar = Archive::new(gz);
+        let mut tar = Archive::new(compression::decoder(tarball)?);
         let prefix = unpack_dir.file_name().unwrap();
         let parent = unpack_dir.parent().unwrap();
         for entry in tar.entries()? {
@@ -533,6 +632,22 @@ impl<'cfg> RegistrySource<'cfg> {
         Ok(())
     }
 
+    /// Checks the signature of `package` listed in the index with the
+    /// `trusted-keys` of the registry, if it has any.
+    ///
+    /// The signature covers the checksum in the index, which the downloaded
+    /// `.crate` file is checked against.
+    fn verify_signature(&mut self, package: PackageId) -> CargoResult<()> {
+        let trusted_keys = match ops::registry_trusted_keys(self.config, self.source_id)? {
+            Some(trusted_keys) => trusted_keys,
+            None => return Ok(()),
+        };
+        let hash = self.index.hash(package, &mut *self.ops)?.to_string();
+        let signature = self.index.signature(package, &mut *self.ops)?;
+        crate_signature::verify(signature.as_deref(), &trusted_keys, package, &hash)
+            .chain_err(|| format!("failed to verify the signature of `{}`", package))
+    }
+
     fn get_pkg(&mut self, package: PackageId, path: &File) -> CargoResult<Package> {
         let path = self
             .unpack_package(package, path)
@@ -629,15 +744,30 @@ impl<'cfg> Source for RegistrySource<'cfg> {
     }
 
     fn download(&mut self, package: PackageId) -> CargoResult<MaybePackage> {
+        if self.config.cli_unstable().crate_signing {
+            self.verify_signature(package)?;
+        }
         let hash = self.index.hash(package, &mut *self.ops)?;
         match self.ops.download(package, hash)? {
             MaybeLock::Ready(file) => self.get_pkg(package, &file).map(MaybePackage::Ready),
-            MaybeLock::Download { url, descriptor } => {
-                Ok(MaybePackage::Download { url, descriptor })
-            }
+            MaybeLock::Download {
+                url,
+                descriptor,
+                partial,
+                authorization,
+            } => Ok(MaybePackage::Download {
+                url,
+                descriptor,
+                partial,
+                authorization,
+            }),
         }
     }
 
+    fn fall_back(&mut self, package: PackageId, err: &anyhow::Error) -> CargoResult<bool> {
+        self.ops.fall_back(package, err)
+    }
+
     fn finish_download(&mut self, package: PackageId, data: Vec<u8>) -> CargoResult<Package> {
         let hash = self.index.hash(package, &mut *self.ops)?;
         let file = self.ops.finish_download(package, hash, &data)?;
@@ -662,4 +792,43 @@ impl<'cfg> Source for RegistrySource<'cfg> {
         }
         self.index.is_yanked(pkg, &mut *self.ops)
     }
+
+    fn index_version(&self) -> Option<InternedString> {
+        self.ops.current_version()
+    }
+
+    fn prebuilt_binary(
+        &mut self,
+        pkg: PackageId,
+        target: &str,
+    ) -> CargoResult<Option<PrebuiltBinary>> {
+        let url = match self.ops.config()?.and_then(|config| config.prebuilt_dl) {
+            Some(url) => url,
+            None => return Ok(None),
+        };
+        let prebuilt = match self.index.prebuilt(pkg, target, &mut *self.ops)? {
+            Some(prebuilt) => prebuilt,
+            None => return Ok(None),
+        };
+        let url = url
+            .replace(CRATE_TEMPLATE, &pkg.name())
+            .replace(VERSION_TEMPLATE, &pkg.version().to_string())
+            .replace(TARGET_TEMPLATE, target);
+        Ok(Some(PrebuiltBinary {
+            url,
+            cksum: prebuilt.cksum,
+            signature: prebuilt.sig,
+        }))
+    }
+
+    fn download_url(&mut self, pkg: PackageId) -> CargoResult<Option<String>> {
+        Ok(self
+            .ops
+            .config()?
+            .map(|config| remote::download_url(&config.dl, pkg)))
+    }
+
+    fn save_caches(&mut self) -> CargoResult<()> {
+        self.index.save_store()
+    }
 }
@@ -1,11 +1,12 @@
 use crate::core::{GitReference, PackageId, SourceId};
+use crate::ops;
 use crate::sources::git;
 use crate::sources::registry::MaybeLock;
 use crate::sources::registry::{
     RegistryConfig, RegistryData, CRATE_TEMPLATE, LOWER_PREFIX_TEMPLATE, PREFIX_TEMPLATE,
     VERSION_TEMPLATE,
 };
-use crate::util::errors::{CargoResult, CargoResultExt};
+use crate::util::errors::{CargoResult, CargoResultExt, HttpNot200};
 use crate::util::interning::InternedString;
 use crate::util::paths;
 use crate::util::{Config, Filesystem, Sha256};
@@ -29,9 +30,33 @@ fn make_dep_prefix(name: &str) -> String {
     }
 }
 
+/// Returns the URL of the `.crate` file of `pkg`, according to the `dl`
+/// template of the `config.json` of its registry.
+pub(super) fn download_url(dl: &str, pkg: PackageId) -> String {
+    let mut url = dl.to_string();
+    if !url.contains(CRATE_TEMPLATE)
+        && !url.contains(VERSION_TEMPLATE)
+        && !url.contains(PREFIX_TEMPLATE)
+        && !url.contains(LOWER_PREFIX_TEMPLATE)
+    {
+        write!(url, "/{}/{}/download", CRATE_TEMPLATE, VERSION_TEMPLATE).unwrap();
+    }
+    let prefix = make_dep_prefix(&pkg.name());
+    url.replace(CRATE_TEMPLATE, &pkg.name())
+        .replace(VERSION_TEMPLATE, &pkg.version().to_string())
+        .replace(PREFIX_TEMPLATE, &prefix)
+        .replace(LOWER_PREFIX_TEMPLATE, &prefix.to_lowercase())
+}
+
 pub struct RemoteRegistry<'cfg> {
     index_path: Filesystem,
+    /// Where the index is fetched to when it's updated, if `index_path` is
+    /// the index of the read-only shared registry.
+    overlay_index_path: Option<Filesystem>,
     cache_path: Filesystem,
+    /// The cache of the read-only shared registry, which `.crate` files are
+    /// also taken from.
+    shared_cache_path: Option<Filesystem>,
     source_id: SourceId,
     index_git_ref: GitReference,
     config: &'cfg Config,
@@ -39,13 +64,39 @@ pub struct RemoteRegistry<'cfg> {
     repo: LazyCell<git2::Repository>,
     head: Cell<Option<git2::Oid>>,
     current_sha: Cell<Option<InternedString>>,
+    /// The token sent with downloads, if the registry requires
+    /// authentication.
+    token: Option<String>,
+    /// Whether the registry rejected a token already.
+    token_rejected: bool,
 }
 
 impl<'cfg> RemoteRegistry<'cfg> {
     pub fn new(source_id: SourceId, config: &'cfg Config, name: &str) -> RemoteRegistry<'cfg> {
+        // Shallow indices are kept apart from full ones, which older versions
+        // of Cargo may try to fetch into with `libgit2`.
+        let index_name = match git::FetchDepth::for_config(config) {
+            git::FetchDepth::Full => name.to_string(),
+            git::FetchDepth::Shallow => format!("{}-shallow", name),
+        };
+        let mut index_path = config.registry_index_path().join(&index_name);
+        let mut overlay_index_path = None;
+        let shared = config.shared_registry_path();
+        if let Some(shared) = &shared {
+            // The index of the shared registry is used as long as it didn't
+            // need to be updated, which is done in the overlay.
+            let shared_index_path = shared.join("index").join(&index_name);
+            if !index_path.as_path_unlocked().exists()
+                && shared_index_path.as_path_unlocked().exists()
+            {
+                overlay_index_path = Some(mem::replace(&mut index_path, shared_index_path));
+            }
+        }
         RemoteRegistry {
-            index_path: config.registry_index_path().join(name),
+            index_path,
+            overlay_index_path,
             cache_path: config.registry_cache_path().join(name),
+            shared_cache_path: shared.map(|shared| shared.join("cache").join(name)),
             source_id,
             config,
             // TODO: we should probably make this configurable
@@ -54,6 +105,8 @@ impl<'cfg> RemoteRegistry<'cfg> {
             repo: LazyCell::new(),
             head: Cell::new(None),
             current_sha: Cell::new(None),
+            token: None,
+            token_rejected: false,
         }
     }
 
@@ -99,6 +152,37 @@ impl<'cfg> RemoteRegistry<'cfg> {
         })
     }
 
+    /// Switches from the read-only index of the shared registry to a new
+    /// one at `overlay`, which can be updated. The new index borrows the
+    /// objects of the shared one, so that fetching it only downloads what
+    /// changed since.
+    fn layer_index(&mut self, overlay: Filesystem) -> CargoResult<()> {
+        let shared = git2::Repository::open(self.index_path.as_path_unlocked())?;
+        let path = self.config.assert_package_cache_locked(&overlay);
+        drop(paths::remove_dir_all(path));
+        paths::create_dir_all(path)?;
+        let mut opts = git2::RepositoryInitOptions::new();
+        opts.external_template(false);
+        let repo = git2::Repository::init_opts(path, &opts)
+            .chain_err(|| "failed to initialize index git repository")?;
+        let alternates = shared.path().join("objects");
+        paths::write(
+            repo.path().join("objects/info/alternates"),
+            format!("{}\n", alternates.display()),
+        )?;
+        drop(repo);
+
+        // Start from the revision of the shared index.
+        let repo = git2::Repository::open(path)?;
+        if let Ok(oid) = self.index_git_ref.resolve(&shared, None) {
+            repo.reference("refs/remotes/origin/HEAD", oid, true, "shared index")?;
+        }
+
+        self.index_path = overlay;
+        self.repo = LazyCell::new();
+        Ok(())
+    }
+
     fn head(&self) -> CargoResult<git2::Oid> {
         if self.head.get().is_none() {
             let repo = self.repo()?;
@@ -215,6 +299,11 @@ impl<'cfg> RegistryData for RemoteRegistry<'cfg> {
 
         debug!("updating the index");
 
+        if let Some(overlay) = self.overlay_index_path.take() {
+            *self.tree.borrow_mut() = None;
+            self.layer_index(overlay)?;
+        }
+
         // Ensure that we'll actually be able to acquire an HTTP handle later on
         // once we start trying to download crates. This will weed out any
         // problems with `.cargo/config` configuration related to HTTP.
@@ -236,7 +325,8 @@ impl<'cfg> RegistryData for RemoteRegistry<'cfg> {
         // checkout.
         let url = self.source_id.url();
         let repo = self.repo.borrow_mut().unwrap();
-        git::fetch(repo, url.as_str(), &self.index_git_ref, self.config)
+        let depth = git::FetchDepth::for_config(self.config);
+        git::fetch(repo, url.as_str(), &self.index_git_ref, depth, self.config)
             .chain_err(|| format!("failed to fetch `{}`", url))?;
         self.config.updated_sources().insert(self.source_id);
 
@@ -247,7 +337,7 @@ impl<'cfg> RegistryData for RemoteRegistry<'cfg> {
         Ok(())
     }
 
-    fn download(&mut self, pkg: PackageId, _checksum: &str) -> CargoResult<MaybeLock> {
+    fn download(&mut self, pkg: PackageId, checksum: &str) -> CargoResult<MaybeLock> {
         let filename = self.filename(pkg);
 
         // Attempt to open an read-only copy first to avoid an exclusive write
@@ -265,28 +355,73 @@ impl<'cfg> RegistryData for RemoteRegistry<'cfg> {
             }
         }
 
-        let config = self.config()?.unwrap();
-        let mut url = config.dl;
-        if !url.contains(CRATE_TEMPLATE)
-            && !url.contains(VERSION_TEMPLATE)
-            && !url.contains(PREFIX_TEMPLATE)
-            && !url.contains(LOWER_PREFIX_TEMPLATE)
-        {
-            write!(url, "/{}/{}/download", CRATE_TEMPLATE, VERSION_TEMPLATE).unwrap();
+        // Anyone with access to the shared registry may have written to its
+        // cache, so `.crate` files from there are only used if they match the
+        // checksum in the index.
+        if let Some(shared_cache_path) = &self.shared_cache_path {
+            let path = shared_cache_path.join(&filename);
+            let path = self.config.assert_package_cache_locked(&path);
+            if let Ok(mut dst) = File::open(path) {
+                if dst.metadata()?.len() > 0 {
+                    let actual = Sha256::new().update_file(&dst)?.finish_hex();
+                    if actual == checksum {
+                        dst.seek(SeekFrom::Start(0))?;
+                        return Ok(MaybeLock::Ready(dst));
+                    }
+                    self.config.shell().warn(format!(
+                        "the checksum of `{}` in the shared registry does not match, \
+                         downloading `{} v{}` again",
+                        path.display(),
+                        pkg.name(),
+                        pkg.version()
+                    ))?;
+                }
+            }
         }
-        let prefix = make_dep_prefix(&*pkg.name());
-        let url = url
-            .replace(CRATE_TEMPLATE, &*pkg.name())
-            .replace(VERSION_TEMPLATE, &pkg.version().to_string())
-            .replace(PREFIX_TEMPLATE, &prefix)
-            .replace(LOWER_PREFIX_TEMPLATE, &prefix.to_lowercase());
 
+        let config = self.config()?.unwrap();
+        let authorization = if config.auth_required {
+            if self.token.is_none() {
+                let token =
+                    ops::registry_token(self.config, self.source_id, config.api.as_deref())?;
+                self.token = Some(token);
+            }
+            self.token.clone()
+        } else {
+            None
+        };
+        let url = download_url(&config.dl, pkg);
+
+        // Data is written next to the `.crate` file while it's downloaded.
+        let partial = self.cache_path.join(format!("{}.part", filename));
+        let partial = self.config.assert_package_cache_locked(&partial);
         Ok(MaybeLock::Download {
             url,
             descriptor: pkg.to_string(),
+            partial: Some(partial.to_path_buf()),
+            authorization,
         })
     }
 
+    fn fall_back(&mut self, pkg: PackageId, err: &anyhow::Error) -> CargoResult<bool> {
+        // The token may have expired, in which case a credential process can
+        // provide a new one, so the download is tried again once.
+        let unauthorized = err
+            .chain()
+            .filter_map(|e| e.downcast_ref::<HttpNot200>())
+            .any(|e| e.code == 401);
+        if !unauthorized || self.token.is_none() || self.token_rejected {
+            return Ok(false);
+        }
+        debug!(
+            "token rejected when downloading {}, asking for a new one",
+            pkg
+        );
+        self.token = None;
+        self.token_rejected = true;
+        Ok(true)
+    }
+
     fn finish_download(
         &mut self,
         pkg: PackageId,
@@ -323,12 +458,17 @@ impl<'cfg> RegistryData for RemoteRegistry<'cfg> {
         let filename = format!("{}-{}.crate", pkg.name(), pkg.version());
         let path = Path::new(&filename);
 
-        let path = self.cache_path.join(path);
-        let path = self.config.assert_package_cache_locked(&path);
-        if let Ok(meta) = fs::metadata(path) {
-            return meta.len() > 0;
-        }
-        false
+        let mut cache_paths = Some(&self.cache_path)
+            .into_iter()
+            .chain(self.shared_cache_path.as_ref());
+        cache_paths.any(|cache_path| {
+            let path = cache_path.join(path);
+            let path = self.config.assert_package_cache_locked(&path);
+            match fs::metadata(path) {
+                Ok(meta) => meta.len() > 0,
+                Err(_) => false,
+            }
+        })
     }
 }
 
@@ -0,0 +1,520 @@
+//! A memory-mapped store of parsed index entries, shared by successive Cargo
+//! invocations.
+//!
+//! The cache files of `index.rs` save Cargo from loading index files through
+//! libgit2, but each invocation still parses the JSON of every entry it looks
+//! at. Scripts running `cargo metadata`, `cargo check` and `cargo build` back
+//! to back parse the same entries over and over again.
+//!
+//! With `-Z index-store`, the entries of each package are also kept in a
+//! compact binary encoding in a single `.cache/.store` file of the index,
+//! which is memory-mapped and decoded without any JSON parsing. The store
+//! belongs to the state of the index it was written for, see
+//! `RegistryData::current_version`, and is started over when the index is
+//! updated.
+//!
+//! The store is never modified in place. Packages looked up for the first
+//! time are added by writing a new store next to it and renaming it over the
+//! previous one, so concurrent invocations keep reading the version they
+//! mapped, and the last writer wins. This happens once the workspace is
+//! resolved, see `Source::save_caches`. The summaries loaded from the store
+//! borrow from its map, and have to copy their data before it is unmapped
+//! for the rename, since Windows can't replace a mapped file. On Windows, a
+//! store mapped by another Cargo can't be replaced either, and the new one is
+//! only written by a later invocation.
+//!
+//! The file starts with a header:
+//!
+//! ```text
+//! magic | format version | index version | entry count | entries...
+//! ```
+//!
+//! where each entry is the path of a package in the index, and the offset and
+//! length of its data. The data of a package is a list of its versions, each
+//! followed by the length and encoding of its `RegistryPackage`. Integers are
+//! 32-bit little-endian, and strings are prefixed with their length.
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, Write};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::str;
+use std::sync::Arc;
+
+use anyhow::{bail, format_err};
+use log::{debug, info};
+use memmap2::Mmap;
+
+use super::{RegistryDependency, RegistryPackage, RegistryPrebuilt};
+use crate::util::errors::{CargoResult, CargoResultExt};
+use crate::util::interning::InternedString;
+use crate::util::{internal, paths};
+
+const MAGIC: &[u8] = b"CIS\0";
+const FORMAT_VERSION: u8 = 3;
+
+/// Bytes which are either owned, or borrowed from the map of a store.
+pub enum RawData {
+    Owned(Vec<u8>),
+    Mapped {
+        map: Arc<Mmap>,
+        start: usize,
+        end: usize,
+    },
+}
+
+impl RawData {
+    /// Copies the bytes if they are borrowed from a map, so that it can be
+    /// unmapped.
+    pub fn detach(&mut self) {
+        if let RawData::Mapped { .. } = self {
+            *self = RawData::Owned(self.to_vec());
+        }
+    }
+}
+
+impl Default for RawData {
+    fn default() -> RawData {
+        RawData::Owned(Vec::new())
+    }
+}
+
+impl Deref for RawData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            RawData::Owned(data) => data,
+            RawData::Mapped { map, start, end } => &map[*start..*end],
+        }
+    }
+}
+
+/// The store of a registry index.
+pub struct IndexStore {
+    path: PathBuf,
+    index_version: String,
+    /// The store as it was when it was opened, if it is of `index_version`.
+    map: Option<Arc<Mmap>>,
+    /// The offset and length in `map` of the data of each package.
+    entries: HashMap<String, (usize, usize)>,
+    /// The data of the packages which were added since it was opened.
+    added: BTreeMap<String, Vec<u8>>,
+}
+
+impl IndexStore {
+    /// Opens the store at `path`, which is empty if it doesn't exist or was
+    /// written for another state of the index than `index_version`.
+    pub fn open(path: &Path, index_version: &str) -> IndexStore {
+        let mut store = IndexStore {
+            path: path.to_path_buf(),
+            index_version: index_version.to_string(),
+            map: None,
+            entries: HashMap::new(),
+            added: BTreeMap::new(),
+        };
+        let map = match File::open(path) {
+            // The store is replaced rather than modified, so the mapped file
+            // doesn't change under us.
+            Ok(file) => match unsafe { Mmap::map(&file) } {
+                Ok(map) => map,
+                Err(e) => {
+                    info!("failed to map index store {:?}: {}", path, e);
+                    return store;
+                }
+            },
+            Err(e) => {
+                debug!("index store {:?} missing: {}", path, e);
+                return store;
+            }
+        };
+        match read_header(&map, index_version) {
+            Ok(entries) => {
+                store.entries = entries;
+                store.map = Some(Arc::new(map));
+            }
+            Err(e) => debug!("not using index store {:?}: {}", path, e),
+        }
+        store
+    }
+
+    /// Returns the state of the index the store is for.
+    pub fn index_version(&self) -> &str {
+        &self.index_version
+    }
+
+    /// Returns the data of the package at `relative` in the index, if the
+    /// store has it.
+    pub fn get(&self, relative: &str) -> Option<RawData> {
+        if let Some(data) = self.added.get(relative) {
+            return Some(RawData::Owned(data.clone()));
+        }
+        let (start, len) = *self.entries.get(relative)?;
+        Some(RawData::Mapped {
+            map: Arc::clone(self.map.as_ref()?),
+            start,
+            end: start + len,
+        })
+    }
+
+    /// Returns whether packages were added since the store was opened, in
+    /// which case `save` writes it back.
+    pub fn has_added(&self) -> bool {
+        !self.added.is_empty()
+    }
+
+    /// Adds the package at `relative` in the index with the JSON `lines` of
+    /// its versions. Lines which can't be parsed are left out, like they are
+    /// skipped when loading the index.
+    pub fn add<'a>(&mut self, relative: &str, lines: impl Iterator<Item = &'a [u8]>) {
+        let mut data = Vec::new();
+        for line in lines {
+            let package: RegistryPackage<'_> = match serde_json::from_slice(line) {
+                Ok(package) => package,
+                Err(_) => continue,
+            };
+            let mut encoded = Vec::new();
+            encode_package(&mut encoded, &package);
+            write_str(&mut data, &package.vers.to_string());
+            write_u32(&mut data, encoded.len());
+            data.extend_from_slice(&encoded);
+        }
+        self.added.insert(relative.to_string(), data);
+    }
+
+    /// Writes the store back if packages were added to it, and opens the
+    /// new one.
+    ///
+    /// The data borrowed from the store with `get` has to be detached
+    /// beforehand, see `RawData::detach`.
+    pub fn save(&mut self) -> CargoResult<()> {
+        if self.added.is_empty() {
+            return Ok(());
+        }
+        let map = self.map.take();
+        let mut packages: BTreeMap<&str, &[u8]> = BTreeMap::new();
+        if let Some(map) = &map {
+            for (relative, &(start, len)) in self.entries.iter() {
+                packages.insert(relative, &map[start..start + len]);
+            }
+        }
+        for (relative, data) in self.added.iter() {
+            packages.insert(relative, data);
+        }
+
+        let mut header = Vec::new();
+        header.extend_from_slice(MAGIC);
+        header.push(FORMAT_VERSION);
+        write_str(&mut header, &self.index_version);
+        write_u32(&mut header, packages.len());
+        let entries_len: usize = packages.keys().map(|relative| 12 + relative.len()).sum();
+        let mut offset = header.len() + entries_len;
+        for (relative, data) in packages.iter() {
+            write_str(&mut header, relative);
+            write_u32(&mut header, offset);
+            write_u32(&mut header, data.len());
+            offset += data.len();
+        }
+
+        // Other invocations may be saving the store at the same time, so each
+        // one writes its own temporary file.
+        let dir = self.path.parent().unwrap();
+        paths::create_dir_all(dir)?;
+        let mut file = tempfile::Builder::new()
+            .prefix(".store")
+            .tempfile_in(dir)
+            .chain_err(|| format!("failed to create a file in `{}`", dir.display()))?;
+        let written = file
+            .write_all(&header)
+            .and_then(|()| packages.values().try_for_each(|data| file.write_all(data)));
+        written.chain_err(|| format!("failed to write `{}`", file.path().display()))?;
+        let count = packages.len();
+        drop(packages);
+
+        // The store is unmapped before it is replaced, which Windows requires.
+        if let Some(map) = map {
+            if Arc::try_unwrap(map).is_err() {
+                return Err(internal("the index store is still borrowed"));
+            }
+        }
+        let persisted = file.persist(&self.path);
+        let added = std::mem::take(&mut self.added);
+        *self = IndexStore::open(&self.path, &self.index_version);
+        match persisted {
+            Ok(_) => {
+                debug!("saved {} packages in index store {:?}", count, self.path);
+                Ok(())
+            }
+            // Another Cargo has the store mapped, see the module comment.
+            Err(e) if cfg!(windows) && e.error.kind() == io::ErrorKind::PermissionDenied => {
+                debug!("index store {:?} is in use: {}", self.path, e.error);
+                self.added = added;
+                Ok(())
+            }
+            Err(e) => Err(anyhow::Error::from(e.error))
+                .chain_err(|| format!("failed to replace `{}`", self.path.display())),
+        }
+    }
+}
+
+/// Returns the versions in the `data` of a package, with the offsets and
+/// lengths of their encoding.
+pub fn versions(data: &[u8]) -> CargoResult<Vec<(&str, usize, usize)>> {
+    let mut reader = Reader { data, pos: 0 };
+    let mut versions = Vec::new();
+    while !reader.is_empty() {
+        let version = reader.str()?;
+        let len = reader.u32()?;
+        let start = reader.pos;
+        reader.bytes(len)?;
+        versions.push((version, start, len));
+    }
+    Ok(versions)
+}
+
+/// Decodes a `RegistryPackage` encoded in the store.
+pub fn decode_package(data: &[u8]) -> CargoResult<RegistryPackage<'_>> {
+    let mut r = Reader { data, pos: 0 };
+    let name = r.interned()?;
+    let vers = r.str()?.parse()?;
+    let deps = (0..r.u32()?)
+        .map(|_| {
+            Ok(RegistryDependency {
+                name: r.interned()?,
+                req: Cow::Borrowed(r.str()?),
+                features: r.interned_list()?,
+                optional: r.bool()?,
+                default_features: r.bool()?,
+                target: r.opt_str()?.map(Cow::Borrowed),
+                kind: r.opt_str()?.map(Cow::Borrowed),
+                registry: r.opt_str()?.map(Cow::Borrowed),
+                package: r.opt_str()?.map(InternedString::new),
+                public: r.opt_bool()?,
+            })
+        })
+        .collect::<CargoResult<_>>()?;
+    let features = r.features()?;
+    let cksum = r.str()?.to_string();
+    let yanked = r.opt_bool()?;
+    let links = r.opt_str()?.map(InternedString::new);
+    let features2 = if r.bool()? { Some(r.features()?) } else { None };
+    let v = if r.bool()? {
+        Some(r.u32()? as u32)
+    } else {
+        None
+    };
+    let sig = r.opt_str()?.map(str::to_string);
+    let prebuilt = if r.bool()? {
+        Some(
+            (0..r.u32()?)
+                .map(|_| {
+                    Ok(RegistryPrebuilt {
+                        target: r.str()?.to_string(),
+                        cksum: r.str()?.to_string(),
+                        sig: r.opt_str()?.map(str::to_string),
+                    })
+                })
+                .collect::<CargoResult<_>>()?,
+        )
+    } else {
+        None
+    };
+    if !r.is_empty() {
+        bail!("trailing data after package `{}`", name);
+    }
+    Ok(RegistryPackage {
+        name,
+        vers,
+        deps,
+        features,
+        cksum,
+        yanked,
+        links,
+        features2,
+        v,
+        sig,
+        prebuilt,
+    })
+}
+
+fn read_header(data: &[u8], index_version: &str) -> CargoResult<HashMap<String, (usize, usize)>> {
+    let mut r = Reader { data, pos: 0 };
+    if r.bytes(MAGIC.len())? != MAGIC || r.bytes(1)? != [FORMAT_VERSION] {
+        bail!("unknown format");
+    }
+    let version = r.str()?;
+    if version != index_version {
+        bail!(
+            "written for another index state ({} != {})",
+            version,
+            index_version
+        );
+    }
+    let mut entries = HashMap::new();
+    for _ in 0..r.u32()? {
+        let relative = r.str()?.to_string();
+        let start = r.u32()?;
+        let len = r.u32()?;
+        if data.len() < start + len {
+            bail!("truncated");
+        }
+        entries.insert(relative, (start, len));
+    }
+    Ok(entries)
+}
+
+fn encode_package(out: &mut Vec<u8>, package: &RegistryPackage<'_>) {
+    write_str(out, &package.name);
+    write_str(out, &package.vers.to_string());
+    write_u32(out, package.deps.len());
+    for dep in package.deps.iter() {
+        write_str(out, &dep.name);
+        write_str(out, &dep.req);
+        write_u32(out, dep.features.len());
+        for feature in dep.features.iter() {
+            write_str(out, feature);
+        }
+        write_bool(out, dep.optional);
+        write_bool(out, dep.default_features);
+        write_opt_str(out, dep.target.as_deref());
+        write_opt_str(out, dep.kind.as_deref());
+        write_opt_str(out, dep.registry.as_deref());
+        write_opt_str(out, dep.package.as_deref());
+        write_opt_bool(out, dep.public);
+    }
+    write_features(out, &package.features);
+    write_str(out, &package.cksum);
+    write_opt_bool(out, package.yanked);
+    write_opt_str(out, package.links.as_deref());
+    write_bool(out, package.features2.is_some());
+    if let Some(features2) = &package.features2 {
+        write_features(out, features2);
+    }
+    write_bool(out, package.v.is_some());
+    if let Some(v) = package.v {
+        write_u32(out, v as usize);
+    }
+    write_opt_str(out, package.sig.as_deref());
+    write_bool(out, package.prebuilt.is_some());
+    if let Some(prebuilt) = &package.prebuilt {
+        write_u32(out, prebuilt.len());
+        for bin in prebuilt {
+            write_str(out, &bin.target);
+            write_str(out, &bin.cksum);
+            write_opt_str(out, bin.sig.as_deref());
+        }
+    }
+}
+
+fn write_u32(out: &mut Vec<u8>, n: usize) {
+    out.extend_from_slice(&(n as u32).to_le_bytes());
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_u32(out, s.len());
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn write_bool(out: &mut Vec<u8>, b: bool) {
+    out.push(b as u8);
+}
+
+fn write_opt_str(out: &mut Vec<u8>, s: Option<&str>) {
+    write_bool(out, s.is_some());
+    if let Some(s) = s {
+        write_str(out, s);
+    }
+}
+
+fn write_opt_bool(out: &mut Vec<u8>, b: Option<bool>) {
+    out.push(match b {
+        None => 0,
+        Some(false) => 1,
+        Some(true) => 2,
+    });
+}
+
+fn write_features(out: &mut Vec<u8>, features: &BTreeMap<InternedString, Vec<InternedString>>) {
+    write_u32(out, features.len());
+    for (name, values) in features {
+        write_str(out, name);
+        write_u32(out, values.len());
+        for value in values {
+            write_str(out, value);
+        }
+    }
+}
+
+/// Reads the encoding of the store, failing rather than panicking on
+/// malformed data.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.pos == self.data.len()
+    }
+
+    fn bytes(&mut self, len: usize) -> CargoResult<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| format_err!("unexpected end of data"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> CargoResult<usize> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.bytes(4)?);
+        Ok(u32::from_le_bytes(bytes) as usize)
+    }
+
+    fn str(&mut self) -> CargoResult<&'a str> {
+        let len = self.u32()?;
+        Ok(str::from_utf8(self.bytes(len)?)?)
+    }
+
+    fn interned(&mut self) -> CargoResult<InternedString> {
+        Ok(InternedString::new(self.str()?))
+    }
+
+    fn interned_list(&mut self) -> CargoResult<Vec<InternedString>> {
+        (0..self.u32()?).map(|_| self.interned()).collect()
+    }
+
+    fn bool(&mut self) -> CargoResult<bool> {
+        match self.bytes(1)? {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            _ => bail!("invalid boolean"),
+        }
+    }
+
+    fn opt_str(&mut self) -> CargoResult<Option<&'a str>> {
+        if self.bool()? {
+            Ok(Some(self.str()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn opt_bool(&mut self) -> CargoResult<Option<bool>> {
+        match self.bytes(1)? {
+            [0] => Ok(None),
+            [1] => Ok(Some(false)),
+            [2] => Ok(Some(true)),
+            _ => bail!("invalid boolean"),
+        }
+    }
+
+    fn features(&mut self) -> CargoResult<BTreeMap<InternedString, Vec<InternedString>>> {
+        (0..self.u32()?)
+            .map(|_| Ok((self.interned()?, self.interned_list()?)))
+            .collect()
+    }
+}
@@ -1,6 +1,7 @@
-use crate::core::source::MaybePackage;
+use crate::core::source::{MaybePackage, PrebuiltBinary};
 use crate::core::{Dependency, Package, PackageId, Source, SourceId, Summary};
 use crate::util::errors::{CargoResult, CargoResultExt};
+use crate::util::interning::InternedString;
 
 pub struct ReplacedSource<'cfg> {
     to_replace: SourceId,
@@ -93,6 +94,11 @@ impl<'cfg> Source for ReplacedSource<'cfg> {
         Ok(pkg.map_source(self.replace_with, self.to_replace))
     }
 
+    fn fall_back(&mut self, id: PackageId, err: &anyhow::Error) -> CargoResult<bool> {
+        let id = id.with_source_id(self.replace_with);
+        self.inner.fall_back(id, err)
+    }
+
     fn fingerprint(&self, id: &Package) -> CargoResult<String> {
         self.inner.fingerprint(id)
     }
@@ -125,4 +131,26 @@ impl<'cfg> Source for ReplacedSource<'cfg> {
     fn is_yanked(&mut self, pkg: PackageId) -> CargoResult<bool> {
         self.inner.is_yanked(pkg)
     }
+
+    fn index_version(&self) -> Option<InternedString> {
+        self.inner.index_version()
+    }
+
+    fn prebuilt_binary(
+        &mut self,
+        pkg: PackageId,
+        target: &str,
+    ) -> CargoResult<Option<PrebuiltBinary>> {
+        let pkg = pkg.with_source_id(self.replace_with);
+        self.inner.prebuilt_binary(pkg, target)
+    }
+
+    fn download_url(&mut self, pkg: PackageId) -> CargoResult<Option<String>> {
+        let pkg = pkg.with_source_id(self.replace_with);
+        self.inner.download_url(pkg)
+    }
+
+    fn save_caches(&mut self) -> CargoResult<()> {
+        self.inner.save_caches()
+    }
 }
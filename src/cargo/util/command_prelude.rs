@@ -1,5 +1,5 @@
-use crate::core::compiler::{BuildConfig, MessageFormat};
-use crate::core::Workspace;
+use crate::core::compiler::{AnnotationFormat, BuildConfig, MessageFormat};
+use crate::core::{PathDepVersion, Workspace};
 use crate::ops::{CompileFilter, CompileOptions, NewOptions, Packages, VersionControl};
 use crate::sources::CRATES_IO_REGISTRY;
 use crate::util::important_paths::find_root_manifest_for_wd;
@@ -14,7 +14,7 @@ use crate::CargoResult;
 use anyhow::bail;
 use clap::{self, SubCommand};
 use std::ffi::{OsStr, OsString};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub use crate::core::compiler::CompileMode;
 pub use crate::{CliError, CliResult, Config};
@@ -34,6 +34,11 @@ pub trait AppExt: Sized {
         self.arg_package_spec_simple(package)
             ._arg(opt("all", "Alias for --workspace (deprecated)"))
             ._arg(opt("workspace", all))
+            ._arg(opt("all-members", "Alias for --workspace"))
+            ._arg(opt(
+                "default-members",
+                "Select the workspace's default members, in addition to any given with -p",
+            ))
             ._arg(multi_opt("exclude", "SPEC", exclude))
     }
 
@@ -48,6 +53,11 @@ pub trait AppExt: Sized {
     ) -> Self {
         self.arg_package_spec_simple(package)
             ._arg(opt("workspace", all))
+            ._arg(opt("all-members", "Alias for --workspace"))
+            ._arg(opt(
+                "default-members",
+                "Select the workspace's default members, in addition to any given with -p",
+            ))
             ._arg(multi_opt("exclude", "SPEC", exclude))
     }
 
@@ -212,6 +222,17 @@ pub trait AppExt: Sized {
     fn arg_dry_run(self, dry_run: &'static str) -> Self {
         self._arg(opt("dry-run", dry_run))
     }
+
+    fn arg_path_dep_version(self) -> Self {
+        self._arg(
+            opt(
+                "path-dep-version",
+                "Kind of version requirement for path dependencies on workspace members",
+            )
+            .value_name("KIND")
+            .possible_values(&["caret", "exact"]),
+        )
+    }
 }
 
 impl AppExt for App {
@@ -310,7 +331,21 @@ pub trait ArgMatchesExt {
         if config.cli_unstable().avoid_dev_deps {
             ws.set_require_optional_deps(false);
         }
-        if ws.is_virtual() && !ws.allows_unstable_package_features() {
+        if self._value_of("path-dep-version") == Some("exact") {
+            ws.set_path_dep_version(PathDepVersion::Exact);
+        }
+        // When a single package is selected with `-p`, the features are
+        // applied to it instead (see `Workspace::members_with_features`).
+        let single_package = match self._values_of("package").as_slice() {
+            [spec] => {
+                !is_glob_pattern(spec)
+                    && !["workspace", "all", "all-members", "default-members"]
+                        .iter()
+                        .any(|flag| self._is_present(flag))
+            }
+            _ => false,
+        };
+        if ws.is_virtual() && !ws.allows_unstable_package_features() && !single_package {
             // --all-features is actually honored. In general, workspaces and
             // feature flags are a bit of a mess right now.
             for flag in &["features", "no-default-features"] {
@@ -331,8 +366,28 @@ pub trait ArgMatchesExt {
         self.value_of_u32("jobs")
     }
 
-    fn targets(&self) -> Vec<String> {
+    /// Returns the values of `--target`.
+    ///
+    /// A relative path to a JSON target specification which does not exist in
+    /// the current directory is resolved against the workspace root instead.
+    fn targets(&self, workspace: Option<&Workspace<'_>>) -> Vec<String> {
         self._values_of("target")
+            .into_iter()
+            .map(|target| {
+                let ws = match workspace {
+                    Some(ws) if target.ends_with(".json") => ws,
+                    _ => return target,
+                };
+                let path = Path::new(&target);
+                if path.is_relative() && !path.exists() {
+                    let ws_path = ws.root().join(path);
+                    if ws_path.exists() {
+                        return ws_path.to_string_lossy().into_owned();
+                    }
+                }
+                target
+            })
+            .collect()
     }
 
     fn get_profile_name(
@@ -388,7 +443,10 @@ pub trait ArgMatchesExt {
     fn packages_from_flags(&self) -> CargoResult<Packages> {
         Packages::from_flags(
             // TODO Integrate into 'workspace'
-            self._is_present("workspace") || self._is_present("all"),
+            self._is_present("workspace")
+                || self._is_present("all")
+                || self._is_present("all-members"),
+            self._is_present("default-members"),
             self._values_of("exclude"),
             self._values_of("package"),
         )
@@ -409,6 +467,16 @@ pub trait ArgMatchesExt {
             render_diagnostics: false,
         };
         for fmt in self._values_of("message-format") {
+            // The template may contain commas, so it has to be the only value.
+            if let Some(template) = fmt.strip_prefix("template=") {
+                if message_format.is_some() {
+                    bail!("cannot specify two kinds of `message-format` arguments");
+                }
+                message_format = Some(MessageFormat::Annotations(AnnotationFormat::template(
+                    template,
+                )?));
+                continue;
+            }
             for fmt in fmt.split(',') {
                 let fmt = fmt.to_ascii_lowercase();
                 match fmt.as_str() {
@@ -430,6 +498,17 @@ pub trait ArgMatchesExt {
                         }
                         message_format = Some(MessageFormat::Short);
                     }
+                    "github" | "teamcity" => {
+                        if message_format.is_some() {
+                            bail!("cannot specify two kinds of `message-format` arguments");
+                        }
+                        let format = if fmt == "github" {
+                            AnnotationFormat::GitHub
+                        } else {
+                            AnnotationFormat::TeamCity
+                        };
+                        message_format = Some(MessageFormat::Annotations(format));
+                    }
                     "json-render-diagnostics" => {
                         if message_format.is_none() {
                             message_format = Some(default_json);
@@ -464,7 +543,22 @@ pub trait ArgMatchesExt {
             }
         }
 
-        let mut build_config = BuildConfig::new(config, self.jobs()?, &self.targets(), mode)?;
+        if let Some(MessageFormat::Annotations(format)) = message_format {
+            if !config.cli_unstable().unstable_options {
+                let fmt = match format {
+                    AnnotationFormat::GitHub => "github",
+                    AnnotationFormat::TeamCity => "teamcity",
+                    AnnotationFormat::Template(_) => "template",
+                };
+                bail!(
+                    "Usage of `--message-format {}` requires `-Z unstable-options`",
+                    fmt
+                );
+            }
+        }
+
+        let mut build_config =
+            BuildConfig::new(config, self.jobs()?, &self.targets(workspace), mode)?;
         build_config.message_format = message_format.unwrap_or(MessageFormat::Human);
         build_config.requested_profile = self.get_profile_name(config, "dev", profile_checking)?;
         build_config.build_plan = self._is_present("build-plan");
@@ -472,12 +566,12 @@ pub trait ArgMatchesExt {
         if build_config.build_plan {
             config
                 .cli_unstable()
-                .fail_if_stable_opt("--build-plan", 5579)?;
+                .fail_if_stable_opt("--build-plan", Some(5579))?;
         };
         if build_config.unit_graph {
             config
                 .cli_unstable()
-                .fail_if_stable_opt("--unit-graph", 8002)?;
+                .fail_if_stable_opt("--unit-graph", Some(8002))?;
         }
 
         let opts = CompileOptions {
@@ -0,0 +1,97 @@
+//! Compression of `.crate` files.
+//!
+//! `.crate` files have always been gzip-compressed tarballs, and every
+//! registry accepts those. Registries listing `"zstd"` in the `compression`
+//! key of their `config.json` also accept zstd-compressed ones, which are
+//! smaller and faster to decompress. Compressed data is recognized by its
+//! magic number when unpacking, so both kinds can be read from any source.
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+use flate2::GzBuilder;
+
+use crate::util::errors::CargoResult;
+use crate::util::paths;
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// The compression of a `.crate` file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CrateCompression {
+    Gzip,
+    Zstd,
+}
+
+impl CrateCompression {
+    /// Picks the best compression accepted by a registry, given the
+    /// `compression` key of its `config.json`.
+    pub fn negotiate(accepted: &[String]) -> CrateCompression {
+        if accepted.iter().any(|c| c == "zstd") {
+            CrateCompression::Zstd
+        } else {
+            CrateCompression::Gzip
+        }
+    }
+
+    /// Returns a writer compressing into `dst`. `filename` is the name of the
+    /// file, which gzip records in its header.
+    pub fn encoder<'a, W: Write + 'a>(
+        self,
+        dst: W,
+        filename: &Path,
+    ) -> CargoResult<Box<dyn Encoder + 'a>> {
+        Ok(match self {
+            CrateCompression::Gzip => Box::new(
+                GzBuilder::new()
+                    .filename(paths::path2bytes(filename)?)
+                    .write(dst, flate2::Compression::best()),
+            ),
+            CrateCompression::Zstd => {
+                let mut encoder = zstd::Encoder::new(dst, 19)?;
+                encoder.include_checksum(true)?;
+                Box::new(encoder)
+            }
+        })
+    }
+}
+
+impl fmt::Display for CrateCompression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CrateCompression::Gzip => f.write_str("gzip"),
+            CrateCompression::Zstd => f.write_str("zstd"),
+        }
+    }
+}
+
+/// A compressing writer, which must be finished to write the end of the
+/// compressed data.
+pub trait Encoder: Write {
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+impl<W: Write> Encoder for flate2::write::GzEncoder<W> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        (*self).finish().map(drop)
+    }
+}
+
+impl<'a, W: Write> Encoder for zstd::Encoder<'a, W> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        (*self).finish().map(drop)
+    }
+}
+
+/// Returns a reader decompressing the `.crate` file read from `src`, whatever
+/// its compression.
+pub fn decoder<'a, R: Read + 'a>(src: R) -> io::Result<Box<dyn Read + 'a>> {
+    let mut src = BufReader::new(src);
+    if src.fill_buf()?.starts_with(&ZSTD_MAGIC) {
+        Ok(Box::new(zstd::Decoder::with_buffer(src)?))
+    } else {
+        Ok(Box::new(GzDecoder::new(src)))
+    }
+}
@@ -100,7 +100,7 @@ macro_rules! get_value_typed {
         /// Low-level private method for getting a config value as an OptValue.
         fn $name(&self, key: &ConfigKey) -> Result<OptValue<$ty>, ConfigError> {
             let cv = self.get_cv(key)?;
-            let env = self.get_env::<$ty>(key)?;
+            let env = self.get_config_env::<$ty>(key)?;
             match (cv, env) {
                 (Some(CV::$variant(val, definition)), Some(env)) => {
                     if definition.is_higher_priority(&env.definition) {
@@ -124,6 +124,9 @@ macro_rules! get_value_typed {
 pub struct Config {
     /// The location of the user's Cargo home directory. OS-dependent.
     home_path: Filesystem,
+    /// The writable directory taking the place of `<cargo_home>/registry`
+    /// for new data, if that one is read-only.
+    registry_overlay: Option<Filesystem>,
     /// Information about how to write messages to the shell
     shell: RefCell<Shell>,
     /// A collection of configuration options
@@ -149,6 +152,11 @@ pub struct Config {
     offline: bool,
     /// A global static IPC control mechanism (used for managing parallel builds)
     jobserver: Option<jobserver::Client>,
+    /// The environment variable `jobserver` was found in.
+    jobserver_env: Option<&'static str>,
+    /// Why Cargo couldn't connect to the jobserver described in its
+    /// environment, if it couldn't.
+    jobserver_error: Option<String>,
     /// Cli flags of the form "-Z something" merged with config file values
     unstable_flags: CliUnstable,
     /// Cli flags of the form "-Z something"
@@ -188,16 +196,29 @@ impl Config {
     /// This does only minimal initialization. In particular, it does not load
     /// any config files from disk. Those will be loaded lazily as-needed.
     pub fn new(shell: Shell, cwd: PathBuf, homedir: PathBuf) -> Config {
-        static mut GLOBAL_JOBSERVER: *mut jobserver::Client = 0 as *mut _;
+        static mut GLOBAL_JOBSERVER: *mut jobserver::FromEnv = std::ptr::null_mut();
         static INIT: Once = Once::new();
 
         // This should be called early on in the process, so in theory the
         // unsafety is ok here. (taken ownership of random fds)
+        //
+        // This understands all the styles of jobservers of GNU make: a pair of
+        // file descriptors, a named fifo (`--jobserver-auth=fifo:PATH`) and a
+        // named semaphore on Windows.
         INIT.call_once(|| unsafe {
-            if let Some(client) = jobserver::Client::from_env() {
-                GLOBAL_JOBSERVER = Box::into_raw(Box::new(client));
-            }
+            GLOBAL_JOBSERVER = Box::into_raw(Box::new(jobserver::Client::from_env_ext(true)));
         });
+        let jobserver = unsafe { &*GLOBAL_JOBSERVER };
+        let jobserver_error = match &jobserver.client {
+            Ok(_) => None,
+            Err(e) => match e.kind() {
+                // There is no jobserver, or it was disabled on purpose.
+                jobserver::FromEnvErrorKind::NoEnvVar
+                | jobserver::FromEnvErrorKind::NoJobserver
+                | jobserver::FromEnvErrorKind::NegativeFd => None,
+                _ => Some(e.to_string()),
+            },
+        };
 
         let env: HashMap<_, _> = env::vars_os()
             .filter_map(|(k, v)| {
@@ -216,6 +237,7 @@ impl Config {
 
         Config {
             home_path: Filesystem::new(homedir),
+            registry_overlay: None,
             shell: RefCell::new(shell),
             cwd,
             values: LazyCell::new(),
@@ -226,13 +248,12 @@ impl Config {
             frozen: false,
             locked: false,
             offline: false,
-            jobserver: unsafe {
-                if GLOBAL_JOBSERVER.is_null() {
-                    None
-                } else {
-                    Some((*GLOBAL_JOBSERVER).clone())
-                }
+            jobserver: jobserver.client.as_ref().ok().cloned(),
+            jobserver_env: match (&jobserver.client, &jobserver.var) {
+                (Ok(_), Some((name, _))) => Some(*name),
+                _ => None,
             },
+            jobserver_error,
             unstable_flags: CliUnstable::default(),
             unstable_flags_cli: None,
             easy: LazyCell::new(),
@@ -281,17 +302,36 @@ impl Config {
 
     /// Gets the Cargo registry index directory (`<cargo_home>/registry/index`).
     pub fn registry_index_path(&self) -> Filesystem {
-        self.home_path.join("registry").join("index")
+        self.registry_path().join("index")
     }
 
     /// Gets the Cargo registry cache directory (`<cargo_home>/registry/path`).
     pub fn registry_cache_path(&self) -> Filesystem {
-        self.home_path.join("registry").join("cache")
+        self.registry_path().join("cache")
     }
 
     /// Gets the Cargo registry source directory (`<cargo_home>/registry/src`).
     pub fn registry_source_path(&self) -> Filesystem {
-        self.home_path.join("registry").join("src")
+        self.registry_path().join("src")
+    }
+
+    /// Gets the Cargo registry directory (`<cargo_home>/registry`), or the
+    /// overlay directory taking its place if it is read-only.
+    fn registry_path(&self) -> Filesystem {
+        match &self.registry_overlay {
+            Some(overlay) => overlay.clone(),
+            None => self.home_path.join("registry"),
+        }
+    }
+
+    /// Gets the read-only Cargo registry directory (`<cargo_home>/registry`)
+    /// if it is read-only, in which case new data is written to an overlay
+    /// directory instead. This is usually a directory shared with other users,
+    /// whose contents are used if they aren't in the overlay.
+    pub fn shared_registry_path(&self) -> Option<Filesystem> {
+        self.registry_overlay
+            .as_ref()
+            .map(|_| self.home_path.join("registry"))
     }
 
     /// Gets the default Cargo registry.
@@ -459,6 +499,24 @@ impl Config {
         }
     }
 
+    /// The directory to store incremental compilation caches in.
+    ///
+    /// Returns `None` if the user has not chosen an explicit directory, in
+    /// which case the caches live inside the target directory.
+    pub fn incremental_dir(&self) -> CargoResult<Option<Filesystem>> {
+        let val = match &self.build_config()?.incremental_dir {
+            Some(val) => val,
+            None => return Ok(None),
+        };
+        if !self.cli_unstable().incremental_dir {
+            bail!(
+                "the `build.incremental-dir` config value requires the \
+                 `-Z incremental-dir` command-line option"
+            );
+        }
+        Ok(Some(Filesystem::new(val.resolve_path(self))))
+    }
+
     /// Get a configuration value by key.
     ///
     /// This does NOT look at environment variables. See `get_cv_with_env` for
@@ -503,7 +561,13 @@ impl Config {
         self.env = env;
     }
 
-    fn get_env<T>(&self, key: &ConfigKey) -> Result<OptValue<T>, ConfigError>
+    /// Gets the environment variable `key`, as of when Cargo started, like
+    /// the environment variables of config values.
+    pub fn get_env(&self, key: &str) -> Option<&str> {
+        self.env.get(key).map(String::as_str)
+    }
+
+    fn get_config_env<T>(&self, key: &ConfigKey) -> Result<OptValue<T>, ConfigError>
     where
         T: FromStr,
         <T as FromStr>::Err: fmt::Display,
@@ -715,7 +779,7 @@ impl Config {
             self.unstable_flags_cli = Some(unstable_flags.to_vec());
         }
         if !cli_config.is_empty() {
-            self.unstable_flags.fail_if_stable_opt("--config", 6699)?;
+            self.unstable_flags.fail_if_stable_opt("--config", Some(6699))?;
             self.cli_config = Some(cli_config.iter().map(|s| s.to_string()).collect());
             self.merge_cli_args()?;
         }
@@ -765,10 +829,30 @@ impl Config {
         self.target_dir = cli_target_dir;
 
         self.load_unstable_flags_from_config()?;
+        self.registry_overlay = self.find_registry_overlay()?;
 
         Ok(())
     }
 
+    /// Returns the directory taking new registry data if the registry
+    /// directory of Cargo home is read-only, for example because it is mounted
+    /// read-only to share it between users or containers, with
+    /// `-Z registry-overlay`.
+    fn find_registry_overlay(&self) -> CargoResult<Option<Filesystem>> {
+        if !self.cli_unstable().registry_overlay {
+            return Ok(None);
+        }
+        let registry = self.home_path.join("registry").into_path_unlocked();
+        if !registry.is_dir() || paths::is_writable(&registry) {
+            return Ok(None);
+        }
+        let overlay = match self.get::<Option<ConfigRelativePath>>("registry.overlay")? {
+            Some(path) => path.resolve_path(self),
+            None => self.home_path.join("registry-overlay").into_path_unlocked(),
+        };
+        Ok(Some(Filesystem::new(overlay)))
+    }
+
     fn load_unstable_flags_from_config(&mut self) -> CargoResult<()> {
         // If nightly features are enabled, allow setting Z-flags from config
         // using the `unstable` table. Ignore that block otherwise.
@@ -804,6 +888,12 @@ impl Config {
         self.offline
     }
 
+    /// Forces offline mode, for commands which must not access the network
+    /// regardless of the command-line flags and configuration.
+    pub fn set_offline(&mut self, offline: bool) {
+        self.offline = offline;
+    }
+
     pub fn frozen(&self) -> bool {
         self.frozen
     }
@@ -1129,6 +1219,49 @@ impl Config {
         Ok(())
     }
 
+    /// Returns the string at `key` in the table of `registry`, or of crates.io
+    /// if `None`, from the credentials file. This is for commands which don't
+    /// load the whole file with `load_credentials`, but only need a token at
+    /// times.
+    pub fn credentials_value(
+        &self,
+        registry: Option<&str>,
+        key: &[&str],
+    ) -> CargoResult<Option<String>> {
+        let home_path = self.home_path.clone().into_path_unlocked();
+        let credentials = match self.get_file_path(&home_path, "credentials", true)? {
+            Some(credentials) => credentials,
+            None => return Ok(None),
+        };
+        let value = self.load_file(&credentials)?;
+        let mut path = match registry {
+            Some(registry) => vec!["registries", registry],
+            None => vec!["registry"],
+        };
+        path.extend_from_slice(key);
+        let mut value = &value;
+        for path_key in path {
+            value = match value {
+                CV::Table(map, _) => match map.get(path_key) {
+                    Some(value) => value,
+                    // Backwards compatibility for old `.cargo/credentials` layout.
+                    None if registry.is_none() && path_key == "registry" && key == ["token"] => {
+                        return Ok(map.get("token").and_then(|v| match v {
+                            CV::String(token, _) => Some(token.clone()),
+                            _ => None,
+                        }))
+                    }
+                    None => return Ok(None),
+                },
+                _ => return Ok(None),
+            };
+        }
+        match value {
+            CV::String(token, _) => Ok(Some(token.clone())),
+            _ => Ok(None),
+        }
+    }
+
     /// Looks for a path for `tool` in an environment variable or the given config, and returns
     /// `None` if it's not present.
     fn maybe_get_tool(&self, tool: &str, from_config: &Option<PathBuf>) -> Option<PathBuf> {
@@ -1163,6 +1296,18 @@ impl Config {
         self.jobserver.as_ref()
     }
 
+    /// Returns the environment variable the jobserver from the environment
+    /// was found in, like `MAKEFLAGS` or `CARGO_MAKEFLAGS`.
+    pub fn jobserver_env_var(&self) -> Option<&'static str> {
+        self.jobserver_env
+    }
+
+    /// Returns why Cargo couldn't connect to the jobserver described in its
+    /// environment, like a missing fifo or closed file descriptors.
+    pub fn jobserver_error(&self) -> Option<&str> {
+        self.jobserver_error.as_deref()
+    }
+
     pub fn http(&self) -> CargoResult<&RefCell<Easy>> {
         let http = self
             .easy
@@ -1267,7 +1412,13 @@ impl Config {
             "package cache lock is not currently held, Cargo forgot to call \
              `acquire_package_cache_lock` before we got to this stack frame",
         );
-        assert!(ret.starts_with(self.home_path.as_path_unlocked()));
+        assert!(
+            ret.starts_with(self.home_path.as_path_unlocked())
+                || matches!(
+                    &self.registry_overlay,
+                    Some(overlay) if ret.starts_with(overlay.as_path_unlocked())
+                )
+        );
         ret
     }
 
@@ -1288,6 +1439,22 @@ impl Config {
                 let path = ".package-cache";
                 let desc = "package cache";
 
+                // If the registry is read-only, nothing is written to it, and
+                // new data goes to the overlay instead, so that's what gets
+                // locked. This doesn't wait on whoever maintains the shared
+                // registry, which may take a while. If the overlay can't be
+                // written to either, a shared lock still synchronizes with
+                // whoever can, and Cargo doesn't go on without any lock.
+                if let Some(overlay) = &self.registry_overlay {
+                    let lock = match overlay.open_rw(path, self, desc) {
+                        Err(e) if maybe_readonly(&e) => overlay.open_ro(path, self, desc),
+                        lock => lock,
+                    };
+                    let lock = lock.chain_err(|| "failed to acquire package cache lock")?;
+                    *slot = Some((Some(lock), 1));
+                    return Ok(PackageCacheLock(self));
+                }
+
                 // First, attempt to open an exclusive lock which is in general
                 // the purpose of this lock!
                 //
@@ -1624,6 +1791,107 @@ pub fn save_credentials(
     cfg: &Config,
     token: Option<String>,
     registry: Option<&str>,
+) -> CargoResult<()> {
+    edit_credentials(cfg, |toml, path| {
+        if let Some(token) = token {
+            // login
+            let (key, mut value) = {
+                let key = "token".to_string();
+                let value = ConfigValue::String(token, Definition::Path(path.to_path_buf()));
+                let mut map = HashMap::new();
+                map.insert(key, value);
+                let table = CV::Table(map, Definition::Path(path.to_path_buf()));
+
+                if let Some(registry) = registry {
+                    let mut map = HashMap::new();
+                    map.insert(registry.to_string(), table);
+                    (
+                        "registries".into(),
+                        CV::Table(map, Definition::Path(path.to_path_buf())),
+                    )
+                } else {
+                    ("registry".into(), table)
+                }
+            };
+
+            if registry.is_some() {
+                if let Some(table) = toml.as_table_mut().unwrap().remove("registries") {
+                    let v = CV::from_toml(Definition::Path(path.to_path_buf()), table)?;
+                    value.merge(v, false)?;
+                }
+            }
+            toml.as_table_mut().unwrap().insert(key, value.into_toml());
+        } else {
+            // logout
+            let table = toml.as_table_mut().unwrap();
+            if let Some(registry) = registry {
+                if let Some(registries) = table.get_mut("registries") {
+                    if let Some(reg) = registries.get_mut(registry) {
+                        let rtable = reg.as_table_mut().ok_or_else(|| {
+                            format_err!("expected `[registries.{}]` to be a table", registry)
+                        })?;
+                        rtable.remove("token");
+                    }
+                }
+            } else {
+                if let Some(registry) = table.get_mut("registry") {
+                    let reg_table = registry
+                        .as_table_mut()
+                        .ok_or_else(|| format_err!("expected `[registry]` to be a table"))?;
+                    reg_table.remove("token");
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Saves the secret key of asymmetric tokens for `registry`, or of crates.io
+/// if `None`, in the `credential` table of the registry in the credentials
+/// file. The key is removed if `secret_key` is `None`.
+pub fn save_secret_key(
+    cfg: &Config,
+    secret_key: Option<String>,
+    registry: Option<&str>,
+) -> CargoResult<()> {
+    edit_credentials(cfg, |toml, _path| {
+        let table = toml.as_table_mut().unwrap();
+        let mut keys = match registry {
+            Some(registry) => vec!["registries", registry],
+            None => vec!["registry"],
+        };
+        keys.push("credential");
+        match secret_key {
+            Some(secret_key) => {
+                let mut table = table;
+                for key in keys {
+                    table = table
+                        .entry(key)
+                        .or_insert_with(|| toml::Value::Table(Default::default()))
+                        .as_table_mut()
+                        .ok_or_else(|| format_err!("expected `{}` to be a table", key))?;
+                }
+                table.insert("secret-key".into(), toml::Value::String(secret_key));
+            }
+            None => {
+                let mut value = table.get_mut(keys[0]);
+                for key in &keys[1..] {
+                    value = value.and_then(|v| v.get_mut(key));
+                }
+                if let Some(table) = value.and_then(|v| v.as_table_mut()) {
+                    table.remove("secret-key");
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Runs `edit` on the contents of the credentials file, and saves them back.
+/// `edit` is also given the path of the file.
+fn edit_credentials(
+    cfg: &Config,
+    edit: impl FnOnce(&mut toml::Value, &Path) -> CargoResult<()>,
 ) -> CargoResult<()> {
     // If 'credentials.toml' exists, we should write to that, otherwise
     // use the legacy 'credentials'. There's no need to print the warning
@@ -1662,55 +1930,7 @@ pub fn save_credentials(
             .insert("registry".into(), map.into());
     }
 
-    if let Some(token) = token {
-        // login
-        let (key, mut value) = {
-            let key = "token".to_string();
-            let value = ConfigValue::String(token, Definition::Path(file.path().to_path_buf()));
-            let mut map = HashMap::new();
-            map.insert(key, value);
-            let table = CV::Table(map, Definition::Path(file.path().to_path_buf()));
-
-            if let Some(registry) = registry {
-                let mut map = HashMap::new();
-                map.insert(registry.to_string(), table);
-                (
-                    "registries".into(),
-                    CV::Table(map, Definition::Path(file.path().to_path_buf())),
-                )
-            } else {
-                ("registry".into(), table)
-            }
-        };
-
-        if registry.is_some() {
-            if let Some(table) = toml.as_table_mut().unwrap().remove("registries") {
-                let v = CV::from_toml(Definition::Path(file.path().to_path_buf()), table)?;
-                value.merge(v, false)?;
-            }
-        }
-        toml.as_table_mut().unwrap().insert(key, value.into_toml());
-    } else {
-        // logout
-        let table = toml.as_table_mut().unwrap();
-        if let Some(registry) = registry {
-            if let Some(registries) = table.get_mut("registries") {
-                if let Some(reg) = registries.get_mut(registry) {
-                    let rtable = reg.as_table_mut().ok_or_else(|| {
-                        format_err!("expected `[registries.{}]` to be a table", registry)
-                    })?;
-                    rtable.remove("token");
-                }
-            }
-        } else {
-            if let Some(registry) = table.get_mut("registry") {
-                let reg_table = registry
-                    .as_table_mut()
-                    .ok_or_else(|| format_err!("expected `[registry]` to be a table"))?;
-                reg_table.remove("token");
-            }
-        }
-    }
+    edit(&mut toml, file.path())?;
 
     let contents = toml.to_string();
     file.seek(SeekFrom::Start(0))?;
@@ -1798,6 +2018,13 @@ pub struct CargoNetConfig {
     pub retry: Option<u32>,
     pub offline: Option<bool>,
     pub git_fetch_with_cli: Option<bool>,
+    pub ssh: Option<CargoSshConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CargoSshConfig {
+    pub known_hosts: Option<Vec<String>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -1807,6 +2034,7 @@ pub struct CargoBuildConfig {
     pub dep_info_basedir: Option<ConfigRelativePath>,
     pub target_dir: Option<ConfigRelativePath>,
     pub incremental: Option<bool>,
+    pub incremental_dir: Option<ConfigRelativePath>,
     pub target: Option<ConfigRelativePath>,
     pub jobs: Option<u32>,
     pub rustflags: Option<StringList>,
@@ -1816,6 +2044,8 @@ pub struct CargoBuildConfig {
     pub rustc: Option<PathBuf>,
     pub rustdoc: Option<PathBuf>,
     pub out_dir: Option<ConfigRelativePath>,
+    pub incompatible_rust_version: Option<String>,
+    pub package_targets: Option<HashMap<String, ConfigRelativePath>>,
 }
 
 #[derive(Deserialize, Default)]
@@ -28,6 +28,9 @@ pub struct TargetConfig {
     pub rustflags: OptValue<StringList>,
     /// The path of the linker for this target.
     pub linker: OptValue<ConfigRelativePath>,
+    /// Whether `cargo test` runs the doc tests for this target through its
+    /// runner when cross-compiling.
+    pub run_doctests: OptValue<bool>,
     /// Build script override for the given library name.
     ///
     /// Any package with a `links` value for the given library name will skip
@@ -74,6 +77,7 @@ pub(super) fn load_target_triple(config: &Config, triple: &str) -> CargoResult<T
     let runner: OptValue<PathAndArgs> = config.get(&format!("target.{}.runner", triple))?;
     let rustflags: OptValue<StringList> = config.get(&format!("target.{}.rustflags", triple))?;
     let linker: OptValue<ConfigRelativePath> = config.get(&format!("target.{}.linker", triple))?;
+    let run_doctests: OptValue<bool> = config.get(&format!("target.{}.run-doctests", triple))?;
     // Links do not support environment variables.
     let target_key = ConfigKey::from_str(&format!("target.{}", triple));
     let links_overrides = match config.get_table(&target_key)? {
@@ -84,6 +88,7 @@ pub(super) fn load_target_triple(config: &Config, triple: &str) -> CargoResult<T
         runner,
         rustflags,
         linker,
+        run_doctests,
         links_overrides,
     })
 }
@@ -100,7 +105,7 @@ fn parse_links_overrides(
         // Skip these keys, it shares the namespace with `TargetConfig`.
         match lib_name.as_str() {
             // `ar` is a historical thing.
-            "ar" | "linker" | "runner" | "rustflags" => continue,
+            "ar" | "linker" | "run-doctests" | "runner" | "rustflags" => continue,
             _ => {}
         }
         let mut output = BuildOutput::default();
@@ -0,0 +1,156 @@
+//! Signatures of published `.crate` files, with `-Z crate-signing`.
+//!
+//! When a `signing-key` is configured for a registry, `cargo publish` signs
+//! the checksum of the `.crate` file it uploads, and the registry lists the
+//! signature in the `sig` field of the index entry. Cargo checks the
+//! signatures of the crates it downloads from registries which have
+//! `trusted-keys`, and refuses the crates which aren't signed by one of them.
+//!
+//! A signature is a [PASETO] `v4.public` token, whose message is a JSON
+//! object with the `name`, `vers` and `cksum` of the package, and the version
+//! `v` of the format, and whose footer is a JSON object with the `kip` of the
+//! key.
+//!
+//! The archives of prebuilt binaries of `-Z prebuilt-install` are signed the
+//! same way, but their message also has the `target` triple they were built
+//! for, so that a signature of a `.crate` file or of the binaries of another
+//! target can't be passed off as theirs.
+//!
+//! [PASETO]: https://github.com/paseto-standard/paseto-spec
+
+use anyhow::bail;
+use serde::Deserialize;
+
+use crate::core::PackageId;
+use crate::util::errors::{CargoResult, CargoResultExt};
+use crate::util::paseto::{PublicKey, SecretKey};
+
+/// The version of the message format.
+const SIGNATURE_VERSION: u32 = 1;
+
+#[derive(Deserialize)]
+struct Message {
+    name: String,
+    vers: String,
+    cksum: String,
+    #[serde(default)]
+    target: Option<String>,
+    v: u32,
+}
+
+/// Signs the `.crate` file of version `vers` of the package `name`, whose
+/// SHA-256 checksum is `cksum`.
+pub fn sign(key: &SecretKey, name: &str, vers: &str, cksum: &str) -> String {
+    sign_message(
+        key,
+        serde_json::json!({
+            "name": name,
+            "vers": vers,
+            "cksum": cksum,
+            "v": SIGNATURE_VERSION,
+        }),
+    )
+}
+
+/// Signs the archive of the binaries of version `vers` of the package `name`
+/// prebuilt for `target`, whose SHA-256 checksum is `cksum`.
+pub fn sign_prebuilt(key: &SecretKey, name: &str, vers: &str, target: &str, cksum: &str) -> String {
+    sign_message(
+        key,
+        serde_json::json!({
+            "name": name,
+            "vers": vers,
+            "target": target,
+            "cksum": cksum,
+            "v": SIGNATURE_VERSION,
+        }),
+    )
+}
+
+fn sign_message(key: &SecretKey, message: serde_json::Value) -> String {
+    let footer = serde_json::json!({ "kip": key.public_id() });
+    key.sign(
+        message.to_string().as_bytes(),
+        footer.to_string().as_bytes(),
+    )
+}
+
+/// Checks that `signature` was made by one of the `trusted_keys`, given as
+/// `k4.public.` PASERKs, for the `.crate` file of `pkg` with the checksum
+/// `cksum`.
+pub fn verify(
+    signature: Option<&str>,
+    trusted_keys: &[String],
+    pkg: PackageId,
+    cksum: &str,
+) -> CargoResult<()> {
+    verify_message(signature, trusted_keys, pkg, None, cksum)
+}
+
+/// Checks that `signature` was made by one of the `trusted_keys` for the
+/// archive of the binaries of `pkg` prebuilt for `target`, with the checksum
+/// `cksum`.
+pub fn verify_prebuilt(
+    signature: Option<&str>,
+    trusted_keys: &[String],
+    pkg: PackageId,
+    target: &str,
+    cksum: &str,
+) -> CargoResult<()> {
+    verify_message(signature, trusted_keys, pkg, Some(target), cksum)
+}
+
+fn verify_message(
+    signature: Option<&str>,
+    trusted_keys: &[String],
+    pkg: PackageId,
+    target: Option<&str>,
+    cksum: &str,
+) -> CargoResult<()> {
+    let signature = match signature {
+        Some(signature) => signature,
+        None => bail!("the registry has no signature for it"),
+    };
+    for key in trusted_keys {
+        let key = PublicKey::from_paserk(key)
+            .chain_err(|| format!("the trusted key `{}` is invalid", key))?;
+        let message = match key.verify(signature) {
+            Ok((message, _footer)) => message,
+            Err(_) => continue,
+        };
+        let message: Message =
+            serde_json::from_slice(&message).chain_err(|| "the signed message is invalid")?;
+        if message.v != SIGNATURE_VERSION {
+            bail!("the signature has unsupported version {}", message.v);
+        }
+        if message.target.as_deref() != target {
+            match &message.target {
+                Some(signed) => bail!(
+                    "the signature is for the binaries of `{} v{}` prebuilt for `{}`",
+                    message.name,
+                    message.vers,
+                    signed
+                ),
+                None => bail!(
+                    "the signature is for the `.crate` file of `{} v{}`",
+                    message.name,
+                    message.vers
+                ),
+            }
+        }
+        if message.name != pkg.name().as_str()
+            || message.vers != pkg.version().to_string()
+            || message.cksum != cksum
+        {
+            bail!(
+                "the signature is for `{} v{}` with checksum {}, not for checksum {}",
+                message.name,
+                message.vers,
+                message.cksum,
+                cksum
+            );
+        }
+        return Ok(());
+    }
+    bail!("it isn't signed by any of the trusted keys of the registry")
+}
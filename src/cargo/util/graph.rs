@@ -89,17 +89,19 @@ impl<N: Eq + Ord + Clone, E: Default + Clone> Graph<N, E> {
 
     /// Resolves one of the paths from the given dependent package down to
     /// a leaf.
-    pub fn path_to_bottom<'a>(&'a self, mut pkg: &'a N) -> Vec<&'a N> {
-        let mut result = vec![pkg];
+    ///
+    /// Each element contains a node along with the edge from the previous
+    /// node, so the first element never has an edge.
+    pub fn path_to_bottom<'a>(&'a self, mut pkg: &'a N) -> Vec<(&'a N, Option<&'a E>)> {
+        let mut result = vec![(pkg, None)];
         while let Some(p) = self.nodes.get(pkg).and_then(|p| {
             p.iter()
                 // Note that we can have "cycles" introduced through dev-dependency
                 // edges, so make sure we don't loop infinitely.
-                .find(|(node, _)| !result.contains(node))
-                .map(|(p, _)| p)
+                .find(|(node, _)| !result.iter().any(|(n, _)| n == node))
         }) {
-            result.push(p);
-            pkg = p;
+            result.push((p.0, Some(p.1)));
+            pkg = p.0;
         }
         result
     }
@@ -101,3 +101,144 @@ impl Message for BuildFinished {
         "build-finished"
     }
 }
+
+#[derive(Serialize)]
+pub struct TestMessage<'a> {
+    pub package_id: PackageId,
+    pub target: &'a Target,
+    /// The test binary, or `None` for doctests.
+    pub executable: Option<&'a Path>,
+    pub doctest: bool,
+    pub event: Box<RawValue>,
+}
+
+impl<'a> Message for TestMessage<'a> {
+    fn reason(&self) -> &str {
+        "test-message"
+    }
+}
+
+/// The result of a benchmark, from libtest or from a bench target with
+/// `harness = false` which prints its results for `cargo bench --format json`.
+#[derive(Serialize)]
+pub struct BenchResult<'a> {
+    pub package_id: PackageId,
+    pub target: &'a Target,
+    pub executable: &'a Path,
+    pub name: &'a str,
+    /// The median time of an iteration, in nanoseconds.
+    pub median: f64,
+    /// The deviation of the time of an iteration, in nanoseconds.
+    pub deviation: f64,
+    /// The number of iterations, if the harness reports it.
+    pub iterations: Option<u64>,
+    /// The throughput, if the benchmark sets the bytes it processes.
+    pub mib_per_second: Option<f64>,
+}
+
+impl<'a> Message for BenchResult<'a> {
+    fn reason(&self) -> &str {
+        "bench-result"
+    }
+}
+
+/// A test binary which wasn't run, since it passed with the same inputs
+/// before, with `-Z test-cache`.
+#[derive(Serialize)]
+pub struct TestCached<'a> {
+    pub package_id: PackageId,
+    pub target: &'a Target,
+    pub executable: &'a Path,
+}
+
+impl<'a> Message for TestCached<'a> {
+    fn reason(&self) -> &str {
+        "test-cached"
+    }
+}
+
+/// A test which failed, then passed when it was run again for `--retries`.
+#[derive(Serialize)]
+pub struct TestFlaky<'a> {
+    pub package_id: PackageId,
+    pub target: &'a Target,
+    pub name: &'a str,
+    /// The number of times the test ran, including the one it passed.
+    pub attempts: u32,
+}
+
+impl<'a> Message for TestFlaky<'a> {
+    fn reason(&self) -> &str {
+        "test-flaky"
+    }
+}
+
+#[derive(Serialize)]
+pub struct TestFinished<'a> {
+    pub success: bool,
+    pub passed: u64,
+    pub failed: u64,
+    pub ignored: u64,
+    pub measured: u64,
+    pub filtered_out: u64,
+    /// The tests counted as passed which first failed, with `--retries`.
+    pub flaky: u64,
+    /// The test targets which failed, with `--no-fail-fast`.
+    pub failures: Vec<TestFailure<'a>>,
+}
+
+/// A test target which failed, in the summary of `--no-fail-fast`.
+#[derive(Serialize)]
+pub struct TestFailure<'a> {
+    pub package_id: PackageId,
+    pub target: &'a Target,
+    /// The test binary, or `None` for doctests.
+    pub executable: Option<&'a Path>,
+    pub doctest: bool,
+    /// The names of the tests which failed, if the test harness reported
+    /// them.
+    pub tests: &'a [String],
+    /// The command to run the failed tests again.
+    pub rerun: String,
+}
+
+impl<'a> Message for TestFinished<'a> {
+    fn reason(&self) -> &str {
+        "test-finished"
+    }
+}
+
+/// The tests of all the test targets, with `cargo test --list`.
+#[derive(Serialize)]
+pub struct TestList<'a> {
+    pub packages: Vec<TestListPackage<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct TestListPackage<'a> {
+    pub package_id: PackageId,
+    pub targets: Vec<TestListTarget<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct TestListTarget<'a> {
+    pub target: &'a Target,
+    /// The test binary, or `None` for doctests.
+    pub executable: Option<&'a Path>,
+    pub doctest: bool,
+    /// The tests of the target, or `None` if it has no test harness to list
+    /// them.
+    pub tests: Option<Vec<ListedTest>>,
+}
+
+#[derive(Serialize)]
+pub struct ListedTest {
+    pub name: String,
+    pub ignored: bool,
+}
+
+impl<'a> Message for TestList<'a> {
+    fn reason(&self) -> &str {
+        "test-list"
+    }
+}
@@ -33,8 +33,10 @@ pub use self::workspace::{
 
 mod canonical_url;
 pub mod command_prelude;
+pub mod compression;
 pub mod config;
 pub mod cpu;
+pub mod crate_signature;
 mod dependency_queue;
 pub mod diagnostic_server;
 pub mod errors;
@@ -51,6 +53,7 @@ pub mod lev_distance;
 mod lockserver;
 pub mod machine_message;
 pub mod network;
+pub mod paseto;
 pub mod paths;
 pub mod process_builder;
 pub mod profile;
@@ -59,6 +62,7 @@ mod queue;
 mod read2;
 pub mod restricted_names;
 pub mod rustc;
+pub mod sandbox;
 mod sha256;
 pub mod to_semver;
 pub mod toml;
@@ -34,6 +34,8 @@ impl<'a> Retry<'a> {
 }
 
 fn maybe_spurious(err: &Error) -> bool {
+    use gix::protocol::transport::IsSpuriousError;
+
     if let Some(git_err) = err.downcast_ref::<git2::Error>() {
         match git_err.class() {
             git2::ErrorClass::Net
@@ -57,6 +59,15 @@ fn maybe_spurious(err: &Error) -> bool {
             return true;
         }
     }
+    if let Some(oxide_err) = err.downcast_ref::<gix::remote::connect::Error>() {
+        return oxide_err.is_spurious();
+    }
+    if let Some(oxide_err) = err.downcast_ref::<gix::remote::fetch::prepare::Error>() {
+        return oxide_err.is_spurious();
+    }
+    if let Some(oxide_err) = err.downcast_ref::<gix::remote::fetch::Error>() {
+        return oxide_err.is_spurious();
+    }
     if let Some(not_200) = err.downcast_ref::<HttpNot200>() {
         if 500 <= not_200.code && not_200.code < 600 {
             return true;
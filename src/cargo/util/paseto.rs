@@ -0,0 +1,182 @@
+//! Signing of [PASETO] `v4.public` tokens, and the [PASERK] serialization of
+//! their Ed25519 keys.
+//!
+//! These are used for asymmetric registry tokens, where Cargo keeps a secret
+//! key and signs each request with it, and the registry only knows the public
+//! key. They also sign published `.crate` files, see `crate_signature`.
+//!
+//! [PASETO]: https://github.com/paseto-standard/paseto-spec
+//! [PASERK]: https://github.com/paseto-standard/paserk
+
+use std::convert::TryInto;
+use std::fmt;
+
+use anyhow::{bail, format_err};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use blake2::digest::{Update, VariableOutput};
+use blake2::Blake2bVar;
+use ed25519_dalek::{
+    Signature, Signer, SigningKey, VerifyingKey, KEYPAIR_LENGTH, PUBLIC_KEY_LENGTH,
+    SECRET_KEY_LENGTH, SIGNATURE_LENGTH,
+};
+
+use crate::util::errors::CargoResult;
+
+const SECRET_PREFIX: &str = "k4.secret.";
+const PUBLIC_PREFIX: &str = "k4.public.";
+const PID_PREFIX: &str = "k4.pid.";
+const TOKEN_HEADER: &str = "v4.public.";
+
+/// An Ed25519 secret key, which signs `v4.public` tokens.
+pub struct SecretKey(SigningKey);
+
+impl SecretKey {
+    /// Generates a new random key.
+    pub fn generate() -> CargoResult<SecretKey> {
+        let mut seed = [0; SECRET_KEY_LENGTH];
+        getrandom::getrandom(&mut seed)
+            .map_err(|e| format_err!("failed to generate a secret key: {}", e))?;
+        Ok(SecretKey(SigningKey::from_bytes(&seed)))
+    }
+
+    /// Parses a key serialized as a `k4.secret.` PASERK.
+    pub fn from_paserk(paserk: &str) -> CargoResult<SecretKey> {
+        let data = match paserk.strip_prefix(SECRET_PREFIX) {
+            Some(data) => data,
+            None => bail!("secret keys must start with `{}`", SECRET_PREFIX),
+        };
+        let bytes = URL_SAFE_NO_PAD
+            .decode(data)
+            .map_err(|_| format_err!("the secret key isn't valid base64"))?;
+        let bytes: [u8; KEYPAIR_LENGTH] = match bytes.try_into() {
+            Ok(bytes) => bytes,
+            Err(_) => bail!("the secret key must be {} bytes long", KEYPAIR_LENGTH),
+        };
+        let key = SigningKey::from_keypair_bytes(&bytes)
+            .map_err(|_| format_err!("the public half of the secret key is invalid"))?;
+        Ok(SecretKey(key))
+    }
+
+    /// Serializes the key as a `k4.secret.` PASERK.
+    pub fn to_paserk(&self) -> String {
+        format!(
+            "{}{}",
+            SECRET_PREFIX,
+            URL_SAFE_NO_PAD.encode(self.0.to_keypair_bytes())
+        )
+    }
+
+    /// Returns the public key as a `k4.public.` PASERK, which is what
+    /// registries are given to check the signatures.
+    pub fn public_paserk(&self) -> String {
+        format!(
+            "{}{}",
+            PUBLIC_PREFIX,
+            URL_SAFE_NO_PAD.encode(self.0.verifying_key().as_bytes())
+        )
+    }
+
+    /// Returns the `k4.pid.` PASERK identifying the public key.
+    pub fn public_id(&self) -> String {
+        let mut hasher = Blake2bVar::new(33).unwrap();
+        hasher.update(PID_PREFIX.as_bytes());
+        hasher.update(self.public_paserk().as_bytes());
+        let mut id = [0; 33];
+        hasher.finalize_variable(&mut id).unwrap();
+        format!("{}{}", PID_PREFIX, URL_SAFE_NO_PAD.encode(id))
+    }
+
+    /// Signs `message` into a `v4.public` token, with `footer` in clear after
+    /// it.
+    pub fn sign(&self, message: &[u8], footer: &[u8]) -> String {
+        let signed = pae(&[TOKEN_HEADER.as_bytes(), message, footer, b""]);
+        let signature = self.0.sign(&signed);
+        let mut body = message.to_vec();
+        body.extend_from_slice(&signature.to_bytes());
+        let mut token = format!("{}{}", TOKEN_HEADER, URL_SAFE_NO_PAD.encode(body));
+        if !footer.is_empty() {
+            token.push('.');
+            token.push_str(&URL_SAFE_NO_PAD.encode(footer));
+        }
+        token
+    }
+}
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SecretKey")
+            .field(&self.public_paserk())
+            .finish()
+    }
+}
+
+/// An Ed25519 public key, which checks `v4.public` tokens.
+pub struct PublicKey(VerifyingKey);
+
+impl PublicKey {
+    /// Parses a key serialized as a `k4.public.` PASERK.
+    pub fn from_paserk(paserk: &str) -> CargoResult<PublicKey> {
+        let data = match paserk.strip_prefix(PUBLIC_PREFIX) {
+            Some(data) => data,
+            None => bail!("public keys must start with `{}`", PUBLIC_PREFIX),
+        };
+        let bytes = URL_SAFE_NO_PAD
+            .decode(data)
+            .map_err(|_| format_err!("the public key isn't valid base64"))?;
+        let bytes: [u8; PUBLIC_KEY_LENGTH] = match bytes.try_into() {
+            Ok(bytes) => bytes,
+            Err(_) => bail!("the public key must be {} bytes long", PUBLIC_KEY_LENGTH),
+        };
+        let key = VerifyingKey::from_bytes(&bytes)
+            .map_err(|_| format_err!("the public key is invalid"))?;
+        Ok(PublicKey(key))
+    }
+
+    /// Checks that the `v4.public` token `token` was signed by this key, and
+    /// returns its message and footer.
+    pub fn verify(&self, token: &str) -> CargoResult<(Vec<u8>, Vec<u8>)> {
+        let rest = match token.strip_prefix(TOKEN_HEADER) {
+            Some(rest) => rest,
+            None => bail!("tokens must start with `{}`", TOKEN_HEADER),
+        };
+        let (body, footer) = match rest.split_once('.') {
+            Some((body, footer)) => (body, footer),
+            None => (rest, ""),
+        };
+        let decode = |data: &str| {
+            URL_SAFE_NO_PAD
+                .decode(data)
+                .map_err(|_| format_err!("the token isn't valid base64"))
+        };
+        let body = decode(body)?;
+        let footer = decode(footer)?;
+        if body.len() < SIGNATURE_LENGTH {
+            bail!("the token is too short");
+        }
+        let (message, signature) = body.split_at(body.len() - SIGNATURE_LENGTH);
+        let signature = Signature::from_slice(signature)?;
+        let signed = pae(&[TOKEN_HEADER.as_bytes(), message, &footer, b""]);
+        self.0
+            .verify_strict(&signed, &signature)
+            .map_err(|_| format_err!("the signature of the token is invalid"))?;
+        Ok((message.to_vec(), footer))
+    }
+}
+
+/// Pre-authentication encoding, which makes the concatenation of `pieces`
+/// unambiguous.
+fn pae(pieces: &[&[u8]]) -> Vec<u8> {
+    // The most significant bit is cleared for interoperability with languages
+    // without unsigned integers.
+    fn le64(n: usize) -> [u8; 8] {
+        ((n as u64) & (u64::MAX >> 1)).to_le_bytes()
+    }
+
+    let mut out = le64(pieces.len()).to_vec();
+    for piece in pieces {
+        out.extend_from_slice(&le64(piece.len()));
+        out.extend_from_slice(piece);
+    }
+    out
+}
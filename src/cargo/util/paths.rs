@@ -276,6 +276,27 @@ pub fn set_invocation_time(path: &Path) -> CargoResult<FileTime> {
     Ok(ft)
 }
 
+/// Returns whether the current user may write to `path`, which is not the
+/// case if its permissions forbid it, or it is on a read-only filesystem.
+pub fn is_writable(path: &Path) -> bool {
+    match path.metadata() {
+        Ok(meta) if !meta.permissions().readonly() => {}
+        _ => return false,
+    }
+    #[cfg(unix)]
+    {
+        use std::ffi::CString;
+        use std::os::unix::prelude::*;
+        let path = match CString::new(path.as_os_str().as_bytes()) {
+            Ok(path) => path,
+            Err(_) => return false,
+        };
+        unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 }
+    }
+    #[cfg(not(unix))]
+    true
+}
+
 #[cfg(unix)]
 pub fn path2bytes(path: &Path) -> CargoResult<&[u8]> {
     use std::os::unix::prelude::*;
@@ -1,3 +1,4 @@
+use crate::util::sandbox::{self, DeniedWrites, Sandbox};
 use crate::util::{process_error, read2, CargoResult, CargoResultExt};
 use anyhow::bail;
 use jobserver::Client;
@@ -7,7 +8,7 @@ use std::env;
 use std::ffi::{OsStr, OsString};
 use std::fmt;
 use std::iter::once;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Output, Stdio};
 
 /// A builder object for an external process, similar to `std::process::Command`.
@@ -26,8 +27,12 @@ pub struct ProcessBuilder {
     ///
     /// [jobserver_docs]: https://docs.rs/jobserver/0.1.6/jobserver/
     jobserver: Option<Client>,
+    /// `true` to also append `jobserver` to `MAKEFLAGS` and `MFLAGS`.
+    jobserver_make: bool,
     /// `true` to include environment variable in display.
     display_env_vars: bool,
+    /// The restrictions of the process.
+    sandbox: Sandbox,
 }
 
 impl fmt::Display for ProcessBuilder {
@@ -142,12 +147,45 @@ impl ProcessBuilder {
         self
     }
 
+    /// Sets the `make` jobserver like `inherit_jobserver`, and also appends
+    /// it to the `MAKEFLAGS` and `MFLAGS` environment variables, so that a
+    /// `make` run by the process uses it too.
+    pub fn inherit_jobserver_for_make(&mut self, jobserver: &Client) -> &mut Self {
+        self.jobserver = Some(jobserver.clone());
+        self.jobserver_make = true;
+        self
+    }
+
     /// Enables environment variable display.
     pub fn display_env_vars(&mut self) -> &mut Self {
         self.display_env_vars = true;
         self
     }
 
+    /// Denies network access to the process, and to the processes it runs.
+    /// See `sandbox::check_network_sandbox_supported` for the platforms this
+    /// works on.
+    pub fn deny_network(&mut self) -> &mut Self {
+        self.sandbox.deny_network = true;
+        self
+    }
+
+    /// Only allows the process, and the processes it runs, to write to
+    /// `paths` and what they contain. See
+    /// `sandbox::check_filesystem_sandbox_supported` for the platforms this
+    /// works on.
+    pub fn restrict_writes(&mut self, paths: Vec<PathBuf>) -> &mut Self {
+        self.sandbox.writable_paths = Some(paths);
+        self
+    }
+
+    /// Gets the writes the process and the processes it ran were denied, if
+    /// they are restricted.
+    pub fn get_denied_writes(&self) -> Option<&DeniedWrites> {
+        self.sandbox.writable_paths.as_ref()?;
+        Some(&self.sandbox.denied_writes)
+    }
+
     /// Runs the process, waiting for completion, and mapping non-success exit codes to an error.
     pub fn exec(&self) -> CargoResult<()> {
         let mut command = self.build_command();
@@ -305,7 +343,11 @@ impl ProcessBuilder {
     /// Converts `ProcessBuilder` into a `std::process::Command`, and handles the jobserver, if
     /// present.
     pub fn build_command(&self) -> Command {
-        let mut command = Command::new(&self.program);
+        let mut command = if self.sandbox.is_enabled() {
+            sandbox::sandboxed_command(&self.program, &self.sandbox)
+        } else {
+            Command::new(&self.program)
+        };
         if let Some(cwd) = self.get_cwd() {
             command.current_dir(cwd);
         }
@@ -324,10 +366,39 @@ impl ProcessBuilder {
         }
         if let Some(ref c) = self.jobserver {
             c.configure(&mut command);
+            if self.jobserver_make {
+                self.append_jobserver_for_make(&mut command);
+            }
         }
         command
     }
 
+    /// Appends the jobserver that `configure` put in `CARGO_MAKEFLAGS` to
+    /// `MAKEFLAGS` and `MFLAGS`, keeping the flags already in them. `make`
+    /// uses the last jobserver of its flags.
+    fn append_jobserver_for_make(&self, command: &mut Command) {
+        let jobserver = command
+            .get_envs()
+            .find(|(k, _)| *k == "CARGO_MAKEFLAGS")
+            .and_then(|(_, v)| v)
+            .map(|v| v.to_os_string());
+        let jobserver = match jobserver {
+            Some(jobserver) => jobserver,
+            None => return,
+        };
+        for var in &["MAKEFLAGS", "MFLAGS"] {
+            let value = match self.get_env(var) {
+                Some(mut flags) if !flags.is_empty() => {
+                    flags.push(" ");
+                    flags.push(&jobserver);
+                    flags
+                }
+                _ => jobserver.clone(),
+            };
+            command.env(var, value);
+        }
+    }
+
     /// Wraps an existing command with the provided wrapper, if it is present and valid.
     ///
     /// # Examples
@@ -368,7 +439,9 @@ pub fn process<T: AsRef<OsStr>>(cmd: T) -> ProcessBuilder {
         cwd: None,
         env: BTreeMap::new(),
         jobserver: None,
+        jobserver_make: false,
         display_env_vars: false,
+        sandbox: Sandbox::default(),
     }
 }
 
@@ -0,0 +1,878 @@
+//! Sandboxing the processes Cargo spawns, for `-Z network-sandbox` and
+//! `-Z build-script-sandbox`.
+//!
+//! On Linux, the restrictions are installed in the child process before it
+//! executes the program, and are inherited by the processes it spawns in
+//! turn, without a way to remove them:
+//!
+//! * Network access is denied by a seccomp filter making the creation of IPv4
+//!   and IPv6 sockets fail with `EACCES`. Unix domain sockets, used by the
+//!   jobserver of some `make` implementations, are still allowed. `io_uring`,
+//!   which can create sockets without the `socket` system call, is denied too.
+//! * Writes are restricted with a Landlock ruleset, which makes creating,
+//!   modifying and removing files outside of the writable paths fail with
+//!   `EACCES`. Reading files isn't restricted.
+//! * Landlock doesn't tell which writes it denied, so the system calls
+//!   writing to paths are also reported to Cargo by a seccomp filter, before
+//!   they run. A thread of Cargo records those outside of the writable paths,
+//!   and lets them continue to Landlock.
+//!
+//! On macOS, the program is run by `sandbox-exec`, with a profile denying the
+//! same accesses. The denied writes are found in the system log.
+//!
+//! Other platforms aren't supported, so builds which ask for a sandbox fail
+//! there rather than running without it.
+
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::util::errors::CargoResult;
+
+/// The restrictions of a sandboxed process.
+#[derive(Clone, Debug, Default)]
+pub struct Sandbox {
+    /// Whether network access is denied.
+    pub deny_network: bool,
+    /// If set, the only paths the process may write to, along with what they
+    /// contain.
+    pub writable_paths: Option<Vec<PathBuf>>,
+    /// The writes which were denied, shared by the clones of the sandbox.
+    pub denied_writes: DeniedWrites,
+}
+
+impl Sandbox {
+    pub fn is_enabled(&self) -> bool {
+        self.deny_network || self.writable_paths.is_some()
+    }
+}
+
+/// The paths the processes of a sandbox weren't allowed to write to.
+#[derive(Clone, Debug, Default)]
+pub struct DeniedWrites {
+    paths: Arc<Mutex<BTreeSet<PathBuf>>>,
+    /// When the first process of the sandbox was started.
+    started: Arc<Mutex<Option<Instant>>>,
+}
+
+impl DeniedWrites {
+    /// Returns the paths recorded as the processes ran. On Linux, these are
+    /// all the denied writes of the processes which exited.
+    pub fn recorded(&self) -> Vec<PathBuf> {
+        self.paths.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Returns the denied writes, searching the system log for them on the
+    /// platforms where they aren't recorded as the processes run. This can
+    /// take a while, and the log may list the denied writes of other
+    /// sandboxed processes running at the same time.
+    pub fn search(&self) -> Vec<PathBuf> {
+        if imp::RECORDS_DENIED_WRITES {
+            return self.recorded();
+        }
+        match *self.started.lock().unwrap() {
+            Some(started) => imp::search_denied_writes(started),
+            None => Vec::new(),
+        }
+    }
+
+    fn insert(&self, path: PathBuf) {
+        self.paths.lock().unwrap().insert(path);
+    }
+
+    fn start(&self) {
+        self.started
+            .lock()
+            .unwrap()
+            .get_or_insert_with(Instant::now);
+    }
+}
+
+/// Checks that processes can be denied network access on this platform.
+pub fn check_network_sandbox_supported() -> CargoResult<()> {
+    if imp::SUPPORTED {
+        Ok(())
+    } else {
+        anyhow::bail!(
+            "denying network access to build scripts and procedural macros isn't \
+             supported on this platform"
+        )
+    }
+}
+
+/// Checks that the writes of processes can be restricted on this platform.
+pub fn check_filesystem_sandbox_supported() -> CargoResult<()> {
+    if imp::SUPPORTED && imp::filesystem_sandbox_supported() {
+        Ok(())
+    } else {
+        anyhow::bail!(
+            "restricting the writes of build scripts isn't supported on this platform{}",
+            if cfg!(target_os = "linux") {
+                ", the kernel doesn't support Landlock or has it disabled"
+            } else {
+                ""
+            }
+        )
+    }
+}
+
+/// Returns a command running `program` in `sandbox`.
+pub fn sandboxed_command(program: &OsStr, sandbox: &Sandbox) -> Command {
+    sandbox.denied_writes.start();
+    imp::sandboxed_command(program, sandbox)
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod imp {
+    use std::ffi::{CString, OsStr, OsString};
+    use std::fs::{self, File};
+    use std::io;
+    use std::mem;
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    use std::os::unix::fs::FileExt;
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+    use std::os::unix::process::CommandExt;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+    use std::ptr;
+    use std::thread;
+    use std::time::Instant;
+
+    use libc::{c_int, c_long, sock_filter, sock_fprog};
+
+    use super::{DeniedWrites, Sandbox};
+
+    pub const SUPPORTED: bool = true;
+    pub const RECORDS_DENIED_WRITES: bool = true;
+
+    /// The `AUDIT_ARCH_*` value of the system calls of this architecture.
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: u32 = 0xC000_003E;
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: u32 = 0xC000_00B7;
+
+    /// The system call numbers from `0x4000_0000` are the x32 ABI ones.
+    const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+    /// Offsets of the fields of `struct seccomp_data`. Only the low 32 bits of
+    /// the first argument are read, which are the first ones on the little
+    /// endian architectures supported here.
+    const DATA_NR: u32 = 0;
+    const DATA_ARCH: u32 = 4;
+    const DATA_ARG0: u32 = 16;
+
+    const DENY: u32 = libc::SECCOMP_RET_ERRNO | libc::EACCES as u32;
+
+    fn stmt(code: u32, k: u32) -> sock_filter {
+        jump(code, k, 0, 0)
+    }
+
+    fn jump(code: u32, k: u32, jt: u8, jf: u8) -> sock_filter {
+        sock_filter {
+            code: code as u16,
+            jt,
+            jf,
+            k,
+        }
+    }
+
+    /// The seccomp program denying the creation of internet sockets. Jumps
+    /// are relative to the next instruction.
+    fn filter() -> Vec<sock_filter> {
+        use libc::{BPF_ABS, BPF_JEQ, BPF_JGE, BPF_JMP, BPF_K, BPF_LD, BPF_RET, BPF_W};
+        vec![
+            // System calls of other architectures are denied, since their
+            // numbers are different.
+            stmt(BPF_LD | BPF_W | BPF_ABS, DATA_ARCH),
+            jump(BPF_JMP | BPF_JEQ | BPF_K, AUDIT_ARCH, 1, 0),
+            stmt(BPF_RET | BPF_K, DENY),
+            stmt(BPF_LD | BPF_W | BPF_ABS, DATA_NR),
+            jump(BPF_JMP | BPF_JGE | BPF_K, X32_SYSCALL_BIT, 5, 0),
+            jump(
+                BPF_JMP | BPF_JEQ | BPF_K,
+                libc::SYS_io_uring_setup as u32,
+                4,
+                0,
+            ),
+            jump(BPF_JMP | BPF_JEQ | BPF_K, libc::SYS_socket as u32, 0, 4),
+            stmt(BPF_LD | BPF_W | BPF_ABS, DATA_ARG0),
+            jump(BPF_JMP | BPF_JEQ | BPF_K, libc::AF_INET as u32, 1, 0),
+            jump(BPF_JMP | BPF_JEQ | BPF_K, libc::AF_INET6 as u32, 0, 1),
+            stmt(BPF_RET | BPF_K, DENY),
+            stmt(BPF_RET | BPF_K, libc::SECCOMP_RET_ALLOW),
+        ]
+    }
+
+    /// `struct landlock_ruleset_attr`, as of the first version of the ABI.
+    #[repr(C)]
+    struct RulesetAttr {
+        handled_access_fs: u64,
+    }
+
+    /// `struct landlock_path_beneath_attr`, which is packed.
+    #[repr(C, packed)]
+    struct PathBeneathAttr {
+        allowed_access: u64,
+        parent_fd: c_int,
+    }
+
+    const LANDLOCK_CREATE_RULESET_VERSION: u32 = 1;
+    const LANDLOCK_RULE_PATH_BENEATH: c_int = 1;
+
+    /// The access rights about files, which are the only ones allowed on the
+    /// rules of files rather than directories.
+    const ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+    const ACCESS_FS_TRUNCATE: u64 = 1 << 14;
+
+    /// The access rights writing to the file system, handled by the ruleset:
+    /// `WRITE_FILE`, `REMOVE_DIR`, `REMOVE_FILE` and the `MAKE_*` ones, then
+    /// `REFER` from the second version of the ABI, and `TRUNCATE` from the
+    /// third one.
+    fn write_access(abi: c_long) -> u64 {
+        let mut access = ACCESS_FS_WRITE_FILE | (0b1_1111_1111 << 4);
+        if abi >= 2 {
+            access |= 1 << 13;
+        }
+        if abi >= 3 {
+            access |= ACCESS_FS_TRUNCATE;
+        }
+        access
+    }
+
+    fn landlock_abi() -> c_long {
+        unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                ptr::null::<RulesetAttr>(),
+                0,
+                LANDLOCK_CREATE_RULESET_VERSION,
+            )
+        }
+    }
+
+    pub fn filesystem_sandbox_supported() -> bool {
+        landlock_abi() >= 1
+    }
+
+    pub fn search_denied_writes(_started: Instant) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    pub fn sandboxed_command(program: &OsStr, sandbox: &Sandbox) -> Command {
+        let mut command = Command::new(program);
+        let mut filter = if sandbox.deny_network {
+            Some(filter())
+        } else {
+            None
+        };
+        let mut monitor = match &sandbox.writable_paths {
+            Some(paths) => start_monitor(paths, &sandbox.denied_writes),
+            None => None,
+        };
+        // The paths which don't exist are left out, and the ones which
+        // aren't directories only get the rights about files.
+        let writable_paths: Option<Vec<(CString, bool)>> =
+            sandbox.writable_paths.as_ref().map(|paths| {
+                paths
+                    .iter()
+                    .filter_map(|path| {
+                        let is_dir = path.metadata().ok()?.is_dir();
+                        let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+                        Some((path, is_dir))
+                    })
+                    .collect()
+            });
+        let abi = if writable_paths.is_some() {
+            landlock_abi()
+        } else {
+            0
+        };
+        // Only system calls are made between `fork` and `exec`, everything
+        // is allocated beforehand.
+        unsafe {
+            command.pre_exec(move || {
+                // Installing a filter or a ruleset requires either
+                // `CAP_SYS_ADMIN` or giving up the privileges of set-user-ID
+                // programs.
+                if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                if let Some(paths) = &writable_paths {
+                    restrict_writes(paths, abi)?;
+                }
+                if let Some(filter) = &mut filter {
+                    let prog = sock_fprog {
+                        len: filter.len() as u16,
+                        filter: filter.as_mut_ptr(),
+                    };
+                    if libc::prctl(
+                        libc::PR_SET_SECCOMP,
+                        libc::SECCOMP_MODE_FILTER,
+                        &prog as *const sock_fprog,
+                    ) != 0
+                    {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                // The writes are still restricted if they can't be
+                // monitored, like when a sandboxed process runs Cargo, since
+                // only one listener is allowed.
+                if let Some((filter, socket)) = &mut monitor {
+                    let prog = sock_fprog {
+                        len: filter.len() as u16,
+                        filter: filter.as_mut_ptr(),
+                    };
+                    let listener = libc::syscall(
+                        libc::SYS_seccomp,
+                        SECCOMP_SET_MODE_FILTER,
+                        SECCOMP_FILTER_FLAG_NEW_LISTENER,
+                        &prog as *const sock_fprog,
+                    );
+                    if listener >= 0 {
+                        // No system call writing to a path can be made until
+                        // the listener is in Cargo's hands.
+                        send_fd(socket.as_raw_fd(), listener as c_int);
+                        libc::close(listener as c_int);
+                    }
+                }
+                Ok(())
+            });
+        }
+        command
+    }
+
+    const SECCOMP_SET_MODE_FILTER: c_long = 1;
+    const SECCOMP_FILTER_FLAG_NEW_LISTENER: c_long = 1 << 3;
+    const SECCOMP_RET_USER_NOTIF: u32 = 0x7fc0_0000;
+    const SECCOMP_USER_NOTIF_FLAG_CONTINUE: u32 = 1;
+    const SECCOMP_IOCTL_NOTIF_RECV: libc::c_ulong = 0xc050_2100;
+    const SECCOMP_IOCTL_NOTIF_SEND: libc::c_ulong = 0xc018_2101;
+    const SECCOMP_IOCTL_NOTIF_ID_VALID: libc::c_ulong = 0x4008_2102;
+
+    /// `struct seccomp_notif`.
+    #[repr(C)]
+    struct Notif {
+        id: u64,
+        pid: u32,
+        flags: u32,
+        nr: c_int,
+        arch: u32,
+        instruction_pointer: u64,
+        args: [u64; 6],
+    }
+
+    /// `struct seccomp_notif_resp`.
+    #[repr(C)]
+    struct NotifResp {
+        id: u64,
+        val: i64,
+        error: i32,
+        flags: u32,
+    }
+
+    /// The flags of `open` asking to write.
+    const OPEN_WRITE_FLAGS: u32 =
+        (libc::O_WRONLY | libc::O_RDWR | libc::O_CREAT | libc::O_TRUNC) as u32;
+
+    /// How the system calls writing to paths take them.
+    enum PathArgs {
+        /// Paths relative to the working directory, at these indices.
+        Cwd(&'static [usize]),
+        /// Pairs of the index of a directory file descriptor and of a path
+        /// relative to it.
+        At(&'static [(usize, usize)]),
+    }
+
+    /// The system calls writing to paths. Those opening files are only
+    /// reported when their flags at the given index ask to write.
+    #[rustfmt::skip]
+    fn path_syscalls() -> Vec<(c_long, Option<usize>, PathArgs)> {
+        use PathArgs::*;
+        let mut syscalls = vec![
+            (libc::SYS_openat, Some(2), At(&[(0, 1)])),
+            // The flags of `openat2` are in a structure, and are checked
+            // when it's reported.
+            (libc::SYS_openat2, None, At(&[(0, 1)])),
+            (libc::SYS_truncate, None, Cwd(&[0])),
+            (libc::SYS_mkdirat, None, At(&[(0, 1)])),
+            (libc::SYS_mknodat, None, At(&[(0, 1)])),
+            (libc::SYS_unlinkat, None, At(&[(0, 1)])),
+            (libc::SYS_renameat, None, At(&[(0, 1), (2, 3)])),
+            (libc::SYS_renameat2, None, At(&[(0, 1), (2, 3)])),
+            (libc::SYS_linkat, None, At(&[(2, 3)])),
+            (libc::SYS_symlinkat, None, At(&[(1, 2)])),
+        ];
+        #[cfg(target_arch = "x86_64")]
+        syscalls.extend(vec![
+            (libc::SYS_open, Some(1), Cwd(&[0])),
+            (libc::SYS_creat, None, Cwd(&[0])),
+            (libc::SYS_mkdir, None, Cwd(&[0])),
+            (libc::SYS_rmdir, None, Cwd(&[0])),
+            (libc::SYS_mknod, None, Cwd(&[0])),
+            (libc::SYS_unlink, None, Cwd(&[0])),
+            (libc::SYS_rename, None, Cwd(&[0, 1])),
+            (libc::SYS_link, None, Cwd(&[1])),
+            (libc::SYS_symlink, None, Cwd(&[1])),
+        ]);
+        syscalls
+    }
+
+    /// The seccomp program reporting the system calls writing to paths to
+    /// the listener.
+    fn monitor_filter() -> Vec<sock_filter> {
+        use libc::{BPF_ABS, BPF_JEQ, BPF_JMP, BPF_JSET, BPF_K, BPF_LD, BPF_RET, BPF_W};
+        let mut filter = vec![
+            stmt(BPF_LD | BPF_W | BPF_ABS, DATA_ARCH),
+            jump(BPF_JMP | BPF_JEQ | BPF_K, AUDIT_ARCH, 1, 0),
+            stmt(BPF_RET | BPF_K, libc::SECCOMP_RET_ALLOW),
+            stmt(BPF_LD | BPF_W | BPF_ABS, DATA_NR),
+        ];
+        for (nr, flags, _) in path_syscalls() {
+            match flags {
+                None => {
+                    filter.push(jump(BPF_JMP | BPF_JEQ | BPF_K, nr as u32, 0, 1));
+                    filter.push(stmt(BPF_RET | BPF_K, SECCOMP_RET_USER_NOTIF));
+                }
+                Some(flags) => {
+                    filter.push(jump(BPF_JMP | BPF_JEQ | BPF_K, nr as u32, 0, 4));
+                    filter.push(stmt(BPF_LD | BPF_W | BPF_ABS, DATA_ARG0 + 8 * flags as u32));
+                    filter.push(jump(BPF_JMP | BPF_JSET | BPF_K, OPEN_WRITE_FLAGS, 0, 1));
+                    filter.push(stmt(BPF_RET | BPF_K, SECCOMP_RET_USER_NOTIF));
+                    filter.push(stmt(BPF_RET | BPF_K, libc::SECCOMP_RET_ALLOW));
+                }
+            }
+        }
+        filter.push(stmt(BPF_RET | BPF_K, libc::SECCOMP_RET_ALLOW));
+        filter
+    }
+
+    /// Starts the thread recording the writes outside of `writable_paths` in
+    /// `denied`, and returns the filter the child process installs, with the
+    /// socket it sends the listener of the filter through.
+    fn start_monitor(
+        writable_paths: &[PathBuf],
+        denied: &DeniedWrites,
+    ) -> Option<(Vec<sock_filter>, File)> {
+        let mut fds = [0; 2];
+        let ret = unsafe {
+            libc::socketpair(
+                libc::AF_UNIX,
+                libc::SOCK_SEQPACKET | libc::SOCK_CLOEXEC,
+                0,
+                fds.as_mut_ptr(),
+            )
+        };
+        if ret != 0 {
+            return None;
+        }
+        let (ours, theirs) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+        // Symbolic links are resolved by Landlock.
+        let writable_paths: Vec<PathBuf> = writable_paths
+            .iter()
+            .filter_map(|path| path.canonicalize().ok())
+            .collect();
+        let denied = denied.clone();
+        // The thread waits for the listener until the child process sends it,
+        // or the command is dropped, and then answers the system calls until
+        // all the processes using the filter have exited.
+        thread::spawn(move || {
+            if let Some(listener) = recv_fd(ours.as_raw_fd()) {
+                drop(ours);
+                let listener = unsafe { File::from_raw_fd(listener) };
+                monitor(&listener, &writable_paths, &denied);
+            }
+        });
+        Some((monitor_filter(), theirs))
+    }
+
+    fn monitor(listener: &File, writable_paths: &[PathBuf], denied: &DeniedWrites) {
+        let syscalls = path_syscalls();
+        loop {
+            let mut poll = libc::pollfd {
+                fd: listener.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            if unsafe { libc::poll(&mut poll, 1, -1) } < 0 {
+                if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return;
+            }
+            if poll.revents & libc::POLLIN == 0 {
+                // All the processes have exited.
+                return;
+            }
+            let mut notif: Notif = unsafe { mem::zeroed() };
+            let ret = unsafe {
+                libc::ioctl(
+                    listener.as_raw_fd(),
+                    SECCOMP_IOCTL_NOTIF_RECV,
+                    &mut notif as *mut Notif,
+                )
+            };
+            if ret != 0 {
+                match io::Error::last_os_error().raw_os_error() {
+                    // The process was killed while making the system call.
+                    Some(libc::ENOENT) | Some(libc::EINTR) => continue,
+                    _ => return,
+                }
+            }
+            let written = syscalls
+                .iter()
+                .find(|(nr, _, _)| *nr == notif.nr as c_long)
+                .map(|(_, _, args)| written_paths(&notif, args))
+                .unwrap_or_default();
+            // The process may have been replaced by another one with the same
+            // ID while its memory was read.
+            let valid = unsafe {
+                libc::ioctl(
+                    listener.as_raw_fd(),
+                    SECCOMP_IOCTL_NOTIF_ID_VALID,
+                    &notif.id as *const u64,
+                )
+            } == 0;
+            if valid {
+                for path in written {
+                    if !writable_paths.iter().any(|w| path.starts_with(w)) {
+                        denied.insert(path);
+                    }
+                }
+            }
+            let mut resp = NotifResp {
+                id: notif.id,
+                val: 0,
+                error: 0,
+                flags: SECCOMP_USER_NOTIF_FLAG_CONTINUE,
+            };
+            unsafe {
+                libc::ioctl(
+                    listener.as_raw_fd(),
+                    SECCOMP_IOCTL_NOTIF_SEND,
+                    &mut resp as *mut NotifResp,
+                );
+            }
+        }
+    }
+
+    /// Returns the absolute paths the system call of `notif` writes to.
+    fn written_paths(notif: &Notif, args: &PathArgs) -> Vec<PathBuf> {
+        let mem = match File::open(format!("/proc/{}/mem", notif.pid)) {
+            Ok(mem) => mem,
+            Err(_) => return Vec::new(),
+        };
+        if notif.nr as c_long == libc::SYS_openat2 {
+            // `struct open_how` starts with the flags.
+            let mut flags = [0; 8];
+            if mem.read_exact_at(&mut flags, notif.args[2]).is_err()
+                || u64::from_ne_bytes(flags) & u64::from(OPEN_WRITE_FLAGS) == 0
+            {
+                return Vec::new();
+            }
+        }
+        let paths: Vec<(Option<c_int>, usize)> = match args {
+            PathArgs::Cwd(paths) => paths.iter().map(|path| (None, *path)).collect(),
+            PathArgs::At(paths) => paths
+                .iter()
+                .map(|(dir, path)| (Some(notif.args[*dir] as c_int), *path))
+                .collect(),
+        };
+        let follows_link = paths.len() == 1
+            && (notif.nr as c_long == libc::SYS_openat
+                || notif.nr as c_long == libc::SYS_openat2
+                || notif.nr as c_long == libc::SYS_truncate);
+        paths
+            .into_iter()
+            .filter_map(|(dir, path)| {
+                let path = read_path(&mem, notif.args[path])?;
+                resolve(notif.pid, dir, &path, follows_link)
+            })
+            .collect()
+    }
+
+    /// Reads the nul-terminated path at `addr` in the memory of a process.
+    fn read_path(mem: &File, addr: u64) -> Option<PathBuf> {
+        let mut buf = vec![0; libc::PATH_MAX as usize];
+        let mut len = 0;
+        // The path may end right before an unmapped page, so it's read up to
+        // the end of each page.
+        while len < buf.len() {
+            let page_end = ((addr + len as u64) | 0xfff) + 1;
+            let end = buf.len().min((page_end - addr) as usize);
+            let read = mem.read_at(&mut buf[len..end], addr + len as u64).ok()?;
+            if read == 0 {
+                return None;
+            }
+            if let Some(nul) = buf[len..len + read].iter().position(|b| *b == 0) {
+                buf.truncate(len + nul);
+                return if buf.is_empty() {
+                    None
+                } else {
+                    Some(PathBuf::from(OsString::from_vec(buf)))
+                };
+            }
+            len += read;
+        }
+        None
+    }
+
+    /// Resolves `path`, relative to the directory `dir` or to the working
+    /// directory of the process `pid`, like Landlock does: symbolic links
+    /// are followed in its directory, and in the path itself if
+    /// `follows_link` is true.
+    fn resolve(pid: u32, dir: Option<c_int>, path: &Path, follows_link: bool) -> Option<PathBuf> {
+        let path = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            let dir = match dir {
+                Some(fd) if fd != libc::AT_FDCWD => format!("/proc/{}/fd/{}", pid, fd),
+                _ => format!("/proc/{}/cwd", pid),
+            };
+            fs::read_link(dir).ok()?.join(path)
+        };
+        // Writes through `/proc`, like to `/proc/self/fd/2`, go to files
+        // opened before, which can't be told from here.
+        if path.starts_with("/proc") {
+            return None;
+        }
+        if follows_link {
+            if let Ok(path) = path.canonicalize() {
+                return Some(path);
+            }
+        }
+        match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => match parent.canonicalize() {
+                Ok(parent) => Some(parent.join(name)),
+                Err(_) => Some(path),
+            },
+            _ => Some(path),
+        }
+    }
+
+    /// Sends the file descriptor `fd` through the socket `socket`. This is
+    /// called between `fork` and `exec`, so it only makes system calls.
+    unsafe fn send_fd(socket: c_int, fd: c_int) {
+        let mut byte = 0u8;
+        let mut iov = libc::iovec {
+            iov_base: &mut byte as *mut u8 as *mut libc::c_void,
+            iov_len: 1,
+        };
+        let mut control = [0u64; 4];
+        let mut msg: libc::msghdr = mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = libc::CMSG_SPACE(mem::size_of::<c_int>() as u32) as usize;
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of::<c_int>() as u32) as usize;
+        ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut c_int, fd);
+        libc::sendmsg(socket, &msg, 0);
+    }
+
+    /// Receives a file descriptor sent by `send_fd`, or returns `None` once
+    /// the other end of the socket is closed.
+    fn recv_fd(socket: c_int) -> Option<c_int> {
+        unsafe {
+            let mut byte = 0u8;
+            let mut iov = libc::iovec {
+                iov_base: &mut byte as *mut u8 as *mut libc::c_void,
+                iov_len: 1,
+            };
+            let mut control = [0u64; 4];
+            let mut msg: libc::msghdr = mem::zeroed();
+            msg.msg_iov = &mut iov;
+            msg.msg_iovlen = 1;
+            msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+            msg.msg_controllen = mem::size_of_val(&control);
+            loop {
+                let ret = libc::recvmsg(socket, &mut msg, libc::MSG_CMSG_CLOEXEC);
+                if ret < 0 && io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                if ret <= 0 {
+                    return None;
+                }
+                break;
+            }
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            if cmsg.is_null()
+                || (*cmsg).cmsg_level != libc::SOL_SOCKET
+                || (*cmsg).cmsg_type != libc::SCM_RIGHTS
+            {
+                return None;
+            }
+            Some(ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const c_int))
+        }
+    }
+
+    /// Restricts the writes of the current process to `paths`, which are
+    /// paired with whether they are directories.
+    unsafe fn restrict_writes(paths: &[(CString, bool)], abi: c_long) -> io::Result<()> {
+        if abi < 1 {
+            return Err(io::Error::from_raw_os_error(libc::ENOSYS));
+        }
+        let access = write_access(abi);
+        let attr = RulesetAttr {
+            handled_access_fs: access,
+        };
+        let ruleset = libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            &attr as *const RulesetAttr,
+            std::mem::size_of::<RulesetAttr>(),
+            0,
+        );
+        if ruleset < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let ruleset = ruleset as c_int;
+        for (path, is_dir) in paths {
+            let fd = libc::open(path.as_ptr(), libc::O_PATH | libc::O_CLOEXEC);
+            if fd < 0 {
+                continue;
+            }
+            let rule = PathBeneathAttr {
+                allowed_access: if *is_dir {
+                    access
+                } else {
+                    access & (ACCESS_FS_WRITE_FILE | ACCESS_FS_TRUNCATE)
+                },
+                parent_fd: fd,
+            };
+            let ret = libc::syscall(
+                libc::SYS_landlock_add_rule,
+                ruleset,
+                LANDLOCK_RULE_PATH_BENEATH,
+                &rule as *const PathBeneathAttr,
+                0,
+            );
+            libc::close(fd);
+            if ret != 0 {
+                let err = io::Error::last_os_error();
+                libc::close(ruleset);
+                return Err(err);
+            }
+        }
+        let ret = libc::syscall(libc::SYS_landlock_restrict_self, ruleset, 0);
+        let err = io::Error::last_os_error();
+        libc::close(ruleset);
+        if ret != 0 {
+            return Err(err);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use std::ffi::OsStr;
+    use std::path::PathBuf;
+    use std::process::Command;
+    use std::time::Instant;
+
+    use super::Sandbox;
+
+    pub const SUPPORTED: bool = true;
+    pub const RECORDS_DENIED_WRITES: bool = false;
+
+    /// Returns the writes the sandbox denied since `started`, which it logs
+    /// like `Sandbox: cc(123) deny(1) file-write-create /path`.
+    pub fn search_denied_writes(started: Instant) -> Vec<PathBuf> {
+        // The log only takes durations in minutes or more.
+        let minutes = started.elapsed().as_secs() / 60 + 1;
+        let output = Command::new("/usr/bin/log")
+            .arg("show")
+            .arg("--style")
+            .arg("compact")
+            .arg("--last")
+            .arg(format!("{}m", minutes))
+            .arg("--predicate")
+            .arg("sender == \"Sandbox\" AND eventMessage CONTAINS \"deny(1) file-write\"")
+            .output();
+        let output = match output {
+            Ok(output) if output.status.success() => output,
+            _ => return Vec::new(),
+        };
+        let mut paths: Vec<PathBuf> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let denial = &line[line.find("deny(1) file-write")?..];
+                let path = &denial[denial.find(' ')? + 1..];
+                Some(PathBuf::from(path.trim_end()))
+            })
+            .collect();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+
+    pub fn filesystem_sandbox_supported() -> bool {
+        true
+    }
+
+    /// Returns the `sandbox-exec` profile denying what `sandbox` denies.
+    fn profile(sandbox: &Sandbox) -> String {
+        let mut profile = String::from("(version 1) (allow default)");
+        if sandbox.deny_network {
+            profile.push_str(" (deny network*) (allow network* (local unix) (remote unix))");
+        }
+        if let Some(paths) = &sandbox.writable_paths {
+            profile.push_str(" (deny file-write*) (allow file-write*");
+            for path in paths {
+                // Symbolic links, like `/tmp`, are resolved by the sandbox.
+                let path = path.canonicalize().unwrap_or_else(|_| path.clone());
+                let path = path.to_string_lossy();
+                profile.push_str(&format!(
+                    " (subpath \"{}\")",
+                    path.replace('\\', "\\\\").replace('"', "\\\"")
+                ));
+            }
+            profile.push(')');
+        }
+        profile
+    }
+
+    pub fn sandboxed_command(program: &OsStr, sandbox: &Sandbox) -> Command {
+        let mut command = Command::new("/usr/bin/sandbox-exec");
+        command.arg("-p").arg(profile(sandbox)).arg(program);
+        command
+    }
+}
+
+#[cfg(not(any(
+    all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ),
+    target_os = "macos"
+)))]
+mod imp {
+    use std::ffi::OsStr;
+    use std::path::PathBuf;
+    use std::process::Command;
+    use std::time::Instant;
+
+    use super::Sandbox;
+
+    pub const SUPPORTED: bool = false;
+    pub const RECORDS_DENIED_WRITES: bool = false;
+
+    pub fn search_denied_writes(_started: Instant) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    pub fn filesystem_sandbox_supported() -> bool {
+        false
+    }
+
+    pub fn sandboxed_command(program: &OsStr, _sandbox: &Sandbox) -> Command {
+        Command::new(program)
+    }
+}
@@ -19,7 +19,9 @@ use crate::core::profiles::Strip;
 use crate::core::resolver::ResolveBehavior;
 use crate::core::{Dependency, Manifest, PackageId, Summary, Target};
 use crate::core::{Edition, EitherManifest, Feature, Features, VirtualManifest, Workspace};
-use crate::core::{GitReference, PackageIdSpec, SourceId, WorkspaceConfig, WorkspaceRootConfig};
+use crate::core::{
+    GitReference, GitSubmodules, PackageIdSpec, SourceId, WorkspaceConfig, WorkspaceRootConfig,
+};
 use crate::sources::{CRATES_IO_INDEX, CRATES_IO_REGISTRY};
 use crate::util::errors::{CargoResult, CargoResultExt, ManifestError};
 use crate::util::interning::InternedString;
@@ -53,6 +55,14 @@ pub fn read_manifest(
         .map_err(|err| ManifestError::new(err, path.into()))
 }
 
+/// Returns the keys of the manifest at `path` which Cargo doesn't know about,
+/// and ignores.
+pub fn unused_manifest_keys(path: &Path, config: &Config) -> CargoResult<BTreeSet<String>> {
+    let contents = paths::read(path)?;
+    let (_manifest, unused) = deserialize_manifest(&contents, path, config)?;
+    Ok(unused)
+}
+
 fn do_read_manifest(
     contents: &str,
     manifest_file: &Path,
@@ -61,19 +71,7 @@ fn do_read_manifest(
 ) -> CargoResult<(EitherManifest, Vec<PathBuf>)> {
     let package_root = manifest_file.parent().unwrap();
 
-    let toml = {
-        let pretty_filename = manifest_file
-            .strip_prefix(config.cwd())
-            .unwrap_or(manifest_file);
-        parse(contents, pretty_filename, config)?
-    };
-
-    let mut unused = BTreeSet::new();
-    let manifest: TomlManifest = serde_ignored::deserialize(toml, |path| {
-        let mut key = String::new();
-        stringify(&mut key, &path);
-        unused.insert(key);
-    })?;
+    let (manifest, unused) = deserialize_manifest(contents, manifest_file, config)?;
     let add_unused = |warnings: &mut Warnings| {
         for key in unused {
             warnings.add_warning(format!("unused manifest key: {}", key));
@@ -102,6 +100,32 @@ fn do_read_manifest(
         add_unused(m.warnings_mut());
         Ok((EitherManifest::Virtual(m), paths))
     };
+}
+
+/// Parses the manifest, with the fields inherited from the workspace, and
+/// returns it along with the keys which aren't used.
+fn deserialize_manifest(
+    contents: &str,
+    manifest_file: &Path,
+    config: &Config,
+) -> CargoResult<(TomlManifest, BTreeSet<String>)> {
+    let package_root = manifest_file.parent().unwrap();
+
+    let mut toml = {
+        let pretty_filename = manifest_file
+            .strip_prefix(config.cwd())
+            .unwrap_or(manifest_file);
+        parse(contents, pretty_filename, config)?
+    };
+    inherit_package_fields(&mut toml, package_root, config)?;
+
+    let mut unused = BTreeSet::new();
+    let manifest: TomlManifest = serde_ignored::deserialize(toml, |path| {
+        let mut key = String::new();
+        stringify(&mut key, &path);
+        unused.insert(key);
+    })?;
+    return Ok((manifest, unused));
 
     fn stringify(dst: &mut String, path: &serde_ignored::Path<'_>) {
         use serde_ignored::Path;
@@ -129,6 +153,174 @@ fn do_read_manifest(
     }
 }
 
+/// The fields of `[package]` which can be inherited from `[workspace.package]`.
+const INHERITABLE_FIELDS: &[&str] = &[
+    "version",
+    "authors",
+    "description",
+    "documentation",
+    "homepage",
+    "repository",
+    "readme",
+    "license",
+    "license-file",
+    "keywords",
+    "categories",
+    "edition",
+    "rust-version",
+    "publish",
+    "include",
+    "exclude",
+];
+
+/// Replaces the fields of `[package]` set to `{ workspace = true }` in the
+/// manifest `toml` with the ones of `[workspace.package]` of the workspace
+/// root, before it is deserialized, so that the rest of Cargo, including
+/// `cargo package`, only sees the resulting values.
+fn inherit_package_fields(
+    toml: &mut toml::Value,
+    package_root: &Path,
+    config: &Config,
+) -> CargoResult<()> {
+    let key = if toml.get("package").is_some() {
+        "package"
+    } else {
+        "project"
+    };
+    let package = match toml.get(key).and_then(|p| p.as_table()) {
+        Some(package) => package,
+        None => return Ok(()),
+    };
+    let fields = package
+        .iter()
+        .filter(|(_, v)| matches!(v.as_table(), Some(t) if t.contains_key("workspace")))
+        .map(|(k, _)| k.clone())
+        .collect::<Vec<_>>();
+    if fields.is_empty() {
+        return Ok(());
+    }
+
+    let cargo_features: Vec<String> = match toml.get("cargo-features") {
+        Some(features) => features.clone().try_into()?,
+        None => Vec::new(),
+    };
+    Features::new(&cargo_features, &mut Vec::new())?.require(Feature::workspace_inheritance())?;
+    let workspace: Option<TomlWorkspace> = toml
+        .get("workspace")
+        .map(|ws| ws.clone().try_into())
+        .transpose()?;
+    let pointer = package.get("workspace").and_then(|w| w.as_str());
+    let (root_manifest, ws) = find_workspace(workspace.as_ref(), pointer, package_root, config)?;
+    let ws_package = toml::Value::try_from(ws.package.unwrap_or_default())?;
+
+    let package = toml.get_mut(key).unwrap().as_table_mut().unwrap();
+    for field in fields {
+        let value = &package[&field];
+        if value.get("workspace").and_then(|w| w.as_bool()) != Some(true)
+            || value.as_table().unwrap().len() != 1
+        {
+            bail!(
+                "`{}.{}` must be `{{ workspace = true }}` to be inherited from the workspace",
+                key,
+                field
+            );
+        }
+        if !INHERITABLE_FIELDS.contains(&field.as_str()) {
+            bail!("`{}.{}` cannot be inherited from the workspace", key, field);
+        }
+        let mut inherited = match ws_package.get(&field) {
+            Some(value) => value.clone(),
+            None => bail!(
+                "`{}.{}` is inherited from the workspace, but it is not in \
+                 `[workspace.package]` of `{}`",
+                key,
+                field,
+                root_manifest.display()
+            ),
+        };
+        // Paths are relative to the workspace root.
+        if field == "readme" || field == "license-file" {
+            if let toml::Value::String(path) = &inherited {
+                let path = paths::normalize_path(&root_manifest.parent().unwrap().join(path));
+                let path = relative_path(package_root, &path);
+                inherited = toml::Value::String(path.display().to_string());
+            }
+        }
+        package.insert(field, inherited);
+    }
+    Ok(())
+}
+
+/// Returns the path of `path` relative to the directory `base`, both being
+/// absolute.
+fn relative_path(base: &Path, path: &Path) -> PathBuf {
+    let base = base.components().collect::<Vec<_>>();
+    let path = path.components().collect::<Vec<_>>();
+    let common = base.iter().zip(&path).take_while(|(a, b)| a == b).count();
+    let mut relative = PathBuf::new();
+    for _ in common..base.len() {
+        relative.push("..");
+    }
+    relative.extend(&path[common..]);
+    relative
+}
+
+/// Finds the root of the workspace of the package at `package_root`, the
+/// same way as `Workspace::find_root`, and returns its manifest path and
+/// `[workspace]` table.
+///
+/// `workspace` is the `[workspace]` table of the package itself, and
+/// `pointer` its `package.workspace` field.
+fn find_workspace(
+    workspace: Option<&TomlWorkspace>,
+    pointer: Option<&str>,
+    package_root: &Path,
+    config: &Config,
+) -> CargoResult<(PathBuf, TomlWorkspace)> {
+    if let Some(ws) = workspace {
+        return Ok((package_root.join("Cargo.toml"), ws.clone()));
+    }
+    if let Some(root) = pointer {
+        let root_manifest = paths::normalize_path(&package_root.join(root).join("Cargo.toml"));
+        return match load_workspace(&root_manifest, config)? {
+            Some(ws) => Ok((root_manifest, ws)),
+            None => bail!(
+                "cannot inherit from the workspace: `{}` has no `[workspace]`",
+                root_manifest.display()
+            ),
+        };
+    }
+    for path in paths::ancestors(package_root).skip(1) {
+        if path.ends_with("target/package") {
+            break;
+        }
+        let root_manifest = path.join("Cargo.toml");
+        if root_manifest.exists() {
+            if let Some(ws) = load_workspace(&root_manifest, config)? {
+                return Ok((root_manifest, ws));
+            }
+        }
+        if config.home() == path {
+            break;
+        }
+    }
+    bail!(
+        "cannot inherit from the workspace: no workspace root was found for `{}`",
+        package_root.join("Cargo.toml").display()
+    );
+
+    fn load_workspace(manifest: &Path, config: &Config) -> CargoResult<Option<TomlWorkspace>> {
+        let contents = paths::read(manifest)?;
+        let toml = parse(&contents, manifest, config)?;
+        // Only the `[workspace]` table is read, since the rest may itself be
+        // inherited.
+        toml.get("workspace")
+            .map(|ws| ws.clone().try_into())
+            .transpose()
+            .chain_err(|| format!("failed to parse manifest at `{}`", manifest.display()))
+    }
+}
+
 /// Attempts to parse a string into a [`toml::Value`]. This is not specific to any
 /// particular kind of TOML file.
 ///
@@ -253,6 +445,7 @@ pub struct DetailedTomlDependency {
     branch: Option<String>,
     tag: Option<String>,
     rev: Option<String>,
+    submodules: Option<VecStringOrBool>,
     features: Option<Vec<String>>,
     optional: Option<bool>,
     default_features: Option<bool>,
@@ -260,6 +453,9 @@ pub struct DetailedTomlDependency {
     default_features2: Option<bool>,
     package: Option<String>,
     public: Option<bool>,
+    allow_prerelease: Option<bool>,
+    /// Whether this dependency is inherited from `[workspace.dependencies]`.
+    workspace: Option<bool>,
 }
 
 /// This type is used to deserialize `Cargo.toml` files.
@@ -288,6 +484,20 @@ pub struct TomlManifest {
     patch: Option<BTreeMap<String, BTreeMap<String, TomlDependency>>>,
     workspace: Option<TomlWorkspace>,
     badges: Option<BTreeMap<String, BTreeMap<String, String>>>,
+    doc: Option<TomlDoc>,
+}
+
+/// The `[doc]` table, which sets how the package is documented.
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct TomlDoc {
+    /// The cfgs set when documenting the package, like `docsrs`.
+    pub cfg: Option<Vec<String>>,
+    /// Extra arguments for rustdoc when documenting the package.
+    pub rustdoc_args: Option<Vec<String>>,
+    /// The combinations of features which `cargo doc --features-matrix`
+    /// documents the package with.
+    pub features_matrix: Option<Vec<Vec<String>>>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, Default)]
@@ -802,6 +1012,8 @@ impl<'de> de::Deserialize<'de> for VecStringOrBool {
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct TomlProject {
     edition: Option<String>,
+    #[serde(rename = "rust-version")]
+    rust_version: Option<String>,
     name: InternedString,
     version: semver::Version,
     authors: Option<Vec<String>>,
@@ -811,6 +1023,8 @@ pub struct TomlProject {
     exclude: Option<Vec<String>>,
     include: Option<Vec<String>>,
     publish: Option<VecStringOrBool>,
+    #[serde(rename = "publish-name")]
+    publish_name: Option<InternedString>,
     #[serde(rename = "publish-lockfile")]
     publish_lockfile: Option<bool>,
     workspace: Option<String>,
@@ -820,6 +1034,7 @@ pub struct TomlProject {
     autoexamples: Option<bool>,
     autotests: Option<bool>,
     autobenches: Option<bool>,
+    autodiscover: Option<TomlAutodiscover>,
     #[serde(rename = "default-run")]
     default_run: Option<String>,
 
@@ -838,7 +1053,46 @@ pub struct TomlProject {
     resolver: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// The `[package.autodiscover]` table, listing the directories in which the
+/// targets of each kind are discovered, relative to the package root.
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct TomlAutodiscover {
+    bins: Option<Vec<String>>,
+    examples: Option<Vec<String>>,
+    tests: Option<Vec<String>>,
+    benches: Option<Vec<String>>,
+}
+
+impl TomlAutodiscover {
+    fn validate(&self) -> CargoResult<()> {
+        let kinds = [
+            ("bins", &self.bins),
+            ("examples", &self.examples),
+            ("tests", &self.tests),
+            ("benches", &self.benches),
+        ];
+        for (kind, dirs) in kinds.iter() {
+            for dir in dirs.iter().flatten() {
+                let path = Path::new(dir);
+                if path.is_absolute()
+                    || path
+                        .components()
+                        .any(|c| matches!(c, std::path::Component::ParentDir))
+                {
+                    bail!(
+                        "invalid directory `{}` in `package.autodiscover.{}`, \
+                         the directories must be inside the package",
+                        dir,
+                        kind
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct TomlWorkspace {
     members: Option<Vec<String>>,
     #[serde(rename = "default-members")]
@@ -846,6 +1100,33 @@ pub struct TomlWorkspace {
     exclude: Option<Vec<String>>,
     metadata: Option<toml::Value>,
     resolver: Option<String>,
+    /// Dependencies which members can inherit with `workspace = true`.
+    dependencies: Option<BTreeMap<String, TomlDependency>>,
+    /// Package fields which members can inherit with `workspace = true`.
+    package: Option<TomlWorkspacePackage>,
+}
+
+/// The `[workspace.package]` table, with the fields listed in
+/// `INHERITABLE_FIELDS`.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+struct TomlWorkspacePackage {
+    version: Option<semver::Version>,
+    authors: Option<Vec<String>>,
+    description: Option<String>,
+    documentation: Option<String>,
+    homepage: Option<String>,
+    repository: Option<String>,
+    readme: Option<StringOrBool>,
+    license: Option<String>,
+    license_file: Option<String>,
+    keywords: Option<Vec<String>>,
+    categories: Option<Vec<String>>,
+    edition: Option<String>,
+    rust_version: Option<String>,
+    publish: Option<VecStringOrBool>,
+    include: Option<Vec<String>>,
+    exclude: Option<Vec<String>>,
 }
 
 impl TomlProject {
@@ -869,13 +1150,12 @@ struct Context<'a, 'b> {
 impl TomlManifest {
     /// Prepares the manfiest for publishing.
     // - Path and git components of dependency specifications are removed.
-    // - License path is updated to point within the package.
+    // - License and readme paths are updated to point within the package.
     pub fn prepare_for_publish(
         &self,
         ws: &Workspace<'_>,
         package_root: &Path,
     ) -> CargoResult<TomlManifest> {
-        let config = ws.config();
         let mut package = self
             .package
             .as_ref()
@@ -883,19 +1163,7 @@ impl TomlManifest {
             .unwrap()
             .clone();
         package.workspace = None;
-        let mut cargo_features = self.cargo_features.clone();
         package.resolver = ws.resolve_behavior().to_manifest();
-        if package.resolver.is_some() {
-            // This should be removed when stabilizing.
-            match &mut cargo_features {
-                None => cargo_features = Some(vec!["resolver".to_string()]),
-                Some(feats) => {
-                    if !feats.iter().any(|feat| feat == "resolver") {
-                        feats.push("resolver".to_string());
-                    }
-                }
-            }
-        }
         if let Some(license_file) = &package.license_file {
             let license_path = Path::new(&license_file);
             let abs_license_path = paths::normalize_path(&package_root.join(license_path));
@@ -912,19 +1180,42 @@ impl TomlManifest {
                 );
             }
         }
+        if let Some(StringOrBool::String(readme)) = &package.readme {
+            let readme_path = Path::new(readme);
+            let abs_readme_path = paths::normalize_path(&package_root.join(readme_path));
+            if abs_readme_path.strip_prefix(package_root).is_err() {
+                // Like the license file, `cargo package` copies the readme
+                // into the root.
+                let readme = readme_path.file_name().unwrap().to_str().unwrap();
+                package.readme = Some(StringOrBool::String(readme.to_string()));
+            }
+        }
+        let mut lib = self.lib.clone();
+        if let Some(publish_name) = package.publish_name.take() {
+            // The library keeps the name it has in the workspace, so that the
+            // code using it doesn't depend on where it comes from.
+            if lib.is_some() || package_root.join("src/lib.rs").exists() {
+                let lib = lib.get_or_insert_with(TomlTarget::new);
+                if lib.name.is_none() {
+                    lib.name = Some(package.name.replace("-", "_"));
+                }
+            }
+            package.name = publish_name;
+        }
         let all = |_d: &TomlDependency| true;
         return Ok(TomlManifest {
             package: Some(package),
             project: None,
             profile: self.profile.clone(),
-            lib: self.lib.clone(),
+            lib,
             bin: self.bin.clone(),
             example: self.example.clone(),
             test: self.test.clone(),
             bench: self.bench.clone(),
-            dependencies: map_deps(config, self.dependencies.as_ref(), all)?,
+            dependencies: map_deps(ws, package_root, self.dependencies.as_ref(), all)?,
             dev_dependencies: map_deps(
-                config,
+                ws,
+                package_root,
                 self.dev_dependencies
                     .as_ref()
                     .or_else(|| self.dev_dependencies2.as_ref()),
@@ -932,7 +1223,8 @@ impl TomlManifest {
             )?,
             dev_dependencies2: None,
             build_dependencies: map_deps(
-                config,
+                ws,
+                package_root,
                 self.build_dependencies
                     .as_ref()
                     .or_else(|| self.build_dependencies2.as_ref()),
@@ -947,9 +1239,15 @@ impl TomlManifest {
                         Ok((
                             k.clone(),
                             TomlPlatform {
-                                dependencies: map_deps(config, v.dependencies.as_ref(), all)?,
+                                dependencies: map_deps(
+                                    ws,
+                                    package_root,
+                                    v.dependencies.as_ref(),
+                                    all,
+                                )?,
                                 dev_dependencies: map_deps(
-                                    config,
+                                    ws,
+                                    package_root,
                                     v.dev_dependencies
                                         .as_ref()
                                         .or_else(|| v.dev_dependencies2.as_ref()),
@@ -957,7 +1255,8 @@ impl TomlManifest {
                                 )?,
                                 dev_dependencies2: None,
                                 build_dependencies: map_deps(
-                                    config,
+                                    ws,
+                                    package_root,
                                     v.build_dependencies
                                         .as_ref()
                                         .or_else(|| v.build_dependencies2.as_ref()),
@@ -977,11 +1276,13 @@ impl TomlManifest {
             patch: None,
             workspace: None,
             badges: self.badges.clone(),
-            cargo_features,
+            doc: self.doc.clone(),
+            cargo_features: self.cargo_features.clone(),
         });
 
         fn map_deps(
-            config: &Config,
+            ws: &Workspace<'_>,
+            package_root: &Path,
             deps: Option<&BTreeMap<String, TomlDependency>>,
             filter: impl Fn(&TomlDependency) -> bool,
         ) -> CargoResult<Option<BTreeMap<String, TomlDependency>>> {
@@ -992,25 +1293,41 @@ impl TomlManifest {
             let deps = deps
                 .iter()
                 .filter(|(_k, v)| filter(v))
-                .map(|(k, v)| Ok((k.clone(), map_dependency(config, v)?)))
+                .map(|(k, v)| Ok((k.clone(), map_dependency(ws, package_root, v)?)))
                 .collect::<CargoResult<BTreeMap<_, _>>>()?;
             Ok(Some(deps))
         }
 
-        fn map_dependency(config: &Config, dep: &TomlDependency) -> CargoResult<TomlDependency> {
+        fn map_dependency(
+            ws: &Workspace<'_>,
+            package_root: &Path,
+            dep: &TomlDependency,
+        ) -> CargoResult<TomlDependency> {
             match dep {
                 TomlDependency::Detailed(d) => {
                     let mut d = d.clone();
-                    // Path dependencies become crates.io deps.
-                    d.path.take();
+                    // Path dependencies become crates.io deps, with the
+                    // version of the member of the workspace they point to
+                    // if they don't specify one, and the name it is
+                    // published under.
+                    if let Some(path) = d.path.take() {
+                        let path = package_root.join(path);
+                        if d.version.is_none() {
+                            d.version = ws.path_dep_version_req(&path);
+                        }
+                        if let Some(publish_name) = ws.path_dep_publish_name(&path) {
+                            d.package = Some(publish_name.to_string());
+                        }
+                    }
                     // Same with git dependencies.
                     d.git.take();
                     d.branch.take();
                     d.tag.take();
                     d.rev.take();
+                    d.submodules.take();
                     // registry specifications are elaborated to the index URL
                     if let Some(registry) = d.registry.take() {
-                        let src = SourceId::alt_registry(config, &registry)?;
+                        let src = SourceId::alt_registry(ws.config(), &registry)?;
                         d.registry_index = Some(src.url().to_string());
                     }
                     Ok(TomlDependency::Detailed(d))
@@ -1023,6 +1340,174 @@ impl TomlManifest {
         }
     }
 
+    /// Returns whether any dependency is inherited from the workspace with
+    /// `workspace = true`.
+    fn inherits_workspace_dependencies(&self) -> bool {
+        let inherits = |deps: &Option<BTreeMap<String, TomlDependency>>| {
+            deps.iter().flatten().any(|(_, dep)| match dep {
+                TomlDependency::Detailed(d) => d.workspace.is_some(),
+                TomlDependency::Simple(_) => false,
+            })
+        };
+        inherits(&self.dependencies)
+            || inherits(&self.dev_dependencies)
+            || inherits(&self.dev_dependencies2)
+            || inherits(&self.build_dependencies)
+            || inherits(&self.build_dependencies2)
+            || self.target.iter().flatten().any(|(_, platform)| {
+                inherits(&platform.dependencies)
+                    || inherits(&platform.dev_dependencies)
+                    || inherits(&platform.dev_dependencies2)
+                    || inherits(&platform.build_dependencies)
+                    || inherits(&platform.build_dependencies2)
+            })
+    }
+
+    /// Returns a copy of the manifest where the dependencies with
+    /// `workspace = true` are replaced with the ones of the same name in
+    /// `[workspace.dependencies]` of the workspace root.
+    ///
+    /// The rest of Cargo only sees the resulting dependencies, so that
+    /// `cargo package` writes them in full in the packaged manifest.
+    fn inherit_workspace_dependencies(
+        &self,
+        package_root: &Path,
+        config: &Config,
+    ) -> CargoResult<TomlManifest> {
+        let project = self.package.as_ref().or(self.project.as_ref());
+        let (root_manifest, ws) = find_workspace(
+            self.workspace.as_ref(),
+            project.and_then(|p| p.workspace.as_deref()),
+            package_root,
+            config,
+        )?;
+        let ws_deps = ws.dependencies.unwrap_or_default();
+        let inherit = |deps: &Option<BTreeMap<String, TomlDependency>>| {
+            deps.as_ref()
+                .map(|deps| {
+                    deps.iter()
+                        .map(|(name, dep)| {
+                            let dep = inherit_dependency(name, dep, &root_manifest, &ws_deps)?;
+                            Ok((name.clone(), dep))
+                        })
+                        .collect::<CargoResult<BTreeMap<_, _>>>()
+                })
+                .transpose()
+        };
+        let target = self
+            .target
+            .as_ref()
+            .map(|targets| {
+                targets
+                    .iter()
+                    .map(|(name, platform)| {
+                        let platform = TomlPlatform {
+                            dependencies: inherit(&platform.dependencies)?,
+                            build_dependencies: inherit(&platform.build_dependencies)?,
+                            build_dependencies2: inherit(&platform.build_dependencies2)?,
+                            dev_dependencies: inherit(&platform.dev_dependencies)?,
+                            dev_dependencies2: inherit(&platform.dev_dependencies2)?,
+                        };
+                        Ok((name.clone(), platform))
+                    })
+                    .collect::<CargoResult<BTreeMap<_, _>>>()
+            })
+            .transpose()?;
+        return Ok(TomlManifest {
+            cargo_features: self.cargo_features.clone(),
+            package: self.package.clone(),
+            project: self.project.clone(),
+            profile: self.profile.clone(),
+            lib: self.lib.clone(),
+            bin: self.bin.clone(),
+            example: self.example.clone(),
+            test: self.test.clone(),
+            bench: self.bench.clone(),
+            dependencies: inherit(&self.dependencies)?,
+            dev_dependencies: inherit(&self.dev_dependencies)?,
+            dev_dependencies2: inherit(&self.dev_dependencies2)?,
+            build_dependencies: inherit(&self.build_dependencies)?,
+            build_dependencies2: inherit(&self.build_dependencies2)?,
+            features: self.features.clone(),
+            target,
+            replace: self.replace.clone(),
+            patch: self.patch.clone(),
+            workspace: self.workspace.clone(),
+            badges: self.badges.clone(),
+            doc: self.doc.clone(),
+        });
+
+        fn inherit_dependency(
+            name: &str,
+            dep: &TomlDependency,
+            root_manifest: &Path,
+            ws_deps: &BTreeMap<String, TomlDependency>,
+        ) -> CargoResult<TomlDependency> {
+            let d = match dep {
+                TomlDependency::Detailed(d) if d.workspace.is_some() => d,
+                _ => return Ok(dep.clone()),
+            };
+            if d.workspace != Some(true) {
+                bail!("`workspace` cannot be `false` for dependency `{}`", name);
+            }
+            let keys = match toml::Value::try_from(d)? {
+                toml::Value::Table(table) => table,
+                _ => unreachable!(),
+            };
+            let other_keys = keys
+                .keys()
+                .filter(|key| !["workspace", "features", "optional"].contains(&key.as_str()))
+                .map(|key| format!("`{}`", key))
+                .collect::<Vec<_>>();
+            if !other_keys.is_empty() {
+                bail!(
+                    "dependency `{}` is inherited from the workspace, so it can \
+                     only specify `features` and `optional`, but it also specifies {}",
+                    name,
+                    other_keys.join(", ")
+                );
+            }
+            let mut inherited = match ws_deps.get(name) {
+                Some(TomlDependency::Simple(version)) => DetailedTomlDependency {
+                    version: Some(version.clone()),
+                    ..Default::default()
+                },
+                Some(TomlDependency::Detailed(ws_dep)) => {
+                    if ws_dep.workspace.is_some() {
+                        bail!("`workspace.dependencies.{}` cannot use `workspace`", name);
+                    }
+                    if ws_dep.optional.is_some() {
+                        bail!(
+                            "`workspace.dependencies.{}` cannot be `optional`, \
+                             the members can set `optional` instead",
+                            name
+                        );
+                    }
+                    ws_dep.clone()
+                }
+                None => bail!(
+                    "dependency `{}` is inherited from the workspace, but it is \
+                     not in `[workspace.dependencies]` of `{}`",
+                    name,
+                    root_manifest.display()
+                ),
+            };
+            // Paths are relative to the workspace root.
+            if let Some(path) = inherited.path.take() {
+                let path = paths::normalize_path(&root_manifest.parent().unwrap().join(path));
+                inherited.path = Some(path.display().to_string());
+            }
+            if let Some(features) = &d.features {
+                inherited
+                    .features
+                    .get_or_insert_with(Vec::new)
+                    .extend(features.iter().cloned());
+            }
+            inherited.optional = d.optional;
+            Ok(TomlDependency::Detailed(inherited))
+        }
+    }
+
     pub fn to_real_manifest(
         me: &Rc<TomlManifest>,
         source_id: SourceId,
@@ -1038,6 +1523,18 @@ impl TomlManifest {
         let cargo_features = me.cargo_features.as_ref().unwrap_or(&empty);
         let features = Features::new(cargo_features, &mut warnings)?;
 
+        if matches!(&me.workspace, Some(ws) if ws.dependencies.is_some()) {
+            features.require(Feature::workspace_inheritance())?;
+        }
+        let inherited;
+        let me = if me.inherits_workspace_dependencies() {
+            features.require(Feature::workspace_inheritance())?;
+            inherited = Rc::new(me.inherit_workspace_dependencies(package_root, config)?);
+            &inherited
+        } else {
+            me
+        };
+
         let project = me.project.as_ref().or_else(|| me.package.as_ref());
         let project = project.ok_or_else(|| anyhow!("no `package` section found"))?;
 
@@ -1061,18 +1558,39 @@ impl TomlManifest {
             Edition::Edition2015
         };
 
+        let rust_version = match &project.rust_version {
+            Some(_) if !features.is_enabled(Feature::rust_version()) => {
+                warnings.push(
+                    "`rust-version` is unstable and is ignored, consider adding \
+                     `cargo-features = [\"rust-version\"]` to the manifest"
+                        .to_string(),
+                );
+                None
+            }
+            rust_version => rust_version.clone(),
+        };
+        if let Some(rust_version) = &rust_version {
+            let parts = rust_version.split('.').collect::<Vec<_>>();
+            if !(2..=3).contains(&parts.len())
+                || parts.iter().any(|part| part.parse::<u64>().is_err())
+            {
+                bail!(
+                    "failed to parse the `rust-version` key: `{}` is not a valid \
+                     rustc version, expected `MAJOR.MINOR` or `MAJOR.MINOR.PATCH`",
+                    rust_version
+                );
+            }
+        }
+
         if project.metabuild.is_some() {
             features.require(Feature::metabuild())?;
         }
 
-        if project.resolver.is_some()
-            || me
-                .workspace
-                .as_ref()
-                .map_or(false, |ws| ws.resolver.is_some())
-        {
-            features.require(Feature::resolver())?;
+        if let Some(autodiscover) = &project.autodiscover {
+            features.require(Feature::autodiscover_dirs())?;
+            autodiscover.validate()?;
         }
+
         let resolve_behavior = match (
             project.resolver.as_ref(),
             me.workspace.as_ref().and_then(|ws| ws.resolver.as_ref()),
@@ -1213,6 +1731,15 @@ impl TomlManifest {
         let include = project.include.clone().unwrap_or_default();
         let empty_features = BTreeMap::new();
 
+        if !features.is_enabled(Feature::optional_dev_dependencies()) {
+            if let Some(dep) = deps.iter().find(|d| d.is_optional() && !d.is_transitive()) {
+                bail!(
+                    "dev-dependencies are not allowed to be optional: `{}`",
+                    dep.name_in_toml()
+                )
+            }
+        }
+
         let summary = Summary::new(
             config,
             pkgid,
@@ -1221,7 +1748,25 @@ impl TomlManifest {
             project.links.as_deref(),
         )?;
         let unstable = config.cli_unstable();
-        summary.unstable_gate(unstable.namespaced_features, unstable.weak_dep_features)?;
+        summary.unstable_gate(unstable.weak_dep_features)?;
+
+        for target in &targets {
+            for name in target.dependencies() {
+                let is_optional_dep = summary.dependencies().iter().any(|dep| {
+                    dep.name_in_toml() == name.as_str()
+                        && dep.kind() == DepKind::Normal
+                        && dep.is_optional()
+                });
+                if !is_optional_dep {
+                    bail!(
+                        "{} lists `{}` in `dependencies`, but it is not an optional \
+                         dependency in `[dependencies]`",
+                        target.description_named(),
+                        name
+                    );
+                }
+            }
+        }
 
         let metadata = ManifestMetadata {
             description: project.description.clone(),
@@ -1264,6 +1809,11 @@ impl TomlManifest {
             None | Some(VecStringOrBool::Bool(true)) => None,
         };
 
+        if let Some(publish_name) = project.publish_name {
+            features.require(Feature::publish_name())?;
+            validate_package_name(&publish_name, "publish name", "")?;
+        }
+
         let publish_lockfile = match project.publish_lockfile {
             Some(b) => {
                 features.require(Feature::publish_lockfile())?;
@@ -1297,6 +1847,10 @@ impl TomlManifest {
             }
         }
 
+        if me.doc.is_some() {
+            features.require(Feature::doc_table())?;
+        }
+
         let custom_metadata = project.metadata.clone();
         let mut manifest = Manifest::new(
             summary,
@@ -1308,17 +1862,20 @@ impl TomlManifest {
             custom_metadata,
             profiles,
             publish,
+            project.publish_name,
             publish_lockfile,
             replace,
             patch,
             workspace_config,
             features,
             edition,
+            rust_version,
             project.im_a_teapot,
             project.default_run.clone(),
             Rc::clone(me),
             project.metabuild.clone().map(|sov| sov.0),
             resolve_behavior,
+            me.doc.clone(),
         );
         if project.license_file.is_some() && project.license.is_some() {
             manifest.warnings_mut().add_warning(
@@ -1384,6 +1941,9 @@ impl TomlManifest {
         if me.badges.is_some() {
             bail!("this virtual manifest specifies a [badges] section, which is not allowed");
         }
+        if me.doc.is_some() {
+            bail!("this virtual manifest specifies a [doc] section, which is not allowed");
+        }
 
         let mut nested_paths = Vec::new();
         let mut warnings = Vec::new();
@@ -1391,6 +1951,9 @@ impl TomlManifest {
         let empty = Vec::new();
         let cargo_features = me.cargo_features.as_ref().unwrap_or(&empty);
         let features = Features::new(cargo_features, &mut warnings)?;
+        if matches!(&me.workspace, Some(ws) if ws.dependencies.is_some()) {
+            features.require(Feature::workspace_inheritance())?;
+        }
 
         let (replace, patch) = {
             let mut cx = Context {
@@ -1410,13 +1973,6 @@ impl TomlManifest {
         if let Some(profiles) = &profiles {
             profiles.validate(&features, &mut warnings)?;
         }
-        if me
-            .workspace
-            .as_ref()
-            .map_or(false, |ws| ws.resolver.is_some())
-        {
-            features.require(Feature::resolver())?;
-        }
         let resolve_behavior = me
             .workspace
             .as_ref()
@@ -1618,6 +2174,14 @@ impl DetailedTomlDependency {
         cx: &mut Context<'_, '_>,
         kind: Option<DepKind>,
     ) -> CargoResult<Dependency> {
+        if self.workspace.is_some() {
+            bail!(
+                "dependency ({}) specifies `workspace`, which is only allowed \
+                 in the dependencies of a package",
+                name_in_toml
+            );
+        }
+
         if self.version.is_none() && self.path.is_none() && self.git.is_none() {
             let msg = format!(
                 "dependency ({}) specified without \
@@ -1657,6 +2221,14 @@ impl DetailedTomlDependency {
                     cx.warnings.push(msg)
                 }
             }
+
+            if self.submodules.is_some() {
+                cx.warnings.push(format!(
+                    "key `submodules` is ignored for dependency ({}). \
+                     This will be considered an error in future versions",
+                    name_in_toml
+                ));
+            }
         }
 
         let new_source_id = match (
@@ -1718,7 +2290,22 @@ impl DetailedTomlDependency {
                     cx.warnings.push(msg)
                 }
 
-                SourceId::for_git(&loc, reference)?
+                let source_id = SourceId::for_git(&loc, reference)?;
+                match &self.submodules {
+                    Some(submodules) => {
+                        cx.features.require(Feature::git_submodules())?;
+                        let submodules = match submodules {
+                            VecStringOrBool::Bool(true) => GitSubmodules::All,
+                            VecStringOrBool::Bool(false) => GitSubmodules::Skip,
+                            VecStringOrBool::VecString(paths) => GitSubmodules::from_paths(paths)
+                                .chain_err(|| {
+                                format!("invalid submodules of dependency ({})", name_in_toml)
+                            })?,
+                        };
+                        source_id.with_git_submodules(submodules)
+                    }
+                    None => source_id,
+                }
             }
             (None, Some(path), _, _) => {
                 cx.nested_paths.push(PathBuf::from(path));
@@ -1791,6 +2378,11 @@ impl DetailedTomlDependency {
 
             dep.set_public(p);
         }
+
+        if let Some(allow_prerelease) = self.allow_prerelease {
+            cx.features.require(Feature::allow_prerelease())?;
+            dep.set_allow_prerelease(allow_prerelease);
+        }
         Ok(dep)
     }
 }
@@ -1820,6 +2412,7 @@ struct TomlTarget {
     #[serde(rename = "required-features")]
     required_features: Option<Vec<String>>,
     edition: Option<String>,
+    dependencies: Option<Vec<String>>,
 }
 
 #[derive(Clone)]
@@ -7,6 +7,9 @@
 //!  * `tests/*.rs` are integration tests
 //!  * `benches/*.rs` are benchmarks
 //!
+//! The directories of the binaries, examples, tests and benchmarks can be
+//! changed with the `[package.autodiscover]` table.
+//!
 //! It is a bit tricky because we need match explicit information from `Cargo.toml`
 //! with implicit info in directory layout.
 
@@ -57,6 +60,7 @@ pub fn targets(
         .as_ref()
         .or_else(|| manifest.project.as_ref())
         .ok_or_else(|| anyhow::format_err!("manifest has no `package` (or `project`)"))?;
+    let autodiscover_dirs = package.autodiscover.clone().unwrap_or_default();
 
     targets.extend(clean_bins(
         features,
@@ -65,6 +69,7 @@ pub fn targets(
         package_name,
         edition,
         package.autobins,
+        autodiscover_dirs.bins.as_deref(),
         warnings,
         errors,
         has_lib,
@@ -76,6 +81,7 @@ pub fn targets(
         package_root,
         edition,
         package.autoexamples,
+        autodiscover_dirs.examples.as_deref(),
         warnings,
         errors,
     )?);
@@ -86,6 +92,7 @@ pub fn targets(
         package_root,
         edition,
         package.autotests,
+        autodiscover_dirs.tests.as_deref(),
         warnings,
         errors,
     )?);
@@ -96,6 +103,7 @@ pub fn targets(
         package_root,
         edition,
         package.autobenches,
+        autodiscover_dirs.benches.as_deref(),
         warnings,
         errors,
     )?);
@@ -246,11 +254,12 @@ fn clean_bins(
     package_name: &str,
     edition: Edition,
     autodiscover: Option<bool>,
+    autodiscover_dirs: Option<&[String]>,
     warnings: &mut Vec<String>,
     errors: &mut Vec<String>,
     has_lib: bool,
 ) -> CargoResult<Vec<Target>> {
-    let inferred = inferred_bins(package_root, package_name);
+    let inferred = inferred_bins(package_root, package_name, autodiscover_dirs);
 
     let bins = toml_targets_and_inferred(
         toml_bins,
@@ -348,10 +357,11 @@ fn clean_examples(
     package_root: &Path,
     edition: Edition,
     autodiscover: Option<bool>,
+    autodiscover_dirs: Option<&[String]>,
     warnings: &mut Vec<String>,
     errors: &mut Vec<String>,
 ) -> CargoResult<Vec<Target>> {
-    let inferred = infer_from_directory(&package_root.join("examples"));
+    let inferred = infer_from_directories(package_root, autodiscover_dirs, "examples");
 
     let targets = clean_targets(
         "example",
@@ -393,10 +403,11 @@ fn clean_tests(
     package_root: &Path,
     edition: Edition,
     autodiscover: Option<bool>,
+    autodiscover_dirs: Option<&[String]>,
     warnings: &mut Vec<String>,
     errors: &mut Vec<String>,
 ) -> CargoResult<Vec<Target>> {
-    let inferred = infer_from_directory(&package_root.join("tests"));
+    let inferred = infer_from_directories(package_root, autodiscover_dirs, "tests");
 
     let targets = clean_targets(
         "test",
@@ -427,6 +438,7 @@ fn clean_benches(
     package_root: &Path,
     edition: Edition,
     autodiscover: Option<bool>,
+    autodiscover_dirs: Option<&[String]>,
     warnings: &mut Vec<String>,
     errors: &mut Vec<String>,
 ) -> CargoResult<Vec<Target>> {
@@ -447,7 +459,7 @@ fn clean_benches(
             Some(legacy_path)
         };
 
-        let inferred = infer_from_directory(&package_root.join("benches"));
+        let inferred = infer_from_directories(package_root, autodiscover_dirs, "benches");
 
         clean_targets_with_legacy_path(
             "benchmark",
@@ -565,17 +577,41 @@ fn inferred_lib(package_root: &Path) -> Option<PathBuf> {
     }
 }
 
-fn inferred_bins(package_root: &Path, package_name: &str) -> Vec<(String, PathBuf)> {
+fn inferred_bins(
+    package_root: &Path,
+    package_name: &str,
+    autodiscover_dirs: Option<&[String]>,
+) -> Vec<(String, PathBuf)> {
     let main = package_root.join("src").join("main.rs");
     let mut result = Vec::new();
     if main.exists() {
         result.push((package_name.to_string(), main));
     }
-    result.extend(infer_from_directory(&package_root.join("src").join("bin")));
+    result.extend(infer_from_directories(
+        package_root,
+        autodiscover_dirs,
+        "src/bin",
+    ));
 
     result
 }
 
+/// Infers the targets in the directories listed in `package.autodiscover`,
+/// or in the `default` one if there are none.
+fn infer_from_directories(
+    package_root: &Path,
+    autodiscover_dirs: Option<&[String]>,
+    default: &str,
+) -> Vec<(String, PathBuf)> {
+    match autodiscover_dirs {
+        Some(dirs) => dirs
+            .iter()
+            .flat_map(|dir| infer_from_directory(&package_root.join(dir)))
+            .collect(),
+        None => infer_from_directory(&package_root.join(default)),
+    }
+}
+
 fn infer_from_directory(directory: &Path) -> Vec<(String, PathBuf)> {
     let entries = match fs::read_dir(directory) {
         Err(_) => return Vec::new(),
@@ -790,6 +826,28 @@ fn configure(features: &Features, toml: &TomlTarget, target: &mut Target) -> Car
                 .chain_err(|| "failed to parse the `edition` key")?,
         );
     }
+    if let Some(dependencies) = &toml.dependencies {
+        features.require(Feature::target_dependencies())?;
+        if !target.is_bin() && !target.is_example() {
+            anyhow::bail!(
+                "`dependencies` can only be set for binaries and examples, \
+                 but it is set for {}",
+                target.description_named()
+            );
+        }
+        // The dependencies are optional dependencies of the package, which
+        // must be enabled for the target to be built, like its required
+        // features.
+        let mut required_features = target.required_features().cloned().unwrap_or_default();
+        for dep in dependencies {
+            if !required_features.contains(dep) {
+                required_features.push(dep.clone());
+            }
+        }
+        target
+            .set_required_features(Some(required_features))
+            .set_dependencies(dependencies.clone());
+    }
     Ok(())
 }
 
@@ -0,0 +1,304 @@
+//! Tests for `-Z advisories`.
+
+use cargo_test_support::registry::Package;
+use cargo_test_support::{git, paths, project, Project};
+
+/// An advisory of `bar` in the RustSec format.
+fn advisory(id: &str, title: &str, versions: &str) -> String {
+    format!(
+        "```toml\n\
+         [advisory]\n\
+         id = \"{}\"\n\
+         package = \"bar\"\n\
+         date = \"2020-01-01\"\n\
+         \n\
+         [versions]\n\
+         {}\n\
+         ```\n\
+         \n\
+         # {}\n\
+         \n\
+         Details.\n",
+        id, versions, title
+    )
+}
+
+/// Creates an advisory database with the given advisories of `bar`, and a
+/// project depending on `bar 0.1` configured to use it, with `extra_config`
+/// in its `[advisories]` table.
+fn setup(advisories: &[(&str, String)], extra_config: &str) -> Project {
+    Package::new("bar", "0.1.0").publish();
+    Package::new("bar", "0.1.2").publish();
+    Package::new("bar", "1.0.0").publish();
+
+    let mut db = git::repo(&paths::root().join("advisory-db")).file("README.md", "");
+    for (name, contents) in advisories {
+        db = db.file(&format!("crates/bar/{}", name), contents);
+    }
+    let db = db.build();
+
+    project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [dependencies]
+                bar = "0.1"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file(
+            "Cargo.lock",
+            r#"
+                [[package]]
+                name = "bar"
+                version = "0.1.0"
+                source = "registry+https://github.com/rust-lang/crates.io-index"
+
+                [[package]]
+                name = "foo"
+                version = "0.1.0"
+                dependencies = [
+                 "bar",
+                ]
+            "#,
+        )
+        .file(
+            ".cargo/config",
+            &format!(
+                r#"
+                    [advisories]
+                    db = "{}"
+                    branch = "master"
+                    {}
+                "#,
+                db.url(),
+                extra_config
+            ),
+        )
+        .build()
+}
+
+#[cargo_test]
+fn build_warns_about_vulnerable_package() {
+    let p = setup(
+        &[(
+            "RUSTSEC-2020-0001.md",
+            advisory(
+                "RUSTSEC-2020-0001",
+                "Oops in bar",
+                r#"patched = [">= 0.1.2"]"#,
+            ),
+        )],
+        "",
+    );
+
+    p.cargo("build -Z advisories")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[UPDATING] advisory database `file://[..]/advisory-db`
+[WARNING] package `bar v0.1.0` in Cargo.lock is affected by RUSTSEC-2020-0001: Oops in bar
+advisory: https://rustsec.org/advisories/RUSTSEC-2020-0001
+patched versions: >= 0.1.2
+to update to a compatible patched version, run `cargo update -p bar --precise 0.1.2`
+[DOWNLOADING] crates ...
+[DOWNLOADED] bar v0.1.0 ([..])
+[COMPILING] bar v0.1.0
+[COMPILING] foo v0.1.0 ([CWD])
+[FINISHED] [..]
+",
+        )
+        .run();
+
+    // The database is only fetched once by builds.
+    p.cargo("build -Z advisories")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[WARNING] package `bar v0.1.0` in Cargo.lock is affected by RUSTSEC-2020-0001: [..]
+advisory: [..]
+patched versions: >= 0.1.2
+to update to a compatible patched version, run `cargo update -p bar --precise 0.1.2`
+[FINISHED] [..]
+",
+        )
+        .run();
+
+    p.cargo("update -p bar --precise 0.1.2 -Z advisories")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[UPDATING] bar v0.1.0 -> v0.1.2
+[UPDATING] advisory database `file://[..]/advisory-db`
+",
+        )
+        .run();
+
+    p.cargo("build -Z advisories")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_does_not_contain("[WARNING][..]")
+        .run();
+}
+
+#[cargo_test]
+fn advisories_require_z_flag() {
+    let p = setup(
+        &[(
+            "RUSTSEC-2020-0001.md",
+            advisory(
+                "RUSTSEC-2020-0001",
+                "Oops in bar",
+                r#"patched = [">= 0.1.2"]"#,
+            ),
+        )],
+        "policy = \"deny\"",
+    );
+
+    p.cargo("build")
+        .with_stderr_does_not_contain("[..]advisory[..]")
+        .run();
+}
+
+#[cargo_test]
+fn no_compatible_patched_version() {
+    let p = setup(
+        &[(
+            "RUSTSEC-2020-0001.md",
+            advisory(
+                "RUSTSEC-2020-0001",
+                "Oops in bar",
+                r#"patched = [">= 1.0.0"]"#,
+            ),
+        )],
+        "",
+    );
+
+    p.cargo("generate-lockfile -Z advisories")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[UPDATING] advisory database `file://[..]/advisory-db`
+[WARNING] package `bar v0.1.2` in Cargo.lock is affected by RUSTSEC-2020-0001: Oops in bar
+advisory: https://rustsec.org/advisories/RUSTSEC-2020-0001
+patched versions: >= 1.0.0
+no patched version is compatible with 0.1.2, the requirements on `bar` need to be updated
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn deny_policy() {
+    let p = setup(
+        &[
+            (
+                "RUSTSEC-2020-0001.md",
+                advisory(
+                    "RUSTSEC-2020-0001",
+                    "Oops in bar",
+                    r#"patched = ["^0.1.2", ">= 1.0.0"]"#,
+                ),
+            ),
+            (
+                "RUSTSEC-2020-0002.md",
+                advisory("RUSTSEC-2020-0002", "Another oops", "patched = []"),
+            ),
+        ],
+        "policy = \"deny\"",
+    );
+
+    p.cargo("check -Z advisories")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[UPDATING] advisory database `file://[..]/advisory-db`
+[ERROR] package `bar v0.1.0` in Cargo.lock is affected by RUSTSEC-2020-0001: Oops in bar
+advisory: https://rustsec.org/advisories/RUSTSEC-2020-0001
+patched versions: ^0.1.2, >= 1.0.0
+to update to a compatible patched version, run `cargo update -p bar --precise 0.1.2`
+[ERROR] package `bar v0.1.0` in Cargo.lock is affected by RUSTSEC-2020-0002: Another oops
+advisory: https://rustsec.org/advisories/RUSTSEC-2020-0002
+no patched version is available
+[ERROR] Cargo.lock contains 2 known vulnerabilities, which `advisories.policy` denies
+Advisories can be ignored by adding their ID to `advisories.ignore`.
+",
+        )
+        .run();
+
+    p.cargo("check -Z advisories")
+        .masquerade_as_nightly_cargo()
+        .env("CARGO_ADVISORIES_IGNORE", "RUSTSEC-2020-0002")
+        .env("CARGO_ADVISORIES_POLICY", "warn")
+        .with_stderr_contains("[WARNING] [..] affected by RUSTSEC-2020-0001: Oops in bar")
+        .with_stderr_does_not_contain("[..]RUSTSEC-2020-0002[..]")
+        .run();
+}
+
+#[cargo_test]
+fn unaffected_and_informational_advisories() {
+    let p = setup(
+        &[
+            (
+                "RUSTSEC-2020-0001.md",
+                advisory(
+                    "RUSTSEC-2020-0001",
+                    "Oops in old bar",
+                    "patched = []\nunaffected = [\">= 0.1.0\"]",
+                ),
+            ),
+            (
+                "RUSTSEC-2020-0002.md",
+                advisory("RUSTSEC-2020-0002", "bar is unmaintained", "patched = []").replace(
+                    "package = \"bar\"",
+                    "package = \"bar\"\ninformational = \"unmaintained\"",
+                ),
+            ),
+            (
+                "RUSTSEC-2020-0003.md",
+                advisory("RUSTSEC-2020-0003", "Withdrawn", "patched = []").replace(
+                    "package = \"bar\"",
+                    "package = \"bar\"\nwithdrawn = \"2020-02-01\"",
+                ),
+            ),
+        ],
+        "policy = \"deny\"",
+    );
+
+    p.cargo("check -Z advisories")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_does_not_contain("[..]RUSTSEC[..]")
+        .run();
+}
+
+#[cargo_test]
+fn offline_without_database() {
+    let p = setup(
+        &[(
+            "RUSTSEC-2020-0001.md",
+            advisory(
+                "RUSTSEC-2020-0001",
+                "Oops in bar",
+                r#"patched = [">= 0.1.2"]"#,
+            ),
+        )],
+        "",
+    );
+    p.cargo("fetch").run();
+
+    p.cargo("check -Z advisories --offline")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains(
+            "[WARNING] the advisory database `file://[..]/advisory-db` was never fetched and \
+             can't be in the offline mode, packages aren't checked for vulnerabilities",
+        )
+        .run();
+}
@@ -3,8 +3,11 @@
 use cargo::util::IntoUrl;
 use cargo_test_support::publish::validate_alt_upload;
 use cargo_test_support::registry::{self, Package};
-use cargo_test_support::{basic_manifest, git, paths, project};
+use cargo_test_support::{basic_manifest, git, paths, project, Project};
 use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread;
 
 #[cargo_test]
 fn depend_on_alt_registry() {
@@ -295,13 +298,13 @@ fn cannot_publish_to_crates_io_with_registry_dependency() {
 
     p.cargo("publish --registry fakeio")
         .with_status(101)
-        .with_stderr_contains("[ERROR] crates cannot be published to crates.io[..]")
+        .with_stderr_contains("  - crates cannot be published to crates.io[..]")
         .run();
 
     p.cargo("publish --token sekrit --index")
         .arg(fakeio_url.to_string())
         .with_status(101)
-        .with_stderr_contains("[ERROR] crates cannot be published to crates.io[..]")
+        .with_stderr_contains("  - crates cannot be published to crates.io[..]")
         .run();
 }
 
@@ -470,6 +473,126 @@ fn publish_to_alt_registry() {
     );
 }
 
+#[cargo_test]
+fn publish_registry_metadata() {
+    Package::new("bar", "0.0.1").alternative(true).publish();
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+                authors = []
+
+                [package.metadata.registry.alternative]
+                team = "compilers"
+                review-tags = ["audited", "internal"]
+                review = { required = true, approvals = 2 }
+
+                [package.metadata.registry.other]
+                team = "ignored"
+            "#,
+        )
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    p.cargo("publish --registry alternative --token TOKEN")
+        .run();
+
+    validate_alt_upload(
+        r#"{
+            "authors": [],
+            "badges": {},
+            "categories": [],
+            "deps": [],
+            "description": null,
+            "documentation": null,
+            "features": {},
+            "homepage": null,
+            "keywords": [],
+            "license": null,
+            "license_file": null,
+            "links": null,
+            "metadata": {
+                "review": { "approvals": 2, "required": true },
+                "review-tags": ["audited", "internal"],
+                "team": "compilers"
+            },
+            "name": "foo",
+            "readme": null,
+            "readme_file": null,
+            "repository": null,
+            "vers": "0.0.1"
+        }"#,
+        "foo-0.0.1.crate",
+        &["Cargo.lock", "Cargo.toml", "Cargo.toml.orig", "src/main.rs"],
+    );
+}
+
+#[cargo_test]
+fn publish_registry_metadata_not_json() {
+    Package::new("bar", "0.0.1").alternative(true).publish();
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+                authors = []
+
+                [package.metadata.registry.alternative]
+                review = [{ score = nan }]
+            "#,
+        )
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    p.cargo("publish --registry alternative --token TOKEN")
+        .with_status(101)
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[ERROR] `package.metadata.registry.alternative.review[0].score` can't be sent to \
+the registry: `NaN` can't be represented in JSON
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn publish_default_registry() {
+    Package::new("bar", "0.0.1").alternative(true).publish();
+    let p = project()
+        .file("src/main.rs", "fn main() {}")
+        .file(
+            ".cargo/config",
+            r#"
+                [publish]
+                default-registry = "alternative"
+            "#,
+        )
+        .build();
+
+    p.cargo("publish --no-verify --token TOKEN")
+        .with_stderr(
+            "\
+[UPDATING] `[..]alternative-registry` index
+[WARNING] manifest has no description, [..]
+See [..]
+[PACKAGING] foo v0.0.1 ([CWD])
+[UPLOADING] foo v0.0.1 ([CWD])
+",
+        )
+        .run();
+
+    // `--registry` takes precedence.
+    p.cargo("publish --no-verify --token TOKEN --registry crates-io")
+        .with_stderr_contains("[UPDATING] `[..]/registry` index")
+        .run();
+}
+
 #[cargo_test]
 fn publish_with_crates_io_dep() {
     let p = project()
@@ -1301,3 +1424,195 @@ fn both_index_and_registry() {
             .run();
     }
 }
+
+/// Makes the alternative registry require authentication, with its `.crate`
+/// files served over HTTP. The server answers `statuses.len()` requests, each
+/// with the given status if it has the `token` token, and with 401 otherwise.
+fn auth_required_registry(token: &'static str, statuses: &'static [u32]) -> thread::JoinHandle<()> {
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    let repo = git2::Repository::open(registry::alt_registry_path()).unwrap();
+    fs::write(
+        registry::alt_registry_path().join("config.json"),
+        format!(
+            r#"{{"dl": "http://{}", "api": "{}", "auth-required": true}}"#,
+            addr,
+            registry::alt_api_url()
+        ),
+    )
+    .unwrap();
+    git::add(&repo);
+    git::commit(&repo);
+
+    let dl_path = registry::alt_dl_path();
+    thread::spawn(move || {
+        for &status in statuses {
+            let mut conn = BufReader::new(server.accept().unwrap().0);
+            let lines: Vec<_> = (&mut conn)
+                .lines()
+                .map(|s| s.unwrap())
+                .take_while(|s| s.len() > 2)
+                .map(|s| s.trim().to_string())
+                .collect();
+            // `GET /bar/0.0.1/download HTTP/1.1`
+            let path = lines[0].split(' ').nth(1).unwrap();
+            let (name, vers) = match path.split('/').collect::<Vec<_>>()[..] {
+                ["", name, vers, "download"] => (name, vers),
+                _ => panic!("unexpected request `{}`", lines[0]),
+            };
+            let authorized = lines
+                .iter()
+                .any(|line| *line == format!("Authorization: {}", token));
+            let body = if authorized && status == 200 {
+                fs::read(
+                    dl_path
+                        .join(name)
+                        .join(vers)
+                        .join(format!("{}-{}.crate", name, vers)),
+                )
+                .unwrap()
+            } else {
+                Vec::new()
+            };
+            let status = if authorized { status } else { 401 };
+            let conn = conn.get_mut();
+            write!(
+                conn,
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                body.len()
+            )
+            .unwrap();
+            conn.write_all(&body).unwrap();
+        }
+    })
+}
+
+fn auth_required_project() -> Project {
+    Package::new("bar", "0.0.1").alternative(true).publish();
+    project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "foo"
+                version = "0.0.1"
+                authors = []
+
+                [dependencies.bar]
+                version = "0.0.1"
+                registry = "alternative"
+            "#,
+        )
+        .file("src/main.rs", "fn main() {}")
+        .build()
+}
+
+#[cargo_test]
+fn auth_required_download() {
+    let p = auth_required_project();
+    let t = auth_required_registry("api-token", &[200]);
+
+    p.cargo("fetch")
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[DOWNLOADING] crates ...
+[DOWNLOADED] bar v0.0.1 (registry `[ROOT][..]`)
+",
+        )
+        .run();
+
+    t.join().unwrap();
+}
+
+#[cargo_test]
+fn auth_required_token_rejected() {
+    // The first token is rejected, and the download is tried again once with
+    // a new one.
+    let p = auth_required_project();
+    let t = auth_required_registry("api-token", &[401, 401]);
+
+    p.cargo("fetch")
+        .with_status(101)
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[DOWNLOADING] crates ...
+[DOWNLOADING] crates ...
+[ERROR] failed to download from `http://[..]/bar/0.0.1/download`
+
+Caused by:
+  failed to get 200 response from `http://[..]/bar/0.0.1/download`, got 401
+",
+        )
+        .run();
+
+    t.join().unwrap();
+}
+
+#[cargo_test]
+fn auth_required_token_rejected_once() {
+    let p = auth_required_project();
+    let t = auth_required_registry("api-token", &[401, 200]);
+
+    p.cargo("fetch")
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[DOWNLOADING] crates ...
+[DOWNLOADING] crates ...
+[DOWNLOADED] bar v0.0.1 (registry `[ROOT][..]`)
+",
+        )
+        .run();
+
+    t.join().unwrap();
+}
+
+#[cargo_test]
+fn auth_required_download_token() {
+    // Only the download token is sent to the download endpoint.
+    let p = auth_required_project();
+    let t = auth_required_registry("dl-token", &[200]);
+    let credentials = paths::home().join(".cargo/credentials");
+    let mut contents = fs::read_to_string(&credentials).unwrap();
+    contents.push_str("download-token = \"dl-token\"\n");
+    fs::write(&credentials, contents).unwrap();
+
+    p.cargo("fetch")
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[DOWNLOADING] crates ...
+[DOWNLOADED] bar v0.0.1 (registry `[ROOT][..]`)
+",
+        )
+        .run();
+
+    t.join().unwrap();
+}
+
+#[cargo_test]
+fn auth_required_no_token() {
+    let p = auth_required_project();
+    let _t = auth_required_registry("api-token", &[]);
+    fs::remove_file(paths::home().join(".cargo/credentials")).unwrap();
+
+    p.cargo("fetch")
+        .with_status(101)
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[ERROR] failed to download `bar v0.0.1 (registry `[ROOT][..]`)`
+
+Caused by:
+  unable to get packages from source
+
+Caused by:
+  no token found for registry `alternative`, which requires authentication, \
+please run `cargo login --registry alternative`
+",
+        )
+        .run();
+}
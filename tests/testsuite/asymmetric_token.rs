@@ -0,0 +1,310 @@
+//! Tests for asymmetric tokens.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use cargo::util::paseto::SecretKey;
+use cargo_test_support::{paths, project, registry, Project};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use std::convert::TryInto;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread;
+use url::Url;
+
+/// Sets up the alternative registry with an API server, which accepts a
+/// single request and returns its `Authorization` header, and a `foo`
+/// project using the registry with `secret_key`.
+fn signed_test(secret_key: &SecretKey) -> (Project, thread::JoinHandle<String>) {
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let api_url = format!("http://{}", server.local_addr().unwrap());
+
+    registry::init_registry(
+        registry::alt_registry_path(),
+        registry::alt_dl_url(),
+        Url::parse(&api_url).unwrap(),
+        registry::alt_api_path(),
+    );
+
+    let t = thread::spawn(move || {
+        let mut conn = BufReader::new(server.accept().unwrap().0);
+        let headers: Vec<_> = (&mut conn)
+            .lines()
+            .map(|s| s.unwrap())
+            .take_while(|s| s.len() > 2)
+            .map(|s| s.trim().to_string())
+            .collect();
+        conn.get_mut()
+            .write_all(
+                b"HTTP/1.1 200\r\n\
+                  Content-Length: 33\r\n\
+                  \r\n\
+                  {\"ok\": true, \"msg\": \"completed!\"}\r\n",
+            )
+            .unwrap();
+        headers
+            .iter()
+            .find_map(|header| header.strip_prefix("Authorization: "))
+            .expect("no authorization header")
+            .to_string()
+    });
+
+    let credentials = paths::home().join(".cargo/credentials");
+    fs::create_dir_all(credentials.parent().unwrap()).unwrap();
+    fs::write(
+        &credentials,
+        format!(
+            "[registries.alternative.credential]\nsecret-key = \"{}\"\n",
+            secret_key.to_paserk()
+        ),
+    )
+    .unwrap();
+
+    let p = project()
+        .file(
+            ".cargo/config",
+            &format!(
+                r#"
+                    [registries.alternative]
+                    index = "{}"
+                "#,
+                registry::alt_registry_url(),
+            ),
+        )
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+                description = "foo"
+                authors = []
+                license = "MIT"
+                homepage = "https://example.com/"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+    (p, t)
+}
+
+/// Checks the signature of `token` against `secret_key`, and returns its
+/// message and footer.
+fn verify(token: &str, secret_key: &SecretKey) -> (serde_json::Value, serde_json::Value) {
+    fn pae(pieces: &[&[u8]]) -> Vec<u8> {
+        let mut out = (pieces.len() as u64).to_le_bytes().to_vec();
+        for piece in pieces {
+            out.extend_from_slice(&(piece.len() as u64).to_le_bytes());
+            out.extend_from_slice(piece);
+        }
+        out
+    }
+
+    let mut parts = token.strip_prefix("v4.public.").unwrap().split('.');
+    let body = URL_SAFE_NO_PAD.decode(parts.next().unwrap()).unwrap();
+    let footer = URL_SAFE_NO_PAD.decode(parts.next().unwrap()).unwrap();
+    assert!(parts.next().is_none());
+    let (message, signature) = body.split_at(body.len() - 64);
+
+    let public_key = secret_key.public_paserk();
+    let public_key = URL_SAFE_NO_PAD
+        .decode(public_key.strip_prefix("k4.public.").unwrap())
+        .unwrap();
+    let public_key = VerifyingKey::from_bytes(&public_key.try_into().unwrap()).unwrap();
+    let signature = Signature::from_slice(signature).unwrap();
+    public_key
+        .verify(&pae(&[b"v4.public.", message, &footer, b""]), &signature)
+        .unwrap();
+
+    (
+        serde_json::from_slice(message).unwrap(),
+        serde_json::from_slice(&footer).unwrap(),
+    )
+}
+
+#[cargo_test]
+fn gated() {
+    registry::init();
+
+    cargo_test_support::cargo_process("login --generate-keypair")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] the `--generate-keypair` flag is unstable, pass `-Z asymmetric-token` to enable it
+See https://github.com/rust-lang/cargo/issues/10519 for more information about asymmetric tokens.
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn generate_keypair() {
+    registry::init();
+    let credentials = paths::home().join(".cargo/credentials");
+    fs::write(&credentials, "").unwrap();
+
+    let secret_key = || {
+        let contents = fs::read_to_string(&credentials).unwrap();
+        let value: toml::Value = toml::from_str(&contents).unwrap();
+        let key = value["registries"]["alternative"]["credential"]["secret-key"]
+            .as_str()
+            .unwrap();
+        SecretKey::from_paserk(key).unwrap()
+    };
+
+    cargo_test_support::cargo_process(
+        "login -Z asymmetric-token --generate-keypair --registry alternative",
+    )
+    .masquerade_as_nightly_cargo()
+    .with_stdout(
+        "\
+add this public key to your account on [..]alt_api:
+k4.public.[..]
+",
+    )
+    .with_stderr(
+        "\
+[UPDATING] [..]
+[LOGIN] secret key for `alternative` saved
+",
+    )
+    .run();
+    let first = secret_key();
+
+    // Generating a key again replaces the previous one.
+    cargo_test_support::cargo_process(
+        "login -Z asymmetric-token --generate-keypair --registry alternative",
+    )
+    .masquerade_as_nightly_cargo()
+    .with_stderr(&format!(
+        "\
+[LOGIN] secret key for `alternative` saved
+[NOTE] the previous key was replaced, its public key can be removed from the registry:
+{}
+",
+        first.public_paserk()
+    ))
+    .run();
+    let second = secret_key();
+    assert_ne!(first.public_paserk(), second.public_paserk());
+
+    cargo_test_support::cargo_process("logout -Z asymmetric-token --registry alternative")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[LOGOUT] token for `alternative` has been removed from local storage
+",
+        )
+        .run();
+    let contents = fs::read_to_string(&credentials).unwrap();
+    assert!(!contents.contains("secret-key"), "{}", contents);
+}
+
+#[cargo_test]
+fn generate_keypair_with_token() {
+    // `registry::init` saves tokens, which must be removed first.
+    registry::init();
+
+    cargo_test_support::cargo_process(
+        "login -Z asymmetric-token --generate-keypair --registry alternative",
+    )
+    .masquerade_as_nightly_cargo()
+    .with_status(101)
+    .with_stderr(
+        "\
+[UPDATING] [..]
+[ERROR] a token or a `credential-process` is already configured for `alternative`, \
+remove it to use an asymmetric token
+",
+    )
+    .run();
+}
+
+#[cargo_test]
+fn publish() {
+    let secret_key = SecretKey::generate().unwrap();
+    let (p, t) = signed_test(&secret_key);
+
+    p.cargo("publish --no-verify --registry alternative -Z asymmetric-token")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[UPDATING] [..]
+[PACKAGING] foo v0.1.0 [..]
+[UPLOADING] foo v0.1.0 [..]
+",
+        )
+        .run();
+
+    let token = t.join().unwrap();
+    let (message, footer) = verify(&token, &secret_key);
+    assert_eq!(message["mutation"], "publish");
+    assert_eq!(message["name"], "foo");
+    assert_eq!(message["vers"], "0.1.0");
+    assert_eq!(message["v"], 1);
+    assert!(message["iat"].is_string());
+    assert!(message.get("sub").is_none());
+    let crate_file = p.root().join("target/package/foo-0.1.0.crate");
+    assert_eq!(
+        message["cksum"],
+        cargo::util::Sha256::new()
+            .update_path(&crate_file)
+            .unwrap()
+            .finish_hex()
+    );
+    assert_eq!(footer["url"], registry::alt_registry_url().as_str());
+    assert_eq!(footer["kip"], secret_key.public_id());
+}
+
+#[cargo_test]
+fn yank_with_subject() {
+    let secret_key = SecretKey::generate().unwrap();
+    let (p, t) = signed_test(&secret_key);
+    cargo::util::paths::append(
+        &paths::home().join(".cargo/credentials"),
+        b"secret-key-subject = \"me\"\n",
+    )
+    .unwrap();
+
+    p.cargo("yank --vers 0.1.0 --registry alternative -Z asymmetric-token")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[UPDATING] [..]
+[YANK] foo:0.1.0
+",
+        )
+        .run();
+
+    let token = t.join().unwrap();
+    let (message, _footer) = verify(&token, &secret_key);
+    assert_eq!(message["mutation"], "yank");
+    assert_eq!(message["name"], "foo");
+    assert_eq!(message["vers"], "0.1.0");
+    assert_eq!(message["sub"], "me");
+    assert!(message.get("cksum").is_none());
+}
+
+#[cargo_test]
+fn secret_key_and_token() {
+    let secret_key = SecretKey::generate().unwrap();
+    let (p, _t) = signed_test(&secret_key);
+    cargo::util::paths::append(
+        &paths::home().join(".cargo/credentials"),
+        b"[registries.alternative]\ntoken = \"sekrit\"\n",
+    )
+    .unwrap();
+
+    p.cargo("publish --no-verify --registry alternative -Z asymmetric-token")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] a `credential.secret-key` was specified in the config together with a token \
+or a `credential-process` for the registry `alternative`
+Only one of these values may be set, remove one or the other to proceed.
+",
+        )
+        .run();
+}
@@ -0,0 +1,169 @@
+//! Tests for the `[package.autodiscover]` table.
+
+use cargo_test_support::project;
+
+const MANIFEST: &str = r#"
+    cargo-features = ["autodiscover-dirs"]
+
+    [package]
+    name = "foo"
+    version = "0.1.0"
+
+    [package.autodiscover]
+    bins = ["src/bin", "tools"]
+    examples = ["demos"]
+    tests = ["checks"]
+    benches = ["perf"]
+"#;
+
+#[cargo_test]
+fn discovers_targets_in_configured_dirs() {
+    let p = project()
+        .file("Cargo.toml", MANIFEST)
+        .file("src/main.rs", r#"fn main() { println!("foo"); }"#)
+        .file("src/bin/a.rs", r#"fn main() { println!("a"); }"#)
+        .file("tools/b.rs", r#"fn main() { println!("b"); }"#)
+        .file("tools/c/main.rs", r#"fn main() { println!("c"); }"#)
+        .file("tools/c/helper.rs", "")
+        .file("demos/d.rs", r#"fn main() { println!("d"); }"#)
+        .file("examples/ignored.rs", "compile_error!(\"not discovered\");")
+        .file("checks/t.rs", "#[test] fn t() {}")
+        .file("tests/ignored.rs", "compile_error!(\"not discovered\");")
+        .file("perf/bench.rs", "fn main() {}")
+        .build();
+
+    for (bin, output) in &[("foo", "foo"), ("a", "a"), ("b", "b"), ("c", "c")] {
+        p.cargo(&format!("run --bin {}", bin))
+            .masquerade_as_nightly_cargo()
+            .with_stdout(output)
+            .run();
+    }
+    p.cargo("run --example d")
+        .masquerade_as_nightly_cargo()
+        .with_stdout("d")
+        .run();
+    p.cargo("test --all-targets")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains("[RUNNING] [..]t-[..]")
+        .with_stdout_contains("test t ... ok")
+        .run();
+
+    p.cargo("bench --bench bench")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains("[RUNNING] [..]bench-[..]")
+        .run();
+}
+
+#[cargo_test]
+fn explicit_targets_use_configured_dirs() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                cargo-features = ["autodiscover-dirs"]
+
+                [package]
+                name = "foo"
+                version = "0.1.0"
+                autobins = false
+
+                [package.autodiscover]
+                bins = ["tools"]
+
+                [[bin]]
+                name = "b"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file("tools/a.rs", r#"fn main() { println!("a"); }"#)
+        .file("tools/b.rs", r#"fn main() { println!("b"); }"#)
+        .build();
+
+    p.cargo("run")
+        .masquerade_as_nightly_cargo()
+        .with_stdout("b")
+        .run();
+    p.cargo("run --bin a")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains("[ERROR] no bin target named `a`")
+        .run();
+}
+
+#[cargo_test]
+fn duplicate_names() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            &MANIFEST.replace(
+                r#"bins = ["src/bin", "tools"]"#,
+                r#"bins = ["tools", "more"]"#,
+            ),
+        )
+        .file("src/lib.rs", "")
+        .file("tools/a.rs", "fn main() {}")
+        .file("more/a.rs", "fn main() {}")
+        .build();
+
+    p.cargo("build")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] failed to parse manifest at `[..]`
+
+Caused by:
+  found duplicate binary name a, but all binary targets must have a unique name
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn dirs_must_be_inside_package() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            &MANIFEST.replace(r#"examples = ["demos"]"#, r#"examples = ["../shared"]"#),
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("build")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] failed to parse manifest at `[..]`
+
+Caused by:
+  invalid directory `../shared` in `package.autodiscover.examples`, \
+the directories must be inside the package
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn requires_cargo_feature() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [package.autodiscover]
+                bins = ["tools"]
+            "#,
+        )
+        .file("tools/a.rs", "fn main() {}")
+        .build();
+
+    p.cargo("build")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains("  feature `autodiscover-dirs` is required")
+        .run();
+}
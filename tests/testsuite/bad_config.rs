@@ -1139,7 +1139,7 @@ fn bad_source_config6() {
             r#"
                 [source.crates-io]
                 registry = 'https://example.com'
-                replace-with = ['not', 'a', 'string']
+                replace-with = 42
             "#,
         )
         .build();
@@ -1151,7 +1151,7 @@ fn bad_source_config6() {
 [ERROR] error in [..]/foo/.cargo/config: could not load config key `source.crates-io.replace-with`
 
 Caused by:
-  error in [..]/foo/.cargo/config: `source.crates-io.replace-with` expected a string, but found a array
+  invalid type: integer `42`, expected a string or a list of strings
 "
         )
         .run();
@@ -1755,3 +1755,103 @@ fn json_artifact_includes_executable_for_benchmark() {
         )
         .run();
 }
+
+#[cargo_test]
+fn bench_format_json() {
+    let p = project()
+        .file("Cargo.toml", &basic_lib_manifest("foo"))
+        .file(
+            "src/lib.rs",
+            r#"
+            #![feature(test)]
+            #[cfg(test)]
+            extern crate test;
+
+            #[bench]
+            fn bench_nothing(_b: &mut test::Bencher) {}
+            "#,
+        )
+        .build();
+
+    p.cargo("bench -Z unstable-options --format json")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .with_json_contains_unordered(
+            r#"
+            {
+                "reason": "bench-result",
+                "package_id": "foo 0.5.0 ([..])",
+                "target": "{...}",
+                "executable": "[..]/foo/target/release/deps/foo-[..][EXE]",
+                "name": "bench_nothing",
+                "median": "{...}",
+                "deviation": "{...}",
+                "iterations": null,
+                "mib_per_second": null
+            }
+            "#,
+        )
+        .run();
+}
+
+#[cargo_test]
+fn bench_format_json_without_harness() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+
+            [[bench]]
+            name = "custom"
+            harness = false
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file(
+            "benches/custom.rs",
+            r##"
+            fn main() {
+                if std::env::var("CARGO_BENCH_FORMAT").as_deref() == Ok("json") {
+                    println!(r#"{{"type":"bench","name":"parse","median":1520.5,"deviation":87.25,"iterations":1000}}"#);
+                }
+                println!("not a result");
+            }
+            "##,
+        )
+        .build();
+
+    p.cargo("bench --bench custom -Z unstable-options --format json")
+        .masquerade_as_nightly_cargo()
+        .with_json_contains_unordered(
+            r#"
+            {
+                "reason": "bench-result",
+                "package_id": "foo 0.0.1 ([..])",
+                "target": "{...}",
+                "executable": "[..]/foo/target/release/deps/custom-[..][EXE]",
+                "name": "parse",
+                "median": 1520.5,
+                "deviation": 87.25,
+                "iterations": 1000,
+                "mib_per_second": null
+            }
+            "#,
+        )
+        .with_stderr_contains("not a result")
+        .run();
+
+    p.cargo("bench --bench custom --format json")
+        .with_status(101)
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[ERROR] the `--format` flag is unstable, pass `-Z unstable-options` to enable it
+See https://doc.rust-lang.org/nightly/cargo/reference/unstable.html \
+for more information about the `--format` flag.
+",
+        )
+        .run();
+}
@@ -1081,14 +1081,14 @@ fn incompatible_dependencies() {
             "\
 error: failed to select a version for `bad`.
     ... required by package `qux v0.1.0`
-    ... which is depended on by `foo v0.0.1 ([..])`
+    ... which satisfies dependency `qux = \"^0.1.0\"` of package `foo v0.0.1 ([..])`
 versions that meet the requirements `>=1.0.1` are: 1.0.2, 1.0.1
 
 all possible versions conflict with previously selected packages.
 
   previously selected package `bad v1.0.0`
-    ... which is depended on by `baz v0.1.0`
-    ... which is depended on by `foo v0.0.1 ([..])`
+    ... which satisfies dependency `bad = \"=1.0.0\"` of package `baz v0.1.0`
+    ... which satisfies dependency `baz = \"^0.1.0\"` of package `foo v0.0.1 ([..])`
 
 failed to select a version for `bad` which could resolve this conflict",
         )
@@ -1132,18 +1132,66 @@ versions that meet the requirements `>=1.0.1, <=2.0.0` are: 2.0.0, 1.0.1
 all possible versions conflict with previously selected packages.
 
   previously selected package `bad v2.0.1`
-    ... which is depended on by `baz v0.1.0`
-    ... which is depended on by `foo v0.0.1 ([..])`
+    ... which satisfies dependency `bad = \">=2.0.1\"` of package `baz v0.1.0`
+    ... which satisfies dependency `baz = \"^0.1.0\"` of package `foo v0.0.1 ([..])`
 
   previously selected package `bad v1.0.0`
-    ... which is depended on by `bar v0.1.0`
-    ... which is depended on by `foo v0.0.1 ([..])`
+    ... which satisfies dependency `bad = \"=1.0.0\"` of package `bar v0.1.0`
+    ... which satisfies dependency `bar = \"^0.1.0\"` of package `foo v0.0.1 ([..])`
 
 failed to select a version for `bad` which could resolve this conflict",
         )
         .run();
 }
 
+#[cargo_test]
+fn incompatible_dependencies_verbose() {
+    Package::new("bad", "1.0.0").publish();
+    Package::new("bad", "1.0.1").publish();
+    Package::new("bar", "0.1.0").dep("bad", "=1.0.0").publish();
+    Package::new("baz", "0.1.0").dep("bad", "=1.0.0").publish();
+    Package::new("qux", "0.1.0").dep("bad", ">=1.0.1").publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "foo"
+                version = "0.0.1"
+
+                [dependencies]
+                bar = "0.1.0"
+                baz = "0.1.0"
+                qux = "0.1.0"
+            "#,
+        )
+        .file("src/main.rs", "fn main(){}")
+        .build();
+
+    p.cargo("build -v")
+        .with_status(101)
+        .with_stderr_contains(
+            "\
+all possible versions conflict with previously selected packages.
+
+  previously selected package `bad v1.0.0`
+    ... which satisfies dependency `bad = \"=1.0.0\"` of package `bar v0.1.0`
+    ... which satisfies dependency `bar = \"^0.1.0\"` of package `foo v0.0.1 ([..])`
+
+  all requirements on `bad v1.0.0`:
+    dependency `bad = \"=1.0.0\"` of package `bar v0.1.0`
+    dependency `bad = \"=1.0.0\"` of package `baz v0.1.0`
+
+failed to select a version for `bad` which could resolve this conflict",
+        )
+        .run();
+    p.cargo("build")
+        .with_status(101)
+        .with_stderr_does_not_contain("[..]all requirements on[..]")
+        .run();
+}
+
 #[cargo_test]
 fn compile_path_dep_then_change_version() {
     let p = project()
@@ -15,6 +15,7 @@ fn cargo_build_plan_simple() {
         .with_json(
             r#"
             {
+                "version": 1,
                 "inputs": [
                     "[..]/foo/Cargo.toml"
                 ],
@@ -22,14 +23,22 @@ fn cargo_build_plan_simple() {
                     {
                         "args": "{...}",
                         "cwd": "[..]/cit/[..]/foo",
+                        "dep_info": "[..]/foo/target/debug/[..]/foo[..].d",
                         "deps": [],
                         "env": "{...}",
+                        "inputs": [
+                            "[..]/foo/Cargo.lock",
+                            "[..]/foo/Cargo.toml",
+                            "[..]/foo/src/foo.rs"
+                        ],
                         "kind": null,
                         "links": "{...}",
                         "outputs": "{...}",
                         "package_name": "foo",
                         "package_version": "0.5.0",
+                        "package_id": "foo 0.5.0 ([..])",
                         "program": "rustc",
+                        "target_name": "foo",
                         "target_kind": ["bin"],
                         "compile_mode": "build"
                     }
@@ -74,6 +83,7 @@ fn cargo_build_plan_single_dep() {
         .with_json(
             r#"
             {
+                "version": 1,
                 "inputs": [
                     "[..]/foo/Cargo.toml",
                     "[..]/foo/bar/Cargo.toml"
@@ -82,8 +92,10 @@ fn cargo_build_plan_single_dep() {
                     {
                         "args": "{...}",
                         "cwd": "[..]/cit/[..]/foo",
+                        "dep_info": "[..]/foo/target/debug/[..]/bar[..].d",
                         "deps": [],
                         "env": "{...}",
+                        "inputs": "{...}",
                         "kind": null,
                         "links": "{...}",
                         "outputs": [
@@ -92,15 +104,19 @@ fn cargo_build_plan_single_dep() {
                         ],
                         "package_name": "bar",
                         "package_version": "0.0.1",
+                        "package_id": "bar 0.0.1 ([..])",
                         "program": "rustc",
+                        "target_name": "bar",
                         "target_kind": ["lib"],
                         "compile_mode": "build"
                     },
                     {
                         "args": "{...}",
                         "cwd": "[..]/cit/[..]/foo",
+                        "dep_info": "[..]/foo/target/debug/[..]/foo[..].d",
                         "deps": [0],
                         "env": "{...}",
+                        "inputs": "{...}",
                         "kind": null,
                         "links": "{...}",
                         "outputs": [
@@ -109,7 +125,9 @@ fn cargo_build_plan_single_dep() {
                         ],
                         "package_name": "foo",
                         "package_version": "0.5.0",
+                        "package_id": "foo 0.5.0 ([..])",
                         "program": "rustc",
+                        "target_name": "foo",
                         "target_kind": ["lib"],
                         "compile_mode": "build"
                     }
@@ -143,6 +161,7 @@ fn cargo_build_plan_build_script() {
         .with_json(
             r#"
             {
+                "version": 1,
                 "inputs": [
                     "[..]/foo/Cargo.toml"
                 ],
@@ -150,42 +169,54 @@ fn cargo_build_plan_build_script() {
                     {
                         "args": "{...}",
                         "cwd": "[..]/cit/[..]/foo",
+                        "dep_info": "[..]/foo/target/debug/[..]/build_script_build[..].d",
                         "deps": [],
                         "env": "{...}",
+                        "inputs": "{...}",
                         "kind": null,
                         "links": "{...}",
                         "outputs": "{...}",
                         "package_name": "foo",
                         "package_version": "0.5.0",
+                        "package_id": "foo 0.5.0 ([..])",
                         "program": "rustc",
+                        "target_name": "build-script-build",
                         "target_kind": ["custom-build"],
                         "compile_mode": "build"
                     },
                     {
                         "args": "{...}",
                         "cwd": "[..]/cit/[..]/foo",
+                        "dep_info": null,
                         "deps": [0],
                         "env": "{...}",
+                        "inputs": "{...}",
                         "kind": null,
                         "links": "{...}",
                         "outputs": [],
                         "package_name": "foo",
                         "package_version": "0.5.0",
+                        "package_id": "foo 0.5.0 ([..])",
                         "program": "[..]/build-script-build",
+                        "target_name": "build-script-build",
                         "target_kind": ["custom-build"],
                         "compile_mode": "run-custom-build"
                     },
                     {
                         "args": "{...}",
                         "cwd": "[..]/cit/[..]/foo",
+                        "dep_info": "[..]/foo/target/debug/[..]/foo[..].d",
                         "deps": [1],
                         "env": "{...}",
+                        "inputs": "{...}",
                         "kind": null,
                         "links": "{...}",
                         "outputs": "{...}",
                         "package_name": "foo",
                         "package_version": "0.5.0",
+                        "package_id": "foo 0.5.0 ([..])",
                         "program": "rustc",
+                        "target_name": "foo",
                         "target_kind": ["bin"],
                         "compile_mode": "build"
                     }
@@ -220,3 +251,41 @@ fn build_plan_with_dev_dep() {
         .masquerade_as_nightly_cargo()
         .run();
 }
+
+#[cargo_test]
+fn build_plan_inputs_from_dep_info() {
+    let p = project()
+        .file("Cargo.toml", &basic_manifest("foo", "0.5.0"))
+        .file("src/lib.rs", "mod a;")
+        .file("src/a.rs", "")
+        .file("src/unused.rs", "")
+        .build();
+
+    let inputs = |p: &cargo_test_support::Project| -> Vec<String> {
+        let output = p
+            .cargo("build --build-plan -Zunstable-options")
+            .masquerade_as_nightly_cargo()
+            .exec_with_output()
+            .unwrap();
+        let plan: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let inputs = plan["invocations"][0]["inputs"].as_array().unwrap();
+        inputs
+            .iter()
+            .map(|input| {
+                let input = std::path::Path::new(input.as_str().unwrap());
+                let input = input.strip_prefix(p.root()).unwrap();
+                input.to_str().unwrap().replace('\\', "/")
+            })
+            .collect()
+    };
+
+    // Without a previous build, every file of the package is an input.
+    assert_eq!(
+        inputs(&p),
+        ["Cargo.toml", "src/a.rs", "src/lib.rs", "src/unused.rs"]
+    );
+
+    // Afterwards, only the files rustc read.
+    p.cargo("build").run();
+    assert_eq!(inputs(&p), ["src/a.rs", "src/lib.rs"]);
+}
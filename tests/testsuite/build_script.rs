@@ -391,6 +391,71 @@ failed to select a version for `a-sys` which could resolve this conflict
 ").run();
 }
 
+#[cargo_test]
+fn links_duplicates_verbose() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "foo"
+                version = "0.5.0"
+                authors = []
+
+                [dependencies.a-sys]
+                path = "a-sys"
+                [dependencies.b-sys]
+                path = "b-sys"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file(
+            "a-sys/Cargo.toml",
+            r#"
+                [project]
+                name = "a-sys"
+                version = "0.5.0"
+                authors = []
+                links = "a"
+                build = "build.rs"
+            "#,
+        )
+        .file("a-sys/src/lib.rs", "")
+        .file("a-sys/build.rs", "")
+        .file(
+            "b-sys/Cargo.toml",
+            r#"
+                [project]
+                name = "b-sys"
+                version = "0.5.0"
+                authors = []
+                links = "a"
+                build = "build.rs"
+            "#,
+        )
+        .file("b-sys/src/lib.rs", "")
+        .file("b-sys/build.rs", "")
+        .build();
+
+    p.cargo("build -v")
+        .with_status(101)
+        .with_stderr_contains(
+            "\
+the package `[..]-sys` links to the native library `a`, but it conflicts with a previous package which links to `a` as well:
+package `[..]-sys v0.5.0 ([..])`
+    ... which satisfies path dependency `[..]-sys` of package `foo v0.5.0 ([..])`
+
+  all requirements on `[..]-sys v0.5.0 ([..])`:
+    path dependency `[..]-sys` of package `foo v0.5.0 ([..])`
+",
+        )
+        .run();
+    p.cargo("build")
+        .with_status(101)
+        .with_stderr_does_not_contain("[..]all requirements on[..]")
+        .run();
+}
+
 #[cargo_test]
 fn links_duplicates_old_registry() {
     // Test old links validator. See `validate_links`.
@@ -500,7 +565,7 @@ fn links_duplicates_deep_dependency() {
                        .with_stderr("\
 error: failed to select a version for `a-sys`.
     ... required by package `a v0.5.0 ([..])`
-    ... which is depended on by `foo v0.5.0 ([..])`
+    ... which satisfies path dependency `a` of package `foo v0.5.0 ([..])`
 versions that meet the requirements `*` are: 0.5.0
 
 the package `a-sys` links to the native library `a`, but it conflicts with a previous package which links to `a` as well:
@@ -4158,3 +4223,165 @@ fn rerun_if_directory() {
     dirty();
     fresh();
 }
+
+#[cargo_test]
+fn rerun_if_glob() {
+    // rerun-if-changed of a glob should rerun if a matching file is added,
+    // changed or removed, but not for other files.
+    let p = project()
+        .file("Cargo.toml", &basic_manifest("foo", "0.1.0"))
+        .file("src/lib.rs", "")
+        .file("csrc/a.c", "")
+        .file("csrc/nested/b.c", "")
+        .file("csrc/README", "")
+        .file(
+            "build.rs",
+            r#"
+                fn main() {
+                    println!("cargo:rerun-if-changed=csrc/**/*.c");
+                }
+            "#,
+        )
+        .build();
+
+    let dirty = || {
+        p.cargo("check")
+            .with_stderr(
+                "[COMPILING] foo [..]\n\
+                 [FINISHED] [..]",
+            )
+            .run();
+    };
+
+    let fresh = || {
+        p.cargo("check").with_stderr("[FINISHED] [..]").run();
+    };
+
+    dirty();
+    fresh();
+
+    if is_coarse_mtime() {
+        sleep_ms(1000);
+    }
+
+    // A file not matching the pattern.
+    p.change_file("csrc/README", "changed");
+    p.change_file("csrc/nested/b.h", "");
+    fresh();
+
+    // Change a matching file.
+    p.change_file("csrc/nested/b.c", "changed");
+    dirty();
+    fresh();
+
+    if is_coarse_mtime() {
+        sleep_ms(1000);
+    }
+
+    // Add a matching file.
+    p.change_file("csrc/nested/deeper/c.c", "");
+    dirty();
+    fresh();
+
+    // Remove a matching file.
+    fs::remove_file(p.root().join("csrc/a.c")).unwrap();
+    dirty();
+    fresh();
+}
+
+#[cargo_test]
+fn rerun_if_invalid_glob() {
+    let p = project()
+        .file("Cargo.toml", &basic_manifest("foo", "0.1.0"))
+        .file("src/lib.rs", "")
+        .file(
+            "build.rs",
+            r#"
+                fn main() {
+                    println!("cargo:rerun-if-changed=src/[*.c");
+                }
+            "#,
+        )
+        .build();
+
+    p.cargo("check")
+        .with_status(101)
+        .with_stderr(
+            "\
+[COMPILING] foo [..]
+[ERROR] invalid glob pattern `src/[*.c` for `rerun-if-changed` in build script of \
+`foo v0.1.0 ([CWD])`: [..]
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn rerun_if_directory_ignores_excluded() {
+    // Files excluded from the package don't rerun a build script which
+    // watches the directory containing them, or a glob matching them.
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+                exclude = ["assets/big", "*.bak"]
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file("assets/a.txt", "")
+        .file("assets/big/data.bin", "")
+        .file("assets/old.bak", "")
+        .file(
+            "build.rs",
+            r#"
+                fn main() {
+                    println!("cargo:rerun-if-changed=assets");
+                    println!("cargo:rerun-if-changed=assets/*.bak");
+                }
+            "#,
+        )
+        .build();
+
+    let dirty = || {
+        p.cargo("check")
+            .with_stderr(
+                "[COMPILING] foo [..]\n\
+                 [FINISHED] [..]",
+            )
+            .run();
+    };
+
+    let fresh = || {
+        p.cargo("check").with_stderr("[FINISHED] [..]").run();
+    };
+
+    dirty();
+    fresh();
+
+    if is_coarse_mtime() {
+        sleep_ms(1000);
+    }
+
+    // Excluded files.
+    p.change_file("assets/big/data.bin", "changed");
+    p.change_file("assets/big/more.bin", "");
+    p.change_file("assets/old.bak", "changed");
+    p.change_file("assets/new.bak", "");
+    fresh();
+
+    // Files of the package.
+    p.change_file("assets/a.txt", "changed");
+    dirty();
+    fresh();
+
+    if is_coarse_mtime() {
+        sleep_ms(1000);
+    }
+
+    p.change_file("assets/b.txt", "");
+    dirty();
+    fresh();
+}
@@ -0,0 +1,234 @@
+//! Tests for `-Z build-script-sandbox`.
+
+use cargo_test_support::{basic_manifest, paths, project};
+
+/// A build script writing a file to `$OUT_DIR` and to `outside`, and
+/// printing whether it could.
+fn build_script(name: &str, outside: &str) -> String {
+    format!(
+        r#"
+            use std::path::Path;
+
+            fn main() {{
+                let out_dir = std::env::var("OUT_DIR").unwrap();
+                std::fs::write(Path::new(&out_dir).join("generated.rs"), "").unwrap();
+                match std::fs::write({1:?}, "") {{
+                    Ok(_) => eprintln!("{0} can write outside"),
+                    Err(e) => eprintln!("{0} can't write outside: {{:?}}", e.kind()),
+                }}
+            }}
+        "#,
+        name, outside
+    )
+}
+
+#[cargo_test]
+fn requires_z_flag() {
+    let outside = paths::root().join("outside");
+    let p = project()
+        .file("build.rs", &build_script("foo", outside.to_str().unwrap()))
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("build -vv")
+        .with_stderr_contains("[foo 0.0.1] foo can write outside")
+        .run();
+    assert!(outside.exists());
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+#[cargo_test]
+fn build_script_writes_restricted() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+
+                [dependencies]
+                bar = { path = "../bar" }
+            "#,
+        )
+        .file("build.rs", &build_script("foo", "src/generated.rs"))
+        .file("src/lib.rs", "")
+        .build();
+    let _bar = project()
+        .at("bar")
+        .file("Cargo.toml", &basic_manifest("bar", "0.0.1"))
+        .file("build.rs", &build_script("bar", "../foo/stolen.rs"))
+        .file("src/lib.rs", "")
+        .build();
+
+    // The build scripts can write to their OUT_DIR and package, but not to
+    // the other packages.
+    p.cargo("build -vv -Z build-script-sandbox")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains("[foo 0.0.1] foo can write outside")
+        .with_stderr_contains("[bar 0.0.1] bar can't write outside: PermissionDenied")
+        .run();
+    assert!(p.root().join("src/generated.rs").exists());
+    assert!(!p.root().join("stolen.rs").exists());
+
+    p.change_file(
+        ".cargo/config",
+        &format!(
+            r#"
+                [sandbox]
+                allow-filesystem = ["bar"]
+                writable-paths = [{:?}]
+            "#,
+            paths::root().join("cache").to_str().unwrap()
+        ),
+    );
+    std::fs::create_dir(paths::root().join("cache")).unwrap();
+    p.change_file(
+        "build.rs",
+        &build_script("foo2", paths::root().join("cache/foo").to_str().unwrap()),
+    );
+    p.cargo("build -vv -Z build-script-sandbox")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains("[foo 0.0.1] foo2 can write outside")
+        .run();
+    assert!(paths::root().join("cache/foo").exists());
+
+    // `bar` is exempt from the sandbox.
+    p.cargo("clean -p bar").run();
+    p.cargo("build -vv -Z build-script-sandbox")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains("[bar 0.0.1] bar can write outside")
+        .run();
+    assert!(p.root().join("stolen.rs").exists());
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+#[cargo_test]
+fn failure_lists_denied_writes() {
+    let outside = paths::root().join("outside");
+    let p = project()
+        .file(
+            "build.rs",
+            &format!(
+                r#"
+                    fn main() {{
+                        std::fs::write({:?}, "").unwrap();
+                    }}
+                "#,
+                outside.to_str().unwrap()
+            ),
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("build -Z build-script-sandbox")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains("[..]PermissionDenied[..]")
+        .with_stderr_contains(
+            "\
+[ERROR] failed to run custom build command for `foo v0.0.1 ([CWD])`
+The sandbox denied writing to `[ROOT]/outside`: build scripts may only write to their \
+`OUT_DIR`, their package and the paths of `sandbox.writable-paths`.",
+        )
+        .run();
+    assert!(!outside.exists());
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+#[cargo_test]
+fn denied_writes_are_reported() {
+    let p = project()
+        .file(
+            "build.rs",
+            r#"
+                use std::fs;
+                use std::path::Path;
+                use std::process::Command;
+
+                fn main() {
+                    let out_dir = std::env::var("OUT_DIR").unwrap();
+                    fs::write(Path::new(&out_dir).join("generated.rs"), "").unwrap();
+                    fs::write("/dev/null", "").unwrap();
+                    // Relative paths, directories and the writes of the
+                    // processes the build script runs are reported.
+                    assert!(fs::write("../relative", "").is_err());
+                    assert!(fs::create_dir("../dir").is_err());
+                    let status = Command::new("sh")
+                        .arg("-c")
+                        .arg("echo > ../from-sh")
+                        .status()
+                        .unwrap();
+                    assert!(!status.success());
+                }
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("build -Z build-script-sandbox")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[COMPILING] foo v0.0.1 ([CWD])
+[WARNING] the sandbox denied writing to `[ROOT]/dir`
+[WARNING] the sandbox denied writing to `[ROOT]/from-sh`
+[WARNING] the sandbox denied writing to `[ROOT]/relative`
+[FINISHED] [..]
+",
+        )
+        .run();
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+#[cargo_test]
+fn invalid_allow_filesystem_spec() {
+    let p = project()
+        .file("src/lib.rs", "")
+        .file(
+            ".cargo/config",
+            r#"
+                [sandbox]
+                allow-filesystem = ["bar:not a version"]
+            "#,
+        )
+        .build();
+
+    p.cargo("build -Z build-script-sandbox")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains(
+            "[ERROR] invalid package ID specification `bar:not a version` \
+             in `sandbox.allow-filesystem`",
+        )
+        .run();
+}
+
+#[cfg(windows)]
+#[cargo_test]
+fn unsupported_platform() {
+    let p = project()
+        .file("Cargo.toml", &basic_manifest("foo", "0.0.1"))
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("build -Z build-script-sandbox")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "[ERROR] restricting the writes of build scripts isn't supported on this platform",
+        )
+        .run();
+}
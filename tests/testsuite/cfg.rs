@@ -176,6 +176,8 @@ fn bad_target_spec() {
 
 Caused by:
   failed to parse `4` as a cfg expression: unexpected character `4` in cfg, [..]
+    4
+    ^
 ",
         )
         .run();
@@ -207,6 +209,72 @@ fn bad_target_spec2() {
 
 Caused by:
   failed to parse `bar =` as a cfg expression: expected a string, but cfg expression ended
+    bar =
+         ^
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn bad_target_spec_operator() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+
+                [target.'cfg(any(unix, and(windows, target_env = "msvc")))'.dependencies]
+                baz = "0.1.0"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("build")
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] failed to parse manifest at `[..]`
+
+Caused by:
+  failed to parse `any(unix, and(windows, target_env = \"msvc\"))` as a cfg expression: \
+  unknown operator `and`, expected `all`, `any` or `not`
+    any(unix, and(windows, target_env = \"msvc\"))
+              ^^^
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn never_matching_cfg_warns() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+
+                [target.'cfg(all(unix, windows))'.dependencies]
+                bar = { path = "bar" }
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file("bar/Cargo.toml", &basic_manifest("bar", "0.1.0"))
+        .file("bar/src/lib.rs", "")
+        .build();
+
+    p.cargo("build")
+        .with_stderr(
+            "\
+[WARNING] Found `cfg(all(unix, windows))` in `target.'cfg(...)'.dependencies`. \
+This expression is false for every target, so these dependencies will never be used.
+[COMPILING] foo v0.0.1 ([..])
+[FINISHED] [..]
 ",
         )
         .run();
@@ -436,6 +504,8 @@ command was: `[..]compiler[..]--crate-type [..]`
 Caused by:
   failed to parse `123` as a cfg expression: unexpected character `1` in cfg, \
   expected parens, a comma, an identifier, or a string
+    123
+    ^
 ",
         )
         .run();
@@ -0,0 +1,191 @@
+//! Tests for `-Z crate-signing`.
+
+use cargo::util::paseto::{PublicKey, SecretKey};
+use cargo::util::Sha256;
+use cargo_test_support::registry::{self, Package};
+use cargo_test_support::{project, Project};
+use std::convert::TryInto;
+use std::fs;
+
+/// A `foo` project depending on `bar` from the alternative registry, which
+/// trusts the `trusted_keys`.
+fn trusting_project(trusted_keys: &[&SecretKey]) -> Project {
+    let trusted_keys = trusted_keys
+        .iter()
+        .map(|key| format!("\"{}\"", key.public_paserk()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    project()
+        .file(
+            ".cargo/config",
+            &format!(
+                r#"
+                    [registries.alternative]
+                    trusted-keys = [{}]
+                "#,
+                trusted_keys
+            ),
+        )
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [dependencies]
+                bar = { version = "0.1.0", registry = "alternative" }
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build()
+}
+
+#[cargo_test]
+fn publish_signs_crate() {
+    registry::init();
+    let key = SecretKey::generate().unwrap();
+
+    let p = project()
+        .file(
+            ".cargo/config",
+            &format!(
+                r#"
+                    [registries.alternative]
+                    signing-key = "{}"
+                "#,
+                key.to_paserk()
+            ),
+        )
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+                description = "foo"
+                license = "MIT"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("publish --no-verify --registry alternative -Z crate-signing")
+        .masquerade_as_nightly_cargo()
+        .run();
+
+    let upload = fs::read(registry::alt_api_path().join("api/v1/crates/new")).unwrap();
+    let json_len = u32::from_le_bytes(upload[..4].try_into().unwrap()) as usize;
+    let json: serde_json::Value = serde_json::from_slice(&upload[4..4 + json_len]).unwrap();
+    let krate = &upload[4 + json_len + 4..];
+    let cksum = Sha256::new().update(krate).finish_hex();
+
+    let public_key = PublicKey::from_paserk(&key.public_paserk()).unwrap();
+    let (message, footer) = public_key
+        .verify(json["signature"].as_str().unwrap())
+        .unwrap();
+    let message: serde_json::Value = serde_json::from_slice(&message).unwrap();
+    assert_eq!(
+        message,
+        serde_json::json!({"name": "foo", "vers": "0.1.0", "cksum": cksum, "v": 1})
+    );
+    let footer: serde_json::Value = serde_json::from_slice(&footer).unwrap();
+    assert_eq!(footer, serde_json::json!({ "kip": key.public_id() }));
+
+    // Nothing is signed without `-Z crate-signing`.
+    p.change_file(
+        "Cargo.toml",
+        &fs::read_to_string(p.root().join("Cargo.toml"))
+            .unwrap()
+            .replace("0.1.0", "0.2.0"),
+    );
+    p.cargo("publish --no-verify --registry alternative").run();
+    let upload = fs::read(registry::alt_api_path().join("api/v1/crates/new")).unwrap();
+    let json_len = u32::from_le_bytes(upload[..4].try_into().unwrap()) as usize;
+    let json: serde_json::Value = serde_json::from_slice(&upload[4..4 + json_len]).unwrap();
+    assert!(json.get("signature").is_none());
+}
+
+#[cargo_test]
+fn downloads_signed_by_trusted_key() {
+    let key = SecretKey::generate().unwrap();
+    let other_key = SecretKey::generate().unwrap();
+    Package::new("bar", "0.1.0")
+        .alternative(true)
+        .signed(&key.to_paserk())
+        .publish();
+
+    let p = trusting_project(&[&other_key, &key]);
+    p.cargo("build -Z crate-signing")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[DOWNLOADING] crates ...
+[DOWNLOADED] bar v0.1.0 (registry `[..]`)
+[COMPILING] bar v0.1.0 (registry `[..]`)
+[COMPILING] foo v0.1.0 ([CWD])
+[FINISHED] [..]
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn rejects_unsigned_and_untrusted() {
+    let key = SecretKey::generate().unwrap();
+    let other_key = SecretKey::generate().unwrap();
+    Package::new("bar", "0.1.0").alternative(true).publish();
+
+    let p = trusting_project(&[&key]);
+    p.cargo("build -Z crate-signing")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains(
+            "\
+[ERROR] failed to download `bar v0.1.0 (registry `[..]`)`
+
+Caused by:
+  unable to get packages from source
+
+Caused by:
+  failed to verify the signature of `bar v0.1.0 (registry `[..]`)`
+
+Caused by:
+  the registry has no signature for it
+",
+        )
+        .run();
+
+    // Checked again for crates which were already downloaded.
+    p.cargo("build").run();
+    p.cargo("build -Z crate-signing")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains("  the registry has no signature for it")
+        .run();
+
+    Package::new("bar", "0.1.1")
+        .alternative(true)
+        .signed(&other_key.to_paserk())
+        .publish();
+    p.cargo("update -p bar").run();
+    p.cargo("build -Z crate-signing")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains(
+            "\
+[ERROR] failed to download `bar v0.1.1 (registry `[..]`)`
+
+Caused by:
+  unable to get packages from source
+
+Caused by:
+  failed to verify the signature of `bar v0.1.1 (registry `[..]`)`
+
+Caused by:
+  it isn't signed by any of the trusted keys of the registry
+",
+        )
+        .run();
+}
@@ -1116,3 +1116,115 @@ fn doctest_xcompile_linker() {
         ))
         .run();
 }
+
+#[cargo_test]
+fn doctest_xcompile_runner() {
+    if cross_compile::disabled() {
+        return;
+    }
+    if !is_nightly() {
+        // rustdoc needs unstable options to use the runner.
+        return;
+    }
+
+    let target = cross_compile::alternate();
+    let p = project()
+        .file(
+            ".cargo/config",
+            &format!(
+                r#"
+                    [target.{}]
+                    runner = "nonexistent-runner"
+                "#,
+                target
+            ),
+        )
+        .file("Cargo.toml", &basic_manifest("foo", "0.1.0"))
+        .file(
+            "src/lib.rs",
+            r#"
+                /// ```
+                /// assert_eq!(1, 1);
+                /// ```
+                pub fn foo() {}
+            "#,
+        )
+        .build();
+
+    // Fails because `nonexistent-runner` doesn't actually exist.
+    p.cargo("test --doc -v --target")
+        .arg(&target)
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains(&format!(
+            "\
+[RUNNING] `rustdoc --crate-type lib --crate-name foo --test [..]\
+    --target {target} [..]--runtool nonexistent-runner[..]
+",
+            target = target,
+        ))
+        .run();
+}
+
+#[cargo_test]
+fn doctest_xcompile_skipped() {
+    if cross_compile::disabled() {
+        return;
+    }
+
+    let target = cross_compile::alternate();
+    let p = project()
+        .file("Cargo.toml", &basic_manifest("foo", "0.1.0"))
+        .file(
+            "src/lib.rs",
+            r#"
+                /// ```
+                /// assert_eq!(1, 1);
+                /// ```
+                pub fn foo() {}
+            "#,
+        )
+        .build();
+
+    p.cargo("test --doc -v --target")
+        .arg(&target)
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains(&format!(
+            "[NOTE] skipping the doc tests of `foo` for target `{}`, since it has no runner",
+            target
+        ))
+        .with_stderr_does_not_contain("[DOCTEST] foo")
+        .run();
+
+    p.change_file(
+        ".cargo/config",
+        &format!(
+            r#"
+                [target.{}]
+                runner = "nonexistent-runner"
+                run-doctests = false
+            "#,
+            target
+        ),
+    );
+    p.cargo("test --doc -v --target")
+        .arg(&target)
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains(&format!(
+            "[NOTE] skipping the doc tests of `foo` for target `{target}`, since \
+             `target.{target}.run-doctests` is false in [..]config",
+            target = target
+        ))
+        .with_stderr_does_not_contain("[DOCTEST] foo")
+        .run();
+
+    // Without the nightly channel, rustdoc can't use the runner.
+    p.cargo("test --doc -v --target")
+        .arg(&target)
+        .with_stderr_contains(&format!(
+            "[NOTE] skipping the doc tests of `foo` for target `{}`, since running \
+             them through its runner requires the nightly channel",
+            target
+        ))
+        .run();
+}
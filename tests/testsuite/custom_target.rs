@@ -3,6 +3,38 @@
 use cargo_test_support::is_nightly;
 use cargo_test_support::{basic_manifest, project};
 
+const MINIMAL_LIB: &str = r#"
+    #![feature(no_core)]
+    #![feature(lang_items)]
+    #![no_core]
+
+    pub fn foo() -> u32 {
+        42
+    }
+
+    #[lang = "sized"]
+    pub trait Sized {
+        // Empty.
+    }
+    #[lang = "copy"]
+    pub trait Copy {
+        // Empty.
+    }
+"#;
+
+const SIMPLE_SPEC: &str = r#"
+    {
+        "llvm-target": "x86_64-unknown-none-gnu",
+        "data-layout": "e-m:e-i64:64-f80:128-n8:16:32:64-S128",
+        "arch": "x86_64",
+        "target-endian": "little",
+        "target-pointer-width": "64",
+        "target-c-int-width": "32",
+        "os": "none",
+        "linker-flavor": "ld.lld"
+    }
+"#;
+
 #[cargo_test]
 fn custom_target_minimal() {
     if !is_nightly() {
@@ -185,3 +217,114 @@ fn custom_bin_target() {
 
     p.cargo("build --target custom-bin-target.json -v").run();
 }
+
+#[cargo_test]
+fn custom_target_relative_to_workspace_root() {
+    if !is_nightly() {
+        // Requires features no_core, lang_items
+        return;
+    }
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [workspace]
+                members = ["foo"]
+            "#,
+        )
+        .file("foo/Cargo.toml", &basic_manifest("foo", "0.0.1"))
+        .file("foo/src/lib.rs", MINIMAL_LIB)
+        .file("custom-target.json", SIMPLE_SPEC)
+        .build();
+
+    p.cargo("build --lib --target custom-target.json -v")
+        .cwd("foo")
+        .with_stderr_contains("[RUNNING] `rustc [..]--target [..]/custom-target.json[..]")
+        .run();
+}
+
+#[cargo_test]
+fn custom_target_spec_changes() {
+    if !is_nightly() {
+        // Requires features no_core, lang_items
+        return;
+    }
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+                build = "build.rs"
+            "#,
+        )
+        .file("src/lib.rs", MINIMAL_LIB)
+        .file(
+            "build.rs",
+            r#"
+                fn main() {
+                    let spec = std::env::var("TARGET_SPEC_PATH").unwrap();
+                    assert!(spec.ends_with("custom-target.json"));
+                    assert!(std::path::Path::new(&spec).exists());
+                }
+            "#,
+        )
+        .file("custom-target.json", SIMPLE_SPEC)
+        .build();
+
+    p.cargo("build --lib --target custom-target.json").run();
+    p.cargo("build --lib --target custom-target.json")
+        .with_stderr("[FINISHED] [..]")
+        .run();
+
+    p.change_file(
+        "custom-target.json",
+        &SIMPLE_SPEC.replace(r#""os": "none","#, r#""os": "none", "vendor": "unknown","#),
+    );
+    p.cargo("build --lib --target custom-target.json -v")
+        .with_stderr_contains("[RUNNING] `[..]/build-script-build`")
+        .with_stderr_contains("[COMPILING] foo v0.0.1 [..]")
+        .run();
+}
+
+#[cargo_test]
+fn custom_target_per_package() {
+    if !is_nightly() {
+        // Requires features no_core, lang_items
+        return;
+    }
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [workspace]
+                members = ["foo", "bar"]
+            "#,
+        )
+        .file("foo/Cargo.toml", &basic_manifest("foo", "0.0.1"))
+        .file("foo/src/lib.rs", MINIMAL_LIB)
+        .file("bar/Cargo.toml", &basic_manifest("bar", "0.0.1"))
+        .file("bar/src/lib.rs", "")
+        .file("custom-target.json", SIMPLE_SPEC)
+        .file(
+            ".cargo/config",
+            r#"
+                [build.package-targets]
+                foo = "custom-target.json"
+            "#,
+        )
+        .build();
+
+    p.cargo("build -v")
+        .with_stderr_contains(
+            "[RUNNING] `rustc --crate-name foo [..]--target [..]/custom-target.json[..]",
+        )
+        .with_stderr_line_without(&["[RUNNING] `rustc --crate-name bar"], &["--target"])
+        .run();
+    assert!(p
+        .root()
+        .join("target/custom-target/debug/libfoo.rlib")
+        .is_file());
+    assert!(p.root().join("target/debug/libbar.rlib").is_file());
+}
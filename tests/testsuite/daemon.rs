@@ -0,0 +1,345 @@
+//! Tests for the `cargo daemon` command.
+
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::process::{Child, Stdio};
+
+use cargo_test_support::{basic_manifest, project, Project};
+use serde_json::{json, Value};
+
+/// A connection to a daemon, which may not be authenticated.
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Connection {
+    fn open(addr: &str) -> Connection {
+        let stream = TcpStream::connect(addr).unwrap();
+        Connection {
+            reader: BufReader::new(stream.try_clone().unwrap()),
+            writer: stream,
+        }
+    }
+
+    fn send_raw(&mut self, line: &str) -> Value {
+        self.writer.write_all(line.as_bytes()).unwrap();
+        self.writer.write_all(b"\n").unwrap();
+        let mut response = String::new();
+        self.reader.read_line(&mut response).unwrap();
+        serde_json::from_str(&response).unwrap()
+    }
+
+    /// Whether the daemon closed the connection.
+    fn is_closed(&mut self) -> bool {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(n) => n == 0,
+            Err(_) => true,
+        }
+    }
+
+    fn authenticate(&mut self, token: &str) -> Value {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "method": "authenticate",
+            "params": { "token": token },
+        });
+        self.send_raw(&request.to_string())
+    }
+}
+
+/// A running daemon, with an authenticated connection to it.
+struct Client {
+    child: Child,
+    addr: String,
+    token_file: PathBuf,
+    token: String,
+    connection: Connection,
+    next_id: u64,
+}
+
+impl Client {
+    fn start(p: &Project) -> Client {
+        let mut child = p
+            .cargo("daemon -Zunstable-options")
+            .masquerade_as_nightly_cargo()
+            .build_command()
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut line = String::new();
+        BufReader::new(child.stdout.as_mut().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        let listening: Value = serde_json::from_str(&line).unwrap();
+        let addr = listening["listening"].as_str().unwrap().to_string();
+        let token_file = PathBuf::from(listening["token_file"].as_str().unwrap());
+        let token = fs::read_to_string(&token_file).unwrap();
+        let mut connection = Connection::open(&addr);
+        let response = connection.authenticate(&token);
+        assert_eq!(response["result"], Value::Null, "{}", response);
+        Client {
+            child,
+            addr,
+            token_file,
+            token,
+            connection,
+            next_id: 0,
+        }
+    }
+
+    fn send_raw(&mut self, line: &str) -> Value {
+        self.connection.send_raw(line)
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": self.next_id,
+            "method": method,
+            "params": params,
+        });
+        let response = self.send_raw(&request.to_string());
+        assert_eq!(response["id"], self.next_id, "{}", response);
+        response
+    }
+
+    fn shutdown(mut self) {
+        let response = self.request("shutdown", Value::Null);
+        assert_eq!(response["result"], Value::Null);
+        assert!(self.child.wait().unwrap().success());
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        // Don't leave the daemon running when a test fails.
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cargo_test]
+fn daemon_requires_unstable_options() {
+    let p = project().file("src/lib.rs", "").build();
+
+    p.cargo("daemon")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr("[ERROR] `cargo daemon` requires `-Z unstable-options`")
+        .run();
+}
+
+#[cargo_test]
+fn listen_only_on_loopback() {
+    let p = project().file("src/lib.rs", "").build();
+
+    p.cargo("daemon -Zunstable-options --listen 0.0.0.0:0")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stdout("")
+        .with_stderr(
+            "[ERROR] cannot listen on `0.0.0.0:0`: `cargo daemon` only listens on \
+             loopback addresses, like `127.0.0.1`, since its connections aren't \
+             encrypted and can run build scripts",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn authentication() {
+    let p = project().file("src/lib.rs", "").build();
+    let mut client = Client::start(&p);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&client.token_file)
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    // Requests are rejected before `authenticate`, and close the connection.
+    let mut connection = Connection::open(&client.addr);
+    let response = connection.send_raw(r#"{"jsonrpc":"2.0","id":1,"method":"shutdown"}"#);
+    assert_eq!(response["error"]["code"], -32001);
+    assert_eq!(
+        response["error"]["message"],
+        "the first request must be `authenticate`"
+    );
+    assert!(connection.is_closed());
+
+    let mut connection = Connection::open(&client.addr);
+    let response = connection.authenticate("not the token");
+    assert_eq!(response["error"]["code"], -32001);
+    assert_eq!(response["error"]["message"], "invalid token");
+    assert!(connection.is_closed());
+
+    // Idle connections don't block the others.
+    let _idle = Connection::open(&client.addr);
+    let mut idle_authenticated = Connection::open(&client.addr);
+    let response = idle_authenticated.authenticate(&client.token);
+    assert_eq!(response["result"], Value::Null, "{}", response);
+    let response = client.request("metadata", json!({ "no_deps": true }));
+    assert_eq!(response["result"]["version"], 1, "{}", response);
+
+    let token_file = client.token_file.clone();
+    client.shutdown();
+    assert!(!token_file.exists());
+}
+
+#[cargo_test]
+fn check_and_metadata() {
+    let p = project()
+        .file("Cargo.toml", &basic_manifest("foo", "0.1.0"))
+        .file("src/lib.rs", "pub fn f() {}")
+        .build();
+    let mut client = Client::start(&p);
+
+    let response = client.request("check", json!({}));
+    let result = &response["result"];
+    assert_eq!(result["success"], true, "{}", response);
+    let reasons: Vec<&str> = result["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["reason"].as_str().unwrap())
+        .collect();
+    assert_eq!(reasons, ["compiler-artifact", "build-finished"]);
+
+    // A failing check still returns the diagnostics.
+    p.change_file("src/lib.rs", "pub fn f() -> u32 { \"\" }");
+    let response = client.request("check", json!({}));
+    let result = &response["result"];
+    assert_eq!(result["success"], false, "{}", response);
+    let message = result["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|m| m["reason"] == "compiler-message")
+        .unwrap();
+    assert_eq!(message["message"]["level"], "error");
+    assert!(result["output"]
+        .as_str()
+        .unwrap()
+        .contains("could not compile `foo`"));
+
+    let response = client.request("metadata", json!({ "no_deps": true }));
+    assert_eq!(response["result"]["packages"][0]["name"], "foo");
+    assert_eq!(response["result"]["packages"][0]["version"], "0.1.0");
+
+    // Changes of the manifest are seen by the next request.
+    p.change_file("Cargo.toml", &basic_manifest("foo", "0.2.0"));
+    let response = client.request("metadata", json!({ "no_deps": true }));
+    assert_eq!(response["result"]["packages"][0]["version"], "0.2.0");
+
+    client.shutdown();
+}
+
+#[cargo_test]
+fn member_added_to_glob() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [workspace]
+                members = ["crates/*"]
+            "#,
+        )
+        .file("crates/a/Cargo.toml", &basic_manifest("a", "0.1.0"))
+        .file("crates/a/src/lib.rs", "")
+        .build();
+    let mut client = Client::start(&p);
+
+    let names = |response: &Value| -> Vec<String> {
+        let mut names: Vec<String> = response["result"]["packages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|pkg| pkg["name"].as_str().unwrap().to_string())
+            .collect();
+        names.sort();
+        names
+    };
+    let response = client.request("metadata", json!({ "no_deps": true }));
+    assert_eq!(names(&response), ["a"], "{}", response);
+
+    // A new member matching the glob is seen by the next request, even though
+    // no manifest of the workspace changed.
+    fs::create_dir_all(p.root().join("crates/b/src")).unwrap();
+    fs::write(
+        p.root().join("crates/b/Cargo.toml"),
+        basic_manifest("b", "0.1.0"),
+    )
+    .unwrap();
+    fs::write(p.root().join("crates/b/src/lib.rs"), "").unwrap();
+    let response = client.request("metadata", json!({ "no_deps": true }));
+    assert_eq!(names(&response), ["a", "b"], "{}", response);
+
+    fs::remove_dir_all(p.root().join("crates/a")).unwrap();
+    let response = client.request("metadata", json!({ "no_deps": true }));
+    assert_eq!(names(&response), ["b"], "{}", response);
+
+    client.shutdown();
+}
+
+#[cargo_test]
+fn unit_graph() {
+    let p = project()
+        .file("Cargo.toml", &basic_manifest("foo", "0.1.0"))
+        .file("src/main.rs", "fn main() {}")
+        .build();
+    let mut client = Client::start(&p);
+
+    let response = client.request("unit-graph", json!({}));
+    let result = &response["result"];
+    assert_eq!(result["version"], 1, "{}", response);
+    assert_eq!(result["units"][0]["target"]["name"], "foo");
+    assert!(!p.bin("foo").exists());
+
+    client.shutdown();
+}
+
+#[cargo_test]
+fn errors() {
+    let p = project().file("src/lib.rs", "").build();
+    let mut client = Client::start(&p);
+
+    let response = client.send_raw("{ not json");
+    assert_eq!(response["error"]["code"], -32700);
+    assert_eq!(response["id"], Value::Null);
+
+    let response = client.send_raw(r#"{"jsonrpc":"2.0","id":1}"#);
+    assert_eq!(response["error"]["code"], -32600);
+
+    let response = client.request("build", json!({}));
+    assert_eq!(response["error"]["code"], -32601);
+    assert_eq!(response["error"]["message"], "unknown method `build`");
+
+    let response = client.request("check", json!({ "jobs": 2 }));
+    assert_eq!(response["error"]["code"], -32602);
+
+    let response = client.request("check", json!({ "packages": ["bar"] }));
+    assert_eq!(response["result"]["success"], false);
+    assert!(response["result"]["output"]
+        .as_str()
+        .unwrap()
+        .contains("package ID specification `bar` matched no packages"));
+
+    p.change_file("Cargo.toml", "[package]");
+    let response = client.request("metadata", json!({}));
+    assert_eq!(response["error"]["code"], -32000);
+    assert!(response["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("failed to parse manifest"));
+
+    client.shutdown();
+}
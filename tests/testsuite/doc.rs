@@ -1708,3 +1708,149 @@ test result: ok. 1 passed; 0 failed; 0 ignored; 0 measured; 0 filtered out[..]
         )
         .run();
 }
+
+#[cargo_test]
+fn doc_table_cfg() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                cargo-features = ["doc-table"]
+
+                [package]
+                name = "foo"
+                version = "0.0.1"
+
+                [doc]
+                cfg = ["docsrs"]
+                rustdoc-args = ["--cfg", "extra"]
+            "#,
+        )
+        .file(
+            "src/lib.rs",
+            r#"
+                #[cfg(all(docsrs, extra))]
+                pub fn only_in_docs() {}
+            "#,
+        )
+        .build();
+
+    p.cargo("doc -v")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains("[RUNNING] `rustdoc [..]--cfg docsrs --cfg extra [..]")
+        .run();
+    assert!(p
+        .root()
+        .join("target/doc/foo/fn.only_in_docs.html")
+        .is_file());
+
+    p.cargo("build -v")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_does_not_contain("[..]docsrs[..]")
+        .run();
+}
+
+#[cargo_test]
+fn doc_table_requires_feature() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+
+                [doc]
+                cfg = ["docsrs"]
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("doc")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains("  feature `doc-table` is required")
+        .run();
+}
+
+#[cargo_test]
+fn features_matrix() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                cargo-features = ["doc-table"]
+
+                [package]
+                name = "foo"
+                version = "0.0.1"
+
+                [features]
+                a = []
+                b = []
+
+                [doc]
+                features-matrix = [[], ["a", "b"]]
+            "#,
+        )
+        .file(
+            "src/lib.rs",
+            r#"
+                #[cfg(all(feature = "a", not(feature = "b")))]
+                compile_error!("`a` requires `b`");
+            "#,
+        )
+        .build();
+
+    p.cargo("doc --all-features --features-matrix")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] the `--features-matrix` flag is unstable, pass `-Z unstable-options` to enable it
+See https://doc.rust-lang.org/nightly/cargo/reference/unstable.html \
+for more information about the `--features-matrix` flag.
+",
+        )
+        .run();
+
+    p.cargo("doc --all-features --features-matrix -Z unstable-options")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[FEATURES] foo []
+[DOCUMENTING] foo v0.0.1 ([CWD])
+[FINISHED] [..]
+[FEATURES] foo [a, b]
+[DOCUMENTING] foo v0.0.1 ([CWD])
+[FINISHED] [..]
+[FINISHED] [..]
+",
+        )
+        .run();
+
+    p.change_file(
+        "Cargo.toml",
+        r#"
+            cargo-features = ["doc-table"]
+
+            [package]
+            name = "foo"
+            version = "0.0.1"
+
+            [features]
+            a = []
+            b = []
+
+            [doc]
+            features-matrix = [["a"]]
+        "#,
+    );
+    p.cargo("doc --all-features --features-matrix -Z unstable-options")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains("[ERROR] failed to document `foo` with the features [a]")
+        .with_stderr_contains("[..]`a` requires `b`[..]")
+        .run();
+}
@@ -0,0 +1,284 @@
+//! Tests for the `cargo expand` command.
+//!
+//! `cargo expand` needs a nightly rustc, so the tests set `RUSTC_BOOTSTRAP`
+//! to run on any toolchain.
+
+use std::env;
+use std::ffi::OsString;
+
+use cargo_test_support::{basic_manifest, is_nightly, project, Project};
+
+fn macro_project() -> Project {
+    project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [features]
+                extra = []
+            "#,
+        )
+        .file(
+            "src/lib.rs",
+            r#"
+                macro_rules! answer {
+                    ($name:ident) => { pub fn $name() -> u32 { 42 } };
+                }
+
+                pub mod inner {
+                    answer!(the_answer);
+
+                    #[cfg(feature = "extra")]
+                    pub fn extra() {}
+                }
+
+                pub struct Unit;
+            "#,
+        )
+        .file("src/main.rs", "fn main() {}")
+        .build()
+}
+
+#[cargo_test]
+fn expand_requires_unstable_options() {
+    let p = project().file("src/lib.rs", "").build();
+
+    p.cargo("expand")
+        .with_status(101)
+        .with_stderr("[ERROR] `cargo expand` requires `-Z unstable-options`")
+        .run();
+}
+
+#[cargo_test]
+fn expand_lib_by_default() {
+    let p = macro_project();
+
+    p.cargo("expand -Z unstable-options --ugly")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .with_stdout_contains("pub mod inner {")
+        .with_stdout_contains("    pub fn the_answer() -> u32 { 42 }")
+        .with_stdout_contains("pub struct Unit;")
+        .with_stdout_does_not_contain("[..]extra[..]")
+        .with_stderr(
+            "\
+[CHECKING] foo v0.1.0 ([CWD])
+[FINISHED] [..]
+",
+        )
+        .run();
+
+    // The code is printed again, even though nothing changed.
+    p.cargo("expand -Z unstable-options --ugly --features extra")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .with_stdout_contains("    pub fn extra() {}")
+        .run();
+    p.cargo("expand -Z unstable-options --ugly --features extra")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .with_stdout_contains("    pub fn extra() {}")
+        .run();
+
+    // A normal check isn't confused by the expansion.
+    p.cargo("check")
+        .with_stderr(
+            "\
+[CHECKING] foo v0.1.0 ([CWD])
+[FINISHED] [..]
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn expand_item() {
+    let p = macro_project();
+
+    p.cargo("expand -Z unstable-options --ugly inner::the_answer")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .with_stdout("pub fn the_answer() -> u32 { 42 }")
+        .run();
+    p.cargo("expand -Z unstable-options --ugly Unit")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .with_stdout("pub struct Unit;")
+        .run();
+    p.cargo("expand -Z unstable-options --ugly the_answer")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .with_status(101)
+        .with_stderr_contains("[ERROR] no item `the_answer` in the expanded code")
+        .run();
+    // Only modules contain items.
+    p.cargo("expand -Z unstable-options --ugly Unit::the_answer")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .with_status(101)
+        .with_stderr_contains("[ERROR] no item `Unit::the_answer` in the expanded code")
+        .run();
+}
+
+#[cargo_test]
+fn expand_bin() {
+    let p = project()
+        .file(
+            "src/main.rs",
+            r#"fn main() { assert!(cfg!(debug_assertions)); }"#,
+        )
+        .file("src/bin/other.rs", "fn main() {}")
+        .build();
+
+    p.cargo("expand -Z unstable-options --ugly")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .with_status(101)
+        .with_stderr_contains("[..]can only be passed to one target[..]")
+        .run();
+    p.cargo("expand -Z unstable-options --ugly --bin foo main")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .with_stdout_contains("fn main() {")
+        .with_stdout_does_not_contain("[..]assert![..]")
+        .run();
+}
+
+#[cargo_test]
+fn rustfmt_failure() {
+    let p = project().file("src/lib.rs", "pub struct Unit;").build();
+
+    p.cargo("expand -Z unstable-options Unit")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .env("RUSTFMT", "does-not-exist")
+        .with_stdout("pub struct Unit;")
+        .with_stderr(
+            "\
+[CHECKING] foo v0.0.1 ([CWD])
+[FINISHED] [..]
+[WARNING] failed to format the expanded code, printing it as is: \
+failed to run `does-not-exist --edition 2015`: [..]
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn expand_requires_nightly_rustc() {
+    if is_nightly() {
+        return;
+    }
+    let p = project().file("src/lib.rs", "").build();
+
+    p.cargo("expand -Z unstable-options")
+        .masquerade_as_nightly_cargo()
+        .env_remove("RUSTC_BOOTSTRAP")
+        .with_status(101)
+        .with_stderr(
+            "[ERROR] `cargo expand` requires a nightly rustc, since it uses the unstable \
+             `-Z unpretty=expanded` flag, but `[..]` is rustc [..]",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn only_the_expanded_target_is_rebuilt() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [dependencies]
+                bar = { path = "bar" }
+            "#,
+        )
+        .file("src/lib.rs", "extern crate bar;")
+        .file("src/main.rs", "fn main() {}")
+        .file("bar/Cargo.toml", &basic_manifest("bar", "0.1.0"))
+        .file("bar/src/lib.rs", "pub struct Bar;")
+        .build();
+
+    p.cargo("expand -Z unstable-options --ugly --bin foo")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .with_stdout_contains("fn main() {}")
+        .with_stderr(
+            "\
+[CHECKING] bar v0.1.0 ([CWD]/bar)
+[CHECKING] foo v0.1.0 ([CWD])
+[FINISHED] [..]
+",
+        )
+        .run();
+    // `bar` and the library of `foo` are fresh, only the binary is expanded
+    // again.
+    p.cargo("expand -Z unstable-options --ugly --bin foo -v")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .with_stdout_contains("fn main() {}")
+        .with_stderr(
+            "\
+[FRESH] bar v0.1.0 ([CWD]/bar)
+[CHECKING] foo v0.1.0 ([CWD])
+[RUNNING] `rustc --crate-name foo src/main.rs [..]-Zunpretty=expanded[..]`
+[FINISHED] [..]
+",
+        )
+        .run();
+}
+
+/// Builds a `cargo-expand` executable printing its arguments, and returns a
+/// `PATH` including it.
+fn external_cargo_expand() -> OsString {
+    let external = project()
+        .at("cargo-expand")
+        .file("Cargo.toml", &basic_manifest("cargo-expand", "1.0.0"))
+        .file(
+            "src/main.rs",
+            r#"
+                fn main() {
+                    let args: Vec<_> = std::env::args().skip(1).collect();
+                    println!("external {:?}", args);
+                }
+            "#,
+        )
+        .build();
+    external.cargo("build").run();
+    let mut path: Vec<_> = env::split_paths(&env::var_os("PATH").unwrap_or_default()).collect();
+    path.insert(0, external.target_debug_dir());
+    env::join_paths(path).unwrap()
+}
+
+#[cargo_test]
+fn external_cargo_expand_takes_precedence() {
+    let path = external_cargo_expand();
+    let p = project().file("src/lib.rs", "").build();
+
+    // Its options aren't parsed by cargo, even those `cargo expand` doesn't
+    // have.
+    p.cargo("expand --lib inner --theme=GitHub")
+        .env("PATH", &path)
+        .with_stdout(r#"external ["expand", "--lib", "inner", "--theme=GitHub"]"#)
+        .run();
+    p.cargo("-v expand --manifest-path Cargo.toml --ugly")
+        .env("PATH", &path)
+        .with_stdout(r#"external ["expand", "--manifest-path", "Cargo.toml", "--ugly"]"#)
+        .run();
+    p.cargo("expand -Z unstable-options --ugly --lib")
+        .masquerade_as_nightly_cargo()
+        .env("PATH", &path)
+        .with_stdout(r#"external ["expand", "-Z", "unstable-options", "--ugly", "--lib"]"#)
+        .run();
+    p.cargo("--list")
+        .env("PATH", &path)
+        .with_stdout_contains("    expand")
+        .with_stdout_does_not_contain("    expand [..]")
+        .run();
+}
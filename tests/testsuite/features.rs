@@ -35,43 +35,6 @@ Caused by:
         .run();
 }
 
-#[cargo_test]
-fn invalid2() {
-    let p = project()
-        .file(
-            "Cargo.toml",
-            r#"
-                [project]
-                name = "foo"
-                version = "0.0.1"
-                authors = []
-
-                [features]
-                bar = ["baz"]
-                baz = []
-
-                [dependencies.bar]
-                path = "bar"
-            "#,
-        )
-        .file("src/main.rs", "")
-        .file("bar/Cargo.toml", &basic_manifest("bar", "1.0.0"))
-        .file("bar/src/lib.rs", "")
-        .build();
-
-    p.cargo("build")
-        .with_status(101)
-        .with_stderr(
-            "\
-[ERROR] failed to parse manifest at `[..]`
-
-Caused by:
-  features and dependencies cannot have the same name: `bar`
-",
-        )
-        .run();
-}
-
 #[cargo_test]
 fn invalid3() {
     let p = project()
@@ -183,6 +146,78 @@ Caused by:
         .run();
 }
 
+#[cargo_test]
+fn optional_dev_dependency() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                cargo-features = ["optional-dev-dependencies"]
+
+                [project]
+                name = "foo"
+                version = "0.0.1"
+                authors = []
+
+                [features]
+                slow-tests = ["bar"]
+
+                [dev-dependencies.bar]
+                path = "bar"
+                optional = true
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file(
+            "tests/t.rs",
+            r#"
+                #[cfg(feature = "slow-tests")]
+                #[test]
+                fn slow() { bar::bar(); }
+            "#,
+        )
+        .file("bar/Cargo.toml", &basic_manifest("bar", "0.0.1"))
+        .file("bar/src/lib.rs", "pub fn bar() {}")
+        .build();
+
+    p.cargo("test")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[COMPILING] foo v0.0.1 ([CWD])
+[FINISHED] [..]
+[RUNNING] [..]
+[RUNNING] [..]
+[DOCTEST] foo
+",
+        )
+        .run();
+
+    // The dev-dependency is only used by tests.
+    p.cargo("build --features slow-tests")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[COMPILING] foo v0.0.1 ([CWD])
+[FINISHED] [..]
+",
+        )
+        .run();
+
+    p.cargo("test --features slow-tests --test t")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[COMPILING] bar v0.0.1 ([CWD]/bar)
+[COMPILING] foo v0.0.1 ([CWD])
+[FINISHED] [..]
+[RUNNING] [..]
+",
+        )
+        .with_stdout_contains("test slow ... ok")
+        .run();
+}
+
 #[cargo_test]
 fn invalid6() {
     let p = project()
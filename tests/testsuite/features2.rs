@@ -1296,8 +1296,8 @@ fn build_dep_activated() {
 }
 
 #[cargo_test]
-fn resolver_gated() {
-    // Check that `resolver` field is feature gated.
+fn resolver_stable() {
+    // The `resolver` field no longer requires a `cargo-features` opt-in.
     let p = project()
         .file(
             "Cargo.toml",
@@ -1311,20 +1311,7 @@ fn resolver_gated() {
         .file("src/lib.rs", "")
         .build();
 
-    p.cargo("build")
-        .masquerade_as_nightly_cargo()
-        .with_status(101)
-        .with_stderr(
-            "\
-error: failed to parse manifest at `[..]/foo/Cargo.toml`
-
-Caused by:
-  feature `resolver` is required
-
-  consider adding `cargo-features = [\"resolver\"]` to the manifest
-",
-        )
-        .run();
+    p.cargo("build").run();
 
     // Test with virtual ws.
     let p = project()
@@ -1340,17 +1327,34 @@ Caused by:
         .file("a/src/lib.rs", "")
         .build();
 
+    p.cargo("build").run();
+}
+
+#[cargo_test]
+fn resolver_feature_warns() {
+    // Listing the now-stable `resolver` cargo feature is harmless.
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+            cargo-features = ["resolver"]
+
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            resolver = "2"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
     p.cargo("build")
-        .masquerade_as_nightly_cargo()
-        .with_status(101)
         .with_stderr(
             "\
-error: failed to parse manifest at `[..]/foo/Cargo.toml`
-
-Caused by:
-  feature `resolver` is required
-
-  consider adding `cargo-features = [\"resolver\"]` to the manifest
+[WARNING] the cargo feature `resolver` is now stable and is no longer \
+necessary to be listed in the manifest
+[COMPILING] foo v0.1.0 [..]
+[FINISHED] [..]
 ",
         )
         .run();
@@ -1363,7 +1367,6 @@ fn resolver_bad_setting() {
         .file(
             "Cargo.toml",
             r#"
-            cargo-features = ["resolver"]
             [package]
             name = "foo"
             version = "0.1.0"
@@ -1412,7 +1415,6 @@ fn resolver_original() {
     let manifest = |resolver| {
         format!(
             r#"
-                cargo-features = ["resolver"]
                 [package]
                 name = "foo"
                 version = "0.1.0"
@@ -1449,7 +1451,6 @@ fn resolver_not_both() {
         .file(
             "Cargo.toml",
             r#"
-            cargo-features = ["resolver"]
             [workspace]
             resolver = "2"
             [package]
@@ -1489,7 +1490,6 @@ fn resolver_ws_member() {
         .file(
             "a/Cargo.toml",
             r#"
-            cargo-features = ["resolver"]
             [package]
             name = "a"
             version = "0.1.0"
@@ -1520,7 +1520,6 @@ fn resolver_ws_root_and_member() {
         .file(
             "Cargo.toml",
             r#"
-            cargo-features = ["resolver"]
             [workspace]
             members = ["a"]
             resolver = "2"
@@ -1529,7 +1528,6 @@ fn resolver_ws_root_and_member() {
         .file(
             "a/Cargo.toml",
             r#"
-            cargo-features = ["resolver"]
             [package]
             name = "a"
             version = "0.1.0"
@@ -1578,7 +1576,6 @@ fn resolver_enables_new_features() {
         .file(
             "Cargo.toml",
             r#"
-            cargo-features = ["resolver"]
             [workspace]
             members = ["a", "b"]
             resolver = "2"
@@ -1698,8 +1695,6 @@ fn install_resolve_behavior() {
         .file(
             "Cargo.toml",
             r#"
-            cargo-features = ["resolver"]
-
             [package]
             name = "foo"
             version = "1.0.0"
@@ -1728,7 +1723,6 @@ fn package_includes_resolve_behavior() {
         .file(
             "Cargo.toml",
             r#"
-            cargo-features = ["resolver"]
             [workspace]
             members = ["a"]
             resolver = "2"
@@ -1756,8 +1750,6 @@ fn package_includes_resolve_behavior() {
 
     let rewritten_toml = format!(
         r#"{}
-cargo-features = ["resolver"]
-
 [package]
 name = "a"
 version = "0.1.0"
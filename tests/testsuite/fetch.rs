@@ -115,6 +115,59 @@ fn fetch_platform_specific_dependencies() {
         .run();
 }
 
+#[cargo_test]
+fn fetch_host_dependencies_of_target() {
+    if cross_compile::disabled() {
+        return;
+    }
+
+    let target = cross_compile::alternate();
+    let host = rustc_host();
+    Package::new("macro-dep", "1.0.0")
+        .target_dep("d1", "1.0", &host)
+        .publish();
+    Package::new("macro", "1.0.0")
+        .proc_macro(true)
+        .dep("macro-dep", "1.0")
+        .publish();
+    Package::new("build-dep", "1.0.0")
+        .target_dep("d2", "1.0", &host)
+        .publish();
+    Package::new("d1", "1.0.0").publish();
+    Package::new("d2", "1.0.0").publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+
+                [dependencies]
+                macro = "1.0"
+
+                [build-dependencies]
+                build-dep = "1.0"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    // The proc-macro and the build dependency are built for the host, along
+    // with their host-specific dependencies.
+    p.cargo("fetch --target")
+        .arg(&target)
+        .with_stderr_contains("[DOWNLOADED] d1 v1.0.0 [..]")
+        .with_stderr_contains("[DOWNLOADED] d2 v1.0.0 [..]")
+        .run();
+
+    p.cargo("build --offline --target")
+        .arg(&target)
+        .with_stderr_does_not_contain("[DOWNLOAD[..]")
+        .run();
+}
+
 #[cargo_test]
 fn fetch_warning() {
     let p = project()
@@ -6,7 +6,7 @@ use std::io::prelude::*;
 use std::net::{TcpListener, TcpStream};
 use std::path::Path;
 use std::str;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread;
 
@@ -153,6 +153,67 @@ fn cargo_compile_git_dep_branch() {
         .run();
 }
 
+#[cargo_test]
+fn update_git_dep_deleted_branch() {
+    let git_project = git::new("dep1", |project| {
+        project
+            .file("Cargo.toml", &basic_lib_manifest("dep1"))
+            .file("src/dep1.rs", "")
+    });
+    let repo = git2::Repository::open(&git_project.root()).unwrap();
+    let head = repo.head().unwrap().target().unwrap();
+    let head = repo.find_commit(head).unwrap();
+    let mut branch = repo.branch("branchy", &head, true).unwrap();
+
+    let project = project()
+        .file(
+            "Cargo.toml",
+            &format!(
+                r#"
+                    [project]
+                    name = "foo"
+                    version = "0.5.0"
+                    authors = []
+
+                    [dependencies.dep1]
+                    git = '{}'
+                    branch = "branchy"
+                "#,
+                git_project.url()
+            ),
+        )
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    project.cargo("generate-lockfile").run();
+
+    // The branch is gone, so its copy from the last fetch isn't used.
+    branch.delete().unwrap();
+    project
+        .cargo("update")
+        .with_status(101)
+        .with_stderr(&format!(
+            "\
+[UPDATING] git repository `{url}`
+[ERROR] failed to get `dep1` as a dependency of package `foo v0.5.0 ([CWD])`
+
+Caused by:
+  failed to load source for dependency `dep1`
+
+Caused by:
+  Unable to update {url}?branch=branchy
+
+Caused by:
+  failed to fetch into: [..]
+
+Caused by:
+  the reference `refs/heads/branchy` was deleted from `{url}`
+",
+            url = git_project.url()
+        ))
+        .run();
+}
+
 #[cargo_test]
 fn cargo_compile_git_dep_tag() {
     let project = project();
@@ -778,13 +839,11 @@ fn update_with_shared_deps() {
         .with_status(101)
         .with_stderr(
             "\
+[UPDATING] git repository [..]
 [ERROR] Unable to update [..]
 
 Caused by:
-  precise value for git is not a git revision: 0.1.2
-
-Caused by:
-  unable to parse OID - contains invalid characters; class=Invalid (3)
+  revspec '0.1.2' not found; [..]
 ",
         )
         .run();
@@ -831,6 +890,69 @@ Caused by:
         .run();
 }
 
+#[cargo_test]
+fn update_precise_to_tag_and_short_rev() {
+    let (git_project, repo) = git::new_repo("bar", |project| {
+        project
+            .file("Cargo.toml", &basic_manifest("bar", "0.5.0"))
+            .file("src/lib.rs", "")
+    });
+    let first = repo.head().unwrap().target().unwrap();
+    git::tag(&repo, "v0.5.0");
+
+    git_project.change_file("src/lib.rs", "pub fn new() {}");
+    git::add(&repo);
+    let second = git::commit(&repo);
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            &format!(
+                r#"
+                    [package]
+                    name = "foo"
+                    version = "0.5.0"
+
+                    [dependencies]
+                    bar = {{ git = '{}' }}
+                "#,
+                git_project.url()
+            ),
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("generate-lockfile").run();
+    assert!(p.read_lockfile().contains(&second.to_string()));
+
+    // A tag is resolved to the commit it points to.
+    p.cargo("update -p bar --precise v0.5.0")
+        .with_stderr(format!(
+            "\
+[UPDATING] git repository `{}`
+[UPDATING] bar v0.5.0 ([..]) -> #{}
+",
+            git_project.url(),
+            &first.to_string()[..8]
+        ))
+        .run();
+    assert!(p.read_lockfile().contains(&first.to_string()));
+
+    // So is an abbreviated commit hash.
+    p.cargo("update -p bar --precise")
+        .arg(&second.to_string()[..10])
+        .with_stderr(format!(
+            "\
+[UPDATING] git repository `{}`
+[UPDATING] bar v0.5.0 ([..]) -> #{}
+",
+            git_project.url(),
+            &second.to_string()[..8]
+        ))
+        .run();
+    assert!(p.read_lockfile().contains(&second.to_string()));
+}
+
 #[cargo_test]
 fn dep_with_submodule() {
     let project = project();
@@ -881,6 +1003,142 @@ fn dep_with_submodule() {
         .run();
 }
 
+#[cargo_test]
+fn dep_with_selected_submodules() {
+    let git_project = git::new("dep1", |project| {
+        project
+            .file("Cargo.toml", &basic_manifest("dep1", "0.5.0"))
+            .file("src/lib.rs", "include!(\"../needed/lib.rs\");")
+    });
+    let needed = git::new("needed", |project| {
+        project.file("lib.rs", "pub fn dep() {}")
+    });
+    let huge = git::new("huge", |project| project.file("data.bin", ""));
+
+    let repo = git2::Repository::open(&git_project.root()).unwrap();
+    let url = path2url(needed.root()).to_string();
+    git::add_submodule(&repo, &url, Path::new("needed"));
+    let url = path2url(huge.root()).to_string();
+    git::add_submodule(&repo, &url, Path::new("huge"));
+    git::commit(&repo);
+    // Fetching the skipped submodule would fail.
+    huge.root().rm_rf();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            &format!(
+                r#"
+                    cargo-features = ["git-submodules"]
+
+                    [project]
+                    name = "foo"
+                    version = "0.5.0"
+
+                    [dependencies.dep1]
+                    git = '{}'
+                    submodules = ["needed"]
+                "#,
+                git_project.url()
+            ),
+        )
+        .file(
+            "src/lib.rs",
+            "extern crate dep1; pub fn foo() { dep1::dep() }",
+        )
+        .build();
+
+    p.cargo("build")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[UPDATING] git repository [..]
+[UPDATING] git submodule `file://[..]/needed`
+[COMPILING] dep1 [..]
+[COMPILING] foo [..]
+[FINISHED] dev [unoptimized + debuginfo] target(s) in [..]\n",
+        )
+        .run();
+
+    let lock = p.read_lockfile();
+    assert!(lock.contains("?submodules=needed#"), "{}", lock);
+
+    // The lock file is up to date with the manifest.
+    p.cargo("build --locked")
+        .masquerade_as_nightly_cargo()
+        .with_stderr("[FINISHED] [..]")
+        .run();
+
+    // Skipping all submodules uses another checkout.
+    p.change_file(
+        "Cargo.toml",
+        &format!(
+            r#"
+                cargo-features = ["git-submodules"]
+
+                [project]
+                name = "foo"
+                version = "0.5.0"
+
+                [dependencies.dep1]
+                git = '{}'
+                submodules = false
+            "#,
+            git_project.url()
+        ),
+    );
+    p.cargo("build")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains("[COMPILING] dep1 [..]")
+        .with_stderr_contains("[..]couldn't read [..]needed/lib.rs[..]")
+        .with_stderr_does_not_contain("[UPDATING] git submodule [..]")
+        .run();
+}
+
+#[cargo_test]
+fn dep_with_submodules_requires_feature() {
+    let git_project = git::new("dep1", |project| {
+        project
+            .file("Cargo.toml", &basic_manifest("dep1", "0.5.0"))
+            .file("src/lib.rs", "")
+    });
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            &format!(
+                r#"
+                    [project]
+                    name = "foo"
+                    version = "0.5.0"
+
+                    [dependencies.dep1]
+                    git = '{}'
+                    submodules = false
+                "#,
+                git_project.url()
+            ),
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("build")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] failed to parse manifest at `[..]`
+
+Caused by:
+  feature `git-submodules` is required
+
+  consider adding `cargo-features = [\"git-submodules\"]` to the manifest
+",
+        )
+        .run();
+}
+
 #[cargo_test]
 fn dep_with_bad_submodule() {
     let project = project();
@@ -2290,6 +2548,7 @@ fn include_overrides_gitignore() {
     p.cargo("package --list --allow-dirty")
         .with_stdout(
             "\
+.cargo_vcs_info.json
 Cargo.toml
 Cargo.toml.orig
 ignored.txt
@@ -2514,6 +2773,197 @@ fn use_the_cli() {
     project.cargo("build -v").with_stderr(stderr).run();
 }
 
+#[cargo_test]
+fn use_gitoxide() {
+    let git_project = git::new("dep1", |project| {
+        project
+            .file("Cargo.toml", &basic_manifest("dep1", "0.5.0"))
+            .file("src/lib.rs", "")
+    });
+
+    let project = project()
+        .file(
+            "Cargo.toml",
+            &format!(
+                r#"
+                    [project]
+                    name = "foo"
+                    version = "0.5.0"
+                    authors = []
+
+                    [dependencies]
+                    dep1 = {{ git = '{}' }}
+                "#,
+                git_project.url()
+            ),
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    project
+        .cargo("build -v -Z gitoxide")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[UPDATING] git repository `[..]`
+[FETCHING] [..]dep1 with gitoxide
+[COMPILING] dep1 [..]
+[RUNNING] `rustc [..]`
+[COMPILING] foo [..]
+[RUNNING] `rustc [..]`
+[FINISHED] [..]
+",
+        )
+        .run();
+
+    // New commits are picked up on update.
+    git_project.change_file("src/lib.rs", "pub fn f() {}");
+    let repo = git2::Repository::open(&git_project.root()).unwrap();
+    git::add(&repo);
+    git::commit(&repo);
+    project.change_file("src/lib.rs", "pub fn g() { dep1::f() }");
+    project
+        .cargo("update -Z gitoxide")
+        .masquerade_as_nightly_cargo()
+        .run();
+    project
+        .cargo("build -Z gitoxide")
+        .masquerade_as_nightly_cargo()
+        .run();
+}
+
+#[cargo_test]
+fn shallow_clones() {
+    let (git_project, repo) = git::new_repo("dep1", |project| {
+        project
+            .file("Cargo.toml", &basic_manifest("dep1", "0.5.0"))
+            .file("src/lib.rs", "pub fn old() {}")
+    });
+    let old_rev = repo.revparse_single("HEAD").unwrap().id();
+    git_project.change_file("src/lib.rs", "pub fn new() {}");
+    git::add(&repo);
+    git::commit(&repo);
+
+    let project = project()
+        .file(
+            "Cargo.toml",
+            &format!(
+                r#"
+                    [project]
+                    name = "foo"
+                    version = "0.5.0"
+                    authors = []
+
+                    [dependencies]
+                    dep1 = {{ git = '{}' }}
+                "#,
+                git_project.url()
+            ),
+        )
+        .file("src/lib.rs", "pub fn f() { dep1::new() }")
+        .build();
+
+    project
+        .cargo("build -Z git-shallow")
+        .masquerade_as_nightly_cargo()
+        .run();
+    let db_paths: Vec<_> = paths::home()
+        .join(".cargo/git/db")
+        .read_dir()
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(db_paths.len(), 1);
+    let db_path = &db_paths[0];
+    assert!(db_path.to_str().unwrap().ends_with("-shallow"));
+    let db = git2::Repository::open(db_path).unwrap();
+    assert!(db.is_shallow());
+    assert!(db.find_commit(old_rev).is_err());
+
+    // Pinning an older revision deepens the clone.
+    project.change_file(
+        "Cargo.toml",
+        &format!(
+            r#"
+                [project]
+                name = "foo"
+                version = "0.5.0"
+                authors = []
+
+                [dependencies]
+                dep1 = {{ git = '{}', rev = '{}' }}
+            "#,
+            git_project.url(),
+            old_rev
+        ),
+    );
+    project.change_file("src/lib.rs", "pub fn f() { dep1::old() }");
+    project
+        .cargo("build -Z git-shallow")
+        .masquerade_as_nightly_cargo()
+        .run();
+    let db = git2::Repository::open(db_path).unwrap();
+    assert!(!db.is_shallow());
+    assert!(db.find_commit(old_rev).is_ok());
+}
+
+#[cargo_test]
+fn gitoxide_uses_http_config() {
+    // A proxy which accepts connections, but never answers.
+    let proxy = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = proxy.local_addr().unwrap();
+    let connections = Arc::new(AtomicUsize::new(0));
+    thread::spawn({
+        let connections = connections.clone();
+        move || {
+            let mut streams = Vec::new();
+            for stream in proxy.incoming() {
+                connections.fetch_add(1, Ordering::SeqCst);
+                streams.push(stream);
+            }
+        }
+    });
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "foo"
+                version = "0.5.0"
+                authors = []
+
+                [dependencies]
+                dep1 = { git = "http://git.example.invalid/dep1" }
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file(
+            ".cargo/config",
+            &format!(
+                r#"
+                    [net]
+                    retry = 1
+
+                    [http]
+                    proxy = "{}"
+                    timeout = 1
+                "#,
+                addr
+            ),
+        )
+        .build();
+
+    // The fetch goes through the proxy, times out like other requests, and
+    // is retried.
+    p.cargo("build -Z gitoxide")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains("[WARNING] spurious network error (1 tries remaining): [..]")
+        .run();
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+}
+
 #[cargo_test]
 fn templatedir_doesnt_cause_problems() {
     let git_project2 = git::new("dep2", |project| {
@@ -274,6 +274,55 @@ Caused by:
     t.join().ok().unwrap();
 }
 
+#[cargo_test]
+fn ssh_config_host_alias() {
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    let t = thread::spawn(move || {
+        drop(server.accept().unwrap());
+    });
+
+    let ssh_config = paths::home().join(".ssh/config");
+    std::fs::create_dir_all(ssh_config.parent().unwrap()).unwrap();
+    std::fs::write(
+        &ssh_config,
+        format!(
+            "Host other\n    Port 1\n\nHost git-alias\n    HostName 127.0.0.1\n    Port {}\n",
+            addr.port()
+        ),
+    )
+    .unwrap();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "foo"
+                version = "0.0.1"
+                authors = []
+
+                [dependencies.bar]
+                git = "ssh://git-alias/foo/bar"
+            "#,
+        )
+        .file("src/main.rs", "")
+        .build();
+
+    // The connection is made to the `HostName` and `Port` of the alias.
+    p.cargo("build -v")
+        .with_status(101)
+        .with_stderr_contains("[UPDATING] git repository `ssh://git-alias/foo/bar`")
+        .with_stderr_contains(
+            "\
+Caused by:
+  [..]failed to start SSH session: Failed getting banner[..]
+",
+        )
+        .run();
+    t.join().ok().unwrap();
+}
+
 #[cargo_test]
 fn net_err_suggests_fetch_with_cli() {
     let p = project()
@@ -0,0 +1,189 @@
+//! Tests for the `-Z incremental-dir` feature.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use cargo_test_support::{basic_manifest, paths, project};
+
+/// Returns the directories of the incremental caches of the target
+/// directories in `incremental_dir`.
+fn workspace_dirs(incremental_dir: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<_> = fs::read_dir(incremental_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+#[cargo_test]
+fn gated() {
+    let p = project()
+        .file(
+            ".cargo/config",
+            r#"
+                [build]
+                incremental-dir = "inc"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("build")
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] the `build.incremental-dir` config value requires the \
+`-Z incremental-dir` command-line option
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn simple() {
+    let p = project()
+        .file(
+            ".cargo/config",
+            r#"
+                [build]
+                incremental-dir = "inc"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("build -v -Z incremental-dir")
+        .env("CARGO_INCREMENTAL", "1")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains("[RUNNING] `rustc [..] -C incremental=[CWD]/inc/foo-[..]/debug [..]`")
+        .run();
+    let dirs = workspace_dirs(&p.root().join("inc"));
+    assert_eq!(dirs.len(), 1);
+    assert!(dirs[0].join("debug").is_dir());
+    assert!(!p.build_dir().join("debug/incremental").exists());
+}
+
+#[cargo_test]
+fn env_var() {
+    let p = project().file("src/lib.rs", "").build();
+    let inc = paths::root().join("elsewhere");
+
+    p.cargo("build -v -Z incremental-dir")
+        .env("CARGO_INCREMENTAL", "1")
+        .env("CARGO_BUILD_INCREMENTAL_DIR", &inc)
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains(
+            "[RUNNING] `rustc [..] -C incremental=[ROOT]/elsewhere/foo-[..]/debug [..]`",
+        )
+        .run();
+    assert!(workspace_dirs(&inc)[0].join("debug").is_dir());
+}
+
+#[cargo_test]
+fn moving_does_not_rebuild() {
+    let p = project()
+        .file("Cargo.toml", &basic_manifest("foo", "0.1.0"))
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("build -Z incremental-dir")
+        .env("CARGO_INCREMENTAL", "1")
+        .env("CARGO_BUILD_INCREMENTAL_DIR", "inc1")
+        .masquerade_as_nightly_cargo()
+        .run();
+    p.cargo("build -Z incremental-dir")
+        .env("CARGO_INCREMENTAL", "1")
+        .env("CARGO_BUILD_INCREMENTAL_DIR", "inc2")
+        .masquerade_as_nightly_cargo()
+        .with_stderr("[FINISHED] [..]")
+        .run();
+}
+
+#[cargo_test]
+fn clean() {
+    let p = project()
+        .file(
+            ".cargo/config",
+            r#"
+                [build]
+                incremental-dir = "inc"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("build -Z incremental-dir")
+        .env("CARGO_INCREMENTAL", "1")
+        .masquerade_as_nightly_cargo()
+        .run();
+    assert_eq!(workspace_dirs(&p.root().join("inc")).len(), 1);
+
+    p.cargo("clean -Z incremental-dir")
+        .masquerade_as_nightly_cargo()
+        .run();
+    assert!(workspace_dirs(&p.root().join("inc")).is_empty());
+    assert!(!p.build_dir().exists());
+}
+
+#[cargo_test]
+fn shared_between_workspaces() {
+    let inc = paths::root().join("inc");
+    let foo = project()
+        .at("foo")
+        .file("Cargo.toml", &basic_manifest("foo", "0.1.0"))
+        .file("src/lib.rs", "")
+        .build();
+    let bar = project()
+        .at("bar")
+        .file("Cargo.toml", &basic_manifest("bar", "0.1.0"))
+        .file("src/lib.rs", "")
+        .build();
+    // Another workspace with a package named `foo`.
+    let other_foo = project()
+        .at("other/foo")
+        .file("Cargo.toml", &basic_manifest("foo", "0.1.0"))
+        .file("src/lib.rs", "")
+        .build();
+
+    for p in &[&foo, &bar, &other_foo] {
+        p.cargo("build -Z incremental-dir")
+            .env("CARGO_INCREMENTAL", "1")
+            .env("CARGO_BUILD_INCREMENTAL_DIR", &inc)
+            .masquerade_as_nightly_cargo()
+            .run();
+    }
+    let dirs = workspace_dirs(&inc);
+    assert_eq!(dirs.len(), 3);
+
+    // Cleaning a package or a workspace leaves the caches of the others.
+    foo.cargo("clean -p foo -Z incremental-dir")
+        .env("CARGO_BUILD_INCREMENTAL_DIR", &inc)
+        .masquerade_as_nightly_cargo()
+        .run();
+    bar.cargo("clean -Z incremental-dir")
+        .env("CARGO_BUILD_INCREMENTAL_DIR", &inc)
+        .masquerade_as_nightly_cargo()
+        .run();
+    let remaining = workspace_dirs(&inc);
+    assert_eq!(remaining.len(), 2);
+    let non_empty: Vec<_> = remaining
+        .iter()
+        .filter(|dir| fs::read_dir(dir.join("debug")).unwrap().next().is_some())
+        .collect();
+    assert_eq!(non_empty.len(), 1);
+    assert!(non_empty[0]
+        .file_name()
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("foo-"));
+
+    other_foo
+        .cargo("build -Z incremental-dir")
+        .env("CARGO_INCREMENTAL", "1")
+        .env("CARGO_BUILD_INCREMENTAL_DIR", &inc)
+        .masquerade_as_nightly_cargo()
+        .with_stderr("[FINISHED] [..]")
+        .run();
+}
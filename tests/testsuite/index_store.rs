@@ -0,0 +1,147 @@
+//! Tests for the `-Z index-store` feature.
+
+use cargo_test_support::paths;
+use cargo_test_support::project;
+use cargo_test_support::registry::{Dependency, Package};
+
+fn store_path() -> std::path::PathBuf {
+    let index = paths::home().join(".cargo/registry/index");
+    let index = index.read_dir().unwrap().next().unwrap().unwrap().path();
+    index.join(".cache/.store")
+}
+
+#[cargo_test]
+fn reuses_parsed_entries() {
+    Package::new("baz", "1.0.0").feature("std", &[]).publish();
+    Package::new("bar", "1.0.0")
+        .add_dep(Dependency::new("baz", "1.0").optional(true))
+        .feature("std", &["baz/std"])
+        .publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [dependencies]
+                bar = { version = "1.0", features = ["std"] }
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("generate-lockfile -Zindex-store")
+        .masquerade_as_nightly_cargo()
+        .run();
+    assert!(store_path().is_file());
+
+    // The second run decodes the entries from the store.
+    p.cargo("tree -e features -Zindex-store")
+        .masquerade_as_nightly_cargo()
+        .env("CARGO_LOG", "cargo::sources::registry::index=debug")
+        .with_stdout(
+            "\
+foo v0.1.0 ([ROOT]/foo)
+├── bar feature \"default\"
+│   └── bar v1.0.0
+│       └── baz feature \"default\"
+│           └── baz v1.0.0
+└── bar feature \"std\"
+    ├── bar v1.0.0 (*)
+    ├── bar feature \"baz\"
+    │   └── bar v1.0.0 (*)
+    └── baz feature \"std\"
+        └── baz v1.0.0
+",
+        )
+        .with_stderr_contains("[..]loaded `3/b/bar` from the index store[..]")
+        .with_stderr_contains("[..]loaded `3/b/baz` from the index store[..]")
+        .run();
+}
+
+#[cargo_test]
+fn starts_over_after_update() {
+    Package::new("bar", "1.0.0").publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [dependencies]
+                bar = "1.0"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("generate-lockfile -Zindex-store")
+        .masquerade_as_nightly_cargo()
+        .run();
+
+    // The store of the previous state of the index is not used.
+    Package::new("bar", "1.0.1").publish();
+    p.cargo("update -Zindex-store")
+        .masquerade_as_nightly_cargo()
+        .env("CARGO_LOG", "cargo::sources::registry::index=debug")
+        .with_stderr_contains("[UPDATING] bar v1.0.0 -> v1.0.1")
+        .with_stderr_does_not_contain("[..]from the index store[..]")
+        .run();
+
+    p.cargo("tree -Zindex-store")
+        .masquerade_as_nightly_cargo()
+        .env("CARGO_LOG", "cargo::sources::registry::index=debug")
+        .with_stdout(
+            "\
+foo v0.1.0 ([ROOT]/foo)
+└── bar v1.0.1
+",
+        )
+        .with_stderr_contains("[..]loaded `3/b/bar` from the index store[..]")
+        .run();
+}
+
+#[cargo_test]
+fn save_errors_are_reported() {
+    Package::new("bar", "1.0.0").publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [dependencies]
+                bar = "1.0"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("generate-lockfile").run();
+    // The store can't replace a directory.
+    std::fs::create_dir_all(store_path()).unwrap();
+    p.cargo("tree -Zindex-store")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] failed to save the index store of [..]
+
+Caused by:
+  failed to replace `[..]/.cache/.store`
+
+Caused by:
+  [..]
+",
+        )
+        .run();
+}
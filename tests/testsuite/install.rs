@@ -1,7 +1,9 @@
 //! Tests for the `cargo install` command.
 
+use std::env;
 use std::fs::{self, OpenOptions};
 use std::io::prelude::*;
+use std::path::Path;
 
 use cargo_test_support::cross_compile;
 use cargo_test_support::git;
@@ -289,16 +291,107 @@ fn install_location_precedence() {
     assert_has_installed_exe(&t4, "foo");
 }
 
+#[cargo_test]
+fn local_install_root() {
+    pkg("foo", "0.0.1");
+    let p = project()
+        .file("src/main.rs", "fn main() {}")
+        .file(
+            ".cargo/config",
+            "
+            [install]
+            root = 'tools'
+            ",
+        )
+        .file("sub/.keep", "")
+        .build();
+
+    // The configuration of the current directory is ignored by default.
+    p.cargo("install foo")
+        .cwd(p.root().join("sub"))
+        .masquerade_as_nightly_cargo()
+        .run();
+    assert_has_installed_exe(cargo_home(), "foo");
+    assert_has_not_installed_exe(p.root().join("tools"), "foo");
+
+    // The root is relative to the project, not to the current directory.
+    p.cargo("install foo -Z local-install-root")
+        .cwd(p.root().join("sub"))
+        .masquerade_as_nightly_cargo()
+        .run();
+    assert_has_installed_exe(p.root().join("tools"), "foo");
+    assert_has_not_installed_exe(p.root().join("sub/tools"), "foo");
+
+    // `--root` and `CARGO_INSTALL_ROOT` take precedence.
+    p.cargo("install foo -Z local-install-root")
+        .env("CARGO_INSTALL_ROOT", paths::root().join("t1"))
+        .masquerade_as_nightly_cargo()
+        .run();
+    assert_has_installed_exe(paths::root().join("t1"), "foo");
+
+    p.cargo("uninstall foo -Z local-install-root")
+        .masquerade_as_nightly_cargo()
+        .with_stderr("[REMOVING] [CWD]/tools/bin/foo[EXE]")
+        .run();
+    assert_has_not_installed_exe(p.root().join("tools"), "foo");
+    assert_has_installed_exe(cargo_home(), "foo");
+}
+
+#[cargo_test]
+fn shadowed_binary_warning() {
+    pkg("foo", "0.0.1");
+    let shadow = paths::root().join("shadow");
+    fs::create_dir(&shadow).unwrap();
+    fs::write(shadow.join(format!("foo{}", env::consts::EXE_SUFFIX)), "").unwrap();
+    let bin = cargo_home().join("bin");
+    let path_with = |dirs: &[&Path]| {
+        let path = env::var_os("PATH").unwrap_or_default();
+        let dirs = dirs.iter().map(|d| d.to_path_buf());
+        env::join_paths(dirs.chain(env::split_paths(&path))).unwrap()
+    };
+
+    cargo_process("install foo")
+        .env("PATH", path_with(&[&shadow]))
+        .with_stderr_contains(
+            "[WARNING] the installed binary `foo[EXE]` is shadowed by \
+             `[ROOT]/shadow/foo[EXE]`, which is in PATH",
+        )
+        .with_stderr_contains("[WARNING] be sure to add `[..]` to your PATH [..]")
+        .run();
+
+    cargo_process("install foo --force")
+        .env("PATH", path_with(&[&shadow, &bin]))
+        .with_stderr_contains(
+            "[WARNING] the installed binary `foo[EXE]` is shadowed by \
+             `[ROOT]/shadow/foo[EXE]`, which comes before `[..]/home/.cargo/bin` in PATH",
+        )
+        .with_stderr_does_not_contain("[..]be sure to add[..]")
+        .run();
+
+    cargo_process("install foo --force")
+        .env("PATH", path_with(&[&bin, &shadow]))
+        .with_stderr_does_not_contain("[WARNING][..]")
+        .run();
+}
+
 #[cargo_test]
 fn install_path() {
     let p = project().file("src/main.rs", "fn main() {}").build();
 
     cargo_process("install --path").arg(p.root()).run();
     assert_has_installed_exe(cargo_home(), "foo");
-    // path-style installs force a reinstall
+    // path-style installs are skipped unless the sources changed
     p.cargo("install --path .")
         .with_stderr(
             "\
+[IGNORED] package `foo v0.0.1 [..]` is already installed and up to date, use --force to reinstall
+[WARNING] be sure to add [..]
+",
+        )
+        .run();
+    p.cargo("install --path . --force")
+        .with_stderr(
+            "\
 [INSTALLING] foo v0.0.1 [..]
 [FINISHED] release [..]
 [REPLACING] [..]/.cargo/bin/foo[EXE]
@@ -359,6 +452,89 @@ fn multiple_crates_error() {
         .run();
 }
 
+#[cargo_test]
+fn path_in_repo() {
+    let p = git::repo(&paths::root().join("foo"))
+        .file("Cargo.toml", &basic_manifest("foo", "0.1.0"))
+        .file("src/main.rs", "fn main() {}")
+        .file("tools/bar/Cargo.toml", &basic_manifest("bar", "0.1.0"))
+        .file("tools/bar/src/main.rs", "fn main() {}")
+        .file("tools/baz/Cargo.toml", &basic_manifest("baz", "0.1.0"))
+        .file("tools/baz/src/main.rs", "fn main() {}")
+        .build();
+
+    cargo_process("install -Z unstable-options --path-in-repo tools/bar --git")
+        .arg(p.url().to_string())
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[UPDATING] git repository [..]
+[INSTALLING] bar v0.1.0 ([..])
+[COMPILING] bar v0.1.0 ([..])
+[FINISHED] release [optimized] target(s) in [..]
+[INSTALLING] [CWD]/home/.cargo/bin/bar[EXE]
+[INSTALLED] package `bar v0.1.0 ([..])` (executable `bar[EXE]`)
+[WARNING] be sure to add `[..]` to your PATH to be able to run the installed binaries
+",
+        )
+        .run();
+    assert_has_installed_exe(cargo_home(), "bar");
+
+    // Only the requested directory is checked out.
+    let checkouts = cargo_home().join("git/checkouts");
+    let db = fs::read_dir(&checkouts).unwrap().next().unwrap().unwrap();
+    assert!(db.file_name().to_str().unwrap().ends_with("-shallow"));
+    let checkout = fs::read_dir(db.path()).unwrap().next().unwrap().unwrap();
+    let checkout = checkout.path();
+    assert!(checkout.join("tools/bar/Cargo.toml").is_file());
+    assert!(!checkout.join("tools/baz").exists());
+    assert!(!checkout.join("src").exists());
+    assert!(!checkout.join("Cargo.toml").exists());
+}
+
+#[cargo_test]
+fn path_in_repo_errors() {
+    let p = git::repo(&paths::root().join("foo"))
+        .file("Cargo.toml", &basic_manifest("foo", "0.1.0"))
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    cargo_process("install --path-in-repo src --git")
+        .arg(p.url().to_string())
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] the `--path-in-repo` flag is unstable, pass `-Z unstable-options` to enable it
+See https://doc.rust-lang.org/nightly/cargo/reference/unstable.html \
+for more information about the `--path-in-repo` flag.
+",
+        )
+        .run();
+
+    cargo_process("install -Z unstable-options --path-in-repo ../bar --git")
+        .arg(p.url().to_string())
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "[ERROR] `--path-in-repo ../bar` must be a path relative to the root of the \
+             repository, without `..`",
+        )
+        .run();
+
+    cargo_process("install -Z unstable-options --path-in-repo missing --git")
+        .arg(p.url().to_string())
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[UPDATING] git repository [..]
+[ERROR] `missing` is not a directory of the git repository `[..]`
+",
+        )
+        .run();
+}
+
 #[cargo_test]
 fn multiple_crates_select() {
     let p = git::repo(&paths::root().join("foo"))
@@ -1665,3 +1841,372 @@ workspace: [..]/foo/Cargo.toml
         .run();
     assert_has_installed_exe(cargo_home(), "foo");
 }
+
+#[cargo_test]
+fn list_json() {
+    pkg("foo", "0.0.1");
+    cargo_process("install foo").run();
+
+    cargo_process("install --list --format json")
+        .with_status(101)
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[ERROR] the `--format` flag is unstable, pass `-Z unstable-options` to enable it
+See https://doc.rust-lang.org/nightly/cargo/reference/unstable.html \
+for more information about the `--format` flag.
+",
+        )
+        .run();
+
+    cargo_process("install --list --format json -Z unstable-options")
+        .masquerade_as_nightly_cargo()
+        .with_json(
+            r#"
+            {
+                "installs": [
+                    {
+                        "package_id": "foo 0.0.1 (registry+[..])",
+                        "name": "foo",
+                        "version": "0.0.1",
+                        "source": {
+                            "kind": "registry",
+                            "url": "[..]",
+                            "reference": null,
+                            "rev": null
+                        },
+                        "version_req": null,
+                        "bins": ["foo[EXE]"],
+                        "features": [],
+                        "all_features": false,
+                        "no_default_features": false,
+                        "profile": "release",
+                        "target": "[..]",
+                        "rustc_version": "{...}"
+                    }
+                ]
+            }
+            "#,
+        )
+        .run();
+}
+
+#[cargo_test]
+fn multiple_pkgs_parallel() {
+    pkg("foo", "0.0.1");
+    pkg("bar", "0.0.2");
+
+    cargo_process("install foo bar baz -Z parallel-install")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains("[INSTALLED] package `foo v0.0.1` (executable `foo[EXE]`)")
+        .with_stderr_contains("[INSTALLED] package `bar v0.0.2` (executable `bar[EXE]`)")
+        .with_stderr_contains("[ERROR] could not find `baz` in registry `[..]` with version `*`")
+        .with_stderr_contains(
+            "\
+[SUMMARY] Successfully installed foo, bar! Failed to install baz (see error(s) above).
+[WARNING] be sure to add `[..]` to your PATH to be able to run the installed binaries
+[ERROR] some crates failed to install
+",
+        )
+        .run();
+    assert_has_installed_exe(cargo_home(), "foo");
+    assert_has_installed_exe(cargo_home(), "bar");
+
+    let v1 = fs::read_to_string(cargo_home().join(".crates.toml")).unwrap();
+    assert!(v1.contains("foo 0.0.1"));
+    assert!(v1.contains("bar 0.0.2"));
+}
+
+#[cargo_test]
+fn dry_run() {
+    pkg("bar", "0.1.0");
+    Package::new("foo", "0.0.1")
+        .dep("bar", "0.1")
+        .feature("extra", &[])
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+
+                [dependencies]
+                bar = "0.1"
+
+                [features]
+                extra = []
+            "#,
+        )
+        .file("src/main.rs", "fn main() {}")
+        .publish();
+
+    cargo_process("install foo --dry-run")
+        .with_status(101)
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[ERROR] the `--dry-run` flag is unstable, pass `-Z unstable-options` to enable it
+See https://doc.rust-lang.org/nightly/cargo/reference/unstable.html \
+for more information about the `--dry-run` flag.
+",
+        )
+        .run();
+
+    cargo_process("install foo --dry-run --features extra -Z unstable-options")
+        .masquerade_as_nightly_cargo()
+        .with_stdout(
+            "\
+foo v0.0.1
+  features: extra
+  dependencies to build:
+    bar v0.1.0
+  binaries:
+    [CWD]/home/.cargo/bin/foo[EXE]
+",
+        )
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[DOWNLOADING] crates ...
+[DOWNLOADED] foo v0.0.1 (registry [..])
+[DOWNLOADING] crates ...
+[DOWNLOADED] bar v0.1.0 (registry [..])
+",
+        )
+        .run();
+    assert_has_not_installed_exe(cargo_home(), "foo");
+
+    cargo_process("install foo").run();
+    Package::new("foo", "0.0.2")
+        .file("src/main.rs", "fn main() {}")
+        .publish();
+    cargo_process("install foo --dry-run -Z unstable-options")
+        .masquerade_as_nightly_cargo()
+        .with_stdout(
+            "\
+foo v0.0.2
+  features: (none)
+  dependencies to build: (none)
+  binaries:
+    [CWD]/home/.cargo/bin/foo[EXE] (replaces the one of `foo v0.0.1`)
+",
+        )
+        .run();
+    cargo_process("install --list")
+        .with_stdout(
+            "\
+foo v0.0.1:
+    foo[EXE]
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn install_locked_uses_workspace_lock_file() {
+    // -Z install-locked uses the lock file of the workspace of the package
+    // given to --path.
+    Package::new("baz", "0.1.0").publish();
+    Package::new("baz", "0.1.1")
+        .file("src/lib.rs", "not rust")
+        .publish();
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+            [workspace]
+            members = ["foo", "bar"]
+            "#,
+        )
+        .file(
+            "foo/Cargo.toml",
+            r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+
+            [dependencies]
+            bar = { path = "../bar" }
+            "#,
+        )
+        .file("foo/src/main.rs", "fn main() { bar::bar(); }")
+        .file(
+            "bar/Cargo.toml",
+            r#"
+            [package]
+            name = "bar"
+            version = "0.1.0"
+
+            [dependencies]
+            baz = "0.1"
+            "#,
+        )
+        .file("bar/src/lib.rs", "pub fn bar() {}")
+        .file(
+            "Cargo.lock",
+            r#"
+[[package]]
+name = "bar"
+version = "0.1.0"
+dependencies = [
+ "baz",
+]
+
+[[package]]
+name = "baz"
+version = "0.1.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "foo"
+version = "0.1.0"
+dependencies = [
+ "bar",
+]
+"#,
+        )
+        .build();
+
+    p.cargo("install --path foo")
+        .with_stderr_contains("[..]not rust[..]")
+        .with_status(101)
+        .run();
+    p.cargo("install --path foo -Z install-locked")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains("[COMPILING] baz v0.1.0")
+        .with_stderr_contains("[COMPILING] bar v0.1.0 ([..]/bar)")
+        .run();
+    assert_has_installed_exe(cargo_home(), "foo");
+
+    // The lock file is not updated.
+    let lock = p.read_lockfile();
+    p.change_file("bar/src/lib.rs", "pub fn bar() { println!(); }");
+    p.cargo("install --path foo -Z install-locked")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains("[REPLACED] package `foo v0.1.0 [..]`[..]")
+        .run();
+    assert_eq!(lock, p.read_lockfile());
+}
+
+#[cargo_test]
+fn uninstall_glob() {
+    for name in &["cargo-foo", "cargo-bar", "baz"] {
+        Package::new(name, "0.0.1")
+            .file("src/main.rs", "fn main() {}")
+            .publish();
+    }
+    cargo_process("install cargo-foo cargo-bar baz").run();
+
+    cargo_process("uninstall cargo-*")
+        .with_stderr(
+            "[ERROR] glob patterns in `cargo uninstall` are unstable, \
+             pass `-Z unstable-options` to use them",
+        )
+        .with_status(101)
+        .run();
+    cargo_process("uninstall -Z unstable-options car?o-* nothing-*")
+        .masquerade_as_nightly_cargo()
+        .with_stderr("[ERROR] no installed packages match the pattern `nothing-*`")
+        .with_status(101)
+        .run();
+    cargo_process("uninstall -Z unstable-options cargo-*")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[REMOVING] [CWD]/home/.cargo/bin/cargo-bar[EXE]
+[REMOVING] [CWD]/home/.cargo/bin/cargo-foo[EXE]
+[SUMMARY] Successfully uninstalled cargo-bar v0.0.1, cargo-foo v0.0.1!
+",
+        )
+        .run();
+    assert_has_not_installed_exe(cargo_home(), "cargo-foo");
+    assert_has_not_installed_exe(cargo_home(), "cargo-bar");
+    assert_has_installed_exe(cargo_home(), "baz");
+}
+
+#[cargo_test]
+fn uninstall_all() {
+    pkg("foo", "0.0.1");
+    pkg("bar", "0.0.1");
+    cargo_process("install foo bar").run();
+
+    cargo_process("uninstall --all")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[ERROR] the `--all` flag is unstable, pass `-Z unstable-options` to enable it
+See https://doc.rust-lang.org/nightly/cargo/reference/unstable.html \
+for more information about the `--all` flag.
+",
+        )
+        .with_status(101)
+        .run();
+    // There is no answer to the confirmation on stdin.
+    cargo_process("uninstall -Z unstable-options --all")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+Uninstall 2 packages (bar v0.0.1, foo v0.0.1)? [y/N] [ERROR] uninstall cancelled, no packages were uninstalled
+",
+        )
+        .with_status(101)
+        .run();
+    assert_has_installed_exe(cargo_home(), "foo");
+    assert_has_installed_exe(cargo_home(), "bar");
+
+    cargo_process("uninstall -Z unstable-options --all --yes")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[REMOVING] [CWD]/home/.cargo/bin/bar[EXE]
+[REMOVING] [CWD]/home/.cargo/bin/foo[EXE]
+[SUMMARY] Successfully uninstalled bar v0.0.1, foo v0.0.1!
+",
+        )
+        .run();
+    assert_has_not_installed_exe(cargo_home(), "foo");
+    assert_has_not_installed_exe(cargo_home(), "bar");
+
+    cargo_process("uninstall -Z unstable-options --all --yes")
+        .masquerade_as_nightly_cargo()
+        .with_stderr("[ERROR] no packages are installed in `[CWD]/home/.cargo`")
+        .with_status(101)
+        .run();
+}
+
+#[cargo_test]
+fn uninstall_all_same_name() {
+    // Two installed packages named `foo`, from a registry and from a path.
+    pkg("foo", "0.0.1");
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.2"
+
+                [[bin]]
+                name = "foo-local"
+                path = "src/main.rs"
+            "#,
+        )
+        .file("src/main.rs", "fn main() {}")
+        .build();
+    cargo_process("install foo").run();
+    p.cargo("install --path .").run();
+
+    cargo_process("uninstall -Z unstable-options fo?")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_unordered(
+            "\
+[REMOVING] [CWD]/home/.cargo/bin/foo[EXE]
+[REMOVING] [CWD]/home/.cargo/bin/foo-local[EXE]
+[SUMMARY] Successfully uninstalled foo v0.0.1, foo v0.0.2!
+",
+        )
+        .run();
+    assert_has_not_installed_exe(cargo_home(), "foo");
+    assert_has_not_installed_exe(cargo_home(), "foo-local");
+}
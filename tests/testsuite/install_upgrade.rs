@@ -240,13 +240,26 @@ if you want to specify semver range, add an explicit qualifier, like ^1.0
 }
 
 #[cargo_test]
-fn path_is_always_dirty() {
-    // --path should always reinstall.
+fn path_is_dirty_when_changed() {
+    // --path should reinstall only when the package's files changed.
     let p = project().file("src/main.rs", "fn main() {}").build();
     p.cargo("install --path .").run();
+    p.cargo("install --path .")
+        .with_stderr_contains(
+            "[IGNORED] package `foo v0.0.1 ([..])` is already installed and up to date[..]",
+        )
+        .with_stderr_does_not_contain("[COMPILING][..]")
+        .run();
+    p.change_file("src/main.rs", "fn main() { println!(\"changed\"); }");
     p.cargo("install --path .")
         .with_stderr_contains("[REPLACING] [..]/foo[EXE]")
         .run();
+    p.cargo("install --path .")
+        .with_stderr_contains("[IGNORED] [..]")
+        .run();
+    p.cargo("install --path . --force")
+        .with_stderr_contains("[REPLACING] [..]/foo[EXE]")
+        .run();
 }
 
 #[cargo_test]
@@ -520,7 +533,7 @@ fn upgrade_git() {
         .arg(git_project.url().to_string())
         .with_stderr_contains(
             "[IGNORED] package `foo v0.0.1 (file://[..]/foo#[..])` is \
-             already installed,[..]",
+             already installed and up to date,[..]",
         )
         .run();
     // Modify a file.
@@ -540,7 +553,7 @@ fn upgrade_git() {
         .arg(git_project.url().to_string())
         .with_stderr_contains(
             "[IGNORED] package `foo v0.0.1 (file://[..]/foo#[..])` is \
-             already installed,[..]",
+             already installed and up to date,[..]",
         )
         .run();
 }
@@ -627,8 +640,8 @@ fn multiple_report() {
         .with_stderr(
             "\
 [UPDATING] `[..]` index
-[IGNORED] package `one v1.0.0` is already installed, use --force to override
-[IGNORED] package `two v1.0.0` is already installed, use --force to override
+[IGNORED] package `one v1.0.0` is already installed and up to date, use --force to reinstall
+[IGNORED] package `two v1.0.0` is already installed and up to date, use --force to reinstall
 [DOWNLOADING] crates ...
 [DOWNLOADED] three v1.0.1 (registry `[..]`)
 [INSTALLING] three v1.0.1
@@ -858,3 +871,41 @@ fn partially_already_installed_does_one_update() {
         )
         .run();
 }
+
+#[cargo_test]
+fn tracks_source_and_rustc_version() {
+    pkg("foo", "1.0.0");
+    let git_project = git::new("bar", |project| {
+        project
+            .file("Cargo.toml", &basic_manifest("bar", "0.1.0"))
+            .file("src/main.rs", "fn main() {}")
+    });
+    let repo = git2::Repository::open(&git_project.root()).unwrap();
+    let rev = repo.head().unwrap().target().unwrap().to_string();
+
+    cargo_process("install foo").run();
+    cargo_process("install --branch master --git")
+        .arg(git_project.url().to_string())
+        .run();
+
+    let output = process("rustc").arg("-V").exec_with_output().unwrap();
+    let output = String::from_utf8(output.stdout).unwrap();
+    let rustc_version = output.split_whitespace().nth(1).unwrap();
+    let v2 = load_crates2();
+    let installs = v2["installs"].as_object().unwrap();
+    assert_eq!(installs.len(), 2);
+    for (pkg_id, info) in installs {
+        assert_eq!(info["rustc_version"], rustc_version);
+        let source = &info["source"];
+        if pkg_id.starts_with("foo ") {
+            assert_eq!(source["kind"], "registry");
+            assert!(source["reference"].is_null());
+            assert!(source["rev"].is_null());
+        } else {
+            assert_eq!(source["kind"], "git");
+            assert_eq!(source["url"], git_project.url().to_string().as_str());
+            assert_eq!(source["reference"], "branch=master");
+            assert_eq!(source["rev"], rev.as_str());
+        }
+    }
+}
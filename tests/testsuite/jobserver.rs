@@ -204,3 +204,143 @@ with an external jobserver in its environment, ignoring the `-j` parameter
         )
         .run();
 }
+
+#[cargo_test]
+fn build_script_make_uses_jobserver() {
+    let make = if cfg!(windows) {
+        "mingw32-make"
+    } else {
+        "make"
+    };
+    if Command::new(make).arg("--version").output().is_err() {
+        return;
+    }
+
+    let p = project()
+        .file(
+            "build.rs",
+            &format!(
+                r#"
+                    use std::env;
+                    use std::process::Command;
+
+                    fn main() {{
+                        let makeflags = env::var("MAKEFLAGS").unwrap();
+                        assert_eq!(makeflags, env::var("CARGO_MAKEFLAGS").unwrap());
+                        assert!(makeflags.contains("--jobserver-auth="));
+                        let output = Command::new("{}").output().unwrap();
+                        assert!(output.status.success());
+                        let stderr = String::from_utf8(output.stderr).unwrap();
+                        assert!(!stderr.contains("jobserver unavailable"), "{{}}", stderr);
+                    }}
+                "#,
+                make
+            ),
+        )
+        .file("src/lib.rs", "")
+        .file(
+            "Makefile",
+            "\
+all:
+\t@echo all
+",
+        )
+        .build();
+
+    p.cargo("build -j2").run();
+}
+
+#[cargo_test]
+fn build_script_keeps_makeflags() {
+    let p = project()
+        .file(
+            "build.rs",
+            r#"
+                use std::env;
+
+                fn main() {
+                    let jobserver = env::var("CARGO_MAKEFLAGS").unwrap();
+                    assert_eq!(env::var("MAKEFLAGS").unwrap(), format!("-k {}", jobserver));
+                    assert_eq!(env::var("MFLAGS").unwrap(), format!("-s {}", jobserver));
+                }
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("build")
+        .env("MAKEFLAGS", "-k")
+        .env("MFLAGS", "-s")
+        .run();
+}
+
+#[cfg(unix)]
+#[cargo_test]
+fn named_fifo_jobserver() {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    let p = project()
+        .file(
+            "build.rs",
+            r#"
+                use std::env;
+
+                fn main() {
+                    let fifo = env::var("FIFO").unwrap();
+                    let arg = format!("--jobserver-auth=fifo:{}", fifo);
+                    assert!(env::var("CARGO_MAKEFLAGS").unwrap().contains(&arg));
+                    // The `MAKEFLAGS` of `make` are kept.
+                    assert!(env::var("MAKEFLAGS").unwrap().starts_with("-k "));
+                }
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+    let fifo = p.root().join("jobserver");
+    let status = match Command::new("mkfifo").arg(&fifo).status() {
+        Ok(status) => status,
+        Err(_) => return,
+    };
+    assert!(status.success());
+    let mut tokens = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&fifo)
+        .unwrap();
+    tokens.write_all(b"+").unwrap();
+
+    p.cargo("build -j2")
+        .env(
+            "MAKEFLAGS",
+            format!("-k -j2 --jobserver-auth=fifo:{}", fifo.display()),
+        )
+        .env("FIFO", &fifo)
+        .with_stderr(
+            "\
+warning: a `-j` argument was passed to Cargo but Cargo is also configured \
+with an external jobserver in its environment, ignoring the `-j` parameter
+[COMPILING] foo [..]
+[FINISHED] [..]
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn unavailable_jobserver() {
+    let p = project().file("src/lib.rs", "").build();
+
+    p.cargo("build -j1")
+        .env("MAKEFLAGS", "-j2 --jobserver-auth=fifo:/nonexistent/fifo")
+        .with_stderr(
+            "\
+warning: failed to connect to the jobserver of the environment, using 1 jobs \
+instead: [..]/nonexistent/fifo[..]
+If Cargo is run by `make`, the line of the Makefile running it may need a `+` prefix.
+[COMPILING] foo [..]
+[FINISHED] [..]
+",
+        )
+        .run();
+}
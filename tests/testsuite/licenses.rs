@@ -0,0 +1,307 @@
+//! Tests for the `cargo licenses` command.
+
+use std::env;
+
+use cargo_test_support::registry::Package;
+use cargo_test_support::{basic_manifest, project};
+
+/// Publishes `name` with the given `license` and `license-file` keys.
+fn licensed(name: &str, license: &str) {
+    Package::new(name, "0.1.0")
+        .file(
+            "Cargo.toml",
+            &format!(
+                r#"
+                    [package]
+                    name = "{}"
+                    version = "0.1.0"
+                    {}
+                "#,
+                name, license
+            ),
+        )
+        .file("src/lib.rs", "")
+        .publish();
+}
+
+fn setup() -> cargo_test_support::Project {
+    licensed("bar", "license = \"MIT/Apache-2.0\"");
+    licensed("baz", "license = \"gpl-3.0+\"");
+    licensed("qux", "license-file = \"LICENSE\"");
+    licensed("devdep", "license = \"MIT\"");
+    licensed("windep", "license = \"MIT\"");
+    project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+                license = "Proprietary"
+
+                [dependencies]
+                bar = "0.1"
+                baz = "0.1"
+
+                [build-dependencies]
+                qux = "0.1"
+
+                [dev-dependencies]
+                devdep = "0.1"
+
+                [target.'cfg(windows_unknown)'.dependencies]
+                windep = "0.1"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build()
+}
+
+#[cargo_test]
+fn human() {
+    let p = setup();
+
+    p.cargo("licenses -Zunstable-options")
+        .masquerade_as_nightly_cargo()
+        .with_stdout(
+            "\
+GPL-3.0-or-later (1): baz v0.1.0
+MIT OR Apache-2.0 (1): bar v0.1.0
+license file `LICENSE` (1): qux v0.1.0
+",
+        )
+        .run();
+
+    p.cargo("licenses -Zunstable-options --dev --target all")
+        .masquerade_as_nightly_cargo()
+        .with_stdout(
+            "\
+GPL-3.0-or-later (1): baz v0.1.0
+MIT (2): devdep v0.1.0, windep v0.1.0
+MIT OR Apache-2.0 (1): bar v0.1.0
+license file `LICENSE` (1): qux v0.1.0
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn json() {
+    let p = setup();
+
+    p.cargo("licenses -Zunstable-options --format json")
+        .masquerade_as_nightly_cargo()
+        .with_json(
+            r#"
+            {
+              "packages": [
+                {
+                  "name": "bar",
+                  "version": "0.1.0",
+                  "id": "bar 0.1.0 (registry+https://github.com/rust-lang/crates.io-index)",
+                  "license": "MIT/Apache-2.0",
+                  "normalized_license": "MIT OR Apache-2.0",
+                  "license_file": null
+                },
+                {
+                  "name": "baz",
+                  "version": "0.1.0",
+                  "id": "baz 0.1.0 (registry+https://github.com/rust-lang/crates.io-index)",
+                  "license": "gpl-3.0+",
+                  "normalized_license": "GPL-3.0-or-later",
+                  "license_file": null
+                },
+                {
+                  "name": "qux",
+                  "version": "0.1.0",
+                  "id": "qux 0.1.0 (registry+https://github.com/rust-lang/crates.io-index)",
+                  "license": null,
+                  "normalized_license": null,
+                  "license_file": "LICENSE"
+                }
+              ],
+              "violations": []
+            }
+            "#,
+        )
+        .run();
+}
+
+#[cargo_test]
+fn csv_and_html() {
+    let p = setup();
+
+    p.cargo("licenses -Zunstable-options --format csv")
+        .masquerade_as_nightly_cargo()
+        .with_stdout(
+            "\
+name,version,source,license,license_file
+bar,0.1.0,registry+https://github.com/rust-lang/crates.io-index,MIT OR Apache-2.0,
+baz,0.1.0,registry+https://github.com/rust-lang/crates.io-index,GPL-3.0-or-later,
+qux,0.1.0,registry+https://github.com/rust-lang/crates.io-index,,LICENSE
+",
+        )
+        .run();
+
+    p.cargo("licenses -Zunstable-options --format html")
+        .masquerade_as_nightly_cargo()
+        .with_stdout_contains(
+            "<tr><td>bar</td><td>0.1.0</td>\
+             <td>registry+https://github.com/rust-lang/crates.io-index</td>\
+             <td>MIT OR Apache-2.0</td><td></td></tr>",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn check_policy() {
+    let p = setup();
+
+    // Without `--check`, the policy isn't enforced.
+    p.change_file(
+        ".cargo/config",
+        r#"
+            [licenses]
+            allow = ["mit", "Apache-2.0"]
+        "#,
+    );
+    p.cargo("licenses -Zunstable-options --format json")
+        .masquerade_as_nightly_cargo()
+        .with_stdout_contains(r#"{"packages":[{[..]}],"violations":[]}"#)
+        .run();
+
+    p.cargo("licenses -Zunstable-options --check")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains(
+            "\
+[ERROR] 2 dependencies violate the license policy:
+  baz v0.1.0: `GPL-3.0-or-later` is not allowed by the license policy
+  qux v0.1.0: no SPDX license expression, only the license file `LICENSE`
+",
+        )
+        .run();
+
+    p.change_file(
+        ".cargo/config",
+        r#"
+            [licenses]
+            deny = ["GPL-3.0+"]
+        "#,
+    );
+    p.cargo("licenses -Zunstable-options --check --format json")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stdout_contains(
+            r#"{[..]"violations":[{"id":"baz [..]","reason":"`GPL-3.0-or-later` is not allowed by the license policy"}]}"#,
+        )
+        .with_stderr_contains("[ERROR] 1 dependency violates the license policy:")
+        .run();
+
+    p.change_file(
+        ".cargo/config",
+        r#"
+            [licenses]
+            allow = ["MIT"]
+            exceptions = ["baz", "qux"]
+        "#,
+    );
+    p.cargo("licenses -Zunstable-options --check")
+        .masquerade_as_nightly_cargo()
+        .run();
+
+    p.change_file(
+        ".cargo/config",
+        r#"
+            [licenses]
+            deny = ["MIT OR GPL-3.0"]
+        "#,
+    );
+    p.cargo("licenses -Zunstable-options --check")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "[ERROR] `licenses.deny` must only contain licenses, not expressions like \
+             `MIT OR GPL-3.0`",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn invalid_expression() {
+    licensed("bar", "license = \"MIT OR (Apache-2.0\"");
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [dependencies]
+                bar = "0.1"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file(
+            ".cargo/config",
+            r#"
+                [licenses]
+                deny = ["GPL-3.0-only"]
+            "#,
+        )
+        .build();
+
+    p.cargo("licenses -Zunstable-options")
+        .masquerade_as_nightly_cargo()
+        .with_stdout("MIT OR (Apache-2.0 (invalid) (1): bar v0.1.0")
+        .run();
+    p.cargo("licenses -Zunstable-options --check")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains(
+            "\
+[ERROR] 1 dependency violates the license policy:
+  bar v0.1.0: invalid SPDX license expression `MIT OR (Apache-2.0`
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn licenses_requires_unstable_options() {
+    let p = project().file("src/lib.rs", "").build();
+
+    p.cargo("licenses")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr("[ERROR] `cargo licenses` requires `-Z unstable-options`")
+        .run();
+}
+
+#[cargo_test]
+fn external_cargo_licenses_takes_precedence() {
+    let external = project()
+        .at("cargo-licenses")
+        .file("Cargo.toml", &basic_manifest("cargo-licenses", "1.0.0"))
+        .file(
+            "src/main.rs",
+            r#"
+                fn main() {
+                    let args: Vec<_> = std::env::args().skip(1).collect();
+                    println!("external {:?}", args);
+                }
+            "#,
+        )
+        .build();
+    external.cargo("build").run();
+    let mut path: Vec<_> = env::split_paths(&env::var_os("PATH").unwrap_or_default()).collect();
+    path.insert(0, external.target_debug_dir());
+    let path = env::join_paths(path).unwrap();
+    let p = project().file("src/lib.rs", "").build();
+
+    p.cargo("licenses --format json --all")
+        .env("PATH", &path)
+        .with_stdout(r#"external ["licenses", "--format", "json", "--all"]"#)
+        .run();
+}
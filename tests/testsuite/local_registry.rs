@@ -488,3 +488,145 @@ fn crates_io_registry_url_is_optional() {
     p.cargo("build").with_stderr("[FINISHED] [..]").run();
     p.cargo("test").run();
 }
+
+#[cargo_test]
+fn generated_index() {
+    setup();
+    Package::new("baz", "0.1.0")
+        .local(true)
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "baz"
+                version = "0.1.0"
+                authors = []
+
+                [features]
+                std = []
+            "#,
+        )
+        .file("src/lib.rs", "#[cfg(feature = \"std\")] pub fn baz() {}")
+        .publish();
+    Package::new("bar", "0.1.0")
+        .local(true)
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "bar"
+                version = "0.1.0"
+                authors = []
+
+                [dependencies]
+                baz = { version = "0.1", features = ["std"] }
+            "#,
+        )
+        .file("src/lib.rs", "pub fn bar() { baz::baz(); }")
+        .publish();
+    // Only keep the crate files.
+    registry_path().join("index").rm_rf();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "foo"
+                version = "0.0.1"
+                authors = []
+
+                [dependencies]
+                bar = "0.1"
+            "#,
+        )
+        .file(
+            "src/lib.rs",
+            "extern crate bar; pub fn foo() { bar::bar(); }",
+        )
+        .build();
+
+    p.cargo("build")
+        .with_stderr_unordered(
+            "\
+[INDEXING] 2 crate files in `[..]registry`
+[UNPACKING] bar v0.1.0 ([..])
+[UNPACKING] baz v0.1.0 ([..])
+[COMPILING] baz v0.1.0
+[COMPILING] bar v0.1.0
+[COMPILING] foo v0.0.1 ([CWD])
+[FINISHED] [..]
+",
+        )
+        .run();
+    p.cargo("build").with_stderr("[FINISHED] [..]").run();
+
+    // Adding a crate file generates the index again.
+    Package::new("bar", "0.1.1")
+        .local(true)
+        .file("src/lib.rs", "pub fn bar() {}")
+        .publish();
+    registry_path().join("index").rm_rf();
+    p.cargo("update")
+        .with_stderr(
+            "\
+[INDEXING] 3 crate files in `[..]registry`
+[UPDATING] bar v0.1.0 -> v0.1.1
+[REMOVING] baz v0.1.0
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn generated_index_misnamed_crate_file() {
+    setup();
+    Package::new("bar", "0.0.1").local(true).publish();
+    registry_path().join("index").rm_rf();
+    fs::rename(
+        registry_path().join("bar-0.0.1.crate"),
+        registry_path().join("bar.crate"),
+    )
+    .unwrap();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "foo"
+                version = "0.0.1"
+                authors = []
+
+                [dependencies]
+                bar = "0.0.1"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("build")
+        .with_status(101)
+        .with_stderr(
+            "\
+[INDEXING] 1 crate files in `[..]registry`
+[ERROR] failed to get `bar` as a dependency of package `foo v0.0.1 ([CWD])`
+
+Caused by:
+  failed to load source for dependency `bar`
+
+Caused by:
+  Unable to update registry `https://[..]`
+
+Caused by:
+  failed to update replaced source registry `https://[..]`
+
+Caused by:
+  failed to index `[..]bar.crate`
+
+Caused by:
+  the crate file contains `bar v0.0.1`, and must be named `bar-0.0.1.crate`
+",
+        )
+        .run();
+}
@@ -762,3 +762,153 @@ dependencies = [
 
     p.cargo("build --locked").run();
 }
+
+#[cargo_test]
+fn v4_path_and_crates_io() {
+    let cksum010 = Package::new("a", "0.1.0").publish();
+    let cksum020 = Package::new("a", "0.2.0").publish();
+
+    let lockfile = format!(
+        r#"# This file is automatically @generated by Cargo.
+# It is not intended for manual editing.
+version = 4
+
+package = [
+    {{ name = "a", version = "0.1.0", source = "crates-io", checksum = "{}" }},
+    {{ name = "a", version = "0.2.0" }},
+    {{ name = "a", version = "0.2.0", source = "crates-io", checksum = "{}" }},
+    {{ name = "foo", version = "0.0.1", dependencies = ["a 0.1.0", "a 0.2.0", "a 0.2.0 (crates-io)"] }},
+]
+"#,
+        cksum010, cksum020,
+    );
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "foo"
+                version = "0.0.1"
+                authors = []
+
+                [dependencies]
+                a = { path = 'a' }
+                b = { version = "0.1", package = 'a' }
+                c = { version = "0.2", package = 'a' }
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file(
+            "a/Cargo.toml",
+            r#"
+                [project]
+                name = "a"
+                version = "0.2.0"
+            "#,
+        )
+        .file("a/src/lib.rs", "")
+        .file("Cargo.lock", &lockfile)
+        .build();
+
+    p.cargo("fetch").run();
+    p.cargo("fetch").run();
+
+    let lock = p.read_lockfile();
+    assert_lockfiles_eq(&lockfile, &lock);
+}
+
+#[cargo_test]
+fn lockfile_version_config() {
+    let cksum = Package::new("bar", "0.1.0").publish();
+
+    let v2 = format!(
+        r#"# This file is automatically @generated by Cargo.
+# It is not intended for manual editing.
+[[package]]
+name = "bar"
+version = "0.1.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "{}"
+
+[[package]]
+name = "foo"
+version = "0.0.1"
+dependencies = [
+ "bar",
+]
+"#,
+        cksum
+    );
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "foo"
+                version = "0.0.1"
+                authors = []
+
+                [dependencies]
+                bar = "0.1.0"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file("Cargo.lock", &v2)
+        .file(
+            ".cargo/config",
+            r#"
+                [lockfile]
+                version = 4
+            "#,
+        )
+        .build();
+
+    p.cargo("fetch")
+        .with_status(101)
+        .with_stderr(
+            "\
+[UPDATING] [..]
+[ERROR] the `lockfile.version` config value requires the \
+`-Z lockfile-version` command-line option
+",
+        )
+        .run();
+
+    p.cargo("fetch -Z lockfile-version")
+        .masquerade_as_nightly_cargo()
+        .run();
+    let v4 = format!(
+        r#"# This file is automatically @generated by Cargo.
+# It is not intended for manual editing.
+version = 4
+
+package = [
+    {{ name = "bar", version = "0.1.0", source = "crates-io", checksum = "{}" }},
+    {{ name = "foo", version = "0.0.1", dependencies = ["bar"] }},
+]
+"#,
+        cksum
+    );
+    assert_lockfiles_eq(&v4, &p.read_lockfile());
+
+    // Pinning an older version converts the lock file back.
+    p.cargo("fetch -Z lockfile-version")
+        .env("CARGO_LOCKFILE_VERSION", "2")
+        .masquerade_as_nightly_cargo()
+        .run();
+    assert_lockfiles_eq(&v2, &p.read_lockfile());
+
+    p.cargo("fetch -Z lockfile-version")
+        .env("CARGO_LOCKFILE_VERSION", "5")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] lock file version `5` specified in `lockfile.version` is not supported, \
+expected a version from 1 to 4
+",
+        )
+        .run();
+}
@@ -12,7 +12,10 @@
 extern crate cargo_test_macro;
 
 mod advanced_env;
+mod advisories;
 mod alt_registry;
+mod asymmetric_token;
+mod autodiscover_dirs;
 mod bad_config;
 mod bad_manifest_path;
 mod bench;
@@ -21,6 +24,7 @@ mod build_plan;
 mod build_script;
 mod build_script_env;
 mod build_script_extra_link_arg;
+mod build_script_sandbox;
 mod cache_messages;
 mod cargo_alias_config;
 mod cargo_command;
@@ -35,9 +39,11 @@ mod config;
 mod config_cli;
 mod config_include;
 mod corrupt_git;
+mod crate_signing;
 mod credential_process;
 mod cross_compile;
 mod cross_publish;
+mod daemon;
 mod custom_target;
 mod death;
 mod dep_info;
@@ -45,6 +51,7 @@ mod directory;
 mod doc;
 mod edition;
 mod error;
+mod expand;
 mod features;
 mod features2;
 mod features_namespaced;
@@ -57,10 +64,13 @@ mod git_auth;
 mod git_gc;
 mod glob_targets;
 mod help;
+mod incremental_dir;
+mod index_store;
 mod init;
 mod install;
 mod install_upgrade;
 mod jobserver;
+mod licenses;
 mod list_availables;
 mod local_registry;
 mod locate_project;
@@ -76,17 +86,20 @@ mod metadata;
 mod minimal_versions;
 mod multitarget;
 mod net_config;
+mod network_sandbox;
 mod new;
 mod offline;
 mod out_dir;
 mod owner;
 mod package;
 mod package_features;
+mod packaging_info;
 mod patch;
 mod path;
 mod paths;
 mod pkgid;
 mod plugins;
+mod prebuilt_install;
 mod proc_macro;
 mod profile_config;
 mod profile_custom;
@@ -94,7 +107,9 @@ mod profile_overrides;
 mod profile_targets;
 mod profiles;
 mod progress;
+mod provenance;
 mod pub_priv;
+mod pubgrub;
 mod publish;
 mod publish_lockfile;
 mod read_manifest;
@@ -102,16 +117,23 @@ mod registry;
 mod rename_deps;
 mod replace;
 mod required_features;
+mod resolve_cache;
 mod run;
+mod rust_version;
 mod rustc;
 mod rustc_info_cache;
+mod rustc_invocations;
 mod rustdoc;
 mod rustdoc_extern_html;
 mod rustdocflags;
 mod rustflags;
+mod sbom;
 mod search;
 mod shell_quoting;
+mod ssh_config;
 mod standard_lib;
+mod subcommand_context;
+mod target_dependencies;
 mod test;
 mod timings;
 mod tool_paths;
@@ -120,10 +142,13 @@ mod tree_graph_features;
 mod unit_graph;
 mod update;
 mod vendor;
+mod verify_manifest;
 mod verify_project;
 mod version;
 mod warn_on_failure;
+mod watchman;
 mod weak_dep_features;
+mod workspace_inheritance;
 mod workspaces;
 mod yank;
 
@@ -109,3 +109,99 @@ fn cargo_renders_ansi() {
         .with_stdout_contains("[..]\\u001b[38;5;9merror[..]")
         .run();
 }
+
+#[cargo_test]
+fn annotations_require_unstable_options() {
+    let p = project()
+        .file("Cargo.toml", &basic_manifest("foo", "0.1.0"))
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("build --message-format github")
+        .with_status(101)
+        .with_stderr("[ERROR] Usage of `--message-format github` requires `-Z unstable-options`")
+        .run();
+    p.cargo("build --message-format template={file}")
+        .with_status(101)
+        .with_stderr("[ERROR] Usage of `--message-format template` requires `-Z unstable-options`")
+        .run();
+}
+
+#[cargo_test]
+fn github_annotations() {
+    let p = project()
+        .file("Cargo.toml", &basic_manifest("foo", "0.1.0"))
+        .file("src/lib.rs", "pub fn f() { let x = 1; }")
+        .build();
+
+    let annotation = "\
+::warning file=src/lib.rs,line=1,endLine=1,col=18,endColumn=19,\
+title=unused variable%3A `x`::warning: unused variable: `x`%0A[..]";
+    p.cargo("build -Z unstable-options --message-format github")
+        .masquerade_as_nightly_cargo()
+        .with_stdout(annotation)
+        .with_stderr_contains("[WARNING] unused variable: `x`")
+        .run();
+
+    // Replayed warnings are annotated too.
+    p.cargo("build -Z unstable-options --message-format github")
+        .masquerade_as_nightly_cargo()
+        .with_stdout(annotation)
+        .run();
+}
+
+#[cargo_test]
+fn teamcity_annotations() {
+    let p = project()
+        .file("Cargo.toml", &basic_manifest("foo", "0.1.0"))
+        .file("src/lib.rs", "pub fn f() -> u32 { \"\" }")
+        .build();
+
+    p.cargo("build -Z unstable-options --message-format teamcity")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stdout(
+            "##teamcity[message text='src/lib.rs:1:21: mismatched types' \
+             errorDetails='error|[E0308|]: mismatched types|n[..]' status='ERROR']",
+        )
+        .with_stderr_contains("error[E0308]: mismatched types")
+        .run();
+}
+
+#[cargo_test]
+fn template_annotations() {
+    let p = project()
+        .file("Cargo.toml", &basic_manifest("foo", "0.1.0"))
+        .file("src/lib.rs", "pub fn f() { let x = 1; }")
+        .build();
+
+    p.cargo("build -Z unstable-options")
+        .arg("--message-format=template={package},{level},{file}:{line}:{column},{{{code}}}")
+        .masquerade_as_nightly_cargo()
+        .with_stdout("foo,warning,src/lib.rs:1:18,{unused_variables}")
+        .run();
+
+    p.cargo("build -Z unstable-options --message-format template={file}:{col}")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "[ERROR] unknown placeholder `{col}` in the message format template \
+             `{file}:{col}`, the supported placeholders are `{level}`, `{file}`, \
+             `{line}`, `{column}`, `{end_line}`, `{end_column}`, `{message}`, \
+             `{code}`, `{package}`, `{rendered}`",
+        )
+        .run();
+    p.cargo("build -Z unstable-options --message-format template={file")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "[ERROR] unmatched `{` in the message format template `{file`, \
+             use `{{` for a literal brace",
+        )
+        .run();
+    p.cargo("build -Z unstable-options --message-format github,template={file}")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr("[ERROR] invalid message format specifier: `template={file}`")
+        .run();
+}
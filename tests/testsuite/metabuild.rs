@@ -457,18 +457,23 @@ fn metabuild_build_plan() {
         .with_json(
             r#"
             {
+                "version": 1,
                 "invocations": [
                     {
                         "package_name": "mb",
                         "package_version": "0.5.0",
+                        "package_id": "mb 0.5.0 ([..])",
+                        "target_name": "mb",
                         "target_kind": ["lib"],
                         "compile_mode": "build",
                         "kind": null,
+                        "inputs": "{...}",
                         "deps": [],
                         "outputs": [
                             "[..]/target/debug/deps/libmb-[..].rlib",
                             "[..]/target/debug/deps/libmb-[..].rmeta"
                         ],
+                        "dep_info": "[..]/target/debug/[..]/mb-[..].d",
                         "links": {},
                         "program": "rustc",
                         "args": "{...}",
@@ -478,14 +483,18 @@ fn metabuild_build_plan() {
                     {
                         "package_name": "mb-other",
                         "package_version": "0.0.1",
+                        "package_id": "mb-other 0.0.1 ([..])",
+                        "target_name": "mb-other",
                         "target_kind": ["lib"],
                         "compile_mode": "build",
                         "kind": null,
+                        "inputs": "{...}",
                         "deps": [],
                         "outputs": [
                             "[..]/target/debug/deps/libmb_other-[..].rlib",
                             "[..]/target/debug/deps/libmb_other-[..].rmeta"
                         ],
+                        "dep_info": "[..]/target/debug/[..]/mb_other-[..].d",
                         "links": {},
                         "program": "rustc",
                         "args": "{...}",
@@ -495,11 +504,15 @@ fn metabuild_build_plan() {
                     {
                         "package_name": "foo",
                         "package_version": "0.0.1",
+                        "package_id": "foo 0.0.1 ([..])",
+                        "target_name": "metabuild-foo",
                         "target_kind": ["custom-build"],
                         "compile_mode": "build",
                         "kind": null,
+                        "inputs": "{...}",
                         "deps": [0, 1],
                         "outputs": "{...}",
+                        "dep_info": "[..]/target/debug/[..]/metabuild_foo-[..].d",
                         "links": "{...}",
                         "program": "rustc",
                         "args": "{...}",
@@ -509,11 +522,15 @@ fn metabuild_build_plan() {
                     {
                         "package_name": "foo",
                         "package_version": "0.0.1",
+                        "package_id": "foo 0.0.1 ([..])",
+                        "target_name": "metabuild-foo",
                         "target_kind": ["custom-build"],
                         "compile_mode": "run-custom-build",
                         "kind": null,
+                        "inputs": "{...}",
                         "deps": [2],
                         "outputs": [],
+                        "dep_info": null,
                         "links": {},
                         "program": "[..]/foo/target/debug/build/foo-[..]/metabuild-foo",
                         "args": [],
@@ -523,14 +540,18 @@ fn metabuild_build_plan() {
                     {
                         "package_name": "foo",
                         "package_version": "0.0.1",
+                        "package_id": "foo 0.0.1 ([..])",
+                        "target_name": "foo",
                         "target_kind": ["lib"],
                         "compile_mode": "build",
                         "kind": null,
+                        "inputs": "{...}",
                         "deps": [3],
                         "outputs": [
                             "[..]/foo/target/debug/deps/libfoo-[..].rlib",
                             "[..]/foo/target/debug/deps/libfoo-[..].rmeta"
                         ],
+                        "dep_info": "[..]/target/debug/[..]/foo-[..].d",
                         "links": "{...}",
                         "program": "rustc",
                         "args": "{...}",
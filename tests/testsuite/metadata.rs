@@ -899,6 +899,33 @@ fn workspace_metadata() {
         .run();
 }
 
+#[cargo_test]
+fn workspace_metadata_from_member() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [workspace]
+                members = ["bar"]
+
+                [workspace.metadata]
+                released = 1979-05-27T07:32:00Z
+                release = { tags = ["v1", 2, 3.5], sign = true }
+            "#,
+        )
+        .file("bar/Cargo.toml", &basic_lib_manifest("bar"))
+        .file("bar/src/lib.rs", "")
+        .build();
+
+    p.cargo("metadata --no-deps --format-version 1")
+        .cwd("bar")
+        .with_stdout_contains(
+            "[..]\"metadata\":{\"released\":\"1979-05-27T07:32:00Z\",\
+             \"release\":{\"sign\":true,\"tags\":[\"v1\",2,3.5]}}[..]",
+        )
+        .run();
+}
+
 #[cargo_test]
 fn workspace_metadata_no_deps() {
     let p = project()
@@ -1845,6 +1872,42 @@ fn rename_dependency() {
         .run();
 }
 
+#[cargo_test]
+fn rename_dependency_twice() {
+    // A package listed under different names gets a `deps` entry for each.
+    Package::new("bar", "0.1.0").publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+
+                [dependencies]
+                baz = { version = "0.1.0", package = "bar" }
+
+                [build-dependencies]
+                bar = "0.1.0"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("metadata")
+        .with_stdout_contains(
+            "[..]\"dependencies\":[\"bar 0.1.0 (registry+[..])\"],\
+             \"deps\":[\
+             {\"name\":\"bar\",\"pkg\":\"bar 0.1.0 (registry+[..])\",\
+             \"dep_kinds\":[{\"kind\":\"build\",\"target\":null}]},\
+             {\"name\":\"baz\",\"pkg\":\"bar 0.1.0 (registry+[..])\",\
+             \"dep_kinds\":[{\"kind\":null,\"target\":null}]}\
+             ][..]",
+        )
+        .run();
+}
+
 #[cargo_test]
 fn metadata_links() {
     let p = project()
@@ -2902,3 +2965,189 @@ fn dep_kinds_workspace() {
         )
         .run();
 }
+
+#[cargo_test]
+fn checksums() {
+    Package::new("bar", "0.1.0").publish();
+    Package::new("baz", "0.2.0").alternative(true).publish();
+    let git = cargo_test_support::git::new("gitdep", |p| {
+        p.file("Cargo.toml", &basic_lib_manifest("gitdep"))
+            .file("src/lib.rs", "")
+    });
+    let p = project()
+        .file(
+            "Cargo.toml",
+            &format!(
+                r#"
+                    [package]
+                    name = "foo"
+                    version = "0.1.0"
+
+                    [dependencies]
+                    bar = "0.1"
+                    baz = {{ version = "0.2", registry = "alternative" }}
+                    gitdep = {{ git = '{}' }}
+                    pathdep = {{ path = "pathdep" }}
+                "#,
+                git.url()
+            ),
+        )
+        .file("src/lib.rs", "")
+        .file("pathdep/Cargo.toml", &basic_lib_manifest("pathdep"))
+        .file("pathdep/src/lib.rs", "")
+        .build();
+
+    p.cargo("metadata --checksums")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] the `--checksums` flag is unstable, pass `-Z unstable-options` to enable it
+See https://doc.rust-lang.org/nightly/cargo/reference/unstable.html \
+for more information about the `--checksums` flag.
+",
+        )
+        .run();
+
+    let output = p
+        .cargo("metadata --format-version 1 --checksums -Z unstable-options")
+        .masquerade_as_nightly_cargo()
+        .exec_with_output()
+        .unwrap();
+    let metadata: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let expected: serde_json::Value = serde_json::from_str(
+        r#"
+        [
+          {
+            "id": "bar 0.1.0 (registry+https://github.com/rust-lang/crates.io-index)",
+            "url": "file://[..]/dl/bar/0.1.0/download",
+            "checksum": "[..]",
+            "rev": null,
+            "directory": "bar-0.1.0"
+          },
+          {
+            "id": "baz 0.2.0 (registry+file://[..]/alternative-registry)",
+            "url": "file://[..]/alt_dl/baz/0.2.0/baz-0.2.0.crate",
+            "checksum": "[..]",
+            "rev": null,
+            "directory": "baz-0.2.0"
+          },
+          {
+            "id": "gitdep 0.5.0 (git+file://[..]/gitdep#[..])",
+            "url": "file://[..]/gitdep",
+            "checksum": null,
+            "rev": "[..]",
+            "directory": null
+          }
+        ]
+        "#,
+    )
+    .unwrap();
+    if let Err(e) = cargo_test_support::find_json_mismatch(&expected, &metadata["checksums"]) {
+        panic!("{}", e);
+    }
+    let checksum = metadata["checksums"][0]["checksum"].as_str().unwrap();
+    assert_eq!(checksum.len(), 64);
+    assert!(!metadata["checksums"][2]["rev"].as_str().unwrap().is_empty());
+
+    p.cargo("metadata --checksums --no-deps -Z unstable-options")
+        .masquerade_as_nightly_cargo()
+        .with_status(1)
+        .with_stderr_contains("error: The argument '--no-deps' cannot be used with '--checksums'")
+        .run();
+}
+
+#[cargo_test]
+fn ndjson_format() {
+    Package::new("bar", "0.1.0").publish();
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [dependencies]
+                bar = "0.1"
+                pathdep = { path = "pathdep" }
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file("pathdep/Cargo.toml", &basic_lib_manifest("pathdep"))
+        .file("pathdep/src/lib.rs", "")
+        .build();
+
+    p.cargo("metadata --format ndjson")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] the `--format` flag is unstable, pass `-Z unstable-options` to enable it
+See https://doc.rust-lang.org/nightly/cargo/reference/unstable.html \
+for more information about the `--format` flag.
+",
+        )
+        .run();
+
+    let stdout = |args: &str| {
+        let output = p
+            .cargo(args)
+            .masquerade_as_nightly_cargo()
+            .exec_with_output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let metadata: serde_json::Value =
+        serde_json::from_str(&stdout("metadata --format-version 1")).unwrap();
+    let lines: Vec<serde_json::Value> =
+        stdout("metadata --format-version 1 --format ndjson -Z unstable-options")
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+    // The workspace comes first, then each package with its node.
+    let workspace = &lines[0];
+    assert_eq!(workspace["reason"], "workspace");
+    assert_eq!(workspace["version"], 1);
+    assert_eq!(
+        workspace["workspace_members"],
+        metadata["workspace_members"]
+    );
+    assert_eq!(workspace["workspace_root"], metadata["workspace_root"]);
+    assert_eq!(workspace["target_directory"], metadata["target_directory"]);
+    assert_eq!(workspace["root"], metadata["resolve"]["root"]);
+    assert_eq!(lines.len(), 1 + 2 * 3);
+    let mut packages = Vec::new();
+    let mut nodes = Vec::new();
+    for pair in lines[1..].chunks(2) {
+        let (mut package, mut node) = (pair[0].clone(), pair[1].clone());
+        assert_eq!(package["reason"], "package");
+        assert_eq!(node["reason"], "resolve-node");
+        assert_eq!(package["id"], node["id"]);
+        package.as_object_mut().unwrap().remove("reason");
+        node.as_object_mut().unwrap().remove("reason");
+        packages.push(package);
+        nodes.push(node);
+    }
+    // The members are written before their dependencies.
+    assert_eq!(packages[0]["name"], "foo");
+    let by_id = |a: &serde_json::Value, b: &serde_json::Value| {
+        a["id"].as_str().unwrap().cmp(b["id"].as_str().unwrap())
+    };
+    packages.sort_by(by_id);
+    nodes.sort_by(by_id);
+    assert_eq!(serde_json::Value::from(packages), metadata["packages"]);
+    assert_eq!(serde_json::Value::from(nodes), metadata["resolve"]["nodes"]);
+
+    let lines: Vec<serde_json::Value> =
+        stdout("metadata --format-version 1 --format ndjson --no-deps -Z unstable-options")
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["reason"], "workspace");
+    assert_eq!(lines[0]["root"], serde_json::Value::Null);
+    assert_eq!(lines[1]["reason"], "package");
+    assert_eq!(lines[1]["name"], "foo");
+}
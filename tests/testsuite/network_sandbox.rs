@@ -0,0 +1,198 @@
+//! Tests for `-Z network-sandbox`.
+
+use cargo_test_support::{basic_manifest, project};
+
+/// A build script printing whether it can create an internet socket.
+fn build_script(name: &str) -> String {
+    format!(
+        r#"
+            fn main() {{
+                match std::net::UdpSocket::bind("127.0.0.1:0") {{
+                    Ok(_) => eprintln!("{0} has network access"),
+                    Err(e) => eprintln!("{0} has no network access: {{:?}}", e.kind()),
+                }}
+            }}
+        "#,
+        name
+    )
+}
+
+#[cargo_test]
+fn requires_z_flag() {
+    let p = project()
+        .file("build.rs", &build_script("foo"))
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("build -vv")
+        .with_stderr_contains("[foo 0.0.1] foo has network access")
+        .run();
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+#[cargo_test]
+fn build_script_denied_network() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+
+                [dependencies]
+                bar = { path = "bar" }
+            "#,
+        )
+        .file("build.rs", &build_script("foo"))
+        .file("src/lib.rs", "")
+        .file("bar/Cargo.toml", &basic_manifest("bar", "0.0.1"))
+        .file("bar/build.rs", &build_script("bar"))
+        .file("bar/src/lib.rs", "")
+        .build();
+
+    p.cargo("build -vv -Z network-sandbox")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains("[foo 0.0.1] foo has no network access: PermissionDenied")
+        .with_stderr_contains("[bar 0.0.1] bar has no network access: PermissionDenied")
+        .run();
+
+    p.change_file(
+        ".cargo/config",
+        r#"
+            [sandbox]
+            allow-network = ["bar"]
+        "#,
+    );
+    p.change_file("build.rs", &build_script("foo2"));
+    p.change_file("bar/build.rs", &build_script("bar2"));
+    p.cargo("build -vv -Z network-sandbox")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains("[foo 0.0.1] foo2 has no network access: PermissionDenied")
+        .with_stderr_contains("[bar 0.0.1] bar2 has network access")
+        .run();
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+#[cargo_test]
+fn proc_macro_denied_network() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+
+                [dependencies]
+                pm = { path = "pm" }
+            "#,
+        )
+        .file(
+            "src/main.rs",
+            r#"
+                pm::network!();
+
+                fn main() {
+                    println!("network: {}", NETWORK);
+                }
+            "#,
+        )
+        .file(
+            "pm/Cargo.toml",
+            r#"
+                [package]
+                name = "pm"
+                version = "0.0.1"
+
+                [lib]
+                proc-macro = true
+            "#,
+        )
+        .file(
+            "pm/src/lib.rs",
+            r#"
+                extern crate proc_macro;
+                use proc_macro::TokenStream;
+
+                #[proc_macro]
+                pub fn network(_input: TokenStream) -> TokenStream {
+                    let network = std::net::UdpSocket::bind("127.0.0.1:0").is_ok();
+                    format!("const NETWORK: bool = {};", network).parse().unwrap()
+                }
+            "#,
+        )
+        .build();
+
+    p.cargo("run -Z network-sandbox")
+        .masquerade_as_nightly_cargo()
+        .with_stdout("network: false")
+        .run();
+
+    p.change_file(
+        ".cargo/config",
+        r#"
+            [sandbox]
+            allow-network = ["pm:0.0.1"]
+        "#,
+    );
+    p.change_file(
+        "src/main.rs",
+        &p.read_file("src/main.rs").replace("network:", "net:"),
+    );
+    p.cargo("run -Z network-sandbox")
+        .masquerade_as_nightly_cargo()
+        .with_stdout("net: true")
+        .run();
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+#[cargo_test]
+fn invalid_allow_network_spec() {
+    let p = project()
+        .file("src/lib.rs", "")
+        .file(
+            ".cargo/config",
+            r#"
+                [sandbox]
+                allow-network = ["bar:not a version"]
+            "#,
+        )
+        .build();
+
+    p.cargo("build -Z network-sandbox")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains(
+            "[ERROR] invalid package ID specification `bar:not a version` \
+             in `sandbox.allow-network`",
+        )
+        .run();
+}
+
+#[cfg(windows)]
+#[cargo_test]
+fn unsupported_platform() {
+    let p = project()
+        .file("Cargo.toml", &basic_manifest("foo", "0.0.1"))
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("build -Z network-sandbox")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "[ERROR] denying network access to build scripts and procedural macros \
+             isn't supported on this platform",
+        )
+        .run();
+}
@@ -1,5 +1,6 @@
 //! Tests for --offline flag.
 
+use cargo_test_support::paths::{self, CargoPathExt};
 use cargo_test_support::{basic_manifest, git, main_file, path2url, project, registry::Package};
 use std::fs;
 
@@ -59,7 +60,12 @@ fn offline_missing_optional() {
     p.cargo("build --offline --features=opt_dep")
         .with_stderr(
             "\
-[ERROR] failed to download `opt_dep v1.0.0`
+[ERROR] the following packages in Cargo.lock are not available offline:
+  opt_dep v1.0.0: the `.crate` file is not downloaded
+    needed by: foo v0.1.0 -> opt_dep v1.0.0
+
+Caused by:
+  failed to download `opt_dep v1.0.0`
 
 Caused by:
   can't make HTTP request in the offline mode
@@ -322,7 +328,14 @@ fn compile_offline_while_transitive_dep_not_cached() {
         .with_status(101)
         .with_stderr(
             "\
-[ERROR] failed to download `bar v0.1.0`
+[ERROR] the following packages in Cargo.lock are not available offline:
+  bar v0.1.0: the `.crate` file is not downloaded
+    needed by: foo v0.0.1 -> bar v0.1.0
+  baz v1.0.0: the `.crate` file is not downloaded
+    needed by: foo v0.0.1 -> bar v0.1.0 -> baz v1.0.0
+
+Caused by:
+  failed to download `bar v0.1.0`
 
 Caused by:
   can't make HTTP request in the offline mode
@@ -562,3 +575,101 @@ fn offline_with_all_patched() {
 
     p.cargo("check --offline").run();
 }
+
+#[cargo_test]
+fn fetch_offline_report() {
+    Package::new("bar", "0.1.0").publish();
+    Package::new("baz", "0.1.0").publish();
+    let git_project = git::new("dep1", |project| {
+        project
+            .file("Cargo.toml", &basic_manifest("dep1", "0.5.0"))
+            .file("src/lib.rs", "")
+    });
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+
+            [dependencies]
+            bar = "0.1"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+    p.cargo("fetch").run();
+
+    // Add dependencies which are locked, but neither downloaded nor fetched.
+    p.change_file(
+        "Cargo.toml",
+        &format!(
+            r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+
+            [dependencies]
+            bar = "0.1"
+            baz = "0.1"
+            dep1 = {{ git = '{}' }}
+            "#,
+            git_project.url()
+        ),
+    );
+    p.cargo("generate-lockfile").run();
+    paths::home().join(".cargo/git").rm_rf();
+
+    let report = "\
+the following packages in Cargo.lock are not available offline:
+  baz v0.1.0: the `.crate` file is not downloaded
+    needed by: foo v0.0.1 -> baz v0.1.0
+  dep1 v0.5.0 ([..]): git revision `[..]` is not fetched
+    needed by: foo v0.0.1 -> dep1 v0.5.0
+";
+    p.cargo("fetch --offline-report")
+        .with_stdout(report)
+        .with_stderr("")
+        .run();
+
+    p.cargo("build --offline")
+        .with_status(101)
+        .with_stderr(&format!(
+            "[ERROR] {}\n\
+Caused by:
+  failed to get `dep1` as a dependency of package `foo v0.0.1 ([..])`
+
+Caused by:
+  failed to load source for dependency `dep1`
+
+Caused by:
+  Unable to update [..]
+
+Caused by:
+  can't checkout from '[..]': you are in the offline mode (--offline)
+",
+            report
+        ))
+        .run();
+
+    p.cargo("fetch").run();
+    p.cargo("fetch --offline-report")
+        .with_stdout("all packages in Cargo.lock are available offline")
+        .run();
+}
+
+#[cargo_test]
+fn fetch_offline_report_without_lockfile() {
+    let p = project().file("src/lib.rs", "").build();
+    p.cargo("fetch --offline-report")
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] a Cargo.lock file is required to check which packages are available offline, \
+run `cargo generate-lockfile` to create one
+",
+        )
+        .run();
+}
@@ -0,0 +1,221 @@
+//! Tests for the `cargo packaging-info` command.
+
+use cargo_test_support::registry::Package;
+use cargo_test_support::{basic_manifest, project};
+
+#[cargo_test]
+fn packaging_info() {
+    Package::new("bar", "0.1.0")
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "bar"
+                version = "0.1.0"
+                license = "MIT"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .publish();
+    Package::new("baz", "0.2.0").dep("bar", "0.1").publish();
+    Package::new("builddep", "1.0.0").publish();
+    Package::new("devdep", "1.0.0").publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "1.2.3"
+                description = "The foo package"
+                license = "MIT OR Apache-2.0"
+                repository = "https://example.com/foo"
+
+                [lib]
+                crate-type = ["cdylib", "rlib"]
+
+                [[bin]]
+                name = "foo-cli"
+                path = "src/main.rs"
+
+                [[bin]]
+                name = "extra"
+                path = "src/extra.rs"
+                required-features = ["extra"]
+
+                [features]
+                extra = []
+
+                [dependencies]
+                baz = "0.2"
+
+                [build-dependencies]
+                builddep = "1.0"
+
+                [dev-dependencies]
+                devdep = "1.0"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file("src/main.rs", "fn main() {}")
+        .file("src/extra.rs", "fn main() {}")
+        .file("build.rs", "fn main() {}")
+        .file("LICENSE-MIT", "")
+        .file("LICENSE-APACHE", "")
+        .file("README.md", "")
+        .build();
+
+    let (cdylib, soname, bin) = if cfg!(target_os = "linux") {
+        ("libfoo.so", "\"libfoo.so.1\"", "foo-cli")
+    } else if cfg!(target_os = "macos") {
+        ("libfoo.dylib", "null", "foo-cli")
+    } else if cfg!(windows) {
+        ("foo.dll", "null", "foo-cli.exe")
+    } else {
+        return;
+    };
+    p.cargo("packaging-info -Zunstable-options")
+        .masquerade_as_nightly_cargo()
+        .with_json(&format!(
+            r#"
+            {{
+              "version": 1,
+              "target": "[..]",
+              "packages": [
+                {{
+                  "id": "foo 1.2.3 ([..])",
+                  "name": "foo",
+                  "version": "1.2.3",
+                  "description": "The foo package",
+                  "homepage": null,
+                  "repository": "https://example.com/foo",
+                  "license": "MIT OR Apache-2.0",
+                  "license_files": [
+                    "[..]/foo/LICENSE-APACHE",
+                    "[..]/foo/LICENSE-MIT"
+                  ],
+                  "binaries": [
+                    {{
+                      "name": "foo-cli",
+                      "install_path": "bin/{bin}"
+                    }}
+                  ],
+                  "cdylibs": [
+                    {{
+                      "name": "foo",
+                      "file_name": "{cdylib}",
+                      "install_path": "lib/{cdylib}",
+                      "soname": {soname},
+                      "soversion": "1"
+                    }}
+                  ],
+                  "dependencies": [
+                    {{
+                      "id": "bar 0.1.0 (registry+https://github.com/rust-lang/crates.io-index)",
+                      "name": "bar",
+                      "version": "0.1.0",
+                      "source": "registry+https://github.com/rust-lang/crates.io-index",
+                      "license": "MIT",
+                      "license_file": null
+                    }},
+                    {{
+                      "id": "baz 0.2.0 (registry+https://github.com/rust-lang/crates.io-index)",
+                      "name": "baz",
+                      "version": "0.2.0",
+                      "source": "registry+https://github.com/rust-lang/crates.io-index",
+                      "license": null,
+                      "license_file": null
+                    }},
+                    {{
+                      "id": "builddep 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+                      "name": "builddep",
+                      "version": "1.0.0",
+                      "source": "registry+https://github.com/rust-lang/crates.io-index",
+                      "license": null,
+                      "license_file": null
+                    }}
+                  ]
+                }}
+              ]
+            }}
+            "#,
+            bin = bin,
+            cdylib = cdylib,
+            soname = soname,
+        ))
+        .run();
+
+    // Binaries are only installed with their required features.
+    p.cargo("packaging-info -Zunstable-options --features extra")
+        .masquerade_as_nightly_cargo()
+        .with_stdout_contains(
+            r#"[..]"binaries":[{"name":"foo-cli",[..]},{"name":"extra","install_path":"bin/extra[..]"}][..]"#,
+        )
+        .run();
+}
+
+#[cargo_test]
+fn zero_major_soversion() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.4.1"
+                license-file = "COPYING"
+
+                [lib]
+                crate-type = ["cdylib"]
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file("COPYING", "")
+        .build();
+
+    p.cargo("packaging-info -Zunstable-options")
+        .masquerade_as_nightly_cargo()
+        .with_stdout_contains(r#"[..]"license_files":["[..]/foo/COPYING"][..]"#)
+        .with_stdout_contains(r#"[..]"soversion":"0.4"[..]"#)
+        .run();
+}
+
+#[cargo_test]
+fn workspace_members() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [workspace]
+                members = ["a", "b"]
+            "#,
+        )
+        .file("a/Cargo.toml", &basic_manifest("a", "0.1.0"))
+        .file("a/src/main.rs", "fn main() {}")
+        .file("b/Cargo.toml", &basic_manifest("b", "0.1.0"))
+        .file("b/src/lib.rs", "")
+        .build();
+
+    p.cargo("packaging-info -Zunstable-options --workspace")
+        .masquerade_as_nightly_cargo()
+        .with_stdout_contains(
+            r#"[..]"packages":[{"id":"a 0.1.0 [..]"[..]},{"id":"b 0.1.0 [..]"[..]}][..]"#,
+        )
+        .run();
+    p.cargo("packaging-info -Zunstable-options -p b")
+        .masquerade_as_nightly_cargo()
+        .with_stdout_contains(r#"[..]"packages":[{"id":"b 0.1.0 [..]","name":"b",[..]"binaries":[],"cdylibs":[],"dependencies":[]}]}"#)
+        .run();
+}
+
+#[cargo_test]
+fn packaging_info_requires_unstable_options() {
+    let p = project().file("src/lib.rs", "").build();
+
+    p.cargo("packaging-info")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr("[ERROR] `cargo packaging-info` requires `-Z unstable-options`")
+        .run();
+}
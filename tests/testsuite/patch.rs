@@ -1222,6 +1222,61 @@ fn multipatch() {
     p.cargo("build").run();
 }
 
+#[cargo_test]
+fn multipatch_path_different_majors() {
+    // Patching two semver-incompatible versions of the same crate from local
+    // paths keeps both locked, so later builds don't re-resolve.
+    Package::new("serde", "0.9.0").publish();
+    Package::new("serde", "1.0.0").publish();
+    Package::new("legacy", "0.1.0")
+        .dep("serde", "0.9")
+        .publish();
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+
+                [dependencies]
+                serde = "1.0"
+                legacy = "0.1"
+
+                [patch.crates-io]
+                serde = { path = "serde" }
+                serde09 = { path = "serde09", package = "serde" }
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file("serde/Cargo.toml", &basic_manifest("serde", "1.0.1"))
+        .file("serde/src/lib.rs", "")
+        .file("serde09/Cargo.toml", &basic_manifest("serde", "0.9.1"))
+        .file("serde09/src/lib.rs", "")
+        .build();
+
+    p.cargo("build")
+        .with_stderr_unordered(
+            "\
+[UPDATING] `[ROOT][..]` index
+[DOWNLOADING] crates ...
+[DOWNLOADED] legacy v0.1.0 ([..])
+[COMPILING] serde v0.9.1 ([CWD]/serde09)
+[COMPILING] serde v1.0.1 ([CWD]/serde)
+[COMPILING] legacy v0.1.0
+[COMPILING] foo v0.0.1 ([CWD])
+[FINISHED] [..]
+",
+        )
+        .run();
+    let lock = p.read_lockfile();
+    assert!(lock.contains("\"serde 0.9.1\""));
+    assert!(lock.contains("\"serde 1.0.1\""));
+
+    p.cargo("build").with_stderr("[FINISHED] [..]").run();
+    assert_eq!(lock, p.read_lockfile());
+}
+
 #[cargo_test]
 fn patch_same_version() {
     let bar = git::repo(&paths::root().join("override"))
@@ -0,0 +1,200 @@
+//! Tests for `cargo install -Z prebuilt-install`.
+
+use cargo::util::paseto::SecretKey;
+use cargo_test_support::git;
+use cargo_test_support::install::{cargo_home, exe};
+use cargo_test_support::registry::{self, Package};
+use cargo_test_support::{cargo_process, rustc_host};
+use std::fs;
+
+/// Sets the `prebuilt-dl` field of the `config.json` of the registry.
+fn enable_prebuilt_dl() {
+    registry::init();
+    let path = registry::registry_path();
+    fs::write(
+        path.join("config.json"),
+        format!(
+            r#"{{"dl":"{}","api":"{}","prebuilt-dl":"{}/{{crate}}/{{version}}/{{target}}.tar.gz"}}"#,
+            registry::dl_url(),
+            registry::api_url(),
+            registry::prebuilt_dl_url()
+        ),
+    )
+    .unwrap();
+    let repo = git2::Repository::open(&path).unwrap();
+    git::add(&repo);
+    git::commit(&repo);
+}
+
+fn pkg_with_prebuilt(name: &str) -> Package {
+    let mut pkg = Package::new(name, "0.1.0");
+    pkg.file("src/main.rs", "fn main() {}").prebuilt(
+        &rustc_host(),
+        &[(&exe(name), &format!("prebuilt {}", name))],
+    );
+    pkg
+}
+
+/// Returns whether the binary `name` installed is the prebuilt one.
+fn is_prebuilt(name: &str) -> bool {
+    let contents = fs::read(cargo_home().join("bin").join(exe(name))).unwrap();
+    contents == format!("prebuilt {}", name).as_bytes()
+}
+
+#[cargo_test]
+fn installs_prebuilt_binaries() {
+    enable_prebuilt_dl();
+    pkg_with_prebuilt("foo").publish();
+
+    cargo_process("install foo -Z prebuilt-install")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(&format!(
+            "\
+[UPDATING] `[..]` index
+[DOWNLOADING] crates ...
+[DOWNLOADED] foo v0.1.0 (registry [..])
+[INSTALLING] foo v0.1.0
+[DOWNLOADING] prebuilt binaries of `foo v0.1.0` for `{}`
+[INSTALLING] [CWD]/home/.cargo/bin/foo[EXE]
+[INSTALLED] package `foo v0.1.0` (executable `foo[EXE]`)
+[WARNING] be sure to add [..]
+",
+            rustc_host()
+        ))
+        .run();
+    assert!(is_prebuilt("foo"));
+}
+
+#[cargo_test]
+fn builds_without_flag_or_with_options() {
+    enable_prebuilt_dl();
+    pkg_with_prebuilt("foo").publish();
+
+    cargo_process("install foo")
+        .with_stderr_contains("[COMPILING] foo v0.1.0")
+        .run();
+    assert!(!is_prebuilt("foo"));
+
+    // Prebuilt binaries are only used for the release profile.
+    cargo_process("install foo --debug --force -Z prebuilt-install")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains("[COMPILING] foo v0.1.0")
+        .with_stderr_does_not_contain("[..]prebuilt[..]")
+        .run();
+    assert!(!is_prebuilt("foo"));
+}
+
+#[cargo_test]
+fn builds_from_source_on_checksum_mismatch() {
+    enable_prebuilt_dl();
+    pkg_with_prebuilt("foo").publish();
+    let archive = registry::prebuilt_dl_path()
+        .join("foo/0.1.0")
+        .join(format!("{}.tar.gz", rustc_host()));
+    fs::write(&archive, "not an archive").unwrap();
+
+    cargo_process("install foo -Z prebuilt-install")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains(
+            "\
+[WARNING] failed to install the prebuilt binaries of `foo v0.1.0`, building it from source instead
+
+the checksum of the archive is [..], not [..] as listed in the index
+",
+        )
+        .with_stderr_contains("[COMPILING] foo v0.1.0")
+        .run();
+    assert!(!is_prebuilt("foo"));
+}
+
+#[cargo_test]
+fn checks_signature_with_trusted_keys() {
+    enable_prebuilt_dl();
+    let trusted = SecretKey::generate().unwrap();
+    let untrusted = SecretKey::generate().unwrap();
+    fs::write(
+        cargo_home().join("config"),
+        format!(
+            "{}\n[registry]\ntrusted-keys = [\"{}\"]\n",
+            fs::read_to_string(cargo_home().join("config")).unwrap(),
+            trusted.public_paserk()
+        ),
+    )
+    .unwrap();
+    pkg_with_prebuilt("foo")
+        .signed(&trusted.to_paserk())
+        .publish();
+    pkg_with_prebuilt("bar")
+        .signed(&untrusted.to_paserk())
+        .publish();
+
+    cargo_process("install foo -Z prebuilt-install")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_does_not_contain("[COMPILING][..]")
+        .run();
+    assert!(is_prebuilt("foo"));
+
+    cargo_process("install bar -Z prebuilt-install")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains(
+            "\
+[WARNING] failed to install the prebuilt binaries of `bar v0.1.0`, building it from source instead
+
+failed to verify the signature of the archive
+
+Caused by:
+  it isn't signed by any of the trusted keys of the registry
+",
+        )
+        .with_stderr_contains("[COMPILING] bar v0.1.0")
+        .run();
+    assert!(!is_prebuilt("bar"));
+}
+
+#[cargo_test]
+fn refuses_signature_for_another_target() {
+    enable_prebuilt_dl();
+    let key = SecretKey::generate().unwrap();
+    fs::write(
+        cargo_home().join("config"),
+        format!(
+            "{}\n[registry]\ntrusted-keys = [\"{}\"]\n",
+            fs::read_to_string(cargo_home().join("config")).unwrap(),
+            key.public_paserk()
+        ),
+    )
+    .unwrap();
+    // The same archive, signed for another target.
+    pkg_with_prebuilt("foo")
+        .prebuilt("x86_64-unknown-other", &[(&exe("foo"), "prebuilt foo")])
+        .signed(&key.to_paserk())
+        .publish();
+
+    let path = registry::registry_path();
+    let index = path.join("3/f/foo");
+    let mut entry: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&index).unwrap()).unwrap();
+    let prebuilt = entry["prebuilt"].as_array_mut().unwrap();
+    assert_eq!(prebuilt[0]["cksum"], prebuilt[1]["cksum"]);
+    prebuilt[0]["sig"] = prebuilt[1]["sig"].clone();
+    fs::write(&index, entry.to_string()).unwrap();
+    let repo = git2::Repository::open(&path).unwrap();
+    git::add(&repo);
+    git::commit(&repo);
+
+    cargo_process("install foo -Z prebuilt-install")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains(
+            "\
+[WARNING] failed to install the prebuilt binaries of `foo v0.1.0`, building it from source instead
+
+failed to verify the signature of the archive
+
+Caused by:
+  the signature is for the binaries of `foo v0.1.0` prebuilt for `x86_64-unknown-other`
+",
+        )
+        .with_stderr_contains("[COMPILING] foo v0.1.0")
+        .run();
+    assert!(!is_prebuilt("foo"));
+}
@@ -0,0 +1,223 @@
+//! Tests for `cargo publish --provenance`.
+
+use std::convert::TryInto;
+use std::fs;
+
+use cargo::util::Sha256;
+use cargo_test_support::registry::{self, registry_path};
+use cargo_test_support::{git, project, Project};
+use serde_json::{json, Value};
+
+const MANIFEST: &str = r#"
+    [package]
+    name = "foo"
+    version = "0.0.1"
+    license = "MIT"
+    description = "foo"
+"#;
+
+/// A `foo` package committed to a git repository with an `origin` remote.
+/// Returns the project and the commit.
+fn git_project() -> (Project, String) {
+    let p = project().no_manifest().build();
+    let repo = git::repo(&p.root())
+        .file("Cargo.toml", MANIFEST)
+        .file("src/lib.rs", "")
+        .build();
+    git2::Repository::open(repo.root())
+        .unwrap()
+        .remote("origin", "https://example.com/foo.git")
+        .unwrap();
+    (p, repo.revparse_head())
+}
+
+/// Makes the registry accept the provenance of published crates.
+fn accept_provenance() {
+    let repo = git2::Repository::open(registry_path()).unwrap();
+    fs::write(
+        registry_path().join("config.json"),
+        format!(
+            r#"{{"dl": "{}", "api": "{}", "provenance": true}}"#,
+            registry::dl_url(),
+            registry::api_url()
+        ),
+    )
+    .unwrap();
+    git::add(&repo);
+    git::commit(&repo);
+}
+
+/// Returns the JSON of the last publish request, and the checksum of the
+/// uploaded crate.
+fn upload() -> (Value, String) {
+    let upload = fs::read(registry::api_path().join("api/v1/crates/new")).unwrap();
+    let json_len = u32::from_le_bytes(upload[..4].try_into().unwrap()) as usize;
+    let json = serde_json::from_slice(&upload[4..4 + json_len]).unwrap();
+    let krate = &upload[4 + json_len + 4..];
+    (json, Sha256::new().update(krate).finish_hex())
+}
+
+#[cargo_test]
+fn provenance_requires_unstable_options() {
+    registry::init();
+    let (p, _) = git_project();
+
+    p.cargo("publish --provenance --token sekrit")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] the `--provenance` flag is unstable, pass `-Z unstable-options` to enable it
+See https://doc.rust-lang.org/nightly/cargo/reference/unstable.html \
+for more information about the `--provenance` flag.
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn provenance_sent_to_registry() {
+    registry::init();
+    accept_provenance();
+    let (p, head) = git_project();
+
+    p.cargo("publish --provenance -Z unstable-options --no-verify --token sekrit")
+        .masquerade_as_nightly_cargo()
+        .env_remove("GITHUB_ACTIONS")
+        .env_remove("GITLAB_CI")
+        .with_stderr_does_not_contain("[..]Writing[..]")
+        .run();
+
+    let (json, cksum) = upload();
+    let provenance = &json["provenance"];
+    assert_eq!(provenance["_type"], "https://in-toto.io/Statement/v0.1");
+    assert_eq!(
+        provenance["subject"],
+        json!([{"name": "foo-0.0.1.crate", "digest": {"sha256": cksum}}])
+    );
+    assert_eq!(
+        provenance["predicateType"],
+        "https://slsa.dev/provenance/v0.2"
+    );
+    let predicate = &provenance["predicate"];
+    assert_eq!(predicate["builder"]["id"], "urn:cargo:local");
+    assert_eq!(
+        predicate["invocation"]["configSource"],
+        json!({
+            "uri": "git+https://example.com/foo.git",
+            "digest": {"sha1": head},
+            "entryPoint": "Cargo.toml",
+        })
+    );
+    assert_eq!(
+        predicate["materials"],
+        json!([{"uri": "git+https://example.com/foo.git", "digest": {"sha1": head}}])
+    );
+    let parameters = &predicate["invocation"]["parameters"];
+    assert_eq!(parameters["package"], "foo");
+    assert_eq!(parameters["version"], "0.0.1");
+    assert_eq!(parameters["verify"], false);
+    let environment = &predicate["invocation"]["environment"];
+    assert!(environment["cargo"].as_str().unwrap().starts_with("cargo "));
+    assert_eq!(environment["ci"], Value::Null);
+    assert_eq!(predicate["metadata"]["completeness"]["materials"], true);
+
+    // Nothing is generated without `--provenance`.
+    p.change_file("Cargo.toml", &MANIFEST.replace("0.0.1", "0.0.2"));
+    p.cargo("publish --no-verify --allow-dirty --token sekrit")
+        .run();
+    assert!(upload().0.get("provenance").is_none());
+}
+
+#[cargo_test]
+fn provenance_written_locally() {
+    registry::init();
+    let (p, head) = git_project();
+
+    p.cargo("publish --provenance -Z unstable-options --no-verify --token sekrit")
+        .masquerade_as_nightly_cargo()
+        .env("GITHUB_ACTIONS", "true")
+        .env("GITHUB_SERVER_URL", "https://github.com")
+        .env("GITHUB_REPOSITORY", "owner/foo")
+        .env("GITHUB_RUN_ID", "42")
+        .env("GITHUB_RUN_ATTEMPT", "2")
+        .with_stderr_contains(
+            "[..]Writing provenance to [CWD]/target/package/foo-0.0.1.provenance.json",
+        )
+        .run();
+
+    let (json, cksum) = upload();
+    assert!(json.get("provenance").is_none());
+    let provenance: Value =
+        serde_json::from_str(&p.read_file("target/package/foo-0.0.1.provenance.json")).unwrap();
+    assert_eq!(provenance["subject"][0]["digest"]["sha256"], cksum);
+    let predicate = &provenance["predicate"];
+    assert_eq!(
+        predicate["builder"]["id"],
+        "https://github.com/owner/foo/actions/runs/42"
+    );
+    assert_eq!(
+        predicate["metadata"]["buildInvocationId"],
+        "https://github.com/owner/foo/actions/runs/42/attempts/2"
+    );
+    assert_eq!(
+        predicate["invocation"]["environment"]["ci"],
+        "github-actions"
+    );
+    assert_eq!(
+        predicate["invocation"]["configSource"]["digest"]["sha1"],
+        head
+    );
+}
+
+#[cargo_test]
+fn provenance_of_dirty_package() {
+    registry::init();
+    accept_provenance();
+    let (p, head) = git_project();
+    p.change_file("src/lib.rs", "pub fn f() {}");
+
+    // With `--dry-run`, the provenance is written locally even if the
+    // registry accepts it.
+    p.cargo("publish --provenance -Z unstable-options --no-verify --allow-dirty --dry-run")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains(
+            "[..]Writing provenance to [CWD]/target/package/foo-0.0.1.provenance.json",
+        )
+        .run();
+
+    let provenance: Value =
+        serde_json::from_str(&p.read_file("target/package/foo-0.0.1.provenance.json")).unwrap();
+    let predicate = &provenance["predicate"];
+    assert_eq!(
+        predicate["invocation"]["configSource"]["digest"]["sha1"],
+        head
+    );
+    assert_eq!(predicate["invocation"]["parameters"]["allow_dirty"], true);
+    assert_eq!(predicate["materials"], json!([]));
+    assert_eq!(predicate["metadata"]["completeness"]["materials"], false);
+}
+
+#[cargo_test]
+fn provenance_without_git() {
+    registry::init();
+    let p = project()
+        .file("Cargo.toml", MANIFEST)
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("publish --provenance -Z unstable-options --no-verify --dry-run")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains(
+            "[WARNING] the provenance of `foo` does not record its source revision, \
+             since it was not packaged from a git checkout",
+        )
+        .run();
+
+    let provenance: Value =
+        serde_json::from_str(&p.read_file("target/package/foo-0.0.1.provenance.json")).unwrap();
+    assert_eq!(
+        provenance["predicate"]["invocation"]["configSource"],
+        json!({})
+    );
+}
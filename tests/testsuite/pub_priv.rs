@@ -1,7 +1,7 @@
 //! Tests for public/private dependencies.
 
-use cargo_test_support::registry::Package;
-use cargo_test_support::{is_nightly, project};
+use cargo_test_support::registry::{Dependency, Package};
+use cargo_test_support::{is_nightly, project, publish};
 
 #[cargo_test]
 fn exported_priv_warning() {
@@ -200,3 +200,160 @@ Caused by:
         )
         .run()
 }
+
+#[cargo_test]
+fn publish_includes_public() {
+    Package::new("pub_dep", "0.1.0").publish();
+    Package::new("priv_dep", "0.1.0").publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                cargo-features = ["public-dependency"]
+
+                [package]
+                name = "foo"
+                version = "0.1.0"
+                description = "foo"
+                license = "MIT"
+                homepage = "https://example.com/"
+
+                [dependencies]
+                pub_dep = { version = "0.1.0", public = true }
+                priv_dep = "0.1.0"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("publish --no-verify --token sekrit")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[UPDATING] [..]
+[PACKAGING] foo v0.1.0 [..]
+[UPLOADING] foo v0.1.0 [..]
+",
+        )
+        .run();
+
+    publish::validate_upload(
+        r#"
+        {
+          "authors": [],
+          "badges": {},
+          "categories": [],
+          "deps": [
+            {
+              "default_features": true,
+              "features": [],
+              "kind": "normal",
+              "name": "priv_dep",
+              "optional": false,
+              "public": false,
+              "registry": "https://github.com/rust-lang/crates.io-index",
+              "target": null,
+              "version_req": "^0.1.0"
+            },
+            {
+              "default_features": true,
+              "features": [],
+              "kind": "normal",
+              "name": "pub_dep",
+              "optional": false,
+              "public": true,
+              "registry": "https://github.com/rust-lang/crates.io-index",
+              "target": null,
+              "version_req": "^0.1.0"
+            }
+          ],
+          "description": "foo",
+          "documentation": null,
+          "features": {},
+          "homepage": "https://example.com/",
+          "keywords": [],
+          "license": "MIT",
+          "license_file": null,
+          "links": null,
+          "name": "foo",
+          "readme": null,
+          "readme_file": null,
+          "repository": null,
+          "vers": "0.1.0"
+        }
+        "#,
+        "foo-0.1.0.crate",
+        &["Cargo.toml", "Cargo.toml.orig", "src/lib.rs"],
+    );
+}
+
+#[cargo_test]
+fn resolve_avoids_public_conflict() {
+    // `bar 0.2.0` publicly exposes `baz 0.2.0`, which would conflict with
+    // the `baz 0.1.0` that `foo` already sees publicly through `shared`.
+    Package::new("baz", "0.1.0").publish();
+    Package::new("baz", "0.2.0").publish();
+    Package::new("shared", "0.1.0")
+        .add_dep(Dependency::new("baz", "0.1.0").public(true))
+        .publish();
+    Package::new("bar", "0.1.0")
+        .add_dep(Dependency::new("baz", "0.1.0").public(true))
+        .publish();
+    Package::new("bar", "0.2.0")
+        .add_dep(Dependency::new("baz", "0.2.0").public(true))
+        .publish();
+
+    let manifest = |features: &str| {
+        format!(
+            r#"
+                {}
+
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [dependencies]
+                shared = {{ version = "0.1.0" }}
+                bar = ">= 0.1.0"
+            "#,
+            features
+        )
+    };
+
+    let p = project()
+        .file("Cargo.toml", &manifest(""))
+        .file("src/lib.rs", "")
+        .build();
+
+    // Without the feature, the newest `bar` is chosen.
+    p.cargo("tree")
+        .with_stdout(
+            "\
+foo v0.1.0 ([ROOT]/foo)
+├── bar v0.2.0
+│   └── baz v0.2.0
+└── shared v0.1.0
+    └── baz v0.1.0
+",
+        )
+        .run();
+
+    p.change_file(
+        "Cargo.toml",
+        &manifest(r#"cargo-features = ["public-dependency"]"#),
+    );
+    p.cargo("update").masquerade_as_nightly_cargo().run();
+    p.cargo("tree")
+        .masquerade_as_nightly_cargo()
+        .with_stdout(
+            "\
+foo v0.1.0 ([ROOT]/foo)
+├── bar v0.1.0
+│   └── baz v0.1.0
+└── shared v0.1.0
+    └── baz v0.1.0
+",
+        )
+        .run();
+}
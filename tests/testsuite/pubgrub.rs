@@ -0,0 +1,256 @@
+//! Tests for the PubGrub based resolver (`-Z pubgrub`).
+//!
+//! Note: The comparison with the regular resolver on random registries is
+//! located in the resolver-tests package.
+
+use cargo_test_support::project;
+use cargo_test_support::registry::{Dependency, Package};
+
+#[cargo_test]
+fn gated() {
+    let p = project().file("src/lib.rs", "").build();
+
+    p.cargo("generate-lockfile -Zpubgrub")
+        .with_status(101)
+        .with_stderr_contains(
+            "[ERROR] the `-Z` flag is only accepted on the nightly channel of Cargo[..]",
+        )
+        .run();
+}
+
+// The lock file is the same as the one from the regular resolver.
+#[cargo_test]
+fn same_lockfile() {
+    Package::new("log", "0.4.0").publish();
+    Package::new("log", "0.4.8").publish();
+    Package::new("serde", "1.0.0").feature("std", &[]).publish();
+    Package::new("serde", "1.0.9")
+        .feature("std", &[])
+        .feature("derive", &["serde_derive"])
+        .add_dep(Dependency::new("serde_derive", "1.0").optional(true))
+        .publish();
+    Package::new("serde_derive", "1.0.3").publish();
+    Package::new("rand", "0.6.5").dep("log", "0.4").publish();
+    Package::new("rand", "0.7.3")
+        .add_dep(Dependency::new("log", "0.4").optional(true))
+        .feature("default", &["log"])
+        .publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [dependencies]
+                serde = { version = "1.0", features = ["derive"] }
+                rand = "0.7"
+                old_rand = { package = "rand", version = "0.6" }
+                bar = { path = "bar" }
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file(
+            "bar/Cargo.toml",
+            r#"
+                [package]
+                name = "bar"
+                version = "0.1.0"
+
+                [dependencies]
+                serde = { version = "1.0.0", default-features = false, features = ["std"] }
+            "#,
+        )
+        .file("bar/src/lib.rs", "")
+        .build();
+
+    p.cargo("generate-lockfile").run();
+    let lock = p.read_lockfile();
+
+    p.cargo("generate-lockfile -Zpubgrub")
+        .masquerade_as_nightly_cargo()
+        .run();
+    assert_eq!(lock, p.read_lockfile());
+
+    p.cargo("tree -e features -Zpubgrub")
+        .masquerade_as_nightly_cargo()
+        .with_stdout(
+            "\
+foo v0.1.0 ([ROOT]/foo)
+├── bar feature \"default\"
+│   └── bar v0.1.0 ([ROOT]/foo/bar)
+│       └── serde feature \"std\"
+│           └── serde v1.0.9
+│               └── serde_derive feature \"default\"
+│                   └── serde_derive v1.0.3
+├── serde feature \"default\"
+│   └── serde v1.0.9 (*)
+├── serde feature \"derive\"
+│   ├── serde v1.0.9 (*)
+│   └── serde feature \"serde_derive\"
+│       └── serde v1.0.9 (*)
+├── rand feature \"default\"
+│   └── rand v0.6.5
+│       └── log feature \"default\"
+│           └── log v0.4.8
+└── rand feature \"default\"
+    ├── rand v0.7.3
+    │   └── log feature \"default\" (*)
+    └── rand feature \"log\"
+        └── rand v0.7.3 (*)
+",
+        )
+        .run();
+}
+
+// Backtracking past a version which can't work.
+#[cargo_test]
+fn backtrack() {
+    Package::new("baz", "1.0.0").publish();
+    Package::new("baz", "1.1.0").publish();
+    Package::new("bar", "1.0.0").dep("baz", "=1.0.0").publish();
+    Package::new("bar", "1.1.0").dep("baz", "=1.1.0").publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [dependencies]
+                bar = "1.0"
+                baz = "=1.0.0"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("generate-lockfile -Zpubgrub")
+        .masquerade_as_nightly_cargo()
+        .run();
+    let lock = p.read_lockfile();
+    assert!(lock.contains("name = \"bar\"\nversion = \"1.0.0\""));
+    assert!(lock.contains("name = \"baz\"\nversion = \"1.0.0\""));
+}
+
+// A conflict is explained by how it was derived.
+#[cargo_test]
+fn explain_conflict() {
+    Package::new("baz", "1.0.0").publish();
+    Package::new("baz", "1.1.0").publish();
+    Package::new("baz", "1.2.0").publish();
+    Package::new("bar", "1.0.0").dep("baz", "=1.1.0").publish();
+    Package::new("bar", "1.1.0").dep("baz", "=1.2.0").publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [dependencies]
+                bar = "1.0"
+                baz = "=1.0.0"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("generate-lockfile -Zpubgrub")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[UPDATING] [..]
+[ERROR] failed to select a version for every dependency:
+
+Because bar 1.1.0 depends on baz 1.2.0 and bar 1.0.0 depends on baz 1.1.0, \
+bar 1.* depends on baz >=1.1.0, <=1.2.0.
+And because foo 0.1.0 depends on bar 1.* and foo 0.1.0 depends on baz 1.0.0, \
+version solving failed.
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn explain_missing_feature() {
+    Package::new("bar", "1.0.0").feature("std", &[]).publish();
+    Package::new("bar", "1.1.0").publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [dependencies]
+                bar = { version = "1.1", features = ["std"] }
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("generate-lockfile -Zpubgrub")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[UPDATING] [..]
+[ERROR] failed to select a version for every dependency:
+
+Because bar 1.1.0 has no feature `std` and foo 0.1.0 depends on bar 1.1.0 \
+with feature `std`, version solving failed.
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn explain_links_conflict() {
+    Package::new("a-sys", "1.0.0").links("a").publish();
+    Package::new("other-sys", "1.0.0").links("a").publish();
+    Package::new("bar", "1.0.0")
+        .dep("other-sys", "1.0")
+        .publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [dependencies]
+                a-sys = "1.0"
+                bar = "1.0"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("generate-lockfile -Zpubgrub")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[UPDATING] [..]
+[ERROR] failed to select a version for every dependency:
+
+Because bar 1.0.0 depends on other-sys 1.0.0 and a-sys 1.0.0 and other-sys 1.0.0 \
+both link to the native library `a`, a-sys 1.0.0 is incompatible with bar 1.0.0.
+And because foo 0.1.0 depends on a-sys 1.0.0 and foo 0.1.0 depends on bar 1.0.0, \
+version solving failed.
+",
+        )
+        .run();
+}
@@ -1,9 +1,10 @@
 //! Tests for the `cargo publish` command.
 
+use cargo::util::IntoUrl;
 use cargo_test_support::git::{self, repo};
 use cargo_test_support::paths;
 use cargo_test_support::registry::{self, registry_path, registry_url, Package};
-use cargo_test_support::{basic_manifest, no_such_file_err_msg, project, publish};
+use cargo_test_support::{basic_manifest, project, publish};
 use std::fs;
 
 const CLEAN_FOO_JSON: &str = r#"
@@ -105,6 +106,404 @@ See [..]
     validate_upload_foo();
 }
 
+#[cargo_test]
+fn wait_for_publish() {
+    registry::init();
+    // The registry makes the version available right away.
+    Package::new("foo", "0.0.1").publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "foo"
+                version = "0.0.1"
+                authors = []
+                license = "MIT"
+                description = "foo"
+            "#,
+        )
+        .file("src/main.rs", "fn main() {}")
+        .file(
+            ".cargo/config",
+            r#"
+                [publish]
+                wait = true
+            "#,
+        )
+        .build();
+
+    p.cargo("publish --no-verify --token sekrit")
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[WARNING] manifest has no documentation, [..]
+See [..]
+[PACKAGING] foo v0.0.1 ([CWD])
+[UPLOADING] foo v0.0.1 ([CWD])
+[WAITING] on `foo v0.0.1` to be available at `[..]` index
+[PUBLISHED] foo v0.0.1 at `[..]` index
+",
+        )
+        .run();
+
+    validate_upload_foo();
+}
+
+#[cargo_test]
+fn wait_for_publish_timeout() {
+    registry::init();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "foo"
+                version = "0.0.1"
+                authors = []
+                license = "MIT"
+                description = "foo"
+            "#,
+        )
+        .file("src/main.rs", "fn main() {}")
+        .file(
+            ".cargo/config",
+            r#"
+                [publish]
+                timeout = 0
+            "#,
+        )
+        .build();
+
+    p.cargo("publish --no-verify --token sekrit --wait")
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[WARNING] manifest has no documentation, [..]
+See [..]
+[PACKAGING] foo v0.0.1 ([CWD])
+[UPLOADING] foo v0.0.1 ([CWD])
+[WAITING] on `foo v0.0.1` to be available at `[..]` index
+[WARNING] timed out waiting for `foo v0.0.1` to be available at `[..]` index
+[NOTE] The registry may have a backlog that is delaying making the crate available. \
+The crate should be available soon.
+",
+        )
+        .run();
+
+    validate_upload_foo();
+}
+
+/// A workspace where `b` depends on `a`, and `c` isn't published.
+fn workspace_project() -> cargo_test_support::Project {
+    project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [workspace]
+                members = ["a", "b", "c"]
+            "#,
+        )
+        .file(
+            "a/Cargo.toml",
+            r#"
+                [package]
+                name = "a"
+                version = "0.0.1"
+                authors = []
+                license = "MIT"
+                description = "a"
+            "#,
+        )
+        .file("a/src/lib.rs", "")
+        .file(
+            "b/Cargo.toml",
+            r#"
+                [package]
+                name = "b"
+                version = "0.0.1"
+                authors = []
+                license = "MIT"
+                description = "b"
+
+                [dependencies]
+                a = { path = "../a", version = "0.0.1" }
+            "#,
+        )
+        .file("b/src/lib.rs", "")
+        .file(
+            "c/Cargo.toml",
+            r#"
+                [package]
+                name = "c"
+                version = "0.0.1"
+                authors = []
+                publish = false
+            "#,
+        )
+        .file("c/src/lib.rs", "")
+        .build()
+}
+
+#[cargo_test]
+fn publish_workspace() {
+    registry::init();
+    // The registry makes the versions available right away.
+    Package::new("a", "0.0.1").publish();
+    Package::new("b", "0.0.1").dep("a", "0.0.1").publish();
+
+    let p = workspace_project();
+
+    p.cargo("publish --workspace --token sekrit")
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[WARNING] manifest has no documentation, [..]
+See [..]
+[PACKAGING] a v0.0.1 ([CWD]/a)
+[VERIFYING] a v0.0.1 ([CWD]/a)
+[COMPILING] a v0.0.1 ([CWD]/target/package/a-0.0.1)
+[FINISHED] [..]
+[UPLOADING] a v0.0.1 ([CWD]/a)
+[WAITING] on `a v0.0.1` to be available at `[..]` index
+[PUBLISHED] a v0.0.1 at `[..]` index
+[WARNING] manifest has no documentation, [..]
+See [..]
+[PACKAGING] b v0.0.1 ([CWD]/b)
+[VERIFYING] b v0.0.1 ([CWD]/b)
+[DOWNLOADING] crates ...
+[DOWNLOADED] a v0.0.1 (registry `[..]`)
+[COMPILING] a v0.0.1
+[COMPILING] b v0.0.1 ([CWD]/target/package/b-0.0.1)
+[FINISHED] [..]
+[UPLOADING] b v0.0.1 ([CWD]/b)
+",
+        )
+        .run();
+
+    publish::validate_upload(
+        r#"
+        {
+          "authors": [],
+          "badges": {},
+          "categories": [],
+          "deps": [
+            {
+              "default_features": true,
+              "features": [],
+              "kind": "normal",
+              "name": "a",
+              "optional": false,
+              "registry": "https://github.com/rust-lang/crates.io-index",
+              "target": null,
+              "version_req": "^0.0.1"
+            }
+          ],
+          "description": "b",
+          "documentation": null,
+          "features": {},
+          "homepage": null,
+          "keywords": [],
+          "license": "MIT",
+          "license_file": null,
+          "links": null,
+          "name": "b",
+          "readme": null,
+          "readme_file": null,
+          "repository": null,
+          "vers": "0.0.1"
+          }
+        "#,
+        "b-0.0.1.crate",
+        &["Cargo.toml", "Cargo.toml.orig", "src/lib.rs"],
+    );
+}
+
+#[cargo_test]
+fn publish_workspace_dry_run() {
+    registry::init();
+
+    let p = workspace_project();
+
+    p.cargo("publish --workspace --dry-run --token sekrit")
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[WARNING] manifest has no documentation, [..]
+See [..]
+[PACKAGING] a v0.0.1 ([CWD]/a)
+[VERIFYING] a v0.0.1 ([CWD]/a)
+[COMPILING] a v0.0.1 ([CWD]/target/package/a-0.0.1)
+[FINISHED] [..]
+[UPLOADING] a v0.0.1 ([CWD]/a)
+[WARNING] aborting upload due to dry run
+[WARNING] `b` is not verified, since it depends on members of the workspace \
+which are not published with `--dry-run`
+[WARNING] manifest has no documentation, [..]
+See [..]
+[PACKAGING] b v0.0.1 ([CWD]/b)
+[UPLOADING] b v0.0.1 ([CWD]/b)
+[WARNING] aborting upload due to dry run
+",
+        )
+        .run();
+
+    // Check that the API server did not receive any uploads.
+    assert!(!registry::api_path().join("api/v1/crates/new").exists());
+}
+
+#[cargo_test]
+fn publish_path_dep_without_version() {
+    registry::init();
+    Package::new("a", "0.0.1").publish();
+
+    let p = workspace_project();
+    p.change_file(
+        "b/Cargo.toml",
+        r#"
+            [package]
+            name = "b"
+            version = "0.0.1"
+            authors = []
+            license = "MIT"
+            description = "b"
+
+            [dependencies]
+            a = { path = "../a" }
+        "#,
+    );
+
+    p.cargo("publish --no-verify --token sekrit --path-dep-version exact")
+        .cwd("b")
+        .run();
+
+    publish::validate_upload(
+        r#"
+        {
+          "authors": [],
+          "badges": {},
+          "categories": [],
+          "deps": [
+            {
+              "default_features": true,
+              "features": [],
+              "kind": "normal",
+              "name": "a",
+              "optional": false,
+              "registry": "https://github.com/rust-lang/crates.io-index",
+              "target": null,
+              "version_req": "=0.0.1"
+            }
+          ],
+          "description": "b",
+          "documentation": null,
+          "features": {},
+          "homepage": null,
+          "keywords": [],
+          "license": "MIT",
+          "license_file": null,
+          "links": null,
+          "name": "b",
+          "readme": null,
+          "readme_file": null,
+          "repository": null,
+          "vers": "0.0.1"
+          }
+        "#,
+        "b-0.0.1.crate",
+        &["Cargo.toml", "Cargo.toml.orig", "src/lib.rs"],
+    );
+}
+
+#[cargo_test]
+fn deny_registries() {
+    registry::init();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "foo"
+                version = "0.0.1"
+                authors = []
+                license = "MIT"
+                description = "foo"
+            "#,
+        )
+        .file("src/main.rs", "fn main() {}")
+        .file(
+            ".cargo/config",
+            r#"
+                [publish]
+                deny-registries = ["crates-io"]
+            "#,
+        )
+        .build();
+
+    p.cargo("publish --no-verify --token sekrit")
+        .with_status(101)
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[ERROR] `foo` cannot be published.
+The registry `crates-io` is denied by `publish.deny-registries` in [CWD]/.cargo/config.
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn publish_zstd_when_accepted() {
+    registry::init();
+
+    // The registry accepts zstd-compressed crate files.
+    let repo = git2::Repository::open(registry_path()).unwrap();
+    fs::write(
+        registry_path().join("config.json"),
+        format!(
+            r#"{{"dl": "{}", "api": "{}", "compression": ["zstd"]}}"#,
+            registry::dl_url(),
+            registry::api_url()
+        ),
+    )
+    .unwrap();
+    git::add(&repo);
+    git::commit(&repo);
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "foo"
+                version = "0.0.1"
+                authors = []
+                license = "MIT"
+                description = "foo"
+            "#,
+        )
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    p.cargo("publish --token sekrit")
+        .with_stderr_contains("[VERIFYING] foo v0.0.1 ([CWD])")
+        .with_stderr_contains("[UPLOADING] foo v0.0.1 ([CWD])")
+        .run();
+
+    let krate = fs::read(p.root().join("target/package/foo-0.0.1.crate")).unwrap();
+    assert!(krate.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]));
+    validate_upload_foo();
+
+    // `cargo package` doesn't know where the package goes, so it sticks to
+    // gzip.
+    p.cargo("package --no-verify").run();
+    let krate = fs::read(p.root().join("target/package/foo-0.0.1.crate")).unwrap();
+    assert!(krate.starts_with(&[0x1f, 0x8b]));
+}
+
 #[cargo_test]
 fn old_token_location() {
     // Check that the `token` key works at the root instead of under a
@@ -282,10 +681,10 @@ fn git_deps() {
         .with_stderr(
             "\
 [UPDATING] [..] index
-[ERROR] all dependencies must have a version specified when publishing.
-dependency `foo` does not specify a version
-Note: The published dependency will use the version from crates.io,
-the `git` specification will be removed from the dependency declaration.
+[ERROR] `foo v0.0.1` cannot be published to [ROOT]/registry:
+  - dependency `foo` does not specify a version
+    Note: The published dependency will use the version from crates.io,
+    the `git` specification will be removed from the dependency declaration.
 ",
         )
         .run();
@@ -320,10 +719,142 @@ fn path_dependency_no_version() {
         .with_stderr(
             "\
 [UPDATING] [..] index
-[ERROR] all dependencies must have a version specified when publishing.
-dependency `bar` does not specify a version
-Note: The published dependency will use the version from crates.io,
-the `path` specification will be removed from the dependency declaration.
+[ERROR] `foo v0.0.1` cannot be published to [ROOT]/registry:
+  - dependency `bar` does not specify a version
+    Note: The published dependency will use the version from crates.io,
+    the `path` specification will be removed from the dependency declaration.
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn preflight_reports_all_problems() {
+    registry::init();
+
+    // A fake index which points to the real crates.io, like in
+    // `alt_registry::cannot_publish_to_crates_io_with_registry_dependency`.
+    let fakeio_path = paths::root().join("fake.io");
+    git::repo(&fakeio_path)
+        .file(
+            "config.json",
+            r#"
+                {"dl": "https://crates.io/api/v1/crates", "api": "https://crates.io"}
+            "#,
+        )
+        .build();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "foo"
+                version = "0.0.1"
+                authors = []
+                readme = "README"
+
+                [dependencies]
+                bar = { path = "bar", version = "*" }
+                baz = { path = "baz" }
+            "#,
+        )
+        .file("src/main.rs", "fn main() {}")
+        .file("bar/Cargo.toml", &basic_manifest("bar", "0.0.1"))
+        .file("bar/src/lib.rs", "")
+        .file("baz/Cargo.toml", &basic_manifest("baz", "0.0.1"))
+        .file("baz/src/lib.rs", "")
+        .build();
+
+    p.cargo("publish --token sekrit --index")
+        .arg(fakeio_path.into_url().unwrap().to_string())
+        .with_status(101)
+        .with_stderr(
+            "\
+[UPDATING] [..] index
+[ERROR] `foo v0.0.1` cannot be published to [ROOT]/fake.io:
+  - the manifest has no `description`
+  - the manifest has neither `license` nor `license-file`
+  - the `readme` file `README` does not exist
+  - dependency `bar` has a wildcard (`*`) version requirement, which crates.io does not accept
+  - dependency `baz` does not specify a version
+    Note: The published dependency will use the version from crates.io,
+    the `path` specification will be removed from the dependency declaration.
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn max_upload_size() {
+    registry::init();
+
+    let repo = git2::Repository::open(registry_path()).unwrap();
+    fs::write(
+        registry_path().join("config.json"),
+        format!(
+            r#"{{"dl": "{}", "api": "{}", "max-upload-size": 100}}"#,
+            registry::dl_url(),
+            registry::api_url()
+        ),
+    )
+    .unwrap();
+    git::add(&repo);
+    git::commit(&repo);
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "foo"
+                version = "0.0.1"
+                authors = []
+                license = "MIT"
+                description = "foo"
+            "#,
+        )
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    p.cargo("publish --no-verify --token sekrit")
+        .with_status(101)
+        .with_stderr(
+            "\
+[UPDATING] [..] index
+[WARNING] manifest has no documentation, [..]
+See [..]
+[PACKAGING] foo v0.0.1 ([CWD])
+[ERROR] `foo v0.0.1` cannot be published to [ROOT]/registry:
+  - the `.crate` file is [..] bytes, more than the 100 bytes accepted by [ROOT]/registry
+    Use `cargo package --list` to find the files which can be left out with `package.exclude`.
+",
+        )
+        .run();
+    assert!(!p.root().join("target/package/foo-0.0.1.crate").exists());
+
+    // The size is reported along with the other problems.
+    p.change_file(
+        "Cargo.toml",
+        r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+            license = "MIT"
+            description = "foo"
+            readme = "MISSING.md"
+        "#,
+    );
+    p.cargo("publish --no-verify --token sekrit")
+        .with_status(101)
+        .with_stderr(
+            "\
+[UPDATING] [..] index
+[ERROR] `foo v0.0.1` cannot be published to [ROOT]/registry:
+  - the `readme` file `MISSING.md` does not exist
+  - the `.crate` file is [..] bytes, more than the 100 bytes accepted by [ROOT]/registry
+    Use `cargo package --list` to find the files which can be left out with `package.exclude`.
 ",
         )
         .run();
@@ -1440,20 +1971,181 @@ fn publish_with_missing_readme() {
 
     p.cargo("publish --no-verify --token sekrit")
         .with_status(101)
-        .with_stderr(&format!(
+        .with_stderr(
             "\
 [UPDATING] [..]
-[PACKAGING] foo v0.1.0 [..]
-[UPLOADING] foo v0.1.0 [..]
-[ERROR] failed to read `readme` file for package `foo v0.1.0 ([ROOT]/foo)`
+[ERROR] `foo v0.1.0` cannot be published to [ROOT]/registry:
+  - the `readme` file `foo.md` does not exist
+",
+        )
+        .run();
+}
 
-Caused by:
-  failed to read `[ROOT]/foo/foo.md`
+#[cargo_test]
+fn dev_dependency_cycle() {
+    registry::init();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+            license = "MIT"
+            description = "foo"
+
+            [dev-dependencies]
+            helper = { path = "helper", version = "0.1.0" }
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file(
+            "helper/Cargo.toml",
+            r#"
+            [package]
+            name = "helper"
+            version = "0.1.0"
+            authors = []
+            license = "MIT"
+            description = "helper"
+
+            [dependencies]
+            foo = { path = "..", version = "0.1.0" }
+            "#,
+        )
+        .file("helper/src/lib.rs", "")
+        .build();
+
+    p.cargo("publish --token sekrit")
+        .with_status(101)
+        .with_stderr(
+            "\
+[UPDATING] [..]
+[WARNING] manifest has no documentation, [..]
+See [..]
+[PACKAGING] foo v0.1.0 ([CWD])
+[VERIFYING] foo v0.1.0 ([CWD])
+[NOTE] dev-dependency `helper` of `foo` depends on `foo` itself:
+  foo v0.1.0 -> helper v0.1.0 -> foo v0.1.0
+`helper` can't be published before `foo`, so it can't be found in the registry when verifying `foo`.
+Remove the `version` of the `helper` dev-dependency to leave it out of the published package.
+[ERROR] failed to verify package tarball
 
 Caused by:
-  {}
+  no matching package named `helper` found
+  location searched: registry [..]
+  required by package `foo v0.1.0 ([CWD]/target/package/foo-0.1.0)`
 ",
-            no_such_file_err_msg()
-        ))
+        )
         .run();
+
+    // With a lock file, the cycle is found in the resolve of the workspace.
+    p.cargo("generate-lockfile").run();
+    p.cargo("publish --token sekrit")
+        .with_status(101)
+        .with_stderr_contains(
+            "\
+[NOTE] dev-dependency `helper` of `foo` depends on `foo` itself:
+  foo v0.1.0 -> helper v0.1.0 -> foo v0.1.0
+",
+        )
+        .with_stderr_contains("[ERROR] failed to verify package tarball")
+        .run();
+}
+
+#[cargo_test]
+fn publish_name() {
+    registry::init();
+
+    let p = workspace_project();
+    p.change_file(
+        "a/Cargo.toml",
+        r#"
+            cargo-features = ["publish-name"]
+
+            [package]
+            name = "a"
+            version = "0.0.1"
+            authors = []
+            license = "MIT"
+            description = "a"
+            publish-name = "a-published"
+        "#,
+    );
+
+    p.cargo("publish --no-verify --token sekrit")
+        .cwd("a")
+        .masquerade_as_nightly_cargo()
+        .run();
+
+    publish::validate_upload(
+        r#"
+        {
+          "authors": [],
+          "badges": {},
+          "categories": [],
+          "deps": [],
+          "description": "a",
+          "documentation": null,
+          "features": {},
+          "homepage": null,
+          "keywords": [],
+          "license": "MIT",
+          "license_file": null,
+          "links": null,
+          "name": "a-published",
+          "readme": null,
+          "readme_file": null,
+          "repository": null,
+          "vers": "0.0.1"
+          }
+        "#,
+        "a-published-0.0.1.crate",
+        &["Cargo.toml", "Cargo.toml.orig", "src/lib.rs"],
+    );
+
+    p.cargo("publish --no-verify --token sekrit")
+        .cwd("b")
+        .masquerade_as_nightly_cargo()
+        .run();
+
+    publish::validate_upload(
+        r#"
+        {
+          "authors": [],
+          "badges": {},
+          "categories": [],
+          "deps": [
+            {
+              "default_features": true,
+              "explicit_name_in_toml": "a",
+              "features": [],
+              "kind": "normal",
+              "name": "a-published",
+              "optional": false,
+              "registry": "https://github.com/rust-lang/crates.io-index",
+              "target": null,
+              "version_req": "^0.0.1"
+            }
+          ],
+          "description": "b",
+          "documentation": null,
+          "features": {},
+          "homepage": null,
+          "keywords": [],
+          "license": "MIT",
+          "license_file": null,
+          "links": null,
+          "name": "b",
+          "readme": null,
+          "readme_file": null,
+          "repository": null,
+          "vers": "0.0.1"
+          }
+        "#,
+        "b-0.0.1.crate",
+        &["Cargo.toml", "Cargo.toml.orig", "src/lib.rs"],
+    );
 }
@@ -3,11 +3,14 @@
 use cargo::{core::SourceId, util::paths::remove_dir_all};
 use cargo_test_support::paths::{self, CargoPathExt};
 use cargo_test_support::registry::{self, registry_path, Dependency, Package};
-use cargo_test_support::{basic_manifest, project};
+use cargo_test_support::{basic_manifest, path2url, project};
 use cargo_test_support::{cargo_process, registry::registry_url};
 use cargo_test_support::{git, install::cargo_home, t};
 use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
 use std::path::Path;
+use std::thread;
 
 #[cargo_test]
 fn simple() {
@@ -333,6 +336,81 @@ required by package `foo v0.0.1 ([..])`
         .run();
 }
 
+#[cargo_test]
+fn zstd_crate_file() {
+    Package::new("bar", "0.1.0")
+        .file("src/lib.rs", "pub fn bar() {}")
+        .zstd(true)
+        .publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "foo"
+                version = "0.0.1"
+                authors = []
+
+                [dependencies]
+                bar = "0.1"
+            "#,
+        )
+        .file("src/main.rs", "fn main() { bar::bar(); }")
+        .build();
+
+    p.cargo("build")
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[DOWNLOADING] crates ...
+[DOWNLOADED] bar v0.1.0 (registry `[ROOT][..]`)
+[COMPILING] bar v0.1.0
+[COMPILING] foo v0.0.1 ([CWD])
+[FINISHED] dev [unoptimized + debuginfo] target(s) in [..]s
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn update_registry_skips_fetch_when_up_to_date() {
+    Package::new("bar", "0.1.0").publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "foo"
+                version = "0.0.1"
+                authors = []
+
+                [dependencies]
+                bar = "0.1"
+            "#,
+        )
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    p.cargo("generate-lockfile").run();
+
+    // Nothing changed in the index, so it isn't fetched again.
+    p.cargo("update")
+        .env("CARGO_LOG", "cargo::sources::git::utils=debug")
+        .with_stderr_contains("[..]remote references of [..] are up-to-date")
+        .with_stderr_does_not_contain("[..]initiating fetch[..]")
+        .run();
+
+    Package::new("bar", "0.1.1").publish();
+
+    p.cargo("update")
+        .env("CARGO_LOG", "cargo::sources::git::utils=debug")
+        .with_stderr_contains("[..]initiating fetch[..]")
+        .with_stderr_contains("[UPDATING] bar v0.1.0 -> v0.1.1")
+        .run();
+}
+
 #[cargo_test]
 fn package_with_path_deps() {
     Package::new("init", "0.0.1").publish();
@@ -547,7 +625,7 @@ error: failed to select a version for the requirement `baz = \"=0.0.2\"`
 candidate versions found which didn't match: 0.0.1
 location searched: `[..]` index (which is replacing registry `[..]`)
 required by package `bar v0.0.1`
-    ... which is depended on by `foo [..]`
+    ... which satisfies dependency `bar = \"*\"` of package `foo [..]`
 ",
         )
         .run();
@@ -2063,25 +2141,114 @@ fn readonly_registry_still_works() {
     p.cargo("build").run();
     // make sure we un-readonly the files afterwards so "cargo clean" can remove them (#6934)
     chmod_readonly(&paths::home(), false);
+}
 
-    fn chmod_readonly(path: &Path, readonly: bool) {
-        for entry in t!(path.read_dir()) {
-            let entry = t!(entry);
-            let path = entry.path();
-            if t!(entry.file_type()).is_dir() {
-                chmod_readonly(&path, readonly);
-            } else {
-                set_readonly(&path, readonly);
-            }
+fn chmod_readonly(path: &Path, readonly: bool) {
+    for entry in t!(path.read_dir()) {
+        let entry = t!(entry);
+        let path = entry.path();
+        if t!(entry.file_type()).is_dir() {
+            chmod_readonly(&path, readonly);
+        } else {
+            set_readonly(&path, readonly);
         }
-        set_readonly(path, readonly);
     }
+    set_readonly(path, readonly);
+}
 
-    fn set_readonly(path: &Path, readonly: bool) {
-        let mut perms = t!(path.metadata()).permissions();
-        perms.set_readonly(readonly);
-        t!(fs::set_permissions(path, perms));
-    }
+fn set_readonly(path: &Path, readonly: bool) {
+    let mut perms = t!(path.metadata()).permissions();
+    perms.set_readonly(readonly);
+    t!(fs::set_permissions(path, perms));
+}
+
+#[cargo_test]
+fn readonly_shared_registry() {
+    Package::new("foo", "0.1.0").publish();
+    Package::new("bar", "0.1.0").publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "a"
+                version = "0.5.0"
+                authors = []
+
+                [dependencies]
+                foo = "0.1"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+    p.cargo("fetch").run();
+
+    // New downloads go to the overlay when the registry is read-only, with
+    // `-Z registry-overlay`.
+    let registry = paths::home().join(".cargo/registry");
+    let overlay = paths::home().join(".cargo/registry-overlay");
+    chmod_readonly(&registry, true);
+    p.change_file(
+        "Cargo.toml",
+        r#"
+            [project]
+            name = "a"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies]
+            foo = "0.1"
+            bar = "0.1"
+        "#,
+    );
+    p.cargo("build -Zregistry-overlay")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_unordered(
+            "\
+[UPDATING] `[..]` index
+[DOWNLOADING] crates ...
+[DOWNLOADED] bar v0.1.0 (registry [..])
+[COMPILING] foo v0.1.0
+[COMPILING] bar v0.1.0
+[COMPILING] a v0.5.0 ([CWD])
+[FINISHED] [..]
+",
+        )
+        .run();
+    let crate_file = |root: &Path, name: &str| {
+        let cache = t!(root.join("cache").read_dir()).next().unwrap();
+        t!(cache).path().join(name)
+    };
+    assert!(crate_file(&overlay, "bar-0.1.0.crate").is_file());
+    assert!(!crate_file(&registry, "bar-0.1.0.crate").exists());
+    assert!(crate_file(&registry, "foo-0.1.0.crate").is_file());
+    assert!(!crate_file(&overlay, "foo-0.1.0.crate").exists());
+
+    // Files from the shared registry are only used if they're intact.
+    let foo = crate_file(&registry, "foo-0.1.0.crate");
+    set_readonly(&foo, false);
+    fs::write(&foo, "corrupt").unwrap();
+    set_readonly(&foo, true);
+    p.cargo("build -Zregistry-overlay")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[WARNING] the checksum of `[..]foo-0.1.0.crate` in the shared registry does not match, \
+downloading `foo v0.1.0` again
+[DOWNLOADING] crates ...
+[DOWNLOADED] foo v0.1.0 (registry [..])
+[FINISHED] [..]
+",
+        )
+        .run();
+    assert!(crate_file(&overlay, "foo-0.1.0.crate").is_file());
+    p.cargo("build -Zregistry-overlay")
+        .masquerade_as_nightly_cargo()
+        .with_stderr("[FINISHED] [..]")
+        .run();
+
+    chmod_readonly(&registry, false);
 }
 
 #[cargo_test]
@@ -2170,3 +2337,485 @@ fn package_lock_inside_package_is_overwritten() {
 
     assert_eq!(ok.metadata().unwrap().len(), 2);
 }
+
+#[cargo_test]
+fn ignores_unknown_index_version() {
+    // If the version field is not understood, it is ignored.
+    Package::new("bar", "1.0.0").publish();
+    Package::new("bar", "1.0.1").schema_version(9999).publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [dependencies]
+                bar = "1.0"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("tree")
+        .with_stdout(
+            "foo v0.1.0 [..]\n\
+             └── bar v1.0.0\n\
+            ",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn allow_prerelease_requires_feature() {
+    Package::new("bar", "1.0.0").publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+
+                [dependencies]
+                bar = { version = "1.0", allow-prerelease = true }
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("build")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+error: failed to parse manifest at `[..]`
+
+Caused by:
+  feature `allow-prerelease` is required
+
+  consider adding `cargo-features = [\"allow-prerelease\"]` to the manifest
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn allow_prerelease() {
+    Package::new("bar", "1.0.0").publish();
+    Package::new("bar", "1.1.0-rc.1").publish();
+    Package::new("bar", "2.0.0-rc.1").publish();
+    Package::new("bar", "3.0.0-rc.1").publish();
+    Package::new("baz", "1.0.0").publish();
+    Package::new("baz", "1.1.0-rc.1").publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                cargo-features = ["allow-prerelease"]
+
+                [package]
+                name = "foo"
+                version = "0.0.1"
+
+                [dependencies]
+                bar = { version = "1.0", allow-prerelease = true }
+                baz = "1.0"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    // Only `bar` opted in, and it picks up the pre-release of the next
+    // incompatible version, but not the one after it.
+    p.cargo("tree")
+        .masquerade_as_nightly_cargo()
+        .with_stdout(
+            "\
+foo v0.0.1 ([ROOT]/foo)
+├── bar v2.0.0-rc.1
+└── baz v1.0.0
+",
+        )
+        .run();
+
+    // Pre-releases within the requirement are accepted too.
+    p.cargo("update -p bar --precise 1.1.0-rc.1")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[UPDATING] bar v2.0.0-rc.1 -> v1.1.0-rc.1
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn shallow_index() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "foo"
+                version = "0.0.1"
+                authors = []
+
+                [dependencies]
+                bar = "0.1"
+            "#,
+        )
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    Package::new("bar", "0.1.0").publish();
+
+    p.cargo("build -Z git-shallow")
+        .masquerade_as_nightly_cargo()
+        .run();
+
+    let index_paths: Vec<_> = cargo_home()
+        .join("registry/index")
+        .read_dir()
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(index_paths.len(), 1);
+    assert!(index_paths[0].to_str().unwrap().ends_with("-shallow"));
+    assert!(index_paths[0].join(".git/shallow").exists());
+
+    // Updates of a shallow index pick up new versions.
+    Package::new("bar", "0.1.1").publish();
+    p.cargo("update -Z git-shallow")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[UPDATING] bar v0.1.0 -> v0.1.1
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn resume_partial_download() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "foo"
+                version = "0.0.1"
+                authors = []
+
+                [dependencies]
+                bar = "0.1"
+            "#,
+        )
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    Package::new("bar", "0.1.0")
+        .file("src/lib.rs", &"// padding\n".repeat(1000))
+        .publish();
+
+    p.cargo("fetch").run();
+    let cache_dirs: Vec<_> = cargo_home()
+        .join("registry/cache")
+        .read_dir()
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(cache_dirs.len(), 1);
+    let crate_path = cache_dirs[0].join("bar-0.1.0.crate");
+    let partial_path = cache_dirs[0].join("bar-0.1.0.crate.part");
+    let contents = fs::read(&crate_path).unwrap();
+
+    // The download continues after the data already on disk.
+    fs::remove_file(&crate_path).unwrap();
+    fs::write(&partial_path, &contents[..contents.len() / 2]).unwrap();
+    p.cargo("fetch")
+        .with_stderr(
+            "\
+[DOWNLOADING] crates ...
+[DOWNLOADED] bar v0.1.0 (registry `[ROOT][..]`)
+",
+        )
+        .run();
+    assert_eq!(fs::read(&crate_path).unwrap(), contents);
+    assert!(!partial_path.exists());
+
+    // Bad data on disk fails verification, and is thrown away.
+    fs::remove_file(&crate_path).unwrap();
+    fs::write(&partial_path, vec![0; contents.len() / 2]).unwrap();
+    p.cargo("fetch")
+        .with_status(101)
+        .with_stderr_contains("[..]failed to verify the checksum of `bar v0.1.0[..]")
+        .run();
+    assert!(!partial_path.exists());
+    p.cargo("fetch").run();
+    assert_eq!(fs::read(&crate_path).unwrap(), contents);
+}
+
+fn mirrors_config(mirror: &str) {
+    fs::write(
+        paths::home().join(".cargo/config"),
+        format!(
+            r#"
+                [source.crates-io]
+                registry = 'https://wut'
+                replace-with = ['mirror', 'dummy-registry']
+
+                [source.mirror]
+                registry = '{mirror}'
+
+                [source.dummy-registry]
+                registry = '{reg}'
+            "#,
+            mirror = mirror,
+            reg = registry_url(),
+        ),
+    )
+    .unwrap();
+}
+
+#[cargo_test]
+fn mirror_unreachable() {
+    registry::init();
+    mirrors_config(path2url(paths::root().join("broken")).as_str());
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "foo"
+                version = "0.0.1"
+                authors = []
+
+                [dependencies]
+                bar = "0.1"
+            "#,
+        )
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    Package::new("bar", "0.1.0").publish();
+
+    p.cargo("fetch")
+        .with_stderr(
+            "\
+[UPDATING] `[..]broken` index
+[WARNING] spurious network error (2 tries remaining): [..]
+[WARNING] spurious network error (1 tries remaining): [..]
+[WARNING] failed to update the index from `mirror`, trying `dummy-registry` instead
+
+Caused by:
+  [..]
+[UPDATING] `[..]registry` index
+[DOWNLOADING] crates ...
+[DOWNLOADED] bar v0.1.0 (registry `[ROOT][..]registry`)
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn mirror_checksum_mismatch() {
+    registry::init();
+    mirrors_config(registry::alt_registry_url().as_str());
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "foo"
+                version = "0.0.1"
+                authors = []
+
+                [dependencies]
+                bar = "0.1"
+            "#,
+        )
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    let cksum = Package::new("bar", "0.1.0").publish();
+    let mirrored = Package::new("bar", "0.1.0").alternative(true).publish();
+    assert_eq!(cksum, mirrored);
+    fs::write(
+        Package::new("bar", "0.1.0").alternative(true).archive_dst(),
+        "not a crate",
+    )
+    .unwrap();
+
+    p.cargo("fetch")
+        .with_stderr(
+            "\
+[UPDATING] `[..]alternative-registry` index
+[DOWNLOADING] crates ...
+[DOWNLOADED] bar v0.1.0 (registry `[ROOT][..]alternative-registry`)
+[WARNING] failed to download `bar v0.1.0` from `mirror`, trying `dummy-registry` instead
+
+Caused by:
+  [..]failed to verify the checksum of `bar v0.1.0[..]
+[UPDATING] `[..]registry` index
+[DOWNLOADED] bar v0.1.0 (registry `[ROOT][..]registry`)
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn mirror_verbose_names_source() {
+    registry::init();
+    mirrors_config(registry::alt_registry_url().as_str());
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "foo"
+                version = "0.0.1"
+                authors = []
+
+                [dependencies]
+                bar = "0.1"
+                baz = "0.1"
+            "#,
+        )
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    Package::new("bar", "0.1.0").publish();
+    Package::new("bar", "0.1.0").alternative(true).publish();
+    Package::new("baz", "0.1.0").publish();
+    Package::new("baz", "0.1.0").alternative(true).publish();
+    fs::write(
+        Package::new("baz", "0.1.0").alternative(true).archive_dst(),
+        "not a crate",
+    )
+    .unwrap();
+
+    p.cargo("fetch -v")
+        .with_stderr_contains("[..]Mirror `mirror` served bar v0.1.0")
+        .with_stderr_contains("[..]Mirror `dummy-registry` served baz v0.1.0")
+        .with_stderr_does_not_contain("[..]Mirror `mirror` served baz v0.1.0")
+        .run();
+
+    paths::home().join(".cargo/registry/cache").rm_rf();
+    p.cargo("fetch")
+        .with_stderr_contains("[DOWNLOADED] bar v0.1.0 [..]")
+        .with_stderr_does_not_contain("[..]Mirror[..]")
+        .run();
+}
+
+/// Serves the `.crate` file of `bar 0.1.0` over HTTP for the registry,
+/// answering the requests with the given statuses, whether they ask for a
+/// range or not. Returns whether each request asked for a range.
+fn range_ignoring_server(statuses: &'static [u32]) -> thread::JoinHandle<Vec<bool>> {
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    let repo = git2::Repository::open(registry_path()).unwrap();
+    fs::write(
+        registry_path().join("config.json"),
+        format!(
+            r#"{{"dl": "http://{}", "api": "{}"}}"#,
+            addr,
+            registry::api_url()
+        ),
+    )
+    .unwrap();
+    git::add(&repo);
+    git::commit(&repo);
+
+    let crate_path = registry::dl_path().join("bar/0.1.0/download");
+    thread::spawn(move || {
+        let mut ranges = Vec::new();
+        for &status in statuses {
+            let mut conn = BufReader::new(server.accept().unwrap().0);
+            let lines: Vec<_> = (&mut conn)
+                .lines()
+                .map(|s| s.unwrap())
+                .take_while(|s| s.len() > 2)
+                .map(|s| s.trim().to_string())
+                .collect();
+            assert_eq!(lines[0], "GET /bar/0.1.0/download HTTP/1.1");
+            ranges.push(lines.iter().any(|line| line.starts_with("Range: ")));
+            let body = if status == 200 {
+                fs::read(&crate_path).unwrap()
+            } else {
+                Vec::new()
+            };
+            let conn = conn.get_mut();
+            write!(
+                conn,
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                body.len()
+            )
+            .unwrap();
+            conn.write_all(&body).unwrap();
+        }
+        ranges
+    })
+}
+
+#[cargo_test]
+fn resume_partial_download_without_range_support() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "foo"
+                version = "0.0.1"
+                authors = []
+
+                [dependencies]
+                bar = "0.1"
+            "#,
+        )
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    Package::new("bar", "0.1.0")
+        .file("src/lib.rs", &"// padding\n".repeat(1000))
+        .publish();
+    let contents = fs::read(registry::dl_path().join("bar/0.1.0/download")).unwrap();
+
+    // The server answers the range request with the whole file, then with 416,
+    // and both times the download starts over.
+    let server = range_ignoring_server(&[200, 200, 200, 416, 200]);
+    p.cargo("fetch").run();
+    let cache_dirs: Vec<_> = cargo_home()
+        .join("registry/cache")
+        .read_dir()
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(cache_dirs.len(), 1);
+    let crate_path = cache_dirs[0].join("bar-0.1.0.crate");
+    let partial_path = cache_dirs[0].join("bar-0.1.0.crate.part");
+    assert_eq!(fs::read(&crate_path).unwrap(), contents);
+
+    for _ in 0..2 {
+        fs::remove_file(&crate_path).unwrap();
+        fs::write(&partial_path, &contents[..contents.len() / 2]).unwrap();
+        p.cargo("fetch")
+            .with_stderr(
+                "\
+[DOWNLOADING] crates ...
+[DOWNLOADED] bar v0.1.0 (registry `[ROOT][..]`)
+",
+            )
+            .run();
+        assert_eq!(fs::read(&crate_path).unwrap(), contents);
+        assert!(!partial_path.exists());
+    }
+    assert_eq!(server.join().unwrap(), [false, true, false, true, false]);
+}
@@ -187,7 +187,8 @@ fn rename_twice() {
 [UPDATING] `[..]` index
 [DOWNLOADING] crates ...
 [DOWNLOADED] foo v0.1.0 (registry [..])
-error: the crate `test v0.1.0 ([CWD])` depends on crate `foo v0.1.0` multiple times with different names
+error: the crate `test v0.1.0 ([CWD])` depends on crate `foo v0.1.0` multiple times with different names: \
+`bar` (renamed from `foo`) in `dependencies`, `foo` in `build-dependencies`
 ",
         )
         .run();
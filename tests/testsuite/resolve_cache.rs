@@ -0,0 +1,146 @@
+//! Tests for the `-Z resolve-cache` feature.
+
+use cargo_test_support::registry::{Dependency, Package};
+use cargo_test_support::{basic_manifest, project};
+
+#[cargo_test]
+fn reuses_registry_queries() {
+    Package::new("baz", "1.0.0").feature("std", &[]).publish();
+    Package::new("bar", "1.0.0")
+        .add_dep(Dependency::new("baz", "1.0").optional(true))
+        .feature("std", &["baz/std"])
+        .publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [dependencies]
+                bar = { version = "1.0", features = ["std"] }
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("generate-lockfile").run();
+    p.cargo("tree -e features -Zresolve-cache")
+        .masquerade_as_nightly_cargo()
+        .run();
+    assert!(p.build_dir().join(".resolve-cache.json").is_file());
+
+    // The second run answers all registry queries from the cache.
+    p.cargo("tree -e features -Zresolve-cache")
+        .masquerade_as_nightly_cargo()
+        .env("CARGO_LOG", "cargo::core::registry::query_cache=debug")
+        .with_stdout(
+            "\
+foo v0.1.0 ([ROOT]/foo)
+├── bar feature \"default\"
+│   └── bar v1.0.0
+│       └── baz feature \"default\"
+│           └── baz v1.0.0
+└── bar feature \"std\"
+    ├── bar v1.0.0 (*)
+    ├── bar feature \"baz\"
+    │   └── bar v1.0.0 (*)
+    └── baz feature \"std\"
+        └── baz v1.0.0
+",
+        )
+        .with_stderr_contains("[..]resolve cache hit for `bar =1.0.0`[..]")
+        .with_stderr_contains("[..]resolve cache hit for `baz =1.0.0`[..]")
+        .run();
+}
+
+#[cargo_test]
+fn follows_lock_file_updates() {
+    Package::new("bar", "1.0.0").publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [dependencies]
+                bar = "1.0"
+                baz = { path = "baz" }
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file("baz/Cargo.toml", &basic_manifest("baz", "0.1.0"))
+        .file("baz/src/lib.rs", "")
+        .build();
+
+    p.cargo("tree -Zresolve-cache")
+        .masquerade_as_nightly_cargo()
+        .with_stdout(
+            "\
+foo v0.1.0 ([ROOT]/foo)
+├── bar v1.0.0
+└── baz v0.1.0 ([ROOT]/foo/baz)
+",
+        )
+        .run();
+
+    Package::new("bar", "1.0.1").dep("qux", "1.0").publish();
+    Package::new("qux", "1.0.0").publish();
+    p.cargo("update -p bar").run();
+
+    p.cargo("tree -Zresolve-cache")
+        .masquerade_as_nightly_cargo()
+        .env("CARGO_LOG", "cargo::core::registry::query_cache=debug")
+        .with_stdout(
+            "\
+foo v0.1.0 ([ROOT]/foo)
+├── bar v1.0.1
+│   └── qux v1.0.0
+└── baz v0.1.0 ([ROOT]/foo/baz)
+",
+        )
+        .with_stderr_contains("[..]different lock file, creating new resolve cache")
+        .run();
+}
+
+#[cargo_test]
+fn write_errors_are_warnings() {
+    Package::new("bar", "1.0.0").publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [dependencies]
+                bar = "1.0"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("generate-lockfile").run();
+    // The cache can't replace a directory.
+    std::fs::create_dir_all(p.build_dir().join(".resolve-cache.json")).unwrap();
+    p.cargo("tree -Zresolve-cache")
+        .masquerade_as_nightly_cargo()
+        .with_stdout_contains("foo v0.1.0 [..]")
+        .with_stderr(
+            "\
+[WARNING] failed to write the resolve cache `[..]/target/.resolve-cache.json`
+
+[..]
+[DOWNLOADING] crates ...
+[DOWNLOADED] bar v1.0.0 (registry `[..]`)
+",
+        )
+        .run();
+}
@@ -0,0 +1,175 @@
+//! Tests for the `rust-version` field.
+
+use cargo_test_support::project;
+use cargo_test_support::registry::Package;
+
+#[cargo_test]
+fn rust_version_ignored_without_feature() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+                rust-version = "1.9876"
+
+                [dependencies]
+                bar = { path = "bar" }
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file(
+            "bar/Cargo.toml",
+            r#"
+                [package]
+                name = "bar"
+                version = "0.1.0"
+                rust-version = "not a version"
+            "#,
+        )
+        .file("bar/src/lib.rs", "")
+        .build();
+
+    p.cargo("build")
+        .with_stderr(
+            "\
+[WARNING] `rust-version` is unstable and is ignored, consider adding \
+`cargo-features = [\"rust-version\"]` to the manifest
+[COMPILING] bar v0.1.0 ([CWD]/bar)
+[COMPILING] foo v0.1.0 ([CWD])
+[FINISHED] [..]
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn rust_version_invalid() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                cargo-features = ["rust-version"]
+
+                [package]
+                name = "foo"
+                version = "0.1.0"
+                rust-version = "1.45.0-beta"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("build")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] failed to parse manifest at `[CWD]/Cargo.toml`
+
+Caused by:
+  failed to parse the `rust-version` key: `1.45.0-beta` is not a valid rustc \
+version, expected `MAJOR.MINOR` or `MAJOR.MINOR.PATCH`
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn rust_version_satisfied() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                cargo-features = ["rust-version"]
+
+                [package]
+                name = "foo"
+                version = "0.1.0"
+                rust-version = "1.0"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("build")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[COMPILING] foo v0.1.0 ([CWD])
+[FINISHED] [..]
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn rust_version_too_high() {
+    Package::new("bar", "0.1.0")
+        .file(
+            "Cargo.toml",
+            r#"
+                cargo-features = ["rust-version"]
+
+                [package]
+                name = "bar"
+                version = "0.1.0"
+                rust-version = "1.9876.0"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                cargo-features = ["rust-version"]
+
+                [package]
+                name = "foo"
+                version = "0.1.0"
+                rust-version = "1.9876"
+
+                [dependencies]
+                bar = "0.1"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("build")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[DOWNLOADING] crates ...
+[DOWNLOADED] bar v0.1.0 ([..])
+[ERROR] package `bar v0.1.0` cannot be built because it requires rustc 1.9876.0 \
+or newer, while the currently active rustc version is [..]
+package `foo v0.1.0 ([CWD])` cannot be built because it requires rustc 1.9876 \
+or newer, while the currently active rustc version is [..]
+Update the toolchain, or set `build.incompatible-rust-version = \"warn\"` to try \
+building anyway.
+",
+        )
+        .run();
+
+    p.cargo("build")
+        .masquerade_as_nightly_cargo()
+        .env("CARGO_BUILD_INCOMPATIBLE_RUST_VERSION", "warn")
+        .with_stderr(
+            "\
+[WARNING] package `bar v0.1.0` cannot be built because it requires rustc 1.9876.0 \
+or newer, while the currently active rustc version is [..]
+[WARNING] package `foo v0.1.0 ([CWD])` cannot be built because it requires rustc \
+1.9876 or newer, while the currently active rustc version is [..]
+[COMPILING] bar v0.1.0
+[COMPILING] foo v0.1.0 ([CWD])
+[FINISHED] [..]
+",
+        )
+        .run();
+}
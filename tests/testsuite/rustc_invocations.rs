@@ -0,0 +1,182 @@
+//! Tests for `cargo build --rustc-invocations`.
+
+use std::process::Command;
+
+use cargo_test_support::paths::CargoPathExt;
+use cargo_test_support::{basic_manifest, project};
+use serde_json::Value;
+
+fn invocations(json: &str) -> Vec<Value> {
+    let db: Value = serde_json::from_str(json).unwrap();
+    assert_eq!(db["version"], 1);
+    db["invocations"].as_array().unwrap().clone()
+}
+
+fn find<'a>(invocations: &'a [Value], target_name: &str) -> &'a Value {
+    invocations
+        .iter()
+        .find(|inv| inv["target_name"] == target_name)
+        .unwrap_or_else(|| panic!("no invocation of `{}` in {:#?}", target_name, invocations))
+}
+
+#[cargo_test]
+fn rustc_invocations_requires_unstable_options() {
+    let p = project().file("src/lib.rs", "").build();
+
+    p.cargo("build --rustc-invocations")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] the `--rustc-invocations` flag is unstable, pass `-Z unstable-options` to enable it
+See https://doc.rust-lang.org/nightly/cargo/reference/unstable.html \
+for more information about the `--rustc-invocations` flag.
+",
+        )
+        .run();
+    p.cargo("check --rustc-invocations")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] the `--rustc-invocations` flag is unstable, pass `-Z unstable-options` to enable it
+See https://doc.rust-lang.org/nightly/cargo/reference/unstable.html \
+for more information about the `--rustc-invocations` flag.
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn lists_all_rustc_invocations() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+                authors = []
+
+                [dependencies]
+                bar = { path = "bar" }
+            "#,
+        )
+        .file(
+            "build.rs",
+            r#"
+                fn main() {
+                    println!("cargo:rustc-cfg=from_build_script");
+                    println!("cargo:rustc-env=FROM_BUILD_SCRIPT=1");
+                }
+            "#,
+        )
+        .file(
+            "src/lib.rs",
+            r#"
+                #[cfg(from_build_script)]
+                pub const FROM_BUILD_SCRIPT: &str = env!("FROM_BUILD_SCRIPT");
+            "#,
+        )
+        .file("bar/Cargo.toml", &basic_manifest("bar", "0.1.0"))
+        .file("bar/src/lib.rs", "")
+        .build();
+
+    p.cargo("build --rustc-invocations -Z unstable-options")
+        .masquerade_as_nightly_cargo()
+        .run();
+
+    let invocations = invocations(&p.read_file("target/debug/rustc-invocations.json"));
+    let names: Vec<_> = invocations
+        .iter()
+        .map(|inv| inv["target_name"].as_str().unwrap())
+        .collect();
+    assert_eq!(invocations.len(), 3, "{:?}", names);
+
+    let script = find(&invocations, "build-script-build");
+    assert_eq!(script["target_kind"], serde_json::json!(["custom-build"]));
+    assert_eq!(script["compile_mode"], "build");
+
+    let foo = find(&invocations, "foo");
+    assert_eq!(foo["crate_name"], "foo");
+    assert_eq!(foo["kind"], Value::Null);
+    assert!(foo["package_id"]
+        .as_str()
+        .unwrap()
+        .starts_with("foo 0.0.1 (path+file://"));
+    let args: Vec<&str> = foo["args"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|arg| arg.as_str().unwrap())
+        .collect();
+    assert!(
+        args.windows(2).any(|w| w == ["--cfg", "from_build_script"]),
+        "{:?}",
+        args
+    );
+    assert!(args.iter().any(|arg| arg.starts_with("bar=")), "{:?}", args);
+    assert_eq!(foo["env"]["FROM_BUILD_SCRIPT"], "1");
+    assert_eq!(foo["env"]["CARGO_PKG_NAME"], "foo");
+    assert_eq!(foo["cwd"].as_str().unwrap(), p.root().to_str().unwrap());
+
+    // The invocation can be run again as is.
+    let mut cmd = Command::new(foo["program"].as_str().unwrap());
+    cmd.args(&args).current_dir(foo["cwd"].as_str().unwrap());
+    for (var, value) in foo["env"].as_object().unwrap() {
+        cmd.env(var, value.as_str().unwrap());
+    }
+    let status = cmd.status().unwrap();
+    assert!(status.success());
+}
+
+#[cargo_test]
+fn fresh_units_without_invocation_are_rebuilt() {
+    let p = project()
+        .file("src/lib.rs", "")
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    p.cargo("build").run();
+    p.cargo("build --rustc-invocations -Z unstable-options")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[COMPILING] foo v0.0.1 ([..])
+[FINISHED] [..]
+",
+        )
+        .run();
+    assert_eq!(
+        invocations(&p.read_file("target/debug/rustc-invocations.json")).len(),
+        2
+    );
+
+    p.root().join("target/debug/rustc-invocations.json").rm_rf();
+    p.cargo("build --rustc-invocations -Z unstable-options")
+        .masquerade_as_nightly_cargo()
+        .with_stderr("[FINISHED] [..]")
+        .run();
+    assert_eq!(
+        invocations(&p.read_file("target/debug/rustc-invocations.json")).len(),
+        2
+    );
+}
+
+#[cargo_test]
+fn check_lists_check_invocations() {
+    let p = project().file("src/lib.rs", "").build();
+
+    p.cargo("check --rustc-invocations -Z unstable-options")
+        .masquerade_as_nightly_cargo()
+        .run();
+
+    let invocations = invocations(&p.read_file("target/debug/rustc-invocations.json"));
+    assert_eq!(invocations.len(), 1);
+    let foo = &invocations[0];
+    assert_eq!(foo["compile_mode"], "check");
+    assert!(foo["args"].as_array().unwrap().iter().any(|arg| arg
+        .as_str()
+        .unwrap()
+        .starts_with("--emit=dep-info,metadata")));
+}
@@ -367,3 +367,20 @@ fn rebuilds_when_changing() {
         )
         .run();
 }
+
+#[cargo_test]
+fn documented_deps_link_locally() {
+    // Dependencies documented in the same build are linked to locally.
+    if !is_nightly() {
+        // --extern-html-root-url is unstable
+        return;
+    }
+    let p = basic_project();
+    p.cargo("doc -v -Zrustdoc-map")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains("[DOCUMENTING] bar v1.0.0")
+        .with_stderr_does_not_contain("[..]--extern-html-root-url[..]")
+        .run();
+    let myfun = p.read_file("target/doc/foo/fn.myfun.html");
+    assert!(myfun.contains(r#"href="../bar/struct.Straw.html""#));
+}
@@ -0,0 +1,242 @@
+//! Tests for `cargo build --sbom`.
+
+use cargo_test_support::registry::Package;
+use cargo_test_support::{basic_manifest, find_json_mismatch, project};
+use std::env::consts::EXE_SUFFIX;
+use std::fs;
+
+#[cargo_test]
+fn sbom_requires_unstable_options() {
+    let p = project().file("src/main.rs", "fn main() {}").build();
+
+    p.cargo("build --sbom")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] the `--sbom` flag is unstable, pass `-Z unstable-options` to enable it
+See https://doc.rust-lang.org/nightly/cargo/reference/unstable.html \
+for more information about the `--sbom` flag.
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn sbom_lists_compiled_packages() {
+    Package::new("bar", "0.1.0")
+        .feature("f1", &[])
+        .feature("f2", &[])
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "bar"
+                version = "0.1.0"
+                license = "MIT OR Apache-2.0"
+                description = "The bar library"
+
+                [features]
+                f1 = []
+                f2 = []
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .publish();
+    Package::new("baz", "0.1.0").publish();
+    Package::new("unused", "0.1.0").publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+                authors = []
+                build = "build.rs"
+
+                [dependencies]
+                bar = { version = "0.1", features = ["f1"] }
+
+                [build-dependencies]
+                baz = "0.1"
+
+                [dev-dependencies]
+                unused = "0.1"
+            "#,
+        )
+        .file("build.rs", "fn main() {}")
+        .file("src/lib.rs", "")
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    p.cargo("build --sbom -Z unstable-options")
+        .masquerade_as_nightly_cargo()
+        .run();
+
+    let sbom = p.read_file(&format!("target/debug/foo{}.cdx.json", EXE_SUFFIX));
+    let actual: serde_json::Value = serde_json::from_str(&sbom).unwrap();
+    let expected: serde_json::Value = serde_json::from_str(
+        r#"
+        {
+          "bomFormat": "CycloneDX",
+          "specVersion": "1.4",
+          "version": 1,
+          "metadata": {
+            "tools": [
+              {
+                "name": "cargo",
+                "version": "[..]"
+              }
+            ],
+            "component": {
+              "type": "application",
+              "bom-ref": "foo v0.0.1 ([..])",
+              "name": "foo",
+              "version": "0.0.1",
+              "properties": [
+                {
+                  "name": "cargo:source",
+                  "value": "path+file://[..]/foo"
+                },
+                {
+                  "name": "cargo:features",
+                  "value": ""
+                }
+              ]
+            }
+          },
+          "components": [
+            {
+              "type": "library",
+              "bom-ref": "bar v0.1.0",
+              "name": "bar",
+              "version": "0.1.0",
+              "scope": "required",
+              "description": "The bar library",
+              "hashes": [
+                {
+                  "alg": "SHA-256",
+                  "content": "[..]"
+                }
+              ],
+              "licenses": [
+                {
+                  "expression": "MIT OR Apache-2.0"
+                }
+              ],
+              "purl": "pkg:cargo/bar@0.1.0",
+              "properties": [
+                {
+                  "name": "cargo:source",
+                  "value": "registry+https://github.com/rust-lang/crates.io-index"
+                },
+                {
+                  "name": "cargo:features",
+                  "value": "f1"
+                }
+              ]
+            },
+            {
+              "type": "library",
+              "bom-ref": "baz v0.1.0",
+              "name": "baz",
+              "version": "0.1.0",
+              "scope": "excluded",
+              "hashes": [
+                {
+                  "alg": "SHA-256",
+                  "content": "[..]"
+                }
+              ],
+              "purl": "pkg:cargo/baz@0.1.0",
+              "properties": [
+                {
+                  "name": "cargo:source",
+                  "value": "registry+https://github.com/rust-lang/crates.io-index"
+                },
+                {
+                  "name": "cargo:features",
+                  "value": ""
+                }
+              ]
+            }
+          ],
+          "dependencies": [
+            {
+              "ref": "foo v0.0.1 ([..])",
+              "dependsOn": ["bar v0.1.0", "baz v0.1.0"]
+            },
+            {
+              "ref": "bar v0.1.0",
+              "dependsOn": []
+            },
+            {
+              "ref": "baz v0.1.0",
+              "dependsOn": []
+            }
+          ]
+        }
+        "#,
+    )
+    .unwrap();
+    if let Err(e) = find_json_mismatch(&expected, &actual) {
+        panic!("{}", e);
+    }
+
+    // The library gets its own SBOM.
+    let mut sboms: Vec<String> = fs::read_dir(p.root().join("target/debug"))
+        .unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name.ends_with(".cdx.json"))
+        .collect();
+    sboms.sort();
+    assert_eq!(
+        sboms,
+        [
+            format!("foo{}.cdx.json", EXE_SUFFIX),
+            "libfoo.rlib.cdx.json".to_string()
+        ]
+    );
+}
+
+#[cargo_test]
+fn sbom_of_git_dependency() {
+    let git = cargo_test_support::git::new("bar", |p| {
+        p.file("Cargo.toml", &basic_manifest("bar", "0.5.0"))
+            .file("src/lib.rs", "")
+    });
+    let p = project()
+        .file(
+            "Cargo.toml",
+            &format!(
+                r#"
+                    [package]
+                    name = "foo"
+                    version = "0.0.1"
+                    authors = []
+
+                    [dependencies]
+                    bar = {{ git = '{}' }}
+                "#,
+                git.url()
+            ),
+        )
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    p.cargo("build --sbom -Z unstable-options")
+        .masquerade_as_nightly_cargo()
+        .run();
+
+    let sbom = p.read_file(&format!("target/debug/foo{}.cdx.json", EXE_SUFFIX));
+    let actual: serde_json::Value = serde_json::from_str(&sbom).unwrap();
+    let purl = actual["components"][0]["purl"].as_str().unwrap();
+    assert!(
+        purl.starts_with("pkg:cargo/bar@0.5.0?vcs_url=git%2Bfile%3A%2F%2F"),
+        "{}",
+        purl
+    );
+    assert!(actual["components"][0].get("hashes").is_none());
+}
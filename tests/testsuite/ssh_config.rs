@@ -0,0 +1,193 @@
+//! Tests for reading the OpenSSH configuration and `known_hosts` files.
+
+use std::fs;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use cargo::sources::git::ssh::{match_line, HostConfig, KnownKey};
+use cargo_test_support::paths;
+
+const KEY: &str = "AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl";
+
+fn known_key(revoked: bool) -> Option<KnownKey> {
+    Some(KnownKey {
+        key_type: "ssh-ed25519".to_string(),
+        key: STANDARD.decode(KEY).unwrap(),
+        revoked,
+    })
+}
+
+fn write_ssh_config(contents: &str) {
+    let ssh = paths::home().join(".ssh");
+    fs::create_dir_all(&ssh).unwrap();
+    fs::write(ssh.join("config"), contents).unwrap();
+}
+
+#[cargo_test]
+fn known_hosts_plain_entries() {
+    let line = format!("example.com,192.0.2.1 ssh-ed25519 {}", KEY);
+    assert_eq!(match_line(&line, "example.com"), known_key(false));
+    assert_eq!(match_line(&line, "192.0.2.1"), known_key(false));
+    assert_eq!(match_line(&line, "example.org"), None);
+
+    // Host names are case insensitive, and ports other than 22 are part of
+    // the name.
+    assert_eq!(match_line(&line, "EXAMPLE.com"), known_key(false));
+    let line = format!("[example.com]:2222 ssh-ed25519 {}", KEY);
+    assert_eq!(match_line(&line, "[example.com]:2222"), known_key(false));
+    assert_eq!(match_line(&line, "example.com"), None);
+
+    assert_eq!(match_line("", "example.com"), None);
+    assert_eq!(match_line("# example.com", "example.com"), None);
+    assert_eq!(match_line("example.com ssh-ed25519", "example.com"), None);
+    assert_eq!(
+        match_line("example.com ssh-ed25519 not-base64!", "example.com"),
+        None
+    );
+}
+
+#[cargo_test]
+fn known_hosts_hashed_entries() {
+    let line = format!(
+        "|1|AAECAwQFBgcICQoLDA0ODxAREhM=|nnUK16ANsXd3hL31YfAkGOluSjU= ssh-ed25519 {}",
+        KEY
+    );
+    assert_eq!(match_line(&line, "example.com"), known_key(false));
+    assert_eq!(match_line(&line, "example.org"), None);
+
+    let line = format!(
+        "|1|AAECAwQFBgcICQoLDA0ODxAREhM=|Wgcx+Fm+LmaWwC7rQ80eIf2uHe0= ssh-ed25519 {}",
+        KEY
+    );
+    assert_eq!(match_line(&line, "[example.com]:2222"), known_key(false));
+    assert_eq!(match_line(&line, "example.com"), None);
+
+    // Salts longer than a SHA-1 block are hashed into the HMAC key.
+    let line = format!(
+        "|1|AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8gISIjJCUmJygpKissLS4vMDEyMzQ1Njc4OTo7PD0+\
+         P0BBQkNERQ==|t9lkCqtqUwEu1cVpyHdGSN6vWpE= ssh-ed25519 {}",
+        KEY
+    );
+    assert_eq!(match_line(&line, "example.com"), known_key(false));
+
+    // Malformed hashes match nothing.
+    let line = format!("|1|AAECAwQFBgcICQoLDA0ODxAREhM= ssh-ed25519 {}", KEY);
+    assert_eq!(match_line(&line, "example.com"), None);
+    let line = format!(
+        "|1|not base64|nnUK16ANsXd3hL31YfAkGOluSjU= ssh-ed25519 {}",
+        KEY
+    );
+    assert_eq!(match_line(&line, "example.com"), None);
+}
+
+#[cargo_test]
+fn known_hosts_markers() {
+    let line = format!("@revoked example.com ssh-ed25519 {}", KEY);
+    assert_eq!(match_line(&line, "example.com"), known_key(true));
+    assert_eq!(match_line(&line, "example.org"), None);
+
+    // Certificate authorities aren't supported.
+    let line = format!("@cert-authority *.example.com ssh-ed25519 {}", KEY);
+    assert_eq!(match_line(&line, "git.example.com"), None);
+}
+
+#[cargo_test]
+fn known_hosts_wildcards() {
+    let line = format!("*.example.com,!secret.example.com ssh-ed25519 {}", KEY);
+    assert_eq!(match_line(&line, "git.example.com"), known_key(false));
+    assert_eq!(match_line(&line, "a.b.example.com"), known_key(false));
+    assert_eq!(match_line(&line, "example.com"), None);
+    assert_eq!(match_line(&line, "secret.example.com"), None);
+
+    let line = format!("git?.example.com ssh-ed25519 {}", KEY);
+    assert_eq!(match_line(&line, "git1.example.com"), known_key(false));
+    assert_eq!(match_line(&line, "git.example.com"), None);
+    assert_eq!(match_line(&line, "git12.example.com"), None);
+}
+
+#[cargo_test]
+fn ssh_config_hosts() {
+    write_ssh_config(
+        "\
+# Settings before the first `Host` apply to every host.
+IdentityFile ~/.ssh/id_global
+
+Host alias
+    HostName git.example.com
+    User = git
+    Port 2222
+    IdentityFile \"~/.ssh/id with space\"
+    UserKnownHostsFile ~/.ssh/known_%h other_known_hosts
+
+Host *.example.com !secret.example.com
+    User wildcard
+    StrictHostKeyChecking accept-new
+
+# The first value of a setting wins.
+Host alias
+    HostName ignored.example.com
+    IdentityFile ~/.ssh/id_second
+",
+    );
+    let home = paths::home();
+
+    let alias = HostConfig::load(&home, "alias");
+    assert_eq!(alias.hostname.as_deref(), Some("git.example.com"));
+    assert_eq!(alias.user.as_deref(), Some("git"));
+    assert_eq!(alias.port, Some(2222));
+    assert_eq!(
+        alias.identity_files,
+        [
+            home.join(".ssh/id_global"),
+            home.join(".ssh/id with space"),
+            home.join(".ssh/id_second"),
+        ]
+    );
+    assert_eq!(
+        alias.user_known_hosts_files,
+        [home.join(".ssh/known_alias"), "other_known_hosts".into()]
+    );
+    assert_eq!(alias.strict_host_key_checking, None);
+
+    let git = HostConfig::load(&home, "git.example.com");
+    assert_eq!(git.hostname, None);
+    assert_eq!(git.user.as_deref(), Some("wildcard"));
+    assert_eq!(git.strict_host_key_checking.as_deref(), Some("accept-new"));
+    assert_eq!(git.identity_files, [home.join(".ssh/id_global")]);
+
+    let secret = HostConfig::load(&home, "secret.example.com");
+    assert_eq!(secret.user, None);
+    assert_eq!(secret.strict_host_key_checking, None);
+}
+
+#[cargo_test]
+fn ssh_config_include_and_match() {
+    write_ssh_config(
+        "\
+Include config.d/*.conf
+
+Match host other
+    User ignored
+
+Match all
+    IdentityAgent none
+",
+    );
+    let home = paths::home();
+    let dir = home.join(".ssh/config.d");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("b.conf"), "Host alias\n    Port 2\n    User b\n").unwrap();
+    fs::write(dir.join("a.conf"), "Host alias\n    Port 1\n").unwrap();
+    fs::write(dir.join("c.txt"), "Host alias\n    User c\n").unwrap();
+
+    // Included files are read in order of their names.
+    let alias = HostConfig::load(&home, "alias");
+    assert_eq!(alias.port, Some(1));
+    assert_eq!(alias.user.as_deref(), Some("b"));
+    // `Match` conditions other than `all` aren't supported, so their
+    // settings are ignored.
+    assert_eq!(alias.identity_agent.as_deref(), Some("none"));
+
+    let other = HostConfig::load(&home, "other");
+    assert_eq!(other.user, None);
+}
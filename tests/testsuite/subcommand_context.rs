@@ -0,0 +1,153 @@
+//! Tests for `-Z subcommand-context`.
+
+use std::env;
+
+use cargo_test_support::paths::{self, CargoPathExt};
+use cargo_test_support::{basic_manifest, cargo_exe, cargo_process, project, Project};
+use serde_json::{json, Value};
+
+/// Builds a `cargo-context` subcommand which prints its context, or
+/// `no context` if it didn't get one.
+fn context_subcommand() -> Project {
+    let p = project()
+        .at("cargo-context")
+        .file("Cargo.toml", &basic_manifest("cargo-context", "1.0.0"))
+        .file(
+            "src/main.rs",
+            r#"
+                fn main() {
+                    match std::env::var_os("CARGO_CONTEXT_FILE") {
+                        Some(path) => print!("{}", std::fs::read_to_string(path).unwrap()),
+                        None => print!("no context"),
+                    }
+                }
+            "#,
+        )
+        .build();
+    p.cargo("build").run();
+    p
+}
+
+fn path_with(subcommand: &Project) -> std::ffi::OsString {
+    let mut path: Vec<_> = env::split_paths(&env::var_os("PATH").unwrap_or_default()).collect();
+    path.insert(0, subcommand.target_debug_dir());
+    env::join_paths(path).unwrap()
+}
+
+fn parse_context(stdout: &[u8]) -> Value {
+    let context: Value = serde_json::from_slice(stdout).unwrap();
+    assert_eq!(context["version"], 1);
+    context
+}
+
+#[cargo_test]
+fn context_of_workspace() {
+    let subcommand = context_subcommand();
+    let p = project()
+        .file("src/lib.rs", "")
+        .file(
+            ".cargo/config",
+            r#"
+                [build]
+                target-dir = "out"
+
+                [alias]
+                b2 = ["build", "-j2"]
+            "#,
+        )
+        .build();
+
+    let output = p
+        .cargo("-Z subcommand-context context")
+        .masquerade_as_nightly_cargo()
+        .env("PATH", path_with(&subcommand))
+        .exec_with_output()
+        .unwrap();
+    let context = parse_context(&output.stdout);
+    let root = p.root();
+    assert_eq!(context["cargo"], json!(cargo_exe().canonicalize().unwrap()));
+    assert_eq!(context["cwd"], json!(root));
+    assert_eq!(context["workspace_root"], json!(root));
+    assert_eq!(context["manifest_path"], json!(root.join("Cargo.toml")));
+    assert_eq!(context["target_dir"], json!(root.join("out")));
+    assert_eq!(context["profile"], "dev");
+    assert_eq!(context["profile_dir"], json!(root.join("out/debug")));
+    assert_eq!(context["config"]["build"]["target-dir"], "out");
+    assert_eq!(context["config"]["alias"]["b2"], json!(["build", "-j2"]));
+}
+
+#[cargo_test]
+fn subcommand_arguments() {
+    let subcommand = context_subcommand();
+    let p = project()
+        .file("src/lib.rs", "")
+        .file("bar/Cargo.toml", &basic_manifest("bar", "0.1.0"))
+        .file("bar/src/lib.rs", "")
+        .build();
+
+    let output = p
+        .cargo("-Z subcommand-context context --release --manifest-path=bar/Cargo.toml")
+        .masquerade_as_nightly_cargo()
+        .env("PATH", path_with(&subcommand))
+        .exec_with_output()
+        .unwrap();
+    let context = parse_context(&output.stdout);
+    let bar = p.root().join("bar");
+    assert_eq!(context["workspace_root"], json!(bar));
+    assert_eq!(context["target_dir"], json!(bar.join("target")));
+    assert_eq!(context["profile"], "release");
+    assert_eq!(context["profile_dir"], json!(bar.join("target/release")));
+
+    // Arguments after `--` are left alone.
+    let output = p
+        .cargo("-Z subcommand-context context -- --release")
+        .masquerade_as_nightly_cargo()
+        .env("PATH", path_with(&subcommand))
+        .exec_with_output()
+        .unwrap();
+    assert_eq!(parse_context(&output.stdout)["profile"], "dev");
+}
+
+#[cargo_test]
+fn outside_of_workspace() {
+    let subcommand = context_subcommand();
+    let dir = paths::root().join("empty");
+    dir.mkdir_p();
+
+    let output = cargo_process("-Z subcommand-context context")
+        .cwd(&dir)
+        .masquerade_as_nightly_cargo()
+        .env("PATH", path_with(&subcommand))
+        .exec_with_output()
+        .unwrap();
+    let context = parse_context(&output.stdout);
+    assert_eq!(context["cwd"], json!(dir));
+    assert_eq!(context["workspace_root"], Value::Null);
+    assert_eq!(context["manifest_path"], Value::Null);
+    assert_eq!(context["target_dir"], Value::Null);
+    assert_eq!(context["profile_dir"], Value::Null);
+    assert_eq!(context["profile"], "dev");
+}
+
+#[cargo_test]
+fn no_context_by_default() {
+    let subcommand = context_subcommand();
+    let p = project().file("src/lib.rs", "").build();
+
+    p.cargo("context")
+        .env("PATH", path_with(&subcommand))
+        .with_stdout("no context")
+        .run();
+}
+
+#[cargo_test]
+fn requires_nightly() {
+    let subcommand = context_subcommand();
+    let p = project().file("src/lib.rs", "").build();
+
+    p.cargo("-Z subcommand-context context")
+        .env("PATH", path_with(&subcommand))
+        .with_status(101)
+        .with_stderr_contains("[..]the `-Z` flag is only accepted on the nightly channel[..]")
+        .run();
+}
@@ -0,0 +1,184 @@
+//! Tests for the `dependencies` field of binaries and examples.
+
+use cargo_test_support::registry::Package;
+use cargo_test_support::{basic_manifest, project};
+
+#[cargo_test]
+fn bin_dependencies() {
+    Package::new("clap", "1.0.0")
+        .file("src/lib.rs", "pub fn parse() {}")
+        .publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                cargo-features = ["target-dependencies"]
+
+                [package]
+                name = "foo"
+                version = "0.1.0"
+                edition = "2018"
+
+                [dependencies]
+                clap = { version = "1.0", optional = true }
+
+                [[bin]]
+                name = "cli"
+                dependencies = ["clap"]
+
+                [[example]]
+                name = "ex"
+            "#,
+        )
+        .file("src/lib.rs", "pub fn f() {}")
+        .file("src/bin/cli.rs", "fn main() { clap::parse(); foo::f(); }")
+        .file("src/bin/other.rs", "fn main() { foo::f(); }")
+        .file("examples/ex.rs", "fn main() { foo::f(); }")
+        .build();
+
+    // The binary isn't built without its dependencies.
+    p.cargo("build")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[COMPILING] foo v0.1.0 ([CWD])
+[FINISHED] [..]
+",
+        )
+        .run();
+    assert!(!p.bin("cli").is_file());
+    assert!(p.bin("other").is_file());
+
+    p.cargo("run --bin cli")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] target `cli` in package `foo` requires the features: `clap`
+Consider enabling them by passing, e.g., `--features=\"clap\"`
+",
+        )
+        .run();
+
+    // Only the binary is linked with its dependencies.
+    p.cargo("build --features clap --lib --bins --examples -v")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains("[RUNNING] `rustc --crate-name cli [..]--extern clap=[..]")
+        .with_stderr_line_without(
+            &["[RUNNING] `rustc --crate-name foo --edition=2018 src/lib.rs"],
+            &["--extern clap"],
+        )
+        .with_stderr_line_without(&["[RUNNING] `rustc --crate-name other"], &["--extern clap"])
+        .with_stderr_line_without(&["[RUNNING] `rustc --crate-name ex"], &["--extern clap"])
+        .run();
+    assert!(p.bin("cli").is_file());
+}
+
+#[cargo_test]
+fn requires_feature() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [[bin]]
+                name = "foo"
+                path = "src/main.rs"
+                dependencies = []
+            "#,
+        )
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    p.cargo("build")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] failed to parse manifest at `[CWD]/Cargo.toml`
+
+Caused by:
+  feature `target-dependencies` is required
+
+  consider adding `cargo-features = [\"target-dependencies\"]` to the manifest
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn invalid_dependencies() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                cargo-features = ["target-dependencies"]
+
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [dependencies]
+                bar = { path = "bar" }
+
+                [[bin]]
+                name = "foo"
+                path = "src/main.rs"
+                dependencies = ["bar"]
+            "#,
+        )
+        .file("src/main.rs", "fn main() {}")
+        .file("bar/Cargo.toml", &basic_manifest("bar", "0.1.0"))
+        .file("bar/src/lib.rs", "")
+        .build();
+
+    p.cargo("build")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] failed to parse manifest at `[CWD]/Cargo.toml`
+
+Caused by:
+  bin \"foo\" lists `bar` in `dependencies`, but it is not an optional dependency \
+in `[dependencies]`
+",
+        )
+        .run();
+
+    p.change_file(
+        "Cargo.toml",
+        r#"
+            cargo-features = ["target-dependencies"]
+
+            [package]
+            name = "foo"
+            version = "0.1.0"
+
+            [dependencies]
+            bar = { path = "bar", optional = true }
+
+            [lib]
+            path = "src/main.rs"
+            dependencies = ["bar"]
+        "#,
+    );
+
+    p.cargo("build")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] failed to parse manifest at `[CWD]/Cargo.toml`
+
+Caused by:
+  `dependencies` can only be set for binaries and examples, but it is set for lib
+",
+        )
+        .run();
+}
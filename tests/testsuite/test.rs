@@ -4245,3 +4245,1037 @@ fn bin_env_for_test() {
     p.cargo("test --test check_env").run();
     p.cargo("check --test check_env").run();
 }
+
+#[cargo_test]
+fn format_requires_unstable_options() {
+    let p = project().file("src/lib.rs", "").build();
+
+    p.cargo("test --format json")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[ERROR] the `--format` flag is unstable, pass `-Z unstable-options` to enable it
+See https://doc.rust-lang.org/nightly/cargo/reference/unstable.html \
+for more information about the `--format` flag.
+",
+        )
+        .with_status(101)
+        .run();
+}
+
+#[cargo_test]
+fn format_json() {
+    // The events of all the test harnesses end up in one stream on stdout.
+    let p = project()
+        .file(
+            "src/lib.rs",
+            r#"
+                /// ```
+                /// foo::f();
+                /// ```
+                pub fn f() {}
+
+                #[test]
+                fn unit() {}
+            "#,
+        )
+        .file(
+            "tests/t1.rs",
+            r#"
+                #[test]
+                fn it() { println!("from it"); }
+            "#,
+        )
+        .build();
+
+    // libtest only emits JSON with unstable options.
+    p.cargo("test -Z unstable-options --format json")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .with_json_contains_unordered(
+            r#"
+                {
+                    "reason": "test-message",
+                    "package_id": "foo 0.0.1 ([..])",
+                    "target": "{...}",
+                    "executable": "[..]/target/debug/deps/foo-[..][EXE]",
+                    "doctest": false,
+                    "event": {"type": "test", "name": "unit", "event": "ok", "exec_time": "{...}"}
+                }
+
+                {
+                    "reason": "test-message",
+                    "package_id": "foo 0.0.1 ([..])",
+                    "target": {
+                        "kind": ["test"],
+                        "crate_types": ["bin"],
+                        "name": "t1",
+                        "src_path": "[..]/tests/t1.rs",
+                        "edition": "2015",
+                        "doc": false,
+                        "doctest": false,
+                        "test": true
+                    },
+                    "executable": "[..]/target/debug/deps/t1-[..][EXE]",
+                    "doctest": false,
+                    "event": {"type": "test", "name": "it", "event": "ok", "exec_time": "{...}"}
+                }
+
+                {
+                    "reason": "test-message",
+                    "package_id": "foo 0.0.1 ([..])",
+                    "target": "{...}",
+                    "executable": null,
+                    "doctest": true,
+                    "event": {
+                        "type": "test",
+                        "name": "src/lib.rs - f (line 2)",
+                        "event": "ok",
+                        "exec_time": "{...}"
+                    }
+                }
+
+                {
+                    "reason": "test-finished",
+                    "success": true,
+                    "passed": 3,
+                    "failed": 0,
+                    "ignored": 0,
+                    "measured": 0,
+                    "filtered_out": 0,
+                    "flaky": 0,
+                    "failures": []
+                }
+            "#,
+        )
+        .run();
+}
+
+#[cargo_test]
+fn format_junit() {
+    let p = project()
+        .file(
+            "src/lib.rs",
+            r#"
+                #[test]
+                fn pass() {}
+
+                #[test]
+                fn fail() { println!("1 < 2"); panic!(); }
+
+                #[test]
+                #[ignore]
+                fn skip() {}
+            "#,
+        )
+        .file("tests/t1.rs", "#[test] fn it() {}")
+        .build();
+
+    p.cargo("test -Z unstable-options --format junit --no-fail-fast")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .with_stdout("")
+        .with_stderr_contains("[..]JUnit report saved to [..]/foo/target/junit.xml")
+        .with_status(101)
+        .run();
+
+    let report = p.read_file("target/junit.xml");
+    assert!(report.starts_with(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <testsuites tests=\"4\" failures=\"1\" skipped=\"1\">\n\
+         \x20 <testsuite name=\"foo::lib::foo\" tests=\"3\" failures=\"1\" skipped=\"1\" time=\""
+    ));
+    assert!(report.contains("    <testcase classname=\"foo::lib::foo\" name=\"fail\" time=\""));
+    assert!(report.contains("      <failure>1 &lt; 2\n"));
+    assert!(report.contains(
+        "    <testcase classname=\"foo::lib::foo\" name=\"skip\">\n      <skipped/>\n    </testcase>\n"
+    ));
+    assert!(report.contains(
+        "  <testsuite name=\"foo::integration-test::t1\" tests=\"1\" failures=\"0\" skipped=\"0\""
+    ));
+    assert!(report.ends_with("  </testsuite>\n</testsuites>\n"));
+}
+
+#[cargo_test]
+fn timeout_kills_hung_test() {
+    let p = project()
+        .file(
+            "src/lib.rs",
+            r#"
+                #[test]
+                fn pass() {}
+            "#,
+        )
+        .file(
+            "tests/hang.rs",
+            r#"
+                #[test]
+                fn hangs() {
+                    std::thread::sleep(std::time::Duration::from_secs(600));
+                }
+            "#,
+        )
+        .build();
+
+    p.cargo("test --timeout 1s")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[ERROR] the `--timeout` flag is unstable, pass `-Z unstable-options` to enable it
+See https://doc.rust-lang.org/nightly/cargo/reference/unstable.html \
+for more information about the `--timeout` flag.
+",
+        )
+        .with_status(101)
+        .run();
+
+    p.cargo("test -Z unstable-options --timeout 1s")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .with_stdout_contains("test pass ... ok")
+        .with_stdout_contains(
+            "test result: ok. 1 passed; 0 failed; 0 ignored; 0 measured; 0 filtered out; \
+             finished in [..]s",
+        )
+        .with_stderr_contains("[ERROR] test failed, to rerun pass '--test hang'")
+        .with_stderr_contains(
+            "  test `hangs` did not finish within 1s, killed process `[..]hang-[..]` ([..])",
+        )
+        .with_status(101)
+        .run();
+
+    p.cargo("test -Z unstable-options")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .env("CARGO_TEST_TIMEOUT", "1s")
+        .with_stderr_contains(
+            "  test `hangs` did not finish within 1s, killed process `[..]hang-[..]` ([..])",
+        )
+        .with_status(101)
+        .run();
+}
+
+#[cargo_test]
+fn timeout_invalid_config() {
+    let p = project().file("src/lib.rs", "").build();
+
+    p.cargo("test -Z unstable-options")
+        .masquerade_as_nightly_cargo()
+        .env("CARGO_TEST_TIMEOUT", "soon")
+        .with_stderr(
+            "\
+[ERROR] invalid configuration for key `test.timeout` in environment variable `CARGO_TEST_TIMEOUT`
+
+Caused by:
+  invalid test timeout `soon`: expected number at 0
+",
+        )
+        .with_status(101)
+        .run();
+}
+
+/// Returns the names of the tests `cargo test` ran, from its output, after
+/// checking the status it printed for `--partition`.
+fn tests_run(output: &std::process::Output, partition: &str) -> Vec<String> {
+    let stderr = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(
+        stderr.contains(&format!("   Partition {}", partition)),
+        "{}",
+        stderr
+    );
+    let stdout = std::str::from_utf8(&output.stdout).unwrap();
+    let mut names: Vec<String> = stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix("test "))
+        .filter_map(|line| line.strip_suffix(" ... ok"))
+        .map(|name| name.to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+#[cargo_test]
+fn partition_runs_each_test_once() {
+    let p = project()
+        .file(
+            "src/lib.rs",
+            r#"
+                /// ```
+                /// foo::f();
+                /// ```
+                pub fn f() {}
+
+                #[test] fn a() {}
+                #[test] fn b() {}
+                #[test] fn c() {}
+                #[test] fn d() {}
+                #[test] fn e() {}
+            "#,
+        )
+        .file(
+            "tests/it.rs",
+            r#"
+                #[test] fn it_a() {}
+                #[test] fn it_b() {}
+                #[test] fn it_c() {}
+            "#,
+        )
+        .build();
+
+    p.cargo("test --partition 1/2")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[ERROR] the `--partition` flag is unstable, pass `-Z unstable-options` to enable it
+See https://doc.rust-lang.org/nightly/cargo/reference/unstable.html \
+for more information about the `--partition` flag.
+",
+        )
+        .with_status(101)
+        .run();
+
+    p.cargo("test -Z unstable-options --partition 3/2")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "[ERROR] invalid partition `3/2`, expected a shard and a number of shards \
+             like `2/4`, with the shard between 1 and the number of shards",
+        )
+        .with_status(101)
+        .run();
+
+    let mut all = Vec::new();
+    for shard in &["1/2", "2/2"] {
+        let output = p
+            .cargo(&format!("test -Z unstable-options --partition {}", shard))
+            .masquerade_as_nightly_cargo()
+            .env("RUSTC_BOOTSTRAP", "1")
+            .exec_with_output()
+            .unwrap();
+        all.extend(tests_run(&output, &format!("{}, ", shard)));
+    }
+    all.sort();
+    assert_eq!(
+        all,
+        [
+            "a",
+            "b",
+            "c",
+            "d",
+            "e",
+            "it_a",
+            "it_b",
+            "it_c",
+            "src/lib.rs - f (line 2)",
+        ]
+    );
+
+    // Filters apply before the tests are split.
+    let mut all = Vec::new();
+    for shard in &["1/2", "2/2"] {
+        let output = p
+            .cargo(&format!(
+                "test -Z unstable-options --partition {} it_",
+                shard
+            ))
+            .masquerade_as_nightly_cargo()
+            .env("RUSTC_BOOTSTRAP", "1")
+            .exec_with_output()
+            .unwrap();
+        all.extend(tests_run(&output, &format!("{}, ", shard)));
+    }
+    all.sort();
+    assert_eq!(all, ["it_a", "it_b", "it_c"]);
+}
+
+#[cargo_test]
+fn partition_strategy_time() {
+    let p = project()
+        .file(
+            "src/lib.rs",
+            r#"
+                #[test] fn a() {}
+                #[test] fn b() {}
+                #[test] fn c() {}
+                #[test] fn slow() {}
+            "#,
+        )
+        .build();
+
+    p.cargo("test -Z unstable-options --partition 1/2")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .run();
+    let timings = p.read_file("target/test-timings.json");
+    assert!(timings.contains("\"foo::lib::foo::"), "{}", timings);
+
+    // With the recorded durations, the slow test gets a shard of its own. The
+    // doc tests of the library count as one more test. Every shard records
+    // the durations of its tests, so they are reset before each run, like CI
+    // machines restoring the same cached file.
+    let timings = r#"{
+        "foo::lib::foo::a": 1.0,
+        "foo::lib::foo::b": 1.0,
+        "foo::lib::foo::c": 1.0,
+        "foo::lib::foo::slow": 10.0
+    }"#;
+    p.change_file("target/test-timings.json", timings);
+    let output = p
+        .cargo("test -Z unstable-options --partition 1/2 --partition-strategy time")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .exec_with_output()
+        .unwrap();
+    assert_eq!(tests_run(&output, "1/2, 1 of 5 tests"), ["slow"]);
+    p.change_file("target/test-timings.json", timings);
+    let output = p
+        .cargo("test -Z unstable-options --partition 2/2 --partition-strategy time")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .exec_with_output()
+        .unwrap();
+    assert_eq!(tests_run(&output, "2/2, 4 of 5 tests"), ["a", "b", "c"]);
+}
+
+#[cargo_test]
+fn partition_strategy_time_invalid_timings() {
+    let p = project()
+        .file(
+            "src/lib.rs",
+            r#"
+                #[test] fn a() {}
+                #[test] fn b() {}
+                #[test] fn slow() {}
+            "#,
+        )
+        .build();
+
+    // Negative durations are ignored, and huge ones make the loads of the
+    // shards infinite without breaking the partition.
+    let timings = r#"{
+        "foo::lib::foo::a": -1.0,
+        "foo::lib::foo::b": 1e308,
+        "foo::lib::foo::slow": 1e308
+    }"#;
+    let mut all = Vec::new();
+    for shard in &["1/2", "2/2"] {
+        p.change_file("target/test-timings.json", timings);
+        let output = p
+            .cargo(&format!(
+                "test -Z unstable-options --partition {} --partition-strategy time --lib",
+                shard
+            ))
+            .masquerade_as_nightly_cargo()
+            .env("RUSTC_BOOTSTRAP", "1")
+            .exec_with_output()
+            .unwrap();
+        all.extend(tests_run(&output, &format!("{}, ", shard)));
+    }
+    all.sort();
+    assert_eq!(all, ["a", "b", "slow"]);
+}
+
+#[cargo_test]
+fn retries_flaky_test() {
+    // The `flaky` test fails until it has been run as many times as the
+    // number in `flaky-runs`.
+    let p = project()
+        .file(
+            "src/lib.rs",
+            r#"
+                #[test]
+                fn pass() {}
+
+                #[test]
+                fn flaky() {
+                    let runs = std::fs::read_to_string("flaky-runs").unwrap();
+                    let runs: u32 = runs.trim().parse().unwrap();
+                    std::fs::write("flaky-runs", (runs - 1).to_string()).unwrap();
+                    assert!(runs <= 1, "not yet");
+                }
+            "#,
+        )
+        .file("flaky-runs", "3")
+        .build();
+
+    p.cargo("test --retries 1")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[ERROR] the `--retries` flag is unstable, pass `-Z unstable-options` to enable it
+See https://doc.rust-lang.org/nightly/cargo/reference/unstable.html \
+for more information about the `--retries` flag.
+",
+        )
+        .with_status(101)
+        .run();
+
+    p.cargo("test -Z unstable-options --retries 2")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .with_stdout_contains("test flaky ... FAILED")
+        .with_stdout_contains("running 1 test")
+        .with_stdout_contains("test flaky ... ok")
+        .with_stdout_contains("    foo::lib::foo::flaky ... flaky-pass (attempt 3)")
+        .with_stderr_contains(
+            "[..]Retrying 1 failed test of target/debug/deps/foo-[..] (attempt 2/3)",
+        )
+        .with_stderr_contains(
+            "[..]Retrying 1 failed test of target/debug/deps/foo-[..] (attempt 3/3)",
+        )
+        .run();
+
+    p.change_file("flaky-runs", "2");
+    p.cargo("test -Z unstable-options --retries 1 --format json")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .with_json_contains_unordered(
+            r#"
+                {
+                    "reason": "test-flaky",
+                    "package_id": "foo 0.0.1 [..]",
+                    "target": "{...}",
+                    "name": "flaky",
+                    "attempts": 2
+                }
+
+                {
+                    "reason": "test-finished",
+                    "success": true,
+                    "passed": 2,
+                    "failed": 0,
+                    "ignored": 0,
+                    "measured": 0,
+                    "filtered_out": 0,
+                    "flaky": 1,
+                    "failures": []
+                }
+            "#,
+        )
+        .run();
+
+    p.change_file("flaky-runs", "2");
+    p.cargo("test -Z unstable-options --retries 1 --format junit")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .run();
+    let report = p.read_file("target/junit.xml");
+    assert!(
+        report.contains("  <testsuite name=\"foo::lib::foo\" tests=\"2\" failures=\"0\""),
+        "{}",
+        report
+    );
+    assert!(report.contains("      <flakyFailure>"), "{}", report);
+    assert!(report.contains("not yet"), "{}", report);
+
+    // A test which fails every time still fails `cargo test`.
+    p.change_file("flaky-runs", "3");
+    p.cargo("test -Z unstable-options --retries 1")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .with_stdout_contains_n("test flaky ... FAILED", 2)
+        .with_stderr_contains(
+            "[..]Retrying 1 failed test of target/debug/deps/foo-[..] (attempt 2/2)",
+        )
+        .with_stderr_contains("[ERROR] test failed, to rerun pass '--lib'")
+        .with_status(101)
+        .run();
+}
+
+#[cargo_test]
+fn no_fail_fast_summary() {
+    let p = project()
+        .file(
+            "src/lib.rs",
+            r#"
+                /// ```
+                /// assert!(false);
+                /// ```
+                pub fn f() {}
+
+                #[test]
+                fn pass() {}
+
+                #[test]
+                fn fails() { panic!() }
+            "#,
+        )
+        .file(
+            "tests/t1.rs",
+            r#"
+                #[test]
+                fn t1_fails() { panic!() }
+
+                mod m {
+                    #[test]
+                    fn fails() { panic!() }
+                }
+            "#,
+        )
+        .file("tests/t2.rs", "#[test] fn pass() {}")
+        .build();
+
+    p.cargo("test --no-fail-fast -- --test-threads 1")
+        .with_status(101)
+        .with_stdout_contains("test fails ... FAILED")
+        .with_stderr_does_not_contain("[SUMMARY][..]")
+        .run();
+
+    p.cargo("test -Z unstable-options --no-fail-fast -- --test-threads 1")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stdout_contains("test fails ... FAILED")
+        .with_stderr_contains(
+            "\
+[SUMMARY] 3 test targets failed
+    foo lib `foo` (target/debug/deps/foo-[..][EXE])
+      failed: fails
+      rerun: cargo test --lib -- --test-threads 1 --exact fails
+    foo integration-test `t1` (target/debug/deps/t1-[..][EXE])
+      failed: m::fails, t1_fails
+      rerun: cargo test --test t1 -- --test-threads 1 --exact m::fails t1_fails
+    foo doctest `foo`
+      failed: src/lib.rs - f (line 2)
+      rerun: cargo test --doc -- --test-threads 1
+",
+        )
+        .run();
+
+    p.cargo("test -Z unstable-options --format json --no-fail-fast --test t1 -- --test-threads 1")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .with_status(101)
+        .with_json_contains_unordered(
+            r#"
+                {
+                    "reason": "test-finished",
+                    "success": false,
+                    "passed": 0,
+                    "failed": 2,
+                    "ignored": 0,
+                    "measured": 0,
+                    "filtered_out": 0,
+                    "flaky": 0,
+                    "failures": [
+                        {
+                            "package_id": "foo 0.0.1 [..]",
+                            "target": "{...}",
+                            "executable": "[..]/foo/target/debug/deps/t1-[..][EXE]",
+                            "doctest": false,
+                            "tests": ["m::fails", "t1_fails"],
+                            "rerun": "cargo test --test t1 -- --test-threads 1 --exact m::fails t1_fails"
+                        }
+                    ]
+                }
+            "#,
+        )
+        .run();
+}
+
+#[cargo_test]
+fn test_cache() {
+    // Each run of the test adds a byte to `runs`, which is in the target
+    // directory, so that it isn't an input of the test.
+    let p = project()
+        .file(
+            "src/lib.rs",
+            r#"
+                #[test]
+                fn pass() {
+                    use std::io::Write;
+                    let mut runs = std::fs::OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open("target/runs")
+                        .unwrap();
+                    runs.write_all(b"x").unwrap();
+                }
+            "#,
+        )
+        .file("tests/fail.rs", "#[test] fn fail() { panic!() }")
+        .file("tests/data/fixture.txt", "1")
+        .build();
+    let runs = || p.read_file("target/runs").len();
+
+    p.cargo("test --no-cache")
+        .with_stderr("[ERROR] Usage of `--no-cache` requires `-Z test-cache`")
+        .with_status(101)
+        .run();
+
+    p.cargo("test -Z test-cache --no-fail-fast")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains("[RUNNING] target/debug/deps/foo-[..][EXE]")
+        .with_stderr_contains("[RUNNING] target/debug/deps/fail-[..][EXE]")
+        .with_status(101)
+        .run();
+    assert_eq!(runs(), 1);
+
+    // The test binary which failed runs again, but not the one which passed.
+    p.cargo("test -Z test-cache --no-fail-fast")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains(
+            "[..]Cached target/debug/deps/foo-[..][EXE] (passed with the same inputs)",
+        )
+        .with_stderr_contains("[RUNNING] target/debug/deps/fail-[..][EXE]")
+        .with_stderr_contains("[DOCTEST] foo")
+        .with_status(101)
+        .run();
+    assert_eq!(runs(), 1);
+
+    // Other arguments for the test harness are other inputs.
+    p.cargo("test -Z test-cache --lib pass")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains("[RUNNING] target/debug/deps/foo-[..][EXE]")
+        .run();
+    assert_eq!(runs(), 2);
+
+    p.cargo("test -Z test-cache --lib --no-cache")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains("[RUNNING] target/debug/deps/foo-[..][EXE]")
+        .run();
+    assert_eq!(runs(), 3);
+
+    p.cargo("test -Z test-cache --lib")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains("[..]Cached target/debug/deps/foo-[..][EXE] [..]")
+        .run();
+    assert_eq!(runs(), 3);
+
+    // A test binary which is built again runs again.
+    p.change_file(
+        "src/lib.rs",
+        &p.read_file("src/lib.rs")
+            .replace("fn pass", "fn pass_again"),
+    );
+    p.cargo("test -Z test-cache --lib")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains("[RUNNING] target/debug/deps/foo-[..][EXE]")
+        .with_stdout_contains("test pass_again ... ok")
+        .run();
+    assert_eq!(runs(), 4);
+
+    // So does one whose package has a file which changed, like a fixture.
+    p.change_file("tests/data/fixture.txt", "2");
+    p.cargo("test -Z test-cache --lib")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains("[RUNNING] target/debug/deps/foo-[..][EXE]")
+        .run();
+    assert_eq!(runs(), 5);
+
+    // And one whose inherited environment changed.
+    p.cargo("test -Z test-cache --lib")
+        .masquerade_as_nightly_cargo()
+        .env("FOO_FIXTURES", "other")
+        .with_stderr_contains("[RUNNING] target/debug/deps/foo-[..][EXE]")
+        .run();
+    assert_eq!(runs(), 6);
+
+    // Without `-Z test-cache`, everything runs.
+    p.cargo("test --lib")
+        .with_stderr_contains("[RUNNING] target/debug/deps/foo-[..][EXE]")
+        .run();
+    assert_eq!(runs(), 7);
+}
+
+#[cargo_test]
+#[cfg(unix)]
+fn coverage() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let p = project()
+        .file(
+            "src/lib.rs",
+            r#"
+                pub fn one() -> u32 { 1 }
+
+                #[test]
+                fn unit() { assert_eq!(one(), 1); }
+            "#,
+        )
+        .file(
+            "tests/integration.rs",
+            "#[test] fn integration() { assert_eq!(foo::one(), 1); }",
+        )
+        .build();
+
+    p.cargo("test --coverage")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[ERROR] the `--coverage` flag is unstable, pass `-Z unstable-options` to enable it
+See https://doc.rust-lang.org/nightly/cargo/reference/unstable.html \
+for more information about the `--coverage` flag.
+",
+        )
+        .with_status(101)
+        .run();
+
+    // Stand-ins for the LLVM tools, which are used if the toolchain doesn't
+    // have them.
+    let tools = paths::root().join("llvm-tools");
+    fs::create_dir_all(&tools).unwrap();
+    let scripts = [
+        (
+            "llvm-profdata",
+            "#!/bin/sh\nfor out; do :; done\ntouch \"$out\"\n",
+        ),
+        (
+            "llvm-cov",
+            "#!/bin/sh\n\
+             case \"$1\" in export) echo 'SF:src/lib.rs' ;; esac\n\
+             for arg; do case \"$arg\" in -output-dir=*) \
+             mkdir -p \"${arg#-output-dir=}\" && touch \"${arg#-output-dir=}/index.html\" ;; \
+             esac; done\n",
+        ),
+    ];
+    for (name, script) in &scripts {
+        let path = tools.join(name);
+        fs::write(&path, script).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    let mut path = vec![tools];
+    path.extend(std::env::split_paths(
+        &std::env::var_os("PATH").unwrap_or_default(),
+    ));
+    let path = std::env::join_paths(path).unwrap();
+
+    p.cargo("test -v -Z unstable-options --coverage")
+        .masquerade_as_nightly_cargo()
+        .env("PATH", &path)
+        .with_stderr_contains(
+            "[RUNNING] `rustc --crate-name foo src/lib.rs [..]-C instrument-coverage[..]",
+        )
+        .with_stderr_contains(
+            "[RUNNING] `rustc --crate-name integration tests/integration.rs [..]\
+             -C instrument-coverage[..]",
+        )
+        .with_stderr_contains(
+            "[..]Coverage target/coverage/lcov.info and target/coverage/html/index.html",
+        )
+        .run();
+
+    let profiles = fs::read_dir(p.root().join("target/coverage"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name.ends_with(".profraw"))
+        .collect::<Vec<_>>();
+    assert!(profiles.iter().any(|name| name.starts_with("foo-")));
+    assert!(profiles.iter().any(|name| name.starts_with("integration-")));
+    assert!(p.root().join("target/coverage/lcov.info").is_file());
+    assert!(p.root().join("target/coverage/html/index.html").is_file());
+    // No raw profile is left in the package.
+    assert!(!fs::read_dir(p.root())
+        .unwrap()
+        .any(|entry| entry.unwrap().path().extension() == Some("profraw".as_ref())));
+
+    // Without `--coverage`, the crates are built again without instrumentation.
+    p.cargo("test -v")
+        .with_stderr_contains("[COMPILING] foo v0.0.1 ([CWD])")
+        .with_stderr_does_not_contain("[..]instrument-coverage[..]")
+        .run();
+}
+
+#[cargo_test]
+fn list_json() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [workspace]
+                members = ["foo", "bar"]
+            "#,
+        )
+        .file("foo/Cargo.toml", &basic_manifest("foo", "0.0.1"))
+        .file(
+            "foo/src/lib.rs",
+            r#"
+                /// ```
+                /// foo::f();
+                /// ```
+                pub fn f() {}
+
+                #[test]
+                fn unit() {}
+
+                #[test]
+                #[ignore]
+                fn slow() {}
+            "#,
+        )
+        .file(
+            "bar/Cargo.toml",
+            r#"
+                [package]
+                name = "bar"
+                version = "0.0.1"
+
+                [[test]]
+                name = "custom"
+                harness = false
+            "#,
+        )
+        .file("bar/src/lib.rs", "")
+        .file("bar/tests/custom.rs", "fn main() { panic!() }")
+        .build();
+
+    p.cargo("test --list")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[ERROR] the `--list` flag is unstable, pass `-Z unstable-options` to enable it
+See https://doc.rust-lang.org/nightly/cargo/reference/unstable.html \
+for more information about the `--list` flag.
+",
+        )
+        .with_status(101)
+        .run();
+
+    p.cargo("test -Z unstable-options --list --format json")
+        .masquerade_as_nightly_cargo()
+        .with_json(
+            r#"
+                {
+                    "reason": "test-list",
+                    "packages": [
+                        {
+                            "package_id": "bar 0.0.1 ([..])",
+                            "targets": [
+                                {
+                                    "target": "{...}",
+                                    "executable": "[..]/target/debug/deps/bar-[..][EXE]",
+                                    "doctest": false,
+                                    "tests": []
+                                },
+                                {
+                                    "target": "{...}",
+                                    "executable": "[..]/target/debug/deps/custom-[..][EXE]",
+                                    "doctest": false,
+                                    "tests": null
+                                },
+                                {
+                                    "target": "{...}",
+                                    "executable": null,
+                                    "doctest": true,
+                                    "tests": []
+                                }
+                            ]
+                        },
+                        {
+                            "package_id": "foo 0.0.1 ([..])",
+                            "targets": [
+                                {
+                                    "target": {
+                                        "kind": ["lib"],
+                                        "crate_types": ["lib"],
+                                        "name": "foo",
+                                        "src_path": "[..]/foo/src/lib.rs",
+                                        "edition": "2015",
+                                        "doc": true,
+                                        "doctest": true,
+                                        "test": true
+                                    },
+                                    "executable": "[..]/target/debug/deps/foo-[..][EXE]",
+                                    "doctest": false,
+                                    "tests": [
+                                        {"name": "slow", "ignored": true},
+                                        {"name": "unit", "ignored": false}
+                                    ]
+                                },
+                                {
+                                    "target": "{...}",
+                                    "executable": null,
+                                    "doctest": true,
+                                    "tests": [
+                                        {"name": "foo/src/lib.rs - f (line 2)", "ignored": false}
+                                    ]
+                                }
+                            ]
+                        }
+                    ]
+                }
+            "#,
+        )
+        .run();
+
+    p.cargo("test -Z unstable-options --list -p foo")
+        .masquerade_as_nightly_cargo()
+        .with_stdout(
+            "\
+foo lib `foo`:
+    slow (ignored)
+    unit
+foo doctest `foo`:
+    foo/src/lib.rs - f (line 2)
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn parallel_doctests() {
+    let p = project()
+        .file(
+            "src/lib.rs",
+            r#"
+                /// ```
+                /// assert_eq!(include_str!(concat!(env!("OUT_DIR"), "/hello")), "hello");
+                /// assert!(cfg!(from_build_script));
+                /// ```
+                pub fn f() {}
+
+                #[test]
+                fn unit() {}
+            "#,
+        )
+        .file(
+            "build.rs",
+            r#"
+                fn main() {
+                    let out_dir = std::env::var("OUT_DIR").unwrap();
+                    std::fs::write(format!("{}/hello", out_dir), "hello").unwrap();
+                    println!("cargo:rustc-cfg=from_build_script");
+                }
+            "#,
+        )
+        .build();
+
+    // The doc tests run during the build, and their results are shown with
+    // the other tests.
+    let output = p
+        .cargo("test -v -Z parallel-doctests")
+        .masquerade_as_nightly_cargo()
+        .exec_with_output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let line = |pred: &dyn Fn(&str) -> bool| stderr.lines().position(|line| pred(line));
+    let rustdoc = line(&|line| line.contains("`rustdoc ") && line.contains(" --test "));
+    let finished = line(&|line| line.contains("Finished"));
+    let doc_tests = line(&|line| line.contains("Doc-tests foo"));
+    assert!(rustdoc < finished && finished < doc_tests, "{}", stderr);
+    assert!(rustdoc.is_some(), "{}", stderr);
+    assert!(stdout.contains("test unit ... ok"), "{}", stdout);
+    assert!(
+        stdout.contains("test src/lib.rs - f (line 2) ... ok"),
+        "{}",
+        stdout
+    );
+
+    p.change_file(
+        "src/lib.rs",
+        r#"
+            /// ```
+            /// assert!(false);
+            /// ```
+            pub fn f() {}
+        "#,
+    );
+    p.cargo("test -Z parallel-doctests -Z unstable-options --no-fail-fast")
+        .masquerade_as_nightly_cargo()
+        .with_stdout_contains("test src/lib.rs - f (line 2) ... FAILED")
+        .with_stderr_contains("[DOCTEST] foo")
+        .with_stderr_contains("[..]Summary 1 test target failed")
+        .with_stderr_contains("    foo doctest `foo`")
+        .with_stderr_contains("[ERROR] test failed, to rerun pass '--doc'")
+        .with_status(101)
+        .run();
+}
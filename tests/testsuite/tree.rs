@@ -832,6 +832,15 @@ foo v0.1.0 ([..]/foo)
     └── foo v0.1.0 ([..]/foo) (*)
 ",
         )
+        .with_stderr_contains(
+            "[NOTE] dev-dependency `bar` of `foo` depends on `foo` itself: \
+             foo v0.1.0 -> bar v0.1.0 -> foo v0.1.0",
+        )
+        .run();
+
+    p.cargo("tree -e normal")
+        .with_stdout("foo v0.1.0 ([..]/foo)")
+        .with_stderr_does_not_contain("[NOTE][..]")
         .run();
 
     p.cargo("tree --no-dedupe")
@@ -1472,3 +1481,64 @@ b v0.1.0 ([..]/foo/b)
         )
         .run();
 }
+
+#[cargo_test]
+fn rename_aware() {
+    // --rename-aware shows the names dependencies are used under.
+    Package::new("dep-a", "1.0.0").publish();
+    Package::new("dep", "1.0.0").dep("dep-a", "1.0").publish();
+    Package::new("bdep", "1.0.0").publish();
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+
+            [dependencies]
+            renamed = { version = "1.0", package = "dep" }
+
+            [build-dependencies]
+            bdep = "1.0"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file("build.rs", "fn main() {}")
+        .build();
+
+    p.cargo("tree --rename-aware")
+        .with_stdout(
+            "\
+foo v0.1.0 ([..]/foo)
+└── renamed: dep v1.0.0
+    └── dep_a: dep-a v1.0.0
+[build-dependencies]
+└── bdep: bdep v1.0.0
+",
+        )
+        .run();
+
+    p.cargo("tree --rename-aware -e features")
+        .with_stdout(
+            "\
+foo v0.1.0 ([..]/foo)
+└── renamed: dep feature \"default\"
+    └── dep v1.0.0
+        └── dep_a: dep-a feature \"default\"
+            └── dep-a v1.0.0
+[build-dependencies]
+└── bdep: bdep feature \"default\"
+    └── bdep v1.0.0
+",
+        )
+        .run();
+
+    p.cargo("tree --rename-aware -i dep")
+        .with_stderr(
+            "[ERROR] the `--rename-aware` flag cannot be used together with \
+             `--invert`, `--duplicates`, `--why-feature` or `--feature-unification`",
+        )
+        .with_status(101)
+        .run();
+}
@@ -359,3 +359,153 @@ foo v0.1.0 ([..]/foo)
         )
         .run();
 }
+
+#[cargo_test]
+fn why_feature() {
+    // Explains which dependency edges enable a feature.
+    Package::new("shared", "1.0.0")
+        .feature("default", &["std"])
+        .feature("std", &[])
+        .feature("alloc", &[])
+        .publish();
+    Package::new("nostd", "1.0.0")
+        .add_dep(
+            Dependency::new("shared", "1.0")
+                .default_features(false)
+                .enable_features(&["alloc"]),
+        )
+        .publish();
+    Package::new("leaky", "1.0.0")
+        .dep("shared", "1.0")
+        .publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+
+            [dependencies]
+            nostd = "1.0"
+
+            [build-dependencies]
+            leaky = "1.0"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("tree --why-feature shared/std")
+        .with_stdout(
+            "\
+shared feature \"std\"
+└── shared feature \"default\"
+    └── leaky v1.0.0
+        └── leaky feature \"default\"
+            [build-dependencies]
+            └── foo v0.1.0 ([ROOT]/foo)
+                └── foo feature \"default\" (command-line)
+",
+        )
+        .run();
+
+    p.cargo("tree --why-feature shared/alloc --why-feature nostd/default")
+        .with_stdout(
+            "\
+shared feature \"alloc\"
+└── nostd v1.0.0
+    └── nostd feature \"default\"
+        └── foo v0.1.0 ([ROOT]/foo)
+            └── foo feature \"default\" (command-line)
+
+nostd feature \"default\" (*)
+",
+        )
+        .run();
+
+    p.cargo("tree --why-feature shared/serde")
+        .with_status(101)
+        .with_stderr("[ERROR] feature `serde` is not enabled for package `shared`")
+        .run();
+
+    p.cargo("tree --why-feature shared")
+        .with_status(101)
+        .with_stderr(
+            "[ERROR] invalid value `shared` for `--why-feature`, \
+             expected a package and a feature like `serde/std`",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn feature_unification() {
+    // Explains features enabled on behalf of other dependents.
+    Package::new("shared", "1.0.0")
+        .feature("default", &["std"])
+        .feature("std", &[])
+        .feature("alloc", &[])
+        .feature("derive", &[])
+        .publish();
+    Package::new("nostd", "1.0.0")
+        .add_dep(
+            Dependency::new("shared", "1.0")
+                .default_features(false)
+                .enable_features(&["alloc"]),
+        )
+        .publish();
+    Package::new("builder", "1.0.0")
+        .add_dep(
+            Dependency::new("shared", "1.0")
+                .default_features(false)
+                .enable_features(&["derive"]),
+        )
+        .publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+
+            [dependencies]
+            nostd = "1.0"
+            shared = "1.0"
+
+            [build-dependencies]
+            builder = "1.0"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("tree --feature-unification")
+        .with_stdout(
+            "\
+shared v1.0.0 is built with features: alloc, default, derive, std
+    builder v1.0.0 requests features: derive
+    foo v0.1.0 ([ROOT]/foo) requests features: default
+    nostd v1.0.0 requests features: alloc
+    help: adding `default-features = false` to the `shared` dependency of foo v0.1.0 ([ROOT]/foo) would leave out features: default, std
+    help: `resolver = \"2\"` would build it with features: alloc, default, std, and with features: derive for build dependencies and proc-macros
+",
+        )
+        .run();
+
+    // Nothing is reported once `foo` only gets what it asks for.
+    p.change_file(
+        "Cargo.toml",
+        r#"
+        [package]
+        name = "foo"
+        version = "0.1.0"
+
+        [dependencies]
+        shared = "1.0"
+        "#,
+    );
+    p.cargo("tree --feature-unification").with_stdout("").run();
+}
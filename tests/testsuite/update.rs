@@ -392,6 +392,136 @@ fn update_precise() {
         .run();
 }
 
+#[cargo_test]
+fn update_precise_multiple() {
+    Package::new("log", "0.1.0").publish();
+    Package::new("log", "0.1.1").publish();
+    Package::new("serde", "0.2.0").publish();
+    Package::new("serde", "0.2.1").publish();
+    Package::new("foo", "0.1.0").dep("log", "0.1").publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "bar"
+                version = "0.0.1"
+
+                [dependencies]
+                serde = "0.2"
+                foo = "0.1"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("generate-lockfile").run();
+
+    // `log` is only a transitive dependency, but it can still be pinned.
+    p.cargo("update -p serde --precise 0.2.0 -p log --precise 0.1.0")
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[UPDATING] log v0.1.1 -> v0.1.0
+[UPDATING] serde v0.2.1 -> v0.2.0
+",
+        )
+        .run();
+
+    p.cargo("update -p serde -p log --precise 0.2.0 --precise 0.1.0 --precise 0.1.0")
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] `--precise` was specified 3 times, but 2 packages were specified with `-p`
+When `--precise` is given more than once, it must be given once for each `-p`.
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn update_precise_allow_yanked() {
+    Package::new("bar", "0.1.0").publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+
+                [dependencies]
+                bar = "0.1"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("generate-lockfile").run();
+    Package::new("bar", "0.1.1").yanked(true).publish();
+
+    p.cargo("update -p bar --precise 0.1.1")
+        .with_status(101)
+        .with_stderr_contains("[ERROR] no matching package named `bar` found")
+        .run();
+
+    p.cargo("update -p bar --precise 0.1.1 --allow-yanked")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains(
+            "[ERROR] the `--allow-yanked` flag is unstable, pass `-Z unstable-options` to enable it",
+        )
+        .run();
+
+    p.cargo("update -p bar --allow-yanked -Zunstable-options")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr("[ERROR] `--allow-yanked` can only be used together with `--precise`")
+        .run();
+
+    p.cargo("update -p bar --precise 0.1.1 --allow-yanked -Zunstable-options")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[UPDATING] bar v0.1.0 -> v0.1.1
+",
+        )
+        .run();
+    let lockfile = p.read_lockfile();
+    assert!(lockfile.contains("version = \"0.1.1\""));
+    assert!(lockfile.contains("yanked = true"));
+
+    // Every build reminds that a yanked version is in use.
+    p.cargo("build")
+        .with_stderr(
+            "\
+[WARNING] package `bar v0.1.1` in Cargo.lock is yanked in registry `crates.io`, \
+but was explicitly allowed with `cargo update --allow-yanked`
+[DOWNLOADING] crates ...
+[DOWNLOADED] bar v0.1.1 (registry `[..]`)
+[COMPILING] bar v0.1.1
+[COMPILING] foo v0.0.1 ([CWD])
+[FINISHED] [..]
+",
+        )
+        .run();
+
+    // Updating the package moves away from the yanked version, and drops
+    // the annotation.
+    p.cargo("update -p bar")
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[UPDATING] bar v0.1.1 -> v0.1.0
+",
+        )
+        .run();
+    assert!(!p.read_lockfile().contains("yanked"));
+}
+
 // cargo update should respect its arguments even without a lockfile.
 // See issue "Running cargo update without a Cargo.lock ignores arguments"
 // at <https://github.com/rust-lang/cargo/issues/6872>.
@@ -676,3 +806,88 @@ fn workspace_only() {
     assert!(!lock1.contains("0.0.2"));
     assert!(!lock2.contains("0.0.1"));
 }
+
+#[cargo_test]
+fn workspace_only_keeps_external_versions() {
+    Package::new("bar", "1.0.0").publish();
+    Package::new("baz", "1.0.0").publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+
+                [dependencies]
+                bar = "1.0"
+                baz = "1.0"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+    p.cargo("generate-lockfile").run();
+
+    Package::new("bar", "1.0.1").publish();
+    Package::new("baz", "1.0.1").publish();
+    Package::new("baz", "2.0.0").publish();
+
+    // Bumping the member keeps everything else as it is.
+    p.change_file(
+        "Cargo.toml",
+        r#"
+            [package]
+            name = "foo"
+            version = "0.0.2"
+
+            [dependencies]
+            bar = "1.0"
+            baz = "1.0"
+        "#,
+    );
+    p.cargo("update --workspace")
+        .with_stderr("[UPDATING] foo v0.0.1 ([..]) -> v0.0.2")
+        .run();
+    let lock = p.read_lockfile();
+    assert!(lock.contains("name = \"bar\"\nversion = \"1.0.0\""));
+    assert!(lock.contains("name = \"baz\"\nversion = \"1.0.0\""));
+
+    // A requirement which no longer matches the locked version would need
+    // another version of it.
+    p.change_file(
+        "Cargo.toml",
+        r#"
+            [package]
+            name = "foo"
+            version = "0.0.3"
+
+            [dependencies]
+            bar = "1.0"
+            baz = "2.0"
+        "#,
+    );
+    p.cargo("update --workspace")
+        .with_status(101)
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[ERROR] updating the workspace members would change the locked versions of other packages:
+  baz v1.0.0 -> v2.0.0
+The dependency requirements of the workspace no longer match the locked versions, \
+update them with `cargo update -p <SPEC>`.
+",
+        )
+        .run();
+    assert_eq!(p.read_lockfile(), lock);
+
+    p.cargo("update -p baz")
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[UPDATING] baz v1.0.0 -> v2.0.0
+[UPDATING] foo v0.0.2 ([..]) -> v0.0.3
+",
+        )
+        .run();
+}
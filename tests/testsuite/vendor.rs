@@ -424,6 +424,65 @@ fn vendoring_git_crates() {
     p.cargo("build").run();
 }
 
+#[cargo_test]
+fn platforms() {
+    Package::new("common", "0.1.0").publish();
+    Package::new("win", "0.1.0").publish();
+    Package::new("win-only", "0.1.0")
+        .dep("win", "0.1")
+        .publish();
+    Package::new("unix-only", "0.1.0").publish();
+    Package::new("wasm-only", "0.1.0").publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [dependencies]
+                common = "0.1"
+
+                [target.'cfg(windows)'.dependencies]
+                win-only = "0.1"
+
+                [target.'cfg(unix)'.dependencies]
+                unix-only = "0.1"
+
+                [target.wasm32-unknown-unknown.build-dependencies]
+                wasm-only = "0.1"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("vendor --respect-source-config --platform x86_64-pc-windows-msvc")
+        .with_stdout_contains(
+            "\
+# Only the dependencies of these platforms are vendored:
+# x86_64-pc-windows-msvc
+",
+        )
+        .run();
+    let vendored = |name: &str| p.root().join("vendor").join(name).exists();
+    assert!(vendored("common"));
+    assert!(vendored("win-only"));
+    assert!(vendored("win"));
+    assert!(!vendored("unix-only"));
+    assert!(!vendored("wasm-only"));
+
+    p.cargo("vendor --respect-source-config --platform x86_64-unknown-linux-gnu --platform wasm32-unknown-unknown")
+        .with_stdout_contains("# x86_64-unknown-linux-gnu, wasm32-unknown-unknown")
+        .run();
+    assert!(vendored("common"));
+    assert!(!vendored("win-only"));
+    assert!(!vendored("win"));
+    assert!(vendored("unix-only"));
+    assert!(vendored("wasm-only"));
+}
+
 #[cargo_test]
 fn git_simple() {
     let git = git::new("git", |p| {
@@ -454,6 +513,48 @@ fn git_simple() {
     assert!(csum.contains("\"package\":null"));
 }
 
+#[cargo_test]
+fn git_skipped_submodules() {
+    let git = git::new("git", |p| {
+        p.file("Cargo.toml", &basic_lib_manifest("a"))
+            .file("src/lib.rs", "")
+    });
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            &format!(
+                r#"
+                    cargo-features = ["git-submodules"]
+
+                    [package]
+                    name = "foo"
+                    version = "0.1.0"
+
+                    [dependencies]
+                    a = {{ git = '{}', submodules = false }}
+                "#,
+                git.url()
+            ),
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    let output = p
+        .cargo("vendor --respect-source-config")
+        .masquerade_as_nightly_cargo()
+        .exec_with_output()
+        .unwrap();
+    let output = String::from_utf8(output.stdout).unwrap();
+    assert!(output.contains("submodules = false"), "{}", output);
+    p.change_file(".cargo/config", &output);
+
+    p.cargo("check -v")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains("[..]foo/vendor/a/src/lib.rs[..]")
+        .run();
+}
+
 #[cargo_test]
 fn git_duplicate() {
     let git = git::new("a", |p| {
@@ -0,0 +1,319 @@
+//! Tests for the `cargo verify-manifest` command.
+
+use cargo_test_support::{basic_manifest, project};
+
+#[cargo_test]
+fn success() {
+    let p = project()
+        .file("Cargo.toml", &basic_manifest("foo", "0.1.0"))
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("verify-manifest -Zunstable-options --strict")
+        .masquerade_as_nightly_cargo()
+        .with_stdout(r#"{"success":true,"problems":[]}"#)
+        .run();
+}
+
+#[cargo_test]
+fn invalid() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [features]
+                bar = ["baz"]
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("verify-manifest -Zunstable-options")
+        .masquerade_as_nightly_cargo()
+        .with_status(1)
+        .with_json(
+            r#"
+                {
+                  "success": false,
+                  "problems": [
+                    {
+                      "kind": "invalid",
+                      "manifest_path": "[..]/foo/Cargo.toml",
+                      "key": null,
+                      "message": "failed to parse manifest at `[..]`\n\nCaused by:\n    [..]`baz`[..]"
+                    }
+                  ]
+                }
+            "#,
+        )
+        .run();
+}
+
+#[cargo_test]
+fn not_strict() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "foo"
+                version = "0.1.0"
+                autor = "me"
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("verify-manifest -Zunstable-options")
+        .masquerade_as_nightly_cargo()
+        .with_stdout(r#"{"success":true,"problems":[]}"#)
+        .run();
+}
+
+#[cargo_test]
+fn unused_and_deprecated_keys() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [project]
+                name = "foo"
+                version = "0.1.0"
+                autor = "me"
+
+                [lib]
+                crate_type = ["rlib"]
+
+                [dev_dependencies]
+                bar = { path = "bar", default_features = false }
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file("bar/Cargo.toml", &basic_manifest("bar", "0.1.0"))
+        .file("bar/src/lib.rs", "")
+        .build();
+
+    p.cargo("verify-manifest -Zunstable-options --strict")
+        .masquerade_as_nightly_cargo()
+        .with_status(1)
+        .with_json(
+            r#"
+                {
+                  "success": false,
+                  "problems": [
+                    {
+                      "kind": "unused-key",
+                      "manifest_path": "[..]/foo/Cargo.toml",
+                      "key": "project.autor",
+                      "message": "unused manifest key: project.autor"
+                    },
+                    {
+                      "kind": "deprecated-key",
+                      "manifest_path": "[..]/foo/Cargo.toml",
+                      "key": "project",
+                      "message": "`project` is deprecated in favor of `package`"
+                    },
+                    {
+                      "kind": "deprecated-key",
+                      "manifest_path": "[..]/foo/Cargo.toml",
+                      "key": "dev_dependencies",
+                      "message": "`dev_dependencies` is deprecated in favor of `dev-dependencies`"
+                    },
+                    {
+                      "kind": "deprecated-key",
+                      "manifest_path": "[..]/foo/Cargo.toml",
+                      "key": "dev_dependencies.bar.default_features",
+                      "message": "`dev_dependencies.bar.default_features` is deprecated in favor of `default-features`"
+                    },
+                    {
+                      "kind": "deprecated-key",
+                      "manifest_path": "[..]/foo/Cargo.toml",
+                      "key": "lib.crate_type",
+                      "message": "`lib.crate_type` is deprecated in favor of `crate-type`"
+                    }
+                  ]
+                }
+            "#,
+        )
+        .run();
+}
+
+#[cargo_test]
+fn never_matching_targets() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [features]
+                win = ["bar"]
+                linux = ["baz/std"]
+
+                [target.'cfg(all(unix, windows))'.dependencies]
+                bar = { path = "bar", optional = true }
+                baz = { path = "baz" }
+
+                [target.'cfg(any(unix, windows))'.dependencies]
+                bar = { path = "bar", optional = true }
+
+                [target.not-a-target.dependencies]
+                qux = { path = "qux" }
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file("bar/Cargo.toml", &basic_manifest("bar", "0.1.0"))
+        .file("bar/src/lib.rs", "")
+        .file(
+            "baz/Cargo.toml",
+            r#"
+                [package]
+                name = "baz"
+                version = "0.1.0"
+
+                [features]
+                std = []
+            "#,
+        )
+        .file("baz/src/lib.rs", "")
+        .file("qux/Cargo.toml", &basic_manifest("qux", "0.1.0"))
+        .file("qux/src/lib.rs", "")
+        .build();
+
+    p.cargo("verify-manifest -Zunstable-options --strict")
+        .masquerade_as_nightly_cargo()
+        .with_status(1)
+        .with_json(
+            r#"
+                {
+                  "success": false,
+                  "problems": [
+                    {
+                      "kind": "never-matching-target",
+                      "manifest_path": "[..]/foo/Cargo.toml",
+                      "key": "target.cfg(all(unix, windows))",
+                      "message": "`target.cfg(all(unix, windows))` can never match, since its `cfg` expression is always false"
+                    },
+                    {
+                      "kind": "never-matching-target",
+                      "manifest_path": "[..]/foo/Cargo.toml",
+                      "key": "target.not-a-target",
+                      "message": "`target.not-a-target` is not a target known to rustc, and only matches a custom target named `not-a-target`"
+                    },
+                    {
+                      "kind": "unused-feature",
+                      "manifest_path": "[..]/foo/Cargo.toml",
+                      "key": "features.linux",
+                      "message": "feature `linux` includes `baz/std`, but `baz` is only a dependency for targets which can never match"
+                    }
+                  ]
+                }
+            "#,
+        )
+        .run();
+}
+
+#[cargo_test]
+fn ignored_keys_in_member() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [workspace]
+                members = ["foo"]
+            "#,
+        )
+        .file(
+            "foo/Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [profile.dev]
+                opt-level = 1
+
+                [patch.crates-io]
+                bar = { path = "../bar" }
+            "#,
+        )
+        .file("foo/src/lib.rs", "")
+        .file("bar/Cargo.toml", &basic_manifest("bar", "0.1.0"))
+        .file("bar/src/lib.rs", "")
+        .build();
+
+    p.cargo("verify-manifest -Zunstable-options --strict")
+        .masquerade_as_nightly_cargo()
+        .with_status(1)
+        .with_json(
+            r#"
+                {
+                  "success": false,
+                  "problems": [
+                    {
+                      "kind": "ignored-key",
+                      "manifest_path": "[..]/foo/foo/Cargo.toml",
+                      "key": "profile",
+                      "message": "`profile` is ignored, since the package is not the root of its workspace; set it in `[..]/foo/Cargo.toml` instead"
+                    },
+                    {
+                      "kind": "ignored-key",
+                      "manifest_path": "[..]/foo/foo/Cargo.toml",
+                      "key": "patch",
+                      "message": "`patch` is ignored, since the package is not the root of its workspace; set it in `[..]/foo/Cargo.toml` instead"
+                    }
+                  ]
+                }
+            "#,
+        )
+        .run();
+}
+
+#[cargo_test]
+fn single_valued_cfg_keys() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [target.'cfg(all(target_os = "linux", target_os = "macos"))'.dependencies]
+                bar = { path = "bar" }
+
+                [target.'cfg(all(target_os = "linux", target_env = "gnu"))'.dependencies]
+                bar = { path = "bar" }
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file("bar/Cargo.toml", &basic_manifest("bar", "0.1.0"))
+        .file("bar/src/lib.rs", "")
+        .build();
+
+    p.cargo("verify-manifest -Zunstable-options --strict")
+        .masquerade_as_nightly_cargo()
+        .with_status(1)
+        .with_stdout_contains(
+            "[..]\"key\":\"target.cfg(all(target_os = [..], target_os = [..]))\"[..]",
+        )
+        .with_stdout_does_not_contain("[..]target_env[..]")
+        .run();
+}
+
+#[cargo_test]
+fn verify_manifest_requires_unstable_options() {
+    let p = project().file("src/lib.rs", "").build();
+
+    p.cargo("verify-manifest")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr("[ERROR] `cargo verify-manifest` requires `-Z unstable-options`")
+        .run();
+}
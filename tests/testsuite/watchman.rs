@@ -0,0 +1,255 @@
+//! Tests for `-Z watchman`.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use cargo_test_support::{basic_manifest, project, Project};
+use filetime::FileTime;
+
+/// Builds a fake `watchman` which watches `FAKE_WATCHMAN_ROOT`, and answers
+/// with the clock in the `clock` file of `FAKE_WATCHMAN_DIR`, and the changed
+/// files listed in its `changes` file. It is a fresh instance if there is a
+/// `fresh` file, and fails if there is a `fail` file. The commands it gets are
+/// appended to the `log` file.
+fn fake_watchman() -> Project {
+    let p = project()
+        .at("fake-watchman")
+        .file("Cargo.toml", &basic_manifest("watchman", "1.0.0"))
+        .file(
+            "src/main.rs",
+            r##"
+            use std::fs;
+            use std::io::{Read, Write};
+            use std::path::Path;
+
+            fn main() {
+                let dir = std::env::var("FAKE_WATCHMAN_DIR").unwrap();
+                let dir = Path::new(&dir);
+                let mut command = String::new();
+                std::io::stdin().read_to_string(&mut command).unwrap();
+                let mut log = fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(dir.join("log"))
+                    .unwrap();
+                writeln!(log, "{}", command).unwrap();
+                if dir.join("fail").exists() {
+                    eprintln!("watchman is broken");
+                    std::process::exit(1);
+                }
+                let clock = fs::read_to_string(dir.join("clock")).unwrap();
+                if command.starts_with(r#"["watch-project""#) {
+                    let root = std::env::var("FAKE_WATCHMAN_ROOT").unwrap();
+                    println!("{{\"watch\":{:?}}}", root);
+                } else if command.starts_with(r#"["clock""#) {
+                    println!("{{\"clock\":{:?}}}", clock);
+                } else if command.starts_with(r#"["query""#) {
+                    let changes = fs::read_to_string(dir.join("changes")).unwrap_or_default();
+                    let files: Vec<String> =
+                        changes.lines().map(|line| format!("{:?}", line)).collect();
+                    println!(
+                        "{{\"clock\":{:?},\"is_fresh_instance\":{},\"files\":[{}]}}",
+                        clock,
+                        dir.join("fresh").exists(),
+                        files.join(",")
+                    );
+                } else {
+                    println!("{{\"error\":\"unknown command\"}}");
+                }
+            }
+            "##,
+        )
+        .build();
+    p.cargo("build").run();
+    p.change_file("clock", "c:1");
+    p
+}
+
+fn path_with(watchman: &Project) -> std::ffi::OsString {
+    let mut path: Vec<PathBuf> =
+        env::split_paths(&env::var_os("PATH").unwrap_or_default()).collect();
+    path.insert(0, watchman.target_debug_dir());
+    env::join_paths(path).unwrap()
+}
+
+/// Makes `file` of `p` newer than everything built so far.
+fn touch(p: &Project, file: &str) {
+    let path = p.root().join(file);
+    let future = FileTime::from_unix_time(FileTime::now().unix_seconds() + 3600, 0);
+    filetime::set_file_times(&path, future, future).unwrap();
+}
+
+#[cargo_test]
+fn unchanged_files_are_not_checked() {
+    let watchman = fake_watchman();
+    let p = project()
+        .file("src/lib.rs", "mod m;")
+        .file("src/m.rs", "")
+        .build();
+    let path = path_with(&watchman);
+    let build = || {
+        let mut execs = p.cargo("build -Zwatchman");
+        execs
+            .masquerade_as_nightly_cargo()
+            .env("PATH", &path)
+            .env("FAKE_WATCHMAN_DIR", watchman.root())
+            .env("FAKE_WATCHMAN_ROOT", p.root());
+        execs
+    };
+
+    build()
+        .with_stderr(
+            "\
+[COMPILING] foo v0.0.1 ([..])
+[FINISHED] [..]
+",
+        )
+        .run();
+    let clock = p
+        .glob("target/debug/.fingerprint/foo-*/watchman-clock-lib-foo")
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(fs::read_to_string(&clock).unwrap(), "c:1");
+
+    // Watchman doesn't report the change, so the file isn't looked at.
+    touch(&p, "src/m.rs");
+    watchman.change_file("clock", "c:2");
+    build().with_stderr("[FINISHED] [..]").run();
+    assert_eq!(fs::read_to_string(&clock).unwrap(), "c:2");
+    let log = fs::read_to_string(watchman.root().join("log")).unwrap();
+    assert!(log.contains(r#""since":"c:1""#), "{}", log);
+
+    watchman.change_file("changes", "src/m.rs");
+    watchman.change_file("clock", "c:3");
+    build()
+        .with_stderr(
+            "\
+[COMPILING] foo v0.0.1 ([..])
+[FINISHED] [..]
+",
+        )
+        .run();
+    assert_eq!(fs::read_to_string(&clock).unwrap(), "c:3");
+}
+
+#[cargo_test]
+fn fresh_instance_checks_all_files() {
+    let watchman = fake_watchman();
+    let p = project().file("src/lib.rs", "").build();
+    let path = path_with(&watchman);
+    let build = || {
+        let mut execs = p.cargo("build -Zwatchman");
+        execs
+            .masquerade_as_nightly_cargo()
+            .env("PATH", &path)
+            .env("FAKE_WATCHMAN_DIR", watchman.root())
+            .env("FAKE_WATCHMAN_ROOT", p.root());
+        execs
+    };
+
+    build().run();
+    touch(&p, "src/lib.rs");
+    // Watchman restarted, so it doesn't know about the change.
+    watchman.change_file("fresh", "");
+    build()
+        .with_stderr(
+            "\
+[COMPILING] foo v0.0.1 ([..])
+[FINISHED] [..]
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn target_dir_is_always_checked() {
+    let watchman = fake_watchman();
+    let p = project()
+        .file(
+            "build.rs",
+            r#"
+            fn main() {
+                let out = std::env::var("OUT_DIR").unwrap();
+                let path = std::path::Path::new(&out).join("gen.rs");
+                if !path.exists() {
+                    std::fs::write(&path, "").unwrap();
+                }
+            }
+            "#,
+        )
+        .file(
+            "src/lib.rs",
+            r#"include!(concat!(env!("OUT_DIR"), "/gen.rs"));"#,
+        )
+        .build();
+    let path = path_with(&watchman);
+    let build = || {
+        let mut execs = p.cargo("build -Zwatchman");
+        execs
+            .masquerade_as_nightly_cargo()
+            .env("PATH", &path)
+            .env("FAKE_WATCHMAN_DIR", watchman.root())
+            .env("FAKE_WATCHMAN_ROOT", p.root());
+        execs
+    };
+
+    build().run();
+    let generated = p
+        .glob("target/debug/build/foo-*/out/gen.rs")
+        .next()
+        .unwrap()
+        .unwrap();
+    let generated = generated.strip_prefix(p.root()).unwrap();
+    touch(&p, generated.to_str().unwrap());
+    build()
+        .with_stderr(
+            "\
+[COMPILING] foo v0.0.1 ([..])
+[FINISHED] [..]
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn unavailable_watchman() {
+    let watchman = fake_watchman();
+    watchman.change_file("fail", "");
+    let p = project().file("src/lib.rs", "").build();
+
+    p.cargo("build -Zwatchman")
+        .masquerade_as_nightly_cargo()
+        .env("PATH", path_with(&watchman))
+        .env("FAKE_WATCHMAN_DIR", watchman.root())
+        .env("FAKE_WATCHMAN_ROOT", p.root())
+        .with_stderr(
+            "\
+[WARNING] checking the mtimes of all files, watchman is unavailable: \
+`watchman` failed ([..]): watchman is broken
+[COMPILING] foo v0.0.1 ([..])
+[FINISHED] [..]
+",
+        )
+        .run();
+
+    touch(&p, "src/lib.rs");
+    p.cargo("build -Zwatchman")
+        .masquerade_as_nightly_cargo()
+        .env("PATH", path_with(&watchman))
+        .env("FAKE_WATCHMAN_DIR", watchman.root())
+        .env("FAKE_WATCHMAN_ROOT", p.root())
+        .with_stderr_contains("[COMPILING] foo v0.0.1 ([..])")
+        .run();
+}
+
+#[cargo_test]
+fn requires_nightly() {
+    let p = project().file("src/lib.rs", "").build();
+
+    p.cargo("build -Zwatchman")
+        .with_status(101)
+        .with_stderr_contains("[..]the `-Z` flag is only accepted on the nightly channel[..]")
+        .run();
+}
@@ -0,0 +1,402 @@
+//! Tests for inheriting dependencies and package fields from the workspace.
+
+use cargo_test_support::publish::validate_crate_contents;
+use cargo_test_support::registry::Package;
+use cargo_test_support::{basic_manifest, project};
+use std::fs::File;
+
+#[cargo_test]
+fn inherit_dependencies() {
+    Package::new("dep", "0.1.0").feature("f", &[]).publish();
+    Package::new("dep", "0.2.0").feature("f", &[]).publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                cargo-features = ["workspace-inheritance"]
+
+                [workspace]
+                members = ["bar"]
+
+                [workspace.dependencies]
+                dep = "0.1"
+                util = { path = "util", version = "0.1.0" }
+            "#,
+        )
+        .file(
+            "bar/Cargo.toml",
+            r#"
+                cargo-features = ["workspace-inheritance"]
+
+                [package]
+                name = "bar"
+                version = "0.1.0"
+                authors = []
+
+                [dependencies]
+                dep = { workspace = true, features = ["f"], optional = true }
+                util = { workspace = true }
+            "#,
+        )
+        .file("bar/src/lib.rs", "")
+        .file("util/Cargo.toml", &basic_manifest("util", "0.1.0"))
+        .file("util/src/lib.rs", "")
+        .build();
+
+    p.cargo("build --features dep")
+        .cwd("bar")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[UPDATING] `[..]` index
+[DOWNLOADING] crates ...
+[DOWNLOADED] dep v0.1.0 ([..])
+[COMPILING] [..]
+[COMPILING] [..]
+[COMPILING] bar v0.1.0 ([CWD])
+[FINISHED] [..]
+",
+        )
+        .run();
+
+    let output = p
+        .cargo("metadata --format-version 1 --no-deps")
+        .masquerade_as_nightly_cargo()
+        .exec_with_output()
+        .unwrap();
+    let metadata: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let bar = metadata["packages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|pkg| pkg["name"] == "bar")
+        .unwrap();
+    let deps = bar["dependencies"].as_array().unwrap();
+    assert_eq!(deps[0]["name"], "dep");
+    assert_eq!(deps[0]["req"], "^0.1");
+    assert_eq!(deps[0]["features"], serde_json::json!(["f"]));
+    assert_eq!(deps[0]["optional"], true);
+    assert_eq!(deps[1]["name"], "util");
+    assert_eq!(deps[1]["req"], "^0.1.0");
+
+    p.cargo("package --no-verify --no-metadata")
+        .cwd("bar")
+        .masquerade_as_nightly_cargo()
+        .run();
+    let f = File::open(&p.root().join("target/package/bar-0.1.0.crate")).unwrap();
+    let rewritten_toml = format!(
+        r#"{}
+cargo-features = ["workspace-inheritance"]
+
+[package]
+name = "bar"
+version = "0.1.0"
+authors = []
+[dependencies.dep]
+version = "0.1"
+features = ["f"]
+optional = true
+
+[dependencies.util]
+version = "0.1.0"
+"#,
+        cargo::core::package::MANIFEST_PREAMBLE
+    );
+    validate_crate_contents(
+        f,
+        "bar-0.1.0.crate",
+        &["Cargo.toml", "Cargo.toml.orig", "src/lib.rs"],
+        &[("Cargo.toml", &rewritten_toml)],
+    );
+}
+
+#[cargo_test]
+fn root_package_inherits() {
+    Package::new("dep", "0.1.0").publish();
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                cargo-features = ["workspace-inheritance"]
+
+                [package]
+                name = "foo"
+                version = "0.1.0"
+                authors = []
+
+                [workspace]
+
+                [workspace.dependencies]
+                dep = "0.1"
+
+                [dev-dependencies]
+                dep = { workspace = true }
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("test --no-run")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains("[DOWNLOADED] dep v0.1.0 ([..])")
+        .run();
+}
+
+#[cargo_test]
+fn requires_feature() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+                authors = []
+
+                [workspace]
+
+                [dependencies]
+                dep = { workspace = true }
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    p.cargo("build")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] failed to parse manifest at `[CWD]/Cargo.toml`
+
+Caused by:
+  feature `workspace-inheritance` is required
+
+  consider adding `cargo-features = [\"workspace-inheritance\"]` to the manifest
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn inherit_errors() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                cargo-features = ["workspace-inheritance"]
+
+                [workspace]
+                members = ["bar"]
+
+                [workspace.dependencies]
+                dep = "0.1"
+            "#,
+        )
+        .file(
+            "bar/Cargo.toml",
+            r#"
+                cargo-features = ["workspace-inheritance"]
+
+                [package]
+                name = "bar"
+                version = "0.1.0"
+                authors = []
+
+                [dependencies]
+                other = { workspace = true }
+            "#,
+        )
+        .file("bar/src/lib.rs", "")
+        .build();
+
+    p.cargo("build")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] failed to parse manifest at `[CWD]/bar/Cargo.toml`
+
+Caused by:
+  dependency `other` is inherited from the workspace, but it is not in \
+`[workspace.dependencies]` of `[CWD]/Cargo.toml`
+",
+        )
+        .run();
+
+    p.change_file(
+        "bar/Cargo.toml",
+        r#"
+            cargo-features = ["workspace-inheritance"]
+
+            [package]
+            name = "bar"
+            version = "0.1.0"
+            authors = []
+
+            [dependencies]
+            dep = { workspace = true, version = "0.2" }
+        "#,
+    );
+
+    p.cargo("build")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] failed to parse manifest at `[CWD]/bar/Cargo.toml`
+
+Caused by:
+  dependency `dep` is inherited from the workspace, so it can only specify \
+`features` and `optional`, but it also specifies `version`
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn inherit_package_fields() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                cargo-features = ["workspace-inheritance"]
+
+                [workspace]
+                members = ["bar"]
+
+                [workspace.package]
+                version = "1.2.3"
+                edition = "2018"
+                authors = ["foo@example.com"]
+                license = "MIT"
+                repository = "https://example.com/repo"
+                readme = "README.md"
+            "#,
+        )
+        .file("README.md", "readme")
+        .file(
+            "bar/Cargo.toml",
+            r#"
+                cargo-features = ["workspace-inheritance"]
+
+                [package]
+                name = "bar"
+                version = { workspace = true }
+                edition = { workspace = true }
+                authors = { workspace = true }
+                license = { workspace = true }
+                repository = { workspace = true }
+                readme = { workspace = true }
+            "#,
+        )
+        .file("bar/src/lib.rs", "")
+        .build();
+
+    p.cargo("build")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[COMPILING] bar v1.2.3 ([CWD]/bar)
+[FINISHED] [..]
+",
+        )
+        .run();
+
+    p.cargo("package --no-verify")
+        .cwd("bar")
+        .masquerade_as_nightly_cargo()
+        .run();
+    let f = File::open(&p.root().join("target/package/bar-1.2.3.crate")).unwrap();
+    let rewritten_toml = format!(
+        r#"{}
+cargo-features = ["workspace-inheritance"]
+
+[package]
+edition = "2018"
+name = "bar"
+version = "1.2.3"
+authors = ["foo@example.com"]
+readme = "README.md"
+license = "MIT"
+repository = "https://example.com/repo"
+"#,
+        cargo::core::package::MANIFEST_PREAMBLE
+    );
+    validate_crate_contents(
+        f,
+        "bar-1.2.3.crate",
+        &["Cargo.toml", "Cargo.toml.orig", "README.md", "src/lib.rs"],
+        &[("Cargo.toml", &rewritten_toml), ("README.md", "readme")],
+    );
+}
+
+#[cargo_test]
+fn inherit_package_fields_errors() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                cargo-features = ["workspace-inheritance"]
+
+                [workspace]
+                members = ["bar"]
+
+                [workspace.package]
+                version = "1.2.3"
+            "#,
+        )
+        .file(
+            "bar/Cargo.toml",
+            r#"
+                cargo-features = ["workspace-inheritance"]
+
+                [package]
+                name = "bar"
+                version = { workspace = true }
+                license = { workspace = true }
+            "#,
+        )
+        .file("bar/src/lib.rs", "")
+        .build();
+
+    p.cargo("build")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] failed to parse manifest at `[CWD]/bar/Cargo.toml`
+
+Caused by:
+  `package.license` is inherited from the workspace, but it is not in \
+`[workspace.package]` of `[CWD]/Cargo.toml`
+",
+        )
+        .run();
+
+    p.change_file(
+        "bar/Cargo.toml",
+        r#"
+            cargo-features = ["workspace-inheritance"]
+
+            [package]
+            name = "bar"
+            version = { workspace = false }
+        "#,
+    );
+
+    p.cargo("build")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] failed to parse manifest at `[CWD]/bar/Cargo.toml`
+
+Caused by:
+  `package.version` must be `{ workspace = true }` to be inherited from the workspace
+",
+        )
+        .run();
+}
@@ -910,6 +910,140 @@ fn virtual_default_members_build_other_member() {
         .run();
 }
 
+#[cargo_test]
+fn virtual_default_members_features_for_other_member() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [workspace]
+                members = ["bar", "baz"]
+                default-members = ["baz"]
+            "#,
+        )
+        .file(
+            "bar/Cargo.toml",
+            r#"
+                [package]
+                name = "bar"
+                version = "0.1.0"
+
+                [features]
+                f1 = []
+            "#,
+        )
+        .file(
+            "bar/src/lib.rs",
+            r#"
+                #[cfg(not(feature = "f1"))]
+                compile_error!("f1 not set");
+            "#,
+        )
+        .file("baz/Cargo.toml", &basic_manifest("baz", "0.1.0"))
+        .file("baz/src/lib.rs", "")
+        .build();
+
+    p.cargo("build -p bar --features f1")
+        .with_stderr(
+            "\
+[COMPILING] bar v0.1.0 ([..])
+[FINISHED] [..]
+",
+        )
+        .run();
+
+    p.cargo("build -p bar -p baz --features f1")
+        .with_status(101)
+        .with_stderr_contains(
+            "[ERROR] --features is not allowed in the root of a virtual workspace",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn default_members_flag() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [workspace]
+                members = ["bar", "baz", "qux"]
+                default-members = ["bar", "baz"]
+            "#,
+        )
+        .file("bar/Cargo.toml", &basic_manifest("bar", "0.1.0"))
+        .file("bar/src/lib.rs", "")
+        .file("baz/Cargo.toml", &basic_manifest("baz", "0.1.0"))
+        .file("baz/src/lib.rs", "")
+        .file("qux/Cargo.toml", &basic_manifest("qux", "0.1.0"))
+        .file("qux/src/lib.rs", "")
+        .build();
+
+    // From a member's directory, `--default-members` still selects the
+    // workspace's default members.
+    p.cargo("check --default-members")
+        .cwd("qux")
+        .with_stderr_unordered(
+            "\
+[CHECKING] bar v0.1.0 ([..])
+[CHECKING] baz v0.1.0 ([..])
+[FINISHED] [..]
+",
+        )
+        .run();
+
+    p.cargo("check --default-members -p qux --exclude bar")
+        .with_stderr_unordered(
+            "\
+[CHECKING] qux v0.1.0 ([..])
+[FINISHED] [..]
+",
+        )
+        .run();
+
+    p.cargo("build --default-members -p qux --exclude baz")
+        .with_stderr_unordered(
+            "\
+[COMPILING] bar v0.1.0 ([..])
+[COMPILING] qux v0.1.0 ([..])
+[FINISHED] [..]
+",
+        )
+        .run();
+
+    p.cargo("check --default-members --workspace")
+        .with_status(101)
+        .with_stderr("[ERROR] --default-members cannot be used together with --workspace")
+        .run();
+}
+
+#[cargo_test]
+fn all_members_flag() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [workspace]
+                members = ["bar", "baz"]
+                default-members = ["bar"]
+            "#,
+        )
+        .file("bar/Cargo.toml", &basic_manifest("bar", "0.1.0"))
+        .file("bar/src/lib.rs", "")
+        .file("baz/Cargo.toml", &basic_manifest("baz", "0.1.0"))
+        .file("baz/src/lib.rs", "")
+        .build();
+
+    p.cargo("check --all-members --exclude bar")
+        .with_stderr(
+            "\
+[CHECKING] baz v0.1.0 ([..])
+[FINISHED] [..]
+",
+        )
+        .run();
+}
+
 #[cargo_test]
 fn virtual_build_no_members() {
     let p = project().file(
@@ -2396,3 +2530,116 @@ fn virtual_primary_package_env_var() {
     p.cargo("clean").run();
     p.cargo("test -p foo").run();
 }
+
+#[cargo_test]
+fn nested_workspace() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [workspace]
+                members = ["crates/*"]
+                default-members = ["crates/*"]
+            "#,
+        )
+        .file(
+            "crates/foo/Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+                authors = []
+
+                [dependencies]
+                inner-a = { path = "../inner/a" }
+            "#,
+        )
+        .file("crates/foo/src/lib.rs", "")
+        .file(
+            "crates/inner/Cargo.toml",
+            r#"
+                [workspace]
+                members = ["a", "b"]
+            "#,
+        )
+        .file(
+            "crates/inner/a/Cargo.toml",
+            &basic_manifest("inner-a", "0.1.0"),
+        )
+        .file("crates/inner/a/src/lib.rs", "")
+        .file(
+            "crates/inner/b/Cargo.toml",
+            &basic_manifest("inner-b", "0.1.0"),
+        )
+        .file("crates/inner/b/src/lib.rs", "")
+        .build();
+
+    // The inner workspace and its members aren't members of the outer one,
+    // even though `crates/*` matches it and `foo` depends on `inner-a`.
+    p.cargo("build")
+        .with_stderr(
+            "\
+[COMPILING] inner-a v0.1.0 ([CWD]/crates/inner/a)
+[COMPILING] foo v0.1.0 ([CWD]/crates/foo)
+[FINISHED] [..]
+",
+        )
+        .run();
+    assert!(p.root().join("Cargo.lock").is_file());
+    assert!(!p.root().join("crates/inner/Cargo.lock").is_file());
+
+    p.cargo("build")
+        .cwd("crates/inner/b")
+        .with_stderr(
+            "\
+[COMPILING] inner-b v0.1.0 ([CWD])
+[FINISHED] [..]
+",
+        )
+        .run();
+    assert!(p.root().join("crates/inner/Cargo.lock").is_file());
+    assert!(p.root().join("crates/inner/target").is_dir());
+
+    p.cargo("build")
+        .cwd("crates/inner")
+        .with_stderr(
+            "\
+[COMPILING] inner-a v0.1.0 ([CWD]/a)
+[FINISHED] [..]
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn nested_workspace_explicit_member() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [workspace]
+                members = ["inner/a"]
+            "#,
+        )
+        .file(
+            "inner/Cargo.toml",
+            r#"
+                [workspace]
+                members = ["a"]
+            "#,
+        )
+        .file("inner/a/Cargo.toml", &basic_manifest("a", "0.1.0"))
+        .file("inner/a/src/lib.rs", "")
+        .build();
+
+    p.cargo("build")
+        .with_status(101)
+        .with_stderr(
+            "\
+error: package `[CWD]/inner/a/Cargo.toml` is a member of the wrong workspace
+expected: [CWD]/Cargo.toml
+actual:   [CWD]/inner/Cargo.toml
+",
+        )
+        .run();
+}
@@ -1,10 +1,14 @@
 //! Tests for the `cargo yank` command.
 
 use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread;
 
 use cargo_test_support::paths::CargoPathExt;
-use cargo_test_support::project;
 use cargo_test_support::registry;
+use cargo_test_support::{project, Project};
+use url::Url;
 
 fn setup(name: &str, version: &str) {
     let dir = registry::api_path().join(format!("api/v1/crates/{}/{}", name, version));
@@ -46,3 +50,137 @@ Caused by:
         )
         .run();
 }
+
+/// Sets up the alternative registry with an API server answering each
+/// request with the next of `responses`, and a `foo` project to yank.
+fn rate_limited_test(responses: &'static [&'static str]) -> (Project, thread::JoinHandle<()>) {
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let api_url = format!("http://{}", server.local_addr().unwrap());
+
+    registry::init_registry(
+        registry::alt_registry_path(),
+        registry::alt_dl_url(),
+        Url::parse(&api_url).unwrap(),
+        registry::alt_api_path(),
+    );
+
+    let t = thread::spawn(move || {
+        for response in responses {
+            let mut conn = BufReader::new(server.accept().unwrap().0);
+            (&mut conn)
+                .lines()
+                .map(|s| s.unwrap())
+                .take_while(|s| s.len() > 2)
+                .for_each(drop);
+            conn.get_mut().write_all(response.as_bytes()).unwrap();
+        }
+    });
+
+    let p = project()
+        .file(
+            ".cargo/config",
+            &format!(
+                r#"
+                    [registries.alternative]
+                    index = "{}"
+                "#,
+                registry::alt_registry_url(),
+            ),
+        )
+        .file(
+            "Cargo.toml",
+            &cargo_test_support::basic_manifest("foo", "0.0.1"),
+        )
+        .file("src/lib.rs", "")
+        .build();
+    (p, t)
+}
+
+#[cargo_test]
+fn rate_limited_retry() {
+    let (p, t) = rate_limited_test(&[
+        "HTTP/1.1 429\r\n\
+         Retry-After: 0\r\n\
+         Connection: close\r\n\
+         Content-Length: 53\r\n\
+         \r\n\
+         {\"errors\": [{\"detail\": \"too many yanks, slow down\"}]}",
+        "HTTP/1.1 200\r\n\
+         Connection: close\r\n\
+         Content-Length: 12\r\n\
+         \r\n\
+         {\"ok\": true}",
+    ]);
+
+    p.cargo("yank --vers 0.0.1 --registry alternative --token sekrit")
+        .with_stderr(
+            "\
+[UPDATING] [..]
+[YANK] foo:0.0.1
+[WARNING] the registry is rate limiting requests (status 429 Too Many Requests): \
+too many yanks, slow down
+retrying in 0s (2 tries remaining)
+",
+        )
+        .run();
+
+    t.join().unwrap();
+}
+
+#[cargo_test]
+fn rate_limited_error() {
+    // Without retries, the error of the registry is reported as is.
+    let (p, t) = rate_limited_test(&["HTTP/1.1 429\r\n\
+          Connection: close\r\n\
+          Content-Length: 31\r\n\
+          \r\n\
+          Slow down, try again tomorrow.\n"]);
+
+    p.cargo("yank --vers 0.0.1 --registry alternative --token sekrit")
+        .env("CARGO_NET_RETRY", "0")
+        .with_status(101)
+        .with_stderr(
+            "\
+[UPDATING] [..]
+[YANK] foo:0.0.1
+[ERROR] failed to yank
+
+Caused by:
+  the registry is rate limiting requests (status 429 Too Many Requests): \
+Slow down, try again tomorrow.
+",
+        )
+        .run();
+
+    t.join().unwrap();
+}
+
+#[cargo_test]
+fn rate_limited_too_long() {
+    // Cargo gives up instead of waiting for an hour.
+    let (p, t) = rate_limited_test(&["HTTP/1.1 429\r\n\
+          Retry-After: 3600\r\n\
+          Connection: close\r\n\
+          Content-Length: 15\r\n\
+          \r\n\
+          Come back later"]);
+
+    p.cargo("yank --vers 0.0.1 --registry alternative --token sekrit")
+        .with_status(101)
+        .with_stderr(
+            "\
+[UPDATING] [..]
+[YANK] foo:0.0.1
+[ERROR] failed to yank
+
+Caused by:
+  the registry asks to retry in 1h, which is longer than Cargo waits for (1m at most)
+
+Caused by:
+  the registry is rate limiting requests (status 429 Too Many Requests): Come back later
+",
+        )
+        .run();
+
+    t.join().unwrap();
+}